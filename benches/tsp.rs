@@ -0,0 +1,51 @@
+//! `cargo bench --bench tsp`
+//! * Benchmarks `compute_route` on points with known optimal tours
+
+// The server is a binary crate, so pull in the modules the router needs directly
+#[allow(dead_code)]
+#[path = "../src/global.rs"]
+mod global;
+#[allow(dead_code)]
+#[path = "../src/agent/tools/tsp.rs"]
+mod tsp;
+
+use criterion::{Criterion, black_box, criterion_group, criterion_main};
+use std::f64::consts::PI;
+use tsp::{EndpointMode, Pt, compute_route, route_length};
+
+/// Points on the unit circle in the given order, the optimal tour walks the circle
+fn circle(order: &[usize]) -> Vec<Pt<'static>> {
+	let n = order.len() as f64;
+	order
+		.iter()
+		.map(|&k| {
+			let angle = 2.0 * PI * k as f64 / n;
+			Pt {
+				id: None,
+				lat: angle.cos(),
+				lng: angle.sin(),
+			}
+		})
+		.collect()
+}
+
+fn bench_compute_route(c: &mut Criterion) {
+	for order in [vec![0, 2, 4, 1, 3], vec![0, 4, 1, 6, 3, 7, 2, 5]] {
+		let n = order.len();
+		let points = circle(&order);
+		let optimal = n as f64 * 2.0 * (PI / n as f64).sin();
+
+		let route = compute_route(&points, EndpointMode::Circle);
+		assert!(
+			(route_length(&points, &route) - optimal).abs() < 1e-9,
+			"{n} point route isn't optimal: {route:?}"
+		);
+
+		c.bench_function(&format!("compute_route circle {n}"), |b| {
+			b.iter(|| compute_route(black_box(&points), EndpointMode::Circle))
+		});
+	}
+}
+
+criterion_group!(benches, bench_compute_route);
+criterion_main!(benches);
@@ -4,6 +4,7 @@ import type { ApiResult } from "../helpers/global";
 import type {
 	CurrentResponse,
 	LoginRequest,
+	ProfilePictureResponse,
 	SignUpRequest,
 	UpdateRequest
 } from "../models/account";
@@ -16,6 +17,7 @@ import type {
 /// # Returns
 /// Status of login call.
 /// * 200: successful login
+/// * 429: too many recent failed attempts, or the account is locked for a while
 /// * -1: fetch call threw an exception
 ///
 /// # Exceptions
@@ -176,3 +178,69 @@ export async function apiCurrent(): Promise<ApiResult<CurrentResponse>> {
 		return { result: null, status: -1 };
 	}
 }
+
+/// Uploads a profile picture
+///
+/// # Method
+/// Sends a `POST /api/account/profilePicture` multipart request with the image
+/// in a `file` field.
+///
+/// # Returns
+/// The URL of the uploaded picture and the status of the API call
+/// * 200: successful upload
+/// * 400: not a PNG, JPEG, or WebP image, or the image is too large
+/// * -1: fetch call threw an exception
+///
+/// # Exceptions
+/// Never throws an exception
+export async function apiUploadProfilePicture(
+	file: File
+): Promise<ApiResult<ProfilePictureResponse>> {
+	const body = new FormData();
+	body.append("file", file);
+	try {
+		const response = await fetch(
+			`${API_BASE_URL}/api/account/profilePicture`,
+			{
+				method: "POST",
+				credentials: import.meta.env.DEV ? "include" : "same-origin",
+				body
+			}
+		);
+		return {
+			result: response.ok ? await response.json() : null,
+			status: response.status
+		};
+	} catch (error) {
+		console.error("Upload Profile Picture API error: ", error);
+		return { result: null, status: -1 };
+	}
+}
+
+/// Removes the profile picture
+///
+/// # Method
+/// Sends a `DELETE /api/account/profilePicture` request.
+///
+/// # Returns
+/// Status of the API call
+/// * 200: picture removed
+/// * -1: fetch call threw an exception
+///
+/// # Exceptions
+/// Never throws an exception
+export async function apiDeleteProfilePicture(): Promise<ApiResult<void>> {
+	try {
+		const response = await fetch(
+			`${API_BASE_URL}/api/account/profilePicture`,
+			{
+				method: "DELETE",
+				credentials: import.meta.env.DEV ? "include" : "same-origin"
+			}
+		);
+		return { result: null, status: response.status };
+	} catch (error) {
+		console.error("Delete Profile Picture API error: ", error);
+		return { result: null, status: -1 };
+	}
+}
@@ -6,11 +6,13 @@ import type {
 	SendMessageRequest,
 	SendMessageResponse,
 	ChatsResponse,
+	ChatSort,
 	Message,
 	UpdateMessageRequest,
 	RenameRequest,
 	ProgressRequest,
-	ProgressResponse
+	ProgressResponse,
+	CancelRequest
 } from "../models/chat";
 
 /// Calls chats
@@ -18,16 +20,20 @@ import type {
 /// # Method
 /// Sends a `GET /api/chat/chats` request to fetch all chat sessions for the current user.
 ///
+/// # Parameters
+/// - `sort`: Optional ordering, `last_message_at` puts the most recently active chats first.
+///
 /// # Returns
 /// - On success: `ChatsResponse` containing all existing chat sessions.
 /// - On failure: A null `ChatsResponse` with a non-200 status code.
 ///
 /// # Exceptions
 /// Never throws an exception
-export async function apiChats(): Promise<ApiResult<ChatsResponse>> {
+export async function apiChats(sort?: ChatSort): Promise<ApiResult<ChatsResponse>> {
 	// TODO: get chats from cache if it exists
 	try {
-		const response = await fetch(`${API_BASE_URL}/api/chat/chats`, {
+		const query = sort ? `?sort=${sort}` : "";
+		const response = await fetch(`${API_BASE_URL}/api/chat/chats${query}`, {
 			method: "GET",
 			credentials: import.meta.env.DEV ? "include" : "same-origin"
 		});
@@ -212,6 +218,40 @@ export async function apiRenameChat(
 	}
 }
 
+/// Cancels the AI response being generated
+///
+/// # Method
+/// Sends a `POST /api/chat/cancel` request to stop the LLM pipeline for a chat session.
+/// The pending `apiSendMessage` call then resolves with a message saying the reply was stopped.
+///
+/// # Parameters
+/// - `payload`: A `CancelRequest` object containing the chat session ID.
+///
+/// # Returns
+/// - On success: Just a 200
+/// - On failure: Just a non-200 status code.
+///
+/// # Exceptions
+/// Never throws an exception
+export async function apiCancel(
+	payload: CancelRequest
+): Promise<ApiResult<void>> {
+	try {
+		const response = await fetch(`${API_BASE_URL}/api/chat/cancel`, {
+			method: "POST",
+			headers: {
+				"Content-Type": "application/json"
+			},
+			credentials: import.meta.env.DEV ? "include" : "same-origin",
+			body: JSON.stringify(payload)
+		});
+		return { result: null, status: response.status };
+	} catch (error) {
+		console.error("apiCancel error:", error);
+		return { result: null, status: -1 };
+	}
+}
+
 /// Updates an existing message with new text and receives a new AI response
 ///
 /// # Method
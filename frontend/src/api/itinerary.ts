@@ -2,6 +2,8 @@ const API_BASE_URL = import.meta.env.VITE_API_BASE_URL;
 import type { ApiResult } from "../helpers/global";
 import type {
 	Itinerary,
+	MoveEventRequest,
+	ReorderRequest,
 	SavedItinerariesResponse,
 	SaveResponse,
 	SearchEventRequest,
@@ -236,6 +238,82 @@ export async function apiDeleteUserEvent(id: number): Promise<ApiResult<void>> {
 	}
 }
 
+/// Reorders the events within one time block of an itinerary
+///
+/// # Method
+/// Sends a `PATCH /api/itinerary/:id/reorder` request to persist the
+/// order of the events in a time block.
+///
+/// # Body
+/// - `ReorderRequest`: The time block and every event id in it, in their new order.
+///
+/// # Returns
+/// - On success: 200 status code.
+/// - On failure: A non-200 status code.
+///
+/// # Exceptions
+/// Never throws an exception
+export async function apiReorderEvents(
+	itinerary_id: number,
+	payload: ReorderRequest
+): Promise<ApiResult<void>> {
+	try {
+		const response = await fetch(
+			`${API_BASE_URL}/api/itinerary/${itinerary_id}/reorder`,
+			{
+				method: "PATCH",
+				headers: {
+					"Content-Type": "application/json"
+				},
+				credentials: import.meta.env.DEV ? "include" : "same-origin",
+				body: JSON.stringify(payload)
+			}
+		);
+		return { result: null, status: response.status };
+	} catch (error) {
+		console.error("apiReorderEvents error:", error);
+		return { result: null, status: -1 };
+	}
+}
+
+/// Moves an event to another time block of an itinerary
+///
+/// # Method
+/// Sends a `PATCH /api/itinerary/:id/moveEvent` request to move one event
+/// to the end of another time block.
+///
+/// # Body
+/// - `MoveEventRequest`: The event, the time block it's in, and where to move it.
+///
+/// # Returns
+/// - On success: 200 status code.
+/// - On failure: A non-200 status code.
+///
+/// # Exceptions
+/// Never throws an exception
+export async function apiMoveEvent(
+	itinerary_id: number,
+	payload: MoveEventRequest
+): Promise<ApiResult<void>> {
+	try {
+		const response = await fetch(
+			`${API_BASE_URL}/api/itinerary/${itinerary_id}/moveEvent`,
+			{
+				method: "PATCH",
+				headers: {
+					"Content-Type": "application/json"
+				},
+				credentials: import.meta.env.DEV ? "include" : "same-origin",
+				body: JSON.stringify(payload)
+			}
+		);
+		return { result: null, status: response.status };
+	} catch (error) {
+		console.error("apiMoveEvent error:", error);
+		return { result: null, status: -1 };
+	}
+}
+
 /// Sends a `GET /api/itinerary/saved` request to fetch all saved itineraries.
 ///
 /// # Returns list of saved itineraries if successful.
@@ -22,9 +22,19 @@ export enum AgentProgress {
 	Optimizing = "Optimizing",
 	RankingEvents = "RankingEvents",
 	// Final Response
-	FinalizingItinerary = "FinalizingItinerary"
+	FinalizingItinerary = "FinalizingItinerary",
+	// Finished without an itinerary
+	AwaitingUser = "AwaitingUser",
+	Failed = "Failed"
 }
 
+// Statuses where the pipeline isn't working on a reply
+export const IDLE_PROGRESS: ReadonlySet<AgentProgress> = new Set([
+	AgentProgress.Ready,
+	AgentProgress.AwaitingUser,
+	AgentProgress.Failed
+]);
+
 export interface AgentInfo {
 	name: string;
 	profilePic: string;
@@ -112,5 +122,16 @@ export const AGENT_PROGRESS_MAP: Record<AgentProgress, AgentInfo> = {
 		name: "Orchestrator",
 		profilePic: "/orchestrator-white-crop.png",
 		message: "Finalizing your itinerary"
+	},
+	// Finished without an itinerary
+	[AgentProgress.AwaitingUser]: {
+		name: "Task Agent",
+		profilePic: "/task-white.png",
+		message: "Waiting for your answer"
+	},
+	[AgentProgress.Failed]: {
+		name: "System",
+		profilePic: "/logo.png",
+		message: "Something went wrong, please try again"
 	}
 };
@@ -33,6 +33,10 @@ export type UpdateRequest = {
 	/// Optional new disabilites
 	/// * String is a comma-separated list of preferences
 	disabilities: string | null;
+	/// Optional new interests, replacing the current ones
+	interests?: Interest[] | null;
+	/// Optional notification preferences, only the given fields are changed
+	notification_preferences?: Partial<NotificationPreferences> | null;
 	profile_picture: string | null;
 };
 
@@ -55,6 +59,10 @@ export type UpdateResponse = {
 	/// Optional disabilites
 	/// * String is a comma-separated list of preferences
 	disabilities: string | null;
+	/// Interests used to rank events
+	interests: Interest[];
+	/// Which notifications the user has opted into
+	notification_preferences: NotificationPreferences;
 	profile_picture: string | null;
 };
 
@@ -75,9 +83,41 @@ export type CurrentResponse = {
 	food_allergies: string | null;
 	/// Optional food and allergies preferences
 	disabilities: string | null;
+	/// Interests used to rank events
+	interests: Interest[];
+	/// Which notifications the user has opted into
+	notification_preferences: NotificationPreferences;
 	profile_picture: string | null;
 };
 
+/// Which notifications the user has opted into
+export type NotificationPreferences = {
+	/// Reminders about upcoming trips
+	trip_reminders: boolean;
+	/// Updates to itineraries shared with the user
+	share_notifications: boolean;
+	/// Product news and offers
+	marketing: boolean;
+};
+
+export type ProfilePictureResponse = {
+	/// URL the uploaded picture is served from
+	url: string;
+};
+
+export enum Interest {
+	Museums = "Museums",
+	Nightlife = "Nightlife",
+	Outdoors = "Outdoors",
+	Food = "Food",
+	Sports = "Sports",
+	Shopping = "Shopping",
+	History = "History",
+	Art = "Art",
+	Music = "Music",
+	Relaxation = "Relaxation"
+}
+
 export enum BudgetBucket {
 	VeryLowBudget = "VeryLowBudget",
 	LowBudget = "LowBudget",
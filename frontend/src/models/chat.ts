@@ -14,8 +14,23 @@ export type ChatSessionRow = {
 	id: number;
 	/// Name of chat for user context
 	title: string;
+	/// UTC timestamp of the latest message, null if the chat is empty
+	last_message_at: string | null;
+	/// Number of messages in the chat
+	message_count: number;
+	/// Number of LLM messages sent since the user last read the chat
+	unread_count: number;
+	/// Whether the user archived the chat
+	archived: boolean;
+	/// Whether the user pinned the chat, pinned chats are listed first
+	pinned: boolean;
+	/// UTC timestamp of the latest message, or when the chat was created if it's empty
+	last_activity: string;
 };
 
+/// Orderings for `GET /api/chat/chats`
+export type ChatSort = "last_message_at";
+
 export type ChatsResponse = {
 	chat_sessions: ChatSessionRow[];
 };
@@ -43,6 +58,8 @@ export type UpdateMessageRequest = {
 	new_text: string;
 	/// A possible itinerary to give context to the LLM
 	itinerary_id: number | null;
+	/// Unique id for this request, so a retry with it gets the original response
+	client_request_id?: string;
 };
 
 export type SendMessageRequest = {
@@ -52,6 +69,8 @@ export type SendMessageRequest = {
 	text: string;
 	/// A possible itinerary to give context to the LLM
 	itinerary_id: number | null;
+	/// Unique id for this request, so a retry with it gets the original response
+	client_request_id?: string;
 };
 
 export type SendMessageResponse = {
@@ -59,6 +78,8 @@ export type SendMessageResponse = {
 	user_message_id: number;
 	/// The response message from the LLM
 	bot_message: Message;
+	/// Whether the LLM didn't reply in time, `bot_message` is then an apology and the message can be sent again
+	timed_out: boolean;
 };
 
 export type NewChatResponse = {
@@ -77,8 +98,29 @@ export type ProgressRequest = {
 	chat_session_id: number;
 };
 
+/// Request model for the `/api/chat/cancel` endpoint
+export type CancelRequest = {
+	chat_session_id: number;
+};
+
 /// Response model for the `/api/chat/progress` endpoint
 export type ProgressResponse = {
 	progress: string;
 	title: string;
+	/// Set while the chat is waiting for the user to answer a clarification question
+	clarification: Clarification | null;
+};
+
+/// What a clarification question asked the user for
+export type Clarification = {
+	missing_info: string[];
+	known: KnownTripDetails;
+};
+
+/// Trip details known when a clarification question was asked
+export type KnownTripDetails = {
+	destination: string | null;
+	start_date: string | null;
+	end_date: string | null;
+	budget: number | null;
 };
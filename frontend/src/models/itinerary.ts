@@ -143,6 +143,9 @@ export type UserEventRequest = {
 	postal_code: number | null;
 	city: string | null;
 	country: string | null;
+	/// Looked up from the address when neither coordinate is given and geocoding is enabled
+	lat?: number | null;
+	lng?: number | null;
 	event_type: string | null;
 	event_description: string | null;
 	event_name: string;
@@ -262,3 +265,21 @@ export const TIMEZONES: string[] = [
 export type UnsaveRequest = {
 	id: number;
 };
+
+export type ReorderRequest = {
+	/// The day containing the time block (YYYY-MM-DD)
+	date: string;
+	time_of_day: "Morning" | "Afternoon" | "Evening";
+	/// Every event id in the time block, in their new order
+	event_ids: number[];
+};
+
+export type MoveEventRequest = {
+	event_id: number;
+	/// The day the event is currently on (YYYY-MM-DD)
+	from_date: string;
+	from_time_of_day: "Morning" | "Afternoon" | "Evening";
+	/// The day to move the event to (YYYY-MM-DD)
+	to_date: string;
+	to_time_of_day: "Morning" | "Afternoon" | "Evening";
+};
@@ -1,4 +1,8 @@
-import { apiUpdateAccount, apiCurrent } from "../api/account";
+import {
+  apiUpdateAccount,
+  apiCurrent,
+  apiUploadProfilePicture
+} from "../api/account";
 import { useNavigate, useLocation } from "react-router-dom";
 import { useState, useEffect, useRef } from "react";
 import type { UpdateRequest } from "../models/account";
@@ -95,64 +99,34 @@ export default function Account() {
     const file = e.target.files?.[0];
     if (!file) return;
 
-    // Check file size (limit to 5MB)
-    if (file.size > 5 * 1024 * 1024) {
-      toast.error("Image must be smaller than 5MB");
+    // Check file size (limit to 2MB, matching the backend)
+    if (file.size > 2 * 1024 * 1024) {
+      toast.error("Image must be smaller than 2MB");
       return;
     }
 
     setIsUploadingPicture(true);
 
-    const reader = new FileReader();
-    reader.onload = async () => {
-      const base64Image = reader.result as string;
-
-      // Auto-save the profile picture
-      const payload: UpdateRequest = {
-        email: null,
-        first_name: null,
-        last_name: null,
-        password: null,
-        current_password: null,
-        budget_preference: null,
-        risk_preference: null,
-        food_allergies: null,
-        disabilities: null,
-        profile_picture: base64Image
-      };
-
-      const updateResult = await apiUpdateAccount(payload);
-
-      if (updateResult.status !== 200) {
-        console.error(
-          "API call to /api/account/update failed with status: ",
-          updateResult.status
-        );
-        toast.error(
-          "Failed to update profile picture. Image may be too large."
-        );
-        setIsUploadingPicture(false);
-        return;
-      }
-
-      toast.success("Profile picture updated successfully!");
+    const uploadResult = await apiUploadProfilePicture(file);
 
-      // Update the displayed image
-      if (updateResult.result?.profile_picture) {
-        setProfileImageUrl(updateResult.result.profile_picture);
-      } else {
-        setProfileImageUrl(base64Image);
-      }
-
-      setIsUploadingPicture(false);
-    };
-
-    reader.onerror = () => {
-      toast.error("Failed to read file. Please try again.");
+    if (uploadResult.status !== 200 || !uploadResult.result) {
+      console.error(
+        "API call to /api/account/profilePicture failed with status: ",
+        uploadResult.status
+      );
+      toast.error(
+        uploadResult.status === 400
+          ? "Image must be a PNG, JPEG, or WebP smaller than 2MB."
+          : "Failed to update profile picture."
+      );
       setIsUploadingPicture(false);
-    };
+      e.target.value = "";
+      return;
+    }
 
-    reader.readAsDataURL(file);
+    toast.success("Profile picture updated successfully!");
+    setProfileImageUrl(uploadResult.result.url);
+    setIsUploadingPicture(false);
 
     // Reset file input so the same file can be selected again
     e.target.value = "";
@@ -317,7 +291,7 @@ export default function Account() {
                         <input
                           ref={fileInputRef}
                           type="file"
-                          accept="image/*"
+                          accept="image/png,image/jpeg,image/webp"
                           onChange={handleProfilePictureChange}
                           style={{ display: "none" }}
                         />
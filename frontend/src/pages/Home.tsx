@@ -26,7 +26,7 @@ import { fetchItinerary, convertToApiFormat } from "../helpers/itinerary";
 import type { DayItinerary } from "../models/itinerary";
 import { apiItineraryDetails, apiSaveItineraryChanges } from "../api/itinerary";
 import { toast } from "../components/Toast";
-import { AgentProgress } from "../config/agentProgress";
+import { AgentProgress, IDLE_PROGRESS } from "../config/agentProgress";
 
 export const ACTIVE_CHAT_SESSION: string = "activeChatSession";
 
@@ -261,13 +261,13 @@ export default function Home() {
         setAgentProgress(currentProgress);
         prevProgressRef.current = currentProgress;
 
-        // When the pipeline transitions back to Ready from a non-ready state,
-        // force a fresh message reload so the final AI message is guaranteed
-        // to appear, and stop polling.
+        // When the pipeline transitions back to an idle state (Ready, waiting
+        // for the user, or failed) from a working one, force a fresh message
+        // reload so the final AI message is guaranteed to appear, and stop polling.
         if (
-          currentProgress === AgentProgress.Ready &&
+          IDLE_PROGRESS.has(currentProgress) &&
           previousProgress !== null &&
-          previousProgress !== AgentProgress.Ready &&
+          !IDLE_PROGRESS.has(previousProgress) &&
           activeChatId !== null
         ) {
           await refreshMessagesForChatSession(activeChatId);
@@ -495,7 +495,8 @@ export default function Home() {
     const payload: SendMessageRequest = {
       chat_session_id: currChatId!,
       text,
-      itinerary_id: selectedItineraryId
+      itinerary_id: selectedItineraryId,
+      client_request_id: crypto.randomUUID()
     };
 
     const sendResult = await apiSendMessage(payload);
@@ -506,6 +507,9 @@ export default function Home() {
     }
 
     const botMessage = sendResult.result!.bot_message;
+    if (sendResult.result!.timed_out) {
+      toast.error("The assistant took too long to reply. Please send your message again.");
+    }
 
     // Thanks, React, for making this the convention for updating state
     // Update the temporary user message id, and append the bot message
@@ -552,7 +556,8 @@ export default function Home() {
     const payload: UpdateMessageRequest = {
       message_id: messageId,
       new_text: newText,
-      itinerary_id: selectedItineraryId
+      itinerary_id: selectedItineraryId,
+      client_request_id: crypto.randomUUID()
     };
 
     // Optimistically update the UI - update the message text
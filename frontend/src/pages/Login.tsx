@@ -21,7 +21,13 @@ export default function Login() {
       const { status } = await apiLogin({ email, password });
       if (status !== 200) {
         setAuthorized(false);
-        toast.error("Invalid email or password.");
+        if (status === 429) {
+          toast.error(
+            "Too many failed attempts. Please wait a few minutes or reset your password."
+          );
+        } else {
+          toast.error("Invalid email or password.");
+        }
         return;
       }
 
@@ -9,11 +9,28 @@ DROP TABLE IF EXISTS chat_sessions CASCADE;
 DROP TABLE IF EXISTS itineraries CASCADE;
 DROP TABLE IF EXISTS event_list CASCADE;
 DROP TABLE IF EXISTS messages CASCADE;
+DROP TABLE IF EXISTS message_requests CASCADE;
+DROP TABLE IF EXISTS message_feedback CASCADE;
+DROP TABLE IF EXISTS trip_contexts CASCADE;
+DROP TABLE IF EXISTS api_keys CASCADE;
+DROP TABLE IF EXISTS sessions CASCADE;
+DROP TABLE IF EXISTS auth_events CASCADE;
+DROP TABLE IF EXISTS email_verifications CASCADE;
+DROP TABLE IF EXISTS password_resets CASCADE;
+DROP TABLE IF EXISTS itinerary_snapshots CASCADE;
+DROP TABLE IF EXISTS event_reviews CASCADE;
+DROP TABLE IF EXISTS favorites CASCADE;
+DROP FUNCTION IF EXISTS touch_chat_session_last_message CASCADE;
+DROP FUNCTION IF EXISTS clear_chat_session_clarification CASCADE;
+DROP FUNCTION IF EXISTS record_message_llm_progress CASCADE;
 DROP TYPE IF EXISTS risk_tolerence CASCADE;
 DROP TYPE IF EXISTS budget_bucket CASCADE;
+DROP TYPE IF EXISTS interest CASCADE;
 DROP TYPE IF EXISTS time_of_day CASCADE;
 DROP TYPE IF EXISTS llm_progress CASCADE;
 DROP TYPE IF EXISTS event_period CASCADE;
+DROP TYPE IF EXISTS auth_event_type CASCADE;
+DROP TYPE IF EXISTS feedback_rating CASCADE;
 
 CREATE EXTENSION IF NOT EXISTS vector; -- Use PGVECTOR (kept for future use)
 
@@ -32,6 +49,19 @@ CREATE TYPE budget_bucket AS ENUM (
     'LuxuryBudget'
 );
 
+CREATE TYPE interest AS ENUM (
+    'Museums',
+    'Nightlife',
+    'Outdoors',
+    'Food',
+    'Sports',
+    'Shopping',
+    'History',
+    'Art',
+    'Music',
+    'Relaxation'
+);
+
 CREATE TYPE time_of_day AS ENUM (
     'Morning',
     'Afternoon',
@@ -53,7 +83,23 @@ CREATE TYPE llm_progress AS ENUM (
     'Scheduling',
     'Optimizing',
     'RankingEvents',
-    'FinalizingItinerary'
+    'FinalizingItinerary',
+    'AwaitingUser',
+    'Failed'
+);
+
+CREATE TYPE auth_event_type AS ENUM (
+    'LoginSuccess',
+    'LoginFailure',
+    'AccountLocked',
+    'Logout',
+    'PasswordChange',
+    'EmailChange'
+);
+
+CREATE TYPE feedback_rating AS ENUM (
+    'Up',
+    'Down'
 );
 
 CREATE TYPE event_period AS (
@@ -80,7 +126,24 @@ CREATE TABLE accounts (
     risk_preference risk_tolerence,
     food_allergies TEXT NOT NULL DEFAULT '',
     disabilities TEXT NOT NULL DEFAULT '',
-    profile_picture TEXT
+    interests interest[] NOT NULL DEFAULT '{}',
+    -- Which notifications the user has opted into, see NotificationPreferences
+    notification_preferences JSONB NOT NULL DEFAULT '{"trip_reminders": true, "share_notifications": true, "marketing": false}'::jsonb,
+    profile_picture TEXT,
+    -- Requested email change, applied once the token sent to it is verified
+    pending_email VARCHAR(255),
+    email_verification_token VARCHAR(255) UNIQUE,
+    email_verification_created_at TIMESTAMPTZ,
+    -- Set when the account was created through a social sign-in, e.g. 'google'
+    oauth_provider VARCHAR(255),
+    -- Set once the signup email is verified, LLM features are refused until then
+    email_verified BOOLEAN NOT NULL DEFAULT FALSE,
+    -- Consecutive failed logins, reset by a successful login or password reset
+    failed_login_attempts INTEGER NOT NULL DEFAULT 0,
+    -- Logins are refused until this time once failed_login_attempts reaches the limit
+    locked_until TIMESTAMPTZ,
+    -- Language tag the LLM replies in, e.g. 'en' or 'pt-BR'
+    preferred_language VARCHAR(10) NOT NULL DEFAULT 'en'
 );
 
 -- Events table
@@ -100,13 +163,16 @@ CREATE TABLE events (
     event_type VARCHAR(255),
     user_created BOOLEAN NOT NULL DEFAULT FALSE,
     account_id INTEGER REFERENCES accounts(id) ON DELETE CASCADE,
+    --Archived user events are kept for existing references but hidden from search
+    archived BOOLEAN NOT NULL DEFAULT FALSE,
     --Timestamp of event location
     hard_start TIMESTAMP WITHOUT TIME ZONE,
     --Timestamp of event location
     hard_end TIMESTAMP WITHOUT TIME ZONE,
     timezone VARCHAR(255),
     --remaining places fields
-    place_id VARCHAR(255) UNIQUE,
+    --one row per Google place, events without a place_id don't conflict
+    place_id VARCHAR(255) CONSTRAINT events_place_id_unique UNIQUE,
     wheelchair_accessible_parking BOOLEAN,
     wheelchair_accessible_entrance BOOLEAN,
     wheelchair_accessible_restroom BOOLEAN,
@@ -128,32 +194,115 @@ CREATE TABLE events (
     next_close_time TIMESTAMP WITHOUT TIME ZONE,
     open_now BOOLEAN,
     periods event_period[] NOT NULL DEFAULT ARRAY[]::event_period[],
-    special_days DATE[] NOT NULL DEFAULT ARRAY[]::DATE[]
+    special_days DATE[] NOT NULL DEFAULT ARRAY[]::DATE[],
+    --Last time the place was fetched from Google Places
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    --Times the event was loaded as part of an itinerary, ranks /api/events/popular
+    view_count INTEGER NOT NULL DEFAULT 0,
+    --Words searched by the `q` of /api/itinerary/searchEvent, names weigh the most
+    search_vector TSVECTOR GENERATED ALWAYS AS (
+        setweight(to_tsvector('english', coalesce(event_name, '')), 'A') ||
+        setweight(to_tsvector('english', coalesce(types, '')), 'B') ||
+        setweight(to_tsvector('english', coalesce(event_description, '')), 'C') ||
+        setweight(to_tsvector('english', coalesce(city, '')), 'C')
+    ) STORED
 );
 
+-- Research matches places without a place_id by name within a city
+CREATE INDEX events_city_event_name_idx ON events(city, event_name);
+
+CREATE INDEX events_search_vector_idx ON events USING GIN(search_vector);
+
+CREATE INDEX events_city_view_count_idx ON events(LOWER(city), view_count DESC);
+
 CREATE TABLE chat_sessions (
 	id SERIAL PRIMARY KEY,
 	account_id INTEGER NOT NULL REFERENCES accounts(id) ON DELETE CASCADE,
 	title VARCHAR(255) NOT NULL,
 	context JSONB DEFAULT '{"tool_history": []}'::jsonb,
 	current_event_ids INTEGER[] NOT NULL DEFAULT ARRAY[]::INTEGER[],
-	llm_progress llm_progress NOT NULL DEFAULT 'Ready'
+	llm_progress llm_progress NOT NULL DEFAULT 'Ready',
+	created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+	-- Set by the message handlers, and by the trigger below for any other inserts
+	last_message_at TIMESTAMPTZ,
+	-- When the user last fetched the latest messages, NULL if never
+	last_read_at TIMESTAMPTZ,
+	-- What the last clarification question asked for, NULL once the pipeline moves on
+	clarification JSONB,
+	-- Hidden from /api/chat/chats unless asked for, cleared when a message is sent
+	archived BOOLEAN NOT NULL DEFAULT FALSE,
+	-- Listed first by /api/chat/chats, at most MAX_PINNED_CHATS per account
+	pinned BOOLEAN NOT NULL DEFAULT FALSE,
+	-- Cached by /api/chat/{id}/summary, stale once a message is sent after summary_updated_at
+	summary TEXT,
+	summary_updated_at TIMESTAMPTZ
 );
 
+-- Forget the clarification once the pipeline moves past asking for it
+CREATE FUNCTION clear_chat_session_clarification() RETURNS TRIGGER AS $$
+BEGIN
+	IF NEW.llm_progress NOT IN ('Ready', 'AskForClarification', 'AwaitingUser') THEN
+		NEW.clarification = NULL;
+	END IF;
+	RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER chat_sessions_clear_clarification
+BEFORE UPDATE OF llm_progress ON chat_sessions
+FOR EACH ROW EXECUTE FUNCTION clear_chat_session_clarification();
+
 -- Itineraries table
 CREATE TABLE itineraries (
     id SERIAL PRIMARY KEY,
     account_id INTEGER REFERENCES accounts(id) ON DELETE CASCADE,
     is_public BOOLEAN NOT NULL DEFAULT FALSE,
+    -- Unguessable id used in share links, regenerated when sharing is turned off
+    share_token UUID NOT NULL UNIQUE DEFAULT gen_random_uuid(),
     -- date of destination's local timezone
     start_date DATE NOT NULL,
     -- date of destination's local timezone
     end_date DATE NOT NULL,
     chat_session_id INTEGER REFERENCES chat_sessions(id) ON DELETE SET NULL,
     saved BOOLEAN NOT NULL,
+    -- Marked by the user for quick access among their saved itineraries
+    starred BOOLEAN NOT NULL DEFAULT FALSE,
     title VARCHAR(255) NOT NULL,
     -- Array of event IDs that are unassigned to any specific time slot
-    unassigned_event_ids INTEGER[] NOT NULL DEFAULT ARRAY[]::INTEGER[]
+    unassigned_event_ids INTEGER[] NOT NULL DEFAULT ARRAY[]::INTEGER[],
+    -- Set when deleted along with its chat, it can be restored until it's purged 30 days later
+    deleted_at TIMESTAMPTZ NULL
+);
+
+-- Previous versions of saved itineraries, kept so a save can be undone
+CREATE TABLE itinerary_snapshots (
+    id SERIAL PRIMARY KEY,
+    itinerary_id INTEGER NOT NULL REFERENCES itineraries(id) ON DELETE CASCADE,
+    -- The full itinerary as returned by the API before the save replaced it
+    snapshot JSONB NOT NULL,
+    saved_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+-- One review per account per event, posting again replaces it
+CREATE TABLE event_reviews (
+    id SERIAL PRIMARY KEY,
+    account_id INTEGER NOT NULL REFERENCES accounts(id) ON DELETE CASCADE,
+    event_id INTEGER NOT NULL REFERENCES events(id) ON DELETE CASCADE,
+    rating SMALLINT NOT NULL CHECK (rating BETWEEN 1 AND 5),
+    comment TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    UNIQUE (account_id, event_id)
+);
+
+CREATE INDEX event_reviews_event_id_idx ON event_reviews(event_id);
+
+-- Events each account bookmarked for future itineraries
+CREATE TABLE favorites (
+    id SERIAL PRIMARY KEY,
+    account_id INTEGER NOT NULL REFERENCES accounts(id) ON DELETE CASCADE,
+    event_id INTEGER NOT NULL REFERENCES events(id) ON DELETE CASCADE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    UNIQUE (account_id, event_id)
 );
 
 -- Event list table
@@ -175,18 +324,126 @@ CREATE TABLE messages (
 	is_user BOOLEAN NOT NULL,
 	-- UTC
 	timestamp TIMESTAMP WITHOUT TIME ZONE NOT NULL,
-	text TEXT NOT NULL
+	text TEXT NOT NULL,
+	-- Set when the user deletes the message, it can be restored until it's purged
+	deleted_at TIMESTAMPTZ NULL,
+	-- Stage the chat's pipeline was at when a bot message was inserted, NULL for user messages
+	llm_progress llm_progress
+);
+
+-- Full text search over messages for /api/chat/searchMessages, queries must use the same to_tsvector expression
+CREATE INDEX messages_text_search_idx ON messages USING GIN (to_tsvector('english', text));
+
+-- Remember which pipeline stage each bot message came out of, so feedback can be traced to it
+CREATE FUNCTION record_message_llm_progress() RETURNS TRIGGER AS $$
+BEGIN
+	IF NOT NEW.is_user THEN
+		NEW.llm_progress := (SELECT llm_progress FROM chat_sessions WHERE id = NEW.chat_session_id);
+	END IF;
+	RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER messages_record_llm_progress
+BEFORE INSERT ON messages
+FOR EACH ROW EXECUTE FUNCTION record_message_llm_progress();
+
+-- Keep chat_sessions.last_message_at current for messages inserted outside the handlers
+CREATE FUNCTION touch_chat_session_last_message() RETURNS TRIGGER AS $$
+BEGIN
+	UPDATE chat_sessions SET last_message_at = NOW() WHERE id = NEW.chat_session_id;
+	RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER messages_touch_chat_session
+AFTER INSERT ON messages
+FOR EACH ROW EXECUTE FUNCTION touch_chat_session_last_message();
+
+-- Responses to sendMessage/updateMessage requests sent with a client_request_id, so retries
+-- get the original response instead of processing the message again
+CREATE TABLE message_requests (
+	chat_session_id INTEGER NOT NULL REFERENCES chat_sessions(id) ON DELETE CASCADE,
+	client_request_id UUID NOT NULL,
+	-- Response body returned to the client, NULL while the request is still being processed
+	response JSONB,
+	created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+	PRIMARY KEY (chat_session_id, client_request_id)
+);
+
+-- Thumbs up or down on a bot message, one per message since only its chat's owner can rate it
+CREATE TABLE message_feedback (
+	message_id INTEGER PRIMARY KEY REFERENCES messages(id) ON DELETE CASCADE,
+	-- The itinerary the message referred to when it was rated
+	itinerary_id INTEGER REFERENCES itineraries(id) ON DELETE SET NULL,
+	rating feedback_rating NOT NULL,
+	comment TEXT,
+	updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+-- API keys for programmatic clients, sent in the X-API-Key header as <id>.<secret>
+CREATE TABLE api_keys (
+	id SERIAL PRIMARY KEY,
+	account_id INTEGER NOT NULL REFERENCES accounts(id) ON DELETE CASCADE,
+	-- Argon2 hash of the secret part of the key
+	key_hash TEXT NOT NULL,
+	name TEXT NOT NULL,
+	created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+	last_used_at TIMESTAMPTZ
+);
+
+-- Server side record of every auth-token cookie so they can be revoked
+CREATE TABLE sessions (
+	id SERIAL PRIMARY KEY,
+	account_id INTEGER NOT NULL REFERENCES accounts(id) ON DELETE CASCADE,
+	user_agent TEXT,
+	created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+	last_seen TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+	expires_at TIMESTAMPTZ NOT NULL,
+	revoked_at TIMESTAMPTZ
+);
+
+-- Single-use tokens emailed on signup to verify the account's email
+CREATE TABLE email_verifications (
+	token TEXT PRIMARY KEY,
+	account_id INTEGER NOT NULL REFERENCES accounts(id) ON DELETE CASCADE,
+	created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+-- Single-use tokens emailed by /forgotPassword, at most one per account
+-- Only the hex SHA-256 of each token is stored
+CREATE TABLE password_resets (
+	token_hash TEXT PRIMARY KEY,
+	account_id INTEGER NOT NULL REFERENCES accounts(id) ON DELETE CASCADE,
+	expires_at TIMESTAMPTZ NOT NULL
+);
+
+-- Audit log of security relevant account activity
+CREATE TABLE auth_events (
+	id SERIAL PRIMARY KEY,
+	account_id INTEGER NOT NULL REFERENCES accounts(id) ON DELETE CASCADE,
+	event_type auth_event_type NOT NULL,
+	ip TEXT,
+	user_agent TEXT,
+	created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+-- Persisted TripContext so trip details survive across browser sessions
+CREATE TABLE trip_contexts (
+	chat_session_id INTEGER PRIMARY KEY REFERENCES chat_sessions(id) ON DELETE CASCADE,
+	context JSONB NOT NULL,
+	updated_at TIMESTAMPTZ DEFAULT NOW()
 );
 
 ------- Dummy data to test ---------
 --Accounts
 -- CF: Password is "whatisrust"
-INSERT INTO accounts (id, email, password, first_name, last_name, budget_preference, risk_preference, food_allergies, disabilities)
-VALUES (1, 'ellieknapp@gmail.com', 'ihateHR', 'ellie', 'knapp', 'VeryLowBudget', 'Adventurer', 'Vegan', 'Blind,Deaf'),
-(2, 'nicklongo@gmail.com', 'iwannabeHR', 'nick', 'longo', 'LowBudget', 'RiskTaker', 'Gluten Free,Hates Cheese', ''),
-(3, 'christianfarrell@gmail.com', '$argon2id$v=19$m=19456,t=2,p=1$boV4nNLYxj5VTn0yRZaQZg$dRSI/RHmPlgxGnKr/Q/bkBt1XRFjWx21FDVjbHKWJZs', 'christian', 'farrell', 'MediumBudget', 'ChillVibes', 'Tree Nuts,Peanuts', 'Mobility Impaired - Wheelchair'),
-(4, 'ethanmorton@gmail.com', 'fakingmyankle', 'ethan', 'morton', 'HighBudget', 'LightFun', 'Dairy,Eggs,Quinoa', 'Bad Ankle'),
-(5, 'peterarvanitis@gmail.com', 'ihateHR', 'peter', 'arvanitis', 'LuxuryBudget', 'ChillVibes','Dairy,Pine nuts,Kiwi','Lazy');
+INSERT INTO accounts (id, email, password, first_name, last_name, budget_preference, risk_preference, food_allergies, disabilities, email_verified)
+VALUES (1, 'ellieknapp@gmail.com', 'ihateHR', 'ellie', 'knapp', 'VeryLowBudget', 'Adventurer', 'Vegan', 'Blind,Deaf', TRUE),
+(2, 'nicklongo@gmail.com', 'iwannabeHR', 'nick', 'longo', 'LowBudget', 'RiskTaker', 'Gluten Free,Hates Cheese', '', TRUE),
+(3, 'christianfarrell@gmail.com', '$argon2id$v=19$m=19456,t=2,p=1$boV4nNLYxj5VTn0yRZaQZg$dRSI/RHmPlgxGnKr/Q/bkBt1XRFjWx21FDVjbHKWJZs', 'christian', 'farrell', 'MediumBudget', 'ChillVibes', 'Tree Nuts,Peanuts', 'Mobility Impaired - Wheelchair', TRUE),
+(4, 'ethanmorton@gmail.com', 'fakingmyankle', 'ethan', 'morton', 'HighBudget', 'LightFun', 'Dairy,Eggs,Quinoa', 'Bad Ankle', TRUE),
+(5, 'peterarvanitis@gmail.com', 'ihateHR', 'peter', 'arvanitis', 'LuxuryBudget', 'ChillVibes','Dairy,Pine nuts,Kiwi','Lazy', TRUE);
 
 -- Ensure the accounts id sequence matches the max(id) after manual inserts
 SELECT setval(
@@ -8,39 +8,37 @@
  */
 
 use std::sync::Arc;
-use std::sync::atomic::AtomicI32;
 
 use langchain_rust::{
 	agent::{AgentError, AgentExecutor, ConversationalAgent, ConversationalAgentBuilder},
 	chain::options::ChainCallOptions,
-	llm::openai::{OpenAI, OpenAIConfig, OpenAIModel},
+	llm::openai::{OpenAI, OpenAIModel},
 	memory::SimpleMemory,
 };
 
+use crate::agent::configs::orchestrator::SharedLlm;
+use crate::agent::create_llm_from_env;
 use crate::agent::tools::constraint::*;
+use crate::global::CONSTRAINT_LLM_MODEL;
 use sqlx::PgPool;
 
+/// The LLM the Constraint Agent prompts, `CONSTRAINT_LLM_MODEL` through [create_llm_from_env]
+pub fn constraint_llm() -> Result<SharedLlm, String> {
+	create_llm_from_env(CONSTRAINT_LLM_MODEL)
+}
+
+/// Creates the Constraint Agent for `chat_session_id`, prompting `llm` from
+/// [crate::agent::create_llm]
 pub fn create_constraint_agent(
-	_llm: OpenAI<OpenAIConfig>,
+	llm: SharedLlm,
 	pool: PgPool,
-	chat_session_id: Arc<AtomicI32>,
+	chat_session_id: i32,
 ) -> Result<AgentExecutor<ConversationalAgent>, AgentError> {
-	// Load environment variables
-	dotenvy::dotenv().ok();
-
-	// Note: Even when DEPLOY_LLM != "1", we still need to create an agent
-	// (it won't be used at runtime). OpenAI API key is still required for agent creation.
-
 	// Create memory
 	let memory = SimpleMemory::new();
 
-	// Select model (will read key from environment variable)
-	let llm = OpenAI::default().with_model(OpenAIModel::Gpt4oMini);
-
-	// Get tools - pass LLM as Arc<dyn LLM> and database pool
-	let llm_arc: Arc<dyn langchain_rust::language_models::llm::LLM + Send + Sync> =
-		Arc::new(llm.clone());
-	let tools = constraint_tools(llm_arc, pool, chat_session_id);
+	// Get tools - pass LLM and database pool
+	let tools = constraint_tools(llm.clone(), pool, chat_session_id);
 
 	// Create agent with system prompt and tools
 	const SYSTEM_PROMPT: &str = include_str!("../prompts/constraint.md");
@@ -50,23 +48,23 @@ pub fn create_constraint_agent(
 		.prefix(system_prompt)
 		.tools(&tools)
 		.options(ChainCallOptions::new().with_max_tokens(1000))
-		.build(llm)
+		.build(llm.clone_box())
 		.unwrap();
 
-	// Limit to 3 iterations - agent should: 1) call tool, 2) get result, 3) return final answer
+	// Limit to 4 iterations - agent should: 1) score accessibility, 2) filter by constraints,
+	// 3) get result, 4) return final answer
 	Ok(AgentExecutor::from_agent(agent)
 		.with_memory(memory.into())
-		.with_max_iterations(3))
+		.with_max_iterations(4))
 }
 
 /// Creates a dummy agent for testing purposes.
 /// This agent will have an invalid API key and will panic if invoked,
 /// but when DEPLOY_LLM != "1", the agent is never invoked, so this is safe.
-/// This allows tests to run without requiring a valid OPENAI_API_KEY.
-#[cfg(test)]
+/// This allows tests, and servers without a valid OPENAI_API_KEY, to run.
 pub fn create_dummy_constraint_agent(
 	pool: PgPool,
-	chat_session_id: Arc<AtomicI32>,
+	chat_session_id: i32,
 ) -> Result<AgentExecutor<ConversationalAgent>, AgentError> {
 	// Set a dummy API key temporarily so agent creation doesn't fail
 	// The agent won't actually be used when DEPLOY_LLM != "1"
@@ -110,5 +108,5 @@ pub fn create_dummy_constraint_agent(
 
 	Ok(AgentExecutor::from_agent(agent)
 		.with_memory(memory.into())
-		.with_max_iterations(3))
+		.with_max_iterations(4))
 }
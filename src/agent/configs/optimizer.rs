@@ -8,7 +8,6 @@
  */
 
 use std::sync::Arc;
-use std::sync::atomic::AtomicI32;
 
 use langchain_rust::{
 	agent::{AgentError, AgentExecutor, ConversationalAgent, ConversationalAgentBuilder},
@@ -17,35 +16,43 @@ use langchain_rust::{
 	memory::SimpleMemory,
 };
 
+use crate::agent::configs::orchestrator::SharedLlm;
+use crate::agent::create_llm_from_env;
+use crate::agent::models::event::SharedEventCache;
 use crate::agent::tools::optimizer::optimizer_tools;
+use crate::global::OPTIMIZE_LLM_MODEL;
 
 use sqlx::PgPool;
 
 const SYSTEM_PROMPT: &str = include_str!("../prompts/optimize.md");
 
+/// The LLM the Optimize Agent prompts, `OPTIMIZE_LLM_MODEL` through [create_llm_from_env]
+pub fn optimize_llm() -> Result<SharedLlm, String> {
+	create_llm_from_env(OPTIMIZE_LLM_MODEL)
+}
+
+/// Creates the Optimize Agent for `chat_session_id`, prompting `llm` from
+/// [crate::agent::create_llm]
 pub fn create_optimize_agent(
-	llm: OpenAI<OpenAIConfig>,
+	llm: SharedLlm,
 	db: PgPool,
-	chat_session_id: Arc<AtomicI32>,
+	event_cache: SharedEventCache,
+	chat_session_id: i32,
 ) -> Result<AgentExecutor<ConversationalAgent>, AgentError> {
-	// Load environment variables
-	dotenvy::dotenv().ok();
-
-	// Note: Even when DEPLOY_LLM != "1", we still need to create an agent
-	// (it won't be used at runtime). OpenAI API key is still required for agent creation.
-
 	// Create memory
 	let memory = SimpleMemory::new();
 
-	// Select model (will read key from environment variable)
-	let agent_llm = OpenAI::default().with_model(OpenAIModel::Gpt4oMini);
-
 	// Create agent
 	let agent = ConversationalAgentBuilder::new()
 		.prefix(SYSTEM_PROMPT.to_string())
-		.tools(&optimizer_tools(Arc::new(llm), db, chat_session_id))
+		.tools(&optimizer_tools(
+			llm.clone(),
+			db,
+			event_cache,
+			chat_session_id,
+		))
 		.options(ChainCallOptions::new().with_max_tokens(1000))
-		.build(agent_llm)
+		.build(llm.clone_box())
 		.unwrap();
 
 	// Limit to 3 iterations - agent should: 1) call tool, 2) get result, 3) return final answer
@@ -57,12 +64,12 @@ pub fn create_optimize_agent(
 /// Creates a dummy agent for testing purposes.
 /// This agent will have an invalid API key and will panic if invoked,
 /// but when DEPLOY_LLM != "1", the agent is never invoked, so this is safe.
-/// This allows tests to run without requiring a valid OPENAI_API_KEY.
-#[cfg(test)]
+/// This allows tests, and servers without a valid OPENAI_API_KEY, to run.
 pub fn create_dummy_optimize_agent(
 	llm: OpenAI<OpenAIConfig>,
 	db: PgPool,
-	chat_session_id: Arc<AtomicI32>,
+	event_cache: SharedEventCache,
+	chat_session_id: i32,
 ) -> Result<AgentExecutor<ConversationalAgent>, AgentError> {
 	// Set a dummy API key temporarily so agent creation doesn't fail
 	// The agent won't actually be used when DEPLOY_LLM != "1"
@@ -82,7 +89,12 @@ pub fn create_dummy_optimize_agent(
 	// Create agent
 	let agent = ConversationalAgentBuilder::new()
 		.prefix(SYSTEM_PROMPT.to_string())
-		.tools(&optimizer_tools(Arc::new(llm), db, chat_session_id))
+		.tools(&optimizer_tools(
+			Arc::new(llm),
+			db,
+			event_cache,
+			chat_session_id,
+		))
 		.options(ChainCallOptions::new().with_max_tokens(1000))
 		.build(agent_llm)
 		.unwrap();
@@ -4,34 +4,39 @@
  * File for Orchestrator Agent Configuration
  */
 
+use std::collections::HashMap;
 use std::sync::Arc;
-use std::sync::atomic::AtomicI32;
 
 use langchain_rust::{
 	agent::{AgentError, AgentExecutor, ConversationalAgent, ConversationalAgentBuilder},
 	chain::options::ChainCallOptions,
 	llm::openai::{OpenAI, OpenAIModel},
 	memory::SimpleMemory,
+	schemas::BaseMemory,
 };
 
 use sqlx::PgPool;
 
+use crate::agent::configs::constraint::constraint_llm;
 use crate::agent::configs::constraint::create_constraint_agent;
-#[cfg(test)]
 use crate::agent::configs::constraint::create_dummy_constraint_agent;
 use crate::agent::configs::mock::MockLLM;
-#[cfg(test)]
 use crate::agent::configs::optimizer::create_dummy_optimize_agent;
 use crate::agent::configs::optimizer::create_optimize_agent;
-#[cfg(test)]
+use crate::agent::configs::optimizer::optimize_llm;
 use crate::agent::configs::research::create_dummy_research_agent;
 use crate::agent::configs::research::create_research_agent;
-#[cfg(test)]
+use crate::agent::configs::research::research_llm;
 use crate::agent::configs::task::create_dummy_task_agent;
 use crate::agent::configs::task::create_task_agent;
+use crate::agent::configs::task::task_llm;
+use crate::agent::create_llm_from_env;
 use crate::agent::models::context::SharedContextStore;
+use crate::agent::models::event::SharedEventCache;
 use crate::agent::tools::orchestrator::get_orchestrator_tools;
+use crate::global::{LLM_MODEL, ORCHESTRATOR_LLM_MODEL};
 use langchain_rust::language_models::llm::LLM;
+use tracing::{error, warn};
 
 // Use a type alias for the agent type to make it easier to use
 pub type AgentType = Arc<
@@ -40,72 +45,215 @@ pub type AgentType = Arc<
 	>,
 >;
 
-pub fn create_orchestrator_agent(
+/// Conversation memory of one chat session, kept between the agents built for it
+pub type SharedMemory = Arc<tokio::sync::Mutex<dyn BaseMemory>>;
+
+/// Builds an orchestrator for a single message.
+///
+/// Every message gets its own agent, sub-agents and tools, constructed with the chat
+/// session and user they work for, so concurrent messages never wait on each other
+/// or read another request's ids.
+pub trait AgentFactory: Send + Sync {
+	fn build(
+		&self,
+		chat_session_id: i32,
+		user_id: i32,
+	) -> Result<AgentExecutor<ConversationalAgent>, AgentError>;
+
+	/// Drops the conversation memory of `chat_session_id`, once the chat is deleted or idle
+	fn forget(&self, chat_session_id: i32);
+}
+
+/// Shared agent factory handed to controllers through an [axum::Extension].
+pub type SharedAgentFactory = Arc<dyn AgentFactory>;
+
+/// LLM handed to controllers that prompt it directly, through an [axum::Extension].
+pub type SharedLlm = Arc<dyn LLM + Send + Sync>;
+
+/// The LLM each agent prompts
+#[derive(Clone)]
+pub struct AgentLlms {
+	pub orchestrator: SharedLlm,
+	pub task: SharedLlm,
+	pub research: SharedLlm,
+	pub constraint: SharedLlm,
+	pub optimize: SharedLlm,
+}
+
+impl AgentLlms {
+	/// Every agent prompting `llm`
+	pub fn shared(llm: SharedLlm) -> Self {
+		Self {
+			orchestrator: llm.clone(),
+			task: llm.clone(),
+			research: llm.clone(),
+			constraint: llm.clone(),
+			optimize: llm,
+		}
+	}
+
+	/// Each agent's LLM from its `*_LLM_MODEL` env var, see [create_llm_from_env]
+	pub fn from_env() -> Result<Self, String> {
+		Ok(Self {
+			orchestrator: orchestrator_llm()?,
+			task: task_llm()?,
+			research: research_llm()?,
+			constraint: constraint_llm()?,
+			optimize: optimize_llm()?,
+		})
+	}
+}
+
+/// The LLM the Orchestrator Agent prompts, `ORCHESTRATOR_LLM_MODEL` through [create_llm_from_env]
+pub fn orchestrator_llm() -> Result<SharedLlm, String> {
+	create_llm_from_env(ORCHESTRATOR_LLM_MODEL)
+}
+
+/// Conversation memories keyed by chat session id
+#[derive(Default)]
+pub struct SessionMemories(std::sync::Mutex<HashMap<i32, SharedMemory>>);
+
+impl SessionMemories {
+	/// The chat session's memory, created empty on its first message
+	pub fn get(&self, chat_session_id: i32) -> SharedMemory {
+		self.0
+			.lock()
+			.unwrap()
+			.entry(chat_session_id)
+			.or_insert_with(|| SimpleMemory::new().into())
+			.clone()
+	}
+
+	/// Drops the chat session's memory, its next message starts an empty one
+	pub fn remove(&self, chat_session_id: i32) {
+		self.0.lock().unwrap().remove(&chat_session_id);
+	}
+}
+
+/// Builds the orchestrator prompting `llms`, see [create_orchestrator_agent]
+pub struct OrchestratorAgentFactory {
 	pool: PgPool,
-) -> Result<
-	(
-		AgentExecutor<ConversationalAgent>,
-		Arc<AtomicI32>,
-		Arc<AtomicI32>,
-		SharedContextStore,
-	),
-	AgentError,
-> {
-	// Load environment variables
-	dotenvy::dotenv().ok();
-
-	// Create a shared LLM instance for the orchestrator and its tools
-	// Use MockLLM if DEPLOY_LLM != "1", otherwise use OpenAI
-	let use_mock = std::env::var("DEPLOY_LLM").unwrap_or_default() != "1";
+	context_store: SharedContextStore,
+	event_cache: SharedEventCache,
+	memories: SessionMemories,
+	llms: AgentLlms,
+}
 
-	let llm_for_subagents = OpenAI::default().with_model(OpenAIModel::Gpt4oMini);
-	let llm_for_tools: Arc<dyn LLM + Send + Sync> = if use_mock {
-		Arc::new(MockLLM)
-	} else {
-		Arc::new(llm_for_subagents.clone())
-	};
+impl OrchestratorAgentFactory {
+	pub fn new(
+		pool: PgPool,
+		context_store: SharedContextStore,
+		event_cache: SharedEventCache,
+		llms: AgentLlms,
+	) -> Self {
+		Self {
+			pool,
+			context_store,
+			event_cache,
+			memories: SessionMemories::default(),
+			llms,
+		}
+	}
+}
 
-	// Create memory for conversation history
-	let memory = SimpleMemory::new();
+impl AgentFactory for OrchestratorAgentFactory {
+	fn build(
+		&self,
+		chat_session_id: i32,
+		user_id: i32,
+	) -> Result<AgentExecutor<ConversationalAgent>, AgentError> {
+		create_orchestrator_agent(
+			self.llms.clone(),
+			self.pool.clone(),
+			self.context_store.clone(),
+			self.event_cache.clone(),
+			self.memories.get(chat_session_id),
+			chat_session_id,
+			user_id,
+		)
+	}
 
-	// Create shared atomics for chat_session_id and user_id (will be set per request)
-	let chat_session_id = Arc::new(AtomicI32::new(0));
-	let user_id = Arc::new(AtomicI32::new(0));
+	fn forget(&self, chat_session_id: i32) {
+		self.memories.remove(chat_session_id);
+	}
+}
 
-	// In-memory context store shared by orchestrator + sub-agents
-	let context_store: SharedContextStore =
-		Arc::new(tokio::sync::RwLock::new(std::collections::HashMap::new()));
+/// Builds the dummy orchestrator, see [create_dummy_orchestrator_agent]
+pub struct DummyOrchestratorAgentFactory {
+	pool: PgPool,
+	context_store: SharedContextStore,
+	event_cache: SharedEventCache,
+	memories: SessionMemories,
+}
+
+impl DummyOrchestratorAgentFactory {
+	pub fn new(
+		pool: PgPool,
+		context_store: SharedContextStore,
+		event_cache: SharedEventCache,
+	) -> Self {
+		Self {
+			pool,
+			context_store,
+			event_cache,
+			memories: SessionMemories::default(),
+		}
+	}
+}
+
+impl AgentFactory for DummyOrchestratorAgentFactory {
+	fn build(
+		&self,
+		chat_session_id: i32,
+		user_id: i32,
+	) -> Result<AgentExecutor<ConversationalAgent>, AgentError> {
+		create_dummy_orchestrator_agent(
+			self.pool.clone(),
+			self.context_store.clone(),
+			self.event_cache.clone(),
+			self.memories.get(chat_session_id),
+			chat_session_id,
+			user_id,
+		)
+	}
 
+	fn forget(&self, chat_session_id: i32) {
+		self.memories.remove(chat_session_id);
+	}
+}
+
+/// Creates the orchestrator for one message in `chat_session_id` from `user_id`.
+/// Its sub-agents and tools are created with the same ids, each prompting its LLM in `llms`.
+pub fn create_orchestrator_agent(
+	llms: AgentLlms,
+	pool: PgPool,
+	context_store: SharedContextStore,
+	event_cache: SharedEventCache,
+	memory: SharedMemory,
+	chat_session_id: i32,
+	user_id: i32,
+) -> Result<AgentExecutor<ConversationalAgent>, AgentError> {
 	// Create research agent
 	let research_agent = Arc::new(tokio::sync::Mutex::new(Arc::new(tokio::sync::Mutex::new(
-		create_research_agent(pool.clone()).unwrap(),
+		create_research_agent(llms.research, pool.clone(), event_cache.clone())?,
 	))));
 
-	// Create constraint agent (wired with shared chat_session_id)
+	// Create constraint agent (wired with this message's chat_session_id)
 	let constraint_agent = Arc::new(tokio::sync::Mutex::new(Arc::new(tokio::sync::Mutex::new(
-		create_constraint_agent(
-			llm_for_subagents.clone(),
-			pool.clone(),
-			Arc::clone(&chat_session_id),
-		)
-		.unwrap(),
+		create_constraint_agent(llms.constraint, pool.clone(), chat_session_id)?,
 	))));
 
-	// Create optimize agent (wired with shared chat_session_id)
+	// Create optimize agent (wired with this message's chat_session_id)
 	let optimize_agent = Arc::new(tokio::sync::Mutex::new(Arc::new(tokio::sync::Mutex::new(
-		create_optimize_agent(
-			llm_for_subagents.clone(),
-			pool.clone(),
-			Arc::clone(&chat_session_id),
-		)
-		.unwrap(),
+		create_optimize_agent(llms.optimize, pool.clone(), event_cache, chat_session_id)?,
 	))));
 
 	// Create Task Agent (sub-agent used to build context and user profile)
 	let task_agent_executor = create_task_agent(
+		llms.task,
 		pool.clone(),
-		Arc::clone(&chat_session_id),
-		Arc::clone(&user_id),
+		chat_session_id,
+		user_id,
 		context_store.clone(),
 	)?;
 	let task_agent_inner: AgentType = Arc::new(tokio::sync::Mutex::new(task_agent_executor));
@@ -113,97 +261,66 @@ pub fn create_orchestrator_agent(
 
 	// Get orchestrator tools
 	let tools = get_orchestrator_tools(
-		llm_for_tools,
-		pool.clone(),
+		llms.orchestrator.clone(),
+		pool,
 		task_agent,
 		research_agent,
 		constraint_agent,
 		optimize_agent,
-		chat_session_id.clone(),
-		user_id.clone(),
-		context_store.clone(),
+		chat_session_id,
+		user_id,
+		context_store,
 	);
 
 	// Create agent with system prompt and tools
-	let agent = if use_mock {
-		let mock_llm = MockLLM;
-		ConversationalAgentBuilder::new()
-			.prefix(ORCHESTRATOR_SYSTEM_PROMPT.to_string())
-			.tools(&tools)
-			.options(ChainCallOptions::new().with_max_tokens(2000))
-			.build(mock_llm)
-			.unwrap()
-	} else {
-		ConversationalAgentBuilder::new()
-			.prefix(ORCHESTRATOR_SYSTEM_PROMPT.to_string())
-			.tools(&tools)
-			.options(ChainCallOptions::new().with_max_tokens(2000))
-			.build(llm_for_subagents)
-			.unwrap()
-	};
+	let agent = ConversationalAgentBuilder::new()
+		.prefix(ORCHESTRATOR_SYSTEM_PROMPT.to_string())
+		.tools(&tools)
+		.options(ChainCallOptions::new().with_max_tokens(2000))
+		.build(llms.orchestrator.clone_box())?;
 
 	// Create executor with increased max iterations for complex multi-agent workflows
 	// Default is 10, but we need more for orchestrator → sub-agent → tools chains
-	Ok((
-		AgentExecutor::from_agent(agent)
-			.with_memory(memory.into())
-			.with_max_iterations(30),
-		chat_session_id,
-		user_id,
-		context_store,
-	))
+	Ok(AgentExecutor::from_agent(agent)
+		.with_memory(memory)
+		.with_max_iterations(30))
 }
 
 /// Creates a dummy agent for testing purposes.
 /// This agent will have an invalid API key and will panic if invoked,
 /// but when DEPLOY_LLM != "1", the agent is never invoked, so this is safe.
-/// This allows tests to run without requiring a valid OPENAI_API_KEY.
-#[cfg(test)]
+/// This allows tests, and servers without a valid OPENAI_API_KEY, to run.
 pub fn create_dummy_orchestrator_agent(
 	pool: PgPool,
-) -> Result<
-	(
-		AgentExecutor<ConversationalAgent>,
-		Arc<AtomicI32>,
-		Arc<AtomicI32>,
-		SharedContextStore,
-	),
-	AgentError,
-> {
+	context_store: SharedContextStore,
+	event_cache: SharedEventCache,
+	memory: SharedMemory,
+	chat_session_id: i32,
+	user_id: i32,
+) -> Result<AgentExecutor<ConversationalAgent>, AgentError> {
 	// Use MockLLM for testing to avoid API key requirements
 	let llm = MockLLM;
 
-	// Create memory
-	let memory = SimpleMemory::new();
-
 	let llm_arc = Arc::new(llm.clone());
-	let chat_session_id = Arc::new(AtomicI32::new(0));
-	let user_id = Arc::new(AtomicI32::new(0));
-	let context_store: SharedContextStore =
-		Arc::new(tokio::sync::RwLock::new(std::collections::HashMap::new()));
 
 	// Dummy sub-agents for testing, each using its own dummy configuration
-	let task_agent_executor = create_dummy_task_agent(
-		pool.clone(),
-		Arc::clone(&chat_session_id),
-		Arc::clone(&user_id),
-	)?;
+	let task_agent_executor = create_dummy_task_agent(pool.clone(), chat_session_id, user_id)?;
 	let task_agent_inner: AgentType = Arc::new(tokio::sync::Mutex::new(task_agent_executor));
 	let task_agent = Arc::new(tokio::sync::Mutex::new(task_agent_inner));
 
 	let research_agent_inner: AgentType = Arc::new(tokio::sync::Mutex::new(
-		create_dummy_research_agent(pool.clone())?,
+		create_dummy_research_agent(pool.clone(), event_cache.clone())?,
 	));
 	let research_agent = Arc::new(tokio::sync::Mutex::new(research_agent_inner));
 
 	let constraint_agent_inner: AgentType = Arc::new(tokio::sync::Mutex::new(
-		create_dummy_constraint_agent(pool.clone(), Arc::clone(&chat_session_id))?,
+		create_dummy_constraint_agent(pool.clone(), chat_session_id)?,
 	));
 	let constraint_agent = Arc::new(tokio::sync::Mutex::new(constraint_agent_inner));
 
 	let optimize_llm = OpenAI::default().with_model(OpenAIModel::Gpt4Turbo);
 	let optimize_agent_inner: AgentType = Arc::new(tokio::sync::Mutex::new(
-		create_dummy_optimize_agent(optimize_llm, pool.clone(), Arc::clone(&chat_session_id))?,
+		create_dummy_optimize_agent(optimize_llm, pool.clone(), event_cache, chat_session_id)?,
 	));
 	let optimize_agent = Arc::new(tokio::sync::Mutex::new(optimize_agent_inner));
 	let tools = get_orchestrator_tools(
@@ -213,9 +330,9 @@ pub fn create_dummy_orchestrator_agent(
 		research_agent,
 		constraint_agent,
 		optimize_agent,
-		chat_session_id.clone(),
-		user_id.clone(),
-		context_store.clone(),
+		chat_session_id,
+		user_id,
+		context_store,
 	);
 
 	let agent = ConversationalAgentBuilder::new()
@@ -225,12 +342,79 @@ pub fn create_dummy_orchestrator_agent(
 		.build(llm)
 		.unwrap();
 
-	Ok((
-		AgentExecutor::from_agent(agent).with_memory(memory.into()),
-		chat_session_id,
-		user_id,
-		context_store,
-	))
+	Ok(AgentExecutor::from_agent(agent).with_memory(memory))
+}
+
+/// Creates the agent factory the server runs with, without failing startup.
+/// - When DEPLOY_LLM != "1" the orchestrator prompts [MockLLM], falling back to the dummy one if it
+///   can't be created
+/// - When DEPLOY_LLM == "1" it prompts the LLMs from [AgentLlms::from_env], if those or the
+///   orchestrator can't be created the factory is `None` and AI features are disabled
+pub fn create_server_orchestrator_agent(
+	pool: PgPool,
+	event_cache: SharedEventCache,
+) -> (Option<SharedAgentFactory>, SharedContextStore) {
+	let use_mock = std::env::var("DEPLOY_LLM").unwrap_or_default() != "1";
+
+	// In-memory context store shared by every orchestrator + sub-agent
+	let context_store = SharedContextStore::default();
+
+	let llms = if use_mock {
+		Ok(AgentLlms::shared(Arc::new(MockLLM)))
+	} else {
+		AgentLlms::from_env().map_err(AgentError::OtherError)
+	};
+
+	// Build an agent up front so misconfiguration is caught at startup, not on the first message
+	let created = llms.and_then(|llms| {
+		let real: SharedAgentFactory = Arc::new(OrchestratorAgentFactory::new(
+			pool.clone(),
+			context_store.clone(),
+			event_cache.clone(),
+			llms,
+		));
+		real.build(0, 0).map(|_| real)
+	});
+	let created = match created {
+		Ok(real) => Ok(real),
+		Err(e) if use_mock => {
+			warn!(
+				"Failed to create orchestrator agent, using the dummy agent: {}",
+				e
+			);
+			let dummy: SharedAgentFactory = Arc::new(DummyOrchestratorAgentFactory::new(
+				pool,
+				context_store.clone(),
+				event_cache,
+			));
+			dummy.build(0, 0).map(|_| dummy)
+		}
+		Err(e) => Err(e),
+	};
+
+	match created {
+		Ok(factory) => (Some(factory), context_store),
+		Err(e) => {
+			error!(
+				"Failed to create orchestrator agent, AI features are disabled: {}",
+				e
+			);
+			(None, context_store)
+		}
+	}
+}
+
+/// The LLM controllers prompt directly, [MockLLM] unless DEPLOY_LLM == "1".
+/// * Otherwise it prompts the `LLM_MODEL` from [create_llm_from_env], or [MockLLM] if
+///   that can't be created
+pub fn create_server_llm() -> SharedLlm {
+	if std::env::var("DEPLOY_LLM").unwrap_or_default() != "1" {
+		return Arc::new(MockLLM);
+	}
+	create_llm_from_env(LLM_MODEL).unwrap_or_else(|e| {
+		error!("Failed to create the LLM, using the mock LLM: {}", e);
+		Arc::new(MockLLM)
+	})
 }
 
 /// The system prompt for the Orchestrator Agent.
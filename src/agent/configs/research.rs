@@ -16,30 +16,33 @@ use langchain_rust::{
 
 use sqlx::PgPool;
 
+use crate::agent::configs::orchestrator::SharedLlm;
+use crate::agent::create_llm_from_env;
+use crate::agent::models::event::SharedEventCache;
 use crate::agent::tools::research::research_tools;
+use crate::global::RESEARCH_LLM_MODEL;
 
 const SYSTEM_PROMPT: &str = include_str!("../prompts/research.md");
 
+/// The LLM the Research Agent prompts, `RESEARCH_LLM_MODEL` through [create_llm_from_env]
+pub fn research_llm() -> Result<SharedLlm, String> {
+	create_llm_from_env(RESEARCH_LLM_MODEL)
+}
+
+/// Creates the Research Agent, prompting `llm` from [crate::agent::create_llm]
 pub fn create_research_agent(
+	llm: SharedLlm,
 	pool: PgPool,
+	event_cache: SharedEventCache,
 ) -> Result<AgentExecutor<ConversationalAgent>, AgentError> {
-	// Load environment variables
-	dotenvy::dotenv().ok();
-
-	// Note: Even when DEPLOY_LLM != "1", we still need to create an agent
-	// (it won't be used at runtime). OpenAI API key is still required for agent creation.
-
 	// Create memory
 	let memory = SimpleMemory::new();
 
-	// Select model (will read key from environment variable)
-	let llm = OpenAI::default().with_model(OpenAIModel::Gpt4oMini);
-
 	let agent = ConversationalAgentBuilder::new()
 		.prefix(SYSTEM_PROMPT.to_string())
-		.tools(&research_tools(pool))
+		.tools(&research_tools(pool, event_cache))
 		.options(ChainCallOptions::new().with_max_tokens(1000))
-		.build(llm)
+		.build(llm.clone_box())
 		.unwrap();
 
 	Ok(AgentExecutor::from_agent(agent).with_memory(memory.into()))
@@ -48,10 +51,10 @@ pub fn create_research_agent(
 /// Creates a dummy agent for testing purposes.
 /// This agent will have an invalid API key and will panic if invoked,
 /// but when DEPLOY_LLM != "1", the agent is never invoked, so this is safe.
-/// This allows tests to run without requiring a valid OPENAI_API_KEY.
-#[cfg(test)]
+/// This allows tests, and servers without a valid OPENAI_API_KEY, to run.
 pub fn create_dummy_research_agent(
 	pool: PgPool,
+	event_cache: SharedEventCache,
 ) -> Result<AgentExecutor<ConversationalAgent>, AgentError> {
 	// Set a dummy API key temporarily so agent creation doesn't fail
 	// The agent won't actually be used when DEPLOY_LLM != "1"
@@ -70,7 +73,7 @@ pub fn create_dummy_research_agent(
 
 	let agent = ConversationalAgentBuilder::new()
 		.prefix(SYSTEM_PROMPT.to_string())
-		.tools(&research_tools(pool))
+		.tools(&research_tools(pool, event_cache))
 		.options(ChainCallOptions::new().with_max_tokens(1000))
 		.build(llm)
 		.unwrap();
@@ -1,3 +1,138 @@
 pub mod configs;
 pub mod models;
+pub mod parsing;
+pub mod security;
 pub mod tools;
+
+use std::str::FromStr;
+use std::sync::Arc;
+
+use langchain_rust::language_models::llm::LLM;
+use langchain_rust::llm::ollama::openai::OllamaConfig;
+use langchain_rust::llm::{Claude, OpenAI};
+
+use tracing::warn;
+
+use crate::global::{
+	ANTHROPIC_API_KEY, CONSTRAINT_LLM_MODEL, DEFAULT_OLLAMA_BASE_URL, LLM_MODEL, LLM_PROVIDER,
+	OLLAMA_BASE_URL, OPENAI_API_KEY, OPTIMIZE_LLM_MODEL, ORCHESTRATOR_LLM_MODEL,
+	RESEARCH_LLM_MODEL, TASK_LLM_MODEL,
+};
+
+/// Service the agents prompt, picked with the `LLM_PROVIDER` env var
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum LlmProvider {
+	#[default]
+	OpenAI,
+	Anthropic,
+	/// A local model served by Ollama, through its OpenAI compatible API
+	Ollama,
+}
+
+impl FromStr for LlmProvider {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s.trim().to_lowercase().as_str() {
+			"openai" => Ok(Self::OpenAI),
+			"anthropic" => Ok(Self::Anthropic),
+			"ollama" => Ok(Self::Ollama),
+			other => Err(format!("Unknown {LLM_PROVIDER}: {other}")),
+		}
+	}
+}
+
+impl LlmProvider {
+	/// The provider named by `LLM_PROVIDER`, [LlmProvider::OpenAI] when it isn't set
+	pub fn from_env() -> Result<Self, String> {
+		match std::env::var(LLM_PROVIDER) {
+			Ok(name) if !name.trim().is_empty() => name.parse(),
+			_ => Ok(Self::default()),
+		}
+	}
+
+	/// Model used when `LLM_MODEL` isn't set
+	pub fn default_model(self) -> &'static str {
+		match self {
+			Self::OpenAI => "gpt-4o-mini",
+			Self::Anthropic => "claude-3-5-sonnet-20240620",
+			Self::Ollama => "llama3.2",
+		}
+	}
+
+	/// Env var holding the provider's API key, `None` if it doesn't need one
+	pub fn api_key_var(self) -> Option<&'static str> {
+		match self {
+			Self::OpenAI => Some(OPENAI_API_KEY),
+			Self::Anthropic => Some(ANTHROPIC_API_KEY),
+			Self::Ollama => None,
+		}
+	}
+}
+
+/// An LLM prompting `model` through `provider`.
+/// * API keys and the Ollama endpoint are read from their env vars
+pub fn create_llm(provider: LlmProvider, model: &str) -> Arc<dyn LLM + Send + Sync> {
+	match provider {
+		LlmProvider::OpenAI => Arc::new(OpenAI::default().with_model(model)),
+		LlmProvider::Anthropic => Arc::new(
+			Claude::new()
+				.with_api_key(std::env::var(ANTHROPIC_API_KEY).unwrap_or_default())
+				.with_model(model),
+		),
+		LlmProvider::Ollama => {
+			let base_url = std::env::var(OLLAMA_BASE_URL)
+				.unwrap_or_else(|_| DEFAULT_OLLAMA_BASE_URL.to_string());
+			Arc::new(OpenAI::new(OllamaConfig::default().with_api_base(base_url)).with_model(model))
+		}
+	}
+}
+
+/// The model named by `model_var`, falling back to `LLM_MODEL` and then the provider's default
+pub fn model_from_env(provider: LlmProvider, model_var: &str) -> String {
+	[model_var, LLM_MODEL]
+		.into_iter()
+		.filter_map(|var| std::env::var(var).ok())
+		.map(|model| model.trim().to_string())
+		.find(|model| !model.is_empty())
+		.unwrap_or_else(|| provider.default_model().to_string())
+}
+
+/// The LLM configured by `LLM_PROVIDER`, prompting the model from [model_from_env], see [create_llm].
+/// * Errors if the provider is unknown or its API key isn't set
+pub fn create_llm_from_env(model_var: &str) -> Result<Arc<dyn LLM + Send + Sync>, String> {
+	let provider = LlmProvider::from_env()?;
+	if let Some(key_var) = provider.api_key_var()
+		&& std::env::var(key_var).unwrap_or_default().is_empty()
+	{
+		return Err(format!(
+			"{provider:?} is the LLM provider but {key_var} is not set"
+		));
+	}
+	Ok(create_llm(provider, &model_from_env(provider, model_var)))
+}
+
+/// Warns about each agent's `*_LLM_MODEL` env var that isn't set, that agent prompts the
+/// `LLM_MODEL` fallback instead.
+/// * Only checked when DEPLOY_LLM == "1", otherwise the agents prompt the mock LLM
+pub fn validate_env() {
+	if std::env::var("DEPLOY_LLM").unwrap_or_default() != "1" {
+		return;
+	}
+	// An unknown provider is reported when the agents are created
+	let Ok(provider) = LlmProvider::from_env() else {
+		return;
+	};
+	let fallback = model_from_env(provider, LLM_MODEL);
+	for var in [
+		ORCHESTRATOR_LLM_MODEL,
+		TASK_LLM_MODEL,
+		RESEARCH_LLM_MODEL,
+		CONSTRAINT_LLM_MODEL,
+		OPTIMIZE_LLM_MODEL,
+	] {
+		if std::env::var(var).unwrap_or_default().trim().is_empty() {
+			warn!("{var} is not set, using {fallback}");
+		}
+	}
+}
@@ -6,12 +6,19 @@
 
 */
 
+use crate::agent::configs::orchestrator::SharedAgentFactory;
+use crate::global::{
+	CONTEXT_EVICTION_INTERVAL_SECONDS, CONTEXT_IDLE_TTL_SECONDS, DEFAULT_LANGUAGE,
+};
 use crate::http_models::event::Event;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
+use sqlx::PgPool;
 use std::collections::HashMap;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 use tokio::sync::RwLock;
+use tokio_util::sync::CancellationToken;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TaskRoute {
@@ -27,7 +34,10 @@ pub struct TripContext {
 	pub destination: Option<String>,
 	pub start_date: Option<String>, // ISO 8601 date format (YYYY-MM-DD)
 	pub end_date: Option<String>,   // ISO 8601 date format (YYYY-MM-DD)
-	pub budget: Option<f64>,        // Total budget in USD
+	pub budget: Option<f64>,        // Budget in USD, for the whole group unless budget_is_per_person
+	pub travelers: Option<u32>,     // Number of people going - OPTIONAL, solo when None
+	pub budget_is_per_person: Option<bool>, // Budget is for each traveler - OPTIONAL
+	pub daily_budget_usd: Option<f64>, // Most to spend each day in USD, split like budget - OPTIONAL
 	pub preferences: Vec<String>,   // ["cultural experiences", "beach time"] - OPTIONAL
 	pub constraints: Vec<String>,   // Dietary, accessibility, etc. - pre-filled from profile
 	pub action: Option<String>,     // "create", "modify", "view", "delete"
@@ -35,6 +45,72 @@ pub struct TripContext {
 	pub asked_clarification: bool,  // Track if we've asked user at least once
 }
 
+impl TripContext {
+	/// Budget for one traveler, the group's budget split evenly unless it's already per person
+	pub fn per_person_budget(&self) -> Option<f64> {
+		let budget = self.budget?;
+		if self.budget_is_per_person == Some(true) {
+			return Some(budget);
+		}
+		Some(budget / self.travelers.unwrap_or(1).max(1) as f64)
+	}
+
+	/// Daily budget for one traveler, split the same way as [TripContext::per_person_budget]
+	pub fn per_person_daily_budget(&self) -> Option<f64> {
+		let daily_budget = self.daily_budget_usd?;
+		if self.budget_is_per_person == Some(true) {
+			return Some(daily_budget);
+		}
+		Some(daily_budget / self.travelers.unwrap_or(1).max(1) as f64)
+	}
+
+	/// Updates the fields the LLM found in `extracted`, keeping the rest as they are.
+	/// Preferences are added to the ones already known.
+	pub fn merge_extracted(&mut self, extracted: &Value) {
+		if let Some(dest) = extracted["destination"].as_str() {
+			self.destination = Some(dest.to_string());
+		}
+		if let Some(start) = extracted["start_date"].as_str() {
+			self.start_date = Some(start.to_string());
+		}
+		if let Some(end) = extracted["end_date"].as_str() {
+			self.end_date = Some(end.to_string());
+		}
+		if let Some(budget) = extracted["budget"].as_f64() {
+			self.budget = Some(budget);
+		}
+		if let Some(travelers) = extracted["travelers"]
+			.as_u64()
+			.and_then(|travelers| u32::try_from(travelers).ok())
+			.filter(|travelers| *travelers > 0)
+		{
+			self.travelers = Some(travelers);
+		}
+		if let Some(daily_budget) = extracted["daily_budget_usd"]
+			.as_f64()
+			.filter(|daily_budget| *daily_budget > 0.0)
+		{
+			self.daily_budget_usd = Some(daily_budget);
+		}
+		if let Some(budget_is_per_person) = extracted["budget_is_per_person"].as_bool() {
+			self.budget_is_per_person = Some(budget_is_per_person);
+		}
+		if let Some(prefs) = extracted["preferences"].as_array() {
+			let new_prefs: Vec<String> = prefs
+				.iter()
+				.filter_map(|v| v.as_str().map(|s| s.to_string()))
+				.collect();
+			if !new_prefs.is_empty() {
+				self.preferences.extend(new_prefs);
+				self.preferences.dedup();
+			}
+		}
+		if let Some(action) = extracted["action"].as_str() {
+			self.action = Some(action.to_string());
+		}
+	}
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ToolExecution {
 	pub tool_name: String,
@@ -66,11 +142,23 @@ pub struct ContextData {
 	pub active_itinerary: Option<Value>,
 	pub events: Vec<Event>, // Current running list of events being processed
 	pub tool_history: Vec<ToolExecution>,
+	#[serde(default)]
+	pub tool_call_count: u32, // Tools called while replying to the latest message, see MAX_TOOL_CALLS
 	pub pipeline_stage: Option<String>, // Current stage in the pipeline
 	pub researched_events: Vec<Event>,  // Events from research agent
 	pub constrained_events: Vec<Event>, // Events validated by constraint agent
 	pub optimized_events: Vec<Event>,   // Events ranked/optimized by optimizer agent
 	pub constraints: Vec<String>, // User constraints extracted from intent (dietary, accessibility, budget, etc.)
+	#[serde(default = "default_language")]
+	pub preferred_language: String, // Language tag the LLM replies in, set for each message sent
+	#[serde(skip)]
+	pub cancellation: CancellationToken, // Cancelled by /api/chat/cancel to stop the reply being generated
+	#[serde(skip, default = "Instant::now")]
+	pub last_accessed: Instant, // Last message sent in this chat, used to evict idle contexts
+}
+
+fn default_language() -> String {
+	DEFAULT_LANGUAGE.to_string()
 }
 
 /// Shared in-memory store for per-chat ContextData.
@@ -80,6 +168,80 @@ pub struct ContextData {
 /// the database on every tool call.
 pub type SharedContextStore = Arc<RwLock<HashMap<i32, ContextData>>>;
 
+/// Language the LLM replies in for the chat, `DEFAULT_LANGUAGE` if it has no context.
+pub async fn preferred_language(
+	context_store: &SharedContextStore,
+	chat_session_id: i32,
+) -> String {
+	context_store
+		.read()
+		.await
+		.get(&chat_session_id)
+		.map(|ctx| ctx.preferred_language.clone())
+		.unwrap_or_else(default_language)
+}
+
+/// Removes contexts that haven't been accessed for `ttl` and returns the chat sessions removed.
+///
+/// The agent forgets the conversations of the removed chats.
+/// Also updates the `context_store_entries` gauge with what's left.
+pub async fn evict_idle_contexts(
+	context_store: &SharedContextStore,
+	agent: Option<&SharedAgentFactory>,
+	ttl: Duration,
+) -> Vec<i32> {
+	let mut store_guard = context_store.write().await;
+	let mut evicted = Vec::new();
+	store_guard.retain(|chat_session_id, ctx| {
+		let keep = ctx.last_accessed.elapsed() < ttl;
+		if !keep {
+			evicted.push(*chat_session_id);
+		}
+		keep
+	});
+	crate::middleware::metrics::CONTEXT_STORE_ENTRIES.set(store_guard.len() as i64);
+	if let Some(agent) = agent {
+		for chat_session_id in &evicted {
+			agent.forget(*chat_session_id);
+		}
+	}
+	evicted
+}
+
+/// Evicts idle contexts every `CONTEXT_EVICTION_INTERVAL_SECONDS` for the lifetime of the server.
+/// * Also purges messages deleted over `MESSAGE_RETENTION_DAYS` ago
+pub fn spawn_context_evictor(
+	context_store: SharedContextStore,
+	pool: PgPool,
+	agent: Option<SharedAgentFactory>,
+) {
+	tokio::spawn(async move {
+		let mut interval =
+			tokio::time::interval(Duration::from_secs(CONTEXT_EVICTION_INTERVAL_SECONDS));
+		loop {
+			interval.tick().await;
+			let evicted = evict_idle_contexts(
+				&context_store,
+				agent.as_ref(),
+				Duration::from_secs(CONTEXT_IDLE_TTL_SECONDS),
+			)
+			.await;
+			if !evicted.is_empty() {
+				tracing::info!(
+					target: "orchestrator_pipeline",
+					evicted = evicted.len(),
+					"Evicted idle chat contexts"
+				);
+			}
+			match crate::controllers::chat::purge_deleted_messages(&pool).await {
+				Ok(0) => {}
+				Ok(purged) => tracing::info!(purged = purged, "Purged deleted messages"),
+				Err(e) => tracing::error!("Failed to purge deleted messages: {e}"),
+			}
+		}
+	});
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PartialResult {
 	pub agent: String,
@@ -1,10 +1,27 @@
 use chrono::{NaiveDate, NaiveDateTime};
+use lru::LruCache;
 use serde::{Deserialize, Serialize};
+use std::num::NonZeroUsize;
+use std::sync::{Arc, Mutex};
 
+use crate::global::EVENT_CACHE_SIZE;
 use crate::sql_models::Period;
 
+/// Events the optimizer fetched, keyed by id, so re-optimizing them in a follow-up message
+/// skips the database.
+///
+/// Also handed to controllers through an [axum::Extension] so edited events can be evicted.
+pub type SharedEventCache = Arc<Mutex<LruCache<i32, Event>>>;
+
+/// An empty [SharedEventCache] holding up to `EVENT_CACHE_SIZE` events
+pub fn new_event_cache() -> SharedEventCache {
+	Arc::new(Mutex::new(LruCache::new(
+		NonZeroUsize::new(EVENT_CACHE_SIZE).unwrap(),
+	)))
+}
+
 /// A subset of [crate::http_models::event::Event] which only contains fields that the LLM might need for context.
-#[derive(Deserialize, Serialize)]
+#[derive(Clone, Default, Deserialize, Serialize)]
 pub struct Event {
 	/// Primary key
 	pub id: i32,
@@ -21,6 +38,8 @@ pub struct Event {
 	pub hard_end: Option<NaiveDateTime>,
 	/// Timezone of hard start and hard end
 	pub timezone: Option<String>,
+	/// Google place id, `None` for user created events
+	pub place_id: Option<String>,
 	pub wheelchair_accessible_parking: Option<bool>,
 	pub wheelchair_accessible_entrance: Option<bool>,
 	pub wheelchair_accessible_restroom: Option<bool>,
@@ -39,3 +58,40 @@ pub struct Event {
 	/// Must be some to guarantee ordering
 	pub block_index: Option<i32>,
 }
+
+impl From<crate::http_models::event::Event> for Event {
+	fn from(event: crate::http_models::event::Event) -> Self {
+		Self {
+			id: event.id,
+			event_name: event.event_name,
+			event_description: event.event_description,
+			street_address: event.street_address,
+			city: event.city,
+			country: event.country,
+			postal_code: event.postal_code,
+			lat: event.lat,
+			lng: event.lng,
+			event_type: event.event_type,
+			hard_start: event.hard_start,
+			hard_end: event.hard_end,
+			timezone: event.timezone,
+			place_id: event.place_id,
+			wheelchair_accessible_parking: event.wheelchair_accessible_parking,
+			wheelchair_accessible_entrance: event.wheelchair_accessible_entrance,
+			wheelchair_accessible_restroom: event.wheelchair_accessible_restroom,
+			wheelchair_accessible_seating: event.wheelchair_accessible_seating,
+			serves_vegetarian_food: event.serves_vegetarian_food,
+			price_level: event.price_level,
+			utc_offset_minutes: event.utc_offset_minutes,
+			types: event.types,
+			weekday_descriptions: event.weekday_descriptions,
+			secondary_hours_type: event.secondary_hours_type,
+			next_open_time: event.next_open_time,
+			next_close_time: event.next_close_time,
+			open_now: event.open_now,
+			periods: event.periods,
+			special_days: event.special_days,
+			block_index: event.block_index,
+		}
+	}
+}
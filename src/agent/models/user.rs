@@ -15,6 +15,8 @@ pub struct UserIntent {
 	pub start_date: Option<String>,
 	pub end_date: Option<String>,
 	pub budget: Option<f64>,
+	pub travelers: Option<u32>,             // Group size, solo when None
+	pub budget_is_per_person: Option<bool>, // Budget is for each traveler, not the group
 	pub preferences: Vec<String>,
 	pub constraints: Vec<String>,
 	pub missing_info: Vec<String>, // What information is still needed
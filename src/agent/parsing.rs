@@ -0,0 +1,426 @@
+/*
+ * src/agent/parsing.rs
+ *
+ * Deterministic parsing of trip details from user messages
+ *
+ * Purpose:
+ *   Find dates, budgets and group sizes in what the user wrote without asking
+ *   the LLM, so tools never ask for details the user already gave.
+ */
+
+use chrono::{Datelike, Duration, NaiveDate, Weekday};
+use once_cell::sync::Lazy;
+use regex::{Captures, Regex};
+
+use crate::agent::models::user::UserIntent;
+
+/// Full or abbreviated English month name
+const MONTH: &str = r"jan(?:uary)?|feb(?:ruary)?|mar(?:ch)?|apr(?:il)?|may|june?|july?|aug(?:ust)?|sept?(?:ember)?|oct(?:ober)?|nov(?:ember)?|dec(?:ember)?";
+/// Words joining the two ends of a range, e.g. `20-30` or `20 to 30`
+const RANGE_SEP: &str = r"\s*(?:-|–|to|through|thru|until|till)\s*";
+/// An amount of money, optionally with thousands separators, e.g. `1,500.50`
+const AMOUNT: &str = r"\d{1,3}(?:,\d{3})+(?:\.\d+)?|\d+(?:\.\d+)?";
+/// A number of people, in digits or spelled out, see [count_number]
+const COUNT: &str = r"\d{1,2}|one|two|three|four|five|six|seven|eight|nine|ten|eleven|twelve";
+
+/// `2026-07-20`, `2026-07-20 to 2026-07-30`
+static REGEX_ISO_DATE: Lazy<Regex> = Lazy::new(|| {
+	Regex::new(&format!(
+		r"\b(?P<y1>\d{{4}})-(?P<m1>\d{{2}})-(?P<d1>\d{{2}})(?:{RANGE_SEP}(?P<y2>\d{{4}})-(?P<m2>\d{{2}})-(?P<d2>\d{{2}}))?\b"
+	))
+	.unwrap()
+});
+
+/// `july 20-30th`, `Jul 20 to Aug 2`, `july 20th, 2026`
+static REGEX_MONTH_DAY: Lazy<Regex> = Lazy::new(|| {
+	Regex::new(&format!(
+		r"(?i)\b(?P<m1>{MONTH})\.?\s+(?P<d1>\d{{1,2}})(?:st|nd|rd|th)?(?:{RANGE_SEP}(?:(?P<m2>{MONTH})\.?\s+)?(?P<d2>\d{{1,2}})(?:st|nd|rd|th)?)?(?:,?\s+(?P<y>\d{{4}}))?\b"
+	))
+	.unwrap()
+});
+
+/// `7/20 - 7/30`, `10/8/26`
+static REGEX_NUMERIC_DATE: Lazy<Regex> = Lazy::new(|| {
+	Regex::new(&format!(
+		r"\b(?P<m1>\d{{1,2}})/(?P<d1>\d{{1,2}})(?:/(?P<y1>\d{{4}}|\d{{2}}))?(?:{RANGE_SEP}(?P<m2>\d{{1,2}})/(?P<d2>\d{{1,2}})(?:/(?P<y2>\d{{4}}|\d{{2}}))?)?\b"
+	))
+	.unwrap()
+});
+
+/// `this weekend`, `next weekend`
+static REGEX_WEEKEND: Lazy<Regex> =
+	Lazy::new(|| Regex::new(r"(?i)\b(?P<which>this|next)\s+weekend\b").unwrap());
+
+/// `$1,500`, `20-30 dollars`, `$2k`, `500 usd`
+/// * Only counted as money with a `$` or a currency word, see [parse_budget]
+static REGEX_MONEY: Lazy<Regex> = Lazy::new(|| {
+	Regex::new(&format!(
+		r"(?i)(?P<cur>\$)?(?P<a>{AMOUNT})(?P<ak>k)?(?:{RANGE_SEP}(?P<cur2>\$)?(?P<b>{AMOUNT})(?P<bk>k)?)?(?:\s*(?P<unit>dollars?|bucks?|usd))?\b"
+	))
+	.unwrap()
+});
+
+/// `budget of 2000`, `budget is around 1500-2000`
+static REGEX_BUDGET_KEYWORD: Lazy<Regex> = Lazy::new(|| {
+	Regex::new(&format!(
+		r"(?i)\bbudget(?:\s*(?:is|of|around|about|roughly|max(?:imum)?|up\s+to|under|:|=|~))*\s*\$?(?P<a>{AMOUNT})(?P<ak>k)?(?:{RANGE_SEP}\$?(?P<b>{AMOUNT})(?P<bk>k)?)?\b"
+	))
+	.unwrap()
+});
+
+/// `2 adults`, `one kid`, `4 people`
+/// * Counts next to each other are added up, see [parse_travelers]
+static REGEX_TRAVELER_COUNT: Lazy<Regex> = Lazy::new(|| {
+	Regex::new(&format!(
+		r"(?i)\b(?P<n>{COUNT})\s+(?:adults?|kids?|child(?:ren)?|teens?|teenagers?|infants?|bab(?:y|ies)|toddlers?|seniors?|people|persons|travell?ers|guests)\b"
+	))
+	.unwrap()
+});
+
+/// `family of four`, `group of 6`, `4 of us`
+static REGEX_GROUP_SIZE: Lazy<Regex> = Lazy::new(|| {
+	Regex::new(&format!(
+		r"(?i)\b(?:(?:family|group|party)\s+of\s+(?P<n>{COUNT})|(?P<us>{COUNT})\s+of\s+us)\b"
+	))
+	.unwrap()
+});
+
+/// `solo`, `by myself`, `just me`
+static REGEX_SOLO: Lazy<Regex> =
+	Lazy::new(|| Regex::new(r"(?i)\b(?:solo|alone|by\s+myself|just\s+me)\b").unwrap());
+
+/// What joins the counts of [REGEX_TRAVELER_COUNT] in `2 adults, 1 kid` or `2 adults and 1 kid`
+static REGEX_COUNT_SEP: Lazy<Regex> =
+	Lazy::new(|| Regex::new(r"(?i)^[\s,&+]*(?:and|plus)?\s*$").unwrap());
+
+/// `$500 per person`, `a head`, `apiece`
+static REGEX_PER_PERSON: Lazy<Regex> = Lazy::new(|| {
+	Regex::new(r"(?i)\b(?:per\s+(?:person|head|travell?er|adult)|a\s+head|apiece)\b").unwrap()
+});
+
+/// `$3000 total`, `combined`, `for all of us`
+static REGEX_GROUP_BUDGET: Lazy<Regex> = Lazy::new(|| {
+	Regex::new(
+		r"(?i)\b(?:in\s+total|total|combined|altogether|for\s+(?:all\s+of\s+us|everyone|the\s+(?:whole\s+)?(?:group|family)))\b",
+	)
+	.unwrap()
+});
+
+/// Trip details found in a message without the LLM
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct ParsedDetails {
+	pub start_date: Option<NaiveDate>,
+	pub end_date: Option<NaiveDate>,
+	/// Total budget in USD, the midpoint when a range was given
+	pub budget: Option<f64>,
+	/// How many people are going, including the user
+	pub travelers: Option<u32>,
+	/// Whether `budget` is for each traveler rather than the whole group
+	pub budget_is_per_person: Option<bool>,
+}
+
+impl ParsedDetails {
+	/// Parses the dates, budget and group size in `text`.
+	///
+	/// When something is mentioned more than once the last mention wins, so later messages
+	/// in a conversation override earlier ones. Dates without a year are the next time that
+	/// date comes around on or after `today`.
+	pub fn parse(text: &str, today: NaiveDate) -> Self {
+		let (start_date, end_date) = match parse_dates(text, today) {
+			Some((start, end)) => (Some(start), end),
+			None => (None, None),
+		};
+		Self {
+			start_date,
+			end_date,
+			budget: parse_budget(text),
+			travelers: parse_travelers(text),
+			budget_is_per_person: parse_budget_is_per_person(text),
+		}
+	}
+
+	/// Overrides what the LLM extracted with what was found here, and drops it from `missing_info`
+	pub fn merge_into(&self, intent: &mut UserIntent) {
+		if let Some(start_date) = self.start_date {
+			intent.start_date = Some(start_date.to_string());
+		}
+		if let Some(end_date) = self.end_date {
+			intent.end_date = Some(end_date.to_string());
+		}
+		if let Some(budget) = self.budget {
+			intent.budget = Some(budget);
+		}
+		if let Some(travelers) = self.travelers {
+			intent.travelers = Some(travelers);
+		}
+		if let Some(budget_is_per_person) = self.budget_is_per_person {
+			intent.budget_is_per_person = Some(budget_is_per_person);
+		}
+		self.remove_known(&mut intent.missing_info);
+	}
+
+	/// Drops entries like `"dates"` or `"budget"` from `missing_info` when they were found
+	/// * The number of travelers is never asked for, trips are for one person unless told otherwise
+	pub fn remove_known(&self, missing_info: &mut Vec<String>) {
+		missing_info.retain(|missing| {
+			let missing = missing.to_lowercase();
+			let found = if [
+				"traveler",
+				"traveller",
+				"people",
+				"group size",
+				"party size",
+			]
+			.iter()
+			.any(|group| missing.contains(group))
+			{
+				true
+			} else if missing.contains("budget") {
+				self.budget.is_some()
+			} else if missing.contains("start date") || missing.contains("start_date") {
+				self.start_date.is_some()
+			} else if missing.contains("end date") || missing.contains("end_date") {
+				self.end_date.is_some()
+			} else if missing.contains("date") || missing.contains("when") {
+				self.start_date.is_some() && self.end_date.is_some()
+			} else {
+				false
+			};
+			!found
+		});
+	}
+
+	/// Readable list of what was found, e.g. `["Dates: 2026-07-20 to 2026-07-30", "Budget: $1500"]`
+	pub fn known_info(&self) -> Vec<String> {
+		let mut known_info = Vec::new();
+		match (self.start_date, self.end_date) {
+			(Some(start), Some(end)) => known_info.push(format!("Dates: {start} to {end}")),
+			(Some(start), None) => known_info.push(format!("Start date: {start}")),
+			_ => {}
+		}
+		if let Some(budget) = self.budget {
+			if self.budget_is_per_person == Some(true) {
+				known_info.push(format!("Budget: ${budget} per person"));
+			} else {
+				known_info.push(format!("Budget: ${budget}"));
+			}
+		}
+		if let Some(travelers) = self.travelers {
+			known_info.push(format!("Travelers: {travelers}"));
+		}
+		known_info
+	}
+}
+
+/// Start and, if given, end date of the last date or date range mentioned in `text`
+/// * `this weekend` is the coming Saturday and Sunday, `next weekend` is the one after
+pub fn parse_dates(text: &str, today: NaiveDate) -> Option<(NaiveDate, Option<NaiveDate>)> {
+	let iso = REGEX_ISO_DATE.captures_iter(text).filter_map(|c| {
+		let start = NaiveDate::from_ymd_opt(
+			c["y1"].parse().ok()?,
+			c["m1"].parse().ok()?,
+			c["d1"].parse().ok()?,
+		)?;
+		let end = match (c.name("y2"), c.name("m2"), c.name("d2")) {
+			(Some(y2), Some(m2), Some(d2)) => Some(NaiveDate::from_ymd_opt(
+				y2.as_str().parse().ok()?,
+				m2.as_str().parse().ok()?,
+				d2.as_str().parse().ok()?,
+			)?),
+			_ => None,
+		};
+		Some((c.get(0)?.start(), (start, end)))
+	});
+	let month_day = REGEX_MONTH_DAY.captures_iter(text).filter_map(|c| {
+		let year = c.name("y").and_then(|y| y.as_str().parse().ok());
+		let start_month = month_number(&c["m1"])?;
+		let start = next_date(year, start_month, c["d1"].parse().ok()?, today)?;
+		let end = match c.name("d2") {
+			Some(d2) => {
+				let end_month = match c.name("m2") {
+					Some(m2) => month_number(m2.as_str())?,
+					None => start_month,
+				};
+				Some(date_after(start, end_month, d2.as_str().parse().ok()?)?)
+			}
+			None => None,
+		};
+		Some((c.get(0)?.start(), (start, end)))
+	});
+	let numeric = REGEX_NUMERIC_DATE.captures_iter(text).filter_map(|c| {
+		let start = next_date(
+			c.name("y1").and_then(|y| parse_year(y.as_str())),
+			c["m1"].parse().ok()?,
+			c["d1"].parse().ok()?,
+			today,
+		)?;
+		let end = match (c.name("m2"), c.name("d2")) {
+			(Some(m2), Some(d2)) => {
+				let (month, day) = (m2.as_str().parse().ok()?, d2.as_str().parse().ok()?);
+				Some(match c.name("y2").and_then(|y| parse_year(y.as_str())) {
+					Some(year) => NaiveDate::from_ymd_opt(year, month, day)?,
+					None => date_after(start, month, day)?,
+				})
+			}
+			_ => None,
+		};
+		Some((c.get(0)?.start(), (start, end)))
+	});
+	let weekend = REGEX_WEEKEND.captures_iter(text).filter_map(|c| {
+		let days_until_saturday = (Weekday::Sat.num_days_from_monday() as i64
+			- today.weekday().num_days_from_monday() as i64)
+			.rem_euclid(7);
+		let mut saturday = today + Duration::days(days_until_saturday);
+		if c["which"].eq_ignore_ascii_case("next") {
+			saturday += Duration::days(7);
+		}
+		Some((
+			c.get(0)?.start(),
+			(saturday, Some(saturday + Duration::days(1))),
+		))
+	});
+
+	iso.chain(month_day)
+		.chain(numeric)
+		.chain(weekend)
+		.max_by_key(|(position, _)| *position)
+		.map(|(_, dates)| dates)
+}
+
+/// Budget in USD of the last amount of money mentioned in `text`, the midpoint for a range
+pub fn parse_budget(text: &str) -> Option<f64> {
+	let money = REGEX_MONEY.captures_iter(text).filter(|c| {
+		c.name("cur").is_some() || c.name("cur2").is_some() || c.name("unit").is_some()
+	});
+	let keyword = REGEX_BUDGET_KEYWORD.captures_iter(text);
+
+	money
+		.chain(keyword)
+		.filter_map(|c| Some((c.get(0)?.start(), amount_or_midpoint(&c)?)))
+		.max_by_key(|(position, _)| *position)
+		.map(|(_, budget)| budget)
+}
+
+/// Number of travelers in the last mention of the group's size in `text`
+/// * Counts listed together are added up, so `2 adults and 1 kid` is 3
+pub fn parse_travelers(text: &str) -> Option<u32> {
+	let mut counts: Vec<(usize, u32)> = Vec::new();
+	let mut run_end = None;
+	for c in REGEX_TRAVELER_COUNT.captures_iter(text) {
+		let (Some(m), Some(n)) = (c.get(0), count_number(&c["n"])) else {
+			continue;
+		};
+		match (run_end, counts.last_mut()) {
+			(Some(end), Some((_, total))) if REGEX_COUNT_SEP.is_match(&text[end..m.start()]) => {
+				*total += n
+			}
+			_ => counts.push((m.start(), n)),
+		}
+		run_end = Some(m.end());
+	}
+	let groups = REGEX_GROUP_SIZE.captures_iter(text).filter_map(|c| {
+		let n = c.name("n").or(c.name("us"))?;
+		Some((c.get(0)?.start(), count_number(n.as_str())?))
+	});
+	let solo = REGEX_SOLO.find_iter(text).map(|m| (m.start(), 1));
+
+	counts
+		.into_iter()
+		.chain(groups)
+		.chain(solo)
+		.filter(|(_, travelers)| *travelers > 0)
+		.max_by_key(|(position, _)| *position)
+		.map(|(_, travelers)| travelers)
+}
+
+/// Whether the last mention of how the budget is split in `text` says it's per person
+pub fn parse_budget_is_per_person(text: &str) -> Option<bool> {
+	let per_person = REGEX_PER_PERSON.find_iter(text).map(|m| (m.start(), true));
+	let group = REGEX_GROUP_BUDGET
+		.find_iter(text)
+		.map(|m| (m.start(), false));
+
+	per_person
+		.chain(group)
+		.max_by_key(|(position, _)| *position)
+		.map(|(_, per_person)| per_person)
+}
+
+/// The number matched by [COUNT]
+fn count_number(count: &str) -> Option<u32> {
+	let number = match count.to_lowercase().as_str() {
+		"one" => 1,
+		"two" => 2,
+		"three" => 3,
+		"four" => 4,
+		"five" => 5,
+		"six" => 6,
+		"seven" => 7,
+		"eight" => 8,
+		"nine" => 9,
+		"ten" => 10,
+		"eleven" => 11,
+		"twelve" => 12,
+		digits => return digits.parse().ok(),
+	};
+	Some(number)
+}
+
+/// The amount in `a`, or the midpoint of `a` and `b` for a range
+fn amount_or_midpoint(c: &Captures) -> Option<f64> {
+	let amount = |value: &str, thousands: bool| -> Option<f64> {
+		let value: f64 = value.replace(',', "").parse().ok()?;
+		Some(if thousands { value * 1000.0 } else { value })
+	};
+	let a = amount(&c["a"], c.name("ak").is_some())?;
+	match c.name("b") {
+		Some(b) => Some((a + amount(b.as_str(), c.name("bk").is_some())?) / 2.0),
+		None => Some(a),
+	}
+}
+
+/// 1-12 for a month name matched by [MONTH]
+fn month_number(name: &str) -> Option<u32> {
+	let month = match name.get(..3)?.to_lowercase().as_str() {
+		"jan" => 1,
+		"feb" => 2,
+		"mar" => 3,
+		"apr" => 4,
+		"may" => 5,
+		"jun" => 6,
+		"jul" => 7,
+		"aug" => 8,
+		"sep" => 9,
+		"oct" => 10,
+		"nov" => 11,
+		"dec" => 12,
+		_ => return None,
+	};
+	Some(month)
+}
+
+/// Four digit years as is, two digit years in the 2000s
+fn parse_year(year: &str) -> Option<i32> {
+	let parsed: i32 = year.parse().ok()?;
+	Some(if year.len() == 2 {
+		2000 + parsed
+	} else {
+		parsed
+	})
+}
+
+/// The date in `year`, or without one the next time the date comes around on or after `today`
+fn next_date(year: Option<i32>, month: u32, day: u32, today: NaiveDate) -> Option<NaiveDate> {
+	if let Some(year) = year {
+		return NaiveDate::from_ymd_opt(year, month, day);
+	}
+	match NaiveDate::from_ymd_opt(today.year(), month, day) {
+		Some(date) if date >= today => Some(date),
+		_ => NaiveDate::from_ymd_opt(today.year() + 1, month, day),
+	}
+}
+
+/// The first `month`/`day` on or after `start`, for the end of a range like `dec 28 - jan 3`
+fn date_after(start: NaiveDate, month: u32, day: u32) -> Option<NaiveDate> {
+	next_date(None, month, day, start)
+}
@@ -0,0 +1,66 @@
+/*
+ * src/agent/security.rs
+ *
+ * File for screening user messages
+ *
+ * Purpose:
+ *   Strip common prompt injection phrases out of what the user wrote and
+ *   bound its size before it is stored or handed to the LLM.
+ */
+
+use once_cell::sync::Lazy;
+use regex::Regex;
+
+use crate::global::MAX_MESSAGE_LENGTH;
+
+/// Phrases trying to override the agent's instructions, removed by [sanitize_user_input]
+static INJECTION_PATTERNS: Lazy<[Regex; 5]> = Lazy::new(|| {
+	[
+		// `Ignore previous instructions`, `disregard all of the above rules`
+		Regex::new(
+			r"(?i)\b(?:ignore|disregard|forget|override)\s+(?:all\s+)?(?:of\s+)?(?:the\s+|your\s+)?(?:previous|prior|above|earlier|preceding)\s+(?:instructions?|prompts?|rules|messages|directions)\b",
+		)
+		.unwrap(),
+		// `output your system prompt`, `reveal the hidden instructions`
+		Regex::new(
+			r"(?i)\b(?:reveal|show|print|output|repeat|display|leak)\s+(?:me\s+)?(?:your|the)\s+(?:system\s+prompt|hidden\s+(?:prompt|instructions)|initial\s+(?:prompt|instructions)|instructions)\b",
+		)
+		.unwrap(),
+		// `enable developer mode`, `you are now in jailbreak mode`
+		Regex::new(
+			r"(?i)\b(?:enable|enter|activate|you\s+are\s+now\s+in)\s+(?:developer|jailbreak|dan|god)\s+mode\b",
+		)
+		.unwrap(),
+		// `new instructions:`, `new system prompt:`
+		Regex::new(r"(?i)\bnew\s+(?:system\s+)?(?:instructions|prompt)\s*:").unwrap(),
+		// Fake chat roles, `system:` at the start of a line or `<|im_start|>` style tokens
+		Regex::new(r"(?im)^\s*(?:system|assistant)\s*:|<\|?/?(?:system|im_start|im_end|endoftext)\|?>")
+			.unwrap(),
+	]
+});
+
+/// Runs of spaces and tabs, but not newlines
+static REGEX_HORIZONTAL_SPACE: Lazy<Regex> = Lazy::new(|| Regex::new(r"[^\S\n]+").unwrap());
+/// Spaces around a newline, once horizontal space is collapsed
+static REGEX_LINE_EDGES: Lazy<Regex> = Lazy::new(|| Regex::new(r" ?\n ?").unwrap());
+/// More than one blank line in a row
+static REGEX_BLANK_LINES: Lazy<Regex> = Lazy::new(|| Regex::new(r"\n{3,}").unwrap());
+
+/// Cleans up a user message before it is stored or sent to the LLM.
+/// * Removes each of the `INJECTION_PATTERNS`
+/// * Collapses runs of whitespace to one space, keeping at most one blank line in a row,
+///   and trims the ends
+/// * Truncates to `MAX_MESSAGE_LENGTH` characters
+pub fn sanitize_user_input(text: &str) -> String {
+	let mut text = text.to_string();
+	for pattern in INJECTION_PATTERNS.iter() {
+		text = pattern.replace_all(&text, " ").into_owned();
+	}
+
+	let text = REGEX_HORIZONTAL_SPACE.replace_all(&text, " ");
+	let text = REGEX_LINE_EDGES.replace_all(&text, "\n");
+	let text = REGEX_BLANK_LINES.replace_all(&text, "\n\n");
+
+	let text: String = text.trim().chars().take(MAX_MESSAGE_LENGTH).collect();
+	text.trim_end().to_string()
+}
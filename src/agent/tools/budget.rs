@@ -0,0 +1,74 @@
+/*
+ * src/agent/tools/budget.rs
+ *
+ * File for daily budget enforcement
+ *
+ * Purpose:
+ *   Estimate what each day of an itinerary costs from its events'
+ *   price levels and unassign events from days over the budget.
+ */
+
+use std::collections::HashMap;
+
+use crate::agent::models::event::Event;
+use crate::agent::tools::modify::PlannedItinerary;
+use crate::global::AVG_PRICE_PER_LEVEL;
+
+/// Estimated cost of the event for one person in USD, free without a `price_level`
+pub fn event_cost(event: &Event) -> f64 {
+	event
+		.price_level
+		.and_then(|level| usize::try_from(level).ok())
+		.map(|level| AVG_PRICE_PER_LEVEL[level.min(AVG_PRICE_PER_LEVEL.len() - 1)])
+		.unwrap_or(0.0)
+}
+
+/// Unassigns events from days that cost more than `daily_budget` per person, returning the
+/// adjusted itinerary and a sentence for each day that changed.
+/// * The cheapest events with a cost go first, earlier ones on ties, until the day fits
+/// * Free events and events missing from `events` are never unassigned
+pub fn enforce_daily_budget(
+	draft: &PlannedItinerary,
+	events: &HashMap<i32, &Event>,
+	daily_budget: f64,
+) -> (PlannedItinerary, Vec<String>) {
+	let mut itinerary = draft.clone();
+	let mut explanations = Vec::new();
+	let cost = |id: &i32| events.get(id).map(|e| event_cost(e)).unwrap_or(0.0);
+
+	for day in itinerary.days.iter_mut() {
+		let mut removed = Vec::new();
+		loop {
+			let scheduled = day
+				.morning
+				.iter()
+				.chain(&day.afternoon)
+				.chain(&day.evening)
+				.copied();
+			if scheduled.clone().map(|id| cost(&id)).sum::<f64>() <= daily_budget {
+				break;
+			}
+			let Some(cheapest) = scheduled
+				.filter(|id| cost(id) > 0.0)
+				.min_by(|a, b| cost(a).total_cmp(&cost(b)))
+			else {
+				break;
+			};
+			for block in [&mut day.morning, &mut day.afternoon, &mut day.evening] {
+				block.retain(|&id| id != cheapest);
+			}
+			itinerary.unassigned.push(cheapest);
+			removed.push(events[&cheapest].event_name.as_str());
+		}
+		if !removed.is_empty() {
+			explanations.push(format!(
+				"{} would go over your daily budget, so {} {} unassigned.",
+				day.date,
+				removed.join(", "),
+				if removed.len() == 1 { "is" } else { "are" },
+			));
+		}
+	}
+
+	(itinerary, explanations)
+}
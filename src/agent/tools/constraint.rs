@@ -13,14 +13,42 @@ use langchain_rust::language_models::llm::LLM;
 use langchain_rust::tools::Tool;
 use serde_json::{Value, json};
 use sqlx::PgPool;
+use std::collections::HashMap;
 use std::error::Error;
 use std::sync::Arc;
-use std::sync::atomic::{AtomicI32, Ordering};
 use std::time::Instant;
 use tracing::{debug, info};
 
+use crate::global::MIN_ACCESSIBILITY_SCORE;
 use crate::http_models::event::Event;
 
+/// Start of the constraint listing the events a regenerated itinerary has to keep
+const KEEP_EVENTS_PREFIX: &str = "Keep event IDs:";
+
+/// Constraint telling the constraint agent these events are mandatory inclusions,
+/// added by `POST /api/itinerary/{id}/regenerate`
+pub fn keep_events_constraint(event_ids: &[i32]) -> String {
+	let ids: Vec<String> = event_ids.iter().map(i32::to_string).collect();
+	format!("{KEEP_EVENTS_PREFIX} {}", ids.join(", "))
+}
+
+/// Whether the constraint was made by [keep_events_constraint], in any case
+pub fn is_keep_events_constraint(constraint: &str) -> bool {
+	constraint
+		.get(..KEEP_EVENTS_PREFIX.len())
+		.is_some_and(|prefix| prefix.eq_ignore_ascii_case(KEEP_EVENTS_PREFIX))
+}
+
+/// Event ids listed by the [keep_events_constraint]s among `constraints`
+pub fn kept_event_ids(constraints: &[String]) -> Vec<i32> {
+	constraints
+		.iter()
+		.filter(|c| is_keep_events_constraint(c))
+		.flat_map(|c| c[KEEP_EVENTS_PREFIX.len()..].split(','))
+		.filter_map(|id| id.trim().parse().ok())
+		.collect()
+}
+
 /// Uses an LLM to intelligently determine if an event should be included
 /// based on trip context, user preferences, and constraints
 async fn should_include_event(
@@ -35,25 +63,7 @@ async fn should_include_event(
 
 	// Create a prompt with all event details
 	let prompt = format!(
-		r#"You are evaluating whether a place/event is relevant for a vacation trip.
-
-TRIP CONTEXT:
-- User preferences: {}
-- User constraints: {}
-
-PLACE TO EVALUATE (all available data):
-{}
-
-RULES:
-1. ALWAYS EXCLUDE: schools, universities, hospitals, clinics, retail stores (Home Depot, Target, CVS, Staples), DMV, government offices, gas stations, banks
-2. INCLUDE places that match user preferences (e.g., if they want to "eat a lot", include restaurants, cafes, bars, wineries)
-3. INCLUDE potentially interesting vacation spots: parks, museums, theaters, attractions, hotels, landmarks
-4. For food preferences, only include actual dining establishments (restaurants, cafes, bars, bakeries, wineries) - NOT grocery stores
-5. If user requires wheelchair accessibility, check the wheelchair_accessible fields and EXCLUDE places that are not accessible
-
-Should this place be INCLUDED in the vacation itinerary?
-Respond with ONLY a JSON object in this exact format:
-{{"include": true/false, "reason": "brief reason"}}"#,
+		include_str!("../prompts/should_include_event.md"),
 		if preferences.is_empty() {
 			"none specified".to_string()
 		} else {
@@ -126,19 +136,15 @@ Respond with ONLY a JSON object in this exact format:
 pub struct FilterEventsByConstraintsTool {
 	llm: Arc<dyn LLM + Send + Sync>,
 	db: PgPool,
-	/// Shared atomic set by the controller before invoking the orchestrator.
+	/// Chat session the orchestrator was created for by the controller.
 	/// The constraint agent (and its tools) reuse this to know which chat
 	/// session they are operating on so they can fetch the authoritative
 	/// event-id list from the database instead of trusting the prompt.
-	chat_session_id: Arc<AtomicI32>,
+	chat_session_id: i32,
 }
 
 impl FilterEventsByConstraintsTool {
-	pub fn new(
-		llm: Arc<dyn LLM + Send + Sync>,
-		db: PgPool,
-		chat_session_id: Arc<AtomicI32>,
-	) -> Self {
+	pub fn new(llm: Arc<dyn LLM + Send + Sync>, db: PgPool, chat_session_id: i32) -> Self {
 		Self {
 			llm,
 			db,
@@ -204,7 +210,7 @@ impl Tool for FilterEventsByConstraintsTool {
 
 		// 1) Try to fetch the current event-id list from the database using
 		//    the chat_session_id set by the controller/orchestrator.
-		let chat_id = self.chat_session_id.load(Ordering::Relaxed);
+		let chat_id = self.chat_session_id;
 		let mut event_ids: Vec<i32> = Vec::new();
 
 		if chat_id > 0 {
@@ -285,6 +291,47 @@ impl Tool for FilterEventsByConstraintsTool {
 			);
 		}
 
+		// Extract constraints (strings, lowercased for matching)
+		let mut constraints_val = if parsed_input.get("constraints").is_some() {
+			parsed_input
+				.get("constraints")
+				.cloned()
+				.unwrap_or(Value::Null)
+		} else {
+			parsed_input
+				.get("trip_context")
+				.and_then(|tc| tc.get("constraints"))
+				.cloned()
+				.unwrap_or(Value::Null)
+		};
+
+		// If constraints is a JSON string, parse it into an array
+		if constraints_val.is_string() {
+			let constraints_str = constraints_val.as_str().unwrap_or("[]");
+			constraints_val = serde_json::from_str(constraints_str).unwrap_or(Value::Null);
+		}
+
+		let constraints: Vec<String> = if let Some(arr) = constraints_val.as_array() {
+			arr.iter()
+				.filter_map(|v| v.as_str().map(|s| s.to_lowercase()))
+				.collect()
+		} else {
+			Vec::new()
+		};
+
+		// Events pinned by an itinerary regenerate are always kept, even if an earlier
+		// tool dropped them, and aren't shown to the LLM as a constraint
+		let kept = kept_event_ids(&constraints);
+		let constraints: Vec<String> = constraints
+			.into_iter()
+			.filter(|c| !is_keep_events_constraint(c))
+			.collect();
+		for id in &kept {
+			if !event_ids.contains(id) {
+				event_ids.push(*id);
+			}
+		}
+
 		if event_ids.is_empty() {
 			crate::tool_trace!(
 				agent: "constraint",
@@ -413,34 +460,6 @@ impl Tool for FilterEventsByConstraintsTool {
 			"Events fetched successfully"
 		);
 
-		// Extract constraints (strings, lowercased for matching)
-		let mut constraints_val = if parsed_input.get("constraints").is_some() {
-			parsed_input
-				.get("constraints")
-				.cloned()
-				.unwrap_or(Value::Null)
-		} else {
-			parsed_input
-				.get("trip_context")
-				.and_then(|tc| tc.get("constraints"))
-				.cloned()
-				.unwrap_or(Value::Null)
-		};
-
-		// If constraints is a JSON string, parse it into an array
-		if constraints_val.is_string() {
-			let constraints_str = constraints_val.as_str().unwrap_or("[]");
-			constraints_val = serde_json::from_str(constraints_str).unwrap_or(Value::Null);
-		}
-
-		let constraints: Vec<String> = if let Some(arr) = constraints_val.as_array() {
-			arr.iter()
-				.filter_map(|v| v.as_str().map(|s| s.to_lowercase()))
-				.collect()
-		} else {
-			Vec::new()
-		};
-
 		// Extract preferences from trip_context
 		let preferences: Vec<String> = parsed_input
 			.get("trip_context")
@@ -481,8 +500,16 @@ impl Tool for FilterEventsByConstraintsTool {
 			let llm = Arc::clone(&self.llm);
 			let preferences = preferences.clone();
 			let constraints = constraints.clone();
+			let pinned = kept.contains(&event.id);
 
 			async move {
+				if pinned {
+					return (
+						event,
+						true,
+						Some("Kept from the previous itinerary".to_string()),
+					);
+				}
 				match should_include_event(&llm, &event, &preferences, &constraints).await {
 					Ok((should_include, reason)) => (event, should_include, reason),
 					Err(e) => {
@@ -577,15 +604,223 @@ impl Tool for FilterEventsByConstraintsTool {
 	}
 }
 
+/// One point for each `wheelchair_accessible_*` feature the venue is known to have, 0 to 4.
+/// Unknown features don't count.
+pub fn accessibility_score(features: [Option<bool>; 4]) -> u8 {
+	features.iter().filter(|f| **f == Some(true)).count() as u8
+}
+
+/// Least [accessibility_score] events need for a user with these `disabilities`,
+/// 0 when none are listed so nothing is filtered
+pub fn disability_min_score(disabilities: &str) -> u8 {
+	if disabilities.trim().is_empty() {
+		0
+	} else {
+		MIN_ACCESSIBILITY_SCORE
+	}
+}
+
+/// Tool that drops events without enough wheelchair accessibility for the user.
+///
+/// Works on the chat session's `current_event_ids`, falling back to `event_ids` in the input,
+/// and the disabilities on the account that owns the chat. Events are scored with
+/// [accessibility_score] and the ones below [disability_min_score] are removed:
+///
+/// ```json
+/// {
+///   "filtered_event_ids": [1, 3, 5, ...],
+///   "removed_events": [ { "event_id": 2, "event_name": "...", "reasons": ["..."] } ],
+///   "count": 3,
+///   "min_score": 3
+/// }
+/// ```
+///
+/// The surviving ids are saved as the session's `current_event_ids`, so
+/// `filter_events_by_constraints` only considers accessible events.
+#[derive(Clone)]
+pub struct AccessibilityScoreTool {
+	db: PgPool,
+	chat_session_id: i32,
+}
+
+impl AccessibilityScoreTool {
+	pub fn new(db: PgPool, chat_session_id: i32) -> Self {
+		Self {
+			db,
+			chat_session_id,
+		}
+	}
+}
+
+#[async_trait]
+impl Tool for AccessibilityScoreTool {
+	fn name(&self) -> String {
+		"score_accessibility".to_string()
+	}
+
+	fn description(&self) -> String {
+		"Scores the current events by wheelchair accessibility and removes the ones that don't meet the user's accessibility needs. Needs no input, it reads the events and the user's disabilities from the database."
+			.to_string()
+	}
+
+	fn parameters(&self) -> Value {
+		json!({
+			"type": "object",
+			"properties": {
+				"input_data": {
+					"type": "string",
+					"description": "Optional JSON with an event_ids array, only used when the chat has no events yet."
+				}
+			},
+			"required": []
+		})
+	}
+
+	async fn run(&self, input: Value) -> Result<String, Box<dyn Error>> {
+		crate::tool_trace!(
+			agent: "constraint",
+			tool: "score_accessibility",
+			status: "start"
+		);
+
+		let parsed_input: Value = if input.is_string() {
+			serde_json::from_str(input.as_str().unwrap_or("{}")).unwrap_or_else(|_| json!({}))
+		} else {
+			input
+		};
+
+		let session = sqlx::query!(
+			r#"
+			SELECT c.current_event_ids, a.disabilities
+			FROM chat_sessions c
+			INNER JOIN accounts a ON a.id = c.account_id
+			WHERE c.id = $1
+			"#,
+			self.chat_session_id
+		)
+		.fetch_optional(&self.db)
+		.await?;
+		let (mut event_ids, disabilities) = match session {
+			Some(row) => (row.current_event_ids, row.disabilities),
+			None => (Vec::new(), String::new()),
+		};
+		if event_ids.is_empty() {
+			event_ids = parsed_input
+				.get("event_ids")
+				.and_then(|v| v.as_array())
+				.map(|ids| {
+					ids.iter()
+						.filter_map(|v| v.as_i64().map(|i| i as i32))
+						.collect()
+				})
+				.unwrap_or_default();
+		}
+		if event_ids.is_empty() {
+			crate::tool_trace!(
+				agent: "constraint",
+				tool: "score_accessibility",
+				status: "error",
+				details: "no event IDs provided"
+			);
+			return Err("No event IDs provided to score_accessibility".into());
+		}
+
+		let min_score = disability_min_score(&disabilities);
+		let rows = sqlx::query!(
+			r#"
+			SELECT
+				id,
+				event_name,
+				wheelchair_accessible_parking,
+				wheelchair_accessible_entrance,
+				wheelchair_accessible_restroom,
+				wheelchair_accessible_seating
+			FROM events
+			WHERE id = ANY($1)
+			"#,
+			&event_ids
+		)
+		.fetch_all(&self.db)
+		.await?;
+		let scores: HashMap<i32, (String, u8)> = rows
+			.into_iter()
+			.map(|row| {
+				let score = accessibility_score([
+					row.wheelchair_accessible_parking,
+					row.wheelchair_accessible_entrance,
+					row.wheelchair_accessible_restroom,
+					row.wheelchair_accessible_seating,
+				]);
+				(row.id, (row.event_name, score))
+			})
+			.collect();
+
+		// Keep the incoming order, ids without an event are dropped
+		let mut filtered_ids = Vec::new();
+		let mut removed = Vec::new();
+		for id in event_ids {
+			let Some((event_name, score)) = scores.get(&id) else {
+				continue;
+			};
+			if *score >= min_score {
+				filtered_ids.push(id);
+			} else {
+				removed.push(json!({
+					"event_id": id,
+					"event_name": event_name,
+					"reasons": [format!("Accessibility score {score}/4 is below the required {min_score}")],
+				}));
+			}
+		}
+
+		if self.chat_session_id > 0 {
+			sqlx::query!(
+				"UPDATE chat_sessions SET current_event_ids = $1 WHERE id = $2",
+				&filtered_ids,
+				self.chat_session_id
+			)
+			.execute(&self.db)
+			.await?;
+		}
+
+		crate::tool_trace!(
+			agent: "constraint",
+			tool: "score_accessibility",
+			status: "success",
+			details: format!(
+				"min_score={}, filtered_count={}, removed_count={}",
+				min_score,
+				filtered_ids.len(),
+				removed.len()
+			)
+		);
+		info!(
+			target: "constraint_tools",
+			tool = "score_accessibility",
+			min_score,
+			filtered_count = filtered_ids.len(),
+			removed_count = removed.len(),
+			"Accessibility scoring completed"
+		);
+
+		Ok(json!({
+			"filtered_event_ids": filtered_ids,
+			"removed_events": removed,
+			"count": filtered_ids.len(),
+			"min_score": min_score,
+		})
+		.to_string())
+	}
+}
+
 /// Export Constraint Tools
 pub fn constraint_tools(
 	llm: Arc<dyn LLM + Send + Sync>,
 	db: PgPool,
-	chat_session_id: Arc<AtomicI32>,
+	chat_session_id: i32,
 ) -> Vec<Arc<dyn Tool>> {
-	vec![Arc::new(FilterEventsByConstraintsTool::new(
-		llm,
-		db,
-		chat_session_id,
-	))]
+	vec![
+		Arc::new(AccessibilityScoreTool::new(db.clone(), chat_session_id)),
+		Arc::new(FilterEventsByConstraintsTool::new(llm, db, chat_session_id)),
+	]
 }
@@ -0,0 +1,148 @@
+/*
+ * src/agent/tools/dedup.rs
+ *
+ * File for duplicate event removal
+ *
+ * Purpose:
+ *   Find copies of the same venue among ranked events, e.g. a user created
+ *   event and the researched one, so an itinerary never visits it twice.
+ */
+
+use serde_json::Value;
+use std::collections::HashMap;
+
+use crate::agent::models::event::Event;
+
+/// An event dropped by [find_duplicates] in favor of a copy of the same venue
+#[derive(Debug, Clone, PartialEq)]
+pub struct Duplicate {
+	pub kept: i32,
+	pub removed: i32,
+}
+
+/// Lowercased words of `text` without punctuation or a leading "the", so
+/// "The Art Institute of Chicago" matches "art institute of chicago"
+fn normalize(text: &str) -> String {
+	let text = text.to_lowercase().replace('&', " and ");
+	let words: Vec<&str> = text
+		.split(|c: char| !c.is_alphanumeric())
+		.filter(|w| !w.is_empty())
+		.collect();
+	match words.split_first() {
+		Some((&"the", rest)) if !rest.is_empty() => rest.join(" "),
+		_ => words.join(" "),
+	}
+}
+
+/// How many details are known about the event, to keep the most complete copy
+fn richness(event: &Event) -> usize {
+	[
+		event.event_description.is_some(),
+		event.street_address.is_some(),
+		event.city.is_some(),
+		event.country.is_some(),
+		event.postal_code.is_some(),
+		event.lat.is_some() && event.lng.is_some(),
+		event.event_type.is_some(),
+		event.types.is_some(),
+		event.place_id.is_some(),
+		event.price_level.is_some(),
+		event.weekday_descriptions.is_some(),
+		!event.periods.is_empty(),
+	]
+	.into_iter()
+	.filter(|&known| known)
+	.count()
+}
+
+/// Copies of the same venue among `ranked` (best first), and which copy of each is kept.
+/// * Events with the same `place_id` are copies
+/// * Events with the same normalized name and city are copies, unless both have a
+///   `place_id` and they differ, e.g. two branches of a chain
+/// * The copy with the most details is kept, the best ranked one on ties
+pub fn find_duplicates(ranked: &[&Event]) -> Vec<Duplicate> {
+	// Copies of each venue, best ranked first, and the venue's place id once one is seen
+	let mut groups: Vec<(Option<&str>, Vec<&Event>)> = Vec::new();
+	let mut by_place_id: HashMap<&str, usize> = HashMap::new();
+	let mut by_name: HashMap<(String, String), usize> = HashMap::new();
+
+	for &event in ranked {
+		let name_key = (
+			normalize(&event.event_name),
+			normalize(event.city.as_deref().unwrap_or_default()),
+		);
+		let place_id = event.place_id.as_deref();
+		let group = match place_id {
+			Some(place_id) => by_place_id.get(place_id).copied().or_else(|| {
+				by_name
+					.get(&name_key)
+					.copied()
+					.filter(|&g| groups[g].0.is_none())
+			}),
+			None => by_name.get(&name_key).copied(),
+		};
+		let group = group.unwrap_or_else(|| {
+			groups.push((None, Vec::new()));
+			groups.len() - 1
+		});
+
+		groups[group].1.push(event);
+		if let Some(place_id) = place_id {
+			groups[group].0.get_or_insert(place_id);
+			by_place_id.insert(place_id, group);
+		}
+		by_name.entry(name_key).or_insert(group);
+	}
+
+	let mut duplicates = Vec::new();
+	for (_, copies) in groups.iter().filter(|(_, copies)| copies.len() > 1) {
+		// max_by_key keeps the last maximum, so search from the worst ranked copy
+		let kept = copies
+			.iter()
+			.rev()
+			.max_by_key(|event| richness(event))
+			.unwrap();
+		duplicates.extend(
+			copies
+				.iter()
+				.filter(|event| event.id != kept.id)
+				.map(|event| Duplicate {
+					kept: kept.id,
+					removed: event.id,
+				}),
+		);
+	}
+	duplicates
+}
+
+/// `ranked_pois` without the removed `duplicates`. Each kept POI takes the place and
+/// `rank` of its best ranked copy.
+pub fn remove_duplicate_pois(ranked_pois: Vec<Value>, duplicates: &[Duplicate]) -> Vec<Value> {
+	let poi_id = |poi: &Value| poi.get("id").and_then(Value::as_i64);
+	let kept_for: HashMap<i64, i64> = duplicates
+		.iter()
+		.map(|d| (d.removed as i64, d.kept as i64))
+		.collect();
+	let mut by_id: HashMap<i64, Value> = ranked_pois
+		.iter()
+		.filter_map(|poi| Some((poi_id(poi)?, poi.clone())))
+		.collect();
+
+	let mut deduped = Vec::with_capacity(by_id.len());
+	for poi in ranked_pois {
+		let Some(id) = poi_id(&poi) else {
+			deduped.push(poi);
+			continue;
+		};
+		let kept = kept_for.get(&id).copied().unwrap_or(id);
+		// Already placed at a better ranked copy
+		let Some(mut kept_poi) = by_id.remove(&kept) else {
+			continue;
+		};
+		if let (Some(rank), Some(fields)) = (poi.get("rank"), kept_poi.as_object_mut()) {
+			fields.insert(String::from("rank"), rank.clone());
+		}
+		deduped.push(kept_poi);
+	}
+	deduped
+}
@@ -0,0 +1,112 @@
+/*
+ * src/agent/tools/hours.rs
+ *
+ * File for venue opening hours checks
+ *
+ * Purpose:
+ *   Decide whether an event's venue is open during a time block of a day,
+ *   from the Google Places `periods` and `special_days` stored with it.
+ */
+
+use chrono::{Datelike, NaiveDate};
+
+use crate::agent::models::event::Event;
+use crate::sql_models::{Period, TimeOfDay};
+
+const MINUTES_PER_DAY: i32 = 24 * 60;
+const MINUTES_PER_WEEK: i32 = 7 * MINUTES_PER_DAY;
+/// Least time a venue has to be open during a block to be scheduled in it
+const MIN_OPEN_MINUTES: i32 = 60;
+
+/// Whether a venue is open during a time block, see [is_open]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OpenStatus {
+	Open,
+	Closed,
+	/// No opening hours are known for that day, so it shouldn't be moved
+	Unknown,
+}
+
+/// Minutes from midnight that each time block covers
+fn block_window(block: TimeOfDay) -> (i32, i32) {
+	match block {
+		TimeOfDay::Morning => (8 * 60, 12 * 60),
+		TimeOfDay::Afternoon => (12 * 60, 17 * 60),
+		TimeOfDay::Evening => (17 * 60, 22 * 60),
+	}
+}
+
+/// Minutes from Sunday midnight a period is open, `None` if it never closes.
+/// * Periods closing on an earlier day or time than they open wrap into the next week
+/// * A truncated open or close is cut off by Google's 7 day window, so it is
+///   stretched to the start or end of that day
+fn period_minutes(period: &Period) -> Option<(i32, i32)> {
+	let open = if period.open_truncated == Some(true) {
+		period.open_day * MINUTES_PER_DAY
+	} else {
+		period.open_day * MINUTES_PER_DAY + period.open_hour * 60 + period.open_minute
+	};
+	let close_day = period.close_day?;
+	let close = if period.close_truncated == Some(true) {
+		(close_day + 1) * MINUTES_PER_DAY
+	} else {
+		close_day * MINUTES_PER_DAY
+			+ period.close_hour.unwrap_or(0) * 60
+			+ period.close_minute.unwrap_or(0)
+	};
+	Some(if close <= open {
+		(open, close + MINUTES_PER_WEEK)
+	} else {
+		(open, close)
+	})
+}
+
+/// Whether `period` is for specific dates that don't include `date`
+fn dated_elsewhere(period: &Period, date: NaiveDate) -> bool {
+	match period.open_date {
+		Some(open_date) => {
+			let close_date = period.close_date.unwrap_or(open_date);
+			date < open_date || date > close_date
+		}
+		None => false,
+	}
+}
+
+/// Whether `event`'s venue is open for at least `MIN_OPEN_MINUTES` of `block` on `date`.
+/// * A period without a close time is open around the clock
+/// * Dated periods only count on their dates, and on `special_days` the regular hours
+///   don't apply, so those days are `Unknown` unless a dated period covers them
+/// * Events without any periods for `date` are `Unknown`
+pub fn is_open(event: &Event, date: NaiveDate, block: TimeOfDay) -> OpenStatus {
+	let special_day = event.special_days.contains(&date);
+	let periods: Vec<&Period> = event
+		.periods
+		.iter()
+		.filter(|p| !dated_elsewhere(p, date) && (!special_day || p.open_date.is_some()))
+		.collect();
+	if periods.is_empty() {
+		return OpenStatus::Unknown;
+	}
+
+	let (start, end) = block_window(block);
+	let day = date.weekday().num_days_from_sunday() as i32 * MINUTES_PER_DAY;
+	let (start, end) = (day + start, day + end);
+
+	let mut open_minutes = 0;
+	for period in periods {
+		let Some((open, close)) = period_minutes(period) else {
+			return OpenStatus::Open;
+		};
+		// Periods wrapping past Saturday night also cover the start of the week
+		for shift in [0, MINUTES_PER_WEEK] {
+			let overlap = close.min(end + shift) - open.max(start + shift);
+			open_minutes += overlap.max(0);
+		}
+	}
+
+	if open_minutes >= MIN_OPEN_MINUTES {
+		OpenStatus::Open
+	} else {
+		OpenStatus::Closed
+	}
+}
@@ -0,0 +1,97 @@
+/*
+ * src/agent/tools/meals.rs
+ *
+ * File for meal slot selection
+ *
+ * Purpose:
+ *   Pick restaurants near each day's events for lunch and dinner,
+ *   without asking the LLM.
+ */
+
+use chrono::NaiveDate;
+use std::collections::HashSet;
+
+use crate::agent::models::event::Event;
+use crate::agent::tools::hours::{OpenStatus, is_open};
+use crate::sql_models::TimeOfDay;
+use crate::util::geo::haversine_km;
+
+/// Farthest a meal can be from the middle of the day's other events
+const MEAL_MAX_DISTANCE_KM: f64 = 5.0;
+
+/// Whether the event is somewhere to eat, from its Google place types
+pub fn is_meal_venue(event: &Event) -> bool {
+	event
+		.types
+		.iter()
+		.flat_map(|types| types.split(','))
+		.chain(event.event_type.as_deref())
+		.map(|t| t.trim().to_lowercase())
+		.any(|t| t.ends_with("restaurant") || t == "cafe")
+}
+
+/// Whether the restaurant suits the user's comma separated `food_allergies`.
+/// * `vegetarian` or `vegan` require `serves_vegetarian_food`
+/// * Other entries rule out restaurants mentioning them, e.g. `peanuts` rules out "Peanut Palace"
+fn suits_diet(event: &Event, food_allergies: &str) -> bool {
+	let text = format!(
+		"{} {} {}",
+		event.event_name,
+		event.event_description.as_deref().unwrap_or_default(),
+		event.types.as_deref().unwrap_or_default()
+	)
+	.to_lowercase();
+	food_allergies
+		.split(',')
+		.map(|a| a.trim().to_lowercase())
+		.filter(|a| !a.is_empty())
+		.all(|allergy| match allergy.as_str() {
+			"vegetarian" | "vegan" => event.serves_vegetarian_food == Some(true),
+			allergy => {
+				let singular = allergy.strip_suffix('s').filter(|a| a.len() > 2);
+				!text.contains(singular.unwrap_or(allergy))
+			}
+		})
+}
+
+/// Picks the restaurant for a meal in `block` on `date`, closest to the middle of `scheduled`.
+/// * Candidates must be meal venues that suit `food_allergies`, aren't in `taken`,
+///   and aren't known to be closed during `block`
+/// * When some of `scheduled` have coordinates, the restaurant must be within
+///   `MEAL_MAX_DISTANCE_KM` of them, otherwise the first suitable candidate is picked
+/// * `None` when nothing suitable is nearby
+pub fn select_meal(
+	scheduled: &[&Event],
+	candidates: &[&Event],
+	food_allergies: &str,
+	taken: &HashSet<i32>,
+	date: NaiveDate,
+	block: TimeOfDay,
+) -> Option<i32> {
+	let mut suitable = candidates.iter().filter(|event| {
+		is_meal_venue(event)
+			&& !taken.contains(&event.id)
+			&& suits_diet(event, food_allergies)
+			&& is_open(event, date, block.clone()) != OpenStatus::Closed
+	});
+
+	let located: Vec<(f64, f64)> = scheduled
+		.iter()
+		.filter_map(|event| event.lat.zip(event.lng))
+		.collect();
+	if located.is_empty() {
+		return suitable.next().map(|event| event.id);
+	}
+	let center = (
+		located.iter().map(|(lat, _)| lat).sum::<f64>() / located.len() as f64,
+		located.iter().map(|(_, lng)| lng).sum::<f64>() / located.len() as f64,
+	);
+
+	suitable
+		.filter_map(|event| {
+			let distance = haversine_km(center, event.lat.zip(event.lng)?);
+			(distance <= MEAL_MAX_DISTANCE_KM).then_some((distance, event.id))
+		})
+		.min_by(|a, b| a.0.total_cmp(&b.0))
+		.map(|(_, id)| id)
+}
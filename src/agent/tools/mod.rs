@@ -1,6 +1,12 @@
+pub mod budget;
 pub mod constraint;
+pub mod dedup;
+pub mod hours;
+pub mod meals;
+pub mod modify;
 pub mod optimizer;
 pub mod orchestrator;
 pub mod research;
 pub mod task;
 pub mod tsp;
+pub mod weather;
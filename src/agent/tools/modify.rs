@@ -0,0 +1,555 @@
+/*
+ * src/agent/tools/modify.rs
+ *
+ * File for the Modify Itinerary Tool
+ *
+ * Purpose:
+ *   Apply targeted edits a user asks for ("add a museum on day 2") to the
+ *   chat's itinerary, instead of researching and optimizing a new trip.
+ */
+
+use async_trait::async_trait;
+use chrono::NaiveDate;
+use langchain_rust::{language_models::llm::LLM, tools::Tool};
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use sqlx::PgPool;
+use std::collections::HashSet;
+use std::error::Error;
+use std::sync::Arc;
+use std::time::Instant;
+use tracing::{debug, info};
+
+use crate::agent::models::context::{SharedContextStore, TripContext};
+use crate::agent::tools::orchestrator::track_tool_execution;
+use crate::agent::tools::task::itinerary_dates;
+use crate::controllers::itinerary::full_itinerary;
+use crate::http_models::itinerary::Itinerary as HttpItinerary;
+use crate::sql_models::TimeOfDay;
+use crate::sql_models::itinerary::ItineraryRow;
+
+/// Most events the LLM is offered to add in one modification
+const CANDIDATE_EVENT_LIMIT: i64 = 30;
+
+/// Changes the LLM asks for, see `prompts/modify_itinerary.md`
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ItineraryDiff {
+	#[serde(default)]
+	pub add: Vec<Placement>,
+	/// Event ids to take out of the itinerary
+	#[serde(default)]
+	pub remove: Vec<i32>,
+	#[serde(default, rename = "move")]
+	pub moves: Vec<Placement>,
+}
+
+/// Where an added or moved event goes
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Placement {
+	pub event_id: i32,
+	pub date: NaiveDate,
+	/// `morning`, `afternoon` or `evening`, in any case
+	pub time_of_day: String,
+}
+
+/// One day of a [PlannedItinerary], event ids in order within each time block
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlannedDay {
+	pub date: NaiveDate,
+	pub morning: Vec<i32>,
+	pub afternoon: Vec<i32>,
+	pub evening: Vec<i32>,
+}
+
+impl PlannedDay {
+	fn new(date: NaiveDate) -> Self {
+		Self {
+			date,
+			morning: vec![],
+			afternoon: vec![],
+			evening: vec![],
+		}
+	}
+
+	pub fn block_mut(&mut self, time_of_day: &TimeOfDay) -> &mut Vec<i32> {
+		match time_of_day {
+			TimeOfDay::Morning => &mut self.morning,
+			TimeOfDay::Afternoon => &mut self.afternoon,
+			TimeOfDay::Evening => &mut self.evening,
+		}
+	}
+}
+
+/// The event ids of an itinerary, which is all a modification needs.
+/// Converts to the `active_itinerary` JSON that `respond_to_user` saves.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlannedItinerary {
+	pub title: String,
+	pub start_date: NaiveDate,
+	pub end_date: NaiveDate,
+	/// In chronological order
+	pub days: Vec<PlannedDay>,
+	pub unassigned: Vec<i32>,
+}
+
+impl From<&HttpItinerary> for PlannedItinerary {
+	fn from(itinerary: &HttpItinerary) -> Self {
+		let ids =
+			|events: &[crate::http_models::event::Event]| events.iter().map(|e| e.id).collect();
+		Self {
+			title: itinerary.title.clone(),
+			start_date: itinerary.start_date,
+			end_date: itinerary.end_date,
+			days: itinerary
+				.event_days
+				.iter()
+				.map(|day| PlannedDay {
+					date: day.date,
+					morning: ids(&day.morning_events),
+					afternoon: ids(&day.afternoon_events),
+					evening: ids(&day.evening_events),
+				})
+				.collect(),
+			unassigned: ids(&itinerary.unassigned_events),
+		}
+	}
+}
+
+impl PlannedItinerary {
+	/// Reads an `active_itinerary` from the context, with the same date fallbacks as `respond_to_user`
+	pub fn from_json(itinerary_json: &Value, trip_context: &TripContext) -> Result<Self, String> {
+		let dates = itinerary_dates(itinerary_json, trip_context)?;
+		let ids = |events: Option<&Value>| -> Vec<i32> {
+			events
+				.and_then(|v| v.as_array())
+				.map(|events| {
+					events
+						.iter()
+						.filter_map(|e| e.get("id").and_then(|v| v.as_i64()))
+						.map(|id| id as i32)
+						.collect()
+				})
+				.unwrap_or_default()
+		};
+		let days = itinerary_json
+			.get("event_days")
+			.and_then(|v| v.as_array())
+			.map(|days| {
+				days.iter()
+					.zip(&dates.day_dates)
+					.map(|(day, &date)| PlannedDay {
+						date,
+						morning: ids(day.get("morning_events")),
+						afternoon: ids(day.get("afternoon_events")),
+						evening: ids(day.get("evening_events")),
+					})
+					.collect()
+			})
+			.unwrap_or_default();
+		Ok(Self {
+			title: itinerary_json
+				.get("title")
+				.and_then(|v| v.as_str())
+				.unwrap_or("Trip Itinerary")
+				.to_string(),
+			start_date: dates.start_date,
+			end_date: dates.end_date,
+			days,
+			unassigned: ids(itinerary_json.get("unassigned_events")),
+		})
+	}
+
+	/// The `active_itinerary` JSON for `respond_to_user`, which fills in the events from their ids
+	pub fn to_json(&self) -> Value {
+		let events = |ids: &[i32]| ids.iter().map(|id| json!({ "id": id })).collect::<Vec<_>>();
+		json!({
+			"title": self.title,
+			"start_date": self.start_date,
+			"end_date": self.end_date,
+			"event_days": self.days.iter().map(|day| json!({
+				"date": day.date,
+				"morning_events": events(&day.morning),
+				"afternoon_events": events(&day.afternoon),
+				"evening_events": events(&day.evening),
+			})).collect::<Vec<_>>(),
+			"unassigned_events": events(&self.unassigned),
+		})
+	}
+
+	/// Every event id in the itinerary, scheduled or unassigned
+	pub fn event_ids(&self) -> Vec<i32> {
+		self.days
+			.iter()
+			.flat_map(|day| day.morning.iter().chain(&day.afternoon).chain(&day.evening))
+			.chain(&self.unassigned)
+			.copied()
+			.collect()
+	}
+
+	/// Applies `diff`, or changes nothing and lists every problem with it.
+	///
+	/// Removed and moved events must be in the itinerary, added ones must be in `addable`
+	/// and not in the itinerary yet, and every placement must be within the trip's dates.
+	/// Added and moved events go at the end of their time block.
+	pub fn apply(&mut self, diff: &ItineraryDiff, addable: &HashSet<i32>) -> Result<(), String> {
+		let current: HashSet<i32> = self.event_ids().into_iter().collect();
+		let mut errors = Vec::new();
+
+		for id in &diff.remove {
+			if !current.contains(id) {
+				errors.push(format!(
+					"event {id} can't be removed, it isn't in the itinerary"
+				));
+			}
+		}
+		let mut placed = HashSet::new();
+		let mut placements = Vec::new();
+		for (placement, adding) in diff
+			.add
+			.iter()
+			.map(|p| (p, true))
+			.chain(diff.moves.iter().map(|p| (p, false)))
+		{
+			let id = placement.event_id;
+			if adding && current.contains(&id) {
+				errors.push(format!(
+					"event {id} can't be added, it's already in the itinerary"
+				));
+			} else if adding && !addable.contains(&id) {
+				errors.push(format!("event {id} can't be added, it doesn't exist"));
+			} else if !adding && !current.contains(&id) {
+				errors.push(format!(
+					"event {id} can't be moved, it isn't in the itinerary"
+				));
+			} else if diff.remove.contains(&id) {
+				errors.push(format!("event {id} can't be both removed and placed"));
+			}
+			if !placed.insert(id) {
+				errors.push(format!("event {id} is placed more than once"));
+			}
+			if placement.date < self.start_date || placement.date > self.end_date {
+				errors.push(format!(
+					"event {id} can't go on {}, the trip is from {} to {}",
+					placement.date, self.start_date, self.end_date
+				));
+			}
+			match parse_time_of_day(&placement.time_of_day) {
+				Some(time_of_day) => placements.push((id, placement.date, time_of_day)),
+				None => errors.push(format!(
+					"event {id} has an unknown time_of_day '{}'",
+					placement.time_of_day
+				)),
+			}
+		}
+		if !errors.is_empty() {
+			return Err(errors.join("; "));
+		}
+
+		// take removed and moved events out, then put placed events in their new spots
+		let taken_out: HashSet<i32> = diff.remove.iter().chain(&placed).copied().collect();
+		for day in self.days.iter_mut() {
+			for block in [&mut day.morning, &mut day.afternoon, &mut day.evening] {
+				block.retain(|id| !taken_out.contains(id));
+			}
+		}
+		self.unassigned.retain(|id| !taken_out.contains(id));
+		for (id, date, time_of_day) in placements {
+			let index = match self.days.binary_search_by_key(&date, |day| day.date) {
+				Ok(index) => index,
+				Err(index) => {
+					self.days.insert(index, PlannedDay::new(date));
+					index
+				}
+			};
+			self.days[index].block_mut(&time_of_day).push(id);
+		}
+		Ok(())
+	}
+}
+
+/// `morning`, `afternoon` or `evening` in any case
+fn parse_time_of_day(time_of_day: &str) -> Option<TimeOfDay> {
+	match time_of_day.trim().to_lowercase().as_str() {
+		"morning" => Some(TimeOfDay::Morning),
+		"afternoon" => Some(TimeOfDay::Afternoon),
+		"evening" => Some(TimeOfDay::Evening),
+		_ => None,
+	}
+}
+
+/// Tool: Modify Itinerary
+/// Applies the change the user asked for to the chat's itinerary and stores the result
+/// as the active itinerary, for `respond_to_user` to save and send.
+#[derive(Clone)]
+pub struct ModifyItineraryTool {
+	llm: Arc<dyn LLM + Send + Sync>,
+	pool: PgPool,
+	chat_session_id: i32,
+	user_id: i32,
+	context_store: SharedContextStore,
+}
+
+impl ModifyItineraryTool {
+	pub fn new(
+		llm: Arc<dyn LLM + Send + Sync>,
+		pool: PgPool,
+		chat_session_id: i32,
+		user_id: i32,
+		context_store: SharedContextStore,
+	) -> Self {
+		Self {
+			llm,
+			pool,
+			chat_session_id,
+			user_id,
+			context_store,
+		}
+	}
+
+	/// The active itinerary from the context, or else the latest itinerary saved for this chat
+	async fn load_itinerary(&self) -> Result<Option<PlannedItinerary>, Box<dyn Error>> {
+		let context = self
+			.context_store
+			.read()
+			.await
+			.get(&self.chat_session_id)
+			.cloned();
+		let trip_context = context
+			.as_ref()
+			.map(|c| c.trip_context.clone())
+			.unwrap_or_default();
+		if let Some(itinerary_json) = context
+			.and_then(|c| c.active_itinerary)
+			.filter(|it| it.as_object().is_some_and(|obj| !obj.is_empty()))
+		{
+			return Ok(Some(PlannedItinerary::from_json(
+				&itinerary_json,
+				&trip_context,
+			)?));
+		}
+
+		let row = sqlx::query_as!(
+			ItineraryRow,
+			r#"
+			SELECT id, account_id, start_date, end_date, chat_session_id, title, unassigned_event_ids, starred
+			FROM itineraries
+			WHERE chat_session_id = $1 AND account_id = $2 AND deleted_at IS NULL
+			ORDER BY id DESC
+			LIMIT 1
+			"#,
+			self.chat_session_id,
+			self.user_id
+		)
+		.fetch_optional(&self.pool)
+		.await?;
+		Ok(match row {
+			Some(row) => Some(PlannedItinerary::from(
+				&full_itinerary(row, &self.pool).await?,
+			)),
+			None => None,
+		})
+	}
+}
+
+#[async_trait]
+impl Tool for ModifyItineraryTool {
+	fn name(&self) -> String {
+		"modify_itinerary".to_string()
+	}
+
+	fn description(&self) -> String {
+		"Applies a targeted change the user asked for (add, remove or move events) to the itinerary already created in this chat, instead of running the research pipeline again. Pass the user's request as 'request'. After it succeeds, call respond_to_user to save and send the updated itinerary. If it reports that there is no itinerary yet, run the research pipeline instead."
+			.to_string()
+	}
+
+	fn parameters(&self) -> Value {
+		json!({
+			"type": "object",
+			"properties": {
+				"request": {
+					"type": "string",
+					"description": "The change the user asked for, e.g. 'add a museum on day 2'"
+				}
+			},
+			"required": ["request"]
+		})
+	}
+
+	async fn run(&self, input: Value) -> Result<String, Box<dyn Error>> {
+		let start_time = Instant::now();
+		let input_clone = input.clone();
+
+		crate::tool_trace!(agent: "orchestrator", tool: "modify_itinerary", status: "start");
+
+		let chat_id = self.chat_session_id;
+		if chat_id == 0 {
+			return Err("chat_session_id not set".into());
+		}
+
+		// langchain_rust passes action_input as a string, which may itself be JSON
+		let request = match &input {
+			Value::String(s) => serde_json::from_str::<Value>(s)
+				.ok()
+				.and_then(|v| v.get("request").and_then(|r| r.as_str()).map(String::from))
+				.unwrap_or_else(|| s.clone()),
+			_ => input
+				.get("request")
+				.and_then(|r| r.as_str())
+				.unwrap_or_default()
+				.to_string(),
+		};
+
+		let Some(mut itinerary) = self.load_itinerary().await? else {
+			return Ok(
+				"There is no itinerary in this chat to modify yet. Run the research pipeline to create one."
+					.to_string(),
+			);
+		};
+
+		let event_ids = itinerary.event_ids();
+		let destination = self
+			.context_store
+			.read()
+			.await
+			.get(&chat_id)
+			.and_then(|c| c.trip_context.destination.clone());
+
+		// Names of the events in the itinerary, for the prompt
+		let names: std::collections::HashMap<i32, (String, Option<String>)> = sqlx::query!(
+			r#"SELECT id, event_name, event_type FROM events WHERE id = ANY($1)"#,
+			&event_ids
+		)
+		.fetch_all(&self.pool)
+		.await?
+		.into_iter()
+		.map(|row| (row.id, (row.event_name, row.event_type)))
+		.collect();
+
+		// Events in the same cities as the itinerary, or the destination, that aren't in it yet
+		let candidates = sqlx::query!(
+			r#"
+			SELECT id, event_name, event_type, city
+			FROM events
+			WHERE (user_created = FALSE OR account_id = $2)
+				AND archived = FALSE
+				AND NOT (id = ANY($1))
+				AND (
+					city IN (SELECT city FROM events WHERE id = ANY($1))
+					OR ($3::TEXT IS NOT NULL AND city ILIKE $3)
+				)
+			ORDER BY id
+			LIMIT $4
+			"#,
+			&event_ids,
+			self.user_id,
+			destination,
+			CANDIDATE_EVENT_LIMIT
+		)
+		.fetch_all(&self.pool)
+		.await?;
+
+		let describe = |id: &i32| match names.get(id) {
+			Some((name, Some(event_type))) => format!("[{id}] {name} ({event_type})"),
+			Some((name, None)) => format!("[{id}] {name}"),
+			None => format!("[{id}]"),
+		};
+		let mut itinerary_lines = Vec::new();
+		for day in &itinerary.days {
+			for (time_of_day, block) in [
+				("morning", &day.morning),
+				("afternoon", &day.afternoon),
+				("evening", &day.evening),
+			] {
+				for id in block {
+					itinerary_lines.push(format!("{} {}: {}", day.date, time_of_day, describe(id)));
+				}
+			}
+		}
+		for id in &itinerary.unassigned {
+			itinerary_lines.push(format!("unassigned: {}", describe(id)));
+		}
+		let candidate_lines: Vec<String> = candidates
+			.iter()
+			.map(|c| {
+				format!(
+					"[{}] {} ({}, {})",
+					c.id,
+					c.event_name,
+					c.event_type.as_deref().unwrap_or("event"),
+					c.city.as_deref().unwrap_or("unknown city")
+				)
+			})
+			.collect();
+
+		let prompt = include_str!("../prompts/modify_itinerary.md")
+			.replace(
+				"{{TRIP}}",
+				&format!(
+					"{} from {} to {}",
+					itinerary.title, itinerary.start_date, itinerary.end_date
+				),
+			)
+			.replace("{{ITINERARY}}", &itinerary_lines.join("\n"))
+			.replace("{{CANDIDATES}}", &candidate_lines.join("\n"))
+			.replace("{{REQUEST}}", &request);
+
+		let response = self
+			.llm
+			.invoke(&prompt)
+			.await
+			.map_err(|e| format!("LLM error: {}", e))?;
+		let cleaned = response
+			.trim()
+			.trim_start_matches("```json")
+			.trim_start_matches("```")
+			.trim_end_matches("```")
+			.trim();
+		debug!(
+			target: "orchestrator_tool",
+			tool = "modify_itinerary",
+			response = %cleaned,
+			"LLM itinerary diff"
+		);
+		let diff: ItineraryDiff = serde_json::from_str(cleaned)
+			.map_err(|e| format!("Failed to parse itinerary changes: {}", e))?;
+
+		let addable: HashSet<i32> = candidates.iter().map(|c| c.id).collect();
+		itinerary
+			.apply(&diff, &addable)
+			.map_err(|e| format!("Invalid itinerary changes: {}", e))?;
+
+		// respond_to_user saves whatever is active
+		if let Some(context_data) = self.context_store.write().await.get_mut(&chat_id) {
+			context_data.active_itinerary = Some(itinerary.to_json());
+		}
+
+		let result = format!(
+			"Itinerary modified: {} added, {} removed, {} moved. Call respond_to_user to send it.",
+			diff.add.len(),
+			diff.remove.len(),
+			diff.moves.len()
+		);
+
+		info!(
+			target: "orchestrator_tool",
+			tool = "modify_itinerary",
+			chat_id = chat_id,
+			elapsed_ms = start_time.elapsed().as_millis() as u64,
+			added = diff.add.len(),
+			removed = diff.remove.len(),
+			moved = diff.moves.len(),
+			"Tool completed"
+		);
+
+		track_tool_execution(
+			&self.context_store,
+			chat_id,
+			"modify_itinerary",
+			&input_clone,
+			&result,
+		)
+		.await?;
+
+		Ok(result)
+	}
+}
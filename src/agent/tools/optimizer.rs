@@ -8,15 +8,32 @@
  */
 
 use async_trait::async_trait;
+use chrono::{NaiveDate, NaiveDateTime, Timelike};
 use langchain_rust::{language_models::llm::LLM, tools::Tool};
 use serde_json::{Value, json};
 use sqlx::PgPool;
-use std::sync::atomic::{AtomicI32, Ordering};
-use std::{error::Error, sync::Arc, time::Instant};
+use std::{
+	cmp::Ordering,
+	collections::{HashMap, HashSet},
+	error::Error,
+	sync::Arc,
+	time::Instant,
+};
 use tracing::{debug, info, warn};
 
-use crate::agent::models::event::Event;
-use crate::sql_models::LlmProgress;
+use crate::agent::models::context::TripContext;
+use crate::agent::models::event::{Event, SharedEventCache};
+use crate::agent::tools::budget::enforce_daily_budget;
+use crate::agent::tools::dedup::{find_duplicates, remove_duplicate_pois};
+use crate::agent::tools::hours::{OpenStatus, is_open};
+use crate::agent::tools::meals::{is_meal_venue, select_meal};
+use crate::agent::tools::modify::{PlannedDay, PlannedItinerary};
+use crate::agent::tools::weather::{
+	Forecast, SharedWeatherProvider, adjust_for_weather, weather_provider_from_env,
+};
+use crate::db::events::fetch_events_by_ids;
+use crate::global::{AVG_PRICE_PER_LEVEL, DEFAULT_LANGUAGE, FAVORITE_SCORE_FACTOR};
+use crate::sql_models::{LlmProgress, TimeOfDay};
 
 /// Main tool that orchestrates the full optimization workflow.
 /// This tool:
@@ -31,19 +48,25 @@ use crate::sql_models::LlmProgress;
 struct OptimizeItineraryTool {
 	llm: Arc<dyn LLM + Send + Sync>,
 	db: PgPool,
-	chat_session_id: Arc<AtomicI32>,
+	event_cache: SharedEventCache,
+	chat_session_id: i32,
+	weather: SharedWeatherProvider,
 }
 
 impl OptimizeItineraryTool {
 	pub fn new(
 		llm: Arc<dyn LLM + Send + Sync>,
 		db: PgPool,
-		chat_session_id: Arc<AtomicI32>,
+		event_cache: SharedEventCache,
+		chat_session_id: i32,
+		weather: SharedWeatherProvider,
 	) -> Self {
 		Self {
 			llm,
 			db,
+			event_cache,
 			chat_session_id,
+			weather,
 		}
 	}
 }
@@ -105,7 +128,7 @@ impl Tool for OptimizeItineraryTool {
 		// written by the orchestrator after research/constraint stages.
 		// Fallback: whatever the agent passed in filtered_event_ids, to preserve
 		// backward compatibility in tests/legacy flows.
-		let chat_id = self.chat_session_id.load(Ordering::Relaxed);
+		let chat_id = self.chat_session_id;
 		let mut event_ids: Vec<i32> = Vec::new();
 
 		if chat_id > 0 {
@@ -194,83 +217,10 @@ impl Tool for OptimizeItineraryTool {
 		info!(
 			target: "optimize_tools",
 			event_count = event_ids.len(),
-			"Fetching events from database"
+			"Fetching events"
 		);
 
-		// Fetch all events from database
-		let rows = sqlx::query!(
-			r#"
-			SELECT
-				id,
-				event_name,
-				event_description,
-				street_address,
-				city,
-				country,
-				postal_code,
-				lat,
-				lng,
-				event_type,
-				hard_start,
-				hard_end,
-				timezone,
-				wheelchair_accessible_parking,
-				wheelchair_accessible_entrance,
-				wheelchair_accessible_restroom,
-				wheelchair_accessible_seating,
-				serves_vegetarian_food,
-				price_level,
-				utc_offset_minutes,
-				types,
-				weekday_descriptions,
-				secondary_hours_type,
-				next_open_time,
-				next_close_time,
-				open_now,
-				periods as "periods!: Vec<crate::sql_models::Period>",
-				special_days
-			FROM events
-			WHERE id = ANY($1)
-			"#,
-			&event_ids
-		)
-		.fetch_all(&self.db)
-		.await?;
-
-		let events: Vec<Event> = rows
-			.into_iter()
-			.map(|row| Event {
-				id: row.id,
-				event_name: row.event_name,
-				event_description: row.event_description,
-				street_address: row.street_address,
-				city: row.city,
-				country: row.country,
-				postal_code: row.postal_code,
-				lat: row.lat,
-				lng: row.lng,
-				event_type: row.event_type,
-				hard_start: row.hard_start,
-				hard_end: row.hard_end,
-				timezone: row.timezone,
-				wheelchair_accessible_parking: row.wheelchair_accessible_parking,
-				wheelchair_accessible_entrance: row.wheelchair_accessible_entrance,
-				wheelchair_accessible_restroom: row.wheelchair_accessible_restroom,
-				wheelchair_accessible_seating: row.wheelchair_accessible_seating,
-				serves_vegetarian_food: row.serves_vegetarian_food,
-				price_level: row.price_level,
-				utc_offset_minutes: row.utc_offset_minutes,
-				types: row.types,
-				weekday_descriptions: row.weekday_descriptions,
-				secondary_hours_type: row.secondary_hours_type,
-				next_open_time: row.next_open_time,
-				next_close_time: row.next_close_time,
-				open_now: row.open_now,
-				periods: row.periods,
-				special_days: row.special_days,
-				block_index: None,
-			})
-			.collect();
+		let events = fetch_events(&self.db, &self.event_cache, &event_ids).await?;
 
 		if events.is_empty() {
 			crate::tool_trace!(
@@ -307,6 +257,14 @@ impl Tool for OptimizeItineraryTool {
 				.unwrap_or(json!({}));
 		}
 
+		// Falls back to the stored profile's language if the agent dropped it from the input
+		let preferred_language = parsed_input
+			.get("preferred_language")
+			.or_else(|| user_profile_val.get("preferred_language"))
+			.and_then(Value::as_str)
+			.unwrap_or(DEFAULT_LANGUAGE)
+			.to_string();
+
 		// STEP 1: Rank POIs by preference
 		// Update progress to show that we're ranking events based on preferences.
 		if chat_id > 0 {
@@ -336,6 +294,7 @@ impl Tool for OptimizeItineraryTool {
 
 		let rank_tool = RankPOIsByPreferenceTool {
 			llm: self.llm.clone(),
+			db: self.db.clone(),
 		};
 		let ranked_result = rank_tool.run(rank_input).await?;
 
@@ -418,6 +377,26 @@ impl Tool for OptimizeItineraryTool {
 			details: format!("rankings=[{}]", rankings.join(", "))
 		);
 
+		// Copies of the same venue would be drafted into the itinerary twice
+		let event_by_id: HashMap<i32, &Event> = events.iter().map(|e| (e.id, e)).collect();
+		let ranked_events: Vec<&Event> = ranked_pois
+			.iter()
+			.filter_map(|poi| poi.get("id").and_then(Value::as_i64))
+			.filter_map(|id| event_by_id.get(&(id as i32)).copied())
+			.collect();
+		let duplicates = find_duplicates(&ranked_events);
+		if !duplicates.is_empty() {
+			info!(
+				target: "optimize_tools",
+				duplicates = ?duplicates
+					.iter()
+					.map(|d| format!("{} (kept {})", d.removed, d.kept))
+					.collect::<Vec<_>>(),
+				"Removed duplicate events before drafting"
+			);
+			ranked_pois = remove_duplicate_pois(ranked_pois, &duplicates);
+		}
+
 		// STEP 2: Draft the itinerary
 		// Update progress to indicate we're drafting the itinerary structure.
 		if chat_id > 0 {
@@ -442,7 +421,8 @@ impl Tool for OptimizeItineraryTool {
 		let draft_input = json!({
 			"pois": ranked_pois,
 			"diversity_factor": 0.7,
-			"trip_context": trip_context_val
+			"trip_context": trip_context_val,
+			"preferred_language": preferred_language
 		});
 
 		let draft_tool = DraftItineraryTool {
@@ -464,9 +444,10 @@ impl Tool for OptimizeItineraryTool {
 			})
 			.unwrap_or(draft_result.trim());
 
-		// Try standard JSON parsing first
-		let mut itinerary: Value = match serde_json::from_str(cleaned_draft) {
-			Ok(value) => value,
+		// Try standard JSON parsing first, then JSON5 which is more lenient
+		// (handles trailing commas, comments, etc.)
+		let drafted = match serde_json::from_str::<Value>(cleaned_draft) {
+			Ok(value) => Ok(value),
 			Err(e) => {
 				warn!(
 					target: "optimize_tools",
@@ -474,38 +455,55 @@ impl Tool for OptimizeItineraryTool {
 					response_len = draft_result.len(),
 					"Failed to parse draft itinerary with standard JSON parser, trying JSON5"
 				);
-
-				// Try JSON5 parser which is more lenient (handles trailing commas, comments, etc.)
-				match json5::from_str(cleaned_draft) {
-					Ok(value) => {
+				json5::from_str::<Value>(cleaned_draft)
+					.inspect(|_| {
 						info!(
 							target: "optimize_tools",
 							"Successfully parsed draft itinerary using JSON5 (lenient parser)"
-						);
-						value
-					}
-					Err(json5_err) => {
-						// Both parsers failed - log detailed error and return error
-						let preview = draft_result.chars().take(500).collect::<String>();
-
-						crate::tool_trace!(
-							agent: "optimize",
-							tool: "draft_itinerary",
-							status: "error",
-							details: format!("JSON parse failed: {}", e)
-						);
+						)
+					})
+					.map_err(|json5_err| format!("JSON error: {e}. JSON5 error: {json5_err}"))
+			}
+		};
 
-						return Err(format!(
-						"Failed to parse draft itinerary. Standard JSON error: {}. JSON5 error: {}. Response preview: {}",
-						e, json5_err, preview
-					).into());
-					}
-				}
+		// An unusable draft falls back to scheduling the ranked POIs without the LLM
+		let (mut itinerary, draft_source) = match drafted {
+			Ok(value) if value.get("event_days").is_some_and(|v| v.is_array()) => (value, "llm"),
+			drafted => {
+				let reason = match drafted {
+					Ok(_) => String::from("draft has no event_days array"),
+					Err(e) => e,
+				};
+				let preview = draft_result.chars().take(500).collect::<String>();
+				warn!(
+					target: "optimize_tools",
+					error = %reason,
+					response_preview = %preview,
+					"Draft itinerary unusable, building fallback itinerary"
+				);
+				crate::tool_trace!(
+					agent: "optimize",
+					tool: "draft_itinerary",
+					status: "error",
+					details: format!("draft unusable, using fallback: {}", reason)
+				);
+
+				let fallback = fallback_itinerary(&ranked_pois, &trip_context_val).map_err(|e| {
+					format!(
+						"Failed to parse draft itinerary ({reason}) and to build a fallback: {e}. Response preview: {preview}"
+					)
+				})?;
+				(fallback.to_json(), "fallback")
 			}
 		};
 
+		info!(
+			target: "optimize_tools",
+			draft_source = draft_source,
+			"Itinerary draft ready"
+		);
+
 		// Build schedule summary and a type map we can use for diversity enforcement.
-		use std::collections::HashMap;
 		let name_by_id: HashMap<i32, String> = events
 			.iter()
 			.map(|e| (e.id, e.event_name.clone()))
@@ -686,6 +684,236 @@ impl Tool for OptimizeItineraryTool {
 			}
 		}
 
+		// STEP 2.6: Only keep events in blocks their venue is open for.
+		//
+		// The draft prompt doesn't know opening hours well enough to avoid e.g.
+		// museums in the evening, so events whose venue is closed during their
+		// block move to unassigned_events.
+		let mut closed_ids: Vec<i32> = Vec::new();
+		if let Some(days) = itinerary
+			.get_mut("event_days")
+			.and_then(|v| v.as_array_mut())
+		{
+			for day in days.iter_mut() {
+				let Some(date) = day
+					.get("date")
+					.and_then(|d| d.as_str())
+					.and_then(|d| NaiveDate::parse_from_str(d, "%Y-%m-%d").ok())
+				else {
+					continue;
+				};
+				for (block, time_of_day) in [
+					("morning_events", TimeOfDay::Morning),
+					("afternoon_events", TimeOfDay::Afternoon),
+					("evening_events", TimeOfDay::Evening),
+				] {
+					if let Some(events_arr) = day.get_mut(block).and_then(|v| v.as_array_mut()) {
+						events_arr.retain(|ev| {
+							let event = ev
+								.get("id")
+								.and_then(|v| v.as_i64())
+								.and_then(|id| event_by_id.get(&(id as i32)));
+							match event {
+								Some(event)
+									if is_open(event, date, time_of_day.clone())
+										== OpenStatus::Closed =>
+								{
+									closed_ids.push(event.id);
+									false
+								}
+								_ => true,
+							}
+						});
+					}
+				}
+			}
+		}
+		if !closed_ids.is_empty() {
+			info!(
+				target: "optimize_tools",
+				closed_count = closed_ids.len(),
+				"Moved events scheduled while their venue is closed to unassigned"
+			);
+			if !itinerary
+				.get("unassigned_events")
+				.is_some_and(|v| v.is_array())
+			{
+				itinerary["unassigned_events"] = json!([]);
+			}
+			if let Some(unassigned) = itinerary["unassigned_events"].as_array_mut() {
+				unassigned.extend(closed_ids.iter().map(|id| json!({ "id": id })));
+			}
+		}
+
+		// STEP 2.7: Reserve lunch and dinner.
+		//
+		// Each day gets a restaurant near its other events at the start of the
+		// afternoon and evening blocks, unless one is already scheduled there.
+		let food_allergies = user_profile_val
+			.get("food_allergies")
+			.and_then(|v| v.as_str())
+			.unwrap_or_default();
+		let restaurants: Vec<&Event> = ranked_pois
+			.iter()
+			.filter_map(|poi| poi.get("id").and_then(|v| v.as_i64()))
+			.filter_map(|id| event_by_id.get(&(id as i32)).copied())
+			.filter(|event| is_meal_venue(event))
+			.collect();
+		let block_ids = |day: &Value, block: &str| -> Vec<i32> {
+			day.get(block)
+				.and_then(|v| v.as_array())
+				.map(|arr| {
+					arr.iter()
+						.filter_map(|ev| ev.get("id").and_then(|v| v.as_i64()))
+						.map(|id| id as i32)
+						.collect()
+				})
+				.unwrap_or_default()
+		};
+		let mut meal_ids: HashSet<i32> = HashSet::new();
+		if !restaurants.is_empty() {
+			if let Some(days) = itinerary
+				.get_mut("event_days")
+				.and_then(|v| v.as_array_mut())
+			{
+				let mut taken: HashSet<i32> = days
+					.iter()
+					.flat_map(|day| {
+						["morning_events", "afternoon_events", "evening_events"]
+							.into_iter()
+							.flat_map(|block| block_ids(day, block))
+					})
+					.collect();
+				for day in days.iter_mut() {
+					let Some(date) = day
+						.get("date")
+						.and_then(|d| d.as_str())
+						.and_then(|d| NaiveDate::parse_from_str(d, "%Y-%m-%d").ok())
+					else {
+						continue;
+					};
+					let scheduled: Vec<&Event> =
+						["morning_events", "afternoon_events", "evening_events"]
+							.into_iter()
+							.flat_map(|block| block_ids(day, block))
+							.filter_map(|id| event_by_id.get(&id).copied())
+							.collect();
+					for (block, time_of_day, meal) in [
+						("afternoon_events", TimeOfDay::Afternoon, "lunch"),
+						("evening_events", TimeOfDay::Evening, "dinner"),
+					] {
+						let has_meal = block_ids(day, block)
+							.iter()
+							.filter_map(|id| event_by_id.get(id))
+							.any(|event| is_meal_venue(event));
+						if has_meal {
+							continue;
+						}
+						match select_meal(
+							&scheduled,
+							&restaurants,
+							food_allergies,
+							&taken,
+							date,
+							time_of_day,
+						) {
+							Some(id) => {
+								taken.insert(id);
+								meal_ids.insert(id);
+								if !day.get(block).is_some_and(|v| v.is_array()) {
+									day[block] = json!([]);
+								}
+								if let Some(events_arr) = day[block].as_array_mut() {
+									events_arr.insert(0, json!(event_by_id[&id]));
+								}
+							}
+							None => warn!(
+								target: "optimize_tools",
+								date = %date,
+								meal = meal,
+								"No suitable restaurant near the day's events"
+							),
+						}
+					}
+				}
+			}
+		}
+		// Restaurants picked for meals are no longer unassigned
+		if let Some(unassigned) = itinerary
+			.get_mut("unassigned_events")
+			.and_then(|v| v.as_array_mut())
+		{
+			unassigned.retain(|ev| {
+				!ev.get("id")
+					.and_then(|v| v.as_i64())
+					.is_some_and(|id| meal_ids.contains(&(id as i32)))
+			});
+		}
+
+		// STEP 2.8: Move outdoor events off rainy days.
+		//
+		// Each day's forecast is for the middle of its events. The changes are
+		// listed in `weather_notes` so respond_to_user can tell the user why.
+		if let Some(draft) = draft_plan(&itinerary) {
+			let mut forecasts: HashMap<NaiveDate, Forecast> = HashMap::new();
+			for day in &draft.days {
+				let coords: Vec<(f64, f64)> = day
+					.morning
+					.iter()
+					.chain(&day.afternoon)
+					.chain(&day.evening)
+					.filter_map(|id| event_by_id.get(id))
+					.filter_map(|event| event.lat.zip(event.lng))
+					.collect();
+				if !coords.is_empty() {
+					let lat = coords.iter().map(|c| c.0).sum::<f64>() / coords.len() as f64;
+					let lng = coords.iter().map(|c| c.1).sum::<f64>() / coords.len() as f64;
+					forecasts.insert(
+						day.date,
+						self.weather.get_forecast(lat, lng, day.date).await,
+					);
+				}
+			}
+			let ranked_ids: Vec<i32> = ranked_pois
+				.iter()
+				.filter_map(|poi| poi.get("id").and_then(|v| v.as_i64()))
+				.map(|id| id as i32)
+				.collect();
+			let (adjusted, weather_notes) =
+				adjust_for_weather(&draft, &forecasts, &event_by_id, &ranked_ids);
+			if !weather_notes.is_empty() {
+				info!(
+					target: "optimize_tools",
+					changes = weather_notes.len(),
+					"Moved outdoor events off rainy days"
+				);
+				apply_plan(&mut itinerary, &adjusted, &event_by_id);
+				itinerary["weather_notes"] = json!(weather_notes);
+			}
+		}
+
+		// STEP 2.9: Keep each day within the daily budget.
+		//
+		// Days whose events' estimated cost is over the budget lose their cheapest
+		// paid events to unassigned_events, explained in `budget_notes`.
+		let mut trip_context = TripContext::default();
+		trip_context.merge_extracted(&trip_context_val);
+		if let (Some(daily_budget), Some(draft)) = (
+			trip_context.per_person_daily_budget(),
+			draft_plan(&itinerary),
+		) {
+			let (adjusted, budget_notes) = enforce_daily_budget(&draft, &event_by_id, daily_budget);
+			if !budget_notes.is_empty() {
+				info!(
+					target: "optimize_tools",
+					days_over_budget = budget_notes.len(),
+					"Unassigned events from days over the daily budget"
+				);
+				apply_plan(&mut itinerary, &adjusted, &event_by_id);
+				itinerary["budget_notes"] = json!(budget_notes);
+			}
+		}
+
 		// STEP 3: Optimize routes for each day
 		// Update progress to show we're optimizing the itinerary routes.
 		if chat_id > 0 {
@@ -917,13 +1145,19 @@ impl Tool for OptimizeItineraryTool {
 			agent: "optimize",
 			tool: "optimize_itinerary",
 			status: "success",
-			details: format!("elapsed_ms={}, events_processed={}", elapsed.as_millis(), events.len())
+			details: format!(
+				"elapsed_ms={}, events_processed={}, draft_source={}",
+				elapsed.as_millis(),
+				events.len(),
+				draft_source
+			)
 		);
 
 		info!(
 			target: "optimize_tools",
 			elapsed_ms = elapsed.as_millis() as u64,
 			events_processed = events.len(),
+			draft_source = draft_source,
 			"Optimization workflow completed successfully"
 		);
 
@@ -931,6 +1165,262 @@ impl Tool for OptimizeItineraryTool {
 	}
 }
 
+/// Events `fallback_itinerary` puts in each morning, afternoon or evening block
+const FALLBACK_EVENTS_PER_BLOCK: usize = 2;
+
+/// Time block a fixed time event belongs in
+fn time_of_day_at(time: NaiveDateTime) -> TimeOfDay {
+	match time.hour() {
+		0..12 => TimeOfDay::Morning,
+		12..17 => TimeOfDay::Afternoon,
+		_ => TimeOfDay::Evening,
+	}
+}
+
+/// Splits `total` over `capacities` as evenly as they allow, in order
+fn spread(mut total: usize, capacities: &[usize]) -> Vec<usize> {
+	let mut later: usize = capacities.iter().sum();
+	capacities
+		.iter()
+		.enumerate()
+		.map(|(i, &capacity)| {
+			later -= capacity;
+			let share = total
+				.div_ceil(capacities.len() - i)
+				.max(total.saturating_sub(later))
+				.min(capacity);
+			total -= share;
+			share
+		})
+		.collect()
+}
+
+/// Schedules `ranked_pois` (best first) over the trip without the LLM, for when the drafted
+/// itinerary can't be used.
+///
+/// * Events with a `hard_start` (or only a `hard_end`) go in the block of that time, or are
+///   unassigned when it's outside the trip
+/// * The best ranked remaining events fill up to `FALLBACK_EVENTS_PER_BLOCK` per block. They are
+///   ordered into a tour with `compute_route` and the tour is cut into one stretch per day, so
+///   each day stays in one area. Events without coordinates go at the end of the tour.
+/// * Everything that doesn't fit is unassigned
+pub fn fallback_itinerary(
+	ranked_pois: &[Value],
+	trip_context: &Value,
+) -> Result<PlannedItinerary, String> {
+	use super::tsp::{EndpointMode, Pt, compute_route};
+
+	let date = |key: &str| {
+		trip_context
+			.get(key)
+			.and_then(|v| v.as_str())
+			.and_then(|date| NaiveDate::parse_from_str(date, "%Y-%m-%d").ok())
+			.ok_or_else(|| format!("trip_context has no valid {key}"))
+	};
+	let start_date = date("start_date")?;
+	let end_date = date("end_date")?;
+	if end_date < start_date {
+		return Err(String::from("trip_context end_date is before start_date"));
+	}
+
+	let mut days: Vec<PlannedDay> = start_date
+		.iter_days()
+		.take_while(|date| *date <= end_date)
+		.map(|date| PlannedDay {
+			date,
+			morning: vec![],
+			afternoon: vec![],
+			evening: vec![],
+		})
+		.collect();
+	let mut unassigned = Vec::new();
+
+	let time = |poi: &Value, key: &str| {
+		poi.get(key)
+			.and_then(|v| v.as_str())
+			.and_then(|time| time.parse::<NaiveDateTime>().ok())
+	};
+	let mut seen = HashSet::new();
+	let mut pinned = Vec::new();
+	let mut flexible = Vec::new();
+	for poi in ranked_pois {
+		let Some(id) = poi.get("id").and_then(|v| v.as_i64()).map(|id| id as i32) else {
+			continue;
+		};
+		if !seen.insert(id) {
+			continue;
+		}
+		match time(poi, "hard_start").or_else(|| time(poi, "hard_end")) {
+			Some(at) => pinned.push((id, at)),
+			None => {
+				let lat = poi.get("lat").and_then(|v| v.as_f64());
+				let lng = poi.get("lng").and_then(|v| v.as_f64());
+				flexible.push((id, lat.zip(lng)));
+			}
+		}
+	}
+
+	// Fixed time events first, in time order, so the flexible ones fill around them
+	pinned.sort_by_key(|&(_, at)| at);
+	for (id, at) in pinned {
+		let day = days.iter_mut().find(|day| day.date == at.date());
+		let block = day.map(|day| match time_of_day_at(at) {
+			TimeOfDay::Morning => &mut day.morning,
+			TimeOfDay::Afternoon => &mut day.afternoon,
+			TimeOfDay::Evening => &mut day.evening,
+		});
+		match block {
+			Some(block) if block.len() < FALLBACK_EVENTS_PER_BLOCK => block.push(id),
+			_ => unassigned.push(id),
+		}
+	}
+
+	let free = |block: &Vec<i32>| FALLBACK_EVENTS_PER_BLOCK.saturating_sub(block.len());
+	let day_free: Vec<usize> = days
+		.iter()
+		.map(|day| free(&day.morning) + free(&day.afternoon) + free(&day.evening))
+		.collect();
+	let scheduled = flexible.len().min(day_free.iter().sum());
+	unassigned.extend(flexible[scheduled..].iter().map(|&(id, _)| id));
+	let flexible = &flexible[..scheduled];
+
+	// Tour through the located events starting from the best ranked one,
+	// without the walk back to it
+	let located: Vec<(i32, Pt)> = flexible
+		.iter()
+		.filter_map(|&(id, coords)| coords.map(|(lat, lng)| (id, Pt { id: None, lat, lng })))
+		.collect();
+	let points: Vec<Pt> = located.iter().map(|&(_, pt)| pt).collect();
+	let mut route = compute_route(&points, EndpointMode::Circle);
+	route.pop();
+	let mut tour: Vec<i32> = route.into_iter().map(|i| located[i].0).collect();
+	tour.extend(
+		flexible
+			.iter()
+			.filter(|(_, c)| c.is_none())
+			.map(|&(id, _)| id),
+	);
+
+	// Spread the tour evenly over the days, then each day's share over its blocks
+	let mut tour = tour.into_iter();
+	for (day, share) in days.iter_mut().zip(spread(scheduled, &day_free)) {
+		let block_free = [free(&day.morning), free(&day.afternoon), free(&day.evening)];
+		let blocks = [&mut day.morning, &mut day.afternoon, &mut day.evening];
+		for (block, count) in blocks.into_iter().zip(spread(share, &block_free)) {
+			block.extend(tour.by_ref().take(count));
+		}
+	}
+
+	Ok(PlannedItinerary {
+		title: trip_context
+			.get("destination")
+			.and_then(|v| v.as_str())
+			.unwrap_or("Trip Itinerary")
+			.to_string(),
+		start_date,
+		end_date,
+		days,
+		unassigned,
+	})
+}
+
+/// Event ids in the `key` array of a drafted day or itinerary
+fn draft_ids(value: &Value, key: &str) -> Vec<i32> {
+	value
+		.get(key)
+		.and_then(|v| v.as_array())
+		.map(|arr| {
+			arr.iter()
+				.filter_map(|ev| ev.get("id").and_then(|v| v.as_i64()))
+				.map(|id| id as i32)
+				.collect()
+		})
+		.unwrap_or_default()
+}
+
+/// The event ids of a drafted itinerary, `None` when no day has a valid date.
+/// Days without one are left out.
+fn draft_plan(itinerary: &Value) -> Option<PlannedItinerary> {
+	let days: Vec<PlannedDay> = itinerary
+		.get("event_days")
+		.and_then(|v| v.as_array())
+		.into_iter()
+		.flatten()
+		.filter_map(|day| {
+			let date = day
+				.get("date")
+				.and_then(|d| d.as_str())
+				.and_then(|d| NaiveDate::parse_from_str(d, "%Y-%m-%d").ok())?;
+			Some(PlannedDay {
+				date,
+				morning: draft_ids(day, "morning_events"),
+				afternoon: draft_ids(day, "afternoon_events"),
+				evening: draft_ids(day, "evening_events"),
+			})
+		})
+		.collect();
+	Some(PlannedItinerary {
+		title: String::new(),
+		start_date: days.first()?.date,
+		end_date: days.last()?.date,
+		unassigned: draft_ids(itinerary, "unassigned_events"),
+		days,
+	})
+}
+
+/// Writes the blocks and unassigned events of `plan` back into the drafted `itinerary` as
+/// full events from `event_by_id`. Events without an id stay at the end of their block.
+fn apply_plan(itinerary: &mut Value, plan: &PlannedItinerary, event_by_id: &HashMap<i32, &Event>) {
+	let to_json = |ids: &[i32], synthetic: Vec<Value>| -> Value {
+		ids.iter()
+			.map(|id| match event_by_id.get(id) {
+				Some(event) => json!(event),
+				None => json!({ "id": id }),
+			})
+			.chain(synthetic)
+			.collect()
+	};
+	let synthetic = |events: Option<&Value>| -> Vec<Value> {
+		events
+			.and_then(|v| v.as_array())
+			.map(|arr| {
+				arr.iter()
+					.filter(|ev| ev.get("id").and_then(|v| v.as_i64()).is_none())
+					.cloned()
+					.collect()
+			})
+			.unwrap_or_default()
+	};
+	let planned_by_date: HashMap<NaiveDate, &PlannedDay> =
+		plan.days.iter().map(|day| (day.date, day)).collect();
+	if let Some(days) = itinerary
+		.get_mut("event_days")
+		.and_then(|v| v.as_array_mut())
+	{
+		for day in days.iter_mut() {
+			let Some(planned) = day
+				.get("date")
+				.and_then(|d| d.as_str())
+				.and_then(|d| NaiveDate::parse_from_str(d, "%Y-%m-%d").ok())
+				.and_then(|date| planned_by_date.get(&date))
+			else {
+				continue;
+			};
+			for (block, ids) in [
+				("morning_events", &planned.morning),
+				("afternoon_events", &planned.afternoon),
+				("evening_events", &planned.evening),
+			] {
+				day[block] = to_json(ids, synthetic(day.get(block)));
+			}
+		}
+	}
+	itinerary["unassigned_events"] = to_json(
+		&plan.unassigned,
+		synthetic(itinerary.get("unassigned_events")),
+	);
+}
+
 /// Tool that ranks Points of Interest based on user preferences and constraints
 ///
 /// This tool evaluates and ranks POIs considering user profile factors such as:
@@ -939,9 +1429,160 @@ impl Tool for OptimizeItineraryTool {
 /// - Dietary restrictions/allergies
 /// - Accessibility needs/disabilities
 /// - Personal interests and preferences
+/// - Ratings from other users' reviews
+/// - Events the user favorited
+/// - How often the event was viewed, to break ties
 #[derive(Clone)]
 struct RankPOIsByPreferenceTool {
 	llm: Arc<dyn LLM + Send + Sync>,
+	db: PgPool,
+}
+
+/// Events with the given ids, taken from `cache` when they're in it and from the database
+/// otherwise, then added to `cache`.
+/// * Ids without an event are skipped and duplicates are only returned once
+/// * Events are sorted by id, wherever they came from
+pub async fn fetch_events(
+	db: &PgPool,
+	cache: &SharedEventCache,
+	event_ids: &[i32],
+) -> Result<Vec<Event>, sqlx::Error> {
+	let mut events = Vec::with_capacity(event_ids.len());
+	let mut missing_ids = Vec::new();
+	{
+		let mut cache = cache.lock().unwrap();
+		let mut seen = HashSet::new();
+		for &id in event_ids.iter().filter(|&&id| seen.insert(id)) {
+			match cache.get(&id) {
+				Some(event) => events.push(event.clone()),
+				None => missing_ids.push(id),
+			}
+		}
+	}
+	debug!(
+		target: "optimize_tools",
+		cached = events.len(),
+		missing = missing_ids.len(),
+		"Looked up events in the event cache"
+	);
+	if missing_ids.is_empty() {
+		events.sort_by_key(|event| event.id);
+		return Ok(events);
+	}
+
+	let fetched: Vec<Event> = fetch_events_by_ids(db, &missing_ids)
+		.await?
+		.into_iter()
+		.map(Event::from)
+		.collect();
+
+	let mut cache = cache.lock().unwrap();
+	for event in fetched {
+		cache.put(event.id, event.clone());
+		events.push(event);
+	}
+	drop(cache);
+	events.sort_by_key(|event| event.id);
+	Ok(events)
+}
+
+/// Adds `average_rating` and `review_count` from `event_reviews`, and the event's
+/// `view_count`, to each POI with an `id`.
+/// * `average_rating` is null for events without reviews
+async fn attach_review_ratings(pois: &mut [Value], db: &PgPool) -> Result<(), sqlx::Error> {
+	let ids: Vec<i32> = pois
+		.iter()
+		.filter_map(|poi| poi.get("id").and_then(|v| v.as_i64()))
+		.map(|id| id as i32)
+		.collect();
+	let ratings: std::collections::HashMap<i32, (Option<f64>, i64, i32)> = sqlx::query!(
+		r#"
+		SELECT
+			e.id,
+			AVG(r.rating)::FLOAT8 AS average_rating,
+			COUNT(r.id) AS "review_count!",
+			e.view_count
+		FROM events e
+		LEFT JOIN event_reviews r ON r.event_id = e.id
+		WHERE e.id = ANY($1)
+		GROUP BY e.id
+		"#,
+		&ids
+	)
+	.fetch_all(db)
+	.await?
+	.into_iter()
+	.map(|row| {
+		(
+			row.id,
+			(row.average_rating, row.review_count, row.view_count),
+		)
+	})
+	.collect();
+
+	for poi in pois.iter_mut() {
+		let rating = poi
+			.get("id")
+			.and_then(|v| v.as_i64())
+			.and_then(|id| ratings.get(&(id as i32)));
+		if let (Some(&(average_rating, review_count, view_count)), Some(obj)) =
+			(rating, poi.as_object_mut())
+		{
+			obj.insert("average_rating".to_string(), json!(average_rating));
+			obj.insert("review_count".to_string(), json!(review_count));
+			obj.insert("view_count".to_string(), json!(view_count));
+		}
+	}
+	Ok(())
+}
+
+/// Lists the profile's `interests` for the ranking prompt.
+fn rank_interests(profile: &Value) -> String {
+	let interests: Vec<&str> = profile
+		.get("interests")
+		.and_then(|v| v.as_array())
+		.map(|arr| arr.iter().filter_map(|v| v.as_str()).collect())
+		.unwrap_or_default();
+	if interests.is_empty() {
+		"None specified".to_string()
+	} else {
+		interests.join(", ")
+	}
+}
+
+/// Ids in the profile's `favorited_event_ids`
+fn favorite_event_ids(profile: &Value) -> HashSet<i64> {
+	profile
+		.get("favorited_event_ids")
+		.and_then(|v| v.as_array())
+		.map(|ids| ids.iter().filter_map(|id| id.as_i64()).collect())
+		.unwrap_or_default()
+}
+
+/// `score` scaled by `FAVORITE_SCORE_FACTOR` when the POI is one of the user's favorites.
+/// * Scores of POIs unsafe for the user (`9999` or more) are kept so they still rank last
+pub fn boost_favorite(score: f64, poi: &Value, favorites: &HashSet<i64>) -> f64 {
+	let favorited = poi
+		.get("id")
+		.and_then(|v| v.as_i64())
+		.is_some_and(|id| favorites.contains(&id));
+	if favorited && score < 9999.0 {
+		score * FAVORITE_SCORE_FACTOR
+	} else {
+		score
+	}
+}
+
+/// Orders scored POIs best first: lowest `score`, then most viewed on equal scores
+pub fn compare_scored(
+	(score_a, poi_a): (f64, &Value),
+	(score_b, poi_b): (f64, &Value),
+) -> Ordering {
+	let views = |poi: &Value| poi.get("view_count").and_then(|v| v.as_i64()).unwrap_or(0);
+	score_a
+		.partial_cmp(&score_b)
+		.unwrap_or(Ordering::Equal)
+		.then_with(|| views(poi_b).cmp(&views(poi_a)))
 }
 
 /// Tool that builds an itinerary from a list of events
@@ -1014,12 +1655,21 @@ impl Tool for RankPOIsByPreferenceTool {
 			"Starting POI ranking"
 		);
 
-		let pois = input["pois"]
+		let mut pois = input["pois"]
 			.as_array()
 			.ok_or("pois must be an array of objects")?
 			.clone();
 		let profile = input["user_profile"].clone();
 
+		// Ratings only nudge the scores, so rank without them rather than fail
+		if let Err(e) = attach_review_ratings(&mut pois, &self.db).await {
+			warn!(
+				target: "optimize_tools",
+				error = %e,
+				"Failed to fetch review ratings; ranking without them"
+			);
+		}
+
 		info!(
 			target: "optimize_tools",
 			pois_count = pois.len(),
@@ -1029,8 +1679,10 @@ impl Tool for RankPOIsByPreferenceTool {
 		// Build a compact prompt and score each POI independently in parallel.
 		// Each score task returns (index, score, poi_object). We then sort by score.
 		let profile_json = serde_json::to_string_pretty(&profile)?;
+		let interests = rank_interests(&profile);
 
-		let template = include_str!("../prompts/rank_pois_preference.md").to_string();
+		let template = include_str!("../prompts/rank_pois_preference.md")
+			.replace("{{USER_INTERESTS}}", &interests);
 
 		let score_tasks = pois.iter().cloned().enumerate().map(|(idx, poi)| {
 			let llm = Arc::clone(&self.llm);
@@ -1075,9 +1727,15 @@ impl Tool for RankPOIsByPreferenceTool {
 
 		let mut scored: Vec<(usize, f64, Value)> = futures::future::join_all(score_tasks).await;
 
-		// Filter out sentinel id = -1 as before and sort by score ascending.
+		// Favorited events rank ahead of equally good matches
+		let favorites = favorite_event_ids(&profile);
+		for (_, score, poi) in scored.iter_mut() {
+			*score = boost_favorite(*score, poi, &favorites);
+		}
+
+		// Filter out sentinel id = -1 as before and sort by score ascending, most viewed first on ties.
 		scored.retain(|(_, _, poi)| poi.get("id").and_then(|v| v.as_i64()) != Some(-1));
-		scored.sort_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(std::cmp::Ordering::Equal));
+		scored.sort_by(|a, b| compare_scored((a.1, &a.2), (b.1, &b.2)));
 
 		// Rebuild POIs with a "rank" field (integer) based on sorted order.
 		let mut ranked_pois: Vec<Value> = Vec::with_capacity(scored.len());
@@ -1135,6 +1793,11 @@ impl Tool for DraftItineraryTool {
 					"type": "number",
 					"description": "Factor controlling how much diversity to enforce (0.0 to 1.0)",
 					"default": 0.7
+				},
+				"preferred_language": {
+					"type": "string",
+					"description": "Language tag to write any text in, e.g. \"en\"",
+					"default": "en"
 				}
 			},
 			"required": ["pois"]
@@ -1164,6 +1827,9 @@ impl Tool for DraftItineraryTool {
 				.ok_or("diversity_factor must be a 64-bit floating point number")
 		});
 		let trip_context = input.get("trip_context").cloned().unwrap_or(json!({}));
+		let preferred_language = input["preferred_language"]
+			.as_str()
+			.unwrap_or(DEFAULT_LANGUAGE);
 
 		info!(
 			target: "optimize_tools",
@@ -1177,7 +1843,14 @@ impl Tool for DraftItineraryTool {
 			serde_json::to_string_pretty(&pois)?,
 			include_str!("../prompts/itinerary.ts"),
 			diversity_factor.unwrap_or(Ok(0.7))?,
-			serde_json::to_string_pretty(&trip_context)?
+			serde_json::to_string_pretty(&trip_context)?,
+			AVG_PRICE_PER_LEVEL
+				.iter()
+				.enumerate()
+				.map(|(level, cost)| format!("{level} = ${cost:.0}"))
+				.collect::<Vec<_>>()
+				.join(", "),
+			preferred_language
 		);
 
 		let response = self.llm.invoke(&prompt).await?;
@@ -1251,7 +1924,7 @@ impl Tool for OptimizeRouteTool {
 	}
 
 	async fn run(&self, input: Value) -> Result<String, Box<dyn Error>> {
-		use super::tsp::{EndpointMode, Pt, compute_route};
+		use super::tsp::{EndpointMode, Pt, compute_route, route_length};
 
 		let start_time = Instant::now();
 
@@ -1345,10 +2018,9 @@ impl Tool for OptimizeRouteTool {
 
 		pois.push(end);
 
-		pois = compute_route(pois.as_slice(), EndpointMode::Path)
-			.into_iter()
-			.map(|i| pois[i])
-			.collect();
+		let route = compute_route(pois.as_slice(), EndpointMode::Path);
+		let route_len = route_length(pois.as_slice(), route.as_slice());
+		pois = route.into_iter().map(|i| pois[i]).collect();
 
 		let elapsed = start_time.elapsed();
 
@@ -1362,6 +2034,7 @@ impl Tool for OptimizeRouteTool {
 		info!(
 			target: "optimize_tools",
 			elapsed_ms = elapsed.as_millis() as u64,
+			route_len = route_len,
 			"Route optimization completed"
 		);
 
@@ -1373,11 +2046,14 @@ impl Tool for OptimizeRouteTool {
 pub fn optimizer_tools(
 	llm: Arc<dyn LLM + Send + Sync>,
 	db: PgPool,
-	chat_session_id: Arc<AtomicI32>,
+	event_cache: SharedEventCache,
+	chat_session_id: i32,
 ) -> Vec<Arc<dyn Tool>> {
 	vec![Arc::new(OptimizeItineraryTool::new(
 		llm.clone(),
 		db.clone(),
+		event_cache,
 		chat_session_id,
+		weather_provider_from_env(),
 	))]
 }
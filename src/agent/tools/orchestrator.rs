@@ -24,34 +24,57 @@
  * whatever format the LLM generates (we handle both in run()).
  */
 
+use crate::agent::configs::orchestrator::AgentType;
 use crate::agent::models::context::{ContextData, SharedContextStore, ToolExecution};
+use crate::agent::tools::modify::ModifyItineraryTool;
 use crate::agent::tools::task::RespondToUserTool;
+use crate::controllers::chat::insert_interrupted_reply;
+#[cfg(test)]
+use crate::global::DEFAULT_LANGUAGE;
+use crate::global::TEST_TOOL_TIMEOUT_SECS;
+use crate::global::TOOL_TIMEOUT_SECS;
 use crate::sql_models::LlmProgress;
 use async_trait::async_trait;
-use langchain_rust::chain::Chain;
+use langchain_rust::chain::{Chain, ChainError};
 use langchain_rust::language_models::llm::LLM;
 use langchain_rust::tools::Tool;
 use serde_json::{Value, json};
 use sqlx::PgPool;
 use std::error::Error;
 use std::sync::Arc;
-use std::sync::atomic::{AtomicI32, Ordering};
+use std::time::Duration;
 use tokio::sync::Mutex;
-use tracing::{debug, error, info};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, error, info, warn};
+
+/// Bot reply sent when a sub-agent doesn't reply within [tool_timeout]
+const TOOL_TIMED_OUT_REPLY: &str = "The AI took too long to respond. Please try again.";
+
+/// How long the research, constraint and optimize agents each get to reply
+fn tool_timeout() -> Duration {
+	let seconds = TOOL_TIMEOUT_SECS;
+
+	// Tests use an agent that never replies to hit the timeout, so keep it short
+	#[cfg(test)]
+	let seconds = seconds.min(TEST_TOOL_TIMEOUT_SECS);
+
+	Duration::from_secs(seconds)
+}
 
 /// Helper function to automatically track tool executions in context.
-/// This is called by every tool to record its execution in the tool_history.
+/// This is called by every tool to record its execution in the tool_history,
+/// and counts towards the `MAX_TOOL_CALLS` of the message being replied to.
 ///
 /// Marked pub(crate) so it can be reused by Task tools without exposing it outside
 /// the agent tools module.
 pub(crate) async fn track_tool_execution(
 	_context_store: &SharedContextStore,
-	chat_session_id: &Arc<AtomicI32>,
+	chat_session_id: i32,
 	tool_name: &str,
 	input: &Value,
 	output: &str,
 ) -> Result<(), Box<dyn Error>> {
-	let chat_id = chat_session_id.load(Ordering::Relaxed);
+	let chat_id = chat_session_id;
 	if chat_id == 0 {
 		// If chat_session_id is not set, we're probably in a test or the tool is being called outside the agent context
 		return Ok(());
@@ -75,17 +98,23 @@ pub(crate) async fn track_tool_execution(
 					active_itinerary: None,
 					events: vec![],
 					tool_history: vec![],
+					tool_call_count: 0,
 					pipeline_stage: None,
 					researched_events: vec![],
 					constrained_events: vec![],
 					optimized_events: vec![],
 					constraints: vec![],
+					preferred_language: DEFAULT_LANGUAGE.to_string(),
+					cancellation: CancellationToken::new(),
+					last_accessed: std::time::Instant::now(),
 				},
 			);
 			store_guard.get_mut(&chat_id).unwrap()
 		}
 	};
 
+	context_data.tool_call_count += 1;
+
 	// Add tool execution to history
 	let tool_exec = ToolExecution {
 		tool_name: tool_name.to_string(),
@@ -120,7 +149,7 @@ pub struct RouteTaskTool {
 	pub constraint_agent: Arc<Mutex<crate::agent::configs::orchestrator::AgentType>>,
 	pub optimize_agent: Arc<Mutex<crate::agent::configs::orchestrator::AgentType>>,
 	pool: PgPool,
-	chat_session_id: Arc<AtomicI32>,
+	chat_session_id: i32,
 	context_store: SharedContextStore,
 }
 
@@ -131,7 +160,7 @@ impl RouteTaskTool {
 		constraint_agent: Arc<Mutex<crate::agent::configs::orchestrator::AgentType>>,
 		optimize_agent: Arc<Mutex<crate::agent::configs::orchestrator::AgentType>>,
 		pool: PgPool,
-		chat_session_id: Arc<AtomicI32>,
+		chat_session_id: i32,
 		context_store: SharedContextStore,
 	) -> Self {
 		Self {
@@ -144,6 +173,68 @@ impl RouteTaskTool {
 			context_store,
 		}
 	}
+
+	/// Marks the pipeline as failed, so the chat isn't left on the stage that errored
+	async fn mark_failed(&self) {
+		if let Err(e) = sqlx::query!(
+			r#"UPDATE chat_sessions
+			SET llm_progress=$1
+			WHERE id=$2;"#,
+			LlmProgress::Failed as _,
+			self.chat_session_id
+		)
+		.execute(&self.pool)
+		.await
+		{
+			error!(target: "orchestrator_pipeline", chat_session_id = self.chat_session_id, error = %e, "Failed to mark LLM progress as failed");
+		}
+	}
+
+	/// Invokes a sub-agent with `payload`, `None` when it doesn't reply within [tool_timeout]
+	async fn invoke_agent(
+		&self,
+		agent: &Arc<Mutex<AgentType>>,
+		payload: &str,
+	) -> Option<Result<String, ChainError>> {
+		let agent_outer = agent.lock().await;
+		let agent_inner = agent_outer.lock().await;
+		tokio::time::timeout(
+			tool_timeout(),
+			agent_inner.invoke(langchain_rust::prompt_args! {
+				"input" => payload,
+			}),
+		)
+		.await
+		.ok()
+	}
+
+	/// Replies to the user with [TOOL_TIMED_OUT_REPLY] after `agent` timed out, marking the
+	/// pipeline as ready again.
+	/// Returns the `MESSAGE_INSERTED:` marker so `send_message_to_llm` returns that message.
+	async fn reply_timed_out(&self, agent: &str, input: &Value) -> Result<String, Box<dyn Error>> {
+		crate::tool_trace!(agent: agent, tool: "complete", status: "timeout");
+		warn!(
+			target: "orchestrator_pipeline",
+			agent = agent,
+			chat_session_id = self.chat_session_id,
+			timeout_seconds = tool_timeout().as_secs(),
+			"Sub-agent timed out"
+		);
+
+		let message =
+			insert_interrupted_reply(&self.pool, self.chat_session_id, TOOL_TIMED_OUT_REPLY)
+				.await?;
+		let result = format!("MESSAGE_INSERTED:{}:{}", message.id, message.text);
+		track_tool_execution(
+			&self.context_store,
+			self.chat_session_id,
+			"route_task",
+			input,
+			&result,
+		)
+		.await?;
+		Ok(result)
+	}
 }
 
 /// Tool 4: Route Task to Sub-Agent
@@ -188,6 +279,24 @@ impl Tool for RouteTaskTool {
 	async fn run(&self, input: Value) -> Result<String, Box<dyn Error>> {
 		let input_clone = input.clone(); // Clone for tracking
 
+		// Don't start another sub-agent once the user has cancelled this reply
+		let chat_id = self.chat_session_id;
+		if self
+			.context_store
+			.read()
+			.await
+			.get(&chat_id)
+			.is_some_and(|ctx| ctx.cancellation.is_cancelled())
+		{
+			info!(
+				target: "orchestrator_tool",
+				tool = "route_task",
+				chat_id = chat_id,
+				"Reply was cancelled, not routing task"
+			);
+			return Err("The user cancelled this request".into());
+		}
+
 		debug!(
 			target: "orchestrator_tool",
 			tool = "route_task",
@@ -301,6 +410,14 @@ impl Tool for RouteTaskTool {
 			details: format!("task_type={}", task_type)
 		);
 
+		// Time the sub-agent until this returns, labelled by agent so unknown task types can't add labels
+		let _pipeline_timer = crate::middleware::metrics::start_llm_pipeline_timer(
+			match task_type_normalized.as_str() {
+				agent @ ("task" | "research" | "constraint" | "optimize") => agent,
+				_ => "unknown",
+			},
+		);
+
 		// Update LLM progress status in database BEFORE processing
 		if let Some(progress) = match task_type_normalized.as_str() {
 			"research" => Some(LlmProgress::Searching),
@@ -309,7 +426,7 @@ impl Tool for RouteTaskTool {
 			"task" => Some(LlmProgress::Scheduling),
 			_ => None,
 		} {
-			let chat_session_id = self.chat_session_id.load(Ordering::Relaxed);
+			let chat_session_id = self.chat_session_id;
 			info!(target: "orchestrator_pipeline", chat_session_id = chat_session_id, progress = ?progress, "Updating LLM progress");
 
 			match sqlx::query!(
@@ -360,6 +477,7 @@ impl Tool for RouteTaskTool {
 				Err(e) => {
 					crate::tool_trace!(agent: "task", tool: "complete", status: "error", details: format!("{}", e));
 					info!(target: "orchestrator_pipeline", agent = "task", status = "error", error = %e, "Task agent error");
+					self.mark_failed().await;
 					format!("TASK_AGENT_ERROR: {}", e)
 				}
 			};
@@ -375,7 +493,7 @@ impl Tool for RouteTaskTool {
 
 			track_tool_execution(
 				&self.context_store,
-				&self.chat_session_id,
+				self.chat_session_id,
 				"route_task",
 				&input_clone,
 				&tracking_str,
@@ -388,7 +506,7 @@ impl Tool for RouteTaskTool {
 		// For research/constraint/optimize agents, inject context from context_store
 		let payload_str = if task_type_normalized == "research" {
 			// Research gets the current trip_context snapshot
-			let chat_id = self.chat_session_id.load(Ordering::Relaxed);
+			let chat_id = self.chat_session_id;
 			if chat_id > 0 {
 				let store_guard = self.context_store.read().await;
 				if let Some(context_data) = store_guard.get(&chat_id) {
@@ -416,7 +534,7 @@ impl Tool for RouteTaskTool {
 			}
 		} else if task_type_normalized == "constraint" {
 			// Constraint gets both trip context and the latest research results
-			let chat_id = self.chat_session_id.load(Ordering::Relaxed);
+			let chat_id = self.chat_session_id;
 			if chat_id > 0 {
 				let store_guard = self.context_store.read().await;
 				if let Some(context_data) = store_guard.get(&chat_id) {
@@ -448,6 +566,7 @@ impl Tool for RouteTaskTool {
 
 					let constraint_payload = json!({
 						"trip_context": &context_data.trip_context,
+						"per_person_budget": context_data.trip_context.per_person_budget(),
 						"constraints": &context_data.constraints,
 						"event_ids": event_ids
 					});
@@ -476,7 +595,7 @@ impl Tool for RouteTaskTool {
 			}
 		} else if task_type_normalized == "optimize" {
 			// Optimize gets trip context, user profile, and constraint results
-			let chat_id = self.chat_session_id.load(Ordering::Relaxed);
+			let chat_id = self.chat_session_id;
 			debug!(
 				target: "orchestrator_pipeline",
 				agent = "optimize",
@@ -658,7 +777,9 @@ impl Tool for RouteTaskTool {
 
 					let optimize_payload = json!({
 						"trip_context": &context_data.trip_context,
+						"per_person_budget": context_data.trip_context.per_person_budget(),
 						"user_profile": &context_data.user_profile,
+						"preferred_language": &context_data.preferred_language,
 						"filtered_event_ids": filtered_ids
 					});
 
@@ -705,14 +826,11 @@ impl Tool for RouteTaskTool {
 				info!(target: "orchestrator_pipeline", agent = "research", "Invoking research agent");
 				debug!(target: "orchestrator_pipeline", agent = "research", payload = %payload_str, "Agent input");
 
-				let agent_outer = self.research_agent.lock().await;
-				let agent_inner = agent_outer.lock().await;
-				let agent_result = match agent_inner
-					.invoke(langchain_rust::prompt_args! {
-						"input" => payload_str.as_str(),
-					})
-					.await
-				{
+				let Some(response) = self.invoke_agent(&self.research_agent, &payload_str).await
+				else {
+					return self.reply_timed_out("research", &input_clone).await;
+				};
+				let agent_result = match response {
 					Ok(response) => {
 						// Parse response as JSON Value if possible
 						let data: Value = serde_json::from_str(&response)
@@ -731,7 +849,7 @@ impl Tool for RouteTaskTool {
 									.iter()
 									.filter_map(|v| v.as_i64().map(|n| n as i32))
 									.collect();
-								let chat_id = self.chat_session_id.load(Ordering::Relaxed);
+								let chat_id = self.chat_session_id;
 								if chat_id > 0 && !event_ids.is_empty() {
 									if let Err(e) = sqlx::query!(
 										r#"
@@ -772,6 +890,7 @@ impl Tool for RouteTaskTool {
 					Err(e) => {
 						crate::tool_trace!(agent: "research", tool: "complete", status: "error", details: format!("{}", e));
 						info!(target: "orchestrator_pipeline", agent = "research", status = "error", error = %e, "Research agent error");
+						self.mark_failed().await;
 						json!({
 							"agent": "research",
 							"status": "error",
@@ -787,14 +906,13 @@ impl Tool for RouteTaskTool {
 				info!(target: "orchestrator_pipeline", agent = "constraint", "Invoking constraint agent");
 				debug!(target: "orchestrator_pipeline", agent = "constraint", payload = %payload_str, "Agent input");
 
-				let agent_outer = self.constraint_agent.lock().await;
-				let agent_inner = agent_outer.lock().await;
-				let agent_result = match agent_inner
-					.invoke(langchain_rust::prompt_args! {
-						"input" => payload_str.as_str(),
-					})
+				let Some(response) = self
+					.invoke_agent(&self.constraint_agent, &payload_str)
 					.await
-				{
+				else {
+					return self.reply_timed_out("constraint", &input_clone).await;
+				};
+				let agent_result = match response {
 					Ok(response) => {
 						debug!(
 							target: "orchestrator_pipeline",
@@ -819,7 +937,7 @@ impl Tool for RouteTaskTool {
 									.iter()
 									.filter_map(|v| v.as_i64().map(|n| n as i32))
 									.collect();
-								let chat_id = self.chat_session_id.load(Ordering::Relaxed);
+								let chat_id = self.chat_session_id;
 								if chat_id > 0 && !filtered_ids.is_empty() {
 									if let Err(e) = sqlx::query!(
 										r#"
@@ -860,6 +978,7 @@ impl Tool for RouteTaskTool {
 					Err(e) => {
 						crate::tool_trace!(agent: "constraint", tool: "complete", status: "error", details: format!("{}", e));
 						info!(target: "orchestrator_pipeline", agent = "constraint", status = "error", error = %e, "Constraint agent error");
+						self.mark_failed().await;
 						json!({
 							"agent": "constraint",
 							"status": "error",
@@ -875,14 +994,11 @@ impl Tool for RouteTaskTool {
 				info!(target: "orchestrator_pipeline", agent = "optimize", "Invoking optimize agent");
 				debug!(target: "orchestrator_pipeline", agent = "optimize", payload = %payload_str, "Agent input");
 
-				let agent_outer = self.optimize_agent.lock().await;
-				let agent_inner = agent_outer.lock().await;
-				match agent_inner
-					.invoke(langchain_rust::prompt_args! {
-						"input" => payload_str.as_str(),
-					})
-					.await
-				{
+				let Some(response) = self.invoke_agent(&self.optimize_agent, &payload_str).await
+				else {
+					return self.reply_timed_out("optimize", &input_clone).await;
+				};
+				match response {
 					Ok(response) => {
 						debug!(
 							target: "orchestrator_pipeline",
@@ -895,7 +1011,7 @@ impl Tool for RouteTaskTool {
 							.unwrap_or_else(|_| json!({ "raw": response }));
 
 						// Store the complete itinerary in active_itinerary context
-						let chat_id = self.chat_session_id.load(Ordering::Relaxed);
+						let chat_id = self.chat_session_id;
 						if chat_id > 0 {
 							let mut store_guard = self.context_store.write().await;
 							if let Some(context_data) = store_guard.get_mut(&chat_id) {
@@ -927,6 +1043,7 @@ impl Tool for RouteTaskTool {
 					Err(e) => {
 						crate::tool_trace!(agent: "optimize", tool: "complete", status: "error", details: format!("{}", e));
 						info!(target: "orchestrator_pipeline", agent = "optimize", status = "error", error = %e, "Optimize agent error");
+						self.mark_failed().await;
 						json!({
 							"agent": "optimize",
 							"status": "error",
@@ -959,7 +1076,7 @@ impl Tool for RouteTaskTool {
 		// Track this tool execution
 		track_tool_execution(
 			&self.context_store,
-			&self.chat_session_id,
+			self.chat_session_id,
 			"route_task",
 			&input_clone,
 			&result_str,
@@ -972,16 +1089,16 @@ impl Tool for RouteTaskTool {
 
 /// Gets all the orchestrator tools.
 /// Returns a vector of Arc<dyn Tool> objects.
-/// chat_session_id and user_id are shared across tools that need them and can be updated per request.
+/// Tools are created per message with the chat_session_id and user_id they work for.
 pub fn get_orchestrator_tools(
-	_llm: Arc<dyn LLM + Send + Sync>,
+	llm: Arc<dyn LLM + Send + Sync>,
 	pool: PgPool,
 	task_agent: Arc<Mutex<crate::agent::configs::orchestrator::AgentType>>,
 	research_agent: Arc<Mutex<crate::agent::configs::orchestrator::AgentType>>,
 	constraint_agent: Arc<Mutex<crate::agent::configs::orchestrator::AgentType>>,
 	optimize_agent: Arc<Mutex<crate::agent::configs::orchestrator::AgentType>>,
-	chat_session_id: Arc<AtomicI32>,
-	_user_id: Arc<AtomicI32>,
+	chat_session_id: i32,
+	user_id: i32,
 	context_store: SharedContextStore,
 ) -> Vec<Arc<dyn Tool>> {
 	vec![
@@ -991,7 +1108,14 @@ pub fn get_orchestrator_tools(
 			constraint_agent,
 			optimize_agent,
 			pool.clone(),
-			Arc::clone(&chat_session_id),
+			chat_session_id,
+			context_store.clone(),
+		)),
+		Arc::new(ModifyItineraryTool::new(
+			llm,
+			pool.clone(),
+			chat_session_id,
+			user_id,
 			context_store.clone(),
 		)),
 		Arc::new(RespondToUserTool::new(pool, chat_session_id, context_store)),
@@ -10,6 +10,7 @@
 use async_trait::async_trait;
 use google_maps::places_new::{Field, FieldMask, PlaceType};
 use langchain_rust::tools::Tool;
+use num_traits::ToPrimitive;
 use serde::{Deserialize, de::IntoDeserializer};
 use serde_json::{Value, json};
 use sqlx::PgPool;
@@ -17,7 +18,12 @@ use std::time::Instant;
 use std::{collections::HashSet, error::Error, sync::Arc};
 use tracing::{debug, info};
 
-use crate::{global::GOOGLE_MAPS_API_KEY, http_models::event::Event};
+use crate::{
+	agent::models::event::SharedEventCache,
+	db::places::get_or_insert_event_by_place,
+	global::{GOOGLE_MAPS_API_KEY, GOOGLE_PLACES_API_KEY},
+	http_models::event::Event,
+};
 
 /// This tool takes an address and converts it into coordinates using Google Maps Geocoding API.
 #[derive(Clone)]
@@ -35,6 +41,35 @@ struct QueryDbEventsTool {
 #[derive(Clone)]
 struct NearbySearchTool {
 	pub db: PgPool,
+	pub event_cache: SharedEventCache,
+}
+
+/// This tool fetches venues around the trip's destination live from Google Places Nearby Search,
+/// so destinations without events in the database can still be planned.
+/// Only given to the research agent when `GOOGLE_PLACES_API_KEY` is set.
+#[derive(Clone)]
+struct FetchPlacesApiTool {
+	pub db: PgPool,
+	pub event_cache: SharedEventCache,
+}
+
+/// Place fields requested from Nearby Search, everything an [Event] is built from
+fn place_field_mask() -> FieldMask {
+	FieldMask::Specific(vec![
+		Field::PlacesAccessibilityOptions,
+		Field::PlacesAdrFormatAddress,
+		Field::PlacesDisplayName,
+		Field::PlacesId,
+		Field::PlacesPhotos,
+		Field::PlacesUtcOffsetMinutes,
+		Field::PlacesPriceLevel,
+		Field::PlacesRegularOpeningHours,
+		Field::PlacesWebsiteUri,
+		Field::PlacesServesVegetarianFood,
+		Field::PlacesTypes,
+		Field::PlacesPrimaryType,
+		Field::PlacesEditorialSummary,
+	])
 }
 
 #[async_trait]
@@ -229,7 +264,7 @@ impl Tool for QueryDbEventsTool {
 	}
 
 	async fn run(&self, input: Value) -> Result<String, Box<dyn Error>> {
-		// TODO create filters and query the db for possibly relevant events
+		// TODO create filters and query the db for possibly relevant, non-archived events
 
 		let name = input["name"].as_str().ok_or("Name should be a string")?;
 		Ok(format!("Hello, {}! Welcome to our AI assistant.", name))
@@ -470,21 +505,7 @@ impl<'db> Tool for NearbySearchTool {
 
 		let primary_res = gm_client
 			.nearby_search((lat, lng, SEARCH_RADIUS_METERS))?
-			.field_mask(FieldMask::Specific(vec![
-				Field::PlacesAccessibilityOptions,
-				Field::PlacesAdrFormatAddress,
-				Field::PlacesDisplayName,
-				Field::PlacesId,
-				Field::PlacesPhotos,
-				Field::PlacesUtcOffsetMinutes,
-				Field::PlacesPriceLevel,
-				Field::PlacesRegularOpeningHours,
-				Field::PlacesWebsiteUri,
-				Field::PlacesServesVegetarianFood,
-				Field::PlacesTypes,
-				Field::PlacesPrimaryType,
-				Field::PlacesEditorialSummary,
-			]))
+			.field_mask(place_field_mask())
 			.included_types(
 				included_types
 					.iter()
@@ -553,21 +574,7 @@ impl<'db> Tool for NearbySearchTool {
 
 		let secondary_res = gm_client
 			.nearby_search((secondary_lat, lng, SEARCH_RADIUS_METERS))?
-			.field_mask(FieldMask::Specific(vec![
-				Field::PlacesAccessibilityOptions,
-				Field::PlacesAdrFormatAddress,
-				Field::PlacesDisplayName,
-				Field::PlacesId,
-				Field::PlacesPhotos,
-				Field::PlacesUtcOffsetMinutes,
-				Field::PlacesPriceLevel,
-				Field::PlacesRegularOpeningHours,
-				Field::PlacesWebsiteUri,
-				Field::PlacesServesVegetarianFood,
-				Field::PlacesTypes,
-				Field::PlacesPrimaryType,
-				Field::PlacesEditorialSummary,
-			]))
+			.field_mask(place_field_mask())
 			.included_types(
 				included_types
 					.iter()
@@ -668,147 +675,16 @@ impl<'db> Tool for NearbySearchTool {
 			"Inserting/updating events in database"
 		);
 
-		// Define a struct to capture the RETURNING clause results
-		struct EventInsertResult {
-			id: i32,
-			event_name: String,
-		}
-
-		// Use query! macro for compile-time type checking
-		// Insert events one by one to use the type-safe macro
-		let mut results: Vec<EventInsertResult> = Vec::with_capacity(events.len());
-
+		// Reuse places other trips already researched instead of inserting them again
+		let mut event_ids: Vec<i32> = Vec::with_capacity(events.len());
 		for ev in events.iter() {
-			let result = sqlx::query!(
-			r#"
-			INSERT INTO events (
-				event_name,
-				event_description,
-				street_address,
-				city,
-				country,
-				postal_code,
-				lat,
-				lng,
-				event_type,
-				user_created,
-				hard_start,
-				hard_end,
-				timezone,
-				place_id,
-				wheelchair_accessible_parking,
-				wheelchair_accessible_entrance,
-				wheelchair_accessible_restroom,
-				wheelchair_accessible_seating,
-				serves_vegetarian_food,
-				price_level,
-				utc_offset_minutes,
-				website_uri,
-				types,
-				photo_name,
-				photo_width,
-				photo_height,
-				photo_author,
-				photo_author_uri,
-				photo_author_photo_uri,
-				weekday_descriptions,
-				secondary_hours_type,
-				next_open_time,
-				next_close_time,
-				open_now,
-				periods,
-				special_days
-			)
-			VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18, $19, $20, $21, $22, $23, $24, $25, $26, $27, $28, $29, $30, $31, $32, $33, $34, $35, $36)
-			ON CONFLICT (place_id) DO UPDATE SET
-				event_name = EXCLUDED.event_name,
-				event_description = EXCLUDED.event_description,
-				street_address = EXCLUDED.street_address,
-				city = EXCLUDED.city,
-				country = EXCLUDED.country,
-				postal_code = EXCLUDED.postal_code,
-				lat = EXCLUDED.lat,
-				lng = EXCLUDED.lng,
-				event_type = EXCLUDED.event_type,
-				user_created = EXCLUDED.user_created,
-				hard_start = EXCLUDED.hard_start,
-				hard_end = EXCLUDED.hard_end,
-				timezone = EXCLUDED.timezone,
-				wheelchair_accessible_parking = EXCLUDED.wheelchair_accessible_parking,
-				wheelchair_accessible_entrance = EXCLUDED.wheelchair_accessible_entrance,
-				wheelchair_accessible_restroom = EXCLUDED.wheelchair_accessible_restroom,
-				wheelchair_accessible_seating = EXCLUDED.wheelchair_accessible_seating,
-				serves_vegetarian_food = EXCLUDED.serves_vegetarian_food,
-				price_level = EXCLUDED.price_level,
-				utc_offset_minutes = EXCLUDED.utc_offset_minutes,
-				website_uri = EXCLUDED.website_uri,
-				types = EXCLUDED.types,
-				photo_name = EXCLUDED.photo_name,
-				photo_width = EXCLUDED.photo_width,
-				photo_height = EXCLUDED.photo_height,
-				photo_author = EXCLUDED.photo_author,
-				photo_author_uri = EXCLUDED.photo_author_uri,
-				photo_author_photo_uri = EXCLUDED.photo_author_photo_uri,
-				weekday_descriptions = EXCLUDED.weekday_descriptions,
-				secondary_hours_type = EXCLUDED.secondary_hours_type,
-				next_open_time = EXCLUDED.next_open_time,
-				next_close_time = EXCLUDED.next_close_time,
-				open_now = EXCLUDED.open_now,
-				periods = EXCLUDED.periods,
-				special_days = EXCLUDED.special_days
-			RETURNING id, event_name
-			"#,
-			&ev.event_name,
-			ev.event_description.as_ref(),
-			ev.street_address.as_ref(),
-			ev.city.as_ref(),
-			ev.country.as_ref(),
-			ev.postal_code,
-			ev.lat,
-			ev.lng,
-			ev.event_type.as_ref(),
-			ev.user_created,
-			ev.hard_start,
-			ev.hard_end,
-			ev.timezone.as_ref(),
-			ev.place_id.as_ref(),
-			ev.wheelchair_accessible_parking,
-			ev.wheelchair_accessible_entrance,
-			ev.wheelchair_accessible_restroom,
-			ev.wheelchair_accessible_seating,
-			ev.serves_vegetarian_food,
-			ev.price_level,
-			ev.utc_offset_minutes,
-			ev.website_uri.as_ref(),
-			ev.types.as_ref(),
-			ev.photo_name.as_ref(),
-			ev.photo_width,
-			ev.photo_height,
-			ev.photo_author.as_ref(),
-			ev.photo_author_uri.as_ref(),
-			ev.photo_author_photo_uri.as_ref(),
-			ev.weekday_descriptions.as_ref(),
-			ev.secondary_hours_type,
-			ev.next_open_time,
-			ev.next_close_time,
-			ev.open_now,
-			&ev.periods as _,
-			&ev.special_days as _,
-		)
-		.fetch_one(&self.db)
-		.await?;
-
-			results.push(EventInsertResult {
-				id: result.id,
-				event_name: result.event_name,
-			});
+			event_ids.push(get_or_insert_event_by_place(&self.db, &self.event_cache, ev).await?);
 		}
 
 		let elapsed = start_time.elapsed();
 
-		// Extract event IDs and names for the response and debugging
-		let event_ids: Vec<i32> = results.iter().map(|r| r.id).collect();
-		let event_names: Vec<String> = results.iter().map(|r| r.event_name.clone()).collect();
+		// Event names for debugging
+		let event_names: Vec<&str> = events.iter().map(|e| e.event_name.as_str()).collect();
 
 		// Return only the IDs to keep the context window clean
 		let result = json!({
@@ -827,7 +703,7 @@ impl<'db> Tool for NearbySearchTool {
 		debug!(
 			target: "research_tools",
 			tool = "nearby_search_tool",
-			events_sample = %serde_json::to_string(&results.iter().take(3).map(|r| json!({"id": r.id, "name": &r.event_name})).collect::<Vec<_>>()).unwrap_or_else(|_| "error".to_string()),
+			events_sample = %serde_json::to_string(&event_ids.iter().zip(&event_names).take(3).map(|(id, name)| json!({"id": id, "name": name})).collect::<Vec<_>>()).unwrap_or_else(|_| "error".to_string()),
 			"Sample of events (first 3)"
 		);
 
@@ -842,11 +718,161 @@ impl<'db> Tool for NearbySearchTool {
 	}
 }
 
+#[async_trait]
+impl Tool for FetchPlacesApiTool {
+	fn name(&self) -> String {
+		"fetch_places_api_tool".to_string()
+	}
+
+	fn description(&self) -> String {
+		"A tool that fetches places around the trip's destination live from the Google Places API, for destinations with few or no events in the database. The places are inserted or refreshed in the database. Returns a JSON object with 'event_ids' (array of integer IDs) and 'count' (number of events found)."
+			.to_string()
+	}
+
+	fn parameters(&self) -> Value {
+		json!({
+			"type": "object",
+			"properties": {
+				"destination": {
+					"type": "string",
+					"description": "The trip_context destination, e.g. a city name."
+				}
+			},
+			"required": ["destination"]
+		})
+	}
+
+	async fn run(&self, input: Value) -> Result<String, Box<dyn Error>> {
+		let start_time = Instant::now();
+
+		crate::tool_trace!(agent: "research", tool: "fetch_places_api_tool", status: "start");
+		debug!(
+			target: "research_tools",
+			tool = "fetch_places_api_tool",
+			input = %serde_json::to_string(&input).unwrap_or_else(|_| "invalid".to_string()),
+			"Tool input"
+		);
+
+		// Accept the trip context (or anything with a destination) as an object or JSON string,
+		// or the destination itself as a plain string
+		let parsed_input: Value = match input.as_str().map(str::trim) {
+			Some(raw) if raw.starts_with('{') => {
+				serde_json::from_str(raw).unwrap_or_else(|_| json!({ "destination": raw }))
+			}
+			Some(raw) => json!({ "destination": raw }),
+			None => input,
+		};
+		let destination = parsed_input
+			.get("destination")
+			.and_then(Value::as_str)
+			.map(str::trim)
+			.filter(|d| !d.is_empty())
+			.ok_or("destination should be a non-empty string")?
+			.to_string();
+
+		let api_key =
+			std::env::var(GOOGLE_PLACES_API_KEY).map_err(|_| "GOOGLE_PLACES_API_KEY is not set")?;
+		let gm_client = google_maps::Client::try_new(api_key)
+			.map_err(|_| "Failed to create client for Google Places API")?;
+
+		let geocode_res = gm_client
+			.geocoding()
+			.with_address(&destination)
+			.execute()
+			.await?;
+		let Some(geocoded) = geocode_res.results.first() else {
+			crate::tool_trace!(
+				agent: "research",
+				tool: "fetch_places_api_tool",
+				status: "error",
+				details: format!("{}ms - No coordinates for {destination}", start_time.elapsed().as_millis())
+			);
+			return Err(format!("Could not get coordinates for {destination}").into());
+		};
+		let (lat, lng) = (
+			geocoded
+				.geometry
+				.location
+				.lat
+				.to_f64()
+				.ok_or("Invalid latitude")?,
+			geocoded
+				.geometry
+				.location
+				.lng
+				.to_f64()
+				.ok_or("Invalid longitude")?,
+		);
+
+		info!(
+			target: "research_tools",
+			tool = "fetch_places_api_tool",
+			destination = %destination,
+			lat = %lat,
+			lng = %lng,
+			"Calling Google Places Nearby Search"
+		);
+
+		const SEARCH_RADIUS_METERS: f64 = 50_000.;
+		let res = gm_client
+			.nearby_search((lat, lng, SEARCH_RADIUS_METERS))?
+			.field_mask(place_field_mask())
+			.execute()
+			.await?;
+		if let Some(err) = res.error() {
+			crate::tool_trace!(
+				agent: "research",
+				tool: "fetch_places_api_tool",
+				status: "error",
+				details: format!("{}ms - API error: {}", start_time.elapsed().as_millis(), err)
+			);
+			return Err(format!("Places Nearby Search failed - {err}").into());
+		}
+
+		// Places come back once each, so each can be looked up as it is
+		let events: Vec<Event> = res.places().into_iter().map(Event::from).collect();
+		let mut event_ids: Vec<i32> = Vec::with_capacity(events.len());
+		for ev in events.iter() {
+			event_ids.push(get_or_insert_event_by_place(&self.db, &self.event_cache, ev).await?);
+		}
+
+		let elapsed = start_time.elapsed();
+		info!(
+			target: "research_tools",
+			tool = "fetch_places_api_tool",
+			elapsed_ms = elapsed.as_millis() as u64,
+			events_count = event_ids.len(),
+			"Fetched places from Google Places"
+		);
+		crate::tool_trace!(
+			agent: "research",
+			tool: "fetch_places_api_tool",
+			status: "success",
+			details: format!("{}ms - {} events", elapsed.as_millis(), event_ids.len())
+		);
+
+		Ok(json!({
+			"event_ids": event_ids,
+			"count": event_ids.len()
+		})
+		.to_string())
+	}
+}
+
 /// Export Research Tools
-pub fn research_tools(db: PgPool) -> [Arc<dyn Tool>; 2] {
-	[
+/// * `fetch_places_api_tool` is only included when `GOOGLE_PLACES_API_KEY` is set
+/// * Places the tools refresh are dropped from `event_cache`
+pub fn research_tools(db: PgPool, event_cache: SharedEventCache) -> Vec<Arc<dyn Tool>> {
+	let mut tools: Vec<Arc<dyn Tool>> = vec![
 		Arc::new(GeocodeTool),
 		// Arc::new(QueryDbEventsTool { db: db.clone() }),
-		Arc::new(NearbySearchTool { db }),
-	]
+		Arc::new(NearbySearchTool {
+			db: db.clone(),
+			event_cache: event_cache.clone(),
+		}),
+	];
+	if std::env::var(GOOGLE_PLACES_API_KEY).is_ok() {
+		tools.push(Arc::new(FetchPlacesApiTool { db, event_cache }));
+	}
+	tools
 }
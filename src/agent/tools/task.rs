@@ -14,23 +14,31 @@
  * from the Orchestrator-specific tools.
  */
 
-use crate::agent::models::context::{ContextData, SharedContextStore};
+use crate::agent::models::context::{
+	ContextData, SharedContextStore, TripContext, preferred_language,
+};
 use crate::agent::models::user::UserIntent;
+use crate::agent::parsing::ParsedDetails;
+use crate::agent::tools::constraint::is_keep_events_constraint;
 use crate::agent::tools::orchestrator::track_tool_execution;
-use crate::controllers::itinerary::insert_event_list;
+use crate::controllers::itinerary::{insert_event_list, validate_trip_dates};
+use crate::db::events::{fetch_events_by_ids, fetch_favorite_event_ids};
+use crate::global::DEFAULT_LANGUAGE;
+use crate::http_models::chat_session::{Clarification, KnownTripDetails};
 use crate::http_models::itinerary::Itinerary as HttpItinerary;
+use crate::middleware::metrics::{CLARIFICATIONS_ASKED_TOTAL, ITINERARIES_GENERATED_TOTAL};
 use crate::sql_models::LlmProgress;
 use async_trait::async_trait;
-use chrono::Datelike;
+use chrono::{Datelike, NaiveDate};
 use langchain_rust::language_models::llm::LLM;
 use langchain_rust::tools::Tool;
 use serde_json::{Value, json};
 use sqlx::PgPool;
 use std::error::Error;
 use std::sync::Arc;
-use std::sync::atomic::{AtomicI32, Ordering};
 use std::time::Instant;
-use tracing::{debug, info};
+use tokio_util::sync::CancellationToken;
+use tracing::{Instrument, debug, info};
 
 /// Tool 1: Parse User Intent
 /// Parses user input to extract intent, destination, dates, budget, and constraints.
@@ -38,14 +46,14 @@ use tracing::{debug, info};
 #[derive(Clone)]
 pub struct ParseUserIntentTool {
 	llm: Arc<dyn LLM + Send + Sync>,
-	chat_session_id: Arc<AtomicI32>,
+	chat_session_id: i32,
 	context_store: SharedContextStore,
 }
 
 impl ParseUserIntentTool {
 	pub fn new(
 		llm: Arc<dyn LLM + Send + Sync>,
-		chat_session_id: Arc<AtomicI32>,
+		chat_session_id: i32,
 		context_store: SharedContextStore,
 	) -> Self {
 		Self {
@@ -109,41 +117,18 @@ impl Tool for ParseUserIntentTool {
 			input["user_message"].to_string()
 		};
 
+		// Dates and budget don't need the LLM, and what's found here overrides its answer
+		let parsed = ParsedDetails::parse(&user_message, chrono::Utc::now().date_naive());
+		let already_found = if parsed == ParsedDetails::default() {
+			String::from("Nothing yet")
+		} else {
+			parsed.known_info().join(", ")
+		};
+
+		let language = preferred_language(&self.context_store, self.chat_session_id).await;
 		let prompt = format!(
-			r#"Extract travel planning information from the user's conversation history.
-
-CRITICAL: You will receive either:
-1. A JSON string containing a chat_history array with role/content messages
-2. A JSON object with destination/dates/budget fields that you should parse
-3. Plain text describing the travel request
-
-Your job is to extract ALL information mentioned in ANY format.
-
-User input: {}
-
-Extract the following information and return ONLY a valid JSON object with these fields:
-{{
-  "action": "create_itinerary" | "modify_itinerary" | "query" | "other",
-  "destination": string or null (extract from ANY field - look for country/city names like "brazil", "paris", "destination", etc.),
-  "start_date": string or null (ISO format YYYY-MM-DD if mentioned - look in "dates", "start_date", or message content),
-  "end_date": string or null (ISO format YYYY-MM-DD if mentioned - look in "dates", "end_date", or message content),
-  "budget": number or null (total budget in USD - look in "budget" field or dollar amounts in messages. Use midpoint for ranges like "20-30"),
-  "preferences": [array of strings - look in "preferences" field or message content for activities, interests],
-  "constraints": [array of strings - dietary restrictions, accessibility needs found anywhere],
-  "missing_info": [array of strings - list ONLY what is truly missing. If destination/dates/budget appear ANYWHERE, they are NOT missing]
-}}
-
-Rules:
-- If input has a "chat_history" array, read ALL messages in it
-- If input has direct fields like "destination", "dates", "budget", extract those
-- If input is plain text, parse it directly
-- For "july 20-30th" or "june 10-20", extract as start_date "2026-07-20" and end_date "2026-07-30" (year 2026 since we're in Dec 2025)
-- For budget ranges like "20-30 dollars", use the midpoint: 25
-- If preferences say "no preferences" or similar, use empty array but don't list it as missing
-- missing_info should ONLY contain items that are completely absent from the input
-
-Return ONLY the JSON object, no other text."#,
-			user_message
+			include_str!("../prompts/parse_user_intent.md"),
+			user_message, already_found, language
 		);
 
 		let response = self.llm.invoke(&prompt).await?;
@@ -157,12 +142,13 @@ Return ONLY the JSON object, no other text."#,
 			.trim();
 
 		// Validate it's proper JSON and return as UserIntent
-		let intent: UserIntent = serde_json::from_str(cleaned).map_err(|e| {
+		let mut intent: UserIntent = serde_json::from_str(cleaned).map_err(|e| {
 			format!(
 				"Failed to parse LLM response as JSON: {}. Response was: {}",
 				e, cleaned
 			)
 		})?;
+		parsed.merge_into(&mut intent);
 
 		info!(
 			target: "orchestrator_tool",
@@ -172,6 +158,7 @@ Return ONLY the JSON object, no other text."#,
 			start_date = ?intent.start_date,
 			end_date = ?intent.end_date,
 			budget = ?intent.budget,
+			travelers = ?intent.travelers,
 			preferences_count = intent.preferences.len(),
 			constraints_count = intent.constraints.len(),
 			missing_info = ?intent.missing_info,
@@ -198,7 +185,7 @@ Return ONLY the JSON object, no other text."#,
 		// Track this tool execution
 		track_tool_execution(
 			&self.context_store,
-			&self.chat_session_id,
+			self.chat_session_id,
 			"parse_user_intent",
 			&input_clone,
 			&result,
@@ -215,16 +202,12 @@ Return ONLY the JSON object, no other text."#,
 #[derive(Clone)]
 pub struct RetrieveChatContextTool {
 	pool: PgPool,
-	chat_session_id: Arc<AtomicI32>,
+	chat_session_id: i32,
 	context_store: SharedContextStore,
 }
 
 impl RetrieveChatContextTool {
-	pub fn new(
-		pool: PgPool,
-		chat_session_id: Arc<AtomicI32>,
-		context_store: SharedContextStore,
-	) -> Self {
+	pub fn new(pool: PgPool, chat_session_id: i32, context_store: SharedContextStore) -> Self {
 		Self {
 			pool,
 			chat_session_id,
@@ -257,8 +240,8 @@ impl Tool for RetrieveChatContextTool {
 
 		crate::tool_trace!(agent: "task", tool: "retrieve_chat_context", status: "start");
 
-		// Get chat_session_id from shared atomic (set by controller before agent invocation)
-		let chat_id = self.chat_session_id.load(Ordering::Relaxed);
+		// chat_session_id the tool was created with by the controller
+		let chat_id = self.chat_session_id;
 		if chat_id == 0 {
 			return Err("chat_session_id not set. This should be set by the controller before invoking the agent.".into());
 		}
@@ -280,7 +263,7 @@ impl Tool for RetrieveChatContextTool {
 				m.text,
 				m.itinerary_id
 			FROM messages m
-			WHERE m.chat_session_id = $1
+			WHERE m.chat_session_id = $1 AND m.deleted_at IS NULL
 			ORDER BY m.timestamp ASC
 			LIMIT 50
 			"#,
@@ -303,6 +286,16 @@ impl Tool for RetrieveChatContextTool {
 			})
 			.collect();
 
+		// If this chat has no in-memory context yet, fall back to the trip context
+		// persisted from a previous session
+		let stored_trip_context = if self.context_store.read().await.contains_key(&chat_id) {
+			None
+		} else {
+			load_trip_context(&self.pool, chat_id)
+				.await
+				.map_err(|e| format!("Database error: {}", e))?
+		};
+
 		// Retrieve or initialize in-memory context (includes pipeline state and events)
 		let mut store_guard = self.context_store.write().await;
 		let context_data = match store_guard.get_mut(&chat_id) {
@@ -316,15 +309,19 @@ impl Tool for RetrieveChatContextTool {
 						user_id: 0,
 						user_profile: None,
 						chat_history: vec![],
-						trip_context: crate::agent::models::context::TripContext::default(),
+						trip_context: stored_trip_context.unwrap_or_default(),
 						active_itinerary: None,
 						events: vec![],
 						tool_history: vec![],
+						tool_call_count: 0,
 						pipeline_stage: None,
 						researched_events: vec![],
 						constrained_events: vec![],
 						optimized_events: vec![],
 						constraints: vec![],
+						preferred_language: DEFAULT_LANGUAGE.to_string(),
+						cancellation: CancellationToken::new(),
+						last_accessed: std::time::Instant::now(),
 					},
 				);
 				store_guard.get_mut(&chat_id).unwrap()
@@ -386,7 +383,7 @@ impl Tool for RetrieveChatContextTool {
 		// Track this tool execution
 		track_tool_execution(
 			&self.context_store,
-			&self.chat_session_id,
+			self.chat_session_id,
 			"retrieve_chat_context",
 			&input_clone,
 			&result,
@@ -398,22 +395,23 @@ impl Tool for RetrieveChatContextTool {
 }
 
 /// Tool 3: Retrieve User Profile
-/// Retrieves user profile information including preferences and past trips.
+/// Retrieves user profile information including preferences, past trips and the ids of
+/// favorited events.
 /// Returns a UserProfile object.
 #[derive(Clone)]
 pub struct RetrieveUserProfileTool {
 	pool: PgPool,
-	chat_session_id: Arc<AtomicI32>,
+	chat_session_id: i32,
 	#[allow(dead_code)]
-	user_id: Arc<AtomicI32>,
+	user_id: i32,
 	context_store: SharedContextStore,
 }
 
 impl RetrieveUserProfileTool {
 	pub fn new(
 		pool: PgPool,
-		chat_session_id: Arc<AtomicI32>,
-		user_id: Arc<AtomicI32>,
+		chat_session_id: i32,
+		user_id: i32,
 		context_store: SharedContextStore,
 	) -> Self {
 		Self {
@@ -432,7 +430,7 @@ impl Tool for RetrieveUserProfileTool {
 	}
 
 	fn description(&self) -> String {
-		"Retrieves user profile information including preferences and past trips. Automatically uses the logged-in user's ID."
+		"Retrieves user profile information including preferences, past trips and favorited event ids. Automatically uses the logged-in user's ID."
 			.to_string()
 	}
 
@@ -457,13 +455,13 @@ impl Tool for RetrieveUserProfileTool {
 			"Received input in retrieve_user_profile"
 		);
 
-		// Get chat_session_id from atomic
-		let chat_id = self.chat_session_id.load(Ordering::Relaxed);
+		// chat_session_id the tool was created with
+		let chat_id = self.chat_session_id;
 		if chat_id == 0 {
 			return Err("chat_session_id not set".into());
 		}
 
-		// Get user_id from context (safer than atomics - no race conditions)
+		// Get user_id from context
 		let user_id = {
 			let store_guard = self.context_store.read().await;
 			store_guard
@@ -491,7 +489,10 @@ impl Tool for RetrieveUserProfileTool {
 				"budget_preference": null,
 				"risk_preference": null,
 				"food_allergies": "",
-				"disabilities": ""
+				"disabilities": "",
+				"interests": [],
+				"preferred_language": DEFAULT_LANGUAGE,
+				"favorited_event_ids": []
 			});
 
 			// Save empty profile into in-memory context for this chat (if any)
@@ -512,7 +513,7 @@ impl Tool for RetrieveUserProfileTool {
 
 			track_tool_execution(
 				&self.context_store,
-				&self.chat_session_id,
+				self.chat_session_id,
 				"retrieve_user_profile",
 				&input_clone,
 				&result,
@@ -526,7 +527,7 @@ impl Tool for RetrieveUserProfileTool {
 		debug!(target: "orchestrator_tool", tool = "retrieve_user_profile", input = %serde_json::to_string(&input)?, "Tool input");
 
 		// Query database for user profile
-		use crate::sql_models::{BudgetBucket, RiskTolerence};
+		use crate::sql_models::{BudgetBucket, Interest, RiskTolerence};
 		let account = sqlx::query_as!(
 			crate::http_models::account::CurrentResponse,
 			r#"
@@ -538,7 +539,10 @@ impl Tool for RetrieveUserProfileTool {
 				risk_preference as "risk_preference: RiskTolerence",
 				COALESCE(food_allergies, '') as "food_allergies!: String",
 				COALESCE(disabilities, '') as "disabilities!: String",
-				COALESCE(profile_picture, '') as "profile_picture!: String"
+				interests as "interests: Vec<Interest>",
+				notification_preferences as "notification_preferences: _",
+				COALESCE(profile_picture, '') as "profile_picture!: String",
+				preferred_language
 			FROM accounts
 			WHERE id = $1
 			"#,
@@ -548,6 +552,10 @@ impl Tool for RetrieveUserProfileTool {
 		.await
 		.map_err(|e| format!("Database error: {}", e))?;
 
+		let favorited_event_ids = fetch_favorite_event_ids(&self.pool, user_id)
+			.await
+			.map_err(|e| format!("Database error: {}", e))?;
+
 		let profile = if let Some(acc) = account {
 			json!({
 				"user_id": user_id,
@@ -557,14 +565,18 @@ impl Tool for RetrieveUserProfileTool {
 				"budget_preference": acc.budget_preference,
 				"risk_preference": acc.risk_preference,
 				"food_allergies": acc.food_allergies,
-				"disabilities": acc.disabilities
+				"disabilities": acc.disabilities,
+				"interests": acc.interests,
+				"preferred_language": acc.preferred_language,
+				"favorited_event_ids": favorited_event_ids
 			})
 		} else {
 			return Err(format!("User with id {} not found", user_id).into());
 		};
 
 		// Automatically save user profile to in-memory context AND pre-fill trip context
-		let chat_id = self.chat_session_id.load(Ordering::Relaxed);
+		let chat_id = self.chat_session_id;
+		let mut prefilled_trip_context = None;
 		if chat_id != 0 {
 			// Get existing in-memory context
 			let mut store_guard = self.context_store.write().await;
@@ -597,8 +609,15 @@ impl Tool for RetrieveUserProfileTool {
 				// Store constraints in trip_context
 				context_data.trip_context.constraints = constraints.clone();
 
-				// Also store in the legacy constraints field for backward compatibility
+				// Also store in the legacy constraints field for backward compatibility,
+				// keeping the events pinned by an itinerary regenerate
+				let kept: Vec<String> = context_data
+					.constraints
+					.drain(..)
+					.filter(|c| is_keep_events_constraint(c))
+					.collect();
 				context_data.constraints = constraints;
+				context_data.constraints.extend(kept);
 
 				info!(
 					target: "orchestrator_tool",
@@ -614,9 +633,17 @@ impl Tool for RetrieveUserProfileTool {
 					constraints = ?context_data.trip_context.constraints,
 					"Pre-filled constraints from user profile"
 				);
+				prefilled_trip_context = Some(context_data.trip_context.clone());
 			}
 		}
 
+		// Persist the pre-filled constraints along with the rest of the trip context
+		if let Some(trip_context) = prefilled_trip_context {
+			save_trip_context(&self.pool, chat_id, &trip_context)
+				.await
+				.map_err(|e| format!("Database error: {}", e))?;
+		}
+
 		let result = serde_json::to_string(&profile)?;
 
 		let elapsed = start_time.elapsed();
@@ -631,7 +658,7 @@ impl Tool for RetrieveUserProfileTool {
 		// Track this tool execution
 		track_tool_execution(
 			&self.context_store,
-			&self.chat_session_id,
+			self.chat_session_id,
 			"retrieve_user_profile",
 			&input_clone,
 			&result,
@@ -649,7 +676,7 @@ impl Tool for RetrieveUserProfileTool {
 pub struct AskForClarificationTool {
 	llm: Arc<dyn LLM + Send + Sync>,
 	pool: PgPool,
-	chat_session_id: Arc<AtomicI32>,
+	chat_session_id: i32,
 	context_store: SharedContextStore,
 }
 
@@ -657,7 +684,7 @@ impl AskForClarificationTool {
 	pub fn new(
 		llm: Arc<dyn LLM + Send + Sync>,
 		pool: PgPool,
-		chat_session_id: Arc<AtomicI32>,
+		chat_session_id: i32,
 		context_store: SharedContextStore,
 	) -> Self {
 		Self {
@@ -741,7 +768,7 @@ impl Tool for AskForClarificationTool {
 		);
 
 		// missing_info should be a JSON string, but handle all cases for robustness
-		let missing_info: Vec<String> = if let Some(s) = parsed_input["missing_info"].as_str() {
+		let mut missing_info: Vec<String> = if let Some(s) = parsed_input["missing_info"].as_str() {
 			// Try to parse as JSON array first
 			if let Ok(parsed) = serde_json::from_str::<Vec<String>>(s) {
 				parsed
@@ -820,7 +847,7 @@ impl Tool for AskForClarificationTool {
 		debug!(target: "orchestrator_tool", tool = "ask_for_clarification", input = %serde_json::to_string(&parsed_input)?, "Tool input");
 
 		// Retrieve chat context to extract known information
-		let chat_id = self.chat_session_id.load(Ordering::Relaxed);
+		let chat_id = self.chat_session_id;
 		if chat_id == 0 {
 			return Err("chat_session_id not set. This should be set by the controller before invoking the agent.".into());
 		}
@@ -842,7 +869,7 @@ impl Tool for AskForClarificationTool {
 
 		// ANTI-LOOP PROTECTION: Check if we've already asked for clarification
 		// If asked_clarification flag is already true in trip context, we should NOT ask again
-		{
+		let trip_context = {
 			let store_guard = self.context_store.read().await;
 			if let Some(context_data) = store_guard.get(&chat_id) {
 				if context_data.trip_context.asked_clarification {
@@ -856,7 +883,11 @@ impl Tool for AskForClarificationTool {
 					return Ok("Ready for research pipeline.".to_string());
 				}
 			}
-		}
+			store_guard
+				.get(&chat_id)
+				.map(|context_data| context_data.trip_context.clone())
+				.unwrap_or_default()
+		};
 
 		// Get chat history to extract known information
 		let messages = sqlx::query!(
@@ -868,7 +899,7 @@ impl Tool for AskForClarificationTool {
 				m.text,
 				m.itinerary_id
 			FROM messages m
-			WHERE m.chat_session_id = $1
+			WHERE m.chat_session_id = $1 AND m.deleted_at IS NULL
 			ORDER BY m.timestamp ASC
 			LIMIT 50
 			"#,
@@ -878,95 +909,31 @@ impl Tool for AskForClarificationTool {
 		.await
 		.map_err(|e| format!("Database error: {}", e))?;
 
-		// Extract known information from chat history and in-memory context
-		let mut known_info: Vec<String> = Vec::new();
+		// Dates and budget are parsed from the user's messages, the destination is whatever
+		// update_trip_context extracted
 		let chat_text = messages
 			.iter()
 			.filter(|m| m.is_user)
 			.map(|m| m.text.as_str())
 			.collect::<Vec<&str>>()
-			.join(" ");
-
-		// Try to extract from chat history using simple patterns
-		if known_info.is_empty() {
-			let chat_lower = chat_text.to_lowercase();
-
-			// Check for destination (common country/city names)
-			let destinations = vec![
-				"brazil",
-				"paris",
-				"tokyo",
-				"london",
-				"new york",
-				"rome",
-				"barcelona",
-				"amsterdam",
-				"berlin",
-				"dubai",
-				"singapore",
-				"sydney",
-				"mumbai",
-				"bangkok",
-			];
-			for dest in destinations {
-				if chat_lower.contains(dest) {
-					known_info.push(format!(
-						"Destination: {}",
-						dest.split_whitespace()
-							.next()
-							.unwrap_or(dest)
-							.to_uppercase()
-					));
-					break;
-				}
-			}
-
-			// Check for budget (numbers with $ or dollar/buck keywords)
-			if chat_lower.contains("$")
-				|| chat_lower.contains("dollar")
-				|| chat_lower.contains("budget")
-				|| chat_lower.contains("buck")
-			{
-				let words: Vec<&str> = chat_text.split_whitespace().collect();
-				for (i, word) in words.iter().enumerate() {
-					let word_lower = word.to_lowercase();
-					if word_lower.contains("$")
-						|| word_lower.contains("dollar")
-						|| word_lower.contains("budget")
-						|| word_lower.contains("buck")
-					{
-						// Look for numbers nearby
-						for j in i.saturating_sub(2)..(i + 3).min(words.len()) {
-							if words[j].chars().any(|c| c.is_numeric()) {
-								known_info.push(format!("Budget: {}", words[j]));
-								break;
-							}
-						}
-					}
-				}
-			}
+			.join("\n");
+		let parsed = ParsedDetails::parse(&chat_text, chrono::Utc::now().date_naive());
+		parsed.remove_known(&mut missing_info);
+		let mut known_info = parsed.known_info();
+		if let Some(destination) = &trip_context.destination {
+			known_info.insert(0, format!("Destination: {}", destination));
+		}
 
-			// Check for dates (months or date patterns)
-			let months = vec![
-				"january",
-				"february",
-				"march",
-				"april",
-				"may",
-				"june",
-				"july",
-				"august",
-				"september",
-				"october",
-				"november",
-				"december",
-			];
-			for month in months {
-				if chat_lower.contains(month) {
-					known_info.push(format!("Dates: mentioned in conversation"));
-					break;
-				}
-			}
+		// Everything asked for was already given, so there's nothing to ask
+		if missing_info.is_empty() {
+			info!(
+				target: "orchestrator_tool",
+				tool = "ask_for_clarification",
+				chat_id = chat_id,
+				known_info = ?known_info,
+				"All missing info was found in the conversation - returning ready signal"
+			);
+			return Ok("Ready for research pipeline.".to_string());
 		}
 
 		let known_info_str = if known_info.is_empty() {
@@ -976,29 +943,10 @@ impl Tool for AskForClarificationTool {
 		};
 		let missing_info_str = missing_info.join(", ");
 
+		let language = preferred_language(&self.context_store, chat_id).await;
 		let prompt = format!(
-			r#"Generate a friendly, natural clarification message for a travel planning conversation.
-
-IMPORTANT: You must show the user what information you already have and what you still need.
-
-Information I Already Have:
-{}
-
-Information I Still Need:
-{}
-
-Conversation Context: {}
-
-Create a friendly message that:
-1. Acknowledges what information you already have (if any)
-2. Clearly states what information is still needed to create the itinerary
-3. Asks for the missing information in a natural, conversational way
-
-Format your response as a complete message that shows both what you know and what you need.
-Example: "Great! I see you're planning a trip to [destination]. To create your itinerary, I still need to know [missing info]. Could you share [specific questions]?"
-
-Return ONLY the message text, nothing else."#,
-			known_info_str, missing_info_str, context_str
+			include_str!("../prompts/ask_for_clarification.md"),
+			known_info_str, missing_info_str, context_str, language
 		);
 
 		let response = self.llm.invoke(&prompt).await?;
@@ -1017,6 +965,7 @@ Return ONLY the message text, nothing else."#,
 		.fetch_one(&self.pool)
 		.await
 		.map_err(|e| format!("Database error: {}", e))?;
+		CLARIFICATIONS_ASKED_TOTAL.inc();
 
 		info!(
 			target: "orchestrator_tool",
@@ -1033,9 +982,9 @@ Return ONLY the message text, nothing else."#,
 		);
 
 		// Mark that we've asked for clarification in the trip context
-		{
+		let asked_trip_context = {
 			let mut store_guard = self.context_store.write().await;
-			if let Some(context_data) = store_guard.get_mut(&chat_id) {
+			store_guard.get_mut(&chat_id).map(|context_data| {
 				context_data.trip_context.asked_clarification = true;
 				info!(
 					target: "trip_context",
@@ -1043,7 +992,15 @@ Return ONLY the message text, nothing else."#,
 					chat_id = chat_id,
 					"Marked asked_clarification flag in trip context"
 				);
-			}
+				context_data.trip_context.clone()
+			})
+		};
+
+		// Persist the flag so a restart doesn't ask for the same details again
+		if let Some(trip_context) = asked_trip_context {
+			save_trip_context(&self.pool, chat_id, &trip_context)
+				.await
+				.map_err(|e| format!("Database error: {}", e))?;
 		}
 
 		// Return the clarification text directly.
@@ -1051,16 +1008,31 @@ Return ONLY the message text, nothing else."#,
 		// The agent prompt instructs to use this as Final Answer immediately.
 		let result = clarification.clone();
 
-		// Reset progress back to Ready now that we've sent the clarification
-		// message to the user so the frontend stops showing an in-progress
-		// status for this chat session.
+		// Wait for the user's answer now that we've sent the clarification
+		// message, so the frontend stops showing an in-progress status for
+		// this chat session. What was asked for stays on the chat session
+		// until the pipeline advances, so the frontend can prefill a form.
+		let asked = Clarification {
+			missing_info,
+			known: KnownTripDetails {
+				destination: trip_context.destination,
+				start_date: trip_context
+					.start_date
+					.or(parsed.start_date.map(|date| date.to_string())),
+				end_date: trip_context
+					.end_date
+					.or(parsed.end_date.map(|date| date.to_string())),
+				budget: trip_context.budget.or(parsed.budget),
+			},
+		};
 		_ = sqlx::query!(
 			r#"
 			UPDATE chat_sessions
-			SET llm_progress = $1
-			WHERE id = $2;
+			SET llm_progress = $1, clarification = $2
+			WHERE id = $3;
 			"#,
-			LlmProgress::Ready as _,
+			LlmProgress::AwaitingUser as _,
+			json!(asked),
 			chat_id,
 		)
 		.execute(&self.pool)
@@ -1083,7 +1055,7 @@ Return ONLY the message text, nothing else."#,
 		// Track this tool execution
 		track_tool_execution(
 			&self.context_store,
-			&self.chat_session_id,
+			self.chat_session_id,
 			"ask_for_clarification",
 			&input_clone,
 			&result,
@@ -1094,22 +1066,68 @@ Return ONLY the message text, nothing else."#,
 	}
 }
 
+/// Dates of an itinerary the LLM produced, see [itinerary_dates]
+#[derive(Debug, PartialEq)]
+pub struct ItineraryDates {
+	pub start_date: NaiveDate,
+	pub end_date: NaiveDate,
+	/// One date per entry in `event_days`
+	pub day_dates: Vec<NaiveDate>,
+}
+
+/// Reads the dates of an LLM itinerary, falling back to the dates in `trip_context`.
+///
+/// The LLM's `start_date`/`end_date` are used when they parse, otherwise the trip context's.
+/// A day whose `date` doesn't parse gets the date it would have counting from the start date.
+/// Errors when neither the itinerary nor the trip context has a valid start and end date,
+/// or when those dates fail [validate_trip_dates].
+pub fn itinerary_dates(
+	itinerary_json: &Value,
+	trip_context: &TripContext,
+) -> Result<ItineraryDates, String> {
+	let parse =
+		|date: Option<&str>| date.and_then(|date| NaiveDate::parse_from_str(date, "%Y-%m-%d").ok());
+	let start_date = parse(itinerary_json.get("start_date").and_then(|v| v.as_str()))
+		.or_else(|| parse(trip_context.start_date.as_deref()))
+		.ok_or("Itinerary has no valid start_date and none is known for the trip")?;
+	let end_date = parse(itinerary_json.get("end_date").and_then(|v| v.as_str()))
+		.or_else(|| parse(trip_context.end_date.as_deref()))
+		.ok_or("Itinerary has no valid end_date and none is known for the trip")?;
+	validate_trip_dates(start_date, end_date)?;
+
+	let day_dates = itinerary_json
+		.get("event_days")
+		.and_then(|v| v.as_array())
+		.map(|days| {
+			days.iter()
+				.enumerate()
+				.map(|(i, day)| {
+					parse(day.get("date").and_then(|v| v.as_str()))
+						.unwrap_or(start_date + chrono::Duration::days(i as i64))
+				})
+				.collect()
+		})
+		.unwrap_or_default();
+
+	Ok(ItineraryDates {
+		start_date,
+		end_date,
+		day_dates,
+	})
+}
+
 /// Tool: Respond to User
 /// Sends a response to the user with the current itinerary (if available) or asks for more information.
 /// This tool STOPS the pipeline and sends the final message to the user.
 #[derive(Clone)]
 pub struct RespondToUserTool {
 	pool: PgPool,
-	chat_session_id: Arc<AtomicI32>,
+	chat_session_id: i32,
 	context_store: SharedContextStore,
 }
 
 impl RespondToUserTool {
-	pub fn new(
-		pool: PgPool,
-		chat_session_id: Arc<AtomicI32>,
-		context_store: SharedContextStore,
-	) -> Self {
+	pub fn new(pool: PgPool, chat_session_id: i32, context_store: SharedContextStore) -> Self {
 		Self {
 			pool,
 			chat_session_id,
@@ -1149,7 +1167,7 @@ impl Tool for RespondToUserTool {
 		crate::tool_trace!(agent: "orchestrator", tool: "respond_to_user", status: "start");
 
 		// Update progress to FinalizingItinerary
-		let chat_id = self.chat_session_id.load(Ordering::Relaxed);
+		let chat_id = self.chat_session_id;
 		if chat_id > 0 {
 			_ = sqlx::query!(
 				r#"UPDATE chat_sessions
@@ -1169,7 +1187,7 @@ impl Tool for RespondToUserTool {
 			"Received input in respond_to_user"
 		);
 
-		// Get chat_session_id from shared atomic (set by controller before agent invocation)
+		// chat_session_id the tool was created with by the controller
 		if chat_id == 0 {
 			return Err("chat_session_id not set. This should be set by the controller before invoking the agent.".into());
 		}
@@ -1198,8 +1216,11 @@ impl Tool for RespondToUserTool {
 		debug!(target: "orchestrator_tool", tool = "respond_to_user", input = %serde_json::to_string(&parsed_input)?, "Tool input");
 
 		// Get context to check for active_itinerary
-		let store_guard = self.context_store.read().await;
-		let context_data = store_guard
+		// The guard is dropped right away, track_tool_execution needs the write lock below
+		let context_data = self
+			.context_store
+			.read()
+			.await
 			.get(&chat_id)
 			.cloned()
 			.unwrap_or_else(|| ContextData {
@@ -1211,11 +1232,15 @@ impl Tool for RespondToUserTool {
 				active_itinerary: None,
 				events: vec![],
 				tool_history: vec![],
+				tool_call_count: 0,
 				pipeline_stage: None,
 				researched_events: vec![],
 				constrained_events: vec![],
 				optimized_events: vec![],
 				constraints: vec![],
+				preferred_language: DEFAULT_LANGUAGE.to_string(),
+				cancellation: CancellationToken::new(),
+				last_accessed: std::time::Instant::now(),
 			});
 
 		// Check if we have an active itinerary
@@ -1232,6 +1257,15 @@ impl Tool for RespondToUserTool {
 		let (message_text, message_id) = if has_itinerary {
 			// Parse and save the itinerary to database
 			let itinerary_json = context_data.active_itinerary.clone().unwrap();
+			let dates = itinerary_dates(&itinerary_json, &context_data.trip_context)?;
+
+			// The itinerary, its event list, and the message are committed together.
+			// Returning early drops the transaction, which rolls it back.
+			let mut tx = self
+				.pool
+				.begin()
+				.await
+				.map_err(|e| format!("Failed to start transaction: {}", e))?;
 
 			// Get user_id from chat_session
 			let user_id = sqlx::query!(
@@ -1275,101 +1309,42 @@ impl Tool for RespondToUserTool {
 
 			// Fetch full event objects from database
 			use crate::http_models::event::Event as HttpEvent;
-			let full_events = if !all_event_ids.is_empty() {
-				sqlx::query_as!(
-					HttpEvent,
-					r#"
-				SELECT 
-					id, event_name, event_description, street_address, city, country, postal_code,
-					lat, lng, event_type, user_created, hard_start, hard_end, timezone, place_id,
-					wheelchair_accessible_parking, wheelchair_accessible_entrance,
-					wheelchair_accessible_restroom, wheelchair_accessible_seating,
-					serves_vegetarian_food, price_level, utc_offset_minutes, website_uri, types,
-					photo_name, photo_width, photo_height, photo_author, photo_author_uri,
-					photo_author_photo_uri, weekday_descriptions, secondary_hours_type,
-					next_open_time, next_close_time, open_now,
-					periods as "periods!: Vec<crate::sql_models::Period>",
-					special_days,
-					NULL::int4 as block_index
-				FROM events
-				WHERE id = ANY($1)
-				"#,
-					&all_event_ids
-				)
-				.fetch_all(&self.pool)
-				.await
-				.map_err(|e| format!("Failed to fetch full events: {}", e))?
-			} else {
-				Vec::new()
-			};
-
-			// Create a map of event ID -> full event for quick lookup
 			let event_map: std::collections::HashMap<i32, HttpEvent> =
-				full_events.into_iter().map(|e| (e.id, e)).collect();
-
-			// Helper function to hydrate events with full data from database
-			let hydrate_events = |partial_events: &Value| -> Vec<HttpEvent> {
-				if let Some(events_arr) = partial_events.as_array() {
-					events_arr
-						.iter()
-						.filter_map(|e| {
-							e.get("id")
-								.and_then(|v| v.as_i64())
-								.and_then(|id| event_map.get(&(id as i32)).cloned())
-						})
-						.collect()
-				} else {
-					Vec::new()
-				}
+				fetch_events_by_ids(&self.pool, &all_event_ids)
+					.await
+					.map_err(|e| format!("Failed to fetch full events: {}", e))?
+					.into_iter()
+					.map(|e| (e.id, e))
+					.collect();
+
+			// Swaps the LLM's partial events for the full ones, dropping ids that don't exist
+			let hydrate_events = |partial_events: Option<&Value>| -> Vec<HttpEvent> {
+				partial_events
+					.and_then(Value::as_array)
+					.into_iter()
+					.flatten()
+					.filter_map(|e| e.get("id").and_then(Value::as_i64))
+					.filter_map(|id| event_map.get(&(id as i32)).cloned())
+					.collect()
 			};
 
 			// Parse itinerary structure (dates, title, days)
 			use crate::http_models::itinerary::EventDay as HttpEventDay;
 			let mut event_days = Vec::new();
 			if let Some(days) = itinerary_json.get("event_days").and_then(|v| v.as_array()) {
-				for day in days {
-					let date_str = day
-						.get("date")
-						.and_then(|v| v.as_str())
-						.unwrap_or("2025-01-01");
-					let date = chrono::NaiveDate::parse_from_str(date_str, "%Y-%m-%d")
-						.unwrap_or_else(|_| chrono::NaiveDate::from_ymd_opt(2025, 1, 1).unwrap());
-
+				for (day, &date) in days.iter().zip(&dates.day_dates) {
 					event_days.push(HttpEventDay {
-						morning_events: hydrate_events(
-							&day.get("morning_events").cloned().unwrap_or(json!([])),
-						),
-						afternoon_events: hydrate_events(
-							&day.get("afternoon_events").cloned().unwrap_or(json!([])),
-						),
-						evening_events: hydrate_events(
-							&day.get("evening_events").cloned().unwrap_or(json!([])),
-						),
+						morning_events: hydrate_events(day.get("morning_events")),
+						afternoon_events: hydrate_events(day.get("afternoon_events")),
+						evening_events: hydrate_events(day.get("evening_events")),
 						date,
 					});
 				}
 			}
 
-			let unassigned_events = hydrate_events(
-				&itinerary_json
-					.get("unassigned_events")
-					.cloned()
-					.unwrap_or(json!([])),
-			);
+			let unassigned_events = hydrate_events(itinerary_json.get("unassigned_events"));
 
 			// Create HttpItinerary with hydrated events
-			let start_date_str = itinerary_json
-				.get("start_date")
-				.and_then(|v| v.as_str())
-				.unwrap_or("2025-01-01");
-			let end_date_str = itinerary_json
-				.get("end_date")
-				.and_then(|v| v.as_str())
-				.unwrap_or("2025-01-01");
-			let start_date = chrono::NaiveDate::parse_from_str(start_date_str, "%Y-%m-%d")
-				.unwrap_or_else(|_| chrono::NaiveDate::from_ymd_opt(2025, 1, 1).unwrap());
-			let end_date = chrono::NaiveDate::parse_from_str(end_date_str, "%Y-%m-%d")
-				.unwrap_or_else(|_| chrono::NaiveDate::from_ymd_opt(2025, 1, 1).unwrap());
 			let title = itinerary_json
 				.get("title")
 				.and_then(|v| v.as_str())
@@ -1378,44 +1353,97 @@ impl Tool for RespondToUserTool {
 
 			let mut itinerary = HttpItinerary {
 				id: 0, // Temporary, will be set after insert
-				start_date,
-				end_date,
+				start_date: dates.start_date,
+				end_date: dates.end_date,
 				event_days,
 				chat_session_id: Some(chat_id),
 				title,
 				unassigned_events,
+				starred: false,
 			};
 
 			// Extract unassigned event IDs
 			let unassigned_event_ids: Vec<i32> =
 				itinerary.unassigned_events.iter().map(|e| e.id).collect();
 
-			// Insert itinerary into database
-			let itinerary_id = sqlx::query!(
-			r#"
-			INSERT INTO itineraries (account_id, is_public, start_date, end_date, chat_session_id, saved, title, unassigned_event_ids)
-			VALUES ($1, FALSE, $2, $3, $4, FALSE, $5, $6)
-			RETURNING id;
+			// Revise the itinerary this chat already produced rather than leaving a trail of them.
+			// Saved itineraries are left as the user saved them, so a new one is forked instead.
+			let existing_id = sqlx::query_scalar!(
+				r#"
+			SELECT id FROM itineraries
+			WHERE chat_session_id = $1 AND account_id = $2 AND saved = FALSE AND deleted_at IS NULL
+			ORDER BY id DESC
+			LIMIT 1
+			FOR UPDATE;
 			"#,
-			user_id,
-			itinerary.start_date,
-			itinerary.end_date,
-			chat_id,
-			itinerary.title,
-			&unassigned_event_ids
-		)
-		.fetch_one(&self.pool)
-		.await
-		.map_err(|e| format!("Failed to insert itinerary: {}", e))?
-		.id;
+				chat_id,
+				user_id
+			)
+			.fetch_optional(&mut *tx)
+			.await
+			.map_err(|e| format!("Failed to find chat itinerary: {}", e))?;
 
-			info!(
-				target: "orchestrator_tool",
-				tool = "respond_to_user",
-				chat_id = chat_id,
-				itinerary_id = itinerary_id,
-				"Created itinerary in database"
-			);
+			let itinerary_id = match existing_id {
+				Some(id) => {
+					sqlx::query!(
+						r#"
+					UPDATE itineraries
+					SET start_date=$1, end_date=$2, title=$3, unassigned_event_ids=$4
+					WHERE id=$5;
+					"#,
+						itinerary.start_date,
+						itinerary.end_date,
+						itinerary.title,
+						&unassigned_event_ids,
+						id
+					)
+					.execute(&mut *tx)
+					.await
+					.map_err(|e| format!("Failed to update itinerary: {}", e))?;
+
+					sqlx::query!(r#"DELETE FROM event_list WHERE itinerary_id=$1;"#, id)
+						.execute(&mut *tx)
+						.await
+						.map_err(|e| format!("Failed to clear event list: {}", e))?;
+
+					info!(
+						target: "orchestrator_tool",
+						tool = "respond_to_user",
+						chat_id = chat_id,
+						itinerary_id = id,
+						"Updated itinerary in database"
+					);
+					id
+				}
+				None => {
+					let id = sqlx::query!(
+						r#"
+					INSERT INTO itineraries (account_id, is_public, start_date, end_date, chat_session_id, saved, title, unassigned_event_ids)
+					VALUES ($1, FALSE, $2, $3, $4, FALSE, $5, $6)
+					RETURNING id;
+					"#,
+						user_id,
+						itinerary.start_date,
+						itinerary.end_date,
+						chat_id,
+						itinerary.title,
+						&unassigned_event_ids
+					)
+					.fetch_one(&mut *tx)
+					.await
+					.map_err(|e| format!("Failed to insert itinerary: {}", e))?
+					.id;
+
+					info!(
+						target: "orchestrator_tool",
+						tool = "respond_to_user",
+						chat_id = chat_id,
+						itinerary_id = id,
+						"Created itinerary in database"
+					);
+					id
+				}
+			};
 
 			// Update itinerary ID for insert_event_list
 			itinerary.id = itinerary_id;
@@ -1424,7 +1452,7 @@ impl Tool for RespondToUserTool {
 			let num_days = itinerary.event_days.len();
 
 			// Insert all events into event_list table
-			insert_event_list(itinerary, &self.pool)
+			insert_event_list(itinerary, &mut *tx)
 				.await
 				.map_err(|e| format!("Failed to insert event list: {}", e))?;
 
@@ -1440,9 +1468,31 @@ impl Tool for RespondToUserTool {
 				"I've created your travel itinerary! It includes {} days with events scheduled throughout. You can view and edit it in your saved itineraries.",
 				num_days
 			);
-			let message = optional_message
+			let mut message = optional_message
 				.map(|s| s.to_string())
 				.unwrap_or(default_message);
+			// Explain schedule changes the optimizer made for the weather and the budget
+			for (key, heading) in [
+				("weather_notes", "I adjusted the plan for the weather:"),
+				(
+					"budget_notes",
+					"Some events are unassigned to keep within your daily budget:",
+				),
+			] {
+				let Some(notes) = itinerary_json
+					.get(key)
+					.and_then(|v| v.as_array())
+					.filter(|notes| !notes.is_empty())
+				else {
+					continue;
+				};
+				message.push_str("\n\n");
+				message.push_str(heading);
+				for note in notes.iter().filter_map(|n| n.as_str()) {
+					message.push_str("\n- ");
+					message.push_str(note);
+				}
+			}
 
 			// Insert message with itinerary_id
 			let record = sqlx::query!(
@@ -1455,10 +1505,15 @@ impl Tool for RespondToUserTool {
 				itinerary_id,
 				message
 			)
-			.fetch_one(&self.pool)
+			.fetch_one(&mut *tx)
 			.await
 			.map_err(|e| format!("Database error: {}", e))?;
 
+			tx.commit()
+				.await
+				.map_err(|e| format!("Failed to commit itinerary: {}", e))?;
+			ITINERARIES_GENERATED_TOTAL.inc();
+
 			info!(
 				target: "orchestrator_tool",
 				tool = "respond_to_user",
@@ -1487,6 +1542,7 @@ impl Tool for RespondToUserTool {
 			.fetch_one(&self.pool)
 			.await
 			.map_err(|e| format!("Database error: {}", e))?;
+			CLARIFICATIONS_ASKED_TOTAL.inc();
 
 			info!(
 				target: "orchestrator_tool",
@@ -1505,29 +1561,32 @@ impl Tool for RespondToUserTool {
 		{
 			let pool = self.pool.clone();
 			let chat_id_copy = chat_id;
-			tokio::spawn(async move {
-				if chat_id_copy > 0 {
-					if let Err(e) = sqlx::query!(
-						r#"
+			tokio::spawn(
+				async move {
+					if chat_id_copy > 0 {
+						if let Err(e) = sqlx::query!(
+							r#"
 						UPDATE chat_sessions
 						SET llm_progress = $1
 						WHERE id = $2;
 						"#,
-						crate::sql_models::LlmProgress::Ready as _,
-						chat_id_copy,
-					)
-					.execute(&pool)
-					.await
-					{
-						tracing::error!(
-							target: "orchestrator_pipeline",
-							chat_session_id = chat_id_copy,
-							error = %e,
-							"Failed to reset llm_progress to Ready after respond_to_user"
-						);
+							crate::sql_models::LlmProgress::Ready as _,
+							chat_id_copy,
+						)
+						.execute(&pool)
+						.await
+						{
+							tracing::error!(
+								target: "orchestrator_pipeline",
+								chat_session_id = chat_id_copy,
+								error = %e,
+								"Failed to reset llm_progress to Ready after respond_to_user"
+							);
+						}
 					}
 				}
-			});
+				.in_current_span(),
+			);
 		}
 
 		// Return a special marker that send_message_to_llm can detect
@@ -1545,7 +1604,7 @@ impl Tool for RespondToUserTool {
 		// Track this tool execution
 		track_tool_execution(
 			&self.context_store,
-			&self.chat_session_id,
+			self.chat_session_id,
 			"respond_to_user",
 			&input_clone,
 			&result,
@@ -1562,18 +1621,21 @@ impl Tool for RespondToUserTool {
 #[derive(Clone)]
 pub struct UpdateTripContextTool {
 	llm: Arc<dyn LLM + Send + Sync>,
-	chat_session_id: Arc<AtomicI32>,
+	pool: PgPool,
+	chat_session_id: i32,
 	context_store: SharedContextStore,
 }
 
 impl UpdateTripContextTool {
 	pub fn new(
 		llm: Arc<dyn LLM + Send + Sync>,
-		chat_session_id: Arc<AtomicI32>,
+		pool: PgPool,
+		chat_session_id: i32,
 		context_store: SharedContextStore,
 	) -> Self {
 		Self {
 			llm,
+			pool,
 			chat_session_id,
 			context_store,
 		}
@@ -1605,7 +1667,7 @@ impl Tool for UpdateTripContextTool {
 
 		crate::tool_trace!(agent: "task", tool: "update_trip_context", status: "start");
 
-		let chat_id = self.chat_session_id.load(Ordering::Relaxed);
+		let chat_id = self.chat_session_id;
 		if chat_id == 0 {
 			return Err("chat_session_id not set".into());
 		}
@@ -1671,6 +1733,7 @@ impl Tool for UpdateTripContextTool {
 			current_start_date = ?current_context.start_date,
 			current_end_date = ?current_context.end_date,
 			current_budget = ?current_context.budget,
+			current_travelers = ?current_context.travelers,
 			current_preferences = ?current_context.preferences,
 			current_constraints = ?current_context.constraints,
 			"Current trip context details"
@@ -1678,35 +1741,7 @@ impl Tool for UpdateTripContextTool {
 
 		// Use LLM to extract trip information from the messages
 		let extraction_prompt = format!(
-			r#"Extract trip planning information from these recent user messages. Return ONLY a JSON object.
-
-Current context (preserve these if not mentioned in new messages):
-- destination: {}
-- start_date: {}
-- end_date: {}
-- budget: {}
-- preferences: {}
-
-Recent user messages (newest first):
-"{}"
-
-IMPORTANT: Extract information from ALL the messages above, not just the first one.
-
-Return JSON with the information found across all messages:
-{{
-  "destination": "string or null",
-  "start_date": "YYYY-MM-DD or null",
-  "end_date": "YYYY-MM-DD or null",
-  "budget": number or null,
-  "preferences": ["array", "of", "strings"] or [],
-  "action": "create|modify|view|delete or null"
-}}
-
-Examples:
-- "Brazil" + "10/8 to 10/20" → {{"destination": "Brazil", "start_date": "2023-10-08", "end_date": "2023-10-20"}}
-- "no preferences" → {{"preferences": []}}
-
-Return valid JSON only."#,
+			include_str!("../prompts/update_trip_context.md"),
 			current_context.destination.as_deref().unwrap_or("null"),
 			current_context.start_date.as_deref().unwrap_or("null"),
 			current_context.end_date.as_deref().unwrap_or("null"),
@@ -1715,6 +1750,21 @@ Return valid JSON only."#,
 				.map(|b| b.to_string())
 				.as_deref()
 				.unwrap_or("null"),
+			current_context
+				.travelers
+				.map(|t| t.to_string())
+				.as_deref()
+				.unwrap_or("null"),
+			current_context
+				.budget_is_per_person
+				.map(|p| p.to_string())
+				.as_deref()
+				.unwrap_or("null"),
+			current_context
+				.daily_budget_usd
+				.map(|b| b.to_string())
+				.as_deref()
+				.unwrap_or("null"),
 			serde_json::to_string(&current_context.preferences)
 				.unwrap_or_else(|_| "[]".to_string()),
 			user_messages
@@ -1751,32 +1801,7 @@ Return valid JSON only."#,
 
 		// Merge with current context (only update non-null fields)
 		let mut updated_context = current_context;
-
-		if let Some(dest) = extracted["destination"].as_str() {
-			updated_context.destination = Some(dest.to_string());
-		}
-		if let Some(start) = extracted["start_date"].as_str() {
-			updated_context.start_date = Some(start.to_string());
-		}
-		if let Some(end) = extracted["end_date"].as_str() {
-			updated_context.end_date = Some(end.to_string());
-		}
-		if let Some(budget) = extracted["budget"].as_f64() {
-			updated_context.budget = Some(budget);
-		}
-		if let Some(prefs) = extracted["preferences"].as_array() {
-			let new_prefs: Vec<String> = prefs
-				.iter()
-				.filter_map(|v| v.as_str().map(|s| s.to_string()))
-				.collect();
-			if !new_prefs.is_empty() {
-				updated_context.preferences.extend(new_prefs);
-				updated_context.preferences.dedup();
-			}
-		}
-		if let Some(action) = extracted["action"].as_str() {
-			updated_context.action = Some(action.to_string());
-		}
+		updated_context.merge_extracted(&extracted);
 
 		// Save updated context
 		{
@@ -1796,6 +1821,7 @@ Return valid JSON only."#,
 					updated_start_date = ?updated_context.start_date,
 					updated_end_date = ?updated_context.end_date,
 					updated_budget = ?updated_context.budget,
+					updated_travelers = ?updated_context.travelers,
 					updated_preferences = ?updated_context.preferences,
 					updated_constraints = ?updated_context.constraints,
 					"Updated trip context details"
@@ -1803,6 +1829,11 @@ Return valid JSON only."#,
 			}
 		}
 
+		// Persist so the trip details survive once the in-memory entry is gone
+		save_trip_context(&self.pool, chat_id, &updated_context)
+			.await
+			.map_err(|e| format!("Database error: {}", e))?;
+
 		// Determine what's still missing - ONLY require destination and dates
 		// Budget, preferences, and constraints are ALL optional
 		let mut missing = Vec::new();
@@ -1815,7 +1846,7 @@ Return valid JSON only."#,
 		if updated_context.end_date.is_none() {
 			missing.push("end_date");
 		}
-		// Budget, preferences, and constraints are optional - don't add to missing
+		// Budget, travelers, preferences, and constraints are optional - don't add to missing
 
 		// Check if we've asked clarification at least once
 		let has_asked_before = updated_context.asked_clarification;
@@ -1852,7 +1883,7 @@ Return valid JSON only."#,
 
 		track_tool_execution(
 			&self.context_store,
-			&self.chat_session_id,
+			self.chat_session_id,
 			"update_trip_context",
 			&input_clone,
 			&result_str,
@@ -1869,16 +1900,12 @@ Return valid JSON only."#,
 #[derive(Clone)]
 pub struct UpdateChatTitleTool {
 	pool: PgPool,
-	chat_session_id: Arc<AtomicI32>,
+	chat_session_id: i32,
 	context_store: SharedContextStore,
 }
 
 impl UpdateChatTitleTool {
-	pub fn new(
-		pool: PgPool,
-		chat_session_id: Arc<AtomicI32>,
-		context_store: SharedContextStore,
-	) -> Self {
+	pub fn new(pool: PgPool, chat_session_id: i32, context_store: SharedContextStore) -> Self {
 		Self {
 			pool,
 			chat_session_id,
@@ -1912,7 +1939,7 @@ impl Tool for UpdateChatTitleTool {
 
 		crate::tool_trace!(agent: "task", tool: "update_chat_title", status: "start");
 
-		let chat_id = self.chat_session_id.load(Ordering::Relaxed);
+		let chat_id = self.chat_session_id;
 		if chat_id == 0 {
 			return Err("chat_session_id not set".into());
 		}
@@ -2029,7 +2056,7 @@ impl Tool for UpdateChatTitleTool {
 
 		track_tool_execution(
 			&self.context_store,
-			&self.chat_session_id,
+			self.chat_session_id,
 			"update_chat_title",
 			&input_clone,
 			&result.to_string(),
@@ -2040,6 +2067,45 @@ impl Tool for UpdateChatTitleTool {
 	}
 }
 
+/// Loads the persisted trip context for a chat session, if one was saved.
+///
+/// Rows that no longer deserialize into a `TripContext` are treated as missing.
+pub(crate) async fn load_trip_context(
+	pool: &PgPool,
+	chat_id: i32,
+) -> Result<Option<TripContext>, sqlx::Error> {
+	let record = sqlx::query!(
+		r#"SELECT context FROM trip_contexts WHERE chat_session_id = $1"#,
+		chat_id
+	)
+	.fetch_optional(pool)
+	.await?;
+
+	Ok(record.and_then(|r| serde_json::from_value(r.context).ok()))
+}
+
+/// Upserts the trip context for a chat session into `trip_contexts`.
+pub(crate) async fn save_trip_context(
+	pool: &PgPool,
+	chat_id: i32,
+	trip_context: &TripContext,
+) -> Result<(), sqlx::Error> {
+	sqlx::query!(
+		r#"
+		INSERT INTO trip_contexts (chat_session_id, context, updated_at)
+		VALUES ($1, $2, NOW())
+		ON CONFLICT (chat_session_id)
+		DO UPDATE SET context = EXCLUDED.context, updated_at = NOW();
+		"#,
+		chat_id,
+		json!(trip_context)
+	)
+	.execute(pool)
+	.await?;
+
+	Ok(())
+}
+
 /// Gets the tools used by the Task Agent to build planning context.
 /// These tools are focused on:
 /// - retrieving user profile
@@ -2049,41 +2115,42 @@ impl Tool for UpdateChatTitleTool {
 pub fn task_tools(
 	llm: Arc<dyn LLM + Send + Sync>,
 	pool: PgPool,
-	chat_session_id: Arc<AtomicI32>,
-	user_id: Arc<AtomicI32>,
+	chat_session_id: i32,
+	user_id: i32,
 	context_store: SharedContextStore,
 ) -> Vec<Arc<dyn Tool>> {
 	vec![
 		Arc::new(ParseUserIntentTool::new(
 			Arc::clone(&llm),
-			Arc::clone(&chat_session_id),
+			chat_session_id,
 			context_store.clone(),
 		)),
 		Arc::new(RetrieveChatContextTool::new(
 			pool.clone(),
-			Arc::clone(&chat_session_id),
+			chat_session_id,
 			context_store.clone(),
 		)),
 		Arc::new(RetrieveUserProfileTool::new(
 			pool.clone(),
-			Arc::clone(&chat_session_id),
-			Arc::clone(&user_id),
+			chat_session_id,
+			user_id,
 			context_store.clone(),
 		)),
 		Arc::new(UpdateTripContextTool::new(
 			Arc::clone(&llm),
-			Arc::clone(&chat_session_id),
+			pool.clone(),
+			chat_session_id,
 			context_store.clone(),
 		)),
 		Arc::new(UpdateChatTitleTool::new(
 			pool.clone(),
-			Arc::clone(&chat_session_id),
+			chat_session_id,
 			context_store.clone(),
 		)),
 		Arc::new(AskForClarificationTool::new(
 			Arc::clone(&llm),
 			pool.clone(),
-			Arc::clone(&chat_session_id),
+			chat_session_id,
 			context_store.clone(),
 		)),
 	]
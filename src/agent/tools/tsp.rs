@@ -1,5 +1,6 @@
 // tsp.rs - vibe coded so don't blame me for bugs
 
+use crate::global::{MAX_2OPT_ITERATIONS, MAX_ROUTE_EXACT_SIZE};
 use serde::{Deserialize, Serialize};
 
 #[derive(Clone, Copy, Debug, Serialize, Deserialize)]
@@ -101,12 +102,18 @@ fn nearest_neighbor_path(points: &[Pt], start: usize, end: usize) -> Vec<usize>
 // ---------------------------
 //
 
-fn two_opt_cycle(points: &[Pt], route: &mut Vec<usize>) {
+/// Improves `route` in place by reversing segments while that shortens it.
+/// * `route[0]` and the last entry never move, so this works for both closed
+///   cycles (which end with the start index again) and fixed end paths
+/// * Stops after `MAX_2OPT_ITERATIONS` passes even if still improving
+fn two_opt(points: &[Pt], route: &mut [usize]) {
 	let n = route.len();
-	let mut improved = true;
+	if n < 4 {
+		return;
+	}
 
-	while improved {
-		improved = false;
+	for _ in 0..MAX_2OPT_ITERATIONS {
+		let mut improved = false;
 		for i in 1..n - 2 {
 			for j in i + 1..n - 1 {
 				let a = route[i - 1];
@@ -117,37 +124,15 @@ fn two_opt_cycle(points: &[Pt], route: &mut Vec<usize>) {
 				let before = dist(points[a], points[b]) + dist(points[c], points[d]);
 				let after = dist(points[a], points[c]) + dist(points[b], points[d]);
 
-				if after < before {
+				// Epsilon keeps float noise from flipping equal length segments forever
+				if after + 1e-12 < before {
 					route[i..=j].reverse();
 					improved = true;
 				}
 			}
 		}
-	}
-}
-
-fn two_opt_path(points: &[Pt], route: &mut Vec<usize>) {
-	let n = route.len();
-	let mut improved = true;
-
-	// route[0] and route[n-1] must not move
-	while improved {
-		improved = false;
-		for i in 1..n - 3 {
-			for j in i + 1..n - 2 {
-				let a = route[i - 1];
-				let b = route[i];
-				let c = route[j];
-				let d = route[j + 1];
-
-				let before = dist(points[a], points[b]) + dist(points[c], points[d]);
-				let after = dist(points[a], points[c]) + dist(points[b], points[d]);
-
-				if after < before {
-					route[i..=j].reverse();
-					improved = true;
-				}
-			}
+		if !improved {
+			break;
 		}
 	}
 }
@@ -163,17 +148,28 @@ pub enum EndpointMode {
 	Path,
 }
 
+/// Orders `points` starting at `points[0]`, returns indices into `points`.
+/// * `Circle` returns to `points[0]` at the end, `Path` ends at the last point
+/// * Routes over `MAX_ROUTE_EXACT_SIZE` points keep the nearest neighbor tour
 pub fn compute_route(points: &[Pt], mode: EndpointMode) -> Vec<usize> {
-	match mode {
-		EndpointMode::Circle => {
-			let mut route = nearest_neighbor_cycle(points, 0);
-			two_opt_cycle(points, &mut route);
-			route
-		}
-		EndpointMode::Path => {
-			let mut route = nearest_neighbor_path(points, 0, points.len() - 1);
-			two_opt_path(points, &mut route);
-			route
-		}
+	if points.is_empty() {
+		return Vec::new();
+	}
+	let mut route = match mode {
+		EndpointMode::Circle => nearest_neighbor_cycle(points, 0),
+		EndpointMode::Path if points.len() == 1 => vec![0],
+		EndpointMode::Path => nearest_neighbor_path(points, 0, points.len() - 1),
+	};
+	if points.len() <= MAX_ROUTE_EXACT_SIZE {
+		two_opt(points, &mut route);
 	}
+	route
+}
+
+/// Length of `route` as returned by `compute_route`, in the same units as `Pt`
+pub fn route_length(points: &[Pt], route: &[usize]) -> f64 {
+	route
+		.windows(2)
+		.map(|w| dist(points[w[0]], points[w[1]]))
+		.sum()
 }
@@ -0,0 +1,209 @@
+/*
+ * src/agent/tools/weather.rs
+ *
+ * File for weather aware scheduling
+ *
+ * Purpose:
+ *   Look up forecasts for the trip's days and move outdoor events
+ *   off rainy days, without asking the LLM.
+ */
+
+use async_trait::async_trait;
+use chrono::NaiveDate;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use tracing::warn;
+
+use crate::agent::models::event::Event;
+use crate::agent::tools::hours::{OpenStatus, is_open};
+use crate::agent::tools::meals::is_meal_venue;
+use crate::agent::tools::modify::{PlannedDay, PlannedItinerary};
+use crate::global::WEATHER_PROVIDER;
+use crate::sql_models::TimeOfDay;
+
+/// Google place types of venues that are no fun in the rain
+const OUTDOOR_TYPES: [&str; 7] = [
+	"hiking_area",
+	"beach",
+	"garden",
+	"botanical_garden",
+	"campground",
+	"zoo",
+	"walking_tour",
+];
+
+/// What the weather is expected to be like on a day
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Forecast {
+	Dry,
+	Rain,
+	/// No forecast is available, e.g. the date is too far out or no provider is configured
+	Unknown,
+}
+
+/// Something that can forecast the weather at a place.
+#[async_trait]
+pub trait WeatherProvider: Send + Sync {
+	/// Forecast for `date` at `lat`, `lng`, `Unknown` when it can't be looked up
+	async fn get_forecast(&self, lat: f64, lng: f64, date: NaiveDate) -> Forecast;
+}
+
+/// Shared weather provider handed to the optimizer.
+pub type SharedWeatherProvider = Arc<dyn WeatherProvider>;
+
+/// Never knows the weather, so schedules are left as drafted.
+///
+/// Stand-in until a forecast service is configured.
+pub struct NoWeatherProvider;
+
+#[async_trait]
+impl WeatherProvider for NoWeatherProvider {
+	async fn get_forecast(&self, _lat: f64, _lng: f64, _date: NaiveDate) -> Forecast {
+		Forecast::Unknown
+	}
+}
+
+/// The provider named by the `WEATHER_PROVIDER` env var.
+/// * Unset or `none` uses [NoWeatherProvider]
+/// * Unknown names are logged and also use [NoWeatherProvider]
+pub fn weather_provider_from_env() -> SharedWeatherProvider {
+	match std::env::var(WEATHER_PROVIDER).as_deref() {
+		Err(_) | Ok("") | Ok("none") => Arc::new(NoWeatherProvider),
+		Ok(name) => {
+			warn!(
+				target: "optimize_tools",
+				provider = name,
+				"Unknown {WEATHER_PROVIDER}, weather won't be considered"
+			);
+			Arc::new(NoWeatherProvider)
+		}
+	}
+}
+
+/// Whether the event happens outside, from its Google place types
+pub fn is_outdoor(event: &Event) -> bool {
+	event
+		.types
+		.iter()
+		.flat_map(|types| types.split(','))
+		.chain(event.event_type.as_deref())
+		.map(|t| t.trim().to_lowercase())
+		.any(|t| t.ends_with("park") || OUTDOOR_TYPES.contains(&t.as_str()))
+}
+
+/// Event ids in `block` of `day`
+fn block_ids<'a>(day: &'a PlannedDay, block: &TimeOfDay) -> &'a [i32] {
+	match block {
+		TimeOfDay::Morning => &day.morning,
+		TimeOfDay::Afternoon => &day.afternoon,
+		TimeOfDay::Evening => &day.evening,
+	}
+}
+
+/// Whether `event` can be in `block` on `date` as far as opening hours are known
+fn can_schedule(event: &Event, date: NaiveDate, block: TimeOfDay) -> bool {
+	is_open(event, date, block) != OpenStatus::Closed
+}
+
+/// Moves outdoor events off days with rain in `forecasts`, returning the adjusted itinerary
+/// and a sentence explaining each change.
+/// * An outdoor event swaps places with an indoor event in the same block of the nearest
+///   dry day, as long as both venues are open in their new slot
+/// * Otherwise it's replaced by the best of `ranked` that is indoor, not a restaurant,
+///   not scheduled yet and open in that block, and becomes unassigned
+/// * Otherwise it stays where it is
+///
+/// Events missing from `events` are left alone.
+pub fn adjust_for_weather(
+	draft: &PlannedItinerary,
+	forecasts: &HashMap<NaiveDate, Forecast>,
+	events: &HashMap<i32, &Event>,
+	ranked: &[i32],
+) -> (PlannedItinerary, Vec<String>) {
+	let mut itinerary = draft.clone();
+	let mut explanations = Vec::new();
+	let forecast = |date: NaiveDate| forecasts.get(&date).copied().unwrap_or(Forecast::Unknown);
+	let outdoor = |id: i32| events.get(&id).is_some_and(|e| is_outdoor(e));
+	let name = |id: i32| events[&id].event_name.as_str();
+	let mut scheduled: HashSet<i32> = itinerary
+		.days
+		.iter()
+		.flat_map(|day| day.morning.iter().chain(&day.afternoon).chain(&day.evening))
+		.copied()
+		.collect();
+
+	for rainy in 0..itinerary.days.len() {
+		let rainy_date = itinerary.days[rainy].date;
+		if forecast(rainy_date) != Forecast::Rain {
+			continue;
+		}
+		// Nearest dry days first, earlier ones on ties
+		let mut dry: Vec<usize> = (0..itinerary.days.len())
+			.filter(|&i| forecast(itinerary.days[i].date) == Forecast::Dry)
+			.collect();
+		dry.sort_by_key(|&i| (i.abs_diff(rainy), i));
+
+		for block in [TimeOfDay::Morning, TimeOfDay::Afternoon, TimeOfDay::Evening] {
+			let mut slot = 0;
+			while slot < block_ids(&itinerary.days[rainy], &block).len() {
+				let id = block_ids(&itinerary.days[rainy], &block)[slot];
+				slot += 1;
+				if !outdoor(id) {
+					continue;
+				}
+
+				let swap = dry.iter().find_map(|&other| {
+					let other_date = itinerary.days[other].date;
+					if !can_schedule(events[&id], other_date, block.clone()) {
+						return None;
+					}
+					block_ids(&itinerary.days[other], &block)
+						.iter()
+						.position(|other_id| {
+							events.get(other_id).is_some_and(|e| {
+								!is_outdoor(e)
+									&& !is_meal_venue(e) && can_schedule(e, rainy_date, block.clone())
+							})
+						})
+						.map(|position| (other, position))
+				});
+				if let Some((other, position)) = swap {
+					let other_date = itinerary.days[other].date;
+					let indoor = std::mem::replace(
+						&mut itinerary.days[other].block_mut(&block)[position],
+						id,
+					);
+					itinerary.days[rainy].block_mut(&block)[slot - 1] = indoor;
+					explanations.push(format!(
+						"Rain is forecast on {rainy_date}, so {} moved to {other_date} and {} moved to {rainy_date}.",
+						name(id),
+						name(indoor),
+					));
+					continue;
+				}
+
+				let replacement = ranked.iter().copied().find(|candidate| {
+					!scheduled.contains(candidate)
+						&& events.get(candidate).is_some_and(|e| {
+							!is_outdoor(e)
+								&& !is_meal_venue(e) && can_schedule(e, rainy_date, block.clone())
+						})
+				});
+				if let Some(indoor) = replacement {
+					itinerary.days[rainy].block_mut(&block)[slot - 1] = indoor;
+					scheduled.remove(&id);
+					scheduled.insert(indoor);
+					itinerary.unassigned.retain(|&u| u != indoor);
+					itinerary.unassigned.push(id);
+					explanations.push(format!(
+						"Rain is forecast on {rainy_date}, so {} replaces {}.",
+						name(indoor),
+						name(id),
+					));
+				}
+			}
+		}
+	}
+
+	(itinerary, explanations)
+}
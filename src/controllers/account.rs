@@ -9,12 +9,23 @@
 
 use argon2::{
 	Argon2,
-	password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString, rand_core::OsRng},
+	password_hash::{
+		PasswordHash, PasswordHasher, PasswordVerifier, SaltString,
+		rand_core::{OsRng, RngCore},
+	},
 };
 use axum::{
 	Extension, Json,
-	routing::{get, post},
+	extract::{
+		DefaultBodyLimit, Multipart, Path, Query,
+		multipart::{Field, MultipartError},
+	},
+	http::{StatusCode, header},
+	response::IntoResponse,
+	routing::{delete, get, patch, post},
 };
+use sha2::{Digest, Sha256};
+use std::path::PathBuf;
 #[cfg(test)]
 use tower_cookies::cookie::CookieJar;
 
@@ -33,12 +44,23 @@ use sqlx::PgPool;
 use tracing::debug;
 use utoipa::OpenApi;
 
+use crate::db::events::{fetch_events_by_ids, fetch_favorite_event_ids};
 use crate::http_models::account::*;
-use crate::middleware::{AuthUser, middleware_auth};
+use crate::mailer::SharedMailer;
+use crate::middleware::{
+	AuthSession, AuthUser, ClientInfo, TokenClaims, middleware_auth, middleware_rate_limit,
+	sign_token,
+};
+use crate::oauth::SharedGoogleOAuth;
 use crate::{
 	controllers::AxumRouter,
-	error::{ApiResult, AppError},
-	sql_models::{BudgetBucket, RiskTolerence, account::AccountRow},
+	error::{ApiResult, AppError, ErrorBody},
+	global::{
+		AUTH_ACTIVITY_LEN, DEFAULT_PROFILE_PICTURE_DIR, EMAIL_VERIFICATION_TOKEN_EXP_SECONDS,
+		LOGIN_LOCKOUT_SECONDS, MAX_LOGIN_ATTEMPTS, PASSWORD_RESET_REQUEST_COOLDOWN_SECONDS,
+		PASSWORD_RESET_TOKEN_EXP_SECONDS, PROFILE_PICTURE_DIR, PROFILE_PICTURE_MAX_BYTES,
+	},
+	sql_models::{AuthEventType, BudgetBucket, Interest, RiskTolerence, account::AccountRow},
 	swagger::SecurityAddon,
 };
 
@@ -50,7 +72,24 @@ use crate::{
 		api_logout,
 		api_validate,
 		api_update,
-		api_current
+		api_update_preferences,
+		api_current,
+		api_forgot_password,
+		api_reset_password,
+		api_verify_email,
+		api_verify,
+		api_google_oauth,
+		api_new_api_key,
+		api_api_keys,
+		api_delete_api_key,
+		api_sessions,
+		api_revoke_session,
+		api_logout_all,
+		api_activity,
+		api_favorites,
+		api_upload_profile_picture,
+		api_delete_profile_picture,
+		api_profile_picture_file
 	),
 	modifiers(&SecurityAddon),
 	security(
@@ -83,32 +122,64 @@ impl CookieStore for CookieJar {
 	}
 }
 
+/// Cookies for the Swagger UI, whose cookie jar only keeps the `auth-token` cookie
+/// when it's `SameSite=None; Secure`.
+#[cfg(debug_assertions)]
+pub struct SwaggerCookies(pub Cookies);
+#[cfg(debug_assertions)]
+impl CookieStore for SwaggerCookies {
+	fn private_add(&mut self, key: &Key, mut cookie: Cookie<'static>) {
+		cookie.set_same_site(SameSite::None);
+		cookie.set_secure(true);
+		self.0.private(key).add(cookie)
+	}
+}
+
+/// How long a fresh `auth-token` cookie and its session last.
+fn cookie_age() -> Duration {
+	#[cfg(not(test))]
+	let age = Duration::days(3);
+
+	// if tests start failing because the cookie expires too fast, just raise it by a little bit
+	#[cfg(test)]
+	let age = Duration::seconds(TEST_COOKIE_EXP_SECONDS);
+
+	age
+}
+
 /// Creates and sets the cookie containing the hashed account id, expiration time, and other data.
 ///
 /// Notes:
-/// - Token format is `user-<id>.<exp>.sign`, where `<exp>` is epoch seconds (UTC) ~3 days out.
+/// - Token format is `user-<id>.<exp>.<session>.<signature>`, where `<exp>` is epoch seconds (UTC) ~3 days out,
+///   `<session>` is the id of the matching row in `sessions`, and `<signature>` is made by [sign_token].
 /// - Cookie name is `auth-token`; in development it uses `SameSite=Lax`, not `Secure`.
-fn set_cookie(account_id: i32, expired: bool, cookies: &mut impl CookieStore, key: &Key) {
+fn set_cookie(
+	account_id: i32,
+	session_id: i32,
+	expired: bool,
+	cookies: &mut impl CookieStore,
+	key: &Key,
+) {
 	// Create token and set cookie as before
 	let domain = option_env!("DOMAIN").unwrap_or("localhost");
 	let app_env = option_env!("APP_ENV").unwrap_or("development");
 	let on_production = app_env == "production";
 
-	// Create a token value (in a real app, this would be a JWT or similar)
-	// Embed expiration epoch seconds inside the token for server-side validation
+	// Embed expiration epoch seconds inside the signed token for server-side validation
 	let (expires, max_age) = if expired {
 		(OffsetDateTime::UNIX_EPOCH, Duration::days(0))
 	} else {
-		#[cfg(not(test))]
-		let age = Duration::days(3);
-
-		// if tests start failing because the cookie expires too fast, just raise it by a little bit
-		#[cfg(test)]
-		let age = Duration::seconds(TEST_COOKIE_EXP_SECONDS);
-
+		let age = cookie_age();
 		(OffsetDateTime::now_utc() + age, age)
 	};
-	let token_value = format!("user-{}.{}.sign", account_id, expires.unix_timestamp());
+	let token_value = sign_token(
+		key,
+		TokenClaims {
+			user_id: account_id,
+			exp: expires.unix_timestamp(),
+			session_id,
+		},
+	);
 
 	debug!(
 		"INFO ->> Generated token: {}. Production is: {}",
@@ -135,6 +206,69 @@ fn set_cookie(account_id: i32, expired: bool, cookies: &mut impl CookieStore, ke
 	cookies.private_add(key, cookie);
 }
 
+/// Records a new session for the account and sets its `auth-token` cookie.
+async fn start_session(
+	account_id: i32,
+	client: &ClientInfo,
+	pool: &PgPool,
+	cookies: &mut impl CookieStore,
+	key: &Key,
+) -> ApiResult<()> {
+	let session_id = sqlx::query!(
+		r#"
+		INSERT INTO sessions (account_id, user_agent, expires_at)
+		VALUES ($1, $2, NOW() + make_interval(secs => $3))
+		RETURNING id
+		"#,
+		account_id,
+		client.user_agent,
+		cookie_age().whole_seconds() as f64
+	)
+	.fetch_one(pool)
+	.await
+	.map_err(AppError::from)?
+	.id;
+
+	set_cookie(account_id, session_id, false, cookies, key);
+
+	Ok(())
+}
+
+/// Appends an entry to the account's `auth_events` audit log.
+async fn record_auth_event(
+	pool: &PgPool,
+	account_id: i32,
+	event_type: AuthEventType,
+	client: &ClientInfo,
+) -> ApiResult<()> {
+	sqlx::query!(
+		r#"
+		INSERT INTO auth_events (account_id, event_type, ip, user_agent)
+		VALUES ($1, $2, $3, $4)
+		"#,
+		account_id,
+		event_type as _,
+		client.ip,
+		client.user_agent
+	)
+	.execute(pool)
+	.await
+	.map_err(AppError::from)?;
+	Ok(())
+}
+
+/// Generates a random 32 byte token, hex encoded.
+fn generate_token() -> String {
+	let mut bytes = [0u8; 32];
+	OsRng.fill_bytes(&mut bytes);
+	bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Hex SHA-256 of a password reset token, what `password_resets` stores in place of the token.
+fn hash_reset_token(token: &str) -> String {
+	hex::encode(Sha256::digest(token.as_bytes()))
+}
+
 /// Create a new user.
 ///
 /// # Method
@@ -147,7 +281,7 @@ fn set_cookie(account_id: i32, expired: bool, cookies: &mut impl CookieStore, ke
 /// - 'password': The user's password (string, required).
 ///
 /// # Responses
-/// - `200 OK` - Signup successful
+/// - `200 OK` - Signup successful, a verification token is emailed to the user
 /// - `400 BAD_REQUEST` - Validation failure (public error)
 /// - `409 CONFLICT` - Email already exists (public error)
 /// - `500 INTERNAL_SERVER_ERROR` - Internal error (private)
@@ -163,11 +297,14 @@ fn set_cookie(account_id: i32, expired: bool, cookies: &mut impl CookieStore, ke
 ///        "password": "password123."
 ///       }'
 /// ```
+///
+/// Notes:
+/// - The account can't use the LLM until the emailed token is sent to `/api/account/verify`.
 #[utoipa::path(
 	post,
 	path="/signup",
 	summary="Create a new account",
-	description="Inserts account details into db (if email isn't already taken), and returns with a cookie.",
+	description="Inserts account details into db (if email isn't already taken), emails a verification token, and returns with a cookie.",
 	request_body(
 		content=SignupRequest,
 		content_type="application/json",
@@ -181,11 +318,11 @@ fn set_cookie(account_id: i32, expired: bool, cookies: &mut impl CookieStore, ke
 	),
 	responses(
 		(status=200, description="Account successfully created"),
-		(status=400, description="Bad Request"),
+		(status=400, description="Bad Request", body=ErrorBody),
 		(status=405, description="Method Not Allowed - Must be POST"),
 		(status=408, description="Request Timed Out"),
-		(status=409, description="Email already in use"),
-		(status=500, description="Internal Server Error")
+		(status=409, description="Email already in use", body=ErrorBody),
+		(status=500, description="Internal Server Error", body=ErrorBody)
 	),
 	security(
 		(),
@@ -195,8 +332,10 @@ fn set_cookie(account_id: i32, expired: bool, cookies: &mut impl CookieStore, ke
 )]
 pub async fn api_signup<C: CookieStore>(
 	cookies: &mut C,
+	client: ClientInfo,
 	Extension(key): Extension<Key>,
 	Extension(pool): Extension<PgPool>,
+	Extension(mailer): Extension<SharedMailer>,
 	Json(payload): Json<SignupRequest>,
 ) -> ApiResult<()> {
 	debug!(
@@ -209,24 +348,6 @@ pub async fn api_signup<C: CookieStore>(
 		return Err(AppError::Validation(validation_error));
 	}
 
-	// Check if user already exists
-	let existing_user_result =
-		sqlx::query!("SELECT id FROM accounts WHERE email = $1", payload.email)
-			.fetch_optional(&pool)
-			.await;
-
-	match existing_user_result {
-		Ok(Some(_)) => {
-			return Err(AppError::Conflict("email already exists".to_string()));
-		}
-		Err(e) => {
-			return Err(AppError::from(e));
-		}
-		Ok(None) => {
-			// User doesn't exist, proceed with signup
-		}
-	}
-
 	// Hash the password
 	let salt = SaltString::generate(&mut OsRng);
 	let argon2 = Argon2::default();
@@ -235,8 +356,9 @@ pub async fn api_signup<C: CookieStore>(
 		.map_err(AppError::from)?
 		.to_string();
 
-	// Insert new user into database
-	let insert_result = sqlx::query!(
+	// Insert new user into database, a taken email violates the unique constraint
+	let mut tx = pool.begin().await.map_err(AppError::from)?;
+	let record = sqlx::query!(
 		"INSERT INTO accounts (email, first_name, last_name, password)
          VALUES ($1, $2, $3, $4)
          RETURNING id",
@@ -245,22 +367,40 @@ pub async fn api_signup<C: CookieStore>(
 		payload.last_name,
 		password_hash
 	)
-	.fetch_one(&pool)
-	.await;
+	.fetch_one(&mut *tx)
+	.await
+	.map_err(|e| match e {
+		sqlx::Error::Database(ref db) if db.is_unique_violation() => AppError::DuplicateEmail,
+		e => AppError::from(e),
+	})?;
 
-	match insert_result {
-		Ok(record) => {
-			debug!(
-				"INFO ->> /api/account/signup 'api_signup' - Created user with id: {}",
-				record.id
-			);
+	let token = generate_token();
+	sqlx::query!(
+		"INSERT INTO email_verifications (token, account_id) VALUES ($1, $2)",
+		token,
+		record.id
+	)
+	.execute(&mut *tx)
+	.await
+	.map_err(AppError::from)?;
+	tx.commit().await.map_err(AppError::from)?;
 
-			set_cookie(record.id, false, cookies, &key);
+	debug!(
+		"INFO ->> /api/account/signup 'api_signup' - Created user with id: {}",
+		record.id
+	);
 
-			Ok(())
-		}
-		Err(e) => Err(AppError::from(e)),
-	}
+	mailer.send(
+		&payload.email,
+		"Verify your email",
+		&format!(
+			"Use this token to verify your email: {}\nIt expires in {} hours.",
+			token,
+			EMAIL_VERIFICATION_TOKEN_EXP_SECONDS / 3600
+		),
+	)?;
+
+	start_session(record.id, &client, &pool, cookies, &key).await
 }
 
 /// Attempt user login
@@ -275,6 +415,13 @@ pub async fn api_signup<C: CookieStore>(
 /// # Responses
 /// - `200 OK` - Login successful with private cookie set
 /// - `400 BAD_REQUEST` - Invalid credentials (public error)
+/// - `429 TOO_MANY_REQUESTS` - Too many recent failures, or the account is locked, see `Retry-After` (public error)
+///
+/// # Lockout
+/// After `MAX_LOGIN_ATTEMPTS` consecutive failures the account is locked for
+/// `LOGIN_LOCKOUT_SECONDS`, answered with `429` and `Retry-After`. This replaces the earlier
+/// `423 LOCKED` until a password reset: the lock now expires by itself, though a password
+/// reset still lifts it early. The failure count starts over after an expired lockout.
 ///
 /// # Examples
 /// ```bash
@@ -287,7 +434,7 @@ pub async fn api_signup<C: CookieStore>(
 /// ```
 ///
 /// Notes:
-/// - Token format is `user-<id>.<exp>.sign`, where `<exp>` is epoch seconds (UTC) ~3 days out.
+/// - Token format is `user-<id>.<exp>.<session>.<signature>`, where `<exp>` is epoch seconds (UTC) ~3 days out.
 /// - Cookie name is `auth-token`; in development it uses `SameSite=Lax`, not `Secure`.
 #[utoipa::path(
 	post,
@@ -304,10 +451,11 @@ pub async fn api_signup<C: CookieStore>(
 	),
 	responses(
 		(status=200, description="Login succeeded"),
-		(status=400, description="Bad Request"),
+		(status=400, description="Bad Request", body=ErrorBody),
 		(status=405, description="Method Not Allowed - Must be POST"),
 		(status=408, description="Request Timed Out"),
-		(status=500, description="Internal Server Error")
+		(status=429, description="Too many failed attempts or account locked, retry after the Retry-After header", body=ErrorBody),
+		(status=500, description="Internal Server Error", body=ErrorBody)
 	),
 	security(
 		(),
@@ -317,6 +465,7 @@ pub async fn api_signup<C: CookieStore>(
 )]
 pub async fn api_login<C: CookieStore>(
 	cookies: &mut C,
+	client: ClientInfo,
 	Extension(key): Extension<Key>,
 	Extension(pool): Extension<PgPool>,
 	Json(payload): Json<LoginRequest>,
@@ -333,7 +482,10 @@ pub async fn api_login<C: CookieStore>(
         SELECT
             id,
             email,
-            password
+            password,
+            CASE WHEN locked_until > NOW()
+                THEN CEIL(EXTRACT(EPOCH FROM locked_until - NOW()))::BIGINT
+            END AS retry_after
         FROM accounts
         WHERE email = $1
         "#,
@@ -344,6 +496,12 @@ pub async fn api_login<C: CookieStore>(
 
 	match user_result {
 		Ok(result) => {
+			// Locked accounts can't login until the lockout ends, even with the right password
+			if let Some(retry_after) = result.retry_after {
+				record_auth_event(&pool, result.id, AuthEventType::LoginFailure, &client).await?;
+				return Err(AppError::TooManyRequests(retry_after.max(1) as u64));
+			}
+
 			// Verify password
 			let parsed_hash = PasswordHash::new(&result.password).map_err(AppError::from)?;
 
@@ -351,19 +509,199 @@ pub async fn api_login<C: CookieStore>(
 			if let Err(_) =
 				Argon2::default().verify_password(payload.password.as_bytes(), &parsed_hash)
 			{
-				return Err(AppError::BadRequest("invalid credentials".to_string()));
+				// The count starts over once an earlier lockout has expired, so one more
+				// failure doesn't lock the account again right away
+				let locked = sqlx::query!(
+					r#"
+					UPDATE accounts SET
+						failed_login_attempts = CASE
+							WHEN locked_until IS NOT NULL AND locked_until <= NOW() THEN 1
+							ELSE failed_login_attempts + 1
+						END,
+						locked_until = CASE
+							WHEN (CASE
+								WHEN locked_until IS NOT NULL AND locked_until <= NOW() THEN 1
+								ELSE failed_login_attempts + 1
+							END) >= $2 THEN NOW() + make_interval(secs => $3)
+							WHEN locked_until <= NOW() THEN NULL
+							ELSE locked_until
+						END
+					WHERE id = $1
+					RETURNING failed_login_attempts >= $2 AS "locked!"
+					"#,
+					result.id,
+					MAX_LOGIN_ATTEMPTS,
+					LOGIN_LOCKOUT_SECONDS as f64
+				)
+				.fetch_one(&pool)
+				.await
+				.map_err(AppError::from)?
+				.locked;
+
+				record_auth_event(&pool, result.id, AuthEventType::LoginFailure, &client).await?;
+				if locked {
+					record_auth_event(&pool, result.id, AuthEventType::AccountLocked, &client)
+						.await?;
+					return Err(AppError::TooManyRequests(LOGIN_LOCKOUT_SECONDS as u64));
+				}
+				return Err(AppError::InvalidCredentials);
 			}
 
-			set_cookie(result.id, false, cookies, &key);
+			sqlx::query!(
+				"UPDATE accounts SET failed_login_attempts = 0, locked_until = NULL WHERE id = $1",
+				result.id
+			)
+			.execute(&pool)
+			.await
+			.map_err(AppError::from)?;
+			record_auth_event(&pool, result.id, AuthEventType::LoginSuccess, &client).await?;
 
-			return Ok(());
+			return start_session(result.id, &client, &pool, cookies, &key).await;
 		}
 		Err(_) => {
-			return Err(AppError::BadRequest("invalid credentials".to_string()));
+			return Err(AppError::InvalidCredentials);
 		}
 	}
 }
 
+/// Sign in with Google, creating an account on first sign-in.
+///
+/// # Method
+/// `POST /api/account/oauth/google`
+///
+/// # Request Body
+/// - `code`: Authorization code from Google's consent screen (string, required).
+/// - `redirect_uri`: Redirect URI the code was issued for (string, required).
+///
+/// # Responses
+/// - `200 OK` - Login successful with private cookie set
+/// - `400 BAD_REQUEST` - Invalid code or unverified Google account (public error)
+/// - `429 TOO_MANY_REQUESTS` - The account is locked by failed logins, see `Retry-After` (public error)
+/// - `500 INTERNAL_SERVER_ERROR` - Internal error (private)
+///
+/// # Examples
+/// ```bash
+/// curl -X POST http://localhost:3001/api/account/oauth/google
+///   -H "Content-Type: application/json"
+///   -d '{
+///        "code": "4/0AbC...",
+///        "redirect_uri": "http://localhost:3000/login/google"
+///       }'
+/// ```
+///
+/// Notes:
+/// - Existing accounts are matched by email.
+/// - New accounts get a random password and `oauth_provider` set to `google`.
+/// - Google only returns verified emails, so the account's email is marked verified.
+#[utoipa::path(
+	post,
+	path="/oauth/google",
+	summary="Sign in with Google",
+	description="Exchanges a Google authorization code, then logs in or creates the matching account and returns with a cookie.",
+	request_body(
+		content=GoogleAuthRequest,
+		content_type="application/json",
+		example=json!({
+			"code": "4/0AbCdEf",
+			"redirect_uri": "http://localhost:3000/login/google"
+		})
+	),
+	responses(
+		(status=200, description="Login succeeded"),
+		(status=400, description="Bad Request", body=ErrorBody),
+		(status=405, description="Method Not Allowed - Must be POST"),
+		(status=408, description="Request Timed Out"),
+		(status=429, description="Account locked, retry after the Retry-After header", body=ErrorBody),
+		(status=500, description="Internal Server Error", body=ErrorBody)
+	),
+	security(
+		(),
+		("set-cookie"=[])
+	),
+	tag="Account"
+)]
+pub async fn api_google_oauth<C: CookieStore>(
+	cookies: &mut C,
+	client: ClientInfo,
+	Extension(key): Extension<Key>,
+	Extension(pool): Extension<PgPool>,
+	Extension(google): Extension<SharedGoogleOAuth>,
+	Json(payload): Json<GoogleAuthRequest>,
+) -> ApiResult<()> {
+	debug!(
+		"HANDLER ->> /api/account/oauth/google 'api_google_oauth' - Redirect URI: {}",
+		payload.redirect_uri
+	);
+
+	let identity = google
+		.verify_code(&payload.code, &payload.redirect_uri)
+		.await?;
+
+	let existing = sqlx::query!(
+		r#"
+		SELECT
+			id,
+			CASE WHEN locked_until > NOW()
+				THEN CEIL(EXTRACT(EPOCH FROM locked_until - NOW()))::BIGINT
+			END AS retry_after
+		FROM accounts
+		WHERE email = $1
+		"#,
+		identity.email
+	)
+	.fetch_optional(&pool)
+	.await
+	.map_err(AppError::from)?;
+
+	let account_id = match existing {
+		// Google vouching for the email doesn't lift a lockout
+		Some(row) => match row.retry_after {
+			Some(retry_after) => return Err(AppError::TooManyRequests(retry_after.max(1) as u64)),
+			None => {
+				sqlx::query!(
+					"UPDATE accounts SET email_verified = TRUE WHERE id = $1",
+					row.id
+				)
+				.execute(&pool)
+				.await
+				.map_err(AppError::from)?;
+				row.id
+			}
+		},
+		None => {
+			// Nobody knows this password, the account can only sign in through
+			// Google until the user sets one with a password reset
+			let salt = SaltString::generate(&mut OsRng);
+			let password_hash = Argon2::default()
+				.hash_password(generate_token().as_bytes(), &salt)
+				.map_err(AppError::from)?
+				.to_string();
+
+			let record = sqlx::query!(
+				"INSERT INTO accounts (email, first_name, last_name, password, oauth_provider, email_verified)
+				VALUES ($1, $2, $3, $4, 'google', TRUE)
+				RETURNING id",
+				identity.email,
+				identity.given_name.unwrap_or_default(),
+				identity.family_name.unwrap_or_default(),
+				password_hash
+			)
+			.fetch_one(&pool)
+			.await
+			.map_err(AppError::from)?;
+
+			debug!(
+				"INFO ->> /api/account/oauth/google 'api_google_oauth' - Created user with id: {}",
+				record.id
+			);
+			record.id
+		}
+	};
+
+	record_auth_event(&pool, account_id, AuthEventType::LoginSuccess, &client).await?;
+	start_session(account_id, &client, &pool, cookies, &key).await
+}
+
 /// Returns whether the user has a valid auth token.
 /// Hit this route to validate the `auth-token` private cookie.
 ///
@@ -384,11 +722,11 @@ pub async fn api_login<C: CookieStore>(
 	description="Returns 200 if token is valid, or 401 if invalid or nonexistant.",
 	responses(
 		(status=200, description="User has a valid cookie"),
-		(status=400, description="Bad Request"),
-		(status=401, description="User has an invalid cookie/no cookie"),
+		(status=400, description="Bad Request", body=ErrorBody),
+		(status=401, description="User has an invalid cookie/no cookie", body=ErrorBody),
 		(status=405, description="Method Not Allowed - Must be GET"),
 		(status=408, description="Request Timed Out"),
-		(status=500, description="Internal Server Error")
+		(status=500, description="Internal Server Error", body=ErrorBody)
 	),
 	security(("set-cookie"=[])),
 	tag="Account"
@@ -435,14 +773,20 @@ pub async fn api_validate(Extension(user): Extension<AuthUser>) -> ApiResult<()>
 				"risk_preference": "Adventurer",
 				"food_allergies": "peanuts,vegetarian,pollen",
 				"disabilities": "knee replacement",
-				"profile_picture": "base64-txt"
+				"notification_preferences": {
+					"trip_reminders": true,
+					"share_notifications": true,
+					"marketing": false
+				},
+				"profile_picture": "base64-txt",
+				"preferred_language": "en"
 			})
 		),
-		(status=400, description="Bad Request"),
-		(status=401, description="User has an invalid cookie/no cookie"),
+		(status=400, description="Bad Request", body=ErrorBody),
+		(status=401, description="User has an invalid cookie/no cookie", body=ErrorBody),
 		(status=405, description="Method Not Allowed - Must be GET"),
 		(status=408, description="Request Timed Out"),
-		(status=500, description="Internal Server Error")
+		(status=500, description="Internal Server Error", body=ErrorBody)
 	),
 	security(("set-cookie"=[])),
 	tag="Account"
@@ -456,7 +800,7 @@ pub async fn api_current(
 		user.id
 	);
 	// Load current user's full account row
-	let account = sqlx::query_as!(
+	let mut account = sqlx::query_as!(
 		CurrentResponse,
 		r#"
         SELECT
@@ -467,7 +811,10 @@ pub async fn api_current(
             risk_preference as "risk_preference: RiskTolerence",
             COALESCE(food_allergies, '') as "food_allergies!: String",
             COALESCE(disabilities, '') as "disabilities!: String",
-			COALESCE(profile_picture, '') as "profile_picture!: String"
+			interests as "interests: Vec<Interest>",
+			notification_preferences as "notification_preferences: _",
+			COALESCE(profile_picture, '') as "profile_picture!: String",
+			preferred_language
         FROM accounts
         WHERE id = $1
         "#,
@@ -476,6 +823,7 @@ pub async fn api_current(
 	.fetch_one(&pool)
 	.await
 	.map_err(AppError::from)?;
+	account.profile_picture = account.profile_picture.map(profile_picture_url);
 
 	Ok(Json(account))
 }
@@ -486,7 +834,7 @@ pub async fn api_current(
 /// `POST /api/account/update`
 ///
 /// # Request Body
-/// - `email`: A valid email address (string).
+/// - `email`: A valid email address (string). Stored as pending until verified.
 /// - 'first_name': The user's first name (string).
 /// - 'last_name': The user's last name (string).
 /// - 'password': The user's password (string).
@@ -494,11 +842,17 @@ pub async fn api_current(
 /// - 'risk_preference': The user's risk preference (string).
 /// - 'food_allergies': The user's allergies (string).
 /// - 'disabilities': The user's disabilities (string).
+/// - 'interests': The user's interests, replacing the current ones (array of strings).
+/// - 'notification_preferences': Notifications to opt in or out of, unspecified ones are kept (object of booleans).
 /// - 'profile_picture': The user's profile pic (string)
+/// - 'preferred_language': Language the LLM replies in, e.g. "en" or "pt-BR" (string)
 ///
 /// # Responses
 /// - `200 OK` - with body: [UpdateResponse]
+/// - `202 ACCEPTED` - with body: [UpdateResponse], email change is waiting on verification
+/// - `400 BAD_REQUEST` - Invalid email format, unknown interest or invalid language (public error)
 /// - `401 UNAUTHORIZED` - Invalid credentials (public error)
+/// - `409 CONFLICT` - Email already in use (public error)
 /// - `500 INTERNAL_SERVER_ERROR` - Internal error (private)
 ///
 /// # Examples
@@ -514,14 +868,17 @@ pub async fn api_current(
 ///         "risk_preference": "",
 ///         "food_allergies": "",
 ///         "disabilities": "",
-/// 		"profile_picture": ""
+///         "interests": ["Museums", "Food"],
+///         "notification_preferences": {"marketing": true},
+/// 		"profile_picture": "",
+///         "preferred_language": "es"
 ///       }'
 /// ```
 #[utoipa::path(
 	post,
 	path="/update",
 	summary="Update information about the user",
-	description="Update account info with provided data.",
+	description="Update account info with provided data. A new email is only applied once verified through the emailed link.",
 	request_body(
 		content=UpdateRequest,
 		content_type="application/json",
@@ -544,28 +901,41 @@ pub async fn api_current(
 				"risk_preference": "Adventurer",
 				"food_allergies": "peanuts,vegetarian,pollen",
 				"disabilities": "knee replacement",
-				"profile_picture": "base64-txt"
+				"notification_preferences": {
+					"trip_reminders": true,
+					"share_notifications": true,
+					"marketing": false
+				},
+				"profile_picture": "base64-txt",
+				"preferred_language": "en"
 			})
 		),
-		(status=400, description="Bad Request"),
-		(status=401, description="User has an invalid cookie/no cookie"),
+		(status=202, description="Account info updated, email change sent for verification", body=UpdateResponse),
+		(status=400, description="Bad Request", body=ErrorBody),
+		(status=401, description="User has an invalid cookie/no cookie", body=ErrorBody),
 		(status=405, description="Method Not Allowed - Must be POST"),
 		(status=408, description="Request Timed Out"),
-		(status=500, description="Internal Server Error")
+		(status=409, description="Email already in use", body=ErrorBody),
+		(status=500, description="Internal Server Error", body=ErrorBody)
 	),
 	security(("set-cookie"=[])),
 	tag="Account"
 )]
 pub async fn api_update(
+	client: ClientInfo,
 	Extension(pool): Extension<PgPool>,
 	Extension(user): Extension<AuthUser>,
+	Extension(mailer): Extension<SharedMailer>,
 	Json(payload): Json<UpdateRequest>,
-) -> ApiResult<Json<UpdateResponse>> {
+) -> ApiResult<(StatusCode, Json<UpdateResponse>)> {
 	debug!(
 		"HANDLER ->> /api/account/update 'api_update' - User ID: {} Payload: {:?}",
 		user.id, payload
 	);
 
+	let interests = payload.parse_interests().map_err(AppError::Validation)?;
+	payload.validate_language().map_err(AppError::Validation)?;
+
 	// If password is being updated, verify current password first
 	if let Some(_) = &payload.password {
 		if let Some(current_pw) = &payload.current_password {
@@ -604,21 +974,71 @@ pub async fn api_update(
 		None
 	};
 
+	// Email changes are held as pending until the new address is verified
+	let mut email_pending = false;
+	if let Some(new_email) = &payload.email {
+		let new_email = new_email.trim();
+		if !SignupRequest::validate_email(new_email) {
+			return Err(AppError::Validation("Invalid email format".to_string()));
+		}
+
+		let existing = sqlx::query!("SELECT id FROM accounts WHERE email = $1", new_email)
+			.fetch_optional(&pool)
+			.await
+			.map_err(AppError::from)?;
+
+		match existing {
+			// Already this account's email, nothing to verify
+			Some(row) if row.id == user.id => {}
+			Some(_) => return Err(AppError::DuplicateEmail),
+			None => {
+				let token = generate_token();
+				sqlx::query!(
+					r#"
+					UPDATE accounts SET
+						pending_email = $1,
+						email_verification_token = $2,
+						email_verification_created_at = NOW()
+					WHERE id = $3
+					"#,
+					new_email,
+					token,
+					user.id
+				)
+				.execute(&pool)
+				.await
+				.map_err(AppError::from)?;
+
+				mailer.send(
+					new_email,
+					"Verify your new email",
+					&format!(
+						"Use this token to confirm your new email: {}\nIt expires in {} hours.",
+						token,
+						EMAIL_VERIFICATION_TOKEN_EXP_SECONDS / 3600
+					),
+				)?;
+				email_pending = true;
+			}
+		}
+	}
+
 	let account = sqlx::query_as!(
 		UpdateResponse,
 		r#"
         UPDATE accounts SET
-            email = COALESCE($1, email),
-            first_name = COALESCE($2, first_name),
-            last_name = COALESCE($3, last_name),
-            password = COALESCE($4, password),
-            budget_preference = COALESCE($5, budget_preference),
-            risk_preference = COALESCE($6, risk_preference),
-            food_allergies = COALESCE($7, food_allergies),
-            disabilities = COALESCE($8, disabilities),
-			profile_picture = COALESCE($9, profile_picture)
-			
-        WHERE id = $10
+            first_name = COALESCE($1, first_name),
+            last_name = COALESCE($2, last_name),
+            password = COALESCE($3, password),
+            budget_preference = COALESCE($4, budget_preference),
+            risk_preference = COALESCE($5, risk_preference),
+            food_allergies = COALESCE($6, food_allergies),
+            disabilities = COALESCE($7, disabilities),
+			profile_picture = COALESCE($8, profile_picture),
+			interests = COALESCE($10, interests),
+			notification_preferences = notification_preferences || COALESCE($11, '{}'::jsonb),
+			preferred_language = COALESCE($12, preferred_language)
+        WHERE id = $9
         RETURNING
             email,
             first_name,
@@ -627,9 +1047,11 @@ pub async fn api_update(
             risk_preference as "risk_preference: RiskTolerence",
             food_allergies,
             disabilities,
-			profile_picture
+			interests as "interests: Vec<Interest>",
+			notification_preferences as "notification_preferences: _",
+			profile_picture,
+			preferred_language
         "#,
-		payload.email,
 		payload.first_name,
 		payload.last_name,
 		hashed_password,
@@ -638,91 +1060,1628 @@ pub async fn api_update(
 		payload.food_allergies,
 		payload.disabilities,
 		payload.profile_picture,
-		user.id
+		user.id,
+		interests as Option<Vec<Interest>>,
+		payload
+			.notification_preferences
+			.as_ref()
+			.map(sqlx::types::Json) as _,
+		payload.preferred_language
 	)
 	.fetch_one(&pool)
 	.await
 	.map_err(AppError::from)?;
+	let account = UpdateResponse {
+		profile_picture: account.profile_picture.map(profile_picture_url),
+		..account
+	};
 
-	Ok(Json(account))
+	if payload.password.is_some() {
+		record_auth_event(&pool, user.id, AuthEventType::PasswordChange, &client).await?;
+	}
+
+	let status = if email_pending {
+		StatusCode::ACCEPTED
+	} else {
+		StatusCode::OK
+	};
+	Ok((status, Json(account)))
 }
 
-/// Logout by setting cookie to expired.
+/// Update only the user's trip preferences
 ///
 /// # Method
-/// `GET /api/account/logout`
+/// `PATCH /api/account/preferences`
+///
+/// # Request Body
+/// - 'budget_preference': The user's budget preference (string).
+/// - 'risk_preference': The user's risk preference (string).
+/// - 'food_allergies': The user's allergies (string).
+/// - 'disabilities': The user's disabilities (string).
 ///
 /// # Responses
-/// - `200 OK` - with body: [UpdateResponse]
+/// - `200 OK` - with body: [PreferencesResponse]
+/// - `400 BAD_REQUEST` - Unknown budget or risk preference (public error)
 /// - `401 UNAUTHORIZED` - Invalid credentials (public error)
 /// - `500 INTERNAL_SERVER_ERROR` - Internal error (private)
 ///
 /// # Examples
 /// ```bash
-/// curl -X GET http://localhost:3001/api/account/logout
+/// curl -X PATCH http://localhost:3001/api/account/preferences
 ///   -H "Content-Type: application/json"
+///   -d '{
+///         "budget_preference": "LowBudget"
+///       }'
 /// ```
 #[utoipa::path(
-	get,
-	path="/logout",
-	summary="Logout by returning with expired cookie",
-	description="Sets the HTTP-only cookie as expired, which deauthenticates the user.",
+	patch,
+	path="/preferences",
+	summary="Update the user's preferences",
+	description="Updates only the given preferences, without the current password that /update needs for some fields.",
+	request_body(
+		content=PreferencesRequest,
+		content_type="application/json",
+		description="Non-null fields will update that field. Null or missing fields will not update that field.",
+		example=json!({
+			"budget_preference": "LowBudget"
+		})
+	),
 	responses(
-		(status=200, description="Logged out successfully"),
-		(status=400, description="Bad Request"),
-		(status=401, description="User has an invalid cookie/no cookie"),
-		(status=405, description="Method Not Allowed - Must be GET"),
+		(
+			status=200,
+			description="Preferences updated successfully",
+			body=PreferencesResponse,
+			content_type="application/json",
+			example=json!({
+				"budget_preference": "LowBudget",
+				"risk_preference": "Adventurer",
+				"food_allergies": "peanuts,vegetarian,pollen",
+				"disabilities": "knee replacement"
+			})
+		),
+		(status=400, description="Bad Request", body=ErrorBody),
+		(status=401, description="User has an invalid cookie/no cookie", body=ErrorBody),
+		(status=405, description="Method Not Allowed - Must be PATCH"),
 		(status=408, description="Request Timed Out"),
-		(status=500, description="Internal Server Error")
+		(status=500, description="Internal Server Error", body=ErrorBody)
 	),
 	security(("set-cookie"=[])),
 	tag="Account"
 )]
-pub async fn api_logout<C: CookieStore>(
-	cookies: &mut C,
-	Extension(key): Extension<Key>,
+pub async fn api_update_preferences(
+	Extension(pool): Extension<PgPool>,
 	Extension(user): Extension<AuthUser>,
-) -> ApiResult<()> {
+	Json(payload): Json<PreferencesRequest>,
+) -> ApiResult<Json<PreferencesResponse>> {
 	debug!(
-		"HANDLER ->> /api/account/logout 'api_logout' - User ID: {}",
-		user.id
+		"HANDLER ->> /api/account/preferences 'api_update_preferences' - User ID: {} Payload: {:?}",
+		user.id, payload
 	);
-	set_cookie(user.id, true, cookies, &key);
-	Ok(())
+
+	let preferences = sqlx::query_as!(
+		PreferencesResponse,
+		r#"
+		UPDATE accounts SET
+			budget_preference = COALESCE($1, budget_preference),
+			risk_preference = COALESCE($2, risk_preference),
+			food_allergies = COALESCE($3, food_allergies),
+			disabilities = COALESCE($4, disabilities)
+		WHERE id = $5
+		RETURNING
+			budget_preference as "budget_preference: BudgetBucket",
+			risk_preference as "risk_preference: RiskTolerence",
+			food_allergies,
+			disabilities
+		"#,
+		payload.budget_preference as Option<BudgetBucket>,
+		payload.risk_preference as Option<RiskTolerence>,
+		payload.food_allergies,
+		payload.disabilities,
+		user.id
+	)
+	.fetch_one(&pool)
+	.await
+	.map_err(AppError::from)?;
+
+	Ok(Json(preferences))
 }
 
-/// Create the account routes with authentication middleware.
+/// Finalize an email change requested through `/api/account/update`.
 ///
-/// # Routes
-/// ## Protected Routes (require authentication)
-/// - `POST /update` - Update user account information
-/// - `GET /current` - Get current user's account details
-/// - `POST /validate` - Validate authentication token
-/// - `GET /logout` - Logout by making cookie expired
+/// # Method
+/// `GET /api/account/verifyEmail?token=...`
 ///
-/// ## Public Routes (no authentication required)
-/// - `POST /signup` - Create a new user account
-/// - `POST /login` - Authenticate user and set auth cookie
+/// # Responses
+/// - `200 OK` - Email changed to the pending email
+/// - `400 BAD_REQUEST` - Invalid, expired, or already used token (public error)
+/// - `409 CONFLICT` - Pending email was taken by another account in the meantime (public error)
+/// - `500 INTERNAL_SERVER_ERROR` - Internal error (private)
 ///
-/// # Middleware
-/// Protected routes are secured by `middleware_auth` which validates the `auth-token` cookie.
-/// Public routes (signup/login) are accessible without authentication.
-pub fn account_routes() -> AxumRouter {
-	AxumRouter::new()
-		.route("/update", post(api_update))
-		.route("/current", get(api_current))
-		.route("/validate", get(api_validate))
-		.route(
-			"/logout",
-			get(|mut c, k, u| async move { api_logout::<Cookies>(&mut c, k, u).await }),
-		)
-		.route_layer(axum::middleware::from_fn(middleware_auth))
-		.route(
-			"/signup",
-			post(|mut c, k, p, b| async move { api_signup::<Cookies>(&mut c, k, p, b).await }),
-		)
-		.route(
-			"/login",
-			post(|mut c, k, p, b| async move { api_login::<Cookies>(&mut c, k, p, b).await }),
-		)
+/// # Examples
+/// ```bash
+/// curl -X GET "http://localhost:3001/api/account/verifyEmail?token=9f86d081884c7d65..."
+/// ```
+#[utoipa::path(
+	get,
+	path="/verifyEmail",
+	summary="Verify a new email",
+	description="Replaces the account's email with its pending email if the emailed token is valid.",
+	params(
+		("token"=String, Query, description="Token emailed to the new address")
+	),
+	responses(
+		(status=200, description="Email changed"),
+		(status=400, description="Invalid or expired token", body=ErrorBody),
+		(status=405, description="Method Not Allowed - Must be GET"),
+		(status=408, description="Request Timed Out"),
+		(status=409, description="Email already in use", body=ErrorBody),
+		(status=500, description="Internal Server Error", body=ErrorBody)
+	),
+	security(()),
+	tag="Account"
+)]
+pub async fn api_verify_email(
+	client: ClientInfo,
+	Extension(pool): Extension<PgPool>,
+	Query(query): Query<VerifyEmailQuery>,
+) -> ApiResult<()> {
+	debug!("HANDLER ->> /api/account/verifyEmail 'api_verify_email'");
+
+	// The unique constraint on email catches accounts that claimed the
+	// pending email after it was requested
+	let result = sqlx::query!(
+		r#"
+		UPDATE accounts SET
+			email = pending_email,
+			email_verified = TRUE,
+			pending_email = NULL,
+			email_verification_token = NULL,
+			email_verification_created_at = NULL
+		WHERE
+			email_verification_token = $1 AND
+			pending_email IS NOT NULL AND
+			email_verification_created_at > NOW() - make_interval(secs => $2)
+		RETURNING id
+		"#,
+		query.token,
+		EMAIL_VERIFICATION_TOKEN_EXP_SECONDS as f64
+	)
+	.fetch_optional(&pool)
+	.await;
+
+	match result {
+		Ok(Some(row)) => {
+			record_auth_event(&pool, row.id, AuthEventType::EmailChange, &client).await
+		}
+		Ok(None) => Err(AppError::BadRequest(
+			"invalid or expired verification token".to_string(),
+		)),
+		Err(sqlx::Error::Database(e)) if e.is_unique_violation() => Err(AppError::DuplicateEmail),
+		Err(e) => Err(AppError::from(e)),
+	}
+}
+
+/// Verify the email of a new account with the token emailed on signup.
+///
+/// # Method
+/// `GET /api/account/verify?token=...`
+///
+/// # Responses
+/// - `200 OK` - Email verified
+/// - `400 BAD_REQUEST` - Invalid, expired, or already used token (public error)
+/// - `500 INTERNAL_SERVER_ERROR` - Internal error (private)
+///
+/// # Examples
+/// ```bash
+/// curl -X GET "http://localhost:3001/api/account/verify?token=9f86d081884c7d65..."
+/// ```
+#[utoipa::path(
+	get,
+	path="/verify",
+	summary="Verify a new account's email",
+	description="Marks the account's email as verified if the token emailed on signup is valid.",
+	params(
+		("token"=String, Query, description="Token emailed on signup")
+	),
+	responses(
+		(status=200, description="Email verified"),
+		(status=400, description="Invalid or expired token", body=ErrorBody),
+		(status=405, description="Method Not Allowed - Must be GET"),
+		(status=408, description="Request Timed Out"),
+		(status=500, description="Internal Server Error", body=ErrorBody)
+	),
+	security(()),
+	tag="Account"
+)]
+pub async fn api_verify(
+	Extension(pool): Extension<PgPool>,
+	Query(query): Query<VerifyEmailQuery>,
+) -> ApiResult<()> {
+	debug!("HANDLER ->> /api/account/verify 'api_verify'");
+
+	// Expired tokens are left in place, they can never match and go with the account
+	let verified = sqlx::query!(
+		r#"
+		WITH used AS (
+			DELETE FROM email_verifications
+			WHERE
+				token = $1 AND
+				created_at > NOW() - make_interval(secs => $2)
+			RETURNING account_id
+		)
+		UPDATE accounts SET email_verified = TRUE
+		WHERE id IN (SELECT account_id FROM used)
+		RETURNING id
+		"#,
+		query.token,
+		EMAIL_VERIFICATION_TOKEN_EXP_SECONDS as f64
+	)
+	.fetch_optional(&pool)
+	.await
+	.map_err(AppError::from)?;
+
+	match verified {
+		Some(_) => Ok(()),
+		None => Err(AppError::BadRequest(
+			"invalid or expired verification token".to_string(),
+		)),
+	}
+}
+
+/// Create an API key for programmatic clients.
+///
+/// # Method
+/// `POST /api/account/apiKey`
+///
+/// # Request Body
+/// - `name`: Label for the key (string, required).
+///
+/// # Responses
+/// - `200 OK` - with body: [NewApiKeyResponse]
+/// - `400 BAD_REQUEST` - Empty or too long name (public error)
+/// - `401 UNAUTHORIZED` - When authentication fails (handled in middleware, public error)
+/// - `500 INTERNAL_SERVER_ERROR` - Internal error (private)
+///
+/// # Examples
+/// ```bash
+/// curl -X POST http://localhost:3001/api/account/apiKey
+///   -H "Content-Type: application/json"
+///   -d '{
+///        "name": "CI"
+///       }'
+/// ```
+///
+/// Notes:
+/// - Key format is `<id>.<secret>`; only an Argon2 hash of the secret is stored.
+/// - The plaintext key is only returned by this endpoint.
+#[utoipa::path(
+	post,
+	path="/apiKey",
+	summary="Create an API key",
+	description="Creates an API key that can be sent in the X-API-Key header instead of a cookie. The key is only returned once.",
+	request_body(
+		content=ApiKeyRequest,
+		content_type="application/json",
+		example=json!({
+			"name": "CI"
+		})
+	),
+	responses(
+		(
+			status=200,
+			description="API key created",
+			body=NewApiKeyResponse,
+			content_type="application/json",
+			example=json!({
+				"id": 12,
+				"name": "CI",
+				"key": "12.9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08"
+			})
+		),
+		(status=400, description="Bad Request", body=ErrorBody),
+		(status=401, description="User has an invalid cookie/no cookie", body=ErrorBody),
+		(status=405, description="Method Not Allowed - Must be POST"),
+		(status=408, description="Request Timed Out"),
+		(status=500, description="Internal Server Error", body=ErrorBody)
+	),
+	security(("set-cookie"=[])),
+	tag="Account"
+)]
+pub async fn api_new_api_key(
+	Extension(pool): Extension<PgPool>,
+	Extension(user): Extension<AuthUser>,
+	Json(payload): Json<ApiKeyRequest>,
+) -> ApiResult<Json<NewApiKeyResponse>> {
+	debug!(
+		"HANDLER ->> /api/account/apiKey 'api_new_api_key' - User ID: {} Payload: {:?}",
+		user.id, payload
+	);
+
+	let name = payload.name.trim();
+	if name.is_empty() {
+		return Err(AppError::Validation("API key name is required".to_string()));
+	}
+	if name.len() > 255 {
+		return Err(AppError::Validation(
+			"API key name must be 255 characters or less".to_string(),
+		));
+	}
+
+	let secret = generate_token();
+	let salt = SaltString::generate(&mut OsRng);
+	let key_hash = Argon2::default()
+		.hash_password(secret.as_bytes(), &salt)
+		.map_err(AppError::from)?
+		.to_string();
+
+	let id = sqlx::query!(
+		r#"
+		INSERT INTO api_keys (account_id, key_hash, name)
+		VALUES ($1, $2, $3)
+		RETURNING id
+		"#,
+		user.id,
+		key_hash,
+		name
+	)
+	.fetch_one(&pool)
+	.await
+	.map_err(AppError::from)?
+	.id;
+
+	Ok(Json(NewApiKeyResponse {
+		id,
+		name: name.to_string(),
+		key: format!("{}.{}", id, secret),
+	}))
+}
+
+/// List the user's API keys.
+///
+/// # Method
+/// `GET /api/account/apiKeys`
+///
+/// # Responses
+/// - `200 OK` - with body: [ApiKeysResponse]
+/// - `401 UNAUTHORIZED` - When authentication fails (handled in middleware, public error)
+/// - `500 INTERNAL_SERVER_ERROR` - Internal error (private)
+///
+/// # Examples
+/// ```bash
+/// curl -X GET http://localhost:3001/api/account/apiKeys
+///   -H "Content-Type: application/json"
+/// ```
+#[utoipa::path(
+	get,
+	path="/apiKeys",
+	summary="List API keys",
+	description="Returns the user's active API keys with their secrets masked.",
+	responses(
+		(
+			status=200,
+			description="The user's API keys",
+			body=ApiKeysResponse,
+			content_type="application/json",
+			example=json!({
+				"api_keys": [{
+					"id": 12,
+					"name": "CI",
+					"masked_key": "12.********",
+					"created_at": "2025-11-05T14:30:00Z",
+					"last_used_at": null
+				}]
+			})
+		),
+		(status=400, description="Bad Request", body=ErrorBody),
+		(status=401, description="User has an invalid cookie/no cookie", body=ErrorBody),
+		(status=405, description="Method Not Allowed - Must be GET"),
+		(status=408, description="Request Timed Out"),
+		(status=500, description="Internal Server Error", body=ErrorBody)
+	),
+	security(("set-cookie"=[])),
+	tag="Account"
+)]
+pub async fn api_api_keys(
+	Extension(pool): Extension<PgPool>,
+	Extension(user): Extension<AuthUser>,
+) -> ApiResult<Json<ApiKeysResponse>> {
+	debug!(
+		"HANDLER ->> /api/account/apiKeys 'api_api_keys' - User ID: {}",
+		user.id
+	);
+
+	let api_keys = sqlx::query!(
+		r#"
+		SELECT id, name, created_at, last_used_at
+		FROM api_keys
+		WHERE account_id = $1
+		ORDER BY created_at DESC, id DESC
+		"#,
+		user.id
+	)
+	.fetch_all(&pool)
+	.await
+	.map_err(AppError::from)?
+	.into_iter()
+	.map(|row| ApiKeyInfo {
+		id: row.id,
+		name: row.name,
+		masked_key: format!("{}.********", row.id),
+		created_at: row.created_at,
+		last_used_at: row.last_used_at,
+	})
+	.collect();
+
+	Ok(Json(ApiKeysResponse { api_keys }))
+}
+
+/// Revoke one of the user's API keys.
+///
+/// # Method
+/// `DELETE /api/account/apiKey/:id`
+///
+/// # Responses
+/// - `200 OK` - API key revoked
+/// - `401 UNAUTHORIZED` - When authentication fails (handled in middleware, public error)
+/// - `404 NOT_FOUND` - The key does not belong to the user or does not exist (public error)
+/// - `500 INTERNAL_SERVER_ERROR` - Internal error (private)
+///
+/// # Examples
+/// ```bash
+/// curl -X DELETE http://localhost:3001/api/account/apiKey/12
+///   -H "Content-Type: application/json"
+/// ```
+#[utoipa::path(
+	delete,
+	path="/apiKey/{id}",
+	summary="Revoke an API key",
+	description="Deletes the API key if it belongs to the user making the request. Requests using it will get 401.",
+	responses(
+		(status=200, description="API key revoked"),
+		(status=400, description="Bad Request", body=ErrorBody),
+		(status=401, description="User has an invalid cookie/no cookie", body=ErrorBody),
+		(status=404, description="API key not found for this user", body=ErrorBody),
+		(status=405, description="Method Not Allowed - Must be DELETE"),
+		(status=408, description="Request Timed Out"),
+		(status=500, description="Internal Server Error", body=ErrorBody)
+	),
+	security(("set-cookie"=[])),
+	tag="Account"
+)]
+pub async fn api_delete_api_key(
+	Extension(pool): Extension<PgPool>,
+	Extension(user): Extension<AuthUser>,
+	Path(id): Path<i32>,
+) -> ApiResult<()> {
+	debug!(
+		"HANDLER ->> /api/account/apiKey/{} 'api_delete_api_key' - User ID: {}",
+		id, user.id
+	);
+
+	sqlx::query!(
+		r#"
+		DELETE FROM api_keys
+		WHERE id = $1 AND account_id = $2
+		RETURNING id
+		"#,
+		id,
+		user.id
+	)
+	.fetch_optional(&pool)
+	.await
+	.map_err(AppError::from)?
+	.ok_or(AppError::NotFound)?;
+
+	Ok(())
+}
+
+/// Logout by setting cookie to expired.
+///
+/// # Method
+/// `GET /api/account/logout`
+///
+/// # Responses
+/// - `200 OK` - with body: [UpdateResponse]
+/// - `401 UNAUTHORIZED` - Invalid credentials (public error)
+/// - `500 INTERNAL_SERVER_ERROR` - Internal error (private)
+///
+/// # Examples
+/// ```bash
+/// curl -X GET http://localhost:3001/api/account/logout
+///   -H "Content-Type: application/json"
+/// ```
+#[utoipa::path(
+	get,
+	path="/logout",
+	summary="Logout by returning with expired cookie",
+	description="Sets the HTTP-only cookie as expired, which deauthenticates the user.",
+	responses(
+		(status=200, description="Logged out successfully"),
+		(status=400, description="Bad Request", body=ErrorBody),
+		(status=401, description="User has an invalid cookie/no cookie", body=ErrorBody),
+		(status=405, description="Method Not Allowed - Must be GET"),
+		(status=408, description="Request Timed Out"),
+		(status=500, description="Internal Server Error", body=ErrorBody)
+	),
+	security(("set-cookie"=[])),
+	tag="Account"
+)]
+pub async fn api_logout<C: CookieStore>(
+	cookies: &mut C,
+	client: ClientInfo,
+	Extension(key): Extension<Key>,
+	Extension(pool): Extension<PgPool>,
+	Extension(user): Extension<AuthUser>,
+	session: Option<Extension<AuthSession>>,
+) -> ApiResult<()> {
+	debug!(
+		"HANDLER ->> /api/account/logout 'api_logout' - User ID: {}",
+		user.id
+	);
+
+	// API key requests have no session to revoke
+	let session_id = match session {
+		Some(Extension(session)) => {
+			sqlx::query!(
+				"UPDATE sessions SET revoked_at = NOW() WHERE id = $1 AND revoked_at IS NULL",
+				session.id
+			)
+			.execute(&pool)
+			.await
+			.map_err(AppError::from)?;
+			session.id
+		}
+		None => 0,
+	};
+	record_auth_event(&pool, user.id, AuthEventType::Logout, &client).await?;
+
+	set_cookie(user.id, session_id, true, cookies, &key);
+	Ok(())
+}
+
+/// List the user's active sessions.
+///
+/// # Method
+/// `GET /api/account/sessions`
+///
+/// # Responses
+/// - `200 OK` - with body: [SessionsResponse]
+/// - `401 UNAUTHORIZED` - When authentication fails (handled in middleware, public error)
+/// - `500 INTERNAL_SERVER_ERROR` - Internal error (private)
+///
+/// # Examples
+/// ```bash
+/// curl -X GET http://localhost:3001/api/account/sessions
+///   -H "Content-Type: application/json"
+/// ```
+#[utoipa::path(
+	get,
+	path="/sessions",
+	summary="List active sessions",
+	description="Returns every unexpired, unrevoked session for the user, marking the one making the request.",
+	responses(
+		(
+			status=200,
+			description="The user's sessions",
+			body=SessionsResponse,
+			content_type="application/json",
+			example=json!({
+				"sessions": [{
+					"id": 4,
+					"user_agent": "Mozilla/5.0 (X11; Linux x86_64; rv:144.0) Gecko/20100101 Firefox/144.0",
+					"created_at": "2025-11-05T14:30:00Z",
+					"last_seen": "2025-11-05T15:10:00Z",
+					"current": true
+				}]
+			})
+		),
+		(status=400, description="Bad Request", body=ErrorBody),
+		(status=401, description="User has an invalid cookie/no cookie", body=ErrorBody),
+		(status=405, description="Method Not Allowed - Must be GET"),
+		(status=408, description="Request Timed Out"),
+		(status=500, description="Internal Server Error", body=ErrorBody)
+	),
+	security(("set-cookie"=[])),
+	tag="Account"
+)]
+pub async fn api_sessions(
+	Extension(pool): Extension<PgPool>,
+	Extension(user): Extension<AuthUser>,
+	session: Option<Extension<AuthSession>>,
+) -> ApiResult<Json<SessionsResponse>> {
+	debug!(
+		"HANDLER ->> /api/account/sessions 'api_sessions' - User ID: {}",
+		user.id
+	);
+
+	let current = session.map(|Extension(s)| s.id);
+	let sessions = sqlx::query!(
+		r#"
+		SELECT id, user_agent, created_at, last_seen
+		FROM sessions
+		WHERE account_id = $1 AND revoked_at IS NULL AND expires_at > NOW()
+		ORDER BY last_seen DESC, id DESC
+		"#,
+		user.id
+	)
+	.fetch_all(&pool)
+	.await
+	.map_err(AppError::from)?
+	.into_iter()
+	.map(|row| SessionInfo {
+		id: row.id,
+		user_agent: row.user_agent,
+		created_at: row.created_at,
+		last_seen: row.last_seen,
+		current: current == Some(row.id),
+	})
+	.collect();
+
+	Ok(Json(SessionsResponse { sessions }))
+}
+
+/// Revoke one of the user's sessions.
+///
+/// # Method
+/// `DELETE /api/account/sessions/:id`
+///
+/// # Responses
+/// - `200 OK` - Session revoked
+/// - `401 UNAUTHORIZED` - When authentication fails (handled in middleware, public error)
+/// - `404 NOT_FOUND` - The session does not belong to the user or is already revoked (public error)
+/// - `500 INTERNAL_SERVER_ERROR` - Internal error (private)
+///
+/// # Examples
+/// ```bash
+/// curl -X DELETE http://localhost:3001/api/account/sessions/4
+///   -H "Content-Type: application/json"
+/// ```
+///
+/// Notes:
+/// - The revoked cookie gets 401 on its next request even if it hasn't expired.
+#[utoipa::path(
+	delete,
+	path="/sessions/{id}",
+	summary="Revoke a session",
+	description="Signs out a session, e.g. a lost device. Its cookie will be rejected from then on.",
+	responses(
+		(status=200, description="Session revoked"),
+		(status=400, description="Bad Request", body=ErrorBody),
+		(status=401, description="User has an invalid cookie/no cookie", body=ErrorBody),
+		(status=404, description="Active session not found for this user", body=ErrorBody),
+		(status=405, description="Method Not Allowed - Must be DELETE"),
+		(status=408, description="Request Timed Out"),
+		(status=500, description="Internal Server Error", body=ErrorBody)
+	),
+	security(("set-cookie"=[])),
+	tag="Account"
+)]
+pub async fn api_revoke_session(
+	Extension(pool): Extension<PgPool>,
+	Extension(user): Extension<AuthUser>,
+	Path(id): Path<i32>,
+) -> ApiResult<()> {
+	debug!(
+		"HANDLER ->> /api/account/sessions/{} 'api_revoke_session' - User ID: {}",
+		id, user.id
+	);
+
+	sqlx::query!(
+		r#"
+		UPDATE sessions
+		SET revoked_at = NOW()
+		WHERE id = $1 AND account_id = $2 AND revoked_at IS NULL
+		RETURNING id
+		"#,
+		id,
+		user.id
+	)
+	.fetch_optional(&pool)
+	.await
+	.map_err(AppError::from)?
+	.ok_or(AppError::NotFound)?;
+
+	Ok(())
+}
+
+/// Logout of every session.
+///
+/// # Method
+/// `POST /api/account/logoutAll`
+///
+/// # Responses
+/// - `200 OK` - All sessions revoked and cookie expired
+/// - `401 UNAUTHORIZED` - When authentication fails (handled in middleware, public error)
+/// - `500 INTERNAL_SERVER_ERROR` - Internal error (private)
+///
+/// # Examples
+/// ```bash
+/// curl -X POST http://localhost:3001/api/account/logoutAll
+///   -H "Content-Type: application/json"
+/// ```
+#[utoipa::path(
+	post,
+	path="/logoutAll",
+	summary="Logout everywhere",
+	description="Revokes every session for the user, including the current one.",
+	responses(
+		(status=200, description="Logged out of all sessions"),
+		(status=400, description="Bad Request", body=ErrorBody),
+		(status=401, description="User has an invalid cookie/no cookie", body=ErrorBody),
+		(status=405, description="Method Not Allowed - Must be POST"),
+		(status=408, description="Request Timed Out"),
+		(status=500, description="Internal Server Error", body=ErrorBody)
+	),
+	security(("set-cookie"=[])),
+	tag="Account"
+)]
+pub async fn api_logout_all<C: CookieStore>(
+	cookies: &mut C,
+	client: ClientInfo,
+	Extension(key): Extension<Key>,
+	Extension(pool): Extension<PgPool>,
+	Extension(user): Extension<AuthUser>,
+) -> ApiResult<()> {
+	debug!(
+		"HANDLER ->> /api/account/logoutAll 'api_logout_all' - User ID: {}",
+		user.id
+	);
+
+	sqlx::query!(
+		"UPDATE sessions SET revoked_at = NOW() WHERE account_id = $1 AND revoked_at IS NULL",
+		user.id
+	)
+	.execute(&pool)
+	.await
+	.map_err(AppError::from)?;
+	record_auth_event(&pool, user.id, AuthEventType::Logout, &client).await?;
+
+	set_cookie(user.id, 0, true, cookies, &key);
+	Ok(())
+}
+
+/// Fetch the user's recent auth activity.
+///
+/// # Method
+/// `GET /api/account/activity`
+///
+/// # Responses
+/// - `200 OK` - with body: [ActivityResponse]
+/// - `401 UNAUTHORIZED` - When authentication fails (handled in middleware, public error)
+/// - `500 INTERNAL_SERVER_ERROR` - Internal error (private)
+///
+/// # Examples
+/// ```bash
+/// curl -X GET http://localhost:3001/api/account/activity
+///   -H "Content-Type: application/json"
+/// ```
+#[utoipa::path(
+	get,
+	path="/activity",
+	summary="Recent auth activity",
+	description="Returns the most recent logins, failed logins, lockouts, logouts, and password/email changes on the account.",
+	responses(
+		(
+			status=200,
+			description="The user's recent auth events",
+			body=ActivityResponse,
+			content_type="application/json",
+			example=json!({
+				"events": [{
+					"event_type": "LoginSuccess",
+					"ip": "203.0.113.7",
+					"user_agent": "Mozilla/5.0 (X11; Linux x86_64; rv:144.0) Gecko/20100101 Firefox/144.0",
+					"created_at": "2025-11-05T14:30:00Z"
+				}]
+			})
+		),
+		(status=400, description="Bad Request", body=ErrorBody),
+		(status=401, description="User has an invalid cookie/no cookie", body=ErrorBody),
+		(status=405, description="Method Not Allowed - Must be GET"),
+		(status=408, description="Request Timed Out"),
+		(status=500, description="Internal Server Error", body=ErrorBody)
+	),
+	security(("set-cookie"=[])),
+	tag="Account"
+)]
+pub async fn api_activity(
+	Extension(pool): Extension<PgPool>,
+	Extension(user): Extension<AuthUser>,
+) -> ApiResult<Json<ActivityResponse>> {
+	debug!(
+		"HANDLER ->> /api/account/activity 'api_activity' - User ID: {}",
+		user.id
+	);
+
+	let events = sqlx::query_as!(
+		AuthEvent,
+		r#"
+		SELECT
+			event_type AS "event_type: AuthEventType",
+			ip,
+			user_agent,
+			created_at
+		FROM auth_events
+		WHERE account_id = $1
+		ORDER BY created_at DESC, id DESC
+		LIMIT $2
+		"#,
+		user.id,
+		AUTH_ACTIVITY_LEN
+	)
+	.fetch_all(&pool)
+	.await
+	.map_err(AppError::from)?;
+
+	Ok(Json(ActivityResponse { events }))
+}
+
+/// List the events the user favorited.
+///
+/// # Method
+/// `GET /api/account/favorites`
+///
+/// # Responses
+/// - `200 OK` - with body: [FavoritesResponse]
+/// - `401 UNAUTHORIZED` - When authentication fails (handled in middleware, public error)
+/// - `500 INTERNAL_SERVER_ERROR` - Internal error (private)
+///
+/// # Examples
+/// ```bash
+/// curl -X GET http://localhost:3001/api/account/favorites
+///   -H "Content-Type: application/json"
+/// ```
+#[utoipa::path(
+	get,
+	path="/favorites",
+	summary="List favorite events",
+	description="Returns the full event for each of the user's favorites, most recently favorited first. Archived events are left out.",
+	responses(
+		(
+			status=200,
+			description="The user's favorite events",
+			body=FavoritesResponse,
+			content_type="application/json",
+			example=json!({
+				"events": [{
+					"id": 42,
+					"event_name": "Art Institute of Chicago",
+					"city": "Chicago",
+					"country": "USA",
+					"lat": 41.8796,
+					"lng": -87.6237,
+					"event_type": "museum",
+					"user_created": false,
+					"price_level": 2,
+					"website_uri": "https://www.artic.edu/",
+					"periods": [],
+					"special_days": []
+				}]
+			})
+		),
+		(status=400, description="Bad Request", body=ErrorBody),
+		(status=401, description="User has an invalid cookie/no cookie", body=ErrorBody),
+		(status=405, description="Method Not Allowed - Must be GET"),
+		(status=408, description="Request Timed Out"),
+		(status=500, description="Internal Server Error", body=ErrorBody)
+	),
+	security(("set-cookie"=[])),
+	tag="Account"
+)]
+pub async fn api_favorites(
+	Extension(pool): Extension<PgPool>,
+	Extension(user): Extension<AuthUser>,
+) -> ApiResult<Json<FavoritesResponse>> {
+	debug!(
+		"HANDLER ->> /api/account/favorites 'api_favorites' - User ID: {}",
+		user.id
+	);
+
+	let ids = fetch_favorite_event_ids(&pool, user.id)
+		.await
+		.map_err(AppError::from)?;
+	let events = fetch_events_by_ids(&pool, &ids)
+		.await
+		.map_err(AppError::from)?;
+
+	Ok(Json(FavoritesResponse { events }))
+}
+
+/// Request a password reset link by email.
+///
+/// # Method
+/// `POST /api/account/forgotPassword`
+///
+/// # Request Body
+/// - `email`: The email of the account to reset (string, required).
+///
+/// # Responses
+/// - `200 OK` - Always, so the response can't be used to discover which emails have accounts
+/// - `500 INTERNAL_SERVER_ERROR` - Internal error (private)
+///
+/// # Examples
+/// ```bash
+/// curl -X POST http://localhost:3001/api/account/forgotPassword
+///   -H "Content-Type: application/json"
+///   -d '{
+///        "email": "alice@example.com"
+///       }'
+/// ```
+///
+/// Notes:
+/// - The link goes to `<FRONTEND_URL>/resetPassword?token=...`, the token is what `/resetPassword` takes.
+/// - The token is single-use and expires after `PASSWORD_RESET_TOKEN_EXP_SECONDS`.
+/// - Only a SHA-256 hash of the token is stored, so a leaked `password_resets` table can't be used to reset passwords.
+/// - Only one token is issued per account every `PASSWORD_RESET_REQUEST_COOLDOWN_SECONDS`,
+///   and a new token replaces the previous one.
+#[utoipa::path(
+	post,
+	path="/forgotPassword",
+	summary="Request a password reset link",
+	description="Emails a link with a single-use password reset token if the email belongs to an account. Always returns 200.",
+	request_body(
+		content=ForgotPasswordRequest,
+		content_type="application/json",
+		example=json!({
+			"email": "example@gmail.com"
+		})
+	),
+	responses(
+		(status=200, description="Reset email sent if the account exists"),
+		(status=400, description="Bad Request", body=ErrorBody),
+		(status=405, description="Method Not Allowed - Must be POST"),
+		(status=408, description="Request Timed Out"),
+		(status=500, description="Internal Server Error", body=ErrorBody)
+	),
+	security(()),
+	tag="Account"
+)]
+pub async fn api_forgot_password(
+	Extension(pool): Extension<PgPool>,
+	Extension(mailer): Extension<SharedMailer>,
+	Json(payload): Json<ForgotPasswordRequest>,
+) -> ApiResult<()> {
+	debug!(
+		"HANDLER ->> /api/account/forgotPassword 'api_forgot_password' - Payload: {:?}",
+		payload
+	);
+
+	// Only issue a new token if the last one is older than the cooldown, replacing it
+	// A token issued within the cooldown expires later than NOW() + (expiry - cooldown)
+	let token = generate_token();
+	let issued = sqlx::query!(
+		r#"
+		WITH account AS (
+			SELECT a.id FROM accounts a
+			WHERE a.email = $2 AND NOT EXISTS (
+				SELECT 1 FROM password_resets p
+				WHERE
+					p.account_id = a.id AND
+					p.expires_at > NOW() + make_interval(secs => $4)
+			)
+		), replaced AS (
+			DELETE FROM password_resets
+			WHERE account_id IN (SELECT id FROM account)
+		)
+		INSERT INTO password_resets (token_hash, account_id, expires_at)
+		SELECT $1, id, NOW() + make_interval(secs => $3) FROM account
+		RETURNING account_id
+		"#,
+		hash_reset_token(&token),
+		payload.email,
+		PASSWORD_RESET_TOKEN_EXP_SECONDS as f64,
+		(PASSWORD_RESET_TOKEN_EXP_SECONDS - PASSWORD_RESET_REQUEST_COOLDOWN_SECONDS) as f64
+	)
+	.fetch_optional(&pool)
+	.await
+	.map_err(AppError::from)?;
+
+	if issued.is_some() {
+		mailer.send(
+			&payload.email,
+			"Reset your password",
+			&format!(
+				"Reset your password here: {}/resetPassword?token={}\nThe link expires in {} minutes.",
+				std::env::var("FRONTEND_URL").unwrap_or_default(),
+				token,
+				PASSWORD_RESET_TOKEN_EXP_SECONDS / 60
+			),
+		)?;
+	}
+
+	Ok(())
+}
+
+/// Reset a password using a token from `/api/account/forgotPassword`.
+///
+/// # Method
+/// `POST /api/account/resetPassword`
+///
+/// # Request Body
+/// - `token`: The emailed reset token (string, required).
+/// - `new_password`: The new password (string, required).
+///
+/// # Responses
+/// - `200 OK` - Password was reset
+/// - `400 BAD_REQUEST` - Invalid, expired, or already used token, or a weak password (public error)
+/// - `500 INTERNAL_SERVER_ERROR` - Internal error (private)
+///
+/// # Examples
+/// ```bash
+/// curl -X POST http://localhost:3001/api/account/resetPassword
+///   -H "Content-Type: application/json"
+///   -d '{
+///        "token": "9f86d081884c7d65...",
+///        "new_password": "Password123"
+///       }'
+/// ```
+///
+/// Notes:
+/// - Also lifts a lockout from too many failed logins.
+#[utoipa::path(
+	post,
+	path="/resetPassword",
+	summary="Reset a password",
+	description="Sets a new password for the account the reset token was issued to, and invalidates the token.",
+	request_body(
+		content=ResetPasswordRequest,
+		content_type="application/json",
+		example=json!({
+			"token": "9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08",
+			"new_password": "Password_123"
+		})
+	),
+	responses(
+		(status=200, description="Password reset successfully"),
+		(status=400, description="Invalid or expired token, or invalid password", body=ErrorBody),
+		(status=405, description="Method Not Allowed - Must be POST"),
+		(status=408, description="Request Timed Out"),
+		(status=500, description="Internal Server Error", body=ErrorBody)
+	),
+	security(()),
+	tag="Account"
+)]
+pub async fn api_reset_password(
+	client: ClientInfo,
+	Extension(pool): Extension<PgPool>,
+	Json(payload): Json<ResetPasswordRequest>,
+) -> ApiResult<()> {
+	debug!("HANDLER ->> /api/account/resetPassword 'api_reset_password'");
+
+	SignupRequest::validate_password(&payload.new_password).map_err(AppError::Validation)?;
+
+	let salt = SaltString::generate(&mut OsRng);
+	let password_hash = Argon2::default()
+		.hash_password(payload.new_password.as_bytes(), &salt)
+		.map_err(AppError::from)?
+		.to_string();
+
+	// Consume the token in the same statement that checks it so it can't be used twice
+	// Resetting the password also lifts a lockout from failed logins
+	let account_id = sqlx::query!(
+		r#"
+		WITH used AS (
+			DELETE FROM password_resets
+			WHERE token_hash = $2 AND expires_at > NOW()
+			RETURNING account_id
+		)
+		UPDATE accounts SET
+			password = $1,
+			failed_login_attempts = 0,
+			locked_until = NULL
+		WHERE id IN (SELECT account_id FROM used)
+		RETURNING id
+		"#,
+		password_hash,
+		hash_reset_token(&payload.token)
+	)
+	.fetch_optional(&pool)
+	.await
+	.map_err(AppError::from)?
+	.ok_or(AppError::BadRequest(
+		"invalid or expired reset token".to_string(),
+	))?
+	.id;
+
+	record_auth_event(&pool, account_id, AuthEventType::PasswordChange, &client).await
+}
+
+/// Directory uploaded profile pictures are stored in, set by `PROFILE_PICTURE_DIR`.
+fn profile_picture_dir() -> PathBuf {
+	std::env::var(PROFILE_PICTURE_DIR)
+		.map(PathBuf::from)
+		.unwrap_or_else(|_| PathBuf::from(DEFAULT_PROFILE_PICTURE_DIR))
+}
+
+/// Maps a stored `profile_picture` to a URL the frontend can load.
+///
+/// Uploads are stored as a path in [profile_picture_dir] and served by [api_profile_picture_file].
+/// Anything else, like an external URL set through `/update`, is returned unchanged.
+fn profile_picture_url(stored: String) -> String {
+	let path = std::path::Path::new(&stored);
+	match path.file_name() {
+		Some(file) if path.starts_with(profile_picture_dir()) => format!(
+			"{}/api/account/profilePicture/{}",
+			std::env::var("API_BASE_URL").unwrap_or_default(),
+			file.to_string_lossy()
+		),
+		_ => stored,
+	}
+}
+
+/// Deletes a previously uploaded profile picture, ignoring values that aren't uploads.
+async fn remove_profile_picture_file(stored: Option<String>) {
+	if let Some(stored) = stored
+		&& std::path::Path::new(&stored).starts_with(profile_picture_dir())
+	{
+		_ = tokio::fs::remove_file(stored).await;
+	}
+}
+
+/// Content type and file extension for each accepted image format, with its magic bytes.
+const PROFILE_PICTURE_TYPES: [(&str, &str, &[u8]); 3] = [
+	("image/png", "png", b"\x89PNG\r\n\x1a\n"),
+	("image/jpeg", "jpg", b"\xff\xd8\xff"),
+	("image/webp", "webp", b"RIFF"),
+];
+
+fn profile_picture_too_large() -> AppError {
+	AppError::BadRequest(format!(
+		"profile picture must be at most {} MB",
+		PROFILE_PICTURE_MAX_BYTES / (1024 * 1024)
+	))
+}
+
+fn multipart_error(e: MultipartError) -> AppError {
+	if e.status() == StatusCode::PAYLOAD_TOO_LARGE {
+		profile_picture_too_large()
+	} else {
+		AppError::BadRequest(e.body_text())
+	}
+}
+
+/// Reads the uploaded image, returning its bytes and file extension.
+async fn read_profile_picture(mut field: Field<'_>) -> ApiResult<(Vec<u8>, &'static str)> {
+	let (_, ext, magic) = PROFILE_PICTURE_TYPES
+		.into_iter()
+		.find(|(content_type, _, _)| field.content_type() == Some(*content_type))
+		.ok_or(AppError::BadRequest(
+			"profile picture must be a PNG, JPEG, or WebP image".to_string(),
+		))?;
+
+	let mut bytes = Vec::new();
+	while let Some(chunk) = field.chunk().await.map_err(multipart_error)? {
+		if bytes.len() + chunk.len() > PROFILE_PICTURE_MAX_BYTES {
+			return Err(profile_picture_too_large());
+		}
+		bytes.extend_from_slice(&chunk);
+	}
+
+	// Don't trust the declared content type alone
+	let matches = bytes.starts_with(magic) && (ext != "webp" || bytes.get(8..12) == Some(b"WEBP"));
+	if !matches {
+		return Err(AppError::BadRequest(
+			"profile picture content doesn't match its content type".to_string(),
+		));
+	}
+	Ok((bytes, ext))
+}
+
+/// Upload a new profile picture.
+///
+/// # Method
+/// `POST /api/account/profilePicture`
+///
+/// # Request Body
+/// `multipart/form-data` with a `file` field containing a PNG, JPEG, or WebP image
+/// no larger than [PROFILE_PICTURE_MAX_BYTES].
+///
+/// # Responses
+/// - `200 OK` - with body: [ProfilePictureResponse]
+/// - `400 BAD_REQUEST` - Missing file, wrong type, or too large (public error)
+/// - `401 UNAUTHORIZED` - When authentication fails (handled in middleware, public error)
+/// - `500 INTERNAL_SERVER_ERROR` - Internal error (private)
+///
+/// # Examples
+/// ```bash
+/// curl -X POST http://localhost:3001/api/account/profilePicture
+///   -F "file=@me.png;type=image/png"
+/// ```
+#[utoipa::path(
+	post,
+	path="/profilePicture",
+	summary="Upload a profile picture",
+	description="Replaces the user's profile picture with the uploaded PNG, JPEG, or WebP image.",
+	request_body(
+		content_type="multipart/form-data",
+		description="A `file` field containing the image.",
+	),
+	responses(
+		(
+			status=200,
+			description="URL of the uploaded picture",
+			body=ProfilePictureResponse,
+			content_type="application/json",
+			example=json!({
+				"url": "http://localhost:3001/api/account/profilePicture/7-9f86d081884c7d65.png"
+			})
+		),
+		(status=400, description="Missing file, unsupported image type, or image too large", body=ErrorBody),
+		(status=401, description="User has an invalid cookie/no cookie", body=ErrorBody),
+		(status=405, description="Method Not Allowed - Must be POST"),
+		(status=408, description="Request Timed Out"),
+		(status=500, description="Internal Server Error", body=ErrorBody)
+	),
+	security(("set-cookie"=[])),
+	tag="Account"
+)]
+pub async fn api_upload_profile_picture(
+	Extension(pool): Extension<PgPool>,
+	Extension(user): Extension<AuthUser>,
+	mut multipart: Multipart,
+) -> ApiResult<Json<ProfilePictureResponse>> {
+	debug!(
+		"HANDLER ->> /api/account/profilePicture 'api_upload_profile_picture' - User ID: {}",
+		user.id
+	);
+
+	let (bytes, ext) = loop {
+		match multipart.next_field().await.map_err(multipart_error)? {
+			Some(field) if field.name() == Some("file") => {
+				break read_profile_picture(field).await?;
+			}
+			Some(_) => continue,
+			None => {
+				return Err(AppError::BadRequest(
+					"missing profile picture file".to_string(),
+				));
+			}
+		}
+	};
+
+	let dir = profile_picture_dir();
+	tokio::fs::create_dir_all(&dir)
+		.await
+		.map_err(|e| AppError::Internal(format!("failed to create {}: {}", dir.display(), e)))?;
+	let path = dir.join(format!("{}-{}.{}", user.id, generate_token(), ext));
+	tokio::fs::write(&path, &bytes)
+		.await
+		.map_err(|e| AppError::Internal(format!("failed to write {}: {}", path.display(), e)))?;
+	let path = path.to_string_lossy().into_owned();
+
+	let old = sqlx::query!(
+		r#"
+		UPDATE accounts new SET profile_picture = $1
+		FROM accounts old
+		WHERE new.id = $2 AND old.id = new.id
+		RETURNING old.profile_picture
+		"#,
+		path,
+		user.id
+	)
+	.fetch_one(&pool)
+	.await
+	.map_err(AppError::from)?
+	.profile_picture;
+	remove_profile_picture_file(old).await;
+
+	Ok(Json(ProfilePictureResponse {
+		url: profile_picture_url(path),
+	}))
+}
+
+/// Remove the user's profile picture.
+///
+/// # Method
+/// `DELETE /api/account/profilePicture`
+///
+/// # Responses
+/// - `200 OK` - Profile picture removed (or there wasn't one)
+/// - `401 UNAUTHORIZED` - When authentication fails (handled in middleware, public error)
+/// - `500 INTERNAL_SERVER_ERROR` - Internal error (private)
+///
+/// # Examples
+/// ```bash
+/// curl -X DELETE http://localhost:3001/api/account/profilePicture
+/// ```
+#[utoipa::path(
+	delete,
+	path="/profilePicture",
+	summary="Remove the profile picture",
+	description="Clears the user's profile picture and deletes the uploaded file.",
+	responses(
+		(status=200, description="Profile picture removed"),
+		(status=400, description="Bad Request", body=ErrorBody),
+		(status=401, description="User has an invalid cookie/no cookie", body=ErrorBody),
+		(status=405, description="Method Not Allowed - Must be DELETE"),
+		(status=408, description="Request Timed Out"),
+		(status=500, description="Internal Server Error", body=ErrorBody)
+	),
+	security(("set-cookie"=[])),
+	tag="Account"
+)]
+pub async fn api_delete_profile_picture(
+	Extension(pool): Extension<PgPool>,
+	Extension(user): Extension<AuthUser>,
+) -> ApiResult<()> {
+	debug!(
+		"HANDLER ->> /api/account/profilePicture 'api_delete_profile_picture' - User ID: {}",
+		user.id
+	);
+
+	let old = sqlx::query!(
+		r#"
+		UPDATE accounts new SET profile_picture = NULL
+		FROM accounts old
+		WHERE new.id = $1 AND old.id = new.id
+		RETURNING old.profile_picture
+		"#,
+		user.id
+	)
+	.fetch_one(&pool)
+	.await
+	.map_err(AppError::from)?
+	.profile_picture;
+	remove_profile_picture_file(old).await;
+
+	Ok(())
+}
+
+/// Serve an uploaded profile picture.
+///
+/// # Method
+/// `GET /api/account/profilePicture/{file}`
+///
+/// # Responses
+/// - `200 OK` - The image
+/// - `404 NOT_FOUND` - No such picture (public error)
+///
+/// # Examples
+/// ```bash
+/// curl -X GET http://localhost:3001/api/account/profilePicture/7-9f86d081884c7d65.png
+/// ```
+#[utoipa::path(
+	get,
+	path="/profilePicture/{file}",
+	summary="Get a profile picture",
+	description="Serves an uploaded profile picture by the file name in its URL.",
+	params(
+		("file"=String, Path, description="File name from the profile picture URL")
+	),
+	responses(
+		(status=200, description="The image", content_type="image/*"),
+		(status=404, description="Profile picture not found", body=ErrorBody),
+		(status=405, description="Method Not Allowed - Must be GET"),
+		(status=408, description="Request Timed Out"),
+		(status=500, description="Internal Server Error", body=ErrorBody)
+	),
+	security(()),
+	tag="Account"
+)]
+pub async fn api_profile_picture_file(Path(file): Path<String>) -> ApiResult<impl IntoResponse> {
+	debug!(
+		"HANDLER ->> /api/account/profilePicture/{} 'api_profile_picture_file'",
+		file
+	);
+
+	// Only plain file names, never a path out of the upload directory
+	if file.starts_with('.') || std::path::Path::new(&file).file_name() != Some(file.as_ref()) {
+		return Err(AppError::NotFound);
+	}
+	let (content_type, _, _) = PROFILE_PICTURE_TYPES
+		.into_iter()
+		.find(|(_, ext, _)| file.ends_with(&format!(".{}", ext)))
+		.ok_or(AppError::NotFound)?;
+
+	let bytes = tokio::fs::read(profile_picture_dir().join(&file))
+		.await
+		.map_err(|_| AppError::NotFound)?;
+	Ok(([(header::CONTENT_TYPE, content_type)], bytes))
+}
+
+/// Login from the Swagger UI, so it can try protected endpoints. Only in debug builds.
+///
+/// # Method
+/// `POST /api/account/swaggerLogin`
+///
+/// # Request Body
+/// - `email`: A valid email address (string, required).
+/// - 'password': The user's password (string, required).
+///
+/// # Responses
+/// - Same as `POST /api/account/login`
+///
+/// Notes:
+/// - Works like `/login`, but the `auth-token` cookie is `SameSite=None; Secure`.
+#[cfg(debug_assertions)]
+#[utoipa::path(
+	post,
+	path="/swaggerLogin",
+	summary="Login from the Swagger UI",
+	description="Debug builds only. Logs in like /login, but sets the cookie with SameSite=None and Secure so the Swagger UI keeps it and can call protected endpoints.",
+	request_body(
+		content=LoginRequest,
+		content_type="application/json",
+		example=json!({
+			"email": "example@gmail.com",
+			"password": "Password_123"
+		})
+	),
+	responses(
+		(status=200, description="Login succeeded"),
+		(status=400, description="Bad Request", body=ErrorBody),
+		(status=405, description="Method Not Allowed - Must be POST"),
+		(status=408, description="Request Timed Out"),
+		(status=429, description="Too many failed attempts or account locked, retry after the Retry-After header", body=ErrorBody),
+		(status=500, description="Internal Server Error", body=ErrorBody)
+	),
+	security(()),
+	tag="Account"
+)]
+pub async fn api_swagger_login(
+	cookies: Cookies,
+	client: ClientInfo,
+	key: Extension<Key>,
+	pool: Extension<PgPool>,
+	payload: Json<LoginRequest>,
+) -> ApiResult<()> {
+	api_login(&mut SwaggerCookies(cookies), client, key, pool, payload).await
+}
+
+/// Logout from the Swagger UI, clearing the cookie set by `/swaggerLogin`. Only in debug builds.
+///
+/// # Method
+/// `GET /api/account/swaggerLogout`
+///
+/// # Responses
+/// - Same as `GET /api/account/logout`
+#[cfg(debug_assertions)]
+#[utoipa::path(
+	get,
+	path="/swaggerLogout",
+	summary="Logout from the Swagger UI",
+	description="Debug builds only. Logs out like /logout, expiring the SameSite=None cookie set by /swaggerLogin.",
+	responses(
+		(status=200, description="Logged out successfully"),
+		(status=400, description="Bad Request", body=ErrorBody),
+		(status=401, description="User has an invalid cookie/no cookie", body=ErrorBody),
+		(status=405, description="Method Not Allowed - Must be GET"),
+		(status=408, description="Request Timed Out"),
+		(status=500, description="Internal Server Error", body=ErrorBody)
+	),
+	security(("set-cookie"=[])),
+	tag="Account"
+)]
+pub async fn api_swagger_logout(
+	cookies: Cookies,
+	client: ClientInfo,
+	key: Extension<Key>,
+	pool: Extension<PgPool>,
+	user: Extension<AuthUser>,
+	session: Option<Extension<AuthSession>>,
+) -> ApiResult<()> {
+	api_logout(
+		&mut SwaggerCookies(cookies),
+		client,
+		key,
+		pool,
+		user,
+		session,
+	)
+	.await
+}
+
+/// Docs for the Swagger UI's login endpoints, which only exist in debug builds
+#[cfg(debug_assertions)]
+#[derive(OpenApi)]
+#[openapi(
+	paths(api_swagger_login, api_swagger_logout),
+	modifiers(&SecurityAddon),
+	tags((name="Account"))
+)]
+pub struct SwaggerAuthApiDoc;
+
+/// Create the account routes with authentication middleware.
+///
+/// # Routes
+/// ## Protected Routes (require authentication)
+/// - `POST /update` - Update user account information
+/// - `PATCH /preferences` - Update only the given trip preferences
+/// - `GET /current` - Get current user's account details
+/// - `POST /validate` - Validate authentication token
+/// - `GET /logout` - Logout by making cookie expired
+/// - `POST /apiKey` - Create an API key
+/// - `GET /apiKeys` - List API keys
+/// - `DELETE /apiKey/{id}` - Revoke an API key
+/// - `GET /sessions` - List active sessions
+/// - `DELETE /sessions/{id}` - Revoke a session
+/// - `POST /logoutAll` - Revoke every session
+/// - `GET /activity` - Recent auth events
+/// - `GET /favorites` - List favorite events
+/// - `POST /profilePicture` - Upload a profile picture
+/// - `DELETE /profilePicture` - Remove the profile picture
+/// - `GET /swaggerLogout` - Logout from the Swagger UI (debug builds only)
+///
+/// ## Public Routes (no authentication required)
+/// - `POST /signup` - Create a new user account
+/// - `POST /login` - Authenticate user and set auth cookie
+/// - `POST /oauth/google` - Sign in or sign up with a Google authorization code
+/// - `POST /forgotPassword` - Email a password reset token
+/// - `POST /resetPassword` - Reset password with an emailed token
+/// - `GET /verifyEmail` - Apply a pending email change with an emailed token
+/// - `GET /profilePicture/{file}` - Serve an uploaded profile picture
+/// - `POST /swaggerLogin` - Login from the Swagger UI (debug builds only)
+///
+/// # Middleware
+/// Protected routes are secured by `middleware_auth` which validates the `auth-token` cookie
+/// or an `X-API-Key` header.
+/// Public routes (signup/login) are accessible without authentication.
+/// Signup and login are rate limited by `middleware_rate_limit`, failing with 429 after repeated failures.
+pub fn account_routes() -> AxumRouter {
+	let protected = AxumRouter::new()
+		.route("/update", post(api_update))
+		.route("/preferences", patch(api_update_preferences))
+		.route("/current", get(api_current))
+		.route("/validate", get(api_validate))
+		.route(
+			"/logout",
+			get(|mut c, ci, k, p, u, s| async move {
+				api_logout::<Cookies>(&mut c, ci, k, p, u, s).await
+			}),
+		)
+		.route(
+			"/logoutAll",
+			post(|mut c, ci, k, p, u| async move {
+				api_logout_all::<Cookies>(&mut c, ci, k, p, u).await
+			}),
+		)
+		.route("/activity", get(api_activity))
+		.route("/favorites", get(api_favorites))
+		.route("/sessions", get(api_sessions))
+		.route("/sessions/{id}", delete(api_revoke_session))
+		.route("/apiKey", post(api_new_api_key))
+		.route("/apiKeys", get(api_api_keys))
+		.route("/apiKey/{id}", delete(api_delete_api_key))
+		.route(
+			"/profilePicture",
+			post(api_upload_profile_picture)
+				.delete(api_delete_profile_picture)
+				// leave room for the multipart framing around the image
+				.layer(DefaultBodyLimit::max(PROFILE_PICTURE_MAX_BYTES + 64 * 1024)),
+		);
+	#[cfg(debug_assertions)]
+	let protected = protected.route("/swaggerLogout", get(api_swagger_logout));
+
+	let public = protected
+		.route_layer(axum::middleware::from_fn(middleware_auth))
+		.route(
+			"/signup",
+			post(|mut c, h, k, p, m, b| async move {
+				api_signup::<Cookies>(&mut c, h, k, p, m, b).await
+			})
+			.layer(axum::middleware::from_fn(middleware_rate_limit)),
+		)
+		.route(
+			"/login",
+			post(|mut c, h, k, p, b| async move { api_login::<Cookies>(&mut c, h, k, p, b).await })
+				.layer(axum::middleware::from_fn(middleware_rate_limit)),
+		)
+		.route(
+			"/oauth/google",
+			post(|mut c, h, k, p, g, b| async move {
+				api_google_oauth::<Cookies>(&mut c, h, k, p, g, b).await
+			}),
+		)
+		.route("/forgotPassword", post(api_forgot_password))
+		.route("/resetPassword", post(api_reset_password))
+		.route("/verifyEmail", get(api_verify_email))
+		.route("/verify", get(api_verify))
+		.route("/profilePicture/{file}", get(api_profile_picture_file));
+	#[cfg(debug_assertions)]
+	let public = public.route(
+		"/swaggerLogin",
+		post(api_swagger_login).layer(axum::middleware::from_fn(middleware_rate_limit)),
+	);
+
+	public
 }
@@ -1,51 +1,103 @@
 use axum::{
-	Extension, Json,
-	extract::Path,
+	BoxError, Extension, Json,
+	body::Body,
+	extract::{
+		Path, Query, Request,
+		ws::{Message as WsMessage, WebSocket, WebSocketUpgrade},
+	},
+	http::header,
+	response::{IntoResponse, Response},
 	routing::{delete, get, post},
 };
-use chrono::NaiveDate;
-use sqlx::PgPool;
+use chrono::{NaiveDate, NaiveDateTime};
+use futures::{SinkExt, Stream, StreamExt};
+use serde::Serialize;
+use sqlx::{PgPool, postgres::PgListener};
+use tower_cookies::{Cookies, Key};
 use utoipa::OpenApi;
+use uuid::Uuid;
 
 use crate::{
-	agent::configs::orchestrator::AgentType,
+	agent::{
+		configs::orchestrator::{SharedAgentFactory, SharedLlm},
+		security::sanitize_user_input,
+		tools::constraint::{is_keep_events_constraint, keep_events_constraint},
+	},
 	controllers::{AxumRouter, itinerary::insert_event_list},
-	error::{ApiResult, AppError},
-	global::MESSAGE_PAGE_LEN,
+	error::{ApiResult, AppError, ErrorBody},
+	global::{
+		AGENT_SEMAPHORE, CHAT_SUMMARY_MESSAGE_LEN, DEFAULT_LANGUAGE,
+		DEFAULT_LLM_PIPELINE_TIMEOUT_SECONDS, FEEDBACK_COMMENT_MAX_LEN,
+		LLM_PIPELINE_TIMEOUT_SECONDS, MAX_PINNED_CHATS, MAX_TOOL_CALLS, MESSAGE_EXPORT_CHUNK_LEN,
+		MESSAGE_PAGE_LEN, MESSAGE_REQUEST_POLL_INTERVAL_MS, MESSAGE_RETENTION_DAYS,
+		SEMAPHORE_ACQUIRE_TIMEOUT_SECS,
+	},
 	http_models::{
 		chat_session::{
-			ChatsResponse, NewChatResponse, ProgressRequest, ProgressResponse, RenameRequest,
+			ArchiveRequest, BranchRequest, CancelRequest, ChatSort, ChatSummaryResponse,
+			ChatsQuery, ChatsResponse, ExportFormat, ExportQuery, NewChatResponse, PinRequest,
+			ProgressRequest, ProgressResponse, RegenerateRequest, RenameRequest,
 		},
 		event::Event,
 		itinerary::{EventDay, Itinerary},
 		message::{
-			Message, MessagePageRequest, MessagePageResponse, SendMessageRequest,
+			ExportedMessage, Feedback, FeedbackRequest, FeedbackStageStats, FeedbackStatsResponse,
+			Message, MessageIdsResponse, MessagePageRequest, MessagePageResponse,
+			MessageSearchResult, SearchMessagesRequest, SearchMessagesResponse, SendMessageRequest,
 			SendMessageResponse, UpdateMessageRequest,
 		},
 	},
-	middleware::{AuthUser, middleware_auth},
+	middleware::{
+		AcceptLanguage, AuthUser, authenticate_cookie,
+		metrics::{
+			ITINERARIES_GENERATED_TOTAL, LLM_PIPELINE_FAILURES_TOTAL, LLM_PIPELINE_RUNS_TOTAL,
+			request_scrape_allowed,
+		},
+		middleware_auth,
+	},
 	sql_models::{
-		LlmProgress,
+		FeedbackRating, LlmProgress,
 		message::{ChatSessionRow, MessageRow},
 	},
 	swagger::SecurityAddon,
 };
 
+#[cfg(test)]
+use crate::global::TEST_LLM_PIPELINE_TIMEOUT_SECONDS;
 use langchain_rust::chain::Chain;
+use langchain_rust::language_models::llm::LLM;
 use langchain_rust::prompt_args;
-use tracing::{debug, error, info};
+use std::time::{Duration, Instant};
+use tokio::sync::{Semaphore, SemaphorePermit};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, error, info, warn};
 
 #[derive(OpenApi)]
 #[openapi(
 	paths(
 		api_chats,
 		api_new_chat,
+		api_branch,
 		api_message_page,
+		api_search_messages,
+		api_export_chat,
+		api_chat_summary,
 		api_send_message,
 		api_update_message,
+		api_regenerate,
 		api_delete_chat,
+		api_delete_message,
+		api_restore_message,
 		api_rename,
-		api_progress
+		api_pin,
+		api_archive,
+		api_unarchive,
+		api_progress,
+		api_cancel,
+		api_feedback,
+		api_get_feedback,
+		api_feedback_stats,
+		api_chat_ws
 	),
 	modifiers(&SecurityAddon),
 	security(("set-cookie"=[])),
@@ -58,21 +110,202 @@ use tracing::{debug, error, info};
 #[allow(dead_code)]
 pub struct ChatApiDoc;
 
+/// Bot reply sent when the agent doesn't reply within [llm_pipeline_timeout]
+const TIMED_OUT_REPLY: &str = "Sorry, this is taking longer than expected and I had to stop. Please try sending your message again.";
+/// Bot reply sent when the user cancels the reply being generated
+const CANCELLED_REPLY: &str = "Okay, I stopped working on that.";
+/// Bot reply sent instead of the agent's when it made `MAX_TOOL_CALLS` or more tool calls
+const TOO_MANY_TOOL_CALLS_REPLY: &str =
+	"I'm having trouble generating your itinerary. Please rephrase your request.";
+/// Bot reply sent when the agent errors or panics
+const AGENT_ERROR_REPLY: &str =
+	"I'm sorry, I encountered an error while planning your trip. Please try again.";
+/// Mark `ts_headline` puts before each search match, swapped for `<b>` by [highlight_snippet]
+const HEADLINE_START_SEL: char = '\u{2}';
+/// Mark `ts_headline` puts after each search match, swapped for `</b>` by [highlight_snippet]
+const HEADLINE_STOP_SEL: char = '\u{3}';
+/// Put before the transcript [api_chat_summary] asks the LLM to summarize
+const SUMMARY_PROMPT: &str = "Summarize this conversation in 2-3 sentences:";
+
+/// A bot reply from [send_message_to_llm]
+struct LlmReply {
+	message: Message,
+	/// The agent didn't reply in time, so `message` is [TIMED_OUT_REPLY]
+	timed_out: bool,
+}
+
+/// How long the agent gets to reply, including waiting for other replies to finish
+fn llm_pipeline_timeout() -> Duration {
+	let seconds = std::env::var(LLM_PIPELINE_TIMEOUT_SECONDS)
+		.ok()
+		.and_then(|s| s.parse().ok())
+		.unwrap_or(DEFAULT_LLM_PIPELINE_TIMEOUT_SECONDS);
+
+	// Tests use an agent that never replies to hit the timeout, so keep it short
+	#[cfg(test)]
+	let seconds = seconds.min(TEST_LLM_PIPELINE_TIMEOUT_SECONDS);
+
+	Duration::from_secs(seconds)
+}
+
+/// Waits up to `timeout` for a permit to run an agent, the server is too busy to reply after that.
+/// * The permit is given back when it's dropped
+pub async fn acquire_agent_permit(
+	semaphore: &Semaphore,
+	timeout: Duration,
+) -> ApiResult<SemaphorePermit<'_>> {
+	match tokio::time::timeout(timeout, semaphore.acquire()).await {
+		Ok(permit) => {
+			permit.map_err(|e| AppError::Internal(format!("agent semaphore closed: {e}")))
+		}
+		Err(_) => Err(AppError::ServiceUnavailable(
+			"Server is busy, please try again shortly".to_string(),
+		)),
+	}
+}
+
+/// A permit from `AGENT_SEMAPHORE` for [send_message_to_llm].
+/// * Acquire it before changing the chat, so a busy server leaves the chat as it was
+pub(crate) async fn agent_permit() -> ApiResult<SemaphorePermit<'static>> {
+	acquire_agent_permit(
+		&AGENT_SEMAPHORE,
+		Duration::from_secs(SEMAPHORE_ACQUIRE_TIMEOUT_SECS),
+	)
+	.await
+}
+
+/// Whether a message request with a `client_request_id` is new or a retry, see [claim_message_request]
+enum MessageRequest {
+	/// Not seen before, so the caller processes it and saves the response with [finish_message_request]
+	New,
+	/// A retry of a processed request, with the response body the original got
+	Done(serde_json::Value),
+}
+
+/// Claims `client_request_id` in the chat session so only one request with it is processed.
+/// * A retry while the original is still being processed waits for its response, and gives
+///   up with a `409` once the pipeline would have timed out
+/// * Claims still without a response after the pipeline timeout were abandoned, e.g. the
+///   client disconnected, so they're taken over
+async fn claim_message_request(
+	pool: &PgPool,
+	chat_session_id: i32,
+	client_request_id: Uuid,
+) -> ApiResult<MessageRequest> {
+	let stale_after = llm_pipeline_timeout();
+	let deadline = Instant::now() + stale_after;
+	loop {
+		let claimed = sqlx::query!(
+			r#"
+			INSERT INTO message_requests (chat_session_id, client_request_id)
+			VALUES ($1, $2)
+			ON CONFLICT (chat_session_id, client_request_id) DO UPDATE SET created_at = NOW()
+			WHERE message_requests.response IS NULL
+				AND message_requests.created_at < NOW() - make_interval(secs => $3)
+			RETURNING client_request_id
+			"#,
+			chat_session_id,
+			client_request_id,
+			stale_after.as_secs_f64(),
+		)
+		.fetch_optional(pool)
+		.await
+		.map_err(AppError::from)?;
+		if claimed.is_some() {
+			return Ok(MessageRequest::New);
+		}
+
+		let response = sqlx::query_scalar!(
+			r#"
+			SELECT response FROM message_requests
+			WHERE chat_session_id = $1 AND client_request_id = $2
+			"#,
+			chat_session_id,
+			client_request_id,
+		)
+		.fetch_optional(pool)
+		.await
+		.map_err(AppError::from)?
+		.flatten();
+		if let Some(response) = response {
+			return Ok(MessageRequest::Done(response));
+		}
+		// Still being processed, or it just failed and the claim can be taken next time around
+		if Instant::now() >= deadline {
+			return Err(AppError::PipelineBusy(String::from(
+				"This message is still being processed",
+			)));
+		}
+		tokio::time::sleep(Duration::from_millis(MESSAGE_REQUEST_POLL_INTERVAL_MS)).await;
+	}
+}
+
+/// Saves the response to a claimed request so retries get it too, or releases the claim when
+/// processing failed so a retry processes the message again. Does nothing without a `client_request_id`.
+async fn finish_message_request<T: Serialize>(
+	pool: &PgPool,
+	chat_session_id: i32,
+	client_request_id: Option<Uuid>,
+	result: &ApiResult<T>,
+) -> ApiResult<()> {
+	let Some(client_request_id) = client_request_id else {
+		return Ok(());
+	};
+	match result {
+		Ok(response) => {
+			sqlx::query!(
+				r#"
+				UPDATE message_requests SET response = $3
+				WHERE chat_session_id = $1 AND client_request_id = $2
+				"#,
+				chat_session_id,
+				client_request_id,
+				serde_json::to_value(response)?,
+			)
+			.execute(pool)
+			.await
+			.map_err(AppError::from)?;
+		}
+		Err(_) => {
+			sqlx::query!(
+				r#"
+				DELETE FROM message_requests
+				WHERE chat_session_id = $1 AND client_request_id = $2
+				"#,
+				chat_session_id,
+				client_request_id,
+			)
+			.execute(pool)
+			.await
+			.map_err(AppError::from)?;
+		}
+	}
+	Ok(())
+}
+
 /// Sends message and latest itinerary in chat session to llm, and waits for response.
 ///
 /// When the bot replies, it's message and itinerary are inserted into the db.
+/// If the agent times out or the user cancels, a canned reply is inserted instead.
+/// `keep_event_ids` are added to the chat's constraints for this reply only, see
+/// [keep_events_constraint].
+/// The reply is in `language` if given, otherwise the account's `preferred_language`.
+/// `_permit` from [agent_permit] is held until the reply is done, so only so many agents run at once.
 /// # Warning!
 /// Assumes the user's message has already been inserted into the db.
-async fn send_message_to_llm(
+#[allow(clippy::too_many_arguments)]
+pub(crate) async fn send_message_to_llm(
+	_permit: SemaphorePermit<'_>,
 	text: &str,
 	account_id: i32,
 	chat_session_id: i32,
 	itinerary_id: Option<i32>,
+	keep_event_ids: &[i32],
+	language: Option<&str>,
 	pool: &PgPool,
-	agent: &AgentType,
-	chat_session_id_atomic: &std::sync::Arc<std::sync::atomic::AtomicI32>,
+	agent: &SharedAgentFactory,
 	context_store: &crate::agent::models::context::SharedContextStore,
-) -> ApiResult<Message> {
+) -> ApiResult<LlmReply> {
 	// Give the LLM an itinerary for context
 	let itinerary_id = match itinerary_id {
 		Some(id) => Some(id), //use the provided itinerary
@@ -87,7 +320,8 @@ async fn send_message_to_llm(
 				WHERE
 					c.account_id=$1 AND
 					c.id=$2 AND
-					m.itinerary_id IS NOT NULL
+					m.itinerary_id IS NOT NULL AND
+					m.deleted_at IS NULL
 				ORDER BY m.timestamp DESC
 				LIMIT 1;
 				"#,
@@ -112,7 +346,20 @@ async fn send_message_to_llm(
 		None => None,
 	};
 
+	let preferred_language = match language {
+		Some(language) => language.to_string(),
+		None => sqlx::query_scalar!(
+			"SELECT preferred_language FROM accounts WHERE id = $1",
+			account_id
+		)
+		.fetch_optional(pool)
+		.await
+		.map_err(AppError::from)?
+		.unwrap_or_else(|| DEFAULT_LANGUAGE.to_string()),
+	};
+
 	// Always invoke the agent (it will use MockLLM when DEPLOY_LLM != "1")
+	LLM_PIPELINE_RUNS_TOTAL.inc();
 	info!(
 		target: "orchestrator_pipeline",
 		chat_session_id = chat_session_id,
@@ -131,10 +378,19 @@ async fn send_message_to_llm(
 	// intentionally removed to avoid confusion.
 
 	// Initialize context with chat_session_id and user_id BEFORE agent runs
-	// This prevents race conditions from global atomics
 	// IMPORTANT: Only initialize if context doesn't exist - preserve existing trip_context!
-	{
-		use crate::agent::models::context::{ContextData, TripContext};
+	let cancellation = {
+		use crate::agent::models::context::ContextData;
+		use crate::agent::tools::task::load_trip_context;
+
+		// Trip details from a previous session, used only if there's no live context
+		let stored_trip_context = if context_store.read().await.contains_key(&chat_session_id) {
+			None
+		} else {
+			load_trip_context(pool, chat_session_id)
+				.await
+				.map_err(AppError::from)?
+		};
 		let mut store_guard = context_store.write().await;
 
 		// Only insert if this chat_session doesn't have context yet
@@ -146,15 +402,19 @@ async fn send_message_to_llm(
 					user_id: account_id,
 					user_profile: None,
 					chat_history: vec![],
-					trip_context: TripContext::default(),
+					trip_context: stored_trip_context.unwrap_or_default(),
 					active_itinerary: None,
 					events: vec![],
 					tool_history: vec![],
+					tool_call_count: 0,
 					pipeline_stage: None,
 					researched_events: vec![],
 					constrained_events: vec![],
 					optimized_events: vec![],
 					constraints: vec![],
+					preferred_language: preferred_language.clone(),
+					cancellation: CancellationToken::new(),
+					last_accessed: std::time::Instant::now(),
 				},
 			);
 
@@ -167,6 +427,7 @@ async fn send_message_to_llm(
 			// Context exists - just update user_id in case it changed
 			if let Some(ctx) = store_guard.get_mut(&chat_session_id) {
 				ctx.user_id = account_id;
+				ctx.last_accessed = std::time::Instant::now();
 			}
 
 			info!(
@@ -175,16 +436,41 @@ async fn send_message_to_llm(
 				"Reusing existing context for chat session"
 			);
 		}
-	}
 
-	// Set the atomic so tools can look up the context
-	use std::sync::atomic::Ordering;
-	chat_session_id_atomic.store(chat_session_id, Ordering::Relaxed);
+		// A cancel only applies to the reply being generated, so each message gets a fresh token
+		// and starts counting tool calls again
+		let cancellation = CancellationToken::new();
+		if let Some(ctx) = store_guard.get_mut(&chat_session_id) {
+			ctx.cancellation = cancellation.clone();
+			ctx.tool_call_count = 0;
+			ctx.preferred_language = preferred_language;
+			// Pinned events only apply to the itinerary regenerate that asked for them
+			ctx.constraints.retain(|c| !is_keep_events_constraint(c));
+			if !keep_event_ids.is_empty() {
+				ctx.constraints.push(keep_events_constraint(keep_event_ids));
+			}
+		}
+		cancellation
+	};
+
+	// A fresh agent per message, so concurrent messages neither wait on nor see each other
+	let agent = agent
+		.build(chat_session_id, account_id)
+		.map_err(|e| AppError::Internal(format!("failed to create orchestrator agent: {e}")))?;
 
-	// Invoke the agent
-	let ai_text = {
-		let agent_guard = agent.lock().await;
+	// Replies tools send from here on are this message's
+	let last_message_id = sqlx::query_scalar!(
+		"SELECT MAX(id) FROM messages WHERE chat_session_id = $1",
+		chat_session_id
+	)
+	.fetch_one(pool)
+	.await
+	.map_err(AppError::from)?
+	.unwrap_or(0);
 
+	// Invoke the agent, giving up if it hangs or the user cancels.
+	// A panicking agent is caught so the user still gets a reply.
+	let invocation = futures::FutureExt::catch_unwind(std::panic::AssertUnwindSafe(async {
 		debug!(
 			target: "orchestrator_pipeline",
 			chat_session_id = chat_session_id,
@@ -192,12 +478,16 @@ async fn send_message_to_llm(
 			"Invoking orchestrator agent"
 		);
 
-		agent_guard
+		agent
 			.invoke(prompt_args! {
 				"input" => text,
 			})
 			.await
-			.map_err(|e| {
+	}));
+	let ai_text = tokio::select! {
+		result = tokio::time::timeout(llm_pipeline_timeout(), invocation) => match result {
+			Ok(Ok(Ok(ai_text))) => ai_text,
+			Ok(Ok(Err(e))) => {
 				error!(
 					target: "orchestrator_pipeline",
 					chat_session_id = chat_session_id,
@@ -211,10 +501,105 @@ async fn send_message_to_llm(
 					error = %e,
 					"Orchestrator agent error"
 				);
-				AppError::Internal(format!("AI agent error: {}", e))
-			})?
+				LLM_PIPELINE_FAILURES_TOTAL.with_label_values(&["error"]).inc();
+				return insert_agent_error_reply(pool, chat_session_id).await;
+			}
+			Ok(Err(_)) => {
+				error!(
+					target: "orchestrator_pipeline",
+					chat_session_id = chat_session_id,
+					"Orchestrator agent panicked"
+				);
+				LLM_PIPELINE_FAILURES_TOTAL.with_label_values(&["panic"]).inc();
+				return insert_agent_error_reply(pool, chat_session_id).await;
+			}
+			Err(_) => {
+				warn!(
+					target: "orchestrator_pipeline",
+					chat_session_id = chat_session_id,
+					timeout_seconds = llm_pipeline_timeout().as_secs(),
+					"Orchestrator agent timed out"
+				);
+				LLM_PIPELINE_FAILURES_TOTAL.with_label_values(&["timeout"]).inc();
+				let message = insert_interrupted_reply(pool, chat_session_id, TIMED_OUT_REPLY).await?;
+				return Ok(LlmReply {
+					message,
+					timed_out: true,
+				});
+			}
+		},
+		_ = cancellation.cancelled() => {
+			info!(
+				target: "orchestrator_pipeline",
+				chat_session_id = chat_session_id,
+				"Orchestrator agent cancelled by the user"
+			);
+			let message = insert_interrupted_reply(pool, chat_session_id, CANCELLED_REPLY).await?;
+			return Ok(LlmReply {
+				message,
+				timed_out: false,
+			});
+		}
 	};
 
+	// An agent stuck calling tools in a loop gets an apology sent instead of whatever it sent
+	let tool_call_count = context_store
+		.read()
+		.await
+		.get(&chat_session_id)
+		.map_or(0, |ctx| ctx.tool_call_count);
+	if tool_call_count >= MAX_TOOL_CALLS {
+		warn!(
+			target: "orchestrator_pipeline",
+			chat_session_id = chat_session_id,
+			tool_call_count = tool_call_count,
+			"Orchestrator agent made too many tool calls, discarding its reply"
+		);
+		LLM_PIPELINE_FAILURES_TOTAL
+			.with_label_values(&["too_many_tool_calls"])
+			.inc();
+		// A reply a tool sent before the agent got stuck is replaced by the apology too
+		sqlx::query!(
+			"DELETE FROM messages WHERE chat_session_id = $1 AND NOT is_user AND id > $2",
+			chat_session_id,
+			last_message_id
+		)
+		.execute(pool)
+		.await
+		.map_err(AppError::from)?;
+		let message =
+			insert_interrupted_reply(pool, chat_session_id, TOO_MANY_TOOL_CALLS_REPLY).await?;
+		return Ok(LlmReply {
+			message,
+			timed_out: false,
+		});
+	}
+
+	let message = handle_agent_output(pool, account_id, chat_session_id, ai_text).await?;
+
+	// A failed reply is over once a message gets through
+	sqlx::query!(
+		"UPDATE chat_sessions SET llm_progress = $1 WHERE id = $2 AND llm_progress = $3",
+		LlmProgress::Ready as _,
+		chat_session_id,
+		LlmProgress::Failed as _,
+	)
+	.execute(pool)
+	.await
+	.map_err(AppError::from)?;
+	Ok(LlmReply {
+		message,
+		timed_out: false,
+	})
+}
+
+/// Turn the orchestrator's output into the bot message, inserting it if no tool already did
+async fn handle_agent_output(
+	pool: &PgPool,
+	account_id: i32,
+	chat_session_id: i32,
+	ai_text: String,
+) -> ApiResult<Message> {
 	info!(
 		target: "orchestrator_pipeline",
 		chat_session_id = chat_session_id,
@@ -247,7 +632,7 @@ async fn send_message_to_llm(
 			r#"
 			SELECT id, timestamp, text, itinerary_id
 			FROM messages
-			WHERE chat_session_id = $1 AND is_user = FALSE
+			WHERE chat_session_id = $1 AND is_user = FALSE AND deleted_at IS NULL
 			ORDER BY timestamp DESC
 			LIMIT 1
 			"#,
@@ -320,7 +705,7 @@ async fn send_message_to_llm(
 			r#"
 			SELECT id, timestamp, text, itinerary_id
 			FROM messages
-			WHERE chat_session_id = $1 AND is_user = FALSE
+			WHERE chat_session_id = $1 AND is_user = FALSE AND deleted_at IS NULL
 			ORDER BY timestamp DESC
 			LIMIT 1
 			"#,
@@ -350,9 +735,81 @@ async fn send_message_to_llm(
 		}
 	}
 
-	// Check if we're using MockLLM
+	// Only the mock LLM fabricates a demo itinerary, real replies are stored as plain messages
 	let use_mock = std::env::var("DEPLOY_LLM").unwrap_or_default() != "1";
+	insert_agent_reply(pool, account_id, chat_session_id, ai_text, use_mock).await
+}
+
+/// Insert [AGENT_ERROR_REPLY] after the agent errored or panicked, so the user gets a bot
+/// message instead of a 500.
+/// * Only errors if the reply can't be inserted, then the chat is marked `Failed` rather than
+///   left on whatever stage the pipeline stopped in
+async fn insert_agent_error_reply(pool: &PgPool, chat_session_id: i32) -> ApiResult<LlmReply> {
+	match insert_interrupted_reply(pool, chat_session_id, AGENT_ERROR_REPLY).await {
+		Ok(message) => Ok(LlmReply {
+			message,
+			timed_out: false,
+		}),
+		Err(e) => {
+			_ = sqlx::query!(
+				"UPDATE chat_sessions SET llm_progress = $1 WHERE id = $2",
+				LlmProgress::Failed as _,
+				chat_session_id
+			)
+			.execute(pool)
+			.await;
+			Err(e)
+		}
+	}
+}
+
+/// Insert a canned bot reply for a reply that was stopped, and mark the pipeline as ready again
+pub async fn insert_interrupted_reply(
+	pool: &PgPool,
+	chat_session_id: i32,
+	text: &str,
+) -> ApiResult<Message> {
+	sqlx::query!(
+		"UPDATE chat_sessions SET llm_progress = $1 WHERE id = $2",
+		LlmProgress::Ready as _,
+		chat_session_id
+	)
+	.execute(pool)
+	.await
+	.map_err(AppError::from)?;
+
+	let record = sqlx::query!(
+		r#"
+		INSERT INTO messages (chat_session_id, itinerary_id, is_user, timestamp, text)
+		VALUES ($1, NULL, FALSE, NOW(), $2)
+		RETURNING id, timestamp;
+		"#,
+		chat_session_id,
+		text
+	)
+	.fetch_one(pool)
+	.await
+	.map_err(AppError::from)?;
+
+	Ok(Message {
+		id: record.id,
+		is_user: false,
+		timestamp: record.timestamp,
+		text: text.to_string(),
+		itinerary_id: None,
+	})
+}
 
+/// Insert the agent's reply when no tool has already inserted it
+/// - With the mock LLM, attaches a demo itinerary so the frontend has something to show
+/// - Otherwise inserts the reply without an itinerary, conversational replies shouldn't create itineraries
+pub async fn insert_agent_reply(
+	pool: &PgPool,
+	account_id: i32,
+	chat_session_id: i32,
+	ai_text: String,
+	use_mock: bool,
+) -> ApiResult<Message> {
 	if use_mock {
 		// MockLLM fallback: Create and insert a dummy itinerary
 		let mut ai_itinerary = Itinerary {
@@ -448,8 +905,12 @@ async fn send_message_to_llm(
 			chat_session_id: None,
 			title: String::from("World Tour 11/5-15 2025"),
 			unassigned_events: vec![],
+			starred: false,
 		};
 
+		// Itinerary, events, and message are written together or not at all
+		let mut tx = pool.begin().await.map_err(AppError::from)?;
+
 		// Insert generated itinerary into db
 		let inserted_itinerary_id = sqlx::query!(
 			r#"
@@ -463,7 +924,7 @@ async fn send_message_to_llm(
 			chat_session_id,
 			ai_itinerary.title
 		)
-		.fetch_one(pool)
+		.fetch_one(&mut *tx)
 		.await
 		.map_err(AppError::from)?
 		.id;
@@ -471,7 +932,7 @@ async fn send_message_to_llm(
 		ai_itinerary.id = inserted_itinerary_id;
 
 		// Insert itinerary events
-		insert_event_list(ai_itinerary, pool).await?;
+		insert_event_list(ai_itinerary, &mut *tx).await?;
 
 		// Insert bot message with itinerary
 		let record = sqlx::query!(
@@ -484,10 +945,13 @@ async fn send_message_to_llm(
 			inserted_itinerary_id,
 			ai_text.clone()
 		)
-		.fetch_one(pool)
+		.fetch_one(&mut *tx)
 		.await
 		.map_err(AppError::from)?;
 
+		tx.commit().await.map_err(AppError::from)?;
+		ITINERARIES_GENERATED_TOTAL.inc();
+
 		let (bot_message_id, timestamp) = (record.id, record.timestamp);
 
 		return Ok(Message {
@@ -499,8 +963,8 @@ async fn send_message_to_llm(
 		});
 	}
 
-	// When using real LLM: The respond_to_user tool handles message insertion,
-	// so we should never reach this point. If we do, insert a plain message without itinerary.
+	// When using real LLM: The respond_to_user tool usually handles message insertion,
+	// so this is only reached for plain replies. Insert them without an itinerary.
 	info!(
 		target: "orchestrator_pipeline",
 		chat_session_id = chat_session_id,
@@ -550,21 +1014,31 @@ async fn send_message_to_llm(
 /// # Method
 /// `GET /api/chat/chats`
 ///
+/// # Query Parameters
+/// - `sort` - optional, `last_message_at` puts the most recently active chats first, after the pinned ones.
+///   Without it chats with the latest messages still come first, but empty chats go last
+/// - `include_archived` - optional, `true` also returns archived chats
+///
 /// # Responses
 /// - `200 OK` - [ChatsResponse] - list of chat session ids
+/// - `400 BAD_REQUEST` - Unknown sort (public error)
 /// - `401 UNAUTHORIZED` - When authentication fails (handled in middleware, public error)
 /// - `500 INTERNAL_SERVER_ERROR` - Internal error (private)
 ///
 /// # Examples
 /// ```bash
-/// curl -X GET http://localhost:3001/api/chat/chats
+/// curl -X GET "http://localhost:3001/api/chat/chats?sort=last_message_at"
 ///   -H "Content-Type: application/json"
 /// ```
 #[utoipa::path(
 	get,
 	path="/chats",
 	summary="Fetch user's chat session IDs",
-	description="Fetches a list of all chat session IDs belonging to the user.",
+	description="Fetches a list of all chat session IDs belonging to the user, pinned chats first. With `sort=last_message_at` the chats with the latest messages come first. Archived chats are left out unless `include_archived=true`.",
+	params(
+		("sort"=Option<ChatSort>, Query, description="Order of the chats, the latest messages first then empty chats in creation order if omitted"),
+		("include_archived"=Option<bool>, Query, description="Also return archived chats, false if omitted")
+	),
 	responses(
 		(
 			status=200,
@@ -575,24 +1049,42 @@ async fn send_message_to_llm(
 				"chat_sessions": [
 					{
 						"id": 5,
-						"title": "Berlin, Germany"
+						"title": "Berlin, Germany",
+						"last_message_at": "2025-10-14T11:39:10Z",
+						"message_count": 6,
+						"unread_count": 1,
+						"archived": false,
+						"pinned": true,
+						"last_activity": "2025-10-14T11:39:10Z"
 					},
 					{
 						"id": 17,
-						"title": "Shanghai, China"
+						"title": "Shanghai, China",
+						"last_message_at": "2025-10-12T08:02:44Z",
+						"message_count": 2,
+						"unread_count": 0,
+						"archived": false,
+						"pinned": false,
+						"last_activity": "2025-10-12T08:02:44Z"
 					},
 					{
 						"id": 41,
-						"title": "Miami, Florida, USA"
+						"title": "Miami, Florida, USA",
+						"last_message_at": null,
+						"message_count": 0,
+						"unread_count": 0,
+						"archived": false,
+						"pinned": false,
+						"last_activity": "2025-10-10T17:20:05Z"
 					}
 				]
 			})
 		),
-		(status=400, description="Bad Request"),
-		(status=401, description="User has an invalid cookie/no cookie"),
+		(status=400, description="Bad Request", body=ErrorBody),
+		(status=401, description="User has an invalid cookie/no cookie", body=ErrorBody),
 		(status=405, description="Method Not Allowed - Must be GET"),
 		(status=408, description="Request Timed Out"),
-		(status=500, description="Internal Server Error")
+		(status=500, description="Internal Server Error", body=ErrorBody)
 	),
 	security(("set-cookie"=[])),
 	tag="Chat"
@@ -600,15 +1092,43 @@ async fn send_message_to_llm(
 pub async fn api_chats(
 	Extension(user): Extension<AuthUser>,
 	Extension(pool): Extension<PgPool>,
+	Query(query): Query<ChatsQuery>,
 ) -> ApiResult<Json<ChatsResponse>> {
+	let by_last_message = query.sort == Some(ChatSort::LastMessageAt);
 	Ok(Json(ChatsResponse {
 		chat_sessions: sqlx::query_as!(
 			ChatSessionRow,
 			r#"
-			SELECT id, title from chat_sessions
-			WHERE account_id=$1;
+			SELECT
+				c.id,
+				c.title,
+				c.last_message_at,
+				(
+					SELECT COUNT(*) FROM messages
+					WHERE chat_session_id = c.id AND deleted_at IS NULL
+				) AS "message_count!",
+				(
+					SELECT COUNT(*) FROM messages m
+					WHERE
+						m.chat_session_id = c.id AND
+						NOT m.is_user AND
+						m.deleted_at IS NULL AND
+						(c.last_read_at IS NULL OR m.timestamp > c.last_read_at AT TIME ZONE 'UTC')
+				) AS "unread_count!",
+				c.archived,
+				c.pinned,
+				COALESCE(c.last_message_at, c.created_at) AS "last_activity!"
+			FROM chat_sessions c
+			WHERE c.account_id=$1 AND ($3 OR NOT c.archived)
+			ORDER BY
+				c.pinned DESC,
+				CASE WHEN $2 THEN COALESCE(c.last_message_at, c.created_at) END DESC,
+				c.last_message_at DESC NULLS LAST,
+				c.id;
 			"#,
-			user.id
+			user.id,
+			by_last_message,
+			query.include_archived
 		)
 		.fetch_all(&pool)
 		.await
@@ -618,6 +1138,8 @@ pub async fn api_chats(
 
 /// Get a page of messages from this chat session belonging to the user who made the request
 ///
+/// Fetching the latest page marks the chat session as read.
+///
 /// # Method
 /// `POST /api/chat/messagePage`
 ///
@@ -712,11 +1234,11 @@ pub async fn api_chats(
 				))
 			)
 		),
-		(status=400, description="Bad Request"),
-		(status=401, description="User has an invalid cookie/no cookie"),
+		(status=400, description="Bad Request", body=ErrorBody),
+		(status=401, description="User has an invalid cookie/no cookie", body=ErrorBody),
 		(status=405, description="Method Not Allowed - Must be POST"),
 		(status=408, description="Request Timed Out"),
-		(status=500, description="Internal Server Error")
+		(status=500, description="Internal Server Error", body=ErrorBody)
 	),
 	security(("set-cookie"=[])),
 	tag="Chat"
@@ -745,6 +1267,7 @@ pub async fn api_message_page(
 		WHERE
 			c.id=$1 AND
 			c.account_id=$2 AND
+			m.deleted_at IS NULL AND
 			(
 				$3::int IS NULL OR
 				m.timestamp <= (SELECT timestamp FROM messages WHERE id=$3)
@@ -771,6 +1294,18 @@ pub async fn api_message_page(
 	})
 	.collect();
 
+	// The user has now seen the latest messages
+	if message_id.is_none() {
+		sqlx::query!(
+			"UPDATE chat_sessions SET last_read_at = NOW() WHERE id = $1 AND account_id = $2",
+			chat_session_id,
+			user.id
+		)
+		.execute(&pool)
+		.await
+		.map_err(AppError::from)?;
+	}
+
 	let prev_message_id = if message_page.len() == MESSAGE_PAGE_LEN as usize + 1 {
 		// there might be a better way to do this, but it should work, and it's only O(MESSAGE_PAGE_LEN) time complexity
 		Some(message_page.remove(0).id)
@@ -784,609 +1319,2647 @@ pub async fn api_message_page(
 	}))
 }
 
-/// Update an existing message with new text, and get a message back from the LLM
+/// Search the text of the user's messages
 ///
 /// # Method
-/// `POST /api/chat/updateMessage`
+/// `POST /api/chat/searchMessages`
 ///
 /// # Request Body
-/// - [UpdateMessageRequest]
+/// - `query`: Words to look for, in web search syntax
+/// - `chat_session_id`: Optional chat session to search in, all of the user's chats otherwise
+/// - `page`: Optional page of results, starting at 0
 ///
 /// # Responses
-/// - `200 OK` - with body: [Message] - message from LLM
-/// - `400 BAD_REQUEST` - Request payload contains invalid data (public error)
+/// - `200 OK` - with body: [SearchMessagesResponse]
+/// - `400 BAD_REQUEST` - The query is empty (public error)
 /// - `401 UNAUTHORIZED` - When authentication fails (handled in middleware, public error)
-/// - `404 NOT_FOUND` - The provided message id does not belong to the user or does not exist (public error)
 /// - `500 INTERNAL_SERVER_ERROR` - Internal error (private)
 ///
 /// # Examples
 /// ```bash
-/// curl -X POST http://localhost:3001/api/chat/updateMessage
+/// curl -X POST http://localhost:3001/api/chat/searchMessages
 ///   -H "Content-Type: application/json"
 ///   -d '{
-///         "message_id": 3,
-///         "new_text": "Updated message",
-///         "itinerary_id": 7
+///         "query": "lisbon",
+///         "page": 0
 ///       }'
 /// ```
+///
+/// Notes:
+/// - Pages have at most `MESSAGE_PAGE_LEN` results, best matches first.
+/// - Deleted messages aren't searched.
+/// - Snippets are HTML escaped, the only markup in them is the `<b>` and `</b>` around matches.
 #[utoipa::path(
 	post,
-	path="/updateMessage",
-	summary="Update the text of a message and wait for a reply from the LLM",
-	description="Updating a message deletes all proceeding messages, updates the text of the given message, and returns a response from the LLM.",
+	path="/searchMessages",
+	summary="Search the user's messages",
+	description="Full text search over the messages in the user's chat sessions, or in one of them. Each match comes with an HTML escaped snippet of the message, with the matching words wrapped in <b> tags.",
 	request_body(
-		content=UpdateMessageRequest,
+		content=SearchMessagesRequest,
 		content_type="application/json",
-		description="Itinerary id is optional and is used to give context to the LLM.",
 		example=json!({
-			"message_id": 41,
-			"new_text": "Updated message content",
-			"itinerary_id": 17
+			"query": "lisbon",
+			"chat_session_id": null,
+			"page": 0
 		})
 	),
 	responses(
 		(
 			status=200,
-			description="Message updated, and LLM replied successfully",
-			body=Message,
+			description="A page of matching messages",
+			body=SearchMessagesResponse,
 			content_type="application/json",
 			example=json!({
-				"id": 43,
-				"is_user": false,
-				"timestamp": "2025-10-14 11-38-52",
-				"text": "Bot reply",
-				"itinerary_id": 19
+				"results": [
+					{
+						"chat_session_id": 4,
+						"message_id": 61,
+						"snippet": "Plan a weekend in <b>Lisbon</b> in May",
+						"timestamp": "2025-10-14T11:36:24"
+					}
+				],
+				"total_count": 1
 			})
 		),
-		(status=400, description="Bad Request"),
-		(status=401, description="User has an invalid cookie/no cookie"),
-		(status=404, description="Message not found in this chat session for this user"),
+		(status=400, description="Search query must not be empty", body=ErrorBody),
+		(status=401, description="User has an invalid cookie/no cookie", body=ErrorBody),
 		(status=405, description="Method Not Allowed - Must be POST"),
 		(status=408, description="Request Timed Out"),
-		(status=500, description="Internal Server Error")
+		(status=500, description="Internal Server Error", body=ErrorBody)
 	),
 	security(("set-cookie"=[])),
 	tag="Chat"
 )]
-pub async fn api_update_message(
+pub async fn api_search_messages(
 	Extension(user): Extension<AuthUser>,
 	Extension(pool): Extension<PgPool>,
-	Extension(agent): Extension<AgentType>,
-	Extension(chat_session_id_atomic): Extension<std::sync::Arc<std::sync::atomic::AtomicI32>>,
-	Extension(context_store): Extension<crate::agent::models::context::SharedContextStore>,
-	Json(UpdateMessageRequest {
-		message_id,
-		new_text,
-		itinerary_id,
-	}): Json<UpdateMessageRequest>,
-) -> ApiResult<Json<Message>> {
-	if new_text.is_empty() {
-		return Err(AppError::BadRequest(String::from("Text cannot be empty")));
+	Json(SearchMessagesRequest {
+		query,
+		chat_session_id,
+		page,
+	}): Json<SearchMessagesRequest>,
+) -> ApiResult<Json<SearchMessagesResponse>> {
+	let query = query.trim();
+	if query.is_empty() {
+		return Err(AppError::Validation(
+			"Search query must not be empty".to_string(),
+		));
 	}
+	let offset = page as i64 * MESSAGE_PAGE_LEN as i64;
 
-	// Get the message and verify ownership in one query
-	let message_info = sqlx::query!(
+	let total_count = sqlx::query_scalar!(
 		r#"
-		SELECT m.chat_session_id, m.timestamp
+		SELECT COUNT(*) AS "count!"
 		FROM messages m
 		INNER JOIN chat_sessions c ON m.chat_session_id = c.id
-		WHERE m.id = $1 AND c.account_id = $2 AND m.is_user = TRUE;
+		WHERE
+			c.account_id = $1 AND
+			($3::int IS NULL OR c.id = $3) AND
+			m.deleted_at IS NULL AND
+			to_tsvector('english', m.text) @@ websearch_to_tsquery('english', $2)
 		"#,
-		message_id,
-		user.id
+		user.id,
+		query,
+		chat_session_id
 	)
-	.fetch_optional(&pool)
+	.fetch_one(&pool)
 	.await
-	.map_err(AppError::from)?
-	.ok_or(AppError::NotFound)?;
-
-	let chat_session_id = message_info.chat_session_id;
-	let message_timestamp = message_info.timestamp;
+	.map_err(AppError::from)?;
 
-	// Delete future messages in this chat session only
-	sqlx::query!(
+	let results = sqlx::query_as!(
+		MessageSearchResult,
 		r#"
-		DELETE FROM messages
-		WHERE chat_session_id = $1
-		  AND timestamp > $2
-		  AND id != $3;
+		SELECT
+			m.chat_session_id,
+			m.id AS message_id,
+			ts_headline('english', m.text, q.query, $6) AS "snippet!",
+			m.timestamp
+		FROM messages m
+		INNER JOIN chat_sessions c ON m.chat_session_id = c.id
+		CROSS JOIN websearch_to_tsquery('english', $2) AS q(query)
+		WHERE
+			c.account_id = $1 AND
+			($3::int IS NULL OR c.id = $3) AND
+			m.deleted_at IS NULL AND
+			to_tsvector('english', m.text) @@ q.query
+		ORDER BY ts_rank(to_tsvector('english', m.text), q.query) DESC, m.timestamp DESC, m.id DESC
+		LIMIT $4 OFFSET $5
 		"#,
+		user.id,
+		query,
 		chat_session_id,
-		message_timestamp,
-		message_id
+		MESSAGE_PAGE_LEN as i64,
+		offset,
+		format!("StartSel=\"{HEADLINE_START_SEL}\", StopSel=\"{HEADLINE_STOP_SEL}\"")
 	)
-	.execute(&pool)
+	.fetch_all(&pool)
 	.await
-	.map_err(AppError::from)?;
+	.map_err(AppError::from)?
+	.into_iter()
+	.map(|result| MessageSearchResult {
+		snippet: highlight_snippet(&result.snippet),
+		..result
+	})
+	.collect();
 
-	// Update the user message
-	sqlx::query!(
-		r#"
-		UPDATE messages
-		SET text = $1, timestamp = NOW()
-		WHERE id = $2;
-		"#,
-		new_text,
-		message_id
-	)
-	.execute(&pool)
-	.await
-	.map_err(AppError::from)?;
+	Ok(Json(SearchMessagesResponse {
+		results,
+		total_count,
+	}))
+}
 
-	// Call LLM and insert bot response
-	let bot_message = send_message_to_llm(
-		new_text.as_str(),
-		user.id,
+/// HTML escapes a `ts_headline` snippet, then wraps each match in `<b>` and `</b>`,
+/// so the message text can't add markup of its own
+fn highlight_snippet(snippet: &str) -> String {
+	let mut highlighted = String::with_capacity(snippet.len());
+	for c in snippet.chars() {
+		match c {
+			'&' => highlighted.push_str("&amp;"),
+			'<' => highlighted.push_str("&lt;"),
+			'>' => highlighted.push_str("&gt;"),
+			'"' => highlighted.push_str("&quot;"),
+			'\'' => highlighted.push_str("&#39;"),
+			HEADLINE_START_SEL => highlighted.push_str("<b>"),
+			HEADLINE_STOP_SEL => highlighted.push_str("</b>"),
+			c => highlighted.push(c),
+		}
+	}
+	highlighted
+}
+
+/// Messages of the chat after `cursor` in chronological order, at most `MESSAGE_EXPORT_CHUNK_LEN`,
+/// each with the dates of its itinerary if it has one
+async fn export_chunk(
+	pool: &PgPool,
+	chat_session_id: i32,
+	cursor: Option<(NaiveDateTime, i32)>,
+) -> Result<Vec<(ExportedMessage, Option<(NaiveDate, NaiveDate)>)>, sqlx::Error> {
+	let (after_timestamp, after_id) = cursor.unzip();
+	let rows = sqlx::query!(
+		r#"
+		SELECT
+			m.id,
+			m.itinerary_id,
+			m.is_user,
+			m.timestamp,
+			m.text,
+			i.title AS "itinerary_title?",
+			i.start_date AS "start_date?",
+			i.end_date AS "end_date?"
+		FROM messages m
+		LEFT JOIN itineraries i
+		ON i.id=m.itinerary_id AND i.deleted_at IS NULL
+		WHERE
+			m.chat_session_id=$1 AND
+			m.deleted_at IS NULL AND
+			($2::timestamp IS NULL OR (m.timestamp, m.id) > ($2, $3::int))
+		ORDER BY m.timestamp, m.id
+		LIMIT $4;
+		"#,
 		chat_session_id,
-		itinerary_id,
-		&pool,
-		&agent,
-		&chat_session_id_atomic,
-		&context_store,
+		after_timestamp,
+		after_id,
+		MESSAGE_EXPORT_CHUNK_LEN
 	)
+	.fetch_all(pool)
 	.await?;
 
-	Ok(Json(bot_message))
+	Ok(rows
+		.into_iter()
+		.map(|row| {
+			let message = ExportedMessage {
+				message: Message {
+					id: row.id,
+					is_user: row.is_user,
+					timestamp: row.timestamp,
+					text: row.text,
+					itinerary_id: row.itinerary_id,
+				},
+				itinerary_title: row.itinerary_title,
+			};
+			(message, row.start_date.zip(row.end_date))
+		})
+		.collect())
 }
 
-/// Send a new message, and get a message back from the LLM
+/// Renders a chunk of [export_chunk] in `format`, `first` is whether it starts the transcript
+fn render_export_chunk(
+	format: ExportFormat,
+	chunk: &[(ExportedMessage, Option<(NaiveDate, NaiveDate)>)],
+	first: bool,
+) -> Result<String, serde_json::Error> {
+	let mut out = String::new();
+	for (i, (exported, dates)) in chunk.iter().enumerate() {
+		match format {
+			ExportFormat::Json => {
+				if !first || i > 0 {
+					out.push(',');
+				}
+				out.push_str(&serde_json::to_string(exported)?);
+			}
+			ExportFormat::Markdown => {
+				let message = &exported.message;
+				let author = if message.is_user { "User" } else { "Journey" };
+				out.push_str(&format!(
+					"## {author} · {} UTC\n\n{}\n\n",
+					message.timestamp.format("%Y-%m-%d %H:%M"),
+					message.text.trim_end()
+				));
+				if let Some(title) = &exported.itinerary_title {
+					out.push_str(&format!("> Itinerary: **{title}**"));
+					if let Some((start_date, end_date)) = dates {
+						out.push_str(&format!(", {start_date} to {end_date}"));
+					}
+					out.push_str("\n\n");
+				}
+			}
+			ExportFormat::Txt => {
+				let message = &exported.message;
+				let author = if message.is_user { "User" } else { "Bot" };
+				out.push_str(&format!(
+					"[{author}] {} UTC: {}\n",
+					message.timestamp.format("%Y-%m-%d %H:%M:%S"),
+					message.text.trim_end()
+				));
+			}
+		}
+	}
+	Ok(out)
+}
+
+/// Streams the chat's transcript in `format`, fetching `MESSAGE_EXPORT_CHUNK_LEN` messages
+/// at a time so long chats are never held in memory at once
+fn export_transcript(
+	pool: PgPool,
+	chat_session_id: i32,
+	title: String,
+	format: ExportFormat,
+) -> impl Stream<Item = Result<String, BoxError>> + Send + 'static {
+	let header = match format {
+		ExportFormat::Json => String::from("["),
+		ExportFormat::Markdown => format!("# {title}\n\n"),
+		ExportFormat::Txt => String::new(),
+	};
+	// The state is `None` once the transcript is finished, otherwise the timestamp and id
+	// of the last message written, if any
+	let body = futures::stream::unfold(Some(None), move |state| {
+		let pool = pool.clone();
+		async move {
+			let cursor: Option<(NaiveDateTime, i32)> = state?;
+			let chunk = match export_chunk(&pool, chat_session_id, cursor).await {
+				Ok(chunk) => chunk,
+				Err(e) => {
+					error!(chat_session_id, error = %e, "Failed to export chat transcript");
+					return Some((Err(e.into()), None));
+				}
+			};
+			let Some((last, _)) = chunk.last() else {
+				let footer = match format {
+					ExportFormat::Json => "]",
+					ExportFormat::Markdown | ExportFormat::Txt => "",
+				};
+				return Some((Ok(String::from(footer)), None));
+			};
+			let next = (last.message.timestamp, last.message.id);
+			let rendered = render_export_chunk(format, &chunk, cursor.is_none());
+			Some(match rendered {
+				Ok(rendered) => (Ok(rendered), Some(Some(next))),
+				Err(e) => (Err(e.into()), None),
+			})
+		}
+	});
+	futures::stream::once(async move { Ok(header) }).chain(body)
+}
+
+/// Download the whole chat session
 ///
 /// # Method
-/// `POST /api/chat/sendMessage`
+/// `GET /api/chat/:id/export`
 ///
-/// # Request Body
-/// - [SendMessageRequest]
+/// # Query Parameters
+/// - `format` - optional, `json` (default), `markdown` (or `md`) or `txt`
 ///
 /// # Responses
-/// - `200 OK` - with body: [SendMessageResponse] - contains message from LLM
-/// - `400 BAD_REQUEST` - Request payload contains invalid data (public error)
+/// - `200 OK` - with a download of every message in chronological order
+///   - `json`: an array of [ExportedMessage]
+///   - `markdown`: the user and bot turns with timestamps and the itineraries they reference
+///   - `txt`: a `[User]` or `[Bot]` line per message with its timestamp
+/// - `400 BAD_REQUEST` - Unknown format (public error)
 /// - `401 UNAUTHORIZED` - When authentication fails (handled in middleware, public error)
 /// - `404 NOT_FOUND` - The provided chat session id does not belong to the user or does not exist (public error)
 /// - `500 INTERNAL_SERVER_ERROR` - Internal error (private)
 ///
 /// # Examples
 /// ```bash
-/// curl -X POST http://localhost:3001/api/chat/sendMessage
-///   -H "Content-Type: application/json"
-///   -d '{
-///         "chat_session_id": 6,
-///         "text": "New message",
-///         "itinerary_id": 7
-///       }'
+/// curl http://localhost:3001/api/chat/7/export?format=markdown
+/// curl http://localhost:3001/api/chat/7/export?format=txt
 /// ```
+///
+/// Notes:
+/// - The transcript is streamed in chunks of `MESSAGE_EXPORT_CHUNK_LEN` messages, so a
+///   database error partway through cuts the download short.
+/// - Deleted messages aren't exported.
 #[utoipa::path(
-	post,
-	path="/sendMessage",
-	summary="Send a message and wait for a reply from the LLM",
-	description="Ask the LLM to generate an itinerary and it should respond with one.",
-	request_body(
-		content=SendMessageRequest,
-		content_type="application/json",
-		description="Itinerary id is optional and is used to give context to the LLM.",
-		example=json!({
-			"chat_session_id": 12,
-			"text": "Make an itinerary",
-			"itinerary_id": 13
-		})
+	get,
+	path="/{id}/export",
+	summary="Export a chat transcript",
+	description="Downloads every message of a chat session that belongs to the user, as JSON, Markdown or plain text. Messages that reference an itinerary include its title in JSON and Markdown.",
+	params(
+		("id"=i32, Path, description="Chat session to export"),
+		("format"=Option<ExportFormat>, Query, description="File format, JSON if omitted")
 	),
 	responses(
 		(
 			status=200,
-			description="Message sent, and LLM replied successfully",
-			body=SendMessageResponse,
+			description="The chat transcript as an attachment",
+			body=Vec<ExportedMessage>,
 			content_type="application/json",
-			example=json!({
-				"user_message_id": 52,
-				"bot_message": {
-					"id": 53,
+			example=json!([
+				{
+					"id": 61,
+					"is_user": true,
+					"timestamp": "2025-10-14T11:36:24",
+					"text": "Plan a weekend in Lisbon in May",
+					"itinerary_id": null,
+					"itinerary_title": null
+				},
+				{
+					"id": 62,
 					"is_user": false,
-					"timestamp": "2025-10-14 11-39-10",
-					"text": "Bot reply",
-					"itinerary_id": 14
+					"timestamp": "2025-10-14T11:36:31",
+					"text": "Here is a weekend in Lisbon!",
+					"itinerary_id": 18,
+					"itinerary_title": "Lisbon Weekend"
 				}
-			})
+			])
 		),
-		(status=400, description="Bad Request"),
-		(status=401, description="User has an invalid cookie/no cookie"),
-		(status=404, description="Chat session not found for this user"),
-		(status=405, description="Method Not Allowed - Must be POST"),
+		(status=400, description="Unknown format", body=ErrorBody),
+		(status=401, description="User has an invalid cookie/no cookie", body=ErrorBody),
+		(status=404, description="Chat session not found for this user", body=ErrorBody),
+		(status=405, description="Method Not Allowed - Must be GET"),
 		(status=408, description="Request Timed Out"),
-		(status=500, description="Internal Server Error")
+		(status=500, description="Internal Server Error", body=ErrorBody)
 	),
 	security(("set-cookie"=[])),
 	tag="Chat"
 )]
-pub async fn api_send_message(
+pub async fn api_export_chat(
 	Extension(user): Extension<AuthUser>,
 	Extension(pool): Extension<PgPool>,
-	Extension(agent): Extension<AgentType>,
-	Extension(chat_session_id_atomic): Extension<std::sync::Arc<std::sync::atomic::AtomicI32>>,
-	Extension(context_store): Extension<crate::agent::models::context::SharedContextStore>,
-	Json(SendMessageRequest {
-		chat_session_id,
-		text,
-		itinerary_id,
-	}): Json<SendMessageRequest>,
-) -> ApiResult<Json<SendMessageResponse>> {
-	if text.is_empty() {
-		return Err(AppError::BadRequest(String::from("Text cannot be empty")));
-	}
-
-	// verify the given chat session belongs to this user
-	sqlx::query!(
-		r#"
-		SELECT id FROM chat_sessions
-		WHERE id=$1 AND account_id=$2;
-		"#,
+	Path(chat_session_id): Path<i32>,
+	Query(ExportQuery { format }): Query<ExportQuery>,
+) -> ApiResult<Response> {
+	// verify chat session belongs to this user
+	let title = sqlx::query_scalar!(
+		r#"SELECT title FROM chat_sessions WHERE id=$1 AND account_id=$2"#,
 		chat_session_id,
 		user.id
 	)
 	.fetch_optional(&pool)
 	.await
 	.map_err(AppError::from)?
-	.ok_or(AppError::NotFound)?;
-
-	// insert user message into db
-	let user_message_id = sqlx::query!(
-		r#"
-		INSERT INTO messages (chat_session_id, itinerary_id, is_user, timestamp, text)
-		VALUES ($1, NULL, TRUE, NOW(), $2)
-		RETURNING id;
-		"#,
-		chat_session_id,
-		text
-	)
-	.fetch_one(&pool)
-	.await
-	.map_err(AppError::from)?
-	.id;
+	.ok_or(AppError::ChatSessionNotFound)?;
 
-	// call llm and insert bot response into db
-	let bot_message = send_message_to_llm(
-		text.as_str(),
-		user.id,
-		chat_session_id,
-		itinerary_id,
-		&pool,
-		&agent,
-		&chat_session_id_atomic,
-		&context_store,
+	let (content_type, extension) = match format {
+		ExportFormat::Json => ("application/json", "json"),
+		ExportFormat::Markdown => ("text/markdown; charset=utf-8", "md"),
+		ExportFormat::Txt => ("text/plain; charset=utf-8", "txt"),
+	};
+	Ok((
+		[
+			(header::CONTENT_TYPE, String::from(content_type)),
+			(
+				header::CONTENT_DISPOSITION,
+				format!("attachment; filename=\"chat-{chat_session_id}.{extension}\""),
+			),
+		],
+		Body::from_stream(export_transcript(pool, chat_session_id, title, format)),
 	)
-	.await?;
-
-	Ok(Json(SendMessageResponse {
-		user_message_id,
-		bot_message,
-	}))
+		.into_response())
 }
 
-/// Get an empty chat session id belonging to this user, or create one if one doesn't exist
+/// Get a short summary of what the chat session is about
 ///
 /// # Method
-/// `GET /api/chat/newChat`
+/// `GET /api/chat/:id/summary`
 ///
 /// # Responses
-/// - `200 OK` - with body: [NewChatResponse]
-/// - `400 BAD_REQUEST` - Request payload contains invalid data (public error)
+/// - `200 OK` - with body: [ChatSummaryResponse]
+/// - `400 BAD_REQUEST` - The chat has no messages to summarize (public error)
 /// - `401 UNAUTHORIZED` - When authentication fails (handled in middleware, public error)
+/// - `404 NOT_FOUND` - The provided chat session id does not belong to the user or does not exist (public error)
 /// - `500 INTERNAL_SERVER_ERROR` - Internal error (private)
 ///
 /// # Examples
 /// ```bash
-/// curl -X POST http://localhost:3001/api/chat/sendMessage
-///   -H "Content-Type: application/json"
-///   -d '{
-///         "chat_session_id": 6,
-///         "text": "New message",
-///         "itinerary_id": 7
-///       }'
+/// curl http://localhost:3001/api/chat/7/summary
 /// ```
+///
+/// Notes:
+/// - The summary is stored with the chat, and only generated again once a message is sent
+///   after it.
+/// - Only the latest `CHAT_SUMMARY_MESSAGE_LEN` messages are given to the LLM.
 #[utoipa::path(
 	get,
-	path="/newChat",
-	summary="Get the chat session id for an empty chat",
-	description="Creates a new empty chat session for this user if one doesn't already exist, and returns its chat session id.",
+	path="/{id}/summary",
+	summary="Summarize a chat session",
+	description="Returns a 2-3 sentence summary of a chat session that belongs to the user. The summary is cached until a new message is sent.",
+	params(
+		("id"=i32, Path, description="Chat session to summarize")
+	),
 	responses(
 		(
 			status=200,
-			description="New chat session retrieved successfully",
-			body=NewChatResponse,
+			description="Summary of the chat session",
+			body=ChatSummaryResponse,
 			content_type="application/json",
 			example=json!({
-				"chat_session_id": 13
+				"summary": "The user is planning a weekend in Lisbon in May. The assistant put together an itinerary focused on food and museums."
 			})
 		),
-		(status=400, description="Bad Request"),
-		(status=401, description="User has an invalid cookie/no cookie"),
+		(status=400, description="The chat has no messages", body=ErrorBody),
+		(status=401, description="User has an invalid cookie/no cookie", body=ErrorBody),
+		(status=404, description="Chat session not found for this user", body=ErrorBody),
 		(status=405, description="Method Not Allowed - Must be GET"),
 		(status=408, description="Request Timed Out"),
-		(status=500, description="Internal Server Error")
+		(status=500, description="Internal Server Error", body=ErrorBody)
 	),
 	security(("set-cookie"=[])),
 	tag="Chat"
 )]
-pub async fn api_new_chat(
+pub async fn api_chat_summary(
 	Extension(user): Extension<AuthUser>,
 	Extension(pool): Extension<PgPool>,
-) -> ApiResult<Json<NewChatResponse>> {
-	// check to see if there's already an empty chat session before making a new one
-	let chat_sessions = sqlx::query!(
+	Extension(llm): Extension<SharedLlm>,
+	Path(chat_session_id): Path<i32>,
+) -> ApiResult<Json<ChatSummaryResponse>> {
+	// verify chat session belongs to this user
+	let cached = sqlx::query!(
 		r#"
-		SELECT c.id
-		FROM chat_sessions c
-		WHERE
-			c.account_id=$1
-			AND NOT EXISTS (
-				SELECT 1
-				FROM messages m
-				WHERE m.chat_session_id=c.id
-			);
+		SELECT summary, summary_updated_at, last_message_at
+		FROM chat_sessions
+		WHERE id=$1 AND account_id=$2
 		"#,
+		chat_session_id,
 		user.id
 	)
+	.fetch_optional(&pool)
+	.await
+	.map_err(AppError::from)?
+	.ok_or(AppError::ChatSessionNotFound)?;
+
+	if let Some(summary) = cached.summary
+		&& cached.summary_updated_at > cached.last_message_at
+	{
+		return Ok(Json(ChatSummaryResponse { summary }));
+	}
+
+	let mut messages = sqlx::query!(
+		r#"
+		SELECT is_user, text
+		FROM messages
+		WHERE chat_session_id=$1 AND deleted_at IS NULL
+		ORDER BY timestamp DESC, id DESC
+		LIMIT $2
+		"#,
+		chat_session_id,
+		CHAT_SUMMARY_MESSAGE_LEN
+	)
 	.fetch_all(&pool)
 	.await
 	.map_err(AppError::from)?;
+	if messages.is_empty() {
+		return Err(AppError::BadRequest(String::from(
+			"There are no messages to summarize",
+		)));
+	}
+	messages.reverse();
 
-	let chat_session_id = match chat_sessions.first() {
-		Some(record) => record.id,
-		None => {
-			// make a new chat session
-			sqlx::query!(
-				r#"
-				INSERT INTO chat_sessions (account_id, title)
-				VALUES ($1, 'New Chat')
-				RETURNING id
-				"#,
-				user.id
-			)
-			.fetch_one(&pool)
-			.await
-			.map_err(AppError::from)?
-			.id
-		}
-	};
+	let mut prompt = String::from(SUMMARY_PROMPT);
+	for message in messages {
+		let speaker = if message.is_user { "User" } else { "Assistant" };
+		prompt.push_str(&format!("\n{speaker}: {}", message.text));
+	}
+	let summary = llm
+		.invoke(&prompt)
+		.await
+		.map_err(|e| AppError::Internal(format!("failed to summarize chat: {e}")))?
+		.trim()
+		.to_string();
 
-	Ok(Json(NewChatResponse { chat_session_id }))
+	sqlx::query!(
+		"UPDATE chat_sessions SET summary = $1, summary_updated_at = NOW() WHERE id = $2",
+		summary,
+		chat_session_id
+	)
+	.execute(&pool)
+	.await
+	.map_err(AppError::from)?;
+
+	Ok(Json(ChatSummaryResponse { summary }))
 }
 
-/// Delete the chat session with the given ID
+/// Update an existing message with new text, and get a message back from the LLM
 ///
 /// # Method
-/// `DELETE /api/chat/:id`
+/// `POST /api/chat/updateMessage`
+///
+/// # Request Body
+/// - [UpdateMessageRequest]
+///   - A request repeating an earlier `client_request_id` in the chat gets the earlier response
 ///
 /// # Responses
-/// - `200 OK` - chat session and associated messages and unsaved itineraries successfully deleted
+/// - `200 OK` - with body: [Message] - message from LLM
 /// - `400 BAD_REQUEST` - Request payload contains invalid data (public error)
 /// - `401 UNAUTHORIZED` - When authentication fails (handled in middleware, public error)
-/// - `404 NOT_FOUND` - The provided chat session id does not belong to the user or does not exist (public error)
+/// - `403 FORBIDDEN` - with code `EMAIL_NOT_VERIFIED` - The account's email isn't verified (public error)
+/// - `404 NOT_FOUND` - The provided message id does not belong to the user or does not exist (public error)
+/// - `409 CONFLICT` - A request with the same `client_request_id` is still being processed (public error)
 /// - `500 INTERNAL_SERVER_ERROR` - Internal error (private)
+/// - `503 SERVICE_UNAVAILABLE` - AI features are disabled, or too many replies are being generated (public error)
 ///
 /// # Examples
 /// ```bash
-/// curl -X DELETE http://localhost:3001/api/chat/7
+/// curl -X POST http://localhost:3001/api/chat/updateMessage
 ///   -H "Content-Type: application/json"
+///   -d '{
+///         "message_id": 3,
+///         "new_text": "Updated message",
+///         "itinerary_id": 7
+///       }'
 /// ```
+///
+/// Notes:
+/// - `new_text` is cleaned up like a sent message before it's stored, see [sanitize_user_input]
 #[utoipa::path(
-	delete,
-	path="/{id}",
-	summary="Delete the given chat session",
-	description="Deletes a chat session and its associated messages and unsaved, private itineraries if it belongs to the user making the request.",
+	post,
+	path="/updateMessage",
+	summary="Update the text of a message and wait for a reply from the LLM",
+	description="Updating a message deletes all proceeding messages, updates the text of the given message, and returns a response from the LLM.",
+	request_body(
+		content=UpdateMessageRequest,
+		content_type="application/json",
+		description="Itinerary id is optional and is used to give context to the LLM. Client request id is optional, retrying with the same one returns the original response instead of updating the message again.",
+		example=json!({
+			"message_id": 41,
+			"new_text": "Updated message content",
+			"itinerary_id": 17,
+			"client_request_id": "9b2f6c1e-4d3a-4f5b-8a7c-2e1d0f9a8b7c"
+		})
+	),
 	responses(
-		(status=200, description="Chat session and associated messages and unsaved, private itineraries deleted successfully"),
-		(status=400, description="Bad Request"),
-		(status=401, description="User has an invalid cookie/no cookie"),
-		(status=404, description="Chat session not found for this user"),
-		(status=405, description="Method Not Allowed - Must be DELETE"),
+		(
+			status=200,
+			description="Message updated, and LLM replied successfully",
+			body=Message,
+			content_type="application/json",
+			example=json!({
+				"id": 43,
+				"is_user": false,
+				"timestamp": "2025-10-14 11-38-52",
+				"text": "Bot reply",
+				"itinerary_id": 19
+			})
+		),
+		(status=400, description="Bad Request", body=ErrorBody),
+		(status=401, description="User has an invalid cookie/no cookie", body=ErrorBody),
+		(status=403, description="Email not verified", body=ErrorBody),
+		(status=404, description="Message not found in this chat session for this user", body=ErrorBody),
+		(status=405, description="Method Not Allowed - Must be POST"),
+		(status=409, description="A request with the same client request id is still being processed", body=ErrorBody),
 		(status=408, description="Request Timed Out"),
-		(status=500, description="Internal Server Error")
+		(status=500, description="Internal Server Error", body=ErrorBody),
+		(status=503, description="AI features are disabled, or the server is busy", body=ErrorBody)
 	),
 	security(("set-cookie"=[])),
 	tag="Chat"
 )]
-pub async fn api_delete_chat(
+pub async fn api_update_message(
 	Extension(user): Extension<AuthUser>,
 	Extension(pool): Extension<PgPool>,
-	Path(chat_session_id): Path<i32>,
-) -> ApiResult<()> {
-	// itineraries do not cascade, so we delete manually
-	sqlx::query!(
-		r#"
-		DELETE FROM itineraries
-		WHERE
-			chat_session_id=$1 AND
-			account_id=$2 AND
-			is_public=FALSE AND
-			saved=FALSE;
-		"#,
-		chat_session_id,
-		user.id
-	)
-	.execute(&pool)
-	.await
-	.map_err(AppError::from)?;
+	Extension(agent): Extension<Option<SharedAgentFactory>>,
+	Extension(context_store): Extension<crate::agent::models::context::SharedContextStore>,
+	Json(UpdateMessageRequest {
+		message_id,
+		new_text,
+		itinerary_id,
+		client_request_id,
+	}): Json<UpdateMessageRequest>,
+) -> ApiResult<Json<Message>> {
+	let agent = require_agent(agent)?;
+	require_verified_email(&pool, user.id).await?;
+	let new_text = sanitize_user_input(&new_text);
+	if new_text.is_empty() {
+		return Err(AppError::EmptyText);
+	}
 
-	// messages will cascade
-	sqlx::query!(
+	// Get the message and verify ownership in one query
+	let message_info = sqlx::query!(
 		r#"
-		DELETE FROM chat_sessions
-		WHERE id=$1 AND account_id=$2
-		RETURNING id;
+		SELECT m.chat_session_id, m.timestamp
+		FROM messages m
+		INNER JOIN chat_sessions c ON m.chat_session_id = c.id
+		WHERE m.id = $1 AND c.account_id = $2 AND m.is_user = TRUE AND m.deleted_at IS NULL;
 		"#,
-		chat_session_id,
+		message_id,
 		user.id
 	)
 	.fetch_optional(&pool)
 	.await
 	.map_err(AppError::from)?
-	.ok_or(AppError::NotFound)?;
+	.ok_or(AppError::MessageNotFound)?;
 
-	Ok(())
+	let chat_session_id = message_info.chat_session_id;
+	let message_timestamp = message_info.timestamp;
+
+	// A retry of a request that was already processed gets the same response
+	if let Some(client_request_id) = client_request_id
+		&& let MessageRequest::Done(response) =
+			claim_message_request(&pool, chat_session_id, client_request_id).await?
+	{
+		return Ok(Json(serde_json::from_value(response)?));
+	}
+
+	let result: ApiResult<Message> = async {
+		let permit = agent_permit().await?;
+
+		// Delete future messages in this chat session only
+		sqlx::query!(
+			r#"
+			DELETE FROM messages
+			WHERE chat_session_id = $1
+			  AND timestamp > $2
+			  AND id != $3;
+			"#,
+			chat_session_id,
+			message_timestamp,
+			message_id
+		)
+		.execute(&pool)
+		.await
+		.map_err(AppError::from)?;
+
+		// Update the user message
+		sqlx::query!(
+			r#"
+			UPDATE messages
+			SET text = $1, timestamp = NOW()
+			WHERE id = $2;
+			"#,
+			new_text,
+			message_id
+		)
+		.execute(&pool)
+		.await
+		.map_err(AppError::from)?;
+
+		touch_chat_session(&pool, chat_session_id).await?;
+
+		// Call LLM and insert bot response
+		let bot_message = send_message_to_llm(
+			permit,
+			new_text.as_str(),
+			user.id,
+			chat_session_id,
+			itinerary_id,
+			&[],
+			None,
+			&pool,
+			&agent,
+			&context_store,
+		)
+		.await?
+		.message;
+		notify_new_message(&pool, chat_session_id, bot_message.id).await?;
+
+		Ok(bot_message)
+	}
+	.await;
+	finish_message_request(&pool, chat_session_id, client_request_id, &result).await?;
+
+	result.map(Json)
 }
 
-/// Rename a chat session
+/// Send a new message, and get a message back from the LLM
 ///
 /// # Method
-/// `POST /api/chat/rename`
+/// `POST /api/chat/sendMessage`
 ///
 /// # Request Body
-/// - [RenameRequest]
+/// - [SendMessageRequest]
+///   - A request repeating an earlier `client_request_id` in the chat gets the earlier response
 ///
 /// # Responses
-/// - `200 OK`
+/// - `200 OK` - with body: [SendMessageResponse] - contains message from LLM
 /// - `400 BAD_REQUEST` - Request payload contains invalid data (public error)
 /// - `401 UNAUTHORIZED` - When authentication fails (handled in middleware, public error)
+/// - `403 FORBIDDEN` - with code `EMAIL_NOT_VERIFIED` - The account's email isn't verified (public error)
 /// - `404 NOT_FOUND` - The provided chat session id does not belong to the user or does not exist (public error)
+/// - `409 CONFLICT` - A request with the same `client_request_id` is still being processed (public error)
 /// - `500 INTERNAL_SERVER_ERROR` - Internal error (private)
+/// - `503 SERVICE_UNAVAILABLE` - AI features are disabled, or too many replies are being generated (public error)
 ///
 /// # Examples
 /// ```bash
-/// curl -X POST http://localhost:3001/api/chat/rename
+/// curl -X POST http://localhost:3001/api/chat/sendMessage
 ///   -H "Content-Type: application/json"
 ///   -d '{
-///         "new_title": "Tokio, Japan (lmao)",
-///         "id": 16
+///         "chat_session_id": 6,
+///         "text": "New message",
+///         "itinerary_id": 7
 ///       }'
 /// ```
+///
+/// Notes:
+/// - Prompt injection phrases are removed, whitespace is collapsed and the text is cut to
+///   `MAX_MESSAGE_LENGTH` characters before it's stored, see [sanitize_user_input]
+/// - The reply is in the `Accept-Language` header's language if one is given, otherwise
+///   the account's `preferred_language`
 #[utoipa::path(
 	post,
-	path="/rename",
-	summary="Rename a chat session",
-	description="Renames a chat session that belongs to this user with the given ID to the given title.",
+	path="/sendMessage",
+	summary="Send a message and wait for a reply from the LLM",
+	description="Ask the LLM to generate an itinerary and it should respond with one. The reply is in the Accept-Language header's language, or the account's preferred language without one.",
+	params(
+		("Accept-Language"=Option<String>, Header, description="Language to reply in, overriding the account's preferred language", example="es")
+	),
 	request_body(
-		content=RenameRequest,
+		content=SendMessageRequest,
 		content_type="application/json",
-		description="Chat session ID must belong to the user who sent the request. New Title must not be empty string.",
+		description="Itinerary id is optional and is used to give context to the LLM. Client request id is optional, retrying with the same one returns the original response instead of sending the message again.",
 		example=json!({
-			"new_title": "Tokio, Japan (lmao)",
-			"id": 16
+			"chat_session_id": 12,
+			"text": "Make an itinerary",
+			"itinerary_id": 13,
+			"client_request_id": "3f8e2a7b-1c4d-4e6f-9a0b-5d7c8e9f1a2b"
 		})
 	),
 	responses(
-		(status=200, description="Chat renamed successfully"),
-		(status=400, description="Bad Request"),
-		(status=401, description="User has an invalid cookie/no cookie"),
-		(status=404, description="Chat session not found for this user"),
+		(
+			status=200,
+			description="Message sent, and LLM replied successfully",
+			body=SendMessageResponse,
+			content_type="application/json",
+			example=json!({
+				"user_message_id": 52,
+				"bot_message": {
+					"id": 53,
+					"is_user": false,
+					"timestamp": "2025-10-14 11-39-10",
+					"text": "Bot reply",
+					"itinerary_id": 14
+				},
+				"timed_out": false
+			})
+		),
+		(status=400, description="Bad Request", body=ErrorBody),
+		(status=401, description="User has an invalid cookie/no cookie", body=ErrorBody),
+		(status=403, description="Email not verified", body=ErrorBody),
+		(status=404, description="Chat session not found for this user", body=ErrorBody),
 		(status=405, description="Method Not Allowed - Must be POST"),
+		(status=409, description="A request with the same client request id is still being processed", body=ErrorBody),
 		(status=408, description="Request Timed Out"),
-		(status=500, description="Internal Server Error")
+		(status=500, description="Internal Server Error", body=ErrorBody),
+		(status=503, description="AI features are disabled, or the server is busy", body=ErrorBody)
 	),
 	security(("set-cookie"=[])),
 	tag="Chat"
 )]
-pub async fn api_rename(
+pub async fn api_send_message(
 	Extension(user): Extension<AuthUser>,
 	Extension(pool): Extension<PgPool>,
-	Json(RenameRequest { new_title, id }): Json<RenameRequest>,
-) -> ApiResult<()> {
-	// no empty titles
-	if new_title.is_empty() {
-		return Err(AppError::BadRequest(String::from(
-			"New title must not be empty",
-		)));
+	Extension(agent): Extension<Option<SharedAgentFactory>>,
+	Extension(context_store): Extension<crate::agent::models::context::SharedContextStore>,
+	AcceptLanguage(accept_language): AcceptLanguage,
+	Json(SendMessageRequest {
+		chat_session_id,
+		text,
+		itinerary_id,
+		client_request_id,
+	}): Json<SendMessageRequest>,
+) -> ApiResult<Json<SendMessageResponse>> {
+	let agent = require_agent(agent)?;
+	require_verified_email(&pool, user.id).await?;
+	let text = sanitize_user_input(&text);
+	if text.is_empty() {
+		return Err(AppError::EmptyText);
 	}
 
-	// verify chat session belongs to this user
+	// verify the given chat session belongs to this user
 	sqlx::query!(
-		r#"SELECT id from chat_sessions WHERE id=$1 AND account_id=$2"#,
-		id,
+		r#"
+		SELECT id FROM chat_sessions
+		WHERE id=$1 AND account_id=$2;
+		"#,
+		chat_session_id,
 		user.id
 	)
 	.fetch_optional(&pool)
 	.await
 	.map_err(AppError::from)?
-	.ok_or(AppError::NotFound)?;
+	.ok_or(AppError::ChatSessionNotFound)?;
 
-	//change name
-	sqlx::query!(
-		r#"UPDATE chat_sessions SET title=$1 WHERE id=$2"#,
-		new_title,
-		id
-	)
-	.execute(&pool)
-	.await
-	.map_err(AppError::from)?;
+	// A retry of a request that was already processed gets the same response
+	if let Some(client_request_id) = client_request_id
+		&& let MessageRequest::Done(response) =
+			claim_message_request(&pool, chat_session_id, client_request_id).await?
+	{
+		return Ok(Json(serde_json::from_value(response)?));
+	}
 
-	Ok(())
+	let result: ApiResult<SendMessageResponse> = async {
+		let permit = agent_permit().await?;
+
+		// insert user message into db
+		let user_message_id = sqlx::query!(
+			r#"
+			INSERT INTO messages (chat_session_id, itinerary_id, is_user, timestamp, text)
+			VALUES ($1, NULL, TRUE, NOW(), $2)
+			RETURNING id;
+			"#,
+			chat_session_id,
+			text
+		)
+		.fetch_one(&pool)
+		.await
+		.map_err(AppError::from)?
+		.id;
+
+		touch_chat_session(&pool, chat_session_id).await?;
+
+		// call llm and insert bot response into db
+		let LlmReply {
+			message: bot_message,
+			timed_out,
+		} = send_message_to_llm(
+			permit,
+			text.as_str(),
+			user.id,
+			chat_session_id,
+			itinerary_id,
+			&[],
+			accept_language.as_deref(),
+			&pool,
+			&agent,
+			&context_store,
+		)
+		.await?;
+		notify_new_message(&pool, chat_session_id, bot_message.id).await?;
+
+		Ok(SendMessageResponse {
+			user_message_id,
+			bot_message,
+			timed_out,
+		})
+	}
+	.await;
+	finish_message_request(&pool, chat_session_id, client_request_id, &result).await?;
+
+	result.map(Json)
 }
 
-/// Fetches the progress of the llm pipeline for this chat session
+/// Replace the bot's reply to the latest user message with a new one
 ///
 /// # Method
-/// `POST /api/chat/progress`
+/// `POST /api/chat/regenerate`
 ///
 /// # Request Body
-/// - [ProgressRequest]
+/// - [RegenerateRequest]
 ///
 /// # Responses
-/// - `200 OK` - [ProgressResponse] - status of the llm pipeline
-/// - `400 BAD_REQUEST` - Request payload contains invalid data (public error)
+/// - `200 OK` - with body: [Message] - the new message from the LLM
+/// - `400 BAD_REQUEST` - The chat has no user message to reply to (public error)
 /// - `401 UNAUTHORIZED` - When authentication fails (handled in middleware, public error)
+/// - `403 FORBIDDEN` - with code `EMAIL_NOT_VERIFIED` - The account's email isn't verified (public error)
 /// - `404 NOT_FOUND` - The provided chat session id does not belong to the user or does not exist (public error)
+/// - `409 CONFLICT` - A reply is still being generated in the chat (public error)
 /// - `500 INTERNAL_SERVER_ERROR` - Internal error (private)
+/// - `503 SERVICE_UNAVAILABLE` - AI features are disabled, or too many replies are being generated (public error)
 ///
 /// # Examples
 /// ```bash
-/// curl -X POST http://localhost:3001/api/chat/progress
+/// curl -X POST http://localhost:3001/api/chat/regenerate
 ///   -H "Content-Type: application/json"
 ///   -d '{
-///         "chat_session_id": 4
+///         "chat_session_id": 6
 ///       }'
 /// ```
+///
+/// Notes:
+/// - Unlike `/updateMessage`, the user's message and everything before it is kept.
+/// - Itineraries only the deleted replies refer to are deleted unless they were saved or shared.
 #[utoipa::path(
 	post,
-	path="/progress",
-	summary="Get status of LLM pipeline",
-	description="Fetches the progress of the llm pipeline for this chat session.",
+	path="/regenerate",
+	summary="Regenerate the LLM's last reply",
+	description="Deletes the bot's replies to the latest user message in the chat, along with their unsaved itineraries, and asks the LLM to reply to it again.",
 	request_body(
-		content=ProgressRequest,
+		content=RegenerateRequest,
 		content_type="application/json",
-		description="Chat session ID must belong to the user who sent the request. New Title must not be empty string.",
+		description="Chat session ID must belong to the user who sent the request.",
 		example=json!({
-			"chat_session_id": 4
+			"chat_session_id": 6
 		})
 	),
 	responses(
 		(
 			status=200,
-			description="The status of the LLM pipeline for the quested chat session",
-			body=ProgressResponse,
+			description="The new reply from the LLM",
+			body=Message,
 			content_type="application/json",
 			example=json!({
-				"progress": "Ready",
-				"title": "Possibly Updated Chat Title"
+				"id": 55,
+				"is_user": false,
+				"timestamp": "2025-10-14 11-41-02",
+				"text": "Bot reply",
+				"itinerary_id": 15
 			})
 		),
-		(status=400, description="Bad Request"),
-		(status=401, description="User has an invalid cookie/no cookie"),
-		(status=404, description="Chat session not found for this user"),
+		(status=400, description="Bad Request", body=ErrorBody),
+		(status=401, description="User has an invalid cookie/no cookie", body=ErrorBody),
+		(status=403, description="Email not verified", body=ErrorBody),
+		(status=404, description="Chat session not found for this user", body=ErrorBody),
 		(status=405, description="Method Not Allowed - Must be POST"),
 		(status=408, description="Request Timed Out"),
-		(status=500, description="Internal Server Error")
+		(status=409, description="A reply is still being generated in this chat", body=ErrorBody),
+		(status=500, description="Internal Server Error", body=ErrorBody),
+		(status=503, description="AI features are disabled, or the server is busy", body=ErrorBody)
 	),
 	security(("set-cookie"=[])),
 	tag="Chat"
 )]
-pub async fn api_progress(
+pub async fn api_regenerate(
 	Extension(user): Extension<AuthUser>,
 	Extension(pool): Extension<PgPool>,
-	Json(ProgressRequest { chat_session_id }): Json<ProgressRequest>,
-) -> ApiResult<Json<ProgressResponse>> {
-	let row = sqlx::query!(
-		r#"SELECT llm_progress as "llm_progress: LlmProgress", title
+	Extension(agent): Extension<Option<SharedAgentFactory>>,
+	Extension(context_store): Extension<crate::agent::models::context::SharedContextStore>,
+	Json(RegenerateRequest { chat_session_id }): Json<RegenerateRequest>,
+) -> ApiResult<Json<Message>> {
+	let agent = require_agent(agent)?;
+	require_verified_email(&pool, user.id).await?;
+	let permit = agent_permit().await?;
+
+	let mut tx = pool.begin().await.map_err(AppError::from)?;
+	let progress = sqlx::query_scalar!(
+		r#"
+		SELECT llm_progress AS "llm_progress: LlmProgress"
 		FROM chat_sessions
-		WHERE account_id=$1 AND id=$2;"#,
-		user.id,
+		WHERE id = $1 AND account_id = $2;
+		"#,
 		chat_session_id,
+		user.id
 	)
-	.fetch_optional(&pool)
+	.fetch_optional(&mut *tx)
 	.await
 	.map_err(AppError::from)?
-	.ok_or(AppError::NotFound)?;
-	Ok(Json(ProgressResponse {
-		progress: row.llm_progress,
-		title: row.title,
-	}))
+	.ok_or(AppError::ChatSessionNotFound)?;
+	if !matches!(
+		progress,
+		LlmProgress::Ready | LlmProgress::AwaitingUser | LlmProgress::Failed
+	) {
+		return Err(AppError::PipelineBusy(String::from(
+			"A reply is still being generated in this chat",
+		)));
+	}
+
+	let user_message = sqlx::query!(
+		r#"
+		SELECT timestamp, text
+		FROM messages
+		WHERE chat_session_id = $1 AND is_user = TRUE AND deleted_at IS NULL
+		ORDER BY timestamp DESC
+		LIMIT 1;
+		"#,
+		chat_session_id
+	)
+	.fetch_optional(&mut *tx)
+	.await
+	.map_err(AppError::from)?
+	.ok_or_else(|| AppError::BadRequest(String::from("There is no message to reply to")))?;
+
+	// Replies deleted along with their user message are left for it to be restored with
+	let itinerary_ids: Vec<i32> = sqlx::query_scalar!(
+		r#"
+		DELETE FROM messages
+		WHERE
+			chat_session_id = $1 AND
+			is_user = FALSE AND
+			timestamp > $2 AND
+			deleted_at IS NULL
+		RETURNING itinerary_id;
+		"#,
+		chat_session_id,
+		user_message.timestamp
+	)
+	.fetch_all(&mut *tx)
+	.await
+	.map_err(AppError::from)?
+	.into_iter()
+	.flatten()
+	.collect();
+
+	sqlx::query!(
+		r#"
+		DELETE FROM itineraries i
+		WHERE
+			i.id = ANY($1) AND
+			i.account_id = $2 AND
+			i.saved = FALSE AND
+			i.is_public = FALSE AND
+			NOT EXISTS (SELECT 1 FROM messages m WHERE m.itinerary_id = i.id);
+		"#,
+		&itinerary_ids,
+		user.id
+	)
+	.execute(&mut *tx)
+	.await
+	.map_err(AppError::from)?;
+	tx.commit().await.map_err(AppError::from)?;
+
+	// The latest itinerary left in the chat is the context the message was first sent with
+	let bot_message = send_message_to_llm(
+		permit,
+		user_message.text.as_str(),
+		user.id,
+		chat_session_id,
+		None,
+		&[],
+		None,
+		&pool,
+		&agent,
+		&context_store,
+	)
+	.await?
+	.message;
+	notify_new_message(&pool, chat_session_id, bot_message.id).await?;
+
+	Ok(Json(bot_message))
+}
+
+/// Get an empty chat session id belonging to this user, or create one if one doesn't exist
+///
+/// # Method
+/// `GET /api/chat/newChat`
+///
+/// # Responses
+/// - `200 OK` - with body: [NewChatResponse]
+/// - `400 BAD_REQUEST` - Request payload contains invalid data (public error)
+/// - `401 UNAUTHORIZED` - When authentication fails (handled in middleware, public error)
+/// - `500 INTERNAL_SERVER_ERROR` - Internal error (private)
+///
+/// # Examples
+/// ```bash
+/// curl -X POST http://localhost:3001/api/chat/sendMessage
+///   -H "Content-Type: application/json"
+///   -d '{
+///         "chat_session_id": 6,
+///         "text": "New message",
+///         "itinerary_id": 7
+///       }'
+/// ```
+#[utoipa::path(
+	get,
+	path="/newChat",
+	summary="Get the chat session id for an empty chat",
+	description="Creates a new empty chat session for this user if one doesn't already exist, and returns its chat session id.",
+	responses(
+		(
+			status=200,
+			description="New chat session retrieved successfully",
+			body=NewChatResponse,
+			content_type="application/json",
+			example=json!({
+				"chat_session_id": 13
+			})
+		),
+		(status=400, description="Bad Request", body=ErrorBody),
+		(status=401, description="User has an invalid cookie/no cookie", body=ErrorBody),
+		(status=405, description="Method Not Allowed - Must be GET"),
+		(status=408, description="Request Timed Out"),
+		(status=500, description="Internal Server Error", body=ErrorBody)
+	),
+	security(("set-cookie"=[])),
+	tag="Chat"
+)]
+pub async fn api_new_chat(
+	Extension(user): Extension<AuthUser>,
+	Extension(pool): Extension<PgPool>,
+) -> ApiResult<Json<NewChatResponse>> {
+	// check to see if there's already an empty chat session before making a new one
+	let chat_sessions = sqlx::query!(
+		r#"
+		SELECT c.id
+		FROM chat_sessions c
+		WHERE
+			c.account_id=$1
+			AND NOT c.archived
+			AND NOT EXISTS (
+				SELECT 1
+				FROM messages m
+				WHERE m.chat_session_id=c.id
+			);
+		"#,
+		user.id
+	)
+	.fetch_all(&pool)
+	.await
+	.map_err(AppError::from)?;
+
+	let chat_session_id = match chat_sessions.first() {
+		Some(record) => record.id,
+		None => {
+			// make a new chat session
+			sqlx::query!(
+				r#"
+				INSERT INTO chat_sessions (account_id, title)
+				VALUES ($1, 'New Chat')
+				RETURNING id
+				"#,
+				user.id
+			)
+			.fetch_one(&pool)
+			.await
+			.map_err(AppError::from)?
+			.id
+		}
+	};
+
+	Ok(Json(NewChatResponse { chat_session_id }))
+}
+
+/// Start a new chat session from a message of an existing one
+///
+/// # Method
+/// `POST /api/chat/branch`
+///
+/// # Request Body
+/// - [BranchRequest]
+///
+/// # Responses
+/// - `200 OK` - with body: [NewChatResponse] - the branched chat session
+/// - `400 BAD_REQUEST` - Request payload contains invalid data (public error)
+/// - `401 UNAUTHORIZED` - When authentication fails (handled in middleware, public error)
+/// - `404 NOT_FOUND` - The provided message id does not belong to the user or does not exist (public error)
+/// - `500 INTERNAL_SERVER_ERROR` - Internal error (private)
+///
+/// # Examples
+/// ```bash
+/// curl -X POST http://localhost:3001/api/chat/branch
+///   -H "Content-Type: application/json"
+///   -d '{
+///         "from_message_id": 42
+///       }'
+/// ```
+///
+/// Notes:
+/// - Messages up to and including the given one are copied, deleted messages aren't.
+/// - Unsaved itineraries the copied messages reference are copied too, saved ones are shared.
+/// - The trip details and agent context are copied as they are now in the original chat.
+#[utoipa::path(
+	post,
+	path="/branch",
+	summary="Branch a chat session",
+	description="Creates a new chat session with copies of every message up to and including the given one, so a different direction can be explored without losing the original chat.",
+	request_body(
+		content=BranchRequest,
+		content_type="application/json",
+		description="The message must be in a chat session that belongs to the user.",
+		example=json!({
+			"from_message_id": 42
+		})
+	),
+	responses(
+		(
+			status=200,
+			description="Chat session branched successfully",
+			body=NewChatResponse,
+			content_type="application/json",
+			example=json!({
+				"chat_session_id": 14
+			})
+		),
+		(status=400, description="Bad Request", body=ErrorBody),
+		(status=401, description="User has an invalid cookie/no cookie", body=ErrorBody),
+		(status=404, description="Message not found for this user", body=ErrorBody),
+		(status=405, description="Method Not Allowed - Must be POST"),
+		(status=408, description="Request Timed Out"),
+		(status=500, description="Internal Server Error", body=ErrorBody)
+	),
+	security(("set-cookie"=[])),
+	tag="Chat"
+)]
+pub async fn api_branch(
+	Extension(user): Extension<AuthUser>,
+	Extension(pool): Extension<PgPool>,
+	Extension(context_store): Extension<crate::agent::models::context::SharedContextStore>,
+	Json(BranchRequest { from_message_id }): Json<BranchRequest>,
+) -> ApiResult<Json<NewChatResponse>> {
+	let mut tx = pool.begin().await.map_err(AppError::from)?;
+
+	// verify message belongs to this user
+	let source = sqlx::query!(
+		r#"
+		SELECT m.chat_session_id, m.timestamp
+		FROM messages m
+		JOIN chat_sessions c ON c.id = m.chat_session_id
+		WHERE m.id = $1 AND c.account_id = $2 AND m.deleted_at IS NULL;
+		"#,
+		from_message_id,
+		user.id
+	)
+	.fetch_optional(&mut *tx)
+	.await
+	.map_err(AppError::from)?
+	.ok_or(AppError::MessageNotFound)?;
+
+	let chat_session_id = sqlx::query_scalar!(
+		r#"
+		INSERT INTO chat_sessions (account_id, title, context, current_event_ids)
+		SELECT account_id, LEFT('Branch of ' || title, 255), context, current_event_ids
+		FROM chat_sessions
+		WHERE id = $1
+		RETURNING id;
+		"#,
+		source.chat_session_id
+	)
+	.fetch_one(&mut *tx)
+	.await
+	.map_err(AppError::from)?;
+
+	// Saved itineraries belong to the account, only the chat's drafts are copied
+	let itinerary_ids = sqlx::query_scalar!(
+		r#"
+		SELECT DISTINCT i.id
+		FROM itineraries i
+		JOIN messages m ON m.itinerary_id = i.id
+		WHERE
+			m.chat_session_id = $1 AND
+			m.timestamp <= $2 AND
+			m.deleted_at IS NULL AND
+			i.saved = FALSE AND
+			i.deleted_at IS NULL;
+		"#,
+		source.chat_session_id,
+		source.timestamp
+	)
+	.fetch_all(&mut *tx)
+	.await
+	.map_err(AppError::from)?;
+
+	let mut copied_ids = Vec::with_capacity(itinerary_ids.len());
+	for itinerary_id in &itinerary_ids {
+		let copy_id = sqlx::query_scalar!(
+			r#"
+			INSERT INTO itineraries (account_id, is_public, start_date, end_date, chat_session_id, saved, title, unassigned_event_ids)
+			SELECT account_id, FALSE, start_date, end_date, $2, FALSE, title, unassigned_event_ids
+			FROM itineraries
+			WHERE id = $1
+			RETURNING id;
+			"#,
+			itinerary_id,
+			chat_session_id
+		)
+		.fetch_one(&mut *tx)
+		.await
+		.map_err(AppError::from)?;
+
+		sqlx::query!(
+			r#"
+			INSERT INTO event_list (itinerary_id, event_id, time_of_day, date, block_index)
+			SELECT $1, event_id, time_of_day, date, block_index
+			FROM event_list
+			WHERE itinerary_id = $2;
+			"#,
+			copy_id,
+			itinerary_id
+		)
+		.execute(&mut *tx)
+		.await
+		.map_err(AppError::from)?;
+		copied_ids.push(copy_id);
+	}
+
+	sqlx::query!(
+		r#"
+		INSERT INTO messages (chat_session_id, itinerary_id, is_user, timestamp, text)
+		SELECT $1, COALESCE(copies.new_id, m.itinerary_id), m.is_user, m.timestamp, m.text
+		FROM messages m
+		LEFT JOIN UNNEST($4::int4[], $5::int4[]) AS copies(old_id, new_id)
+			ON copies.old_id = m.itinerary_id
+		WHERE
+			m.chat_session_id = $2 AND
+			m.timestamp <= $3 AND
+			m.deleted_at IS NULL
+		ORDER BY m.timestamp, m.id;
+		"#,
+		chat_session_id,
+		source.chat_session_id,
+		source.timestamp,
+		&itinerary_ids,
+		&copied_ids
+	)
+	.execute(&mut *tx)
+	.await
+	.map_err(AppError::from)?;
+
+	sqlx::query!(
+		r#"
+		INSERT INTO trip_contexts (chat_session_id, context, updated_at)
+		SELECT $1, context, NOW()
+		FROM trip_contexts
+		WHERE chat_session_id = $2;
+		"#,
+		chat_session_id,
+		source.chat_session_id
+	)
+	.execute(&mut *tx)
+	.await
+	.map_err(AppError::from)?;
+
+	tx.commit().await.map_err(AppError::from)?;
+
+	// The branch gets its own copy of the live context, with nothing in flight
+	let mut store_guard = context_store.write().await;
+	if let Some(ctx) = store_guard.get(&source.chat_session_id) {
+		let mut ctx = ctx.clone();
+		ctx.chat_session_id = chat_session_id;
+		ctx.tool_call_count = 0;
+		ctx.cancellation = CancellationToken::new();
+		ctx.last_accessed = Instant::now();
+		store_guard.insert(chat_session_id, ctx);
+	}
+
+	Ok(Json(NewChatResponse { chat_session_id }))
+}
+
+/// Delete the chat session with the given ID
+///
+/// # Method
+/// `DELETE /api/chat/:id`
+///
+/// # Responses
+/// - `200 OK` - chat session and associated messages and unsaved itineraries successfully deleted
+/// - `400 BAD_REQUEST` - Request payload contains invalid data (public error)
+/// - `401 UNAUTHORIZED` - When authentication fails (handled in middleware, public error)
+/// - `404 NOT_FOUND` - The provided chat session id does not belong to the user or does not exist (public error)
+/// - `500 INTERNAL_SERVER_ERROR` - Internal error (private)
+///
+/// # Examples
+/// ```bash
+/// curl -X DELETE http://localhost:3001/api/chat/7
+///   -H "Content-Type: application/json"
+/// ```
+///
+/// Notes:
+/// - Deleted itineraries can be restored with `/api/itinerary/{id}/restore` for `ITINERARY_RETENTION_DAYS`.
+#[utoipa::path(
+	delete,
+	path="/{id}",
+	summary="Delete the given chat session",
+	description="Deletes a chat session and its associated messages and unsaved, private itineraries if it belongs to the user making the request.",
+	responses(
+		(status=200, description="Chat session and associated messages and unsaved, private itineraries deleted successfully"),
+		(status=400, description="Bad Request", body=ErrorBody),
+		(status=401, description="User has an invalid cookie/no cookie", body=ErrorBody),
+		(status=404, description="Chat session not found for this user", body=ErrorBody),
+		(status=405, description="Method Not Allowed - Must be DELETE"),
+		(status=408, description="Request Timed Out"),
+		(status=500, description="Internal Server Error", body=ErrorBody)
+	),
+	security(("set-cookie"=[])),
+	tag="Chat"
+)]
+pub async fn api_delete_chat(
+	Extension(user): Extension<AuthUser>,
+	Extension(pool): Extension<PgPool>,
+	Extension(context_store): Extension<crate::agent::models::context::SharedContextStore>,
+	Extension(agent): Extension<Option<SharedAgentFactory>>,
+	Path(chat_session_id): Path<i32>,
+) -> ApiResult<()> {
+	// the itineraries are only marked deleted if the chat is too
+	let mut tx = pool.begin().await.map_err(AppError::from)?;
+
+	// itineraries do not cascade, so we delete them manually
+	// they're only marked deleted so they can be restored, see `api_restore_deleted`
+	sqlx::query!(
+		r#"
+		UPDATE itineraries
+		SET deleted_at = NOW()
+		WHERE
+			chat_session_id=$1 AND
+			account_id=$2 AND
+			is_public=FALSE AND
+			saved=FALSE AND
+			deleted_at IS NULL;
+		"#,
+		chat_session_id,
+		user.id
+	)
+	.execute(&mut *tx)
+	.await
+	.map_err(AppError::from)?;
+
+	// messages will cascade
+	sqlx::query!(
+		r#"
+		DELETE FROM chat_sessions
+		WHERE id=$1 AND account_id=$2
+		RETURNING id;
+		"#,
+		chat_session_id,
+		user.id
+	)
+	.fetch_optional(&mut *tx)
+	.await
+	.map_err(AppError::from)?
+	.ok_or(AppError::ChatSessionNotFound)?;
+
+	tx.commit().await.map_err(AppError::from)?;
+
+	// the agent context and conversation memory are only kept in memory
+	context_store.write().await.remove(&chat_session_id);
+	if let Some(agent) = agent {
+		agent.forget(chat_session_id);
+	}
+
+	Ok(())
+}
+
+/// Delete one of the user's messages along with the bot's replies to it
+///
+/// # Method
+/// `DELETE /api/chat/message/:id`
+///
+/// # Responses
+/// - `200 OK` - with body: [MessageIdsResponse]
+/// - `401 UNAUTHORIZED` - When authentication fails (handled in middleware, public error)
+/// - `404 NOT_FOUND` - Message not found, already deleted, not sent by the user, or in another user's chat (public error)
+/// - `500 INTERNAL_SERVER_ERROR` - Internal error (private)
+///
+/// # Examples
+/// ```bash
+/// curl -X DELETE http://localhost:3001/api/chat/message/42
+///   -H "Cookie: auth-token=..."
+/// ```
+///
+/// Notes:
+/// - The replies are the bot messages after it, up to the user's next message.
+/// - Deleted messages are left out of `/messagePage` and the agent's chat history, and can be
+///   restored with `/message/{id}/restore` for `MESSAGE_RETENTION_DAYS`.
+#[utoipa::path(
+	delete,
+	path="/message/{id}",
+	summary="Delete a message",
+	description="Deletes one of the user's messages and the bot's replies to it. They can be restored for a while before they're permanently deleted.",
+	responses(
+		(
+			status=200,
+			description="Ids of the deleted messages",
+			body=MessageIdsResponse,
+			content_type="application/json",
+			example=json!({
+				"message_ids": [42, 43]
+			})
+		),
+		(status=400, description="Bad Request", body=ErrorBody),
+		(status=401, description="User has an invalid cookie/no cookie", body=ErrorBody),
+		(status=404, description="User message not found for this user", body=ErrorBody),
+		(status=405, description="Method Not Allowed - Must be DELETE"),
+		(status=408, description="Request Timed Out"),
+		(status=500, description="Internal Server Error", body=ErrorBody)
+	),
+	security(("set-cookie"=[])),
+	tag="Chat"
+)]
+pub async fn api_delete_message(
+	Extension(user): Extension<AuthUser>,
+	Extension(pool): Extension<PgPool>,
+	Path(message_id): Path<i32>,
+) -> ApiResult<Json<MessageIdsResponse>> {
+	// Every message deleted together gets the same deleted_at, so a restore can find them again
+	let mut message_ids: Vec<i32> = sqlx::query_scalar!(
+		r#"
+		WITH target AS (
+			SELECT m.chat_session_id, m.timestamp
+			FROM messages m
+			INNER JOIN chat_sessions c ON m.chat_session_id = c.id
+			WHERE m.id = $1 AND c.account_id = $2 AND m.is_user = TRUE AND m.deleted_at IS NULL
+		)
+		UPDATE messages m
+		SET deleted_at = NOW()
+		FROM target t
+		WHERE
+			m.chat_session_id = t.chat_session_id AND
+			m.deleted_at IS NULL AND
+			(
+				m.id = $1 OR
+				(
+					NOT m.is_user AND
+					m.timestamp > t.timestamp AND
+					NOT EXISTS (
+						SELECT 1 FROM messages u
+						WHERE
+							u.chat_session_id = t.chat_session_id AND
+							u.is_user AND
+							u.timestamp > t.timestamp AND
+							u.timestamp < m.timestamp
+					)
+				)
+			)
+		RETURNING m.id;
+		"#,
+		message_id,
+		user.id
+	)
+	.fetch_all(&pool)
+	.await
+	.map_err(AppError::from)?;
+	if message_ids.is_empty() {
+		return Err(AppError::MessageNotFound);
+	}
+	message_ids.sort_unstable();
+
+	Ok(Json(MessageIdsResponse { message_ids }))
+}
+
+/// Restore a deleted message along with the bot replies deleted with it
+///
+/// # Method
+/// `POST /api/chat/message/:id/restore`
+///
+/// # Responses
+/// - `200 OK` - with body: [MessageIdsResponse]
+/// - `401 UNAUTHORIZED` - When authentication fails (handled in middleware, public error)
+/// - `404 NOT_FOUND` - Message not deleted, deleted over `MESSAGE_RETENTION_DAYS` ago, or in another user's chat (public error)
+/// - `500 INTERNAL_SERVER_ERROR` - Internal error (private)
+///
+/// # Examples
+/// ```bash
+/// curl -X POST http://localhost:3001/api/chat/message/42/restore
+///   -H "Cookie: auth-token=..."
+/// ```
+#[utoipa::path(
+	post,
+	path="/message/{id}/restore",
+	summary="Restore a deleted message",
+	description="Undoes deleting one of the user's messages, bringing back the bot replies deleted with it, as long as it hasn't been permanently deleted yet.",
+	responses(
+		(
+			status=200,
+			description="Ids of the restored messages",
+			body=MessageIdsResponse,
+			content_type="application/json",
+			example=json!({
+				"message_ids": [42, 43]
+			})
+		),
+		(status=400, description="Bad Request", body=ErrorBody),
+		(status=401, description="User has an invalid cookie/no cookie", body=ErrorBody),
+		(status=404, description="Deleted message not found for this user", body=ErrorBody),
+		(status=405, description="Method Not Allowed - Must be POST"),
+		(status=408, description="Request Timed Out"),
+		(status=500, description="Internal Server Error", body=ErrorBody)
+	),
+	security(("set-cookie"=[])),
+	tag="Chat"
+)]
+pub async fn api_restore_message(
+	Extension(user): Extension<AuthUser>,
+	Extension(pool): Extension<PgPool>,
+	Path(message_id): Path<i32>,
+) -> ApiResult<Json<MessageIdsResponse>> {
+	let mut message_ids: Vec<i32> = sqlx::query_scalar!(
+		r#"
+		WITH target AS (
+			SELECT m.chat_session_id, m.timestamp, m.deleted_at
+			FROM messages m
+			INNER JOIN chat_sessions c ON m.chat_session_id = c.id
+			WHERE
+				m.id = $1 AND
+				c.account_id = $2 AND
+				m.is_user = TRUE AND
+				m.deleted_at > NOW() - make_interval(days => $3)
+		)
+		UPDATE messages m
+		SET deleted_at = NULL
+		FROM target t
+		WHERE
+			m.chat_session_id = t.chat_session_id AND
+			m.deleted_at = t.deleted_at AND
+			(
+				m.id = $1 OR
+				(
+					NOT m.is_user AND
+					m.timestamp > t.timestamp AND
+					NOT EXISTS (
+						SELECT 1 FROM messages u
+						WHERE
+							u.chat_session_id = t.chat_session_id AND
+							u.is_user AND
+							u.timestamp > t.timestamp AND
+							u.timestamp < m.timestamp
+					)
+				)
+			)
+		RETURNING m.id;
+		"#,
+		message_id,
+		user.id,
+		MESSAGE_RETENTION_DAYS
+	)
+	.fetch_all(&pool)
+	.await
+	.map_err(AppError::from)?;
+	if message_ids.is_empty() {
+		return Err(AppError::MessageNotFound);
+	}
+	message_ids.sort_unstable();
+
+	Ok(Json(MessageIdsResponse { message_ids }))
+}
+
+/// Permanently deletes messages deleted over `MESSAGE_RETENTION_DAYS` ago.
+/// Returns how many were deleted.
+pub async fn purge_deleted_messages(pool: &PgPool) -> Result<u64, sqlx::Error> {
+	Ok(sqlx::query!(
+		r#"
+		DELETE FROM messages
+		WHERE deleted_at < NOW() - make_interval(days => $1);
+		"#,
+		MESSAGE_RETENTION_DAYS
+	)
+	.execute(pool)
+	.await?
+	.rows_affected())
+}
+
+/// Rename a chat session
+///
+/// # Method
+/// `POST /api/chat/rename`
+///
+/// # Request Body
+/// - [RenameRequest]
+///
+/// # Responses
+/// - `200 OK`
+/// - `400 BAD_REQUEST` - Request payload contains invalid data (public error)
+/// - `401 UNAUTHORIZED` - When authentication fails (handled in middleware, public error)
+/// - `404 NOT_FOUND` - The provided chat session id does not belong to the user or does not exist (public error)
+/// - `500 INTERNAL_SERVER_ERROR` - Internal error (private)
+///
+/// # Examples
+/// ```bash
+/// curl -X POST http://localhost:3001/api/chat/rename
+///   -H "Content-Type: application/json"
+///   -d '{
+///         "new_title": "Tokio, Japan (lmao)",
+///         "id": 16
+///       }'
+/// ```
+#[utoipa::path(
+	post,
+	path="/rename",
+	summary="Rename a chat session",
+	description="Renames a chat session that belongs to this user with the given ID to the given title.",
+	request_body(
+		content=RenameRequest,
+		content_type="application/json",
+		description="Chat session ID must belong to the user who sent the request. New Title must not be empty string.",
+		example=json!({
+			"new_title": "Tokio, Japan (lmao)",
+			"id": 16
+		})
+	),
+	responses(
+		(status=200, description="Chat renamed successfully"),
+		(status=400, description="Bad Request", body=ErrorBody),
+		(status=401, description="User has an invalid cookie/no cookie", body=ErrorBody),
+		(status=404, description="Chat session not found for this user", body=ErrorBody),
+		(status=405, description="Method Not Allowed - Must be POST"),
+		(status=408, description="Request Timed Out"),
+		(status=500, description="Internal Server Error", body=ErrorBody)
+	),
+	security(("set-cookie"=[])),
+	tag="Chat"
+)]
+pub async fn api_rename(
+	Extension(user): Extension<AuthUser>,
+	Extension(pool): Extension<PgPool>,
+	Json(RenameRequest { new_title, id }): Json<RenameRequest>,
+) -> ApiResult<()> {
+	// no empty titles
+	if new_title.is_empty() {
+		return Err(AppError::BadRequest(String::from(
+			"New title must not be empty",
+		)));
+	}
+
+	// verify chat session belongs to this user
+	sqlx::query!(
+		r#"SELECT id from chat_sessions WHERE id=$1 AND account_id=$2"#,
+		id,
+		user.id
+	)
+	.fetch_optional(&pool)
+	.await
+	.map_err(AppError::from)?
+	.ok_or(AppError::ChatSessionNotFound)?;
+
+	//change name
+	sqlx::query!(
+		r#"UPDATE chat_sessions SET title=$1 WHERE id=$2"#,
+		new_title,
+		id
+	)
+	.execute(&pool)
+	.await
+	.map_err(AppError::from)?;
+
+	Ok(())
+}
+
+/// Pin or unpin a chat session
+///
+/// # Method
+/// `POST /api/chat/pin`
+///
+/// # Request Body
+/// - [PinRequest]
+///
+/// # Responses
+/// - `200 OK`
+/// - `400 BAD_REQUEST` - The user already has `MAX_PINNED_CHATS` pinned chats (public error)
+/// - `401 UNAUTHORIZED` - When authentication fails (handled in middleware, public error)
+/// - `404 NOT_FOUND` - The provided chat session id does not belong to the user or does not exist (public error)
+/// - `500 INTERNAL_SERVER_ERROR` - Internal error (private)
+///
+/// # Examples
+/// ```bash
+/// curl -X POST http://localhost:3001/api/chat/pin
+///   -H "Content-Type: application/json"
+///   -d '{
+///         "id": 16,
+///         "pinned": true
+///       }'
+/// ```
+///
+/// Notes:
+/// - Pinned chats are listed first by `/api/chat/chats`.
+#[utoipa::path(
+	post,
+	path="/pin",
+	summary="Pin or unpin a chat session",
+	description="Pins a chat session that belongs to this user to the top of the chat list, or unpins it.",
+	request_body(
+		content=PinRequest,
+		content_type="application/json",
+		description="Chat session ID must belong to the user who sent the request.",
+		example=json!({
+			"id": 16,
+			"pinned": true
+		})
+	),
+	responses(
+		(status=200, description="Chat pinned or unpinned successfully"),
+		(status=400, description="Too many pinned chats", body=ErrorBody),
+		(status=401, description="User has an invalid cookie/no cookie", body=ErrorBody),
+		(status=404, description="Chat session not found for this user", body=ErrorBody),
+		(status=405, description="Method Not Allowed - Must be POST"),
+		(status=408, description="Request Timed Out"),
+		(status=500, description="Internal Server Error", body=ErrorBody)
+	),
+	security(("set-cookie"=[])),
+	tag="Chat"
+)]
+pub async fn api_pin(
+	Extension(user): Extension<AuthUser>,
+	Extension(pool): Extension<PgPool>,
+	Json(PinRequest { id, pinned }): Json<PinRequest>,
+) -> ApiResult<()> {
+	let mut tx = pool.begin().await.map_err(AppError::from)?;
+
+	// Lock the user's chats so concurrent pins are counted one after the other
+	let chat_ids = sqlx::query_scalar!(
+		r#"SELECT id FROM chat_sessions WHERE account_id=$1 ORDER BY id FOR UPDATE"#,
+		user.id
+	)
+	.fetch_all(&mut *tx)
+	.await
+	.map_err(AppError::from)?;
+	// verify chat session belongs to this user
+	if !chat_ids.contains(&id) {
+		return Err(AppError::ChatSessionNotFound);
+	}
+
+	// Re-pinning a pinned chat is fine, pinning another one has to fit under the limit
+	sqlx::query!(
+		r#"
+		UPDATE chat_sessions c
+		SET pinned = $3
+		WHERE
+			c.id = $1 AND
+			(
+				NOT $3 OR
+				c.pinned OR
+				(SELECT COUNT(*) FROM chat_sessions WHERE account_id = $2 AND pinned) < $4
+			)
+		RETURNING id
+		"#,
+		id,
+		user.id,
+		pinned,
+		MAX_PINNED_CHATS
+	)
+	.fetch_optional(&mut *tx)
+	.await
+	.map_err(AppError::from)?
+	.ok_or_else(|| {
+		AppError::BadRequest(format!("At most {MAX_PINNED_CHATS} chats can be pinned"))
+	})?;
+
+	tx.commit().await.map_err(AppError::from)?;
+	Ok(())
+}
+
+/// Archive a chat session
+///
+/// # Method
+/// `POST /api/chat/archive`
+///
+/// # Request Body
+/// - [ArchiveRequest]
+///
+/// # Responses
+/// - `200 OK`
+/// - `401 UNAUTHORIZED` - When authentication fails (handled in middleware, public error)
+/// - `404 NOT_FOUND` - The provided chat session id does not belong to the user or does not exist (public error)
+/// - `500 INTERNAL_SERVER_ERROR` - Internal error (private)
+///
+/// # Examples
+/// ```bash
+/// curl -X POST http://localhost:3001/api/chat/archive
+///   -H "Content-Type: application/json"
+///   -d '{
+///         "chat_session_id": 16
+///       }'
+/// ```
+///
+/// Notes:
+/// - Archived chats keep their messages and itineraries, they're only left out of `/api/chat/chats`.
+/// - Sending a message to an archived chat unarchives it.
+#[utoipa::path(
+	post,
+	path="/archive",
+	summary="Archive a chat session",
+	description="Hides a chat session that belongs to this user from the chat list without deleting it.",
+	request_body(
+		content=ArchiveRequest,
+		content_type="application/json",
+		description="Chat session ID must belong to the user who sent the request.",
+		example=json!({
+			"chat_session_id": 16
+		})
+	),
+	responses(
+		(status=200, description="Chat archived successfully"),
+		(status=400, description="Bad Request", body=ErrorBody),
+		(status=401, description="User has an invalid cookie/no cookie", body=ErrorBody),
+		(status=404, description="Chat session not found for this user", body=ErrorBody),
+		(status=405, description="Method Not Allowed - Must be POST"),
+		(status=408, description="Request Timed Out"),
+		(status=500, description="Internal Server Error", body=ErrorBody)
+	),
+	security(("set-cookie"=[])),
+	tag="Chat"
+)]
+pub async fn api_archive(
+	Extension(user): Extension<AuthUser>,
+	Extension(pool): Extension<PgPool>,
+	Json(ArchiveRequest { chat_session_id }): Json<ArchiveRequest>,
+) -> ApiResult<()> {
+	set_archived(&pool, user.id, chat_session_id, true).await
+}
+
+/// Unarchive a chat session
+///
+/// # Method
+/// `POST /api/chat/unarchive`
+///
+/// # Request Body
+/// - [ArchiveRequest]
+///
+/// # Responses
+/// - `200 OK`
+/// - `401 UNAUTHORIZED` - When authentication fails (handled in middleware, public error)
+/// - `404 NOT_FOUND` - The provided chat session id does not belong to the user or does not exist (public error)
+/// - `500 INTERNAL_SERVER_ERROR` - Internal error (private)
+///
+/// # Examples
+/// ```bash
+/// curl -X POST http://localhost:3001/api/chat/unarchive
+///   -H "Content-Type: application/json"
+///   -d '{
+///         "chat_session_id": 16
+///       }'
+/// ```
+#[utoipa::path(
+	post,
+	path="/unarchive",
+	summary="Unarchive a chat session",
+	description="Puts an archived chat session that belongs to this user back in the chat list.",
+	request_body(
+		content=ArchiveRequest,
+		content_type="application/json",
+		description="Chat session ID must belong to the user who sent the request.",
+		example=json!({
+			"chat_session_id": 16
+		})
+	),
+	responses(
+		(status=200, description="Chat unarchived successfully"),
+		(status=400, description="Bad Request", body=ErrorBody),
+		(status=401, description="User has an invalid cookie/no cookie", body=ErrorBody),
+		(status=404, description="Chat session not found for this user", body=ErrorBody),
+		(status=405, description="Method Not Allowed - Must be POST"),
+		(status=408, description="Request Timed Out"),
+		(status=500, description="Internal Server Error", body=ErrorBody)
+	),
+	security(("set-cookie"=[])),
+	tag="Chat"
+)]
+pub async fn api_unarchive(
+	Extension(user): Extension<AuthUser>,
+	Extension(pool): Extension<PgPool>,
+	Json(ArchiveRequest { chat_session_id }): Json<ArchiveRequest>,
+) -> ApiResult<()> {
+	set_archived(&pool, user.id, chat_session_id, false).await
+}
+
+/// Archives or unarchives one of the account's chat sessions, `404` if it has none with that id
+async fn set_archived(
+	pool: &PgPool,
+	account_id: i32,
+	chat_session_id: i32,
+	archived: bool,
+) -> ApiResult<()> {
+	let result = sqlx::query!(
+		"UPDATE chat_sessions SET archived = $1 WHERE id = $2 AND account_id = $3",
+		archived,
+		chat_session_id,
+		account_id
+	)
+	.execute(pool)
+	.await
+	.map_err(AppError::from)?;
+
+	if result.rows_affected() == 0 {
+		return Err(AppError::ChatSessionNotFound);
+	}
+	Ok(())
+}
+
+/// Fetches the progress of the llm pipeline for this chat session
+///
+/// # Method
+/// `POST /api/chat/progress`
+///
+/// # Request Body
+/// - [ProgressRequest]
+///
+/// # Responses
+/// - `200 OK` - [ProgressResponse] - status of the llm pipeline, `AwaitingUser` or `Failed` once a reply stopped without a bot message
+/// - `400 BAD_REQUEST` - Request payload contains invalid data (public error)
+/// - `401 UNAUTHORIZED` - When authentication fails (handled in middleware, public error)
+/// - `404 NOT_FOUND` - The provided chat session id does not belong to the user or does not exist (public error)
+/// - `500 INTERNAL_SERVER_ERROR` - Internal error (private)
+///
+/// # Examples
+/// ```bash
+/// curl -X POST http://localhost:3001/api/chat/progress
+///   -H "Content-Type: application/json"
+///   -d '{
+///         "chat_session_id": 4
+///       }'
+/// ```
+#[utoipa::path(
+	post,
+	path="/progress",
+	summary="Get status of LLM pipeline",
+	description="Fetches the progress of the llm pipeline for this chat session. `AwaitingUser` means a clarification question was sent and `Failed` means the last reply errored without a bot message being saved, both are idle like `Ready`.",
+	request_body(
+		content=ProgressRequest,
+		content_type="application/json",
+		description="Chat session ID must belong to the user who sent the request. New Title must not be empty string.",
+		example=json!({
+			"chat_session_id": 4
+		})
+	),
+	responses(
+		(
+			status=200,
+			description="The status of the LLM pipeline for the quested chat session",
+			body=ProgressResponse,
+			content_type="application/json",
+			example=json!({
+				"progress": "AwaitingUser",
+				"title": "Possibly Updated Chat Title",
+				"clarification": {
+					"missing_info": ["budget"],
+					"known": {
+						"destination": "Kyoto",
+						"start_date": "2026-04-01",
+						"end_date": "2026-04-05",
+						"budget": null
+					}
+				}
+			})
+		),
+		(status=400, description="Bad Request", body=ErrorBody),
+		(status=401, description="User has an invalid cookie/no cookie", body=ErrorBody),
+		(status=404, description="Chat session not found for this user", body=ErrorBody),
+		(status=405, description="Method Not Allowed - Must be POST"),
+		(status=408, description="Request Timed Out"),
+		(status=500, description="Internal Server Error", body=ErrorBody)
+	),
+	security(("set-cookie"=[])),
+	tag="Chat"
+)]
+pub async fn api_progress(
+	Extension(user): Extension<AuthUser>,
+	Extension(pool): Extension<PgPool>,
+	Json(ProgressRequest { chat_session_id }): Json<ProgressRequest>,
+) -> ApiResult<Json<ProgressResponse>> {
+	let row = sqlx::query!(
+		r#"SELECT llm_progress as "llm_progress: LlmProgress", title, clarification
+		FROM chat_sessions
+		WHERE account_id=$1 AND id=$2;"#,
+		user.id,
+		chat_session_id,
+	)
+	.fetch_optional(&pool)
+	.await
+	.map_err(AppError::from)?
+	.ok_or(AppError::ChatSessionNotFound)?;
+	Ok(Json(ProgressResponse {
+		progress: row.llm_progress,
+		title: row.title,
+		clarification: row
+			.clarification
+			.and_then(|clarification| serde_json::from_value(clarification).ok()),
+	}))
+}
+
+/// Stop generating the reply to the latest message in this chat session
+///
+/// # Method
+/// `POST /api/chat/cancel`
+///
+/// # Request Body
+/// - [CancelRequest]
+///
+/// # Responses
+/// - `200 OK` - The reply was cancelled, or no reply was being generated
+/// - `400 BAD_REQUEST` - Request payload contains invalid data (public error)
+/// - `401 UNAUTHORIZED` - When authentication fails (handled in middleware, public error)
+/// - `404 NOT_FOUND` - The provided chat session id does not belong to the user or does not exist (public error)
+/// - `500 INTERNAL_SERVER_ERROR` - Internal error (private)
+///
+/// # Examples
+/// ```bash
+/// curl -X POST http://localhost:3001/api/chat/cancel
+///   -H "Content-Type: application/json"
+///   -d '{
+///         "chat_session_id": 4
+///       }'
+/// ```
+///
+/// Notes:
+/// - The pending `sendMessage` or `updateMessage` request returns with a message saying the reply was stopped.
+#[utoipa::path(
+	post,
+	path="/cancel",
+	summary="Cancel the LLM reply",
+	description="Stops the LLM pipeline generating a reply in this chat session. The pending sendMessage request returns with a message saying the reply was stopped.",
+	request_body(
+		content=CancelRequest,
+		content_type="application/json",
+		description="Chat session ID must belong to the user who sent the request.",
+		example=json!({
+			"chat_session_id": 4
+		})
+	),
+	responses(
+		(status=200, description="Reply cancelled, or no reply was being generated"),
+		(status=400, description="Bad Request", body=ErrorBody),
+		(status=401, description="User has an invalid cookie/no cookie", body=ErrorBody),
+		(status=404, description="Chat session not found for this user", body=ErrorBody),
+		(status=405, description="Method Not Allowed - Must be POST"),
+		(status=408, description="Request Timed Out"),
+		(status=500, description="Internal Server Error", body=ErrorBody)
+	),
+	security(("set-cookie"=[])),
+	tag="Chat"
+)]
+pub async fn api_cancel(
+	Extension(user): Extension<AuthUser>,
+	Extension(pool): Extension<PgPool>,
+	Extension(context_store): Extension<crate::agent::models::context::SharedContextStore>,
+	Json(CancelRequest { chat_session_id }): Json<CancelRequest>,
+) -> ApiResult<()> {
+	sqlx::query!(
+		"SELECT id FROM chat_sessions WHERE id = $1 AND account_id = $2",
+		chat_session_id,
+		user.id
+	)
+	.fetch_optional(&pool)
+	.await
+	.map_err(AppError::from)?
+	.ok_or(AppError::ChatSessionNotFound)?;
+
+	if let Some(ctx) = context_store.read().await.get(&chat_session_id) {
+		ctx.cancellation.cancel();
+	}
+	Ok(())
+}
+
+/// Rate a bot message with a thumbs up or down
+///
+/// # Method
+/// `POST /api/chat/feedback`
+///
+/// # Request Body
+/// - [FeedbackRequest]
+///
+/// # Responses
+/// - `200 OK` - with body: [Feedback]
+/// - `400 BAD_REQUEST` - The comment is over `FEEDBACK_COMMENT_MAX_LEN` characters (public error)
+/// - `401 UNAUTHORIZED` - When authentication fails (handled in middleware, public error)
+/// - `404 NOT_FOUND` - Bot message not found in the user's chats (public error)
+/// - `500 INTERNAL_SERVER_ERROR` - Internal error (private)
+///
+/// # Examples
+/// ```bash
+/// curl -X POST http://localhost:3001/api/chat/feedback
+///   -H "Content-Type: application/json"
+///   -d '{
+///         "message_id": 53,
+///         "rating": "down",
+///         "comment": "Too many museums"
+///       }'
+/// ```
+///
+/// Notes:
+/// - Each message has one rating, submitting again replaces it.
+#[utoipa::path(
+	post,
+	path="/feedback",
+	summary="Rate a bot message",
+	description="Saves a thumbs up or down and an optional comment for a bot message in one of the user's chats, replacing any earlier feedback on it.",
+	request_body(
+		content=FeedbackRequest,
+		content_type="application/json",
+		example=json!({
+			"message_id": 53,
+			"rating": "down",
+			"comment": "Too many museums"
+		})
+	),
+	responses(
+		(
+			status=200,
+			description="The saved feedback",
+			body=Feedback,
+			content_type="application/json",
+			example=json!({
+				"message_id": 53,
+				"itinerary_id": 14,
+				"rating": "down",
+				"comment": "Too many museums",
+				"updated_at": "2025-10-14T11:39:10Z"
+			})
+		),
+		(status=400, description="Comment must be at most FEEDBACK_COMMENT_MAX_LEN characters", body=ErrorBody),
+		(status=401, description="User has an invalid cookie/no cookie", body=ErrorBody),
+		(status=404, description="Bot message not found for this user", body=ErrorBody),
+		(status=405, description="Method Not Allowed - Must be POST"),
+		(status=408, description="Request Timed Out"),
+		(status=500, description="Internal Server Error", body=ErrorBody)
+	),
+	security(("set-cookie"=[])),
+	tag="Chat"
+)]
+pub async fn api_feedback(
+	Extension(user): Extension<AuthUser>,
+	Extension(pool): Extension<PgPool>,
+	Json(FeedbackRequest {
+		message_id,
+		rating,
+		comment,
+	}): Json<FeedbackRequest>,
+) -> ApiResult<Json<Feedback>> {
+	let comment = comment
+		.as_deref()
+		.map(str::trim)
+		.filter(|comment| !comment.is_empty());
+	if comment.is_some_and(|comment| comment.chars().count() > FEEDBACK_COMMENT_MAX_LEN) {
+		return Err(AppError::Validation(format!(
+			"Comment must be at most {FEEDBACK_COMMENT_MAX_LEN} characters"
+		)));
+	}
+
+	let feedback = sqlx::query_as!(
+		Feedback,
+		r#"
+		INSERT INTO message_feedback (message_id, itinerary_id, rating, comment)
+		SELECT m.id, m.itinerary_id, $3, $4
+		FROM messages m
+		INNER JOIN chat_sessions c ON m.chat_session_id = c.id
+		WHERE m.id = $1 AND c.account_id = $2 AND m.is_user = FALSE AND m.deleted_at IS NULL
+		ON CONFLICT (message_id) DO UPDATE
+		SET
+			itinerary_id = EXCLUDED.itinerary_id,
+			rating = EXCLUDED.rating,
+			comment = EXCLUDED.comment,
+			updated_at = NOW()
+		RETURNING message_id, itinerary_id, rating AS "rating: FeedbackRating", comment, updated_at;
+		"#,
+		message_id,
+		user.id,
+		rating as _,
+		comment
+	)
+	.fetch_optional(&pool)
+	.await
+	.map_err(AppError::from)?
+	.ok_or(AppError::MessageNotFound)?;
+
+	Ok(Json(feedback))
+}
+
+/// Get the user's feedback on a bot message
+///
+/// # Method
+/// `GET /api/chat/feedback/:message_id`
+///
+/// # Responses
+/// - `200 OK` - with body: [Feedback]
+/// - `401 UNAUTHORIZED` - When authentication fails (handled in middleware, public error)
+/// - `404 NOT_FOUND` - The message hasn't been rated, or isn't in the user's chats (public error)
+/// - `500 INTERNAL_SERVER_ERROR` - Internal error (private)
+///
+/// # Examples
+/// ```bash
+/// curl -X GET http://localhost:3001/api/chat/feedback/53
+///   -H "Cookie: auth-token=..."
+/// ```
+#[utoipa::path(
+	get,
+	path="/feedback/{message_id}",
+	summary="Get feedback on a bot message",
+	description="Returns the rating and comment the user gave a bot message, so it can be shown next to the message.",
+	responses(
+		(
+			status=200,
+			description="The user's feedback",
+			body=Feedback,
+			content_type="application/json",
+			example=json!({
+				"message_id": 53,
+				"itinerary_id": 14,
+				"rating": "up",
+				"comment": null,
+				"updated_at": "2025-10-14T11:39:10Z"
+			})
+		),
+		(status=400, description="Bad Request", body=ErrorBody),
+		(status=401, description="User has an invalid cookie/no cookie", body=ErrorBody),
+		(status=404, description="No feedback on this message for this user", body=ErrorBody),
+		(status=405, description="Method Not Allowed - Must be GET"),
+		(status=408, description="Request Timed Out"),
+		(status=500, description="Internal Server Error", body=ErrorBody)
+	),
+	security(("set-cookie"=[])),
+	tag="Chat"
+)]
+pub async fn api_get_feedback(
+	Extension(user): Extension<AuthUser>,
+	Extension(pool): Extension<PgPool>,
+	Path(message_id): Path<i32>,
+) -> ApiResult<Json<Feedback>> {
+	let feedback = sqlx::query_as!(
+		Feedback,
+		r#"
+		SELECT f.message_id, f.itinerary_id, f.rating AS "rating: FeedbackRating", f.comment, f.updated_at
+		FROM message_feedback f
+		INNER JOIN messages m ON f.message_id = m.id
+		INNER JOIN chat_sessions c ON m.chat_session_id = c.id
+		WHERE f.message_id = $1 AND c.account_id = $2;
+		"#,
+		message_id,
+		user.id
+	)
+	.fetch_optional(&pool)
+	.await
+	.map_err(AppError::from)?
+	.ok_or(AppError::NotFound)?;
+
+	Ok(Json(feedback))
+}
+
+/// Count feedback by the pipeline stage the rated messages came out of
+///
+/// Unauthenticated, but only reachable by clients allowed to scrape `/api/metrics`, see
+/// [request_scrape_allowed].
+///
+/// # Method
+/// `GET /api/chat/feedbackStats`
+///
+/// # Responses
+/// - `200 OK` - with body: [FeedbackStatsResponse]
+/// - `403 FORBIDDEN` - The client IP isn't in the allow-list
+/// - `500 INTERNAL_SERVER_ERROR` - Internal error (private)
+///
+/// # Examples
+/// ```bash
+/// curl http://localhost:3001/api/chat/feedbackStats
+/// ```
+#[utoipa::path(
+	get,
+	path="/feedbackStats",
+	summary="Feedback counts by pipeline stage",
+	description="Internal. Counts thumbs up and down on bot messages, grouped by the stage the LLM pipeline was at when each message was sent. Only reachable from the metrics allow-list.",
+	responses(
+		(
+			status=200,
+			description="Feedback counts",
+			body=FeedbackStatsResponse,
+			content_type="application/json",
+			example=json!({
+				"stages": [
+					{ "llm_progress": "AwaitingUser", "up": 12, "down": 3 },
+					{ "llm_progress": "FinalizingItinerary", "up": 40, "down": 9 }
+				]
+			})
+		),
+		(status=403, description="Client IP isn't allowed", body=ErrorBody),
+		(status=500, description="Internal Server Error", body=ErrorBody)
+	),
+	security(()),
+	tag="Chat"
+)]
+pub async fn api_feedback_stats(
+	Extension(pool): Extension<PgPool>,
+	req: Request,
+) -> ApiResult<Json<FeedbackStatsResponse>> {
+	if !request_scrape_allowed(&req) {
+		return Err(AppError::Forbidden);
+	}
+
+	let stages = sqlx::query_as!(
+		FeedbackStageStats,
+		r#"
+		SELECT
+			m.llm_progress AS "llm_progress: LlmProgress",
+			COUNT(*) FILTER (WHERE f.rating = 'Up') AS "up!",
+			COUNT(*) FILTER (WHERE f.rating = 'Down') AS "down!"
+		FROM message_feedback f
+		INNER JOIN messages m ON f.message_id = m.id
+		GROUP BY m.llm_progress
+		ORDER BY m.llm_progress;
+		"#
+	)
+	.fetch_all(&pool)
+	.await
+	.map_err(AppError::from)?;
+
+	Ok(Json(FeedbackStatsResponse { stages }))
+}
+
+/// Postgres `NOTIFY` channel carrying ids of new messages in a chat session
+fn chat_channel(chat_session_id: i32) -> String {
+	format!("chat_session_{}", chat_session_id)
+}
+
+/// The orchestrator agent factory, or a 503 if the server started without one
+pub(crate) fn require_agent(agent: Option<SharedAgentFactory>) -> ApiResult<SharedAgentFactory> {
+	agent.ok_or_else(|| AppError::ServiceUnavailable(String::from("AI features are disabled")))
+}
+
+/// 403 with code `EMAIL_NOT_VERIFIED` until the account verifies its email
+pub(crate) async fn require_verified_email(pool: &PgPool, account_id: i32) -> ApiResult<()> {
+	let verified = sqlx::query_scalar!(
+		"SELECT email_verified FROM accounts WHERE id = $1",
+		account_id
+	)
+	.fetch_optional(pool)
+	.await
+	.map_err(AppError::from)?
+	.ok_or(AppError::NotFound)?;
+
+	if verified {
+		Ok(())
+	} else {
+		Err(AppError::EmailNotVerified)
+	}
+}
+
+/// Mark the chat session as having just received a message, which also unarchives it
+pub(crate) async fn touch_chat_session(pool: &PgPool, chat_session_id: i32) -> ApiResult<()> {
+	sqlx::query!(
+		"UPDATE chat_sessions SET last_message_at = NOW(), archived = FALSE WHERE id = $1",
+		chat_session_id
+	)
+	.execute(pool)
+	.await
+	.map_err(AppError::from)?;
+	Ok(())
+}
+
+/// Tells websocket listeners on this chat session about a new message.
+///
+/// Only the message id is sent since `NOTIFY` payloads are limited to 8000 bytes.
+pub(crate) async fn notify_new_message(
+	pool: &PgPool,
+	chat_session_id: i32,
+	message_id: i32,
+) -> ApiResult<()> {
+	sqlx::query!(
+		"SELECT pg_notify($1, $2)",
+		chat_channel(chat_session_id),
+		message_id.to_string()
+	)
+	.execute(pool)
+	.await
+	.map_err(AppError::from)?;
+	Ok(())
+}
+
+/// Open a websocket that receives new messages in a chat session as they're created
+///
+/// # Method
+/// `GET /api/chat/ws/:chat_session_id`
+///
+/// # Responses
+/// - `101 SWITCHING_PROTOCOLS` - Each new [Message] is sent as a JSON text frame
+/// - `401 UNAUTHORIZED` - Invalid or missing `auth-token` cookie (public error)
+/// - `404 NOT_FOUND` - The chat session does not belong to the user or does not exist (public error)
+/// - `500 INTERNAL_SERVER_ERROR` - Internal error (private)
+///
+/// # Examples
+/// ```bash
+/// websocat ws://localhost:3001/api/chat/ws/6
+/// ```
+///
+/// Notes:
+/// - Browsers can't set headers on websocket requests, so this route authenticates the
+///   `auth-token` cookie itself instead of going through `middleware_auth`.
+#[utoipa::path(
+	get,
+	path="/ws/{chat_session_id}",
+	summary="Stream new messages in a chat session",
+	description="Upgrades to a websocket that pushes each new message in the chat session as JSON, so the frontend doesn't have to poll.",
+	responses(
+		(
+			status=101,
+			description="Switched to websocket, frames are JSON encoded messages",
+			body=Message,
+			content_type="application/json",
+			example=json!({
+				"id": 53,
+				"is_user": false,
+				"timestamp": "2025-10-14 11-39-10",
+				"text": "Bot reply",
+				"itinerary_id": 14
+			})
+		),
+		(status=400, description="Bad Request - Not a websocket upgrade", body=ErrorBody),
+		(status=401, description="User has an invalid cookie/no cookie", body=ErrorBody),
+		(status=404, description="Chat session not found for this user", body=ErrorBody),
+		(status=405, description="Method Not Allowed - Must be GET"),
+		(status=408, description="Request Timed Out"),
+		(status=500, description="Internal Server Error", body=ErrorBody)
+	),
+	security(("set-cookie"=[])),
+	tag="Chat"
+)]
+pub async fn api_chat_ws(
+	cookies: Cookies,
+	Extension(key): Extension<Key>,
+	Extension(pool): Extension<PgPool>,
+	Path(chat_session_id): Path<i32>,
+	ws: WebSocketUpgrade,
+) -> ApiResult<Response> {
+	let (user, _) = authenticate_cookie(&cookies, &key, &pool)
+		.await
+		.ok_or(AppError::Unauthorized)?;
+
+	// verify the given chat session belongs to this user
+	sqlx::query!(
+		r#"
+		SELECT id FROM chat_sessions
+		WHERE id=$1 AND account_id=$2;
+		"#,
+		chat_session_id,
+		user.id
+	)
+	.fetch_optional(&pool)
+	.await
+	.map_err(AppError::from)?
+	.ok_or(AppError::ChatSessionNotFound)?;
+
+	// Listen before upgrading so no message slips through in between
+	let mut listener = PgListener::connect_with(&pool)
+		.await
+		.map_err(AppError::from)?;
+	listener
+		.listen(&chat_channel(chat_session_id))
+		.await
+		.map_err(AppError::from)?;
+
+	Ok(ws.on_upgrade(move |socket| chat_socket(socket, listener, pool, chat_session_id)))
+}
+
+/// Forwards new messages to the websocket until either side closes.
+async fn chat_socket(
+	socket: WebSocket,
+	mut listener: PgListener,
+	pool: PgPool,
+	chat_session_id: i32,
+) {
+	let (mut sender, mut receiver) = socket.split();
+
+	let mut send_task = tokio::spawn(async move {
+		loop {
+			let notification = match listener.recv().await {
+				Ok(n) => n,
+				Err(e) => {
+					error!(
+						"WS ->> chat session {} listener failed: {}",
+						chat_session_id, e
+					);
+					break;
+				}
+			};
+			let Ok(message_id) = notification.payload().parse::<i32>() else {
+				continue;
+			};
+
+			let row = match sqlx::query_as!(
+				MessageRow,
+				r#"
+				SELECT id, chat_session_id, itinerary_id, is_user, timestamp, text
+				FROM messages
+				WHERE id=$1 AND chat_session_id=$2;
+				"#,
+				message_id,
+				chat_session_id
+			)
+			.fetch_optional(&pool)
+			.await
+			{
+				Ok(Some(row)) => row,
+				// deleted before we got to it
+				Ok(None) => continue,
+				Err(e) => {
+					error!("WS ->> failed to fetch message {}: {}", message_id, e);
+					continue;
+				}
+			};
+
+			let text = match serde_json::to_string(&Message {
+				id: row.id,
+				is_user: row.is_user,
+				timestamp: row.timestamp,
+				text: row.text,
+				itinerary_id: row.itinerary_id,
+			}) {
+				Ok(text) => text,
+				Err(e) => {
+					error!("WS ->> failed to serialize message {}: {}", message_id, e);
+					continue;
+				}
+			};
+
+			if sender.send(WsMessage::Text(text.into())).await.is_err() {
+				break;
+			}
+		}
+	});
+
+	// The client doesn't send anything meaningful, just wait for it to hang up
+	let mut recv_task = tokio::spawn(async move {
+		while let Some(Ok(msg)) = receiver.next().await {
+			if let WsMessage::Close(_) = msg {
+				break;
+			}
+		}
+	});
+
+	tokio::select! {
+		_ = &mut send_task => recv_task.abort(),
+		_ = &mut recv_task => send_task.abort(),
+	}
 }
 
 /// Create the chat routes with authentication middleware.
@@ -1394,24 +3967,49 @@ pub async fn api_progress(
 /// # Routes
 /// - `GET /chats` - Get metadata for all the user's chat sessions (protected)
 /// - `POST /messagePage` - Gets a page of messages in the session, ending with message_id or the latest message (protected)
+/// - `GET /:id/export` - Downloads every message in a chat session as JSON, Markdown or text (protected)
+/// - `GET /:id/summary` - Gets a short, cached summary of a chat session (protected)
 /// - `POST /updateMessage` - Updates a user's message and waits for a bot reply (protected)
 /// - `POST /sendMessage` - Sends a user's message and waits for a bot reply (protected)
+/// - `POST /regenerate` - Replaces the bot's reply to the latest user message (protected)
 /// - `GET /newChat` - Gets a chat session id for an empty chat (protected)
+/// - `POST /branch` - Copies a chat session up to a message into a new one (protected)
 /// - `DELETE /:id` - Delete a chat session and associated messages (protected)
+/// - `DELETE /message/:id` - Delete a user's message and the bot's replies to it (protected)
+/// - `POST /message/:id/restore` - Restore a deleted message and its replies (protected)
 /// - `POST /rename` - Renames the title of a chat session (protected)
 /// - `POST /progress` - Fetches the progress of the llm pipeline for this chat session (protected)
+/// - `POST /feedback` - Rates a bot message (protected)
+/// - `GET /feedback/:message_id` - Gets the user's rating of a bot message (protected)
+/// - `GET /feedbackStats` - Feedback counts by pipeline stage (internal, IP allow-listed)
+/// - `GET /ws/:chat_session_id` - Websocket streaming new messages in the chat session (authenticates its own cookie)
 ///
 /// # Middleware
-/// All routes are protected by `middleware_auth` which validates the `auth-token` cookie.
+/// All routes except the websocket and `/feedbackStats` are protected by `middleware_auth` which validates the `auth-token` cookie.
 pub fn chat_routes() -> AxumRouter {
 	AxumRouter::new()
 		.route("/chats", get(api_chats))
 		.route("/messagePage", post(api_message_page))
+		.route("/searchMessages", post(api_search_messages))
+		.route("/{id}/export", get(api_export_chat))
+		.route("/{id}/summary", get(api_chat_summary))
 		.route("/updateMessage", post(api_update_message))
 		.route("/sendMessage", post(api_send_message))
+		.route("/regenerate", post(api_regenerate))
 		.route("/newChat", get(api_new_chat))
+		.route("/branch", post(api_branch))
 		.route("/{id}", delete(api_delete_chat))
+		.route("/message/{id}", delete(api_delete_message))
+		.route("/message/{id}/restore", post(api_restore_message))
 		.route("/rename", post(api_rename))
+		.route("/pin", post(api_pin))
+		.route("/archive", post(api_archive))
+		.route("/unarchive", post(api_unarchive))
 		.route("/progress", post(api_progress))
+		.route("/cancel", post(api_cancel))
+		.route("/feedback", post(api_feedback))
+		.route("/feedback/{message_id}", get(api_get_feedback))
 		.route_layer(axum::middleware::from_fn(middleware_auth))
+		.route("/feedbackStats", get(api_feedback_stats))
+		.route("/ws/{chat_session_id}", get(api_chat_ws))
 }
@@ -0,0 +1,525 @@
+/*
+ * src/controllers/events.rs
+ *
+ * File for Event Controller API Endpoints
+ *
+ * Purpose:
+ *   Serve Event Related API Requests, like reviews and favorites
+ */
+
+use axum::routing::{get, post};
+use axum::{
+	Extension, Json,
+	extract::{Path, Query},
+};
+use sqlx::PgPool;
+use tracing::debug;
+use utoipa::OpenApi;
+
+use crate::controllers::AxumRouter;
+use crate::db::events::fetch_events_by_ids;
+use crate::error::{ApiResult, AppError, ErrorBody};
+use crate::global::{POPULAR_EVENTS_LEN, POPULAR_EVENTS_MAX, REVIEW_COMMENT_MAX_LEN};
+use crate::http_models::event::{Event, PopularQuery, Review, ReviewRequest, ReviewsResponse};
+use crate::middleware::{AuthUser, middleware_auth};
+use crate::swagger::SecurityAddon;
+
+#[derive(OpenApi)]
+#[openapi(
+	paths(
+		api_review,
+		api_reviews,
+		api_favorite,
+		api_unfavorite,
+		api_popular
+	),
+	modifiers(&SecurityAddon),
+	security(("set-cookie"=[])),
+    info(
+    	title="Event Routes",
+    	description = "API endpoints dealing with events outside of an itinerary."
+    ),
+    tags((name="Events"))
+)]
+#[allow(dead_code)]
+pub struct EventsApiDoc;
+
+/// Checks that the event exists and this account can see it,
+/// which is every event except other accounts' user-created ones
+pub async fn check_event_visible(event_id: i32, account_id: i32, pool: &PgPool) -> ApiResult<()> {
+	sqlx::query!(
+		r#"SELECT id FROM events WHERE id = $1 AND (user_created = FALSE OR account_id = $2)"#,
+		event_id,
+		account_id
+	)
+	.fetch_optional(pool)
+	.await
+	.map_err(AppError::from)?
+	.ok_or(AppError::EventNotFound)?;
+	Ok(())
+}
+
+/// Rate and review an event
+///
+/// # Method
+/// `POST /api/events/:id/review`
+///
+/// # Request Body
+/// - [ReviewRequest]
+///
+/// # Responses
+/// - `200 OK` - with body: [Review]
+/// - `400 BAD_REQUEST` - Rating isn't 1 to 5, or the comment is too long (public error)
+/// - `401 UNAUTHORIZED` - When authentication fails (handled in middleware, public error)
+/// - `404 NOT_FOUND` - Event not found (public error)
+/// - `500 INTERNAL_SERVER_ERROR` - Internal error (private)
+///
+/// # Examples
+/// ```bash
+/// curl -X POST http://localhost:3001/api/events/42/review
+///   -H "Content-Type: application/json"
+///   -H "Cookie: auth-token=..."
+///   -d '{
+///     "rating": 4,
+///     "comment": "Great views, long line"
+///   }'
+/// ```
+///
+/// Notes:
+/// - Each account has one review per event, posting again replaces it.
+/// - Blank comments are saved as no comment.
+#[utoipa::path(
+	post,
+	path="/{id}/review",
+	summary="Rate and review an event",
+	description="Saves this user's 1 to 5 star rating and optional comment for the event, replacing their previous review of it.",
+	request_body(
+		content=ReviewRequest,
+		content_type="application/json",
+		description="The rating and comment",
+		example=json!({
+			"rating": 4,
+			"comment": "Great views, long line"
+		})
+	),
+	responses(
+		(
+			status=200,
+			description="The saved review",
+			body=Review,
+			content_type="application/json",
+			example=json!({
+				"id": 7,
+				"reviewer": "Jane",
+				"rating": 4,
+				"comment": "Great views, long line",
+				"created_at": "2025-11-05T14:03:10Z"
+			})
+		),
+		(status=400, description="Rating must be 1 to 5 and the comment at most REVIEW_COMMENT_MAX_LEN characters", body=ErrorBody),
+		(status=401, description="User has an invalid cookie/no cookie", body=ErrorBody),
+		(status=404, description="Event not found", body=ErrorBody),
+		(status=405, description="Method Not Allowed - Must be POST"),
+		(status=408, description="Request Timed Out"),
+		(status=500, description="Internal Server Error", body=ErrorBody)
+	),
+	security(("set-cookie"=[])),
+	tag="Events"
+)]
+pub async fn api_review(
+	Extension(user): Extension<AuthUser>,
+	Extension(pool): Extension<PgPool>,
+	Path(event_id): Path<i32>,
+	Json(request): Json<ReviewRequest>,
+) -> ApiResult<Json<Review>> {
+	debug!(
+		"HANDLER ->> /api/events/{}/review 'api_review' - User ID: {}",
+		event_id, user.id
+	);
+
+	if !(1..=5).contains(&request.rating) {
+		return Err(AppError::Validation(
+			"Rating must be between 1 and 5".to_string(),
+		));
+	}
+	let comment = request
+		.comment
+		.as_deref()
+		.map(str::trim)
+		.filter(|comment| !comment.is_empty());
+	if comment.is_some_and(|comment| comment.chars().count() > REVIEW_COMMENT_MAX_LEN) {
+		return Err(AppError::Validation(format!(
+			"Comment must be at most {REVIEW_COMMENT_MAX_LEN} characters"
+		)));
+	}
+
+	check_event_visible(event_id, user.id, &pool).await?;
+
+	let review = sqlx::query_as!(
+		Review,
+		r#"
+		WITH review AS (
+			INSERT INTO event_reviews (account_id, event_id, rating, comment)
+			VALUES ($1, $2, $3, $4)
+			ON CONFLICT (account_id, event_id) DO UPDATE
+			SET rating = EXCLUDED.rating, comment = EXCLUDED.comment, created_at = NOW()
+			RETURNING id, account_id, rating, comment, created_at
+		)
+		SELECT
+			r.id AS "id!",
+			a.first_name AS reviewer,
+			r.rating AS "rating!",
+			r.comment,
+			r.created_at AS "created_at!"
+		FROM review r
+		JOIN accounts a ON a.id = r.account_id;
+		"#,
+		user.id,
+		event_id,
+		request.rating,
+		comment
+	)
+	.fetch_one(&pool)
+	.await
+	.map_err(AppError::from)?;
+
+	Ok(Json(review))
+}
+
+/// Get the reviews of an event
+///
+/// # Method
+/// `GET /api/events/:id/reviews`
+///
+/// # Responses
+/// - `200 OK` - with body: [ReviewsResponse]
+/// - `401 UNAUTHORIZED` - When authentication fails (handled in middleware, public error)
+/// - `404 NOT_FOUND` - Event not found (public error)
+/// - `500 INTERNAL_SERVER_ERROR` - Internal error (private)
+///
+/// # Examples
+/// ```bash
+/// curl -X GET http://localhost:3001/api/events/42/reviews
+///   -H "Cookie: auth-token=..."
+/// ```
+#[utoipa::path(
+	get,
+	path="/{id}/reviews",
+	summary="Get the reviews of an event",
+	description="Fetches every review of the event, newest first, with the average rating.",
+	responses(
+		(
+			status=200,
+			description="The event's reviews",
+			body=ReviewsResponse,
+			content_type="application/json",
+			example=json!({
+				"average_rating": 4.5,
+				"reviews": [
+					{
+						"id": 7,
+						"reviewer": "Jane",
+						"rating": 4,
+						"comment": "Great views, long line",
+						"created_at": "2025-11-05T14:03:10Z"
+					},
+					{
+						"id": 3,
+						"reviewer": "Sam",
+						"rating": 5,
+						"comment": null,
+						"created_at": "2025-10-21T09:45:00Z"
+					}
+				]
+			})
+		),
+		(status=400, description="Bad Request", body=ErrorBody),
+		(status=401, description="User has an invalid cookie/no cookie", body=ErrorBody),
+		(status=404, description="Event not found", body=ErrorBody),
+		(status=405, description="Method Not Allowed - Must be GET"),
+		(status=408, description="Request Timed Out"),
+		(status=500, description="Internal Server Error", body=ErrorBody)
+	),
+	security(("set-cookie"=[])),
+	tag="Events"
+)]
+pub async fn api_reviews(
+	Extension(user): Extension<AuthUser>,
+	Extension(pool): Extension<PgPool>,
+	Path(event_id): Path<i32>,
+) -> ApiResult<Json<ReviewsResponse>> {
+	debug!(
+		"HANDLER ->> /api/events/{}/reviews 'api_reviews' - User ID: {}",
+		event_id, user.id
+	);
+
+	check_event_visible(event_id, user.id, &pool).await?;
+
+	let reviews = sqlx::query_as!(
+		Review,
+		r#"
+		SELECT
+			r.id,
+			a.first_name AS reviewer,
+			r.rating,
+			r.comment,
+			r.created_at
+		FROM event_reviews r
+		JOIN accounts a ON a.id = r.account_id
+		WHERE r.event_id = $1
+		ORDER BY r.created_at DESC, r.id DESC;
+		"#,
+		event_id
+	)
+	.fetch_all(&pool)
+	.await
+	.map_err(AppError::from)?;
+
+	let average_rating = (!reviews.is_empty()).then(|| {
+		reviews
+			.iter()
+			.map(|review| review.rating as f64)
+			.sum::<f64>()
+			/ reviews.len() as f64
+	});
+
+	Ok(Json(ReviewsResponse {
+		average_rating,
+		reviews,
+	}))
+}
+
+/// Favorite an event to find it again for future itineraries
+///
+/// # Method
+/// `POST /api/events/:id/favorite`
+///
+/// # Responses
+/// - `200 OK` - Event is favorited
+/// - `401 UNAUTHORIZED` - When authentication fails (handled in middleware, public error)
+/// - `404 NOT_FOUND` - Event not found (public error)
+/// - `500 INTERNAL_SERVER_ERROR` - Internal error (private)
+///
+/// # Examples
+/// ```bash
+/// curl -X POST http://localhost:3001/api/events/42/favorite
+///   -H "Cookie: auth-token=..."
+/// ```
+#[utoipa::path(
+	post,
+	path="/{id}/favorite",
+	summary="Favorite an event",
+	description="Adds the event to this user's favorites, listed by `/api/account/favorites` and preferred when ranking events for an itinerary. Favoriting a favorite does nothing.",
+	responses(
+		(status=200, description="Event is favorited"),
+		(status=400, description="Bad Request", body=ErrorBody),
+		(status=401, description="User has an invalid cookie/no cookie", body=ErrorBody),
+		(status=404, description="Event not found", body=ErrorBody),
+		(status=405, description="Method Not Allowed - Must be POST"),
+		(status=408, description="Request Timed Out"),
+		(status=500, description="Internal Server Error", body=ErrorBody)
+	),
+	security(("set-cookie"=[])),
+	tag="Events"
+)]
+pub async fn api_favorite(
+	Extension(user): Extension<AuthUser>,
+	Extension(pool): Extension<PgPool>,
+	Path(event_id): Path<i32>,
+) -> ApiResult<()> {
+	debug!(
+		"HANDLER ->> /api/events/{}/favorite 'api_favorite' - User ID: {}",
+		event_id, user.id
+	);
+
+	check_event_visible(event_id, user.id, &pool).await?;
+
+	sqlx::query!(
+		r#"
+		INSERT INTO favorites (account_id, event_id)
+		VALUES ($1, $2)
+		ON CONFLICT (account_id, event_id) DO NOTHING;
+		"#,
+		user.id,
+		event_id
+	)
+	.execute(&pool)
+	.await
+	.map_err(AppError::from)?;
+
+	Ok(())
+}
+
+/// Remove an event from the user's favorites
+///
+/// # Method
+/// `DELETE /api/events/:id/favorite`
+///
+/// # Responses
+/// - `200 OK` - Event is no longer a favorite
+/// - `401 UNAUTHORIZED` - When authentication fails (handled in middleware, public error)
+/// - `404 NOT_FOUND` - Event not found (public error)
+/// - `500 INTERNAL_SERVER_ERROR` - Internal error (private)
+///
+/// # Examples
+/// ```bash
+/// curl -X DELETE http://localhost:3001/api/events/42/favorite
+///   -H "Cookie: auth-token=..."
+/// ```
+#[utoipa::path(
+	delete,
+	path="/{id}/favorite",
+	summary="Unfavorite an event",
+	description="Removes the event from this user's favorites. Unfavoriting an event that isn't a favorite does nothing.",
+	responses(
+		(status=200, description="Event is no longer a favorite"),
+		(status=400, description="Bad Request", body=ErrorBody),
+		(status=401, description="User has an invalid cookie/no cookie", body=ErrorBody),
+		(status=404, description="Event not found", body=ErrorBody),
+		(status=405, description="Method Not Allowed - Must be DELETE"),
+		(status=408, description="Request Timed Out"),
+		(status=500, description="Internal Server Error", body=ErrorBody)
+	),
+	security(("set-cookie"=[])),
+	tag="Events"
+)]
+pub async fn api_unfavorite(
+	Extension(user): Extension<AuthUser>,
+	Extension(pool): Extension<PgPool>,
+	Path(event_id): Path<i32>,
+) -> ApiResult<()> {
+	debug!(
+		"HANDLER ->> /api/events/{}/favorite 'api_unfavorite' - User ID: {}",
+		event_id, user.id
+	);
+
+	check_event_visible(event_id, user.id, &pool).await?;
+
+	sqlx::query!(
+		r#"DELETE FROM favorites WHERE account_id = $1 AND event_id = $2"#,
+		user.id,
+		event_id
+	)
+	.execute(&pool)
+	.await
+	.map_err(AppError::from)?;
+
+	Ok(())
+}
+
+/// Get the most viewed events in a city
+///
+/// # Method
+/// `GET /api/events/popular?city=Tokyo&limit=10`
+///
+/// # Responses
+/// - `200 OK` - with body: list of [Event], most viewed first
+/// - `400 BAD_REQUEST` - No city given (public error)
+/// - `401 UNAUTHORIZED` - When authentication fails (handled in middleware, public error)
+/// - `500 INTERNAL_SERVER_ERROR` - Internal error (private)
+///
+/// # Examples
+/// ```bash
+/// curl -X GET "http://localhost:3001/api/events/popular?city=Tokyo&limit=10"
+///   -H "Cookie: auth-token=..."
+/// ```
+///
+/// Notes:
+/// - An event is viewed each time an itinerary containing it is opened on its own, listing saved itineraries doesn't count.
+/// - Archived and user-created events are left out.
+#[utoipa::path(
+	get,
+	path="/popular",
+	summary="Get the most viewed events in a city",
+	description="Fetches the events in the city that were opened in itineraries the most, most viewed first. Archived and user-created events are left out.",
+	params(
+		("city"=String, Query, description="Only events in this city, ignoring case"),
+		("limit"=Option<u32>, Query, description="Events to return, 10 by default and at most 50")
+	),
+	responses(
+		(
+			status=200,
+			description="The most viewed events",
+			body=Vec<Event>,
+			content_type="application/json",
+			example=json!([{
+				"id": 42,
+				"event_name": "Senso-ji",
+				"city": "Tokyo",
+				"country": "Japan",
+				"lat": 35.7148,
+				"lng": 139.7967,
+				"event_type": "place_of_worship",
+				"user_created": false,
+				"price_level": 1,
+				"periods": [],
+				"special_days": []
+			}])
+		),
+		(status=400, description="Missing city", body=ErrorBody),
+		(status=401, description="User has an invalid cookie/no cookie", body=ErrorBody),
+		(status=405, description="Method Not Allowed - Must be GET"),
+		(status=408, description="Request Timed Out"),
+		(status=500, description="Internal Server Error", body=ErrorBody)
+	),
+	security(("set-cookie"=[])),
+	tag="Events"
+)]
+pub async fn api_popular(
+	Extension(user): Extension<AuthUser>,
+	Extension(pool): Extension<PgPool>,
+	Query(query): Query<PopularQuery>,
+) -> ApiResult<Json<Vec<Event>>> {
+	debug!(
+		"HANDLER ->> /api/events/popular 'api_popular' - User ID: {}",
+		user.id
+	);
+
+	let city = query.city.trim();
+	if city.is_empty() {
+		return Err(AppError::Validation("City must not be empty".to_string()));
+	}
+	let limit = query
+		.limit
+		.unwrap_or(POPULAR_EVENTS_LEN)
+		.clamp(1, POPULAR_EVENTS_MAX) as i64;
+	let ids = sqlx::query_scalar!(
+		r#"
+		SELECT id
+		FROM events
+		WHERE LOWER(city) = LOWER($1) AND user_created = FALSE AND archived = FALSE
+		ORDER BY view_count DESC, id ASC
+		LIMIT $2
+		"#,
+		city,
+		limit
+	)
+	.fetch_all(&pool)
+	.await
+	.map_err(AppError::from)?;
+
+	let events = fetch_events_by_ids(&pool, &ids)
+		.await
+		.map_err(AppError::from)?;
+	Ok(Json(events))
+}
+
+/// Create the event routes with authentication middleware.
+///
+/// # Routes
+/// - `POST /{id}/review` - Rates and reviews an event (protected)
+/// - `GET /{id}/reviews` - Gets the reviews of an event (protected)
+/// - `POST /{id}/favorite` - Adds an event to the user's favorites (protected)
+/// - `DELETE /{id}/favorite` - Removes an event from the user's favorites (protected)
+/// - `GET /popular` - Gets the most viewed events in a city (protected)
+///
+/// # Middleware
+/// All routes are protected by `middleware_auth` which validates the `auth-token` cookie.
+pub fn events_routes() -> AxumRouter {
+	AxumRouter::new()
+		.route("/{id}/review", post(api_review))
+		.route("/{id}/reviews", get(api_reviews))
+		.route("/{id}/favorite", post(api_favorite).delete(api_unfavorite))
+		.route("/popular", get(api_popular))
+		.route_layer(axum::middleware::from_fn(middleware_auth))
+}
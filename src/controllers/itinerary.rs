@@ -7,25 +7,48 @@
  *   Serve Itinerary Related API Requests
  */
 
-use axum::routing::{delete, post};
-use axum::{Extension, Json, extract::Path, routing::get};
+use axum::routing::{delete, patch, post};
+use axum::{
+	Extension, Json,
+	extract::{Path, Query},
+	routing::get,
+};
 use chrono::NaiveDate;
-use sqlx::PgPool;
-use tracing::debug;
+use sqlx::{Executor, FromRow, PgPool, Postgres, Row};
+use tracing::{debug, error, info};
 use utoipa::OpenApi;
+use uuid::Uuid;
 
+use crate::agent::configs::orchestrator::SharedAgentFactory;
+use crate::agent::models::context::SharedContextStore;
+use crate::agent::models::event::SharedEventCache;
 use crate::controllers::AxumRouter;
-use crate::error::{ApiResult, AppError};
-use crate::global::EVENT_SEARCH_RESULT_LEN;
+use crate::controllers::chat::{
+	agent_permit, notify_new_message, require_agent, require_verified_email, send_message_to_llm,
+	touch_chat_session,
+};
+use crate::controllers::events::check_event_visible;
+use crate::db::events::{fetch_event, fetch_events_by_ids};
+use crate::error::{ApiResult, AppError, ErrorBody};
+use crate::geocoding::geocode_address;
+use crate::global::{
+	EVENT_SEARCH_RESULT_LEN, ITINERARY_BROWSE_PAGE_LEN, ITINERARY_BROWSE_PAGE_MAX,
+	ITINERARY_PURGE_INTERVAL_SECONDS, ITINERARY_RETENTION_DAYS, ITINERARY_SNAPSHOT_LIMIT,
+	ITINERARY_TITLE_MAX_LEN, MAX_TRIP_DURATION_DAYS,
+};
 use crate::http_models::event::{
-	Event, SearchEventRequest, SearchEventResponse, UserEventRequest, UserEventResponse,
+	Event, SearchEventQuery, SearchEventRequest, SearchEventResponse, SearchEventResult,
+	UserEventRequest, UserEventResponse,
 };
 use crate::http_models::itinerary::*;
+use crate::http_models::message::Message;
 use crate::middleware::{AuthUser, middleware_auth};
+use crate::notifications::{NotificationKind, SharedNotifier, notify_account};
 use crate::sql_models::event_list::EventListJoinRow;
 use crate::sql_models::itinerary::ItineraryRow;
-use crate::sql_models::{Period, TimeOfDay};
+use crate::sql_models::{LlmProgress, Period, TimeOfDay};
 use crate::swagger::SecurityAddon;
+use crate::util::geo::EARTH_RADIUS_KM;
 
 #[derive(OpenApi)]
 #[openapi(
@@ -36,7 +59,24 @@ use crate::swagger::SecurityAddon;
 		api_unsave,
 		api_user_event,
 		api_search_event,
-		api_delete_user_event
+		api_get_event,
+		api_delete_user_event,
+		api_reorder_events,
+		api_move_event,
+		api_rename_itinerary,
+		api_history,
+		api_restore,
+		api_itinerary_stats,
+		api_share,
+		api_unshare,
+		api_star,
+		api_unstar,
+		api_shared_itinerary,
+		api_browse,
+		api_clone_itinerary,
+		api_regenerate_itinerary,
+		api_restore_deleted,
+		api_merge_itinerary
 	),
 	modifiers(&SecurityAddon),
 	security(("set-cookie"=[])),
@@ -51,6 +91,7 @@ pub struct ItineraryApiDoc;
 
 /// Returns the [EventDay]s associated with this itinerary
 /// Returns only the days that exist in event_list (including empty days with NULL event_id)
+/// Archived events are left out
 async fn itinerary_events(
 	itinerary_id: i32,
 	_start_date: NaiveDate,
@@ -71,7 +112,7 @@ async fn itinerary_events(
 	.await
 	.map_err(AppError::from)?;
 
-	// Now get all events (excluding placeholders with NULL event_id)
+	// Now get all events (excluding placeholders with NULL event_id and archived events)
 	let event_list: Vec<EventListJoinRow> = sqlx::query_as!(
 		EventListJoinRow,
 		r#"
@@ -118,8 +159,8 @@ async fn itinerary_events(
 			el.block_index
 		FROM event_list el
 		JOIN events e ON e.id = el.event_id
-		WHERE el.itinerary_id = $1 AND el.event_id IS NOT NULL
-		ORDER BY el.date, el.time_of_day
+		WHERE el.itinerary_id = $1 AND el.event_id IS NOT NULL AND e.archived = FALSE
+		ORDER BY el.date, el.time_of_day, el.block_index
 		"#,
 		itinerary_id
 	)
@@ -143,6 +184,7 @@ async fn itinerary_events(
 		let mut evening_events = Vec::new();
 
 		// If there are events for this date, populate them
+		// Rows are already ordered by block_index within each time block
 		if let Some(day_events) = events_by_date.get(&date) {
 			for event in day_events {
 				match event.time_of_day {
@@ -152,15 +194,6 @@ async fn itinerary_events(
 				}
 			}
 		}
-		#[cfg(not(tarpaulin_include))]
-		fn sort(a: &Event, b: &Event) -> std::cmp::Ordering {
-			a.block_index
-				.unwrap_or(i32::MAX)
-				.cmp(&b.block_index.unwrap_or(i32::MAX))
-		}
-		morning_events.sort_by(sort);
-		afternoon_events.sort_by(sort);
-		evening_events.sort_by(sort);
 
 		event_days.push(EventDay {
 			morning_events,
@@ -175,68 +208,20 @@ async fn itinerary_events(
 
 /// Returns the unassigned events for this itinerary
 async fn unassigned_events(event_ids: &[i32], pool: &PgPool) -> ApiResult<Vec<Event>> {
-	if event_ids.is_empty() {
-		return Ok(Vec::new());
-	}
-
-	let events = sqlx::query_as!(
-		Event,
-		r#"
-		SELECT
-			id,
-			street_address,
-			postal_code,
-			city,
-			country,
-			lat,
-			lng,
-			event_type,
-			event_description,
-			event_name,
-			user_created,
-			hard_start,
-			hard_end,
-			timezone,
-			place_id,
-			wheelchair_accessible_parking,
-			wheelchair_accessible_entrance,
-			wheelchair_accessible_restroom,
-			wheelchair_accessible_seating,
-			serves_vegetarian_food,
-			price_level,
-			utc_offset_minutes,
-			website_uri,
-			types,
-			photo_name,
-			photo_width,
-			photo_height,
-			photo_author,
-			photo_author_uri,
-			photo_author_photo_uri,
-			weekday_descriptions,
-			secondary_hours_type,
-			next_open_time,
-			next_close_time,
-			open_now,
-			periods as "periods: Vec<Period>",
-			special_days,
-			NULL::int AS block_index
-		FROM events
-		WHERE id = ANY($1)
-		"#,
-		event_ids
-	)
-	.fetch_all(pool)
-	.await
-	.map_err(AppError::from)?;
-
-	Ok(events)
+	fetch_events_by_ids(pool, event_ids)
+		.await
+		.map_err(AppError::from)
 }
 
 /// Inserts the events associated with this itinerary into the `event_list` table.
 /// Assumes the itinerary was already inserted into `itineraries` table.
 /// Also inserts placeholder entries (event_id = NULL) for empty days to preserve them.
-pub async fn insert_event_list(itinerary: Itinerary, pool: &PgPool) -> ApiResult<()> {
+/// Takes a pool, or the caller's transaction as `&mut *tx` so a failure here also undoes
+/// the itinerary insert.
+pub async fn insert_event_list<'e, E>(itinerary: Itinerary, executor: E) -> ApiResult<()>
+where
+	E: Executor<'e, Database = Postgres>,
+{
 	let mut cap = 0;
 	for day in itinerary.event_days.iter() {
 		cap += day.morning_events.len();
@@ -285,17 +270,17 @@ pub async fn insert_event_list(itinerary: Itinerary, pool: &PgPool) -> ApiResult
 			indices.extend(
 				day.morning_events
 					.into_iter()
-					.map(|event| event.block_index),
+					.map(|event| Some(event.block_index.unwrap_or(0))),
 			);
 			indices.extend(
 				day.afternoon_events
 					.into_iter()
-					.map(|event| event.block_index),
+					.map(|event| Some(event.block_index.unwrap_or(0))),
 			);
 			indices.extend(
 				day.evening_events
 					.into_iter()
-					.map(|event| event.block_index),
+					.map(|event| Some(event.block_index.unwrap_or(0))),
 			);
 		}
 	}
@@ -312,7 +297,7 @@ pub async fn insert_event_list(itinerary: Itinerary, pool: &PgPool) -> ApiResult
 		dates.as_slice(),
 		indices.as_slice() as &[Option<i32>],
 	)
-	.execute(pool)
+	.execute(executor)
 	.await
 	.map_err(AppError::from)?;
 
@@ -323,11 +308,15 @@ pub async fn insert_event_list(itinerary: Itinerary, pool: &PgPool) -> ApiResult
 ///
 /// # Method
 /// `GET /api/itinerary/saved`
+/// `GET /api/itinerary/saved?starred=true`
 ///
 /// # Auth
 /// Protected by `auth_middleware` which validates the `auth-token` private cookie,
 /// checks expiration, and injects `Extension<AuthUser>`.
 ///
+/// # Query
+/// - `starred` - optional, `true` for only starred itineraries and `false` for only the others
+///
 /// # Responses
 /// - `200 OK` - JSON body `{ "itineraries": [Itinerary] }` containing user's saved itineraries with eventlist
 /// - `401 UNAUTHORIZED` - When authentication fails (handled in middleware, public error)
@@ -343,7 +332,10 @@ pub async fn insert_event_list(itinerary: Itinerary, pool: &PgPool) -> ApiResult
 	get,
 	path="/saved",
 	summary="Fetch all the saved itineraries from this user",
-	description="Fetches all the itineraries from this user that are marked as saved.",
+	description="Fetches all the itineraries from this user that are marked as saved. With `starred` only the starred itineraries, or only the others, are returned.",
+	params(
+		("starred"=Option<bool>, Query, description="Only starred itineraries when true, only the others when false")
+	),
 	responses(
 		(
 			status=200,
@@ -352,11 +344,11 @@ pub async fn insert_event_list(itinerary: Itinerary, pool: &PgPool) -> ApiResult
 			content_type="application/json",
 			//TODO example
 		),
-		(status=400, description="Bad Request"),
-		(status=401, description="User has an invalid cookie/no cookie"),
+		(status=400, description="Bad Request", body=ErrorBody),
+		(status=401, description="User has an invalid cookie/no cookie", body=ErrorBody),
 		(status=405, description="Method Not Allowed - Must be GET"),
 		(status=408, description="Request Timed Out"),
-		(status=500, description="Internal Server Error")
+		(status=500, description="Internal Server Error", body=ErrorBody)
 	),
 	security(("set-cookie"=[])),
 	tag="Itinerary"
@@ -364,6 +356,7 @@ pub async fn insert_event_list(itinerary: Itinerary, pool: &PgPool) -> ApiResult
 pub async fn api_saved_itineraries(
 	Extension(user): Extension<AuthUser>,
 	Extension(pool): Extension<PgPool>,
+	Query(query): Query<SavedQuery>,
 ) -> ApiResult<Json<SavedResponse>> {
 	debug!(
 		"HANDLER ->> /api/itinerary/saved 'api_saved_itineraries' - User ID: {}",
@@ -380,9 +373,13 @@ pub async fn api_saved_itineraries(
            	end_date,
             chat_session_id,
             title,
-            unassigned_event_ids
-        FROM itineraries WHERE account_id=$1 AND saved=TRUE"#,
-		user.id
+            unassigned_event_ids,
+            starred
+        FROM itineraries
+        WHERE account_id=$1 AND saved=TRUE AND deleted_at IS NULL
+        	AND ($2::BOOLEAN IS NULL OR starred = $2)"#,
+		user.id,
+		query.starred
 	)
 	.fetch_all(&pool)
 	.await
@@ -405,6 +402,7 @@ pub async fn api_saved_itineraries(
 			chat_session_id: itinerary.chat_session_id,
 			title: itinerary.title,
 			unassigned_events: unassigned_events(&unassigned_ids, &pool).await?,
+			starred: itinerary.starred,
 		});
 	}
 
@@ -431,6 +429,9 @@ pub async fn api_saved_itineraries(
 /// curl -X GET http://localhost:3001/api/itinerary/123
 ///   -H "Cookie: auth-token=..."
 /// ```
+///
+/// Notes:
+/// - Each call counts as a view of the itinerary's events for `/api/events/popular`.
 #[utoipa::path(
 	get,
 	path="/saved/{id}",
@@ -444,12 +445,12 @@ pub async fn api_saved_itineraries(
 			content_type="application/json",
 			//TODO example
 		),
-		(status=400, description="Bad Request"),
-		(status=401, description="User has an invalid cookie/no cookie"),
-		(status=404, description="Itinerary not found"),
+		(status=400, description="Bad Request", body=ErrorBody),
+		(status=401, description="User has an invalid cookie/no cookie", body=ErrorBody),
+		(status=404, description="Itinerary not found", body=ErrorBody),
 		(status=405, description="Method Not Allowed - Must be GET"),
 		(status=408, description="Request Timed Out"),
-		(status=500, description="Internal Server Error")
+		(status=500, description="Internal Server Error", body=ErrorBody)
 	),
 	security(("set-cookie"=[])),
 	tag="Itinerary"
@@ -474,326 +475,1650 @@ pub async fn api_get_itinerary(
            	end_date,
             chat_session_id,
             title,
-            unassigned_event_ids
-        FROM itineraries WHERE id = $1 AND (account_id = $2 OR is_public=TRUE)"#,
+            unassigned_event_ids,
+            starred
+        FROM itineraries WHERE id = $1 AND (account_id = $2 OR is_public=TRUE) AND deleted_at IS NULL"#,
 		itinerary_id,
 		user.id
 	)
 	.fetch_optional(&pool)
 	.await
 	.map_err(AppError::from)?
-	.ok_or(AppError::NotFound)?;
+	.ok_or(AppError::ItineraryNotFound)?;
+	let itinerary = full_itinerary(itinerary, &pool).await?;
+
+	// Opening an itinerary counts as a view of its events, for /api/events/popular
+	let event_ids: Vec<i32> = itinerary
+		.event_days
+		.iter()
+		.flat_map(|day| {
+			day.morning_events
+				.iter()
+				.chain(&day.afternoon_events)
+				.chain(&day.evening_events)
+		})
+		.map(|event| event.id)
+		.collect();
+	sqlx::query!(
+		"UPDATE events SET view_count = view_count + 1 WHERE id = ANY($1)",
+		&event_ids
+	)
+	.execute(&pool)
+	.await
+	.map_err(AppError::from)?;
+
+	Ok(Json(itinerary))
+}
 
+/// Fills in the events of an [ItineraryRow] to build the full [Itinerary]
+pub async fn full_itinerary(itinerary: ItineraryRow, pool: &PgPool) -> ApiResult<Itinerary> {
 	let unassigned_ids = itinerary.unassigned_event_ids.unwrap_or_default();
-	Ok(Json(Itinerary {
+	Ok(Itinerary {
 		id: itinerary.id,
 		start_date: itinerary.start_date,
 		end_date: itinerary.end_date,
-		event_days: itinerary_events(
-			itinerary_id,
-			itinerary.start_date,
-			itinerary.end_date,
-			&pool,
-		)
-		.await?,
+		event_days: itinerary_events(itinerary.id, itinerary.start_date, itinerary.end_date, pool)
+			.await?,
 		chat_session_id: itinerary.chat_session_id,
 		title: itinerary.title,
-		unassigned_events: unassigned_events(&unassigned_ids, &pool).await?,
-	}))
+		unassigned_events: unassigned_events(&unassigned_ids, pool).await?,
+		starred: itinerary.starred,
+	})
 }
 
-/// Update an existing or save a new itinerary for the user
+/// Rename an itinerary without saving the rest of it
 ///
 /// # Method
-/// `POST /api/itinerary/save`
+/// `PATCH /api/itinerary/:id/title`
 ///
 /// # Request Body
-/// - [Itinerary]
+/// - [TitleRequest]
 ///
 /// # Responses
-/// - `200 OK` - with body: [SaveResponse]
-/// - `400 BAD_REQUEST` - Request payload contains invalid data (public error)
+/// - `200 OK` - Itinerary renamed successfully
+/// - `400 BAD_REQUEST` - Title is blank or longer than `ITINERARY_TITLE_MAX_LEN` characters (public error)
 /// - `401 UNAUTHORIZED` - When authentication fails (handled in middleware, public error)
+/// - `404 NOT_FOUND` - Itinerary not found or doesn't belong to user (public error)
 /// - `500 INTERNAL_SERVER_ERROR` - Internal error (private)
 ///
 /// # Examples
 /// ```bash
-/// curl -X POST http://localhost:3001/api/itinerary/save
+/// curl -X PATCH http://localhost:3001/api/itinerary/3/title
 ///   -H "Content-Type: application/json"
 ///   -d '{
-///         "id": 3,
-///         "start_date": "2025-07-15",
-///         "end_date": "2025-07-21",
-///         "event_days": [
-///           {
-///             "morning_events": [],
-///             "afternoon_events": [
-///               {
-///                 "id": 4,
-///                 "street_address": "3399 North Rd",
-///                 "postal_code": 12601,
-///                 "city": "Poughkeepsie",
-///                 "event_type": "Park",
-///                 "event_description": "Take a tour of Marist University",
-///                 "event_name": "Marist University"
-///               }
-///             ],
-///             "evening_events": [],
-///             "date": "2025-07-21"
-///           }
-///         ],
-///         "chat_session_id": 4,
-///         "title": "Poughkeepsie 7/15-21 2025"
+///         "title": "Tokyo Spring Break"
 ///       }'
 /// ```
 #[utoipa::path(
-	post,
-	path="/save",
-	summary="Save a new or update an existing itinerary",
-	description="If the itinerary id is already saved for this user, it's updated with the provided values. Otherwise a new one is created.",
+	patch,
+	path="/{id}/title",
+	summary="Rename an itinerary",
+	description="Sets the title of the itinerary. Surrounding whitespace is trimmed.",
 	request_body(
-		content=Itinerary,
+		content=TitleRequest,
 		content_type="application/json",
-		description="The itinerary to save for the user.",
-		//TODO example
+		description="The new title, between 1 and 200 characters.",
+		example=json!({
+			"title": "Tokyo Spring Break"
+		})
 	),
 	responses(
-		(
-			status=200,
-			description="The id of the itinerary that was just saved. It may be the same as the id passed in the request.",
-			body=SaveResponse,
-			content_type="application/json",
-			//TODO example
-		),
-		(status=400, description="Bad Request"),
-		(status=401, description="User has an invalid cookie/no cookie"),
-		(status=405, description="Method Not Allowed - Must be POST"),
+		(status=200, description="Itinerary renamed successfully"),
+		(status=400, description="Bad Request", body=ErrorBody),
+		(status=401, description="User has an invalid cookie/no cookie", body=ErrorBody),
+		(status=404, description="Itinerary not found or doesn't belong to user", body=ErrorBody),
+		(status=405, description="Method Not Allowed - Must be PATCH"),
 		(status=408, description="Request Timed Out"),
-		(status=500, description="Internal Server Error")
+		(status=500, description="Internal Server Error", body=ErrorBody)
 	),
 	security(("set-cookie"=[])),
 	tag="Itinerary"
 )]
-pub async fn api_save(
+pub async fn api_rename_itinerary(
 	Extension(user): Extension<AuthUser>,
 	Extension(pool): Extension<PgPool>,
-	Json(itinerary): Json<Itinerary>,
-) -> ApiResult<Json<SaveResponse>> {
-	// check if itinerary id already exists for this user
-	let id_opt = sqlx::query!(
-		r#"SELECT id FROM itineraries WHERE id=$1 AND account_id=$2"#,
-		itinerary.id,
-		user.id
-	)
-	.fetch_optional(&pool)
-	.await
-	.map_err(AppError::from)?
-	.map(|record| record.id);
-
-	// Extract unassigned event IDs
-	let unassigned_event_ids: Vec<i32> = itinerary.unassigned_events.iter().map(|e| e.id).collect();
-
-	// if it doesn't exist, insert a new one
-	let id = match id_opt {
-		Some(id) => {
-			// UPDATE existing itinerary and set saved=TRUE
-			sqlx::query!(
-				r#"
-				UPDATE itineraries
-				SET start_date = $1, end_date = $2, title = $3, chat_session_id = $4, saved = TRUE, unassigned_event_ids = $7
-				WHERE id = $5 AND account_id = $6;
-				"#,
-				itinerary.start_date,
-				itinerary.end_date,
-				itinerary.title,
-				itinerary.chat_session_id,
-				id,
-				user.id,
-				&unassigned_event_ids
-			)
-			.execute(&pool)
-			.await
-			.map_err(AppError::from)?;
-
-			id
-		}
-		None => {
-			sqlx::query!(
-				r#"
-				INSERT INTO itineraries (account_id, is_public, start_date, end_date, chat_session_id, saved, title, unassigned_event_ids)
-				VALUES ($1, FALSE, $2, $3, $4, TRUE, $5, $6)
-				RETURNING id;
-				"#,
-				user.id,
-				itinerary.start_date,
-				itinerary.end_date,
-				itinerary.chat_session_id,
-				itinerary.title,
-				&unassigned_event_ids
-			)
-			.fetch_one(&pool)
-			.await
-			.map_err(AppError::from)?
-			.id
-		}
-	};
+	Path(itinerary_id): Path<i32>,
+	Json(TitleRequest { title }): Json<TitleRequest>,
+) -> ApiResult<()> {
+	let title = title.trim();
+	if title.is_empty() {
+		return Err(AppError::Validation("Title must not be empty".to_string()));
+	}
+	if title.chars().count() > ITINERARY_TITLE_MAX_LEN {
+		return Err(AppError::Validation(format!(
+			"Title must be {ITINERARY_TITLE_MAX_LEN} characters or less"
+		)));
+	}
 
-	// delete event_list for this itinerary and make a new one
 	sqlx::query!(
 		r#"
-		DELETE FROM event_list
-		WHERE itinerary_id=$1;
+		UPDATE itineraries
+		SET title = $1
+		WHERE id = $2 AND account_id = $3 AND deleted_at IS NULL
+		RETURNING id;
 		"#,
-		id
+		title,
+		itinerary_id,
+		user.id
 	)
-	.execute(&pool)
+	.fetch_optional(&pool)
 	.await
-	.map_err(AppError::from)?;
-
-	insert_event_list(itinerary, &pool).await?;
+	.map_err(AppError::from)?
+	.ok_or(AppError::ItineraryNotFound)?;
 
-	Ok(Json(SaveResponse { id }))
+	Ok(())
 }
 
-/// Unsave an existing itinerary for the user
+/// List the previous versions of an itinerary
 ///
 /// # Method
-/// `POST /api/itinerary/unsave`
-///
-/// # Request Body
-/// - [UnsaveRequest]
+/// `GET /api/itinerary/:id/history`
 ///
 /// # Responses
-/// - `200 OK` - Successfully unsaved itinerary for this user
-/// - `400 BAD_REQUEST` - Request payload contains invalid data (public error)
+/// - `200 OK` - with body: [HistoryResponse]
 /// - `401 UNAUTHORIZED` - When authentication fails (handled in middleware, public error)
 /// - `404 NOT_FOUND` - Itinerary not found or doesn't belong to user (public error)
 /// - `500 INTERNAL_SERVER_ERROR` - Internal error (private)
 ///
 /// # Examples
 /// ```bash
-/// curl -X POST http://localhost:3001/api/itinerary/unsave
-///   -H "Content-Type: application/json"
-///   -d '{
-///         "id": 3
-///       }'
+/// curl -X GET http://localhost:3001/api/itinerary/3/history
+///   -H "Cookie: auth-token=..."
 /// ```
+///
+/// Notes:
+/// - A version is kept each time `/save` or `/restore` replaces the itinerary,
+///   up to `ITINERARY_SNAPSHOT_LIMIT` of them.
 #[utoipa::path(
-	post,
-	path="/unsave",
-	summary="Unsave an existing itinerary",
-	description="Sets the saved field to false for the given itinerary. Verifies the itinerary belongs to the user.",
-	request_body(
-		content=UnsaveRequest,
-		content_type="application/json",
-		description="The itinerary id to unsave."
-	),
+	get,
+	path="/{id}/history",
+	summary="List previous versions of an itinerary",
+	description="Lists the versions of the itinerary that were replaced by a save, newest first. Any of them can be passed to `/{id}/restore/{snapshot_id}`.",
 	responses(
-		(status=200, description="Successfully unsaved itinerary"),
-		(status=400, description="Bad Request"),
-		(status=401, description="User has an invalid cookie/no cookie"),
-		(status=404, description="Itinerary not found or doesn't belong to user"),
-		(status=405, description="Method Not Allowed - Must be POST"),
+		(
+			status=200,
+			description="Previous versions of the itinerary",
+			body=HistoryResponse,
+			content_type="application/json",
+			example=json!({
+				"snapshots": [
+					{
+						"id": 12,
+						"saved_at": "2025-11-05T14:03:10Z",
+						"event_count": 6
+					}
+				]
+			})
+		),
+		(status=400, description="Bad Request", body=ErrorBody),
+		(status=401, description="User has an invalid cookie/no cookie", body=ErrorBody),
+		(status=404, description="Itinerary not found or doesn't belong to user", body=ErrorBody),
+		(status=405, description="Method Not Allowed - Must be GET"),
 		(status=408, description="Request Timed Out"),
-		(status=500, description="Internal Server Error")
+		(status=500, description="Internal Server Error", body=ErrorBody)
 	),
 	security(("set-cookie"=[])),
 	tag="Itinerary"
 )]
-pub async fn api_unsave(
+pub async fn api_history(
 	Extension(user): Extension<AuthUser>,
 	Extension(pool): Extension<PgPool>,
-	Json(UnsaveRequest { id }): Json<UnsaveRequest>,
-) -> ApiResult<()> {
-	// Update the itinerary to set saved=FALSE
+	Path(itinerary_id): Path<i32>,
+) -> ApiResult<Json<HistoryResponse>> {
 	sqlx::query!(
-		r#"
-		UPDATE itineraries
-		SET saved = FALSE
-		WHERE id = $1 AND account_id = $2
-		RETURNING id;
-		"#,
-		id,
+		r#"SELECT id FROM itineraries WHERE id=$1 AND account_id=$2 AND deleted_at IS NULL"#,
+		itinerary_id,
 		user.id
 	)
 	.fetch_optional(&pool)
 	.await
 	.map_err(AppError::from)?
-	.ok_or(AppError::NotFound)?;
+	.ok_or(AppError::ItineraryNotFound)?;
 
-	Ok(())
+	let snapshots = sqlx::query!(
+		r#"
+		SELECT id, snapshot, saved_at
+		FROM itinerary_snapshots
+		WHERE itinerary_id = $1
+		ORDER BY id DESC;
+		"#,
+		itinerary_id
+	)
+	.fetch_all(&pool)
+	.await
+	.map_err(AppError::from)?
+	.into_iter()
+	.map(|row| {
+		let snapshot: Itinerary = serde_json::from_value(row.snapshot)?;
+		Ok(SnapshotInfo {
+			id: row.id,
+			saved_at: row.saved_at,
+			event_count: snapshot
+				.event_days
+				.iter()
+				.map(|day| {
+					day.morning_events.len() + day.afternoon_events.len() + day.evening_events.len()
+				})
+				.sum::<usize>()
+				+ snapshot.unassigned_events.len(),
+		})
+	})
+	.collect::<ApiResult<Vec<_>>>()?;
+
+	Ok(Json(HistoryResponse { snapshots }))
 }
 
-/// Insert or update a user-created custom event
+/// Summarize an itinerary's events
 ///
 /// # Method
-/// `POST /api/itinerary/userEvent`
-///
-/// # Request Body
-/// - [UserEventRequest]
+/// `GET /api/itinerary/:id/stats`
 ///
 /// # Responses
-/// - `200 OK` - with body: [UserEventResponse] - event id that was just inserted or updated
-/// - `400 BAD_REQUEST` - Request payload contains invalid data (public error)
+/// - `200 OK` - with body: [ItineraryStats]
 /// - `401 UNAUTHORIZED` - When authentication fails (handled in middleware, public error)
-/// - `404 NOT_FOUND` - The provided event id does not belong to the user or does not exist (public error)
+/// - `404 NOT_FOUND` - Itinerary not found or doesn't belong to user (public error)
 /// - `500 INTERNAL_SERVER_ERROR` - Internal error (private)
 ///
 /// # Examples
 /// ```bash
-/// curl -X POST http://localhost:3001/api/itinerary/userEvent
-///   -H "Content-Type: application/json"
-///   -d '{
-///         "event_name": "Custom Event",
-///	        "event_description": "I want to do something and it's easier to make a custom event than to tell the LLM exactly how I want it."
-///       }'
+/// curl -X GET http://localhost:3001/api/itinerary/3/stats
+///   -H "Cookie: auth-token=..."
 /// ```
+///
+/// Notes:
+/// - Distance is the straight-line distance between consecutive events, not a route.
+/// - Costs are rough ranges from each event's `price_level`.
 #[utoipa::path(
-	post,
-	path="/userEvent",
-	summary="Insert or update a user-created custom event",
-	description="Insert a new or updates an existing user-created event with the values passed in the request, returning the event id.",
-	request_body(
-		content=UserEventRequest,
-		content_type="application/json",
-		description="If id is provided, the event will be updated, otherwise it is inserted. The event name is required.",
-		example=json!({
-			"event_name": "Custom Event",
-			"event_description": "I want to do something and it's easier to make a custom event than to tell the LLM exactly how I want it."
-		})
-	),
+	get,
+	path="/{id}/stats",
+	summary="Summarize an itinerary",
+	description="Counts the scheduled events and cities in the itinerary, and estimates the distance travelled between events and the total cost.",
 	responses(
 		(
 			status=200,
-			description="Contains the id of the event that was just inserted or updated.",
-			body=UserEventResponse,
+			description="Statistics of the itinerary",
+			body=ItineraryStats,
 			content_type="application/json",
 			example=json!({
-				"id": 43
+				"total_events": 7,
+				"unique_cities": 2,
+				"estimated_distance_km": 12.4,
+				"estimated_cost_low_usd": 95,
+				"estimated_cost_high_usd": 415,
+				"event_type_counts": {
+					"Museums": 4,
+					"Restaurants": 3
+				}
 			})
 		),
-		(status=400, description="Bad Request"),
-		(status=401, description="User has an invalid cookie/no cookie"),
-		(status=404, description="User-event not found for this user"),
-		(status=405, description="Method Not Allowed - Must be POST"),
+		(status=400, description="Bad Request", body=ErrorBody),
+		(status=401, description="User has an invalid cookie/no cookie", body=ErrorBody),
+		(status=404, description="Itinerary not found or doesn't belong to user", body=ErrorBody),
+		(status=405, description="Method Not Allowed - Must be GET"),
 		(status=408, description="Request Timed Out"),
-		(status=500, description="Internal Server Error")
+		(status=500, description="Internal Server Error", body=ErrorBody)
 	),
 	security(("set-cookie"=[])),
 	tag="Itinerary"
 )]
-pub async fn api_user_event(
+pub async fn api_itinerary_stats(
 	Extension(user): Extension<AuthUser>,
 	Extension(pool): Extension<PgPool>,
-	Json(event): Json<UserEventRequest>,
-) -> ApiResult<Json<UserEventResponse>> {
-	if event.event_name.is_empty() {
-		return Err(AppError::BadRequest(String::from(
-			"Event name must not be empty",
-		)));
-	}
-	let id = if let Some(id) = event.id {
-		sqlx::query!(
+	Path(itinerary_id): Path<i32>,
+) -> ApiResult<Json<ItineraryStats>> {
+	let itinerary = sqlx::query!(
+		r#"SELECT start_date, end_date FROM itineraries WHERE id=$1 AND account_id=$2 AND deleted_at IS NULL"#,
+		itinerary_id,
+		user.id
+	)
+	.fetch_optional(&pool)
+	.await
+	.map_err(AppError::from)?
+	.ok_or(AppError::ItineraryNotFound)?;
+
+	let event_days = itinerary_events(
+		itinerary_id,
+		itinerary.start_date,
+		itinerary.end_date,
+		&pool,
+	)
+	.await?;
+
+	Ok(Json(ItineraryStats::from_event_days(&event_days)))
+}
+
+/// Restore a previous version of an itinerary
+///
+/// # Method
+/// `POST /api/itinerary/:id/restore/:snapshot_id`
+///
+/// # Responses
+/// - `200 OK` - with body: [SaveResponse]
+/// - `401 UNAUTHORIZED` - When authentication fails (handled in middleware, public error)
+/// - `404 NOT_FOUND` - Snapshot not found for this itinerary, or the itinerary doesn't belong to user (public error)
+/// - `500 INTERNAL_SERVER_ERROR` - Internal error (private)
+///
+/// # Examples
+/// ```bash
+/// curl -X POST http://localhost:3001/api/itinerary/3/restore/12
+///   -H "Cookie: auth-token=..."
+/// ```
+///
+/// Notes:
+/// - The restore is saved like any other save, so the version it replaces can be restored too.
+/// - Events deleted since the snapshot, like removed user-created events, are left out.
+#[utoipa::path(
+	post,
+	path="/{id}/restore/{snapshot_id}",
+	summary="Restore a previous version of an itinerary",
+	description="Saves the itinerary as it was in the snapshot from `/{id}/history`.",
+	responses(
+		(
+			status=200,
+			description="The id of the restored itinerary",
+			body=SaveResponse,
+			content_type="application/json",
+			example=json!({
+				"id": 3
+			})
+		),
+		(status=400, description="Bad Request", body=ErrorBody),
+		(status=401, description="User has an invalid cookie/no cookie", body=ErrorBody),
+		(status=404, description="Snapshot not found for this itinerary", body=ErrorBody),
+		(status=405, description="Method Not Allowed - Must be POST"),
+		(status=408, description="Request Timed Out"),
+		(status=500, description="Internal Server Error", body=ErrorBody)
+	),
+	security(("set-cookie"=[])),
+	tag="Itinerary"
+)]
+pub async fn api_restore(
+	Extension(user): Extension<AuthUser>,
+	Extension(pool): Extension<PgPool>,
+	Path((itinerary_id, snapshot_id)): Path<(i32, i32)>,
+) -> ApiResult<Json<SaveResponse>> {
+	let snapshot = sqlx::query!(
+		r#"
+		SELECT s.snapshot
+		FROM itinerary_snapshots s
+		JOIN itineraries i ON i.id = s.itinerary_id
+		WHERE s.id = $1 AND s.itinerary_id = $2 AND i.account_id = $3 AND i.deleted_at IS NULL;
+		"#,
+		snapshot_id,
+		itinerary_id,
+		user.id
+	)
+	.fetch_optional(&pool)
+	.await
+	.map_err(AppError::from)?
+	.ok_or(AppError::NotFound)?
+	.snapshot;
+	let mut itinerary: Itinerary = serde_json::from_value(snapshot)?;
+	itinerary.id = itinerary_id;
+
+	// events deleted since the snapshot can't go back in the event list
+	let event_ids: Vec<i32> = itinerary
+		.event_days
+		.iter()
+		.flat_map(|day| {
+			day.morning_events
+				.iter()
+				.chain(&day.afternoon_events)
+				.chain(&day.evening_events)
+		})
+		.chain(&itinerary.unassigned_events)
+		.map(|event| event.id)
+		.collect();
+	let existing: std::collections::HashSet<i32> =
+		sqlx::query_scalar!(r#"SELECT id FROM events WHERE id = ANY($1)"#, &event_ids)
+			.fetch_all(&pool)
+			.await
+			.map_err(AppError::from)?
+			.into_iter()
+			.collect();
+	for day in itinerary.event_days.iter_mut() {
+		day.morning_events
+			.retain(|event| existing.contains(&event.id));
+		day.afternoon_events
+			.retain(|event| existing.contains(&event.id));
+		day.evening_events
+			.retain(|event| existing.contains(&event.id));
+	}
+	itinerary
+		.unassigned_events
+		.retain(|event| existing.contains(&event.id));
+
+	let id = save_itinerary(user.id, itinerary, &pool).await?;
+	Ok(Json(SaveResponse { id }))
+}
+
+/// Restore an itinerary that was deleted along with its chat
+///
+/// # Method
+/// `POST /api/itinerary/:id/restore`
+///
+/// # Responses
+/// - `200 OK` - with body: [SaveResponse]
+/// - `401 UNAUTHORIZED` - When authentication fails (handled in middleware, public error)
+/// - `404 NOT_FOUND` - Deleted itinerary not found or doesn't belong to user (public error)
+/// - `500 INTERNAL_SERVER_ERROR` - Internal error (private)
+///
+/// # Examples
+/// ```bash
+/// curl -X POST http://localhost:3001/api/itinerary/3/restore
+///   -H "Cookie: auth-token=..."
+/// ```
+///
+/// Notes:
+/// - Deleted itineraries are kept for `ITINERARY_RETENTION_DAYS` before they're permanently deleted.
+/// - The restored itinerary is saved, since the chat it came from is gone.
+#[utoipa::path(
+	post,
+	path="/{id}/restore",
+	summary="Restore a deleted itinerary",
+	description="Undoes deleting an itinerary with its chat, as long as it hasn't been permanently deleted yet.",
+	responses(
+		(
+			status=200,
+			description="The id of the restored itinerary",
+			body=SaveResponse,
+			content_type="application/json",
+			example=json!({
+				"id": 3
+			})
+		),
+		(status=400, description="Bad Request", body=ErrorBody),
+		(status=401, description="User has an invalid cookie/no cookie", body=ErrorBody),
+		(status=404, description="Deleted itinerary not found for this user", body=ErrorBody),
+		(status=405, description="Method Not Allowed - Must be POST"),
+		(status=408, description="Request Timed Out"),
+		(status=500, description="Internal Server Error", body=ErrorBody)
+	),
+	security(("set-cookie"=[])),
+	tag="Itinerary"
+)]
+pub async fn api_restore_deleted(
+	Extension(user): Extension<AuthUser>,
+	Extension(pool): Extension<PgPool>,
+	Path(itinerary_id): Path<i32>,
+) -> ApiResult<Json<SaveResponse>> {
+	let id = sqlx::query!(
+		r#"
+		UPDATE itineraries
+		SET deleted_at = NULL, saved = TRUE
+		WHERE id = $1 AND account_id = $2 AND deleted_at IS NOT NULL
+		RETURNING id;
+		"#,
+		itinerary_id,
+		user.id
+	)
+	.fetch_optional(&pool)
+	.await
+	.map_err(AppError::from)?
+	.ok_or(AppError::ItineraryNotFound)?
+	.id;
+
+	Ok(Json(SaveResponse { id }))
+}
+
+/// Make an itinerary public and get a link to share it
+///
+/// # Method
+/// `POST /api/itinerary/:id/share`
+///
+/// # Responses
+/// - `200 OK` - with body: [ShareResponse]
+/// - `401 UNAUTHORIZED` - When authentication fails (handled in middleware, public error)
+/// - `404 NOT_FOUND` - Itinerary not found or doesn't belong to user (public error)
+/// - `500 INTERNAL_SERVER_ERROR` - Internal error (private)
+///
+/// # Examples
+/// ```bash
+/// curl -X POST http://localhost:3001/api/itinerary/3/share
+///   -H "Cookie: auth-token=..."
+/// ```
+///
+/// Notes:
+/// - Sharing an itinerary that's already shared returns the same link.
+/// - The owner gets a share notification with the link, if they opted into them.
+#[utoipa::path(
+	post,
+	path="/{id}/share",
+	summary="Share an itinerary",
+	description="Makes the itinerary public and returns a link that shows it to anyone, even if they aren't logged in.",
+	responses(
+		(
+			status=200,
+			description="Link to the shared itinerary",
+			body=ShareResponse,
+			content_type="application/json",
+			example=json!({
+				"share_url": "http://localhost:3001/api/itinerary/shared/0b5d6f1e-5c4a-4a8e-9d57-6f1f2f0f4b7a"
+			})
+		),
+		(status=400, description="Bad Request", body=ErrorBody),
+		(status=401, description="User has an invalid cookie/no cookie", body=ErrorBody),
+		(status=404, description="Itinerary not found or doesn't belong to user", body=ErrorBody),
+		(status=405, description="Method Not Allowed - Must be POST"),
+		(status=408, description="Request Timed Out"),
+		(status=500, description="Internal Server Error", body=ErrorBody)
+	),
+	security(("set-cookie"=[])),
+	tag="Itinerary"
+)]
+pub async fn api_share(
+	Extension(user): Extension<AuthUser>,
+	Extension(pool): Extension<PgPool>,
+	Extension(notifier): Extension<SharedNotifier>,
+	Path(itinerary_id): Path<i32>,
+) -> ApiResult<Json<ShareResponse>> {
+	let shared = sqlx::query!(
+		r#"
+		UPDATE itineraries
+		SET is_public = TRUE
+		WHERE id = $1 AND account_id = $2 AND deleted_at IS NULL
+		RETURNING share_token, title;
+		"#,
+		itinerary_id,
+		user.id
+	)
+	.fetch_optional(&pool)
+	.await
+	.map_err(AppError::from)?
+	.ok_or(AppError::ItineraryNotFound)?;
+
+	let share_url = format!(
+		"{}/api/itinerary/shared/{}",
+		std::env::var("API_BASE_URL").unwrap_or_default(),
+		shared.share_token
+	);
+
+	// The itinerary is shared either way, so a failed notification is only logged
+	if let Err(e) = notify_account(
+		&pool,
+		&notifier,
+		user.id,
+		NotificationKind::Share,
+		&format!("\"{}\" is shared at {}", shared.title, share_url),
+	)
+	.await
+	{
+		error!("Failed to send share notification for itinerary {itinerary_id}: {e}");
+	}
+
+	Ok(Json(ShareResponse { share_url }))
+}
+
+/// Stop sharing an itinerary
+///
+/// # Method
+/// `DELETE /api/itinerary/:id/share`
+///
+/// # Responses
+/// - `200 OK` - Itinerary is no longer shared
+/// - `401 UNAUTHORIZED` - When authentication fails (handled in middleware, public error)
+/// - `404 NOT_FOUND` - Itinerary not found or doesn't belong to user (public error)
+/// - `500 INTERNAL_SERVER_ERROR` - Internal error (private)
+///
+/// # Examples
+/// ```bash
+/// curl -X DELETE http://localhost:3001/api/itinerary/3/share
+///   -H "Cookie: auth-token=..."
+/// ```
+///
+/// Notes:
+/// - A new share token is generated so old links stop working, even if it's shared again.
+#[utoipa::path(
+	delete,
+	path="/{id}/share",
+	summary="Stop sharing an itinerary",
+	description="Makes the itinerary private again and invalidates any link previously returned by `/{id}/share`.",
+	responses(
+		(status=200, description="Itinerary is no longer shared"),
+		(status=400, description="Bad Request", body=ErrorBody),
+		(status=401, description="User has an invalid cookie/no cookie", body=ErrorBody),
+		(status=404, description="Itinerary not found or doesn't belong to user", body=ErrorBody),
+		(status=405, description="Method Not Allowed - Must be DELETE"),
+		(status=408, description="Request Timed Out"),
+		(status=500, description="Internal Server Error", body=ErrorBody)
+	),
+	security(("set-cookie"=[])),
+	tag="Itinerary"
+)]
+pub async fn api_unshare(
+	Extension(user): Extension<AuthUser>,
+	Extension(pool): Extension<PgPool>,
+	Path(itinerary_id): Path<i32>,
+) -> ApiResult<()> {
+	sqlx::query!(
+		r#"
+		UPDATE itineraries
+		SET
+			is_public = FALSE,
+			share_token = gen_random_uuid()
+		WHERE id = $1 AND account_id = $2 AND deleted_at IS NULL
+		RETURNING id;
+		"#,
+		itinerary_id,
+		user.id
+	)
+	.fetch_optional(&pool)
+	.await
+	.map_err(AppError::from)?
+	.ok_or(AppError::ItineraryNotFound)?;
+
+	Ok(())
+}
+
+/// Star an itinerary for quick access
+///
+/// # Method
+/// `POST /api/itinerary/:id/star`
+///
+/// # Responses
+/// - `200 OK` - Itinerary is starred
+/// - `401 UNAUTHORIZED` - When authentication fails (handled in middleware, public error)
+/// - `404 NOT_FOUND` - Itinerary not found or doesn't belong to user (public error)
+/// - `500 INTERNAL_SERVER_ERROR` - Internal error (private)
+///
+/// # Examples
+/// ```bash
+/// curl -X POST http://localhost:3001/api/itinerary/3/star
+///   -H "Cookie: auth-token=..."
+/// ```
+#[utoipa::path(
+	post,
+	path="/{id}/star",
+	summary="Star an itinerary",
+	description="Marks the itinerary as starred, so it can be found with `/saved?starred=true`. Starring a starred itinerary does nothing.",
+	responses(
+		(status=200, description="Itinerary is starred"),
+		(status=400, description="Bad Request", body=ErrorBody),
+		(status=401, description="User has an invalid cookie/no cookie", body=ErrorBody),
+		(status=404, description="Itinerary not found or doesn't belong to user", body=ErrorBody),
+		(status=405, description="Method Not Allowed - Must be POST"),
+		(status=408, description="Request Timed Out"),
+		(status=500, description="Internal Server Error", body=ErrorBody)
+	),
+	security(("set-cookie"=[])),
+	tag="Itinerary"
+)]
+pub async fn api_star(
+	Extension(user): Extension<AuthUser>,
+	Extension(pool): Extension<PgPool>,
+	Path(itinerary_id): Path<i32>,
+) -> ApiResult<()> {
+	set_starred(&pool, user.id, itinerary_id, true).await
+}
+
+/// Unstar an itinerary
+///
+/// # Method
+/// `DELETE /api/itinerary/:id/star`
+///
+/// # Responses
+/// - `200 OK` - Itinerary is no longer starred
+/// - `401 UNAUTHORIZED` - When authentication fails (handled in middleware, public error)
+/// - `404 NOT_FOUND` - Itinerary not found or doesn't belong to user (public error)
+/// - `500 INTERNAL_SERVER_ERROR` - Internal error (private)
+///
+/// # Examples
+/// ```bash
+/// curl -X DELETE http://localhost:3001/api/itinerary/3/star
+///   -H "Cookie: auth-token=..."
+/// ```
+#[utoipa::path(
+	delete,
+	path="/{id}/star",
+	summary="Unstar an itinerary",
+	description="Removes the itinerary's star. Unstarring an itinerary that isn't starred does nothing.",
+	responses(
+		(status=200, description="Itinerary is no longer starred"),
+		(status=400, description="Bad Request", body=ErrorBody),
+		(status=401, description="User has an invalid cookie/no cookie", body=ErrorBody),
+		(status=404, description="Itinerary not found or doesn't belong to user", body=ErrorBody),
+		(status=405, description="Method Not Allowed - Must be DELETE"),
+		(status=408, description="Request Timed Out"),
+		(status=500, description="Internal Server Error", body=ErrorBody)
+	),
+	security(("set-cookie"=[])),
+	tag="Itinerary"
+)]
+pub async fn api_unstar(
+	Extension(user): Extension<AuthUser>,
+	Extension(pool): Extension<PgPool>,
+	Path(itinerary_id): Path<i32>,
+) -> ApiResult<()> {
+	set_starred(&pool, user.id, itinerary_id, false).await
+}
+
+/// Stars or unstars the user's itinerary, [AppError::ItineraryNotFound] if they don't have it
+async fn set_starred(
+	pool: &PgPool,
+	account_id: i32,
+	itinerary_id: i32,
+	starred: bool,
+) -> ApiResult<()> {
+	sqlx::query!(
+		r#"
+		UPDATE itineraries
+		SET starred = $1
+		WHERE id = $2 AND account_id = $3 AND deleted_at IS NULL
+		RETURNING id;
+		"#,
+		starred,
+		itinerary_id,
+		account_id
+	)
+	.fetch_optional(pool)
+	.await
+	.map_err(AppError::from)?
+	.ok_or(AppError::ItineraryNotFound)?;
+
+	Ok(())
+}
+
+/// Get an itinerary through its share link
+///
+/// # Method
+/// `GET /api/itinerary/shared/:token`
+///
+/// # Auth
+/// None, the share token is enough to view the itinerary.
+///
+/// # Responses
+/// - `200 OK` - with body: [Itinerary]
+/// - `400 BAD_REQUEST` - The token isn't a valid UUID (public error)
+/// - `404 NOT_FOUND` - No shared itinerary has this token (public error)
+/// - `500 INTERNAL_SERVER_ERROR` - Internal error (private)
+///
+/// # Examples
+/// ```bash
+/// curl -X GET http://localhost:3001/api/itinerary/shared/0b5d6f1e-5c4a-4a8e-9d57-6f1f2f0f4b7a
+/// ```
+#[utoipa::path(
+	get,
+	path="/shared/{token}",
+	summary="Fetch a shared itinerary",
+	description="Fetches the itinerary with this share token if it's still shared. Doesn't require logging in.",
+	responses(
+		(
+			status=200,
+			description="The shared itinerary",
+			body=Itinerary,
+			content_type="application/json",
+		),
+		(status=400, description="Bad Request", body=ErrorBody),
+		(status=404, description="Itinerary not found or no longer shared", body=ErrorBody),
+		(status=405, description="Method Not Allowed - Must be GET"),
+		(status=408, description="Request Timed Out"),
+		(status=500, description="Internal Server Error", body=ErrorBody)
+	),
+	security(()),
+	tag="Itinerary"
+)]
+pub async fn api_shared_itinerary(
+	Extension(pool): Extension<PgPool>,
+	Path(share_token): Path<Uuid>,
+) -> ApiResult<Json<Itinerary>> {
+	let itinerary: ItineraryRow = sqlx::query_as!(
+		ItineraryRow,
+		r#"SELECT
+			id,
+			account_id,
+			start_date,
+			end_date,
+			chat_session_id,
+			title,
+			unassigned_event_ids,
+			starred
+		FROM itineraries WHERE share_token = $1 AND is_public = TRUE AND deleted_at IS NULL"#,
+		share_token
+	)
+	.fetch_optional(&pool)
+	.await
+	.map_err(AppError::from)?
+	.ok_or(AppError::ItineraryNotFound)?;
+
+	Ok(Json(full_itinerary(itinerary, &pool).await?))
+}
+
+/// Browse other users' public itineraries
+///
+/// # Method
+/// `GET /api/itinerary/browse?destination=Tokyo&page=0&per_page=20`
+///
+/// # Responses
+/// - `200 OK` - with body: [BrowseResponse]
+/// - `400 BAD_REQUEST` - `page` or `per_page` isn't a non-negative integer (public error)
+/// - `401 UNAUTHORIZED` - When authentication fails (handled in middleware, public error)
+/// - `500 INTERNAL_SERVER_ERROR` - Internal error (private)
+///
+/// # Examples
+/// ```bash
+/// curl -X GET "http://localhost:3001/api/itinerary/browse?destination=Tokyo&page=0&per_page=20"
+///   -H "Cookie: auth-token=..."
+/// ```
+///
+/// Notes:
+/// - Requires logging in so public itineraries can't be scraped anonymously.
+/// - Only the author's first name is returned, nothing else about them.
+#[utoipa::path(
+	get,
+	path="/browse",
+	summary="Browse public itineraries",
+	description="Lists public itineraries, newest first. `destination` matches the itinerary's title or the cities of its events, ignoring case.",
+	params(
+		("destination"=Option<String>, Query, description="Only itineraries whose title or event cities contain this"),
+		("page"=Option<u32>, Query, description="Page to return, starting at 0"),
+		("per_page"=Option<u32>, Query, description="Itineraries per page, 20 by default and at most 50")
+	),
+	responses(
+		(
+			status=200,
+			description="A page of public itineraries",
+			body=BrowseResponse,
+			content_type="application/json",
+			example=json!({
+				"itineraries": [
+					{
+						"id": 12,
+						"title": "Tokyo in Spring",
+						"start_date": "2026-04-01",
+						"end_date": "2026-04-05",
+						"event_count": 11,
+						"author_first_name": "Aiko"
+					}
+				],
+				"total_count": 1
+			})
+		),
+		(status=400, description="Bad Request", body=ErrorBody),
+		(status=401, description="User has an invalid cookie/no cookie", body=ErrorBody),
+		(status=405, description="Method Not Allowed - Must be GET"),
+		(status=408, description="Request Timed Out"),
+		(status=500, description="Internal Server Error", body=ErrorBody)
+	),
+	security(("set-cookie"=[])),
+	tag="Itinerary"
+)]
+pub async fn api_browse(
+	Extension(user): Extension<AuthUser>,
+	Extension(pool): Extension<PgPool>,
+	Query(query): Query<BrowseQuery>,
+) -> ApiResult<Json<BrowseResponse>> {
+	debug!(
+		"HANDLER ->> /api/itinerary/browse 'api_browse' - User ID: {} Query: {:?}",
+		user.id, query
+	);
+	let destination = query
+		.destination
+		.as_deref()
+		.map(str::trim)
+		.filter(|d| !d.is_empty());
+	let per_page = query
+		.per_page
+		.unwrap_or(ITINERARY_BROWSE_PAGE_LEN)
+		.clamp(1, ITINERARY_BROWSE_PAGE_MAX) as i64;
+	let offset = query.page as i64 * per_page;
+
+	let total_count = sqlx::query_scalar!(
+		r#"
+		SELECT COUNT(*) AS "count!"
+		FROM itineraries i
+		WHERE
+			i.is_public = TRUE AND
+			i.deleted_at IS NULL AND
+			(
+				$1::text IS NULL OR
+				i.title ILIKE '%' || $1 || '%' OR
+				EXISTS (
+					SELECT 1 FROM event_list el
+					JOIN events e ON e.id = el.event_id
+					WHERE el.itinerary_id = i.id AND e.city ILIKE '%' || $1 || '%'
+				)
+			)
+		"#,
+		destination
+	)
+	.fetch_one(&pool)
+	.await
+	.map_err(AppError::from)?;
+
+	let itineraries = sqlx::query_as!(
+		ItinerarySummary,
+		r#"
+		SELECT
+			i.id,
+			i.title,
+			i.start_date,
+			i.end_date,
+			(
+				(SELECT COUNT(*) FROM event_list el WHERE el.itinerary_id = i.id AND el.event_id IS NOT NULL)
+				+ CARDINALITY(i.unassigned_event_ids)
+			) AS "event_count!",
+			a.first_name AS author_first_name
+		FROM itineraries i
+		JOIN accounts a ON a.id = i.account_id
+		WHERE
+			i.is_public = TRUE AND
+			i.deleted_at IS NULL AND
+			(
+				$1::text IS NULL OR
+				i.title ILIKE '%' || $1 || '%' OR
+				EXISTS (
+					SELECT 1 FROM event_list el
+					JOIN events e ON e.id = el.event_id
+					WHERE el.itinerary_id = i.id AND e.city ILIKE '%' || $1 || '%'
+				)
+			)
+		ORDER BY i.id DESC
+		LIMIT $2 OFFSET $3
+		"#,
+		destination,
+		per_page,
+		offset
+	)
+	.fetch_all(&pool)
+	.await
+	.map_err(AppError::from)?;
+
+	Ok(Json(BrowseResponse {
+		itineraries,
+		total_count,
+	}))
+}
+
+/// Copy a public itinerary to the user's account
+///
+/// # Method
+/// `POST /api/itinerary/:id/clone`
+///
+/// # Responses
+/// - `200 OK` - with body: [SaveResponse]
+/// - `401 UNAUTHORIZED` - When authentication fails (handled in middleware, public error)
+/// - `404 NOT_FOUND` - Itinerary not found, or it's private and doesn't belong to user (public error)
+/// - `500 INTERNAL_SERVER_ERROR` - Internal error (private)
+///
+/// # Examples
+/// ```bash
+/// curl -X POST http://localhost:3001/api/itinerary/12/clone
+///   -H "Cookie: auth-token=..."
+/// ```
+///
+/// Notes:
+/// - The copy is saved, private, and titled "Copy of " followed by the original title.
+/// - The copy isn't linked to a chat session.
+#[utoipa::path(
+	post,
+	path="/{id}/clone",
+	summary="Copy an itinerary",
+	description="Saves a private copy of a public itinerary, or one of the user's own, to the user's account.",
+	responses(
+		(
+			status=200,
+			description="The id of the new copy",
+			body=SaveResponse,
+			content_type="application/json",
+			example=json!({
+				"id": 27
+			})
+		),
+		(status=400, description="Bad Request", body=ErrorBody),
+		(status=401, description="User has an invalid cookie/no cookie", body=ErrorBody),
+		(status=404, description="Itinerary not found or not public", body=ErrorBody),
+		(status=405, description="Method Not Allowed - Must be POST"),
+		(status=408, description="Request Timed Out"),
+		(status=500, description="Internal Server Error", body=ErrorBody)
+	),
+	security(("set-cookie"=[])),
+	tag="Itinerary"
+)]
+pub async fn api_clone_itinerary(
+	Extension(user): Extension<AuthUser>,
+	Extension(pool): Extension<PgPool>,
+	Path(itinerary_id): Path<i32>,
+) -> ApiResult<Json<SaveResponse>> {
+	debug!(
+		"HANDLER ->> /api/itinerary/{}/clone 'api_clone_itinerary' - User ID: {}",
+		itinerary_id, user.id
+	);
+
+	let mut tx = pool.begin().await.map_err(AppError::from)?;
+
+	let id = sqlx::query!(
+		r#"
+		INSERT INTO itineraries (account_id, is_public, start_date, end_date, saved, title, unassigned_event_ids)
+		SELECT $2, FALSE, start_date, end_date, TRUE, LEFT('Copy of ' || title, 255), unassigned_event_ids
+		FROM itineraries
+		WHERE id = $1 AND (is_public = TRUE OR account_id = $2) AND deleted_at IS NULL
+		RETURNING id;
+		"#,
+		itinerary_id,
+		user.id
+	)
+	.fetch_optional(&mut *tx)
+	.await
+	.map_err(AppError::from)?
+	.ok_or(AppError::ItineraryNotFound)?
+	.id;
+
+	sqlx::query!(
+		r#"
+		INSERT INTO event_list (itinerary_id, event_id, time_of_day, date, block_index)
+		SELECT $1, event_id, time_of_day, date, block_index
+		FROM event_list
+		WHERE itinerary_id = $2;
+		"#,
+		id,
+		itinerary_id
+	)
+	.execute(&mut *tx)
+	.await
+	.map_err(AppError::from)?;
+
+	tx.commit().await.map_err(AppError::from)?;
+
+	Ok(Json(SaveResponse { id }))
+}
+
+/// Regenerate an itinerary, keeping the events the user liked
+///
+/// # Method
+/// `POST /api/itinerary/:id/regenerate`
+///
+/// # Request Body
+/// - [RegenerateItineraryRequest]
+///
+/// # Responses
+/// - `200 OK` - with body: [Message] - the LLM's reply, with the new itinerary if it made one
+/// - `400 BAD_REQUEST` - The itinerary isn't part of a chat, or a kept event isn't in it (public error)
+/// - `401 UNAUTHORIZED` - When authentication fails (handled in middleware, public error)
+/// - `403 FORBIDDEN` - with code `EMAIL_NOT_VERIFIED` - The account's email isn't verified (public error)
+/// - `404 NOT_FOUND` - Itinerary not found or doesn't belong to user (public error)
+/// - `409 CONFLICT` - A reply is still being generated in the itinerary's chat (public error)
+/// - `500 INTERNAL_SERVER_ERROR` - Internal error (private)
+/// - `503 SERVICE_UNAVAILABLE` - AI features are disabled, or too many replies are being generated (public error)
+///
+/// # Examples
+/// ```bash
+/// curl -X POST http://localhost:3001/api/itinerary/12/regenerate
+///   -H "Content-Type: application/json"
+///   -d '{
+///         "keep_event_ids": [1, 2, 3]
+///       }'
+/// ```
+///
+/// Notes:
+/// - The events that aren't kept are removed from the itinerary, its days stay.
+/// - The request is sent to the itinerary's chat as a user message, and the kept events are
+///   added to the chat's constraints so the constraint agent never filters them out.
+#[utoipa::path(
+	post,
+	path="/{id}/regenerate",
+	summary="Regenerate an itinerary around kept events",
+	description="Removes every event of the itinerary except the kept ones and asks the LLM, in the itinerary's chat, to regenerate the rest.",
+	request_body(
+		content=RegenerateItineraryRequest,
+		content_type="application/json",
+		description="Events of the itinerary to keep exactly as they are.",
+		example=json!({
+			"keep_event_ids": [1, 2, 3]
+		})
+	),
+	responses(
+		(
+			status=200,
+			description="The new reply from the LLM",
+			body=Message,
+			content_type="application/json",
+			example=json!({
+				"id": 55,
+				"is_user": false,
+				"timestamp": "2025-10-14 11-41-02",
+				"text": "Bot reply",
+				"itinerary_id": 15
+			})
+		),
+		(status=400, description="Bad Request", body=ErrorBody),
+		(status=401, description="User has an invalid cookie/no cookie", body=ErrorBody),
+		(status=403, description="Email not verified", body=ErrorBody),
+		(status=404, description="Itinerary not found for this user", body=ErrorBody),
+		(status=405, description="Method Not Allowed - Must be POST"),
+		(status=408, description="Request Timed Out"),
+		(status=409, description="A reply is still being generated in the itinerary's chat", body=ErrorBody),
+		(status=500, description="Internal Server Error", body=ErrorBody),
+		(status=503, description="AI features are disabled, or the server is busy", body=ErrorBody)
+	),
+	security(("set-cookie"=[])),
+	tag="Itinerary"
+)]
+pub async fn api_regenerate_itinerary(
+	Extension(user): Extension<AuthUser>,
+	Extension(pool): Extension<PgPool>,
+	Extension(agent): Extension<Option<SharedAgentFactory>>,
+	Extension(context_store): Extension<SharedContextStore>,
+	Path(itinerary_id): Path<i32>,
+	Json(RegenerateItineraryRequest { keep_event_ids }): Json<RegenerateItineraryRequest>,
+) -> ApiResult<Json<Message>> {
+	let agent = require_agent(agent)?;
+	require_verified_email(&pool, user.id).await?;
+	let permit = agent_permit().await?;
+
+	let mut tx = pool.begin().await.map_err(AppError::from)?;
+	let itinerary = sqlx::query!(
+		r#"
+		SELECT
+			i.chat_session_id,
+			i.unassigned_event_ids,
+			c.llm_progress AS "llm_progress?: LlmProgress"
+		FROM itineraries i
+		LEFT JOIN chat_sessions c ON c.id = i.chat_session_id
+		WHERE i.id = $1 AND i.account_id = $2 AND i.deleted_at IS NULL;
+		"#,
+		itinerary_id,
+		user.id
+	)
+	.fetch_optional(&mut *tx)
+	.await
+	.map_err(AppError::from)?
+	.ok_or(AppError::ItineraryNotFound)?;
+	let (Some(chat_session_id), Some(progress)) =
+		(itinerary.chat_session_id, itinerary.llm_progress)
+	else {
+		return Err(AppError::BadRequest(String::from(
+			"The itinerary isn't part of a chat",
+		)));
+	};
+	if !matches!(
+		progress,
+		LlmProgress::Ready | LlmProgress::AwaitingUser | LlmProgress::Failed
+	) {
+		return Err(AppError::PipelineBusy(String::from(
+			"A reply is still being generated in this chat",
+		)));
+	}
+
+	let rows = sqlx::query!(
+		"SELECT event_id, date FROM event_list WHERE itinerary_id = $1",
+		itinerary_id
+	)
+	.fetch_all(&mut *tx)
+	.await
+	.map_err(AppError::from)?;
+	if let Some(id) = keep_event_ids.iter().find(|id| {
+		!rows.iter().any(|row| row.event_id == Some(**id))
+			&& !itinerary.unassigned_event_ids.contains(id)
+	}) {
+		return Err(AppError::BadRequest(format!(
+			"Event {id} isn't in the itinerary"
+		)));
+	}
+
+	// Kept events stay in their slots, days left without events get a placeholder
+	sqlx::query!(
+		r#"
+		DELETE FROM event_list
+		WHERE itinerary_id = $1 AND event_id IS NOT NULL AND NOT (event_id = ANY($2));
+		"#,
+		itinerary_id,
+		&keep_event_ids
+	)
+	.execute(&mut *tx)
+	.await
+	.map_err(AppError::from)?;
+	let dates: Vec<NaiveDate> = rows.iter().map(|row| row.date).collect();
+	sqlx::query!(
+		r#"
+		INSERT INTO event_list (itinerary_id, event_id, time_of_day, date)
+		SELECT DISTINCT $1::int4, NULL::int4, $2::time_of_day, d
+		FROM UNNEST($3::date[]) AS d
+		WHERE NOT EXISTS (
+			SELECT 1 FROM event_list WHERE itinerary_id = $1 AND date = d
+		);
+		"#,
+		itinerary_id,
+		TimeOfDay::Morning as TimeOfDay,
+		&dates
+	)
+	.execute(&mut *tx)
+	.await
+	.map_err(AppError::from)?;
+	let unassigned: Vec<i32> = itinerary
+		.unassigned_event_ids
+		.into_iter()
+		.filter(|id| keep_event_ids.contains(id))
+		.collect();
+	sqlx::query!(
+		"UPDATE itineraries SET unassigned_event_ids = $1 WHERE id = $2",
+		&unassigned,
+		itinerary_id
+	)
+	.execute(&mut *tx)
+	.await
+	.map_err(AppError::from)?;
+
+	let text = format!(
+		"Regenerate the itinerary, keeping the following event IDs exactly as they are: {:?}",
+		keep_event_ids
+	);
+	sqlx::query!(
+		r#"
+		INSERT INTO messages (chat_session_id, itinerary_id, is_user, timestamp, text)
+		VALUES ($1, NULL, TRUE, NOW(), $2);
+		"#,
+		chat_session_id,
+		text
+	)
+	.execute(&mut *tx)
+	.await
+	.map_err(AppError::from)?;
+	tx.commit().await.map_err(AppError::from)?;
+	touch_chat_session(&pool, chat_session_id).await?;
+
+	let bot_message = send_message_to_llm(
+		permit,
+		text.as_str(),
+		user.id,
+		chat_session_id,
+		Some(itinerary_id),
+		&keep_event_ids,
+		None,
+		&pool,
+		&agent,
+		&context_store,
+	)
+	.await?
+	.message;
+	notify_new_message(&pool, chat_session_id, bot_message.id).await?;
+
+	Ok(Json(bot_message))
+}
+
+/// Update an existing or save a new itinerary for the user
+///
+/// # Method
+/// `POST /api/itinerary/save`
+///
+/// # Request Body
+/// - [Itinerary]
+///
+/// # Responses
+/// - `200 OK` - with body: [SaveResponse]
+/// - `400 BAD_REQUEST` - Request payload contains invalid data (public error)
+/// - `401 UNAUTHORIZED` - When authentication fails (handled in middleware, public error)
+/// - `500 INTERNAL_SERVER_ERROR` - Internal error (private)
+///
+/// # Examples
+/// ```bash
+/// curl -X POST http://localhost:3001/api/itinerary/save
+///   -H "Content-Type: application/json"
+///   -d '{
+///         "id": 3,
+///         "start_date": "2025-07-15",
+///         "end_date": "2025-07-21",
+///         "event_days": [
+///           {
+///             "morning_events": [],
+///             "afternoon_events": [
+///               {
+///                 "id": 4,
+///                 "street_address": "3399 North Rd",
+///                 "postal_code": 12601,
+///                 "city": "Poughkeepsie",
+///                 "event_type": "Park",
+///                 "event_description": "Take a tour of Marist University",
+///                 "event_name": "Marist University"
+///               }
+///             ],
+///             "evening_events": [],
+///             "date": "2025-07-21"
+///           }
+///         ],
+///         "chat_session_id": 4,
+///         "title": "Poughkeepsie 7/15-21 2025"
+///       }'
+/// ```
+#[utoipa::path(
+	post,
+	path="/save",
+	summary="Save a new or update an existing itinerary",
+	description="If the itinerary id is already saved for this user, it's updated with the provided values. Otherwise a new one is created.",
+	request_body(
+		content=Itinerary,
+		content_type="application/json",
+		description="The itinerary to save for the user.",
+		//TODO example
+	),
+	responses(
+		(
+			status=200,
+			description="The id of the itinerary that was just saved. It may be the same as the id passed in the request.",
+			body=SaveResponse,
+			content_type="application/json",
+			//TODO example
+		),
+		(status=400, description="Bad Request", body=ErrorBody),
+		(status=401, description="User has an invalid cookie/no cookie", body=ErrorBody),
+		(status=405, description="Method Not Allowed - Must be POST"),
+		(status=408, description="Request Timed Out"),
+		(status=500, description="Internal Server Error", body=ErrorBody)
+	),
+	security(("set-cookie"=[])),
+	tag="Itinerary"
+)]
+pub async fn api_save(
+	Extension(user): Extension<AuthUser>,
+	Extension(pool): Extension<PgPool>,
+	Json(itinerary): Json<Itinerary>,
+) -> ApiResult<Json<SaveResponse>> {
+	validate_trip_dates(itinerary.start_date, itinerary.end_date).map_err(AppError::BadRequest)?;
+	let id = save_itinerary(user.id, itinerary, &pool).await?;
+	Ok(Json(SaveResponse { id }))
+}
+
+/// Checks that a trip doesn't end before it starts or last over `MAX_TRIP_DURATION_DAYS`.
+/// Returns the reason the dates are invalid.
+pub fn validate_trip_dates(start_date: NaiveDate, end_date: NaiveDate) -> Result<(), String> {
+	if start_date > end_date {
+		return Err("Start date must not be after end date".to_string());
+	}
+	if (end_date - start_date).num_days() > MAX_TRIP_DURATION_DAYS {
+		return Err(format!(
+			"Trip duration cannot exceed {MAX_TRIP_DURATION_DAYS} days"
+		));
+	}
+	Ok(())
+}
+
+/// Saves the itinerary for this account, updating it if the account already has one with its id.
+/// The version being replaced is kept in `itinerary_snapshots`, see [api_history].
+/// Returns the id of the saved itinerary.
+async fn save_itinerary(
+	account_id: i32,
+	mut itinerary: Itinerary,
+	pool: &PgPool,
+) -> ApiResult<i32> {
+	let mut tx = pool.begin().await.map_err(AppError::from)?;
+
+	// check if itinerary id already exists for this user
+	// locked so concurrent saves don't both snapshot the same version
+	let current = sqlx::query_as!(
+		ItineraryRow,
+		r#"SELECT
+			id,
+			account_id,
+			start_date,
+			end_date,
+			chat_session_id,
+			title,
+			unassigned_event_ids,
+			starred
+		FROM itineraries WHERE id=$1 AND account_id=$2 AND deleted_at IS NULL
+		FOR UPDATE"#,
+		itinerary.id,
+		account_id
+	)
+	.fetch_optional(&mut *tx)
+	.await
+	.map_err(AppError::from)?;
+
+	// Extract unassigned event IDs
+	let unassigned_event_ids: Vec<i32> = itinerary.unassigned_events.iter().map(|e| e.id).collect();
+
+	// if it doesn't exist, insert a new one
+	let id = match current {
+		Some(current) => {
+			let id = current.id;
+
+			// keep the version being replaced, and only the newest ITINERARY_SNAPSHOT_LIMIT of them
+			let snapshot = serde_json::to_value(full_itinerary(current, pool).await?)?;
+			sqlx::query!(
+				r#"
+				INSERT INTO itinerary_snapshots (itinerary_id, snapshot)
+				VALUES ($1, $2);
+				"#,
+				id,
+				snapshot
+			)
+			.execute(&mut *tx)
+			.await
+			.map_err(AppError::from)?;
+			sqlx::query!(
+				r#"
+				DELETE FROM itinerary_snapshots
+				WHERE itinerary_id = $1 AND id NOT IN (
+					SELECT id FROM itinerary_snapshots
+					WHERE itinerary_id = $1
+					ORDER BY id DESC
+					LIMIT $2
+				);
+				"#,
+				id,
+				ITINERARY_SNAPSHOT_LIMIT
+			)
+			.execute(&mut *tx)
+			.await
+			.map_err(AppError::from)?;
+
+			// UPDATE existing itinerary and set saved=TRUE
+			sqlx::query!(
+				r#"
+				UPDATE itineraries
+				SET start_date = $1, end_date = $2, title = $3, chat_session_id = $4, saved = TRUE, unassigned_event_ids = $7
+				WHERE id = $5 AND account_id = $6;
+				"#,
+				itinerary.start_date,
+				itinerary.end_date,
+				itinerary.title,
+				itinerary.chat_session_id,
+				id,
+				account_id,
+				&unassigned_event_ids
+			)
+			.execute(&mut *tx)
+			.await
+			.map_err(AppError::from)?;
+
+			id
+		}
+		None => {
+			sqlx::query!(
+				r#"
+				INSERT INTO itineraries (account_id, is_public, start_date, end_date, chat_session_id, saved, title, unassigned_event_ids)
+				VALUES ($1, FALSE, $2, $3, $4, TRUE, $5, $6)
+				RETURNING id;
+				"#,
+				account_id,
+				itinerary.start_date,
+				itinerary.end_date,
+				itinerary.chat_session_id,
+				itinerary.title,
+				&unassigned_event_ids
+			)
+			.fetch_one(&mut *tx)
+			.await
+			.map_err(AppError::from)?
+			.id
+		}
+	};
+
+	// delete event_list for this itinerary and make a new one
+	sqlx::query!(
+		r#"
+		DELETE FROM event_list
+		WHERE itinerary_id=$1;
+		"#,
+		id
+	)
+	.execute(&mut *tx)
+	.await
+	.map_err(AppError::from)?;
+
+	// new itineraries only get their id from the insert above
+	itinerary.id = id;
+	insert_event_list(itinerary, &mut *tx).await?;
+
+	tx.commit().await.map_err(AppError::from)?;
+
+	Ok(id)
+}
+
+/// Unsave an existing itinerary for the user
+///
+/// # Method
+/// `POST /api/itinerary/unsave`
+///
+/// # Request Body
+/// - [UnsaveRequest]
+///
+/// # Responses
+/// - `200 OK` - Successfully unsaved itinerary for this user
+/// - `400 BAD_REQUEST` - Request payload contains invalid data (public error)
+/// - `401 UNAUTHORIZED` - When authentication fails (handled in middleware, public error)
+/// - `404 NOT_FOUND` - Itinerary not found or doesn't belong to user (public error)
+/// - `500 INTERNAL_SERVER_ERROR` - Internal error (private)
+///
+/// # Examples
+/// ```bash
+/// curl -X POST http://localhost:3001/api/itinerary/unsave
+///   -H "Content-Type: application/json"
+///   -d '{
+///         "id": 3
+///       }'
+/// ```
+#[utoipa::path(
+	post,
+	path="/unsave",
+	summary="Unsave an existing itinerary",
+	description="Sets the saved field to false for the given itinerary. Verifies the itinerary belongs to the user.",
+	request_body(
+		content=UnsaveRequest,
+		content_type="application/json",
+		description="The itinerary id to unsave."
+	),
+	responses(
+		(status=200, description="Successfully unsaved itinerary"),
+		(status=400, description="Bad Request", body=ErrorBody),
+		(status=401, description="User has an invalid cookie/no cookie", body=ErrorBody),
+		(status=404, description="Itinerary not found or doesn't belong to user", body=ErrorBody),
+		(status=405, description="Method Not Allowed - Must be POST"),
+		(status=408, description="Request Timed Out"),
+		(status=500, description="Internal Server Error", body=ErrorBody)
+	),
+	security(("set-cookie"=[])),
+	tag="Itinerary"
+)]
+pub async fn api_unsave(
+	Extension(user): Extension<AuthUser>,
+	Extension(pool): Extension<PgPool>,
+	Json(UnsaveRequest { id }): Json<UnsaveRequest>,
+) -> ApiResult<()> {
+	// Update the itinerary to set saved=FALSE
+	sqlx::query!(
+		r#"
+		UPDATE itineraries
+		SET saved = FALSE
+		WHERE id = $1 AND account_id = $2 AND deleted_at IS NULL
+		RETURNING id;
+		"#,
+		id,
+		user.id
+	)
+	.fetch_optional(&pool)
+	.await
+	.map_err(AppError::from)?
+	.ok_or(AppError::ItineraryNotFound)?;
+
+	Ok(())
+}
+
+/// Insert or update a user-created custom event
+///
+/// # Method
+/// `POST /api/itinerary/userEvent`
+///
+/// # Request Body
+/// - [UserEventRequest]
+///   - Without `lat` and `lng`, they're geocoded from `street_address`, `city` and `country`
+///     when `GEOCODING_ENABLED` is set
+///   - An updated event keeps its coordinates when none are given or found
+///   - An updated event is evicted from the optimizer's [SharedEventCache]
+///
+/// # Responses
+/// - `200 OK` - with body: [UserEventResponse] - event id that was just inserted or updated
+/// - `400 BAD_REQUEST` - Request payload contains invalid data (public error)
+/// - `401 UNAUTHORIZED` - When authentication fails (handled in middleware, public error)
+/// - `404 NOT_FOUND` - The provided event id does not belong to the user or does not exist (public error)
+/// - `500 INTERNAL_SERVER_ERROR` - Internal error (private)
+///
+/// # Examples
+/// ```bash
+/// curl -X POST http://localhost:3001/api/itinerary/userEvent
+///   -H "Content-Type: application/json"
+///   -d '{
+///         "event_name": "Custom Event",
+///	        "event_description": "I want to do something and it's easier to make a custom event than to tell the LLM exactly how I want it."
+///       }'
+/// ```
+#[utoipa::path(
+	post,
+	path="/userEvent",
+	summary="Insert or update a user-created custom event",
+	description="Insert a new or updates an existing user-created event with the values passed in the request, returning the event id. When neither lat nor lng is given and geocoding is enabled, they are looked up from the street address, city and country.",
+	request_body(
+		content=UserEventRequest,
+		content_type="application/json",
+		description="If id is provided, the event will be updated, otherwise it is inserted. The event name is required.",
+		example=json!({
+			"event_name": "Custom Event",
+			"event_description": "I want to do something and it's easier to make a custom event than to tell the LLM exactly how I want it."
+		})
+	),
+	responses(
+		(
+			status=200,
+			description="Contains the id of the event that was just inserted or updated.",
+			body=UserEventResponse,
+			content_type="application/json",
+			example=json!({
+				"id": 43
+			})
+		),
+		(status=400, description="Bad Request", body=ErrorBody),
+		(status=401, description="User has an invalid cookie/no cookie", body=ErrorBody),
+		(status=404, description="User-event not found for this user", body=ErrorBody),
+		(status=405, description="Method Not Allowed - Must be POST"),
+		(status=408, description="Request Timed Out"),
+		(status=500, description="Internal Server Error", body=ErrorBody)
+	),
+	security(("set-cookie"=[])),
+	tag="Itinerary"
+)]
+pub async fn api_user_event(
+	Extension(user): Extension<AuthUser>,
+	Extension(pool): Extension<PgPool>,
+	Extension(event_cache): Extension<SharedEventCache>,
+	Json(event): Json<UserEventRequest>,
+) -> ApiResult<Json<UserEventResponse>> {
+	if event.event_name.is_empty() {
+		return Err(AppError::BadRequest(String::from(
+			"Event name must not be empty",
+		)));
+	}
+	// Without coordinates the event can't be routed, so try to find them from its address
+	let (lat, lng) = match (event.lat, event.lng) {
+		(None, None) => geocode_address(
+			event.street_address.as_deref(),
+			event.city.as_deref(),
+			event.country.as_deref(),
+		)
+		.await
+		.map_or((None, None), |(lat, lng)| (Some(lat), Some(lng))),
+		coordinates => coordinates,
+	};
+	let id = if let Some(id) = event.id {
+		sqlx::query!(
 			r#"
 			UPDATE events
 			SET
@@ -807,8 +2132,10 @@ pub async fn api_user_event(
 				hard_start        = $8,
 				hard_end          = $9,
 				timezone          = $10,
-				photo_name        = $11
-			WHERE id=$12 AND user_created=TRUE AND account_id=$13
+				photo_name        = $11,
+				lat               = COALESCE($12, lat),
+				lng               = COALESCE($13, lng)
+			WHERE id=$14 AND user_created=TRUE AND account_id=$15
 			RETURNING id
 			"#,
 			event.street_address,
@@ -822,13 +2149,17 @@ pub async fn api_user_event(
 			event.hard_end,
 			event.timezone,
 			event.photo_name,
+			lat,
+			lng,
 			id,
 			user.id,
 		)
 		.fetch_optional(&pool)
 		.await
 		.map_err(AppError::from)?
-		.ok_or(AppError::NotFound)?;
+		.ok_or(AppError::EventNotFound)?;
+		// The optimizer would otherwise keep planning with the old details
+		event_cache.lock().unwrap().pop(&id);
 		id
 	} else {
 		sqlx::query!(
@@ -837,9 +2168,9 @@ pub async fn api_user_event(
 				street_address, postal_code, city, country,
 				event_type, event_description, event_name,
 				user_created, account_id, hard_start, hard_end,
-				timezone, photo_name
+				timezone, photo_name, lat, lng
 			)
-			VALUES($1, $2, $3, $4, $5, $6, $7, TRUE, $8, $9, $10, $11, $12)
+			VALUES($1, $2, $3, $4, $5, $6, $7, TRUE, $8, $9, $10, $11, $12, $13, $14)
 			RETURNING id
 			"#,
 			event.street_address,
@@ -854,6 +2185,8 @@ pub async fn api_user_event(
 			event.hard_end,
 			event.timezone,
 			event.photo_name,
+			lat,
+			lng,
 		)
 		.fetch_one(&pool)
 		.await
@@ -868,6 +2201,9 @@ pub async fn api_user_event(
 /// # Method
 /// `POST /api/itinerary/searchEvent`
 ///
+/// # Query Parameters
+/// - `include_archived` - optional, `true` also returns the user's archived events
+///
 /// # Request Body
 /// - [SearchEventRequest]
 ///   - Example filters:
@@ -876,9 +2212,12 @@ pub async fn api_user_event(
 ///     - `event_type`: Type of event
 ///     - `hard_start_after`: ISO 8601 timestamp to filter events starting after this time
 ///     - `hard_start_before`: ISO 8601 timestamp to filter events starting before this time
+///     - `lat`, `lng`, `radius_km`: Only events within `radius_km` of the point, nearest first
+///     - `q`: Free text like "modern art museum paris", most relevant first
 ///
 /// # Responses
-/// - `200 OK` - with body: [SearchEventResponse] - the best matching events for the query
+/// - `200 OK` - with body: [SearchEventResponse] - the best matching events for the query,
+///   each with its `distance_km` when searching around a point and its `relevance` with a `q`
 /// - `400 BAD_REQUEST` - Request payload contains invalid data (public error)
 /// - `401 UNAUTHORIZED` - When authentication fails (handled in middleware, public error)
 /// - `500 INTERNAL_SERVER_ERROR` - Internal error (private)
@@ -935,7 +2274,10 @@ pub async fn api_user_event(
     post,
     path="/searchEvent",
     summary="Search for events with the given filters and return a list of the best matching events",
-    description="Returns a limited number of events that best match the filters provided in the request.",
+    description="Returns a limited number of events that best match the filters provided in the request. Archived events are left out unless `include_archived=true`. With `lat`, `lng` and `radius_km` only events within the radius are returned, nearest first, along with their distances. With `q` events are full-text searched by name, description, city and types, most relevant first (then nearest), along with their relevance scores.",
+    params(
+        ("include_archived"=Option<bool>, Query, description="Also return archived events, false if omitted")
+    ),
     request_body(
         content=SearchEventRequest,
         content_type="application/json",
@@ -988,11 +2330,11 @@ pub async fn api_user_event(
                 ]
             })
         ),
-        (status=400, description="Bad Request"),
-        (status=401, description="User has an invalid cookie/no cookie"),
+        (status=400, description="Only some of lat, lng and radius_km were given, or they're out of range", body=ErrorBody),
+        (status=401, description="User has an invalid cookie/no cookie", body=ErrorBody),
         (status=405, description="Method Not Allowed - Must be POST"),
         (status=408, description="Request Timed Out"),
-        (status=500, description="Internal Server Error")
+        (status=500, description="Internal Server Error", body=ErrorBody)
     ),
     security(("set-cookie"=[])),
     tag="Itinerary"
@@ -1000,12 +2342,61 @@ pub async fn api_user_event(
 pub async fn api_search_event(
 	Extension(user): Extension<AuthUser>,
 	Extension(pool): Extension<PgPool>,
+	Query(params): Query<SearchEventQuery>,
 	Json(query): Json<SearchEventRequest>,
 ) -> ApiResult<Json<SearchEventResponse>> {
-	let mut qb = sqlx::QueryBuilder::new(
-		"SELECT *, NULL::int as block_index FROM events WHERE (user_created=FALSE OR account_id=",
-	);
+	let near = match (query.lat, query.lng, query.radius_km) {
+		(None, None, None) => None,
+		(Some(lat), Some(lng), Some(radius_km)) => {
+			if !(-90.0..=90.0).contains(&lat) || !(-180.0..=180.0).contains(&lng) {
+				return Err(AppError::Validation(
+					"lat must be between -90 and 90 and lng between -180 and 180".to_string(),
+				));
+			}
+			if radius_km <= 0.0 {
+				return Err(AppError::Validation(
+					"radius_km must be greater than 0".to_string(),
+				));
+			}
+			Some((lat, lng, radius_km))
+		}
+		_ => {
+			return Err(AppError::Validation(
+				"lat, lng and radius_km must be given together".to_string(),
+			));
+		}
+	};
+
+	let mut qb = sqlx::QueryBuilder::new("SELECT *, NULL::int as block_index FROM events");
+	if let Some((lat, lng, _)) = near {
+		// Haversine distance, clamped so rounding can't push asin out of its domain
+		qb.push(" CROSS JOIN LATERAL (SELECT 2 * ")
+			.push_bind(EARTH_RADIUS_KM)
+			.push(" * ASIN(LEAST(1, SQRT(POWER(SIN(RADIANS(lat - ")
+			.push_bind(lat)
+			.push(") / 2), 2) + COS(RADIANS(")
+			.push_bind(lat)
+			.push(")) * COS(RADIANS(lat)) * POWER(SIN(RADIANS(lng - ")
+			.push_bind(lng)
+			.push(") / 2), 2)))) AS distance_km) d");
+	}
+	let text = query.q.as_deref().map(str::trim).filter(|q| !q.is_empty());
+	if let Some(text) = text {
+		// Any of the stemmed words matches, events with more of them rank higher
+		qb.push(
+			" CROSS JOIN LATERAL (SELECT to_tsquery('english', replace(plainto_tsquery('english', ",
+		)
+		.push_bind(text)
+		.push(")::text, '&', '|')) AS search_query) s");
+		qb.push(
+			" CROSS JOIN LATERAL (SELECT ts_rank(search_vector, s.search_query) AS relevance) r",
+		);
+	}
+	qb.push(" WHERE (user_created=FALSE OR account_id=");
 	qb.push_bind(user.id).push(")");
+	if !params.include_archived {
+		qb.push(" AND archived = FALSE");
+	}
 	// Dynamically add filters if present
 	if let Some(id) = query.id {
 		qb.push(" AND id = ").push_bind(id);
@@ -1052,20 +2443,147 @@ pub async fn api_search_event(
 		qb.push(" AND timezone ILIKE ")
 			.push_bind(format!("%{}%", timezone));
 	}
-	qb.push(" ORDER BY hard_start ASC LIMIT ")
-		.push_bind(EVENT_SEARCH_RESULT_LEN);
-	Ok(Json(SearchEventResponse {
-		events: qb.build_query_as().fetch_all(&pool).await?,
-	}))
+	if let Some((_, _, radius_km)) = near {
+		qb.push(" AND lat IS NOT NULL AND lng IS NOT NULL AND d.distance_km <= ")
+			.push_bind(radius_km);
+	}
+	if text.is_some() {
+		qb.push(" AND search_vector @@ s.search_query");
+	}
+	match (text, near) {
+		(Some(_), Some(_)) => qb.push(" ORDER BY r.relevance DESC, d.distance_km ASC, id ASC"),
+		(Some(_), None) => qb.push(" ORDER BY r.relevance DESC, id ASC"),
+		(None, Some(_)) => qb.push(" ORDER BY d.distance_km ASC, id ASC"),
+		(None, None) => qb.push(" ORDER BY hard_start ASC"),
+	};
+	qb.push(" LIMIT ").push_bind(EVENT_SEARCH_RESULT_LEN);
+
+	let events = qb
+		.build()
+		.fetch_all(&pool)
+		.await?
+		.iter()
+		.map(|row| {
+			Ok(SearchEventResult {
+				event: Event::from_row(row)?,
+				distance_km: near.map(|_| row.try_get("distance_km")).transpose()?,
+				relevance: text.map(|_| row.try_get("relevance")).transpose()?,
+			})
+		})
+		.collect::<Result<Vec<_>, sqlx::Error>>()?;
+	Ok(Json(SearchEventResponse { events }))
+}
+
+/// Get every detail of one event
+///
+/// # Method
+/// `GET /api/itinerary/event/:id`
+///
+/// # Responses
+/// - `200 OK` - with body: [Event]
+/// - `401 UNAUTHORIZED` - When authentication fails (handled in middleware, public error)
+/// - `404 NOT_FOUND` - Event not found, or another user's user-created event (public error)
+/// - `500 INTERNAL_SERVER_ERROR` - Internal error (private)
+///
+/// # Examples
+/// ```bash
+/// curl -X GET http://localhost:3001/api/itinerary/event/42
+///   -H "Cookie: auth-token=..."
+/// ```
+///
+/// Notes:
+/// - Events are shared between itineraries, so the event doesn't need to be in one of the user's.
+#[utoipa::path(
+	get,
+	path="/event/{id}",
+	summary="Fetch a single event",
+	description="Fetches the full event record, including opening hours, photo, website and accessibility details. Other users' user-created events are not found.",
+	responses(
+		(
+			status=200,
+			description="The event",
+			body=Event,
+			content_type="application/json",
+			example=json!({
+				"id": 42,
+				"event_name": "Art Institute of Chicago",
+				"event_description": null,
+				"street_address": "111 S Michigan Ave",
+				"city": "Chicago",
+				"country": "USA",
+				"postal_code": 60603,
+				"lat": 41.8796,
+				"lng": -87.6237,
+				"event_type": "museum",
+				"user_created": false,
+				"hard_start": null,
+				"hard_end": null,
+				"timezone": null,
+				"place_id": "ChIJ2ZX2HqMsDogRLGW4XJ1Bi_Y",
+				"wheelchair_accessible_parking": null,
+				"wheelchair_accessible_entrance": true,
+				"wheelchair_accessible_restroom": true,
+				"wheelchair_accessible_seating": true,
+				"serves_vegetarian_food": null,
+				"price_level": 2,
+				"utc_offset_minutes": -300,
+				"website_uri": "https://www.artic.edu/",
+				"types": "museum,tourist_attraction",
+				"photo_name": null,
+				"photo_width": null,
+				"photo_height": null,
+				"photo_author": null,
+				"photo_author_uri": null,
+				"photo_author_photo_uri": null,
+				"weekday_descriptions": "Monday: 11:00 AM – 5:00 PM",
+				"secondary_hours_type": null,
+				"next_open_time": null,
+				"next_close_time": null,
+				"open_now": true,
+				"periods": [],
+				"special_days": [],
+				"block_index": null
+			})
+		),
+		(status=400, description="Bad Request", body=ErrorBody),
+		(status=401, description="User has an invalid cookie/no cookie", body=ErrorBody),
+		(status=404, description="Event not found", body=ErrorBody),
+		(status=405, description="Method Not Allowed - Must be GET"),
+		(status=408, description="Request Timed Out"),
+		(status=500, description="Internal Server Error", body=ErrorBody)
+	),
+	security(("set-cookie"=[])),
+	tag="Itinerary"
+)]
+pub async fn api_get_event(
+	Extension(user): Extension<AuthUser>,
+	Extension(pool): Extension<PgPool>,
+	Path(event_id): Path<i32>,
+) -> ApiResult<Json<Event>> {
+	debug!(
+		"HANDLER ->> /api/itinerary/event/{} 'api_get_event' - User ID: {}",
+		event_id, user.id
+	);
+
+	check_event_visible(event_id, user.id, &pool).await?;
+
+	let event = fetch_event(&pool, event_id)
+		.await
+		.map_err(AppError::from)?
+		.ok_or(AppError::EventNotFound)?;
+
+	Ok(Json(event))
 }
 
-/// Deletes a user-created event from the db
+/// Archives a user-created event, so it no longer shows up in searches or itineraries
+///
+/// The row is kept so itineraries and reviews referencing it stay intact.
 ///
 /// # Method
 /// `DELETE /api/itinerary/userEvent/:id`
 ///
 /// # Responses
-/// - `200 OK` - User-created event deleted successfully
+/// - `200 OK` - User-created event archived successfully
 /// - `400 BAD_REQUEST` - Request payload contains invalid data (public error)
 /// - `404 NOT_FOUND` - User-created event was not found or does not belong to this user (public error)
 /// - `401 UNAUTHORIZED` - When authentication fails (handled in middleware, public error)
@@ -1079,16 +2597,16 @@ pub async fn api_search_event(
 #[utoipa::path(
     delete,
     path="/userEvent/{id}",
-    summary="Deletes a user-event from the DB",
-    description="Deletes the user-created event from the DB using the provided event ID. Event must have been created by this user.",
+    summary="Archives a user-event",
+    description="Archives the user-created event with the provided event ID, hiding it from searches and itineraries. Event must have been created by this user.",
     responses(
-        (status=200, description="User-created event successfully deleted"),
-        (status=400, description="Bad Request"),
-        (status=401, description="User has an invalid cookie/no cookie"),
-        (status=404, description="User-event not found or does not belong to this user"),
+        (status=200, description="User-created event successfully archived"),
+        (status=400, description="Bad Request", body=ErrorBody),
+        (status=401, description="User has an invalid cookie/no cookie", body=ErrorBody),
+        (status=404, description="User-event not found or does not belong to this user", body=ErrorBody),
         (status=405, description="Method Not Allowed - Must be POST"),
         (status=408, description="Request Timed Out"),
-        (status=500, description="Internal Server Error")
+        (status=500, description="Internal Server Error", body=ErrorBody)
     ),
     security(("set-cookie"=[])),
     tag="Itinerary"
@@ -1100,11 +2618,12 @@ pub async fn api_delete_user_event(
 ) -> ApiResult<()> {
 	sqlx::query!(
 		r#"
-		DELETE FROM events
+		UPDATE events
+		SET archived = TRUE
 		WHERE
-			id=$1 AND
-			account_id=$2 AND
-			user_created=TRUE
+			id = $1 AND
+			user_created = TRUE AND
+			(account_id IS NULL OR account_id = $2)
 		RETURNING id;
 		"#,
 		event_id,
@@ -1113,10 +2632,466 @@ pub async fn api_delete_user_event(
 	.fetch_optional(&pool)
 	.await
 	.map_err(AppError::from)?
-	.ok_or(AppError::NotFound)?;
+	.ok_or(AppError::EventNotFound)?;
+	Ok(())
+}
+
+/// Reorder the events within one time block of an itinerary
+///
+/// # Method
+/// `PATCH /api/itinerary/:id/reorder`
+///
+/// # Request Body
+/// - [ReorderRequest]
+///
+/// # Responses
+/// - `200 OK` - Events reordered successfully
+/// - `400 BAD_REQUEST` - `event_ids` doesn't contain exactly the events in that time block (public error)
+/// - `401 UNAUTHORIZED` - When authentication fails (handled in middleware, public error)
+/// - `404 NOT_FOUND` - Itinerary not found or doesn't belong to user (public error)
+/// - `500 INTERNAL_SERVER_ERROR` - Internal error (private)
+///
+/// # Examples
+/// ```bash
+/// curl -X PATCH http://localhost:3001/api/itinerary/3/reorder
+///   -H "Content-Type: application/json"
+///   -d '{
+///         "date": "2025-11-05",
+///         "time_of_day": "Morning",
+///         "event_ids": [3, 1, 2]
+///       }'
+/// ```
+#[utoipa::path(
+	patch,
+	path="/{id}/reorder",
+	summary="Reorder events within a time block",
+	description="Sets the order of the events in one time block of a day to the order of `event_ids`. The ids must be exactly the events already in that block.",
+	request_body(
+		content=ReorderRequest,
+		content_type="application/json",
+		description="The time block and its events in their new order.",
+		example=json!({
+			"date": "2025-11-05",
+			"time_of_day": "Morning",
+			"event_ids": [3, 1, 2]
+		})
+	),
+	responses(
+		(status=200, description="Events reordered successfully"),
+		(status=400, description="Bad Request", body=ErrorBody),
+		(status=401, description="User has an invalid cookie/no cookie", body=ErrorBody),
+		(status=404, description="Itinerary not found or doesn't belong to user", body=ErrorBody),
+		(status=405, description="Method Not Allowed - Must be PATCH"),
+		(status=408, description="Request Timed Out"),
+		(status=500, description="Internal Server Error", body=ErrorBody)
+	),
+	security(("set-cookie"=[])),
+	tag="Itinerary"
+)]
+pub async fn api_reorder_events(
+	Extension(user): Extension<AuthUser>,
+	Extension(pool): Extension<PgPool>,
+	Path(itinerary_id): Path<i32>,
+	Json(request): Json<ReorderRequest>,
+) -> ApiResult<()> {
+	debug!(
+		"HANDLER ->> /api/itinerary/{}/reorder 'api_reorder_events' - User ID: {}",
+		itinerary_id, user.id
+	);
+
+	let mut tx = pool.begin().await.map_err(AppError::from)?;
+
+	// Lock the itinerary so concurrent reorders/saves don't interleave
+	sqlx::query!(
+		"SELECT id FROM itineraries WHERE id = $1 AND account_id = $2 AND deleted_at IS NULL FOR UPDATE",
+		itinerary_id,
+		user.id
+	)
+	.fetch_optional(&mut *tx)
+	.await
+	.map_err(AppError::from)?
+	.ok_or(AppError::ItineraryNotFound)?;
+
+	let mut current: Vec<i32> = sqlx::query_scalar!(
+		r#"
+		SELECT event_id AS "event_id!"
+		FROM event_list
+		WHERE itinerary_id = $1 AND date = $2 AND time_of_day = $3 AND event_id IS NOT NULL
+		"#,
+		itinerary_id,
+		request.date,
+		request.time_of_day.clone() as TimeOfDay
+	)
+	.fetch_all(&mut *tx)
+	.await
+	.map_err(AppError::from)?;
+
+	let mut requested = request.event_ids.clone();
+	current.sort_unstable();
+	requested.sort_unstable();
+	if current != requested {
+		return Err(AppError::BadRequest(
+			"event_ids must contain exactly the events in this time block".to_string(),
+		));
+	}
+
+	sqlx::query!(
+		r#"
+		UPDATE event_list el
+		SET block_index = u.position - 1
+		FROM UNNEST($4::int4[]) WITH ORDINALITY AS u(event_id, position)
+		WHERE el.itinerary_id = $1 AND el.date = $2 AND el.time_of_day = $3 AND el.event_id = u.event_id
+		"#,
+		itinerary_id,
+		request.date,
+		request.time_of_day as TimeOfDay,
+		&request.event_ids
+	)
+	.execute(&mut *tx)
+	.await
+	.map_err(AppError::from)?;
+
+	tx.commit().await.map_err(AppError::from)?;
+
+	Ok(())
+}
+
+/// Move an event from one time block of an itinerary to another
+///
+/// # Method
+/// `PATCH /api/itinerary/:id/moveEvent`
+///
+/// # Request Body
+/// - [MoveEventRequest]
+///
+/// # Responses
+/// - `200 OK` - Event moved successfully
+/// - `400 BAD_REQUEST` - `to_date` is outside the itinerary's dates (public error)
+/// - `401 UNAUTHORIZED` - When authentication fails (handled in middleware, public error)
+/// - `404 NOT_FOUND` - Itinerary not found, doesn't belong to user, or the event isn't in the `from` time block (public error)
+/// - `500 INTERNAL_SERVER_ERROR` - Internal error (private)
+///
+/// # Examples
+/// ```bash
+/// curl -X PATCH http://localhost:3001/api/itinerary/3/moveEvent
+///   -H "Content-Type: application/json"
+///   -d '{
+///         "event_id": 5,
+///         "from_date": "2025-07-15",
+///         "from_time_of_day": "Morning",
+///         "to_date": "2025-07-16",
+///         "to_time_of_day": "Afternoon"
+///       }'
+/// ```
+#[utoipa::path(
+	patch,
+	path="/{id}/moveEvent",
+	summary="Move an event to another time block",
+	description="Moves an event from one time block to the end of another, possibly on a different day of the itinerary.",
+	request_body(
+		content=MoveEventRequest,
+		content_type="application/json",
+		description="The event, the time block it's in, and the time block to move it to.",
+		example=json!({
+			"event_id": 5,
+			"from_date": "2025-07-15",
+			"from_time_of_day": "Morning",
+			"to_date": "2025-07-16",
+			"to_time_of_day": "Afternoon"
+		})
+	),
+	responses(
+		(status=200, description="Event moved successfully"),
+		(status=400, description="Bad Request", body=ErrorBody),
+		(status=401, description="User has an invalid cookie/no cookie", body=ErrorBody),
+		(status=404, description="Itinerary not found, doesn't belong to user, or event not in the from time block", body=ErrorBody),
+		(status=405, description="Method Not Allowed - Must be PATCH"),
+		(status=408, description="Request Timed Out"),
+		(status=500, description="Internal Server Error", body=ErrorBody)
+	),
+	security(("set-cookie"=[])),
+	tag="Itinerary"
+)]
+pub async fn api_move_event(
+	Extension(user): Extension<AuthUser>,
+	Extension(pool): Extension<PgPool>,
+	Path(itinerary_id): Path<i32>,
+	Json(request): Json<MoveEventRequest>,
+) -> ApiResult<()> {
+	debug!(
+		"HANDLER ->> /api/itinerary/{}/moveEvent 'api_move_event' - User ID: {}",
+		itinerary_id, user.id
+	);
+
+	let mut tx = pool.begin().await.map_err(AppError::from)?;
+
+	// Lock the itinerary so concurrent moves/saves don't interleave
+	let itinerary = sqlx::query!(
+		"SELECT start_date, end_date FROM itineraries WHERE id = $1 AND account_id = $2 AND deleted_at IS NULL FOR UPDATE",
+		itinerary_id,
+		user.id
+	)
+	.fetch_optional(&mut *tx)
+	.await
+	.map_err(AppError::from)?
+	.ok_or(AppError::ItineraryNotFound)?;
+
+	if request.to_date < itinerary.start_date || request.to_date > itinerary.end_date {
+		return Err(AppError::BadRequest(
+			"to_date must be within the itinerary's dates".to_string(),
+		));
+	}
+
+	// The moved event goes to the end of its new time block
+	sqlx::query!(
+		r#"
+		UPDATE event_list
+		SET
+			date = $1,
+			time_of_day = $2,
+			block_index = (
+				SELECT COALESCE(MAX(block_index) + 1, 0)
+				FROM event_list
+				WHERE itinerary_id = $3 AND date = $1 AND time_of_day = $2 AND event_id IS NOT NULL
+			)
+		WHERE itinerary_id = $3 AND event_id = $4 AND date = $5 AND time_of_day = $6
+		RETURNING id
+		"#,
+		request.to_date,
+		request.to_time_of_day as TimeOfDay,
+		itinerary_id,
+		request.event_id,
+		request.from_date,
+		request.from_time_of_day as TimeOfDay
+	)
+	.fetch_optional(&mut *tx)
+	.await
+	.map_err(AppError::from)?
+	.ok_or(AppError::EventNotFound)?;
+
+	// Keep the day the event left in the itinerary even if it's now empty
+	sqlx::query!(
+		r#"
+		INSERT INTO event_list (itinerary_id, event_id, time_of_day, date)
+		SELECT $1, NULL, 'Morning', $2
+		WHERE NOT EXISTS (SELECT 1 FROM event_list WHERE itinerary_id = $1 AND date = $2)
+		"#,
+		itinerary_id,
+		request.from_date
+	)
+	.execute(&mut *tx)
+	.await
+	.map_err(AppError::from)?;
+
+	tx.commit().await.map_err(AppError::from)?;
+
 	Ok(())
 }
 
+/// Add the days of one itinerary to the end of another
+///
+/// # Method
+/// `POST /api/itinerary/merge`
+///
+/// # Request Body
+/// - [MergeRequest]
+///
+/// # Responses
+/// - `200 OK` - with body: [Itinerary] - the merged target itinerary
+/// - `400 BAD_REQUEST` - The source and target are the same itinerary (public error)
+/// - `401 UNAUTHORIZED` - When authentication fails (handled in middleware, public error)
+/// - `404 NOT_FOUND` - Either itinerary not found or doesn't belong to user (public error)
+/// - `500 INTERNAL_SERVER_ERROR` - Internal error (private)
+///
+/// # Examples
+/// ```bash
+/// curl -X POST http://localhost:3001/api/itinerary/merge
+///   -H "Content-Type: application/json"
+///   -d '{
+///         "source_id": 1,
+///         "target_id": 2
+///       }'
+/// ```
+///
+/// Notes:
+/// - The source's days are moved to start the day after the target ends, see [merge_offset_days].
+/// - The source's unassigned events are added to the target's, and the source itself is left as is.
+#[utoipa::path(
+	post,
+	path="/merge",
+	summary="Merge two itineraries",
+	description="Appends the source itinerary's days to the end of the target itinerary, shifting their dates to follow on from the target's last day.",
+	request_body(
+		content=MergeRequest,
+		content_type="application/json",
+		description="The itinerary to copy days from, and the itinerary to add them to.",
+		example=json!({
+			"source_id": 1,
+			"target_id": 2
+		})
+	),
+	responses(
+		(
+			status=200,
+			description="The target itinerary with the source's days added",
+			body=Itinerary,
+			content_type="application/json"
+		),
+		(status=400, description="Bad Request", body=ErrorBody),
+		(status=401, description="User has an invalid cookie/no cookie", body=ErrorBody),
+		(status=404, description="Itinerary not found or doesn't belong to user", body=ErrorBody),
+		(status=405, description="Method Not Allowed - Must be POST"),
+		(status=408, description="Request Timed Out"),
+		(status=500, description="Internal Server Error", body=ErrorBody)
+	),
+	security(("set-cookie"=[])),
+	tag="Itinerary"
+)]
+pub async fn api_merge_itinerary(
+	Extension(user): Extension<AuthUser>,
+	Extension(pool): Extension<PgPool>,
+	Json(MergeRequest {
+		source_id,
+		target_id,
+	}): Json<MergeRequest>,
+) -> ApiResult<Json<Itinerary>> {
+	debug!(
+		"HANDLER ->> /api/itinerary/merge 'api_merge_itinerary' - User ID: {} Source: {} Target: {}",
+		user.id, source_id, target_id
+	);
+	if source_id == target_id {
+		return Err(AppError::BadRequest(
+			"An itinerary can't be merged into itself".to_string(),
+		));
+	}
+
+	let mut tx = pool.begin().await.map_err(AppError::from)?;
+
+	// Lock both itineraries, in id order so concurrent merges of the same two don't deadlock
+	let itineraries = sqlx::query!(
+		r#"
+		SELECT id, start_date, end_date, unassigned_event_ids
+		FROM itineraries
+		WHERE id = ANY($1) AND account_id = $2 AND deleted_at IS NULL
+		ORDER BY id
+		FOR UPDATE
+		"#,
+		&[source_id, target_id],
+		user.id
+	)
+	.fetch_all(&mut *tx)
+	.await
+	.map_err(AppError::from)?;
+	let source = itineraries
+		.iter()
+		.find(|i| i.id == source_id)
+		.ok_or(AppError::ItineraryNotFound)?;
+	let target = itineraries
+		.iter()
+		.find(|i| i.id == target_id)
+		.ok_or(AppError::ItineraryNotFound)?;
+
+	let offset = merge_offset_days(source.start_date, target.end_date);
+	sqlx::query!(
+		r#"
+		INSERT INTO event_list (itinerary_id, event_id, time_of_day, date, block_index)
+		SELECT $1, event_id, time_of_day, date + $3::int, block_index
+		FROM event_list
+		WHERE itinerary_id = $2;
+		"#,
+		target_id,
+		source_id,
+		offset
+	)
+	.execute(&mut *tx)
+	.await
+	.map_err(AppError::from)?;
+
+	let end_date = target
+		.end_date
+		.max(source.end_date + chrono::Duration::days(offset.into()));
+	let mut unassigned_event_ids = target.unassigned_event_ids.clone();
+	for id in &source.unassigned_event_ids {
+		if !unassigned_event_ids.contains(id) {
+			unassigned_event_ids.push(*id);
+		}
+	}
+	sqlx::query!(
+		r#"
+		UPDATE itineraries
+		SET end_date = $1, unassigned_event_ids = $2
+		WHERE id = $3;
+		"#,
+		end_date,
+		&unassigned_event_ids,
+		target_id
+	)
+	.execute(&mut *tx)
+	.await
+	.map_err(AppError::from)?;
+
+	tx.commit().await.map_err(AppError::from)?;
+
+	let itinerary = sqlx::query_as!(
+		ItineraryRow,
+		r#"SELECT
+			id,
+			account_id,
+			start_date,
+			end_date,
+			chat_session_id,
+			title,
+			unassigned_event_ids,
+			starred
+		FROM itineraries WHERE id = $1"#,
+		target_id
+	)
+	.fetch_one(&pool)
+	.await
+	.map_err(AppError::from)?;
+
+	Ok(Json(full_itinerary(itinerary, &pool).await?))
+}
+
+/// Days to add to the source's dates when merging it into the target, so the source's
+/// first day is the day after the target's last day.
+/// * Negative when the source starts more than a day after the target ends
+pub fn merge_offset_days(source_start_date: NaiveDate, target_end_date: NaiveDate) -> i32 {
+	(target_end_date.succ_opt().unwrap_or(target_end_date) - source_start_date).num_days() as i32
+}
+
+/// Permanently deletes itineraries deleted over `ITINERARY_RETENTION_DAYS` ago.
+/// Returns how many were deleted.
+pub async fn purge_deleted_itineraries(pool: &PgPool) -> Result<u64, sqlx::Error> {
+	Ok(sqlx::query!(
+		r#"
+		DELETE FROM itineraries
+		WHERE deleted_at < NOW() - make_interval(days => $1);
+		"#,
+		ITINERARY_RETENTION_DAYS
+	)
+	.execute(pool)
+	.await?
+	.rows_affected())
+}
+
+/// Purges deleted itineraries every `ITINERARY_PURGE_INTERVAL_SECONDS` for the lifetime of the server.
+pub fn spawn_itinerary_purger(pool: PgPool) {
+	tokio::spawn(async move {
+		let mut interval = tokio::time::interval(std::time::Duration::from_secs(
+			ITINERARY_PURGE_INTERVAL_SECONDS,
+		));
+		loop {
+			interval.tick().await;
+			match purge_deleted_itineraries(&pool).await {
+				Ok(0) => {}
+				Ok(purged) => info!(purged = purged, "Purged deleted itineraries"),
+				Err(e) => error!("Failed to purge deleted itineraries: {e}"),
+			}
+		}
+	});
+}
+
 /// Create the itinerary routes with authentication middleware.
 ///
 /// # Routes
@@ -1125,18 +3100,42 @@ pub async fn api_delete_user_event(
 /// - `GET /{id}` - Get single itinerary metadata (protected)
 /// - `POST /userEvent` - Insert or update a user-created custom event (protected)
 /// - `POST /searchEvent` - queries the DB for an event that matches the provided filters (protected)
+/// - `GET /event/{id}` - Gets every detail of one event (protected)
 /// - `DELETE /userEvent/{id}` - Deletes the user-created event from the db (protected)
+/// - `PATCH /{id}/reorder` - Reorders the events in one time block (protected)
+/// - `PATCH /{id}/moveEvent` - Moves an event to another time block (protected)
+/// - `GET /{id}/stats` - Summarizes the itinerary's events (protected)
+/// - `GET /browse` - Lists other users' public itineraries (protected)
+/// - `POST /{id}/clone` - Copies a public itinerary to the user's account (protected)
+/// - `POST /{id}/regenerate` - Asks the LLM to replace all but the kept events (protected)
+/// - `POST /{id}/restore` - Restores an itinerary deleted with its chat (protected)
+/// - `POST /merge` - Adds the days of one itinerary to the end of another (protected)
 ///
 /// # Middleware
 /// All routes are protected by `middleware_auth` which validates the `auth-token` cookie.
 pub fn itinerary_routes() -> AxumRouter {
 	AxumRouter::new()
 		.route("/saved", get(api_saved_itineraries))
+		.route("/browse", get(api_browse))
 		.route("/save", post(api_save))
 		.route("/unsave", post(api_unsave))
+		.route("/merge", post(api_merge_itinerary))
 		.route("/{id}", get(api_get_itinerary))
+		.route("/{id}/reorder", patch(api_reorder_events))
+		.route("/{id}/moveEvent", patch(api_move_event))
+		.route("/{id}/title", patch(api_rename_itinerary))
+		.route("/{id}/history", get(api_history))
+		.route("/{id}/restore/{snapshot_id}", post(api_restore))
+		.route("/{id}/restore", post(api_restore_deleted))
+		.route("/{id}/stats", get(api_itinerary_stats))
+		.route("/{id}/clone", post(api_clone_itinerary))
+		.route("/{id}/regenerate", post(api_regenerate_itinerary))
 		.route("/userEvent", post(api_user_event))
 		.route("/searchEvent", post(api_search_event))
+		.route("/event/{id}", get(api_get_event))
 		.route("/userEvent/{id}", delete(api_delete_user_event))
+		.route("/{id}/share", post(api_share).delete(api_unshare))
+		.route("/{id}/star", post(api_star).delete(api_unstar))
 		.route_layer(axum::middleware::from_fn(middleware_auth))
+		.route("/shared/{token}", get(api_shared_itinerary))
 }
@@ -1,5 +1,6 @@
 pub mod account;
 pub mod chat;
+pub mod events;
 pub mod itinerary;
 
 /// A regular [axum::Router] in test and release builds, or [utoipa_axum::router::OpenApiRouter] in non-test or dev builds
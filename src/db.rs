@@ -1,19 +1,113 @@
 // src/db/pool.rs
-use sqlx::{PgPool, postgres::PgPoolOptions};
-use std::env;
+pub mod events;
+pub mod places;
+
+use sqlx::{
+	ConnectOptions, PgPool,
+	postgres::{PgConnectOptions, PgPoolOptions},
+};
+use std::{env, str::FromStr, time::Duration};
+use tracing::warn;
+
+use crate::global::{
+	DB_ACQUIRE_TIMEOUT_SECONDS, DB_APPLICATION_NAME, DB_MAX_CONNECTIONS, DB_MIN_CONNECTIONS,
+	DB_SLOW_QUERY_MS, DB_STATEMENT_TIMEOUT_MS, DEFAULT_DB_ACQUIRE_TIMEOUT_SECONDS,
+	DEFAULT_DB_MAX_CONNECTIONS, DEFAULT_DB_MIN_CONNECTIONS, DEFAULT_DB_SLOW_QUERY_MS,
+	DEFAULT_DB_STATEMENT_TIMEOUT_MS,
+};
 // Pgpool- A pool of PostgreSQL connections
 // PgPoolOptions - The "configuration options" for creating a pool (the max number of connections).
 
+/// How the database pool is sized and how long queries may take, see [PoolConfig::from_env]
+#[derive(Debug, Clone, PartialEq)]
+pub struct PoolConfig {
+	pub max_connections: u32,
+	pub min_connections: u32,
+	/// How long a query waits for a free connection
+	pub acquire_timeout: Duration,
+	/// How long Postgres lets a statement run, zero for no limit
+	pub statement_timeout: Duration,
+	/// Queries taking longer are logged at WARN with their duration
+	pub slow_query_threshold: Duration,
+}
+
+impl PoolConfig {
+	/// Reads each setting from its env var, using its default when unset or invalid.
+	/// * At least 1 connection is allowed
+	/// * The pool never keeps more idle connections than it can open
+	pub fn from_env() -> Self {
+		let max_connections = env_or(DB_MAX_CONNECTIONS, DEFAULT_DB_MAX_CONNECTIONS).max(1);
+		Self {
+			max_connections,
+			min_connections: env_or(DB_MIN_CONNECTIONS, DEFAULT_DB_MIN_CONNECTIONS)
+				.min(max_connections),
+			acquire_timeout: Duration::from_secs(env_or(
+				DB_ACQUIRE_TIMEOUT_SECONDS,
+				DEFAULT_DB_ACQUIRE_TIMEOUT_SECONDS,
+			)),
+			statement_timeout: Duration::from_millis(env_or(
+				DB_STATEMENT_TIMEOUT_MS,
+				DEFAULT_DB_STATEMENT_TIMEOUT_MS,
+			)),
+			slow_query_threshold: Duration::from_millis(env_or(
+				DB_SLOW_QUERY_MS,
+				DEFAULT_DB_SLOW_QUERY_MS,
+			)),
+		}
+	}
+}
+
+/// The env var `name` parsed as a `T`, `default` when it's unset or doesn't parse
+fn env_or<T: FromStr>(name: &str, default: T) -> T {
+	match env::var(name) {
+		Ok(value) => value.trim().parse().unwrap_or_else(|_| {
+			warn!("{name}={value:?} is invalid, using the default");
+			default
+		}),
+		Err(_) => default,
+	}
+}
+
 pub async fn create_pool() -> PgPool {
 	// Retrieve the database URL from an environment variable
 	let database_url = env::var("DATABASE_URL")
 		//"Panic" if no url is found
 		.expect("DATABASE_URL must be set in .env or environment");
+	let config = PoolConfig::from_env();
+
+	let connect_options = PgConnectOptions::from_str(&database_url)
+		.expect("DATABASE_URL must be a valid Postgres connection string")
+		.application_name(DB_APPLICATION_NAME)
+		.options([(
+			"statement_timeout",
+			config.statement_timeout.as_millis().to_string(),
+		)])
+		.log_slow_statements(::log::LevelFilter::Warn, config.slow_query_threshold);
 
-	// Creates a connection pool with up to 5 connections
 	PgPoolOptions::new()
-		.max_connections(5)
-		.connect(&database_url)
+		.max_connections(config.max_connections)
+		.min_connections(config.min_connections)
+		.acquire_timeout(config.acquire_timeout)
+		.connect_with(connect_options)
 		.await
 		.expect("Failed to create database pool")
 }
+
+/// Connections the pool has open at the moment
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PoolStats {
+	pub size: u32,
+	pub idle: u32,
+	pub in_use: u32,
+}
+
+/// How many of the pool's connections are open and how many of those are idle
+pub fn pool_stats(pool: &PgPool) -> PoolStats {
+	let size = pool.size();
+	let idle = u32::try_from(pool.num_idle()).unwrap_or(u32::MAX).min(size);
+	PoolStats {
+		size,
+		idle,
+		in_use: size - idle,
+	}
+}
@@ -0,0 +1,95 @@
+/*
+ * src/db/events.rs
+ *
+ * File for loading events
+ *
+ * Purpose:
+ *   One place that selects full event rows by id, so tools and controllers
+ *   hydrating events all get the same columns.
+ */
+
+use sqlx::PgPool;
+
+use crate::http_models::event::Event;
+use crate::sql_models::Period;
+
+/// The events with these ids, in the order of `ids`, with `block_index` unset.
+/// * Ids without an event are skipped, duplicate ids give the event once
+pub async fn fetch_events_by_ids(pool: &PgPool, ids: &[i32]) -> sqlx::Result<Vec<Event>> {
+	if ids.is_empty() {
+		return Ok(Vec::new());
+	}
+
+	sqlx::query_as!(
+		Event,
+		r#"
+		SELECT
+			id,
+			street_address,
+			postal_code,
+			city,
+			country,
+			lat,
+			lng,
+			event_type,
+			event_description,
+			event_name,
+			user_created,
+			hard_start,
+			hard_end,
+			timezone,
+			place_id,
+			wheelchair_accessible_parking,
+			wheelchair_accessible_entrance,
+			wheelchair_accessible_restroom,
+			wheelchair_accessible_seating,
+			serves_vegetarian_food,
+			price_level,
+			utc_offset_minutes,
+			website_uri,
+			types,
+			photo_name,
+			photo_width,
+			photo_height,
+			photo_author,
+			photo_author_uri,
+			photo_author_photo_uri,
+			weekday_descriptions,
+			secondary_hours_type,
+			next_open_time,
+			next_close_time,
+			open_now,
+			periods as "periods: Vec<Period>",
+			special_days,
+			NULL::int AS block_index
+		FROM events
+		WHERE id = ANY($1)
+		ORDER BY array_position($1, id)
+		"#,
+		ids
+	)
+	.fetch_all(pool)
+	.await
+}
+
+/// The event with this id, `None` if there isn't one
+pub async fn fetch_event(pool: &PgPool, id: i32) -> sqlx::Result<Option<Event>> {
+	Ok(fetch_events_by_ids(pool, &[id]).await?.into_iter().next())
+}
+
+/// Ids of the account's favorite events, most recently favorited first, leaving out
+/// archived events
+pub async fn fetch_favorite_event_ids(pool: &PgPool, account_id: i32) -> sqlx::Result<Vec<i32>> {
+	sqlx::query_scalar!(
+		r#"
+		SELECT f.event_id
+		FROM favorites f
+		JOIN events e ON e.id = f.event_id
+		WHERE f.account_id = $1 AND e.archived = FALSE
+		ORDER BY f.created_at DESC, f.id DESC
+		"#,
+		account_id
+	)
+	.fetch_all(pool)
+	.await
+}
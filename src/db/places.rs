@@ -0,0 +1,186 @@
+/*
+ * src/db/places.rs
+ *
+ * File for caching researched places
+ *
+ * Purpose:
+ *   Reuse the events row of a place research already found, so popular venues
+ *   are stored once and only their opening status is refreshed once it's stale.
+ */
+
+use sqlx::PgPool;
+
+use crate::agent::models::event::SharedEventCache;
+use crate::global::PLACE_REFRESH_HOURS;
+use crate::http_models::event::Event;
+
+/// Id of the events row for the researched `candidate`, inserting it if it's new.
+/// * Rows are matched by `place_id`, or by `city` and `event_name` when it has none
+/// * A matched row updated over `PLACE_REFRESH_HOURS` ago has its `open_now`, `next_open_time`,
+///   `next_close_time`, `price_level` and `weekday_descriptions` refreshed from `candidate`,
+///   and is dropped from `cache` so the optimizer doesn't keep the old values
+/// * User created events are never matched by name
+pub async fn get_or_insert_event_by_place(
+	pool: &PgPool,
+	cache: &SharedEventCache,
+	candidate: &Event,
+) -> sqlx::Result<i32> {
+	let existing = match (candidate.place_id.as_deref(), candidate.city.as_deref()) {
+		(Some(place_id), _) => {
+			sqlx::query_scalar!("SELECT id FROM events WHERE place_id = $1", place_id)
+				.fetch_optional(pool)
+				.await?
+		}
+		(None, Some(city)) => {
+			sqlx::query_scalar!(
+				r#"
+				SELECT id FROM events
+				WHERE city = $1 AND event_name = $2 AND NOT user_created
+				ORDER BY id
+				LIMIT 1
+				"#,
+				city,
+				&candidate.event_name
+			)
+			.fetch_optional(pool)
+			.await?
+		}
+		(None, None) => None,
+	};
+
+	match existing {
+		Some(id) => {
+			if refresh_if_stale(pool, id, candidate).await? {
+				cache.lock().unwrap().pop(&id);
+			}
+			Ok(id)
+		}
+		None => {
+			// Another request may have inserted and refreshed the place meanwhile
+			let id = insert_place(pool, candidate).await?;
+			cache.lock().unwrap().pop(&id);
+			Ok(id)
+		}
+	}
+}
+
+/// Refreshes what changes often about the place, if it's older than `PLACE_REFRESH_HOURS`.
+/// Returns whether it was refreshed.
+async fn refresh_if_stale(pool: &PgPool, id: i32, candidate: &Event) -> sqlx::Result<bool> {
+	let result = sqlx::query!(
+		r#"
+		UPDATE events
+		SET
+			open_now = $2,
+			next_open_time = $3,
+			next_close_time = $4,
+			price_level = $5,
+			updated_at = NOW()
+		WHERE id = $1 AND updated_at < NOW() - make_interval(hours => $6)
+		"#,
+		id,
+		candidate.open_now,
+		candidate.next_open_time,
+		candidate.next_close_time,
+		candidate.price_level,
+		PLACE_REFRESH_HOURS,
+	)
+	.execute(pool)
+	.await?;
+	Ok(())
+}
+
+/// Inserts the place, returning its id.
+/// * If another request inserted the same `place_id` first, that row's id is returned
+///   after refreshing the same fields as [refresh_if_stale]
+async fn insert_place(pool: &PgPool, ev: &Event) -> sqlx::Result<i32> {
+	sqlx::query_scalar!(
+		r#"
+		INSERT INTO events (
+			event_name,
+			event_description,
+			street_address,
+			city,
+			country,
+			postal_code,
+			lat,
+			lng,
+			event_type,
+			user_created,
+			hard_start,
+			hard_end,
+			timezone,
+			place_id,
+			wheelchair_accessible_parking,
+			wheelchair_accessible_entrance,
+			wheelchair_accessible_restroom,
+			wheelchair_accessible_seating,
+			serves_vegetarian_food,
+			price_level,
+			utc_offset_minutes,
+			website_uri,
+			types,
+			photo_name,
+			photo_width,
+			photo_height,
+			photo_author,
+			photo_author_uri,
+			photo_author_photo_uri,
+			weekday_descriptions,
+			secondary_hours_type,
+			next_open_time,
+			next_close_time,
+			open_now,
+			periods,
+			special_days
+		)
+		VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18, $19, $20, $21, $22, $23, $24, $25, $26, $27, $28, $29, $30, $31, $32, $33, $34, $35, $36)
+		ON CONFLICT (place_id) DO UPDATE SET
+			open_now = EXCLUDED.open_now,
+			next_open_time = EXCLUDED.next_open_time,
+			next_close_time = EXCLUDED.next_close_time,
+			price_level = EXCLUDED.price_level,
+			weekday_descriptions = EXCLUDED.weekday_descriptions,
+			updated_at = NOW()
+		RETURNING id
+		"#,
+		&ev.event_name,
+		ev.event_description.as_ref(),
+		ev.street_address.as_ref(),
+		ev.city.as_ref(),
+		ev.country.as_ref(),
+		ev.postal_code,
+		ev.lat,
+		ev.lng,
+		ev.event_type.as_ref(),
+		ev.user_created,
+		ev.hard_start,
+		ev.hard_end,
+		ev.timezone.as_ref(),
+		ev.place_id.as_ref(),
+		ev.wheelchair_accessible_parking,
+		ev.wheelchair_accessible_entrance,
+		ev.wheelchair_accessible_restroom,
+		ev.wheelchair_accessible_seating,
+		ev.serves_vegetarian_food,
+		ev.price_level,
+		ev.utc_offset_minutes,
+		ev.website_uri.as_ref(),
+		ev.types.as_ref(),
+		ev.photo_name.as_ref(),
+		ev.photo_width,
+		ev.photo_height,
+		ev.photo_author.as_ref(),
+		ev.photo_author_uri.as_ref(),
+		ev.photo_author_photo_uri.as_ref(),
+		ev.weekday_descriptions.as_ref(),
+		ev.secondary_hours_type,
+		ev.next_open_time,
+		ev.next_close_time,
+		ev.open_now,
+		&ev.periods as _,
+		&ev.special_days as _,
+	)
+	.fetch_one(pool)
+	.await
+}
@@ -1,7 +1,9 @@
-use axum::http::StatusCode;
+use axum::http::{StatusCode, header};
 use axum::response::{IntoResponse, Response};
+use serde::{Deserialize, Serialize};
 use std::fmt;
 use tracing::error;
+use utoipa::{ToResponse, ToSchema};
 
 // Unified API result type
 #[cfg(not(tarpaulin_include))]
@@ -13,25 +15,155 @@ pub type ApiResult<T> = std::result::Result<T, AppError>;
 pub enum AppError {
 	Validation(String),
 	BadRequest(String),
+	/// A message was sent without any text
+	EmptyText,
+	/// Wrong email or password on login
+	InvalidCredentials,
 	Unauthorized,
+	Forbidden,
+	/// The account hasn't verified its email yet, see `/api/account/verify`
+	EmailNotVerified,
 	NotFound,
+	/// The chat session doesn't exist or belongs to someone else
+	ChatSessionNotFound,
+	/// The message doesn't exist, was deleted or belongs to someone else
+	MessageNotFound,
+	/// The itinerary doesn't exist, was deleted or belongs to someone else
+	ItineraryNotFound,
+	/// The event doesn't exist or isn't visible to the user
+	EventNotFound,
 	Conflict(String),
+	/// Another account already uses the email
+	DuplicateEmail,
+	/// The chat is still working on a reply
+	PipelineBusy(String),
+	/// Seconds until the client may retry, sent as `Retry-After`
+	TooManyRequests(u64),
+	/// A feature the server was started without, like the LLM agent, or the server is too busy
+	ServiceUnavailable(String),
 	Internal(String),
 }
 
+/// Machine readable code of an error response, one per [AppError] variant
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+#[cfg(not(tarpaulin_include))]
+pub enum ErrorCode {
+	ValidationError,
+	BadRequest,
+	EmptyText,
+	InvalidCredentials,
+	Unauthorized,
+	Forbidden,
+	EmailNotVerified,
+	NotFound,
+	ChatSessionNotFound,
+	MessageNotFound,
+	ItineraryNotFound,
+	EventNotFound,
+	Conflict,
+	DuplicateEmail,
+	PipelineBusy,
+	TooManyRequests,
+	ServiceUnavailable,
+	InternalError,
+}
+
+/// Body of every error response
+#[derive(Debug, Serialize, Deserialize, ToSchema, ToResponse)]
+#[cfg(not(tarpaulin_include))]
+pub struct ErrorBody {
+	/// What went wrong, for the frontend to match on
+	pub code: ErrorCode,
+	/// Human readable description, private errors only get a generic one
+	pub message: String,
+	/// Extra information for some codes, e.g. `retry_after` seconds for `TOO_MANY_REQUESTS`
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	#[schema(value_type = Option<Object>)]
+	pub details: Option<serde_json::Value>,
+}
+
 #[cfg(not(tarpaulin_include))]
 impl AppError {
 	pub fn status_code(&self) -> StatusCode {
 		match self {
 			AppError::Validation(_) => StatusCode::BAD_REQUEST,
 			AppError::BadRequest(_) => StatusCode::BAD_REQUEST,
+			AppError::EmptyText => StatusCode::BAD_REQUEST,
+			AppError::InvalidCredentials => StatusCode::BAD_REQUEST,
 			AppError::Unauthorized => StatusCode::UNAUTHORIZED,
-			AppError::NotFound => StatusCode::NOT_FOUND,
+			AppError::Forbidden => StatusCode::FORBIDDEN,
+			AppError::EmailNotVerified => StatusCode::FORBIDDEN,
+			AppError::NotFound
+			| AppError::ChatSessionNotFound
+			| AppError::MessageNotFound
+			| AppError::ItineraryNotFound
+			| AppError::EventNotFound => StatusCode::NOT_FOUND,
 			AppError::Conflict(_) => StatusCode::CONFLICT,
+			AppError::DuplicateEmail => StatusCode::CONFLICT,
+			AppError::PipelineBusy(_) => StatusCode::CONFLICT,
+			AppError::TooManyRequests(_) => StatusCode::TOO_MANY_REQUESTS,
+			AppError::ServiceUnavailable(_) => StatusCode::SERVICE_UNAVAILABLE,
 			AppError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
 		}
 	}
 
+	pub fn code(&self) -> ErrorCode {
+		match self {
+			AppError::Validation(_) => ErrorCode::ValidationError,
+			AppError::BadRequest(_) => ErrorCode::BadRequest,
+			AppError::EmptyText => ErrorCode::EmptyText,
+			AppError::InvalidCredentials => ErrorCode::InvalidCredentials,
+			AppError::Unauthorized => ErrorCode::Unauthorized,
+			AppError::Forbidden => ErrorCode::Forbidden,
+			AppError::EmailNotVerified => ErrorCode::EmailNotVerified,
+			AppError::NotFound => ErrorCode::NotFound,
+			AppError::ChatSessionNotFound => ErrorCode::ChatSessionNotFound,
+			AppError::MessageNotFound => ErrorCode::MessageNotFound,
+			AppError::ItineraryNotFound => ErrorCode::ItineraryNotFound,
+			AppError::EventNotFound => ErrorCode::EventNotFound,
+			AppError::Conflict(_) => ErrorCode::Conflict,
+			AppError::DuplicateEmail => ErrorCode::DuplicateEmail,
+			AppError::PipelineBusy(_) => ErrorCode::PipelineBusy,
+			AppError::TooManyRequests(_) => ErrorCode::TooManyRequests,
+			AppError::ServiceUnavailable(_) => ErrorCode::ServiceUnavailable,
+			AppError::Internal(_) => ErrorCode::InternalError,
+		}
+	}
+
+	/// The body sent to the client, messages of private errors stay in the logs
+	pub fn body(&self) -> ErrorBody {
+		let message = match self {
+			AppError::Validation(m)
+			| AppError::BadRequest(m)
+			| AppError::PipelineBusy(m)
+			| AppError::ServiceUnavailable(m) => m.clone(),
+			AppError::EmptyText => String::from("Text cannot be empty"),
+			AppError::InvalidCredentials => String::from("Invalid email or password"),
+			AppError::Unauthorized => String::from("Not signed in"),
+			AppError::Forbidden => String::from("Not allowed"),
+			AppError::EmailNotVerified => String::from("Email address is not verified"),
+			AppError::NotFound => String::from("Not found"),
+			AppError::ChatSessionNotFound => String::from("Chat session not found"),
+			AppError::MessageNotFound => String::from("Message not found"),
+			AppError::ItineraryNotFound => String::from("Itinerary not found"),
+			AppError::EventNotFound => String::from("Event not found"),
+			AppError::Conflict(_) => String::from("Conflicts with existing data"),
+			AppError::DuplicateEmail => String::from("Email already in use"),
+			AppError::TooManyRequests(s) => format!("Too many requests, retry after {s}s"),
+			AppError::Internal(_) => String::from("Internal server error"),
+		};
+		let details = match self {
+			AppError::TooManyRequests(s) => Some(serde_json::json!({ "retry_after": s })),
+			_ => None,
+		};
+		ErrorBody {
+			code: self.code(),
+			message,
+			details,
+		}
+	}
+
 	pub fn log(&self) {
 		match self {
 			AppError::Validation(m) => {
@@ -40,15 +172,51 @@ impl AppError {
 			AppError::BadRequest(m) => {
 				error!(target: "api_error", prefix = "ERROR ->>", kind = "bad_request", message = %m)
 			}
+			AppError::EmptyText => {
+				error!(target: "api_error", prefix = "ERROR ->>", kind = "empty_text")
+			}
+			AppError::InvalidCredentials => {
+				error!(target: "api_error", prefix = "ERROR ->>", kind = "invalid_credentials")
+			}
 			AppError::Unauthorized => {
 				error!(target: "api_error", prefix = "ERROR ->>", kind = "unauthorized")
 			}
+			AppError::Forbidden => {
+				error!(target: "api_error", prefix = "ERROR ->>", kind = "forbidden")
+			}
+			AppError::EmailNotVerified => {
+				error!(target: "api_error", prefix = "ERROR ->>", kind = "email_not_verified")
+			}
 			AppError::NotFound => {
 				error!(target: "api_error", prefix = "ERROR ->>", kind = "not_found")
 			}
+			AppError::ChatSessionNotFound => {
+				error!(target: "api_error", prefix = "ERROR ->>", kind = "chat_session_not_found")
+			}
+			AppError::MessageNotFound => {
+				error!(target: "api_error", prefix = "ERROR ->>", kind = "message_not_found")
+			}
+			AppError::ItineraryNotFound => {
+				error!(target: "api_error", prefix = "ERROR ->>", kind = "itinerary_not_found")
+			}
+			AppError::EventNotFound => {
+				error!(target: "api_error", prefix = "ERROR ->>", kind = "event_not_found")
+			}
 			AppError::Conflict(m) => {
 				error!(target: "api_error", prefix = "ERROR ->>", kind = "conflict", message = %m)
 			}
+			AppError::DuplicateEmail => {
+				error!(target: "api_error", prefix = "ERROR ->>", kind = "duplicate_email")
+			}
+			AppError::PipelineBusy(m) => {
+				error!(target: "api_error", prefix = "ERROR ->>", kind = "pipeline_busy", message = %m)
+			}
+			AppError::TooManyRequests(s) => {
+				error!(target: "api_error", prefix = "ERROR ->>", kind = "too_many_requests", retry_after = %s)
+			}
+			AppError::ServiceUnavailable(m) => {
+				error!(target: "api_error", prefix = "ERROR ->>", kind = "service_unavailable", message = %m)
+			}
 			AppError::Internal(m) => {
 				error!(target: "api_error", prefix = "ERROR ->>", kind = "internal", message = %m)
 			}
@@ -62,9 +230,21 @@ impl fmt::Display for AppError {
 		match self {
 			AppError::Validation(m) => write!(f, "validation error: {m}"),
 			AppError::BadRequest(m) => write!(f, "bad request: {m}"),
+			AppError::EmptyText => write!(f, "empty text"),
+			AppError::InvalidCredentials => write!(f, "invalid credentials"),
 			AppError::Unauthorized => write!(f, "unauthorized"),
+			AppError::Forbidden => write!(f, "forbidden"),
+			AppError::EmailNotVerified => write!(f, "email not verified"),
 			AppError::NotFound => write!(f, "not found"),
+			AppError::ChatSessionNotFound => write!(f, "chat session not found"),
+			AppError::MessageNotFound => write!(f, "message not found"),
+			AppError::ItineraryNotFound => write!(f, "itinerary not found"),
+			AppError::EventNotFound => write!(f, "event not found"),
 			AppError::Conflict(m) => write!(f, "conflict: {m}"),
+			AppError::DuplicateEmail => write!(f, "email already in use"),
+			AppError::PipelineBusy(m) => write!(f, "pipeline busy: {m}"),
+			AppError::TooManyRequests(s) => write!(f, "too many requests, retry after {s}s"),
+			AppError::ServiceUnavailable(m) => write!(f, "service unavailable: {m}"),
 			AppError::Internal(m) => write!(f, "internal error: {m}"),
 		}
 	}
@@ -77,7 +257,16 @@ impl std::error::Error for AppError {}
 #[cfg(not(tarpaulin_include))]
 impl From<sqlx::Error> for AppError {
 	fn from(e: sqlx::Error) -> Self {
-		AppError::Internal(format!("db error: {e:?}"))
+		match e {
+			sqlx::Error::RowNotFound => AppError::NotFound,
+			sqlx::Error::Database(ref db) if db.is_unique_violation() => {
+				AppError::Conflict(format!(
+					"already exists ({})",
+					db.constraint().unwrap_or("unique constraint")
+				))
+			}
+			_ => AppError::Internal(format!("db error: {e:?}")),
+		}
 	}
 }
 #[cfg(not(tarpaulin_include))]
@@ -102,8 +291,17 @@ impl From<std::env::VarError> for AppError {
 #[cfg(not(tarpaulin_include))]
 impl IntoResponse for AppError {
 	fn into_response(self) -> Response {
-		// Always log; return only status code
+		// Always log; the body has the code, and the message for public errors
 		self.log();
-		self.status_code().into_response()
+		let body = axum::Json(self.body());
+		match self {
+			AppError::TooManyRequests(s) => (
+				self.status_code(),
+				[(header::RETRY_AFTER, s.to_string())],
+				body,
+			)
+				.into_response(),
+			_ => (self.status_code(), body).into_response(),
+		}
 	}
 }
@@ -0,0 +1,92 @@
+/*
+ * src/geocoding.rs
+ *
+ * File for address geocoding
+ *
+ * Purpose:
+ *   Look up coordinates for user-created events that only have an address,
+ *   using OpenStreetMap's Nominatim within its usage policy.
+ */
+
+use once_cell::sync::Lazy;
+use serde::Deserialize;
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+use tracing::warn;
+
+use crate::global::{GEOCODING_ENABLED, NOMINATIM_MIN_INTERVAL_SECONDS, NOMINATIM_TIMEOUT_SECONDS};
+
+const NOMINATIM_SEARCH_URL: &str = "https://nominatim.openstreetmap.org/search";
+/// Nominatim refuses requests that don't identify the application
+const NOMINATIM_USER_AGENT: &str = "Journey/1.0 (trip planner)";
+
+/// When the latest Nominatim request is sent. Each lookup books the next free slot, so
+/// concurrent lookups queue up instead of going over the rate limit
+static LAST_REQUEST: Lazy<Mutex<Option<Instant>>> = Lazy::new(|| Mutex::new(None));
+
+static CLIENT: Lazy<reqwest::Client> = Lazy::new(|| {
+	reqwest::Client::builder()
+		.timeout(Duration::from_secs(NOMINATIM_TIMEOUT_SECONDS))
+		.build()
+		.expect("Nominatim client should build")
+});
+
+/// A Nominatim search result, which gives coordinates as strings
+#[derive(Deserialize)]
+struct NominatimPlace {
+	lat: String,
+	lon: String,
+}
+
+/// Whether the `GEOCODING_ENABLED` env var is `1` or `true`, it's disabled by default
+pub fn geocoding_enabled() -> bool {
+	std::env::var(GEOCODING_ENABLED).is_ok_and(|v| v == "1" || v.eq_ignore_ascii_case("true"))
+}
+
+/// Coordinates of the first Nominatim result for the address, `None` when geocoding is
+/// disabled, part of the address is missing, nothing matched or the lookup failed.
+/// * Requests are at least `NOMINATIM_MIN_INTERVAL_SECONDS` apart across the whole server
+/// * A lookup taking over `NOMINATIM_TIMEOUT_SECONDS` fails
+pub async fn geocode_address(
+	street_address: Option<&str>,
+	city: Option<&str>,
+	country: Option<&str>,
+) -> Option<(f64, f64)> {
+	if !geocoding_enabled() {
+		return None;
+	}
+	let (Some(street_address), Some(city), Some(country)) = (street_address, city, country) else {
+		return None;
+	};
+	let query = format!("{street_address}, {city}, {country}");
+
+	// Book a slot, then wait for it without holding the lock
+	let send_at = {
+		let mut last_request = LAST_REQUEST.lock().await;
+		let min_interval = Duration::from_secs(NOMINATIM_MIN_INTERVAL_SECONDS);
+		let now = Instant::now();
+		let send_at = last_request.map_or(now, |last| (last + min_interval).max(now));
+		*last_request = Some(send_at);
+		send_at
+	};
+	tokio::time::sleep_until(send_at.into()).await;
+	let response = CLIENT
+		.get(NOMINATIM_SEARCH_URL)
+		.header(reqwest::header::USER_AGENT, NOMINATIM_USER_AGENT)
+		.query(&[("format", "json"), ("limit", "1"), ("q", query.as_str())])
+		.send()
+		.await;
+
+	let places = match response {
+		Ok(response) => response.json::<Vec<NominatimPlace>>().await,
+		Err(e) => Err(e),
+	};
+	let place = match places {
+		Ok(places) => places.into_iter().next()?,
+		Err(e) => {
+			warn!(query = %query, error = %e, "Nominatim geocoding failed");
+			return None;
+		}
+	};
+	Some((place.lat.parse().ok()?, place.lon.parse().ok()?))
+}
@@ -1,11 +1,202 @@
+use once_cell::sync::Lazy;
+use std::sync::Arc;
+use tokio::sync::Semaphore;
+
 pub const LOG_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/logs");
 pub const CRASH_LOG: &str = "crash.log";
 pub const LATEST_LOG: &str = "latest.log";
 pub const TOOLS_LOG: &str = "tools.log";
+/// Size a log file can grow to before it's rolled over to a timestamped file
+pub const LOG_MAX_BYTES: u64 = 50 * 1024 * 1024;
+/// Rolled over files kept for each log, the oldest are deleted first
+pub const LOG_MAX_ROTATED_FILES: usize = 5;
 pub const DIST_DIR: &str = "frontend/dist";
 pub const MESSAGE_PAGE_LEN: i32 = 10;
+/// Latest messages of a chat the LLM is given to summarize it
+pub const CHAT_SUMMARY_MESSAGE_LEN: i64 = 20;
+/// Messages fetched at a time while streaming a `/api/chat/{id}/export` transcript
+pub const MESSAGE_EXPORT_CHUNK_LEN: i64 = 500;
 pub const EVENT_SEARCH_RESULT_LEN: i32 = 10;
+/// Longest trip an itinerary can span, in days from its start date to its end date
+pub const MAX_TRIP_DURATION_DAYS: i64 = 90;
+/// Most chat sessions an account can pin with `/api/chat/pin`
+pub const MAX_PINNED_CHATS: i64 = 5;
+/// Previous versions kept for each itinerary, older ones are deleted on save
+pub const ITINERARY_SNAPSHOT_LIMIT: i64 = 10;
+/// Longest title accepted by `/api/itinerary/{id}/title`, in characters
+pub const ITINERARY_TITLE_MAX_LEN: usize = 200;
+/// Default page size of `/api/itinerary/browse`
+pub const ITINERARY_BROWSE_PAGE_LEN: u32 = 20;
+/// Largest page size `/api/itinerary/browse` accepts, bigger ones are capped
+pub const ITINERARY_BROWSE_PAGE_MAX: u32 = 50;
+/// Days a deleted itinerary can be restored for before it's permanently deleted
+pub const ITINERARY_RETENTION_DAYS: i32 = 30;
+/// How often itineraries deleted over `ITINERARY_RETENTION_DAYS` ago are purged
+pub const ITINERARY_PURGE_INTERVAL_SECONDS: u64 = 60 * 60;
+/// Days a deleted message can be restored for before it's permanently deleted
+pub const MESSAGE_RETENTION_DAYS: i32 = 7;
+/// Longest comment accepted by `/api/events/{id}/review`, in characters
+pub const REVIEW_COMMENT_MAX_LEN: usize = 2000;
+/// Default number of events returned by `/api/events/popular`
+pub const POPULAR_EVENTS_LEN: u32 = 10;
+/// Most events `/api/events/popular` returns, bigger limits are capped
+pub const POPULAR_EVENTS_MAX: u32 = 50;
+/// Longest comment accepted by `/api/chat/feedback`, in characters
+pub const FEEDBACK_COMMENT_MAX_LEN: usize = 2000;
+/// Longest user message passed to the LLM, in characters, longer ones are truncated
+pub const MAX_MESSAGE_LENGTH: usize = 4000;
+/// Longest language tag stored as an account's `preferred_language`, matching the column
+pub const MAX_LANGUAGE_TAG_LENGTH: usize = 10;
+/// Language the LLM replies in when the user hasn't picked one
+pub const DEFAULT_LANGUAGE: &str = "en";
+/// Most 2-opt passes `compute_route` makes over a route before settling for it
+pub const MAX_2OPT_ITERATIONS: usize = 100;
+/// Routes with more points than this skip 2-opt and keep the nearest neighbor tour
+pub const MAX_ROUTE_EXACT_SIZE: usize = 12;
+/// Most events the optimizer keeps cached between pipeline runs
+pub const EVENT_CACHE_SIZE: usize = 1000;
+/// Estimated cost of an event for one person in USD for each Google `price_level`, from free to very expensive
+pub const AVG_PRICE_PER_LEVEL: [f64; 5] = [0.0, 15.0, 35.0, 75.0, 150.0];
+/// What a favorited event's ranking score is multiplied by, lower scores rank first
+pub const FAVORITE_SCORE_FACTOR: f64 = 0.5;
 pub const GOOGLE_MAPS_API_KEY: &str = "GOOGLE_MAPS_PRIVATE_API_KEY";
+/// Env var holding the Google Places key, the research agent can only fetch live venues when it is set
+pub const GOOGLE_PLACES_API_KEY: &str = "GOOGLE_PLACES_API_KEY";
+pub const GOOGLE_CLIENT_ID: &str = "GOOGLE_CLIENT_ID";
+pub const GOOGLE_CLIENT_SECRET: &str = "GOOGLE_CLIENT_SECRET";
+/// Env var holding the base64 encoded key private cookies are encrypted and signed with
+pub const COOKIE_KEY: &str = "COOKIE_KEY";
+/// Length in bytes of the decoded `COOKIE_KEY`
+pub const COOKIE_KEY_LEN: usize = 64;
+/// How long a password reset token stays valid after it is issued
+pub const PASSWORD_RESET_TOKEN_EXP_SECONDS: i64 = 60 * 60;
+/// Minimum time between password reset emails for the same account
+pub const PASSWORD_RESET_REQUEST_COOLDOWN_SECONDS: i64 = 60;
+/// How long an email verification token (signup or email change) stays valid after it is issued
+pub const EMAIL_VERIFICATION_TOKEN_EXP_SECONDS: i64 = 24 * 60 * 60;
+/// Minimum time between `last_seen` updates for a session
+pub const SESSION_LAST_SEEN_INTERVAL_SECONDS: i64 = 5 * 60;
+/// Failed login/signup attempts allowed per email within `AUTH_RATE_LIMIT_WINDOW_SECONDS`
+pub const AUTH_RATE_LIMIT_MAX_PER_EMAIL: u32 = 5;
+/// Failed login/signup attempts allowed per IP within `AUTH_RATE_LIMIT_WINDOW_SECONDS`
+pub const AUTH_RATE_LIMIT_MAX_PER_IP: u32 = 20;
+pub const AUTH_RATE_LIMIT_WINDOW_SECONDS: u64 = 15 * 60;
+/// Consecutive failed logins before the account is locked for `LOGIN_LOCKOUT_SECONDS`
+pub const MAX_LOGIN_ATTEMPTS: i32 = 10;
+/// How long logins are refused once `MAX_LOGIN_ATTEMPTS` is reached
+pub const LOGIN_LOCKOUT_SECONDS: i64 = 15 * 60;
+/// Number of events returned by `/api/account/activity`
+pub const AUTH_ACTIVITY_LEN: i64 = 20;
+/// Env var naming the directory uploaded profile pictures are stored in
+pub const PROFILE_PICTURE_DIR: &str = "PROFILE_PICTURE_DIR";
+/// Used when `PROFILE_PICTURE_DIR` isn't set
+pub const DEFAULT_PROFILE_PICTURE_DIR: &str = "uploads/profile_pictures";
+/// Largest profile picture that can be uploaded
+pub const PROFILE_PICTURE_MAX_BYTES: usize = 2 * 1024 * 1024;
+/// Largest request body accepted by any route, larger bodies get a 413
+/// * Uploads over this need their own sub-router with a higher limit
+pub const MAX_REQUEST_BODY_BYTES: usize = 5 * 1024 * 1024;
+/// Env var holding how many seconds the LLM pipeline gets to reply to a message
+pub const LLM_PIPELINE_TIMEOUT_SECONDS: &str = "LLM_PIPELINE_TIMEOUT_SECONDS";
+/// Used when `LLM_PIPELINE_TIMEOUT_SECONDS` isn't set
+pub const DEFAULT_LLM_PIPELINE_TIMEOUT_SECONDS: u64 = 3 * 60;
+/// Seconds the research, constraint and optimize agents each get before their reply is given up on
+pub const TOOL_TIMEOUT_SECS: u64 = 60;
+/// Most tool calls the agents can make replying to one message before the reply is replaced by
+/// an apology, so a model stuck calling tools in a loop doesn't get its output sent
+pub const MAX_TOOL_CALLS: u32 = 25;
+/// Most agent pipelines that run at once, messages past this wait for one to finish
+pub const MAX_CONCURRENT_AGENT_SESSIONS: usize = 16;
+/// How long a message waits for a free agent session before the server reports it's busy
+pub const SEMAPHORE_ACQUIRE_TIMEOUT_SECS: u64 = 30;
+/// Limits agent pipelines to `MAX_CONCURRENT_AGENT_SESSIONS`, so bursts of messages don't
+/// overwhelm the LLM provider or use up the database pool
+pub static AGENT_SEMAPHORE: Lazy<Arc<Semaphore>> =
+	Lazy::new(|| Arc::new(Semaphore::new(MAX_CONCURRENT_AGENT_SESSIONS)));
+/// How often a retried message request checks whether the original has a response yet
+pub const MESSAGE_REQUEST_POLL_INTERVAL_MS: u64 = 250;
+/// How long a chat's in-memory agent context is kept after its last message
+pub const CONTEXT_IDLE_TTL_SECONDS: u64 = 2 * 60 * 60;
+/// How often idle agent contexts are evicted
+pub const CONTEXT_EVICTION_INTERVAL_SECONDS: u64 = 5 * 60;
+/// Env var holding the comma separated CIDRs allowed to scrape `/api/metrics`
+pub const METRICS_ALLOWED_CIDRS: &str = "METRICS_ALLOWED_CIDRS";
+/// Used when `METRICS_ALLOWED_CIDRS` isn't set
+pub const DEFAULT_METRICS_ALLOWED_CIDRS: &str = "127.0.0.1/32,::1/128";
+/// Env var holding a bearer token that lets clients outside `METRICS_ALLOWED_CIDRS` scrape
+/// `/api/metrics`, only the allow-list is checked when it isn't set
+pub const METRICS_BEARER_TOKEN: &str = "METRICS_BEARER_TOKEN";
+/// Env vars configuring outgoing email, emails are only logged when `SMTP_HOST` isn't set
+pub const SMTP_HOST: &str = "SMTP_HOST";
+pub const SMTP_PORT: &str = "SMTP_PORT";
+pub const SMTP_USERNAME: &str = "SMTP_USERNAME";
+pub const SMTP_PASSWORD: &str = "SMTP_PASSWORD";
+/// Address emails are sent from, e.g. `Journey <no-reply@example.com>`
+pub const SMTP_FROM: &str = "SMTP_FROM";
+/// Least number of the four wheelchair accessibility features an event needs when the user lists a disability
+pub const MIN_ACCESSIBILITY_SCORE: u8 = 3;
+/// Env var naming the forecast service the optimizer uses, weather is ignored when it isn't set
+pub const WEATHER_PROVIDER: &str = "WEATHER_PROVIDER";
+/// Env var picking the LLM service, `openai` (default), `anthropic` or `ollama`
+pub const LLM_PROVIDER: &str = "LLM_PROVIDER";
+/// Env var naming the model agents prompt unless their own `*_LLM_MODEL` is set, each provider has a default
+pub const LLM_MODEL: &str = "LLM_MODEL";
+/// Env var naming the Orchestrator Agent's model
+pub const ORCHESTRATOR_LLM_MODEL: &str = "ORCHESTRATOR_LLM_MODEL";
+/// Env var naming the Task Agent's model
+pub const TASK_LLM_MODEL: &str = "TASK_LLM_MODEL";
+/// Env var naming the Research Agent's model
+pub const RESEARCH_LLM_MODEL: &str = "RESEARCH_LLM_MODEL";
+/// Env var naming the Constraint Agent's model
+pub const CONSTRAINT_LLM_MODEL: &str = "CONSTRAINT_LLM_MODEL";
+/// Env var naming the Optimize Agent's model
+pub const OPTIMIZE_LLM_MODEL: &str = "OPTIMIZE_LLM_MODEL";
+/// Env var with the key for the `anthropic` provider
+pub const ANTHROPIC_API_KEY: &str = "ANTHROPIC_API_KEY";
+/// Env var with the key for the `openai` provider
+pub const OPENAI_API_KEY: &str = "OPENAI_API_KEY";
+/// Env var with the OpenAI compatible endpoint of the `ollama` provider
+pub const OLLAMA_BASE_URL: &str = "OLLAMA_BASE_URL";
+/// Where Ollama serves its OpenAI compatible API when run locally
+pub const DEFAULT_OLLAMA_BASE_URL: &str = "http://localhost:11434/v1";
+/// Env var that turns on geocoding user events with an address but no coordinates, off unless `1` or `true`
+pub const GEOCODING_ENABLED: &str = "GEOCODING_ENABLED";
+/// Env var that switches `logs/latest.log` and `logs/crash.log` to JSON lines when set to `json`
+pub const LOG_FORMAT: &str = "LOG_FORMAT";
+/// Least time between Nominatim requests, their usage policy allows one per second
+pub const NOMINATIM_MIN_INTERVAL_SECONDS: u64 = 1;
+/// How long a Nominatim lookup may take before it's given up on
+pub const NOMINATIM_TIMEOUT_SECONDS: u64 = 10;
+/// How long a researched place's opening status is reused before research refreshes it
+pub const PLACE_REFRESH_HOURS: i32 = 24;
+/// Env var holding the most connections the database pool opens
+pub const DB_MAX_CONNECTIONS: &str = "DB_MAX_CONNECTIONS";
+/// Used when `DB_MAX_CONNECTIONS` isn't set
+pub const DEFAULT_DB_MAX_CONNECTIONS: u32 = 5;
+/// Env var holding how many idle connections the database pool keeps open
+pub const DB_MIN_CONNECTIONS: &str = "DB_MIN_CONNECTIONS";
+/// Used when `DB_MIN_CONNECTIONS` isn't set
+pub const DEFAULT_DB_MIN_CONNECTIONS: u32 = 0;
+/// Env var holding how many seconds a query waits for a free connection before failing
+pub const DB_ACQUIRE_TIMEOUT_SECONDS: &str = "DB_ACQUIRE_TIMEOUT_SECONDS";
+/// Used when `DB_ACQUIRE_TIMEOUT_SECONDS` isn't set
+pub const DEFAULT_DB_ACQUIRE_TIMEOUT_SECONDS: u64 = 30;
+/// Env var holding how many milliseconds Postgres lets a statement run, `0` for no limit
+pub const DB_STATEMENT_TIMEOUT_MS: &str = "DB_STATEMENT_TIMEOUT_MS";
+/// Used when `DB_STATEMENT_TIMEOUT_MS` isn't set
+pub const DEFAULT_DB_STATEMENT_TIMEOUT_MS: u64 = 30 * 1000;
+/// Env var holding how many milliseconds a query can take before it's logged as slow
+pub const DB_SLOW_QUERY_MS: &str = "DB_SLOW_QUERY_MS";
+/// Used when `DB_SLOW_QUERY_MS` isn't set
+pub const DEFAULT_DB_SLOW_QUERY_MS: u64 = 1000;
+/// Shown for the server's connections in `pg_stat_activity`
+pub const DB_APPLICATION_NAME: &str = "journey";
 
 #[cfg(test)]
 pub const TEST_COOKIE_EXP_SECONDS: i64 = 60;
+#[cfg(test)]
+pub const TEST_AUTH_RATE_LIMIT_WINDOW_SECONDS: u64 = 10;
+#[cfg(test)]
+pub const TEST_LLM_PIPELINE_TIMEOUT_SECONDS: u64 = 5;
+#[cfg(test)]
+pub const TEST_TOOL_TIMEOUT_SECS: u64 = 1;
@@ -7,7 +7,12 @@
  *   Strongly-typed models for the `accounts` table
  */
 
-use crate::sql_models::{BudgetBucket, RiskTolerence};
+use crate::global::MAX_LANGUAGE_TAG_LENGTH;
+use crate::http_models::event::Event;
+use crate::sql_models::{
+	AuthEventType, BudgetBucket, Interest, RiskTolerence, account::NotificationPreferences,
+};
+use chrono::{DateTime, Utc};
 use regex::Regex;
 use serde::{Deserialize, Serialize};
 use utoipa::{ToResponse, ToSchema};
@@ -33,11 +38,44 @@ pub struct SignupRequest {
 	pub password: String,
 }
 
+/// Request payload for POST `/api/account/oauth/google`.
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct GoogleAuthRequest {
+	/// Authorization code returned to the frontend by Google's consent screen
+	pub code: String,
+	/// Redirect URI used to obtain `code`, must match the one registered with Google
+	pub redirect_uri: String,
+}
+
+/// Request payload for POST `/api/account/forgotPassword`.
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct ForgotPasswordRequest {
+	/// Email of the account to reset
+	pub email: String,
+}
+
+/// Request payload for POST `/api/account/resetPassword`.
+#[derive(Deserialize, ToSchema)]
+pub struct ResetPasswordRequest {
+	/// Token emailed by `/api/account/forgotPassword`
+	pub token: String,
+	/// New plaintext password, validated like signup passwords
+	pub new_password: String,
+}
+
+/// Query parameters for GET `/api/account/verifyEmail`.
+#[derive(Deserialize, ToSchema)]
+pub struct VerifyEmailQuery {
+	/// Token emailed to the pending address by `/api/account/update`
+	pub token: String,
+}
+
 /// Request payload for POST `/api/account/update`.
 /// - Only `Some` fields are updated.
 #[derive(Debug, Deserialize, ToSchema)]
 pub struct UpdateRequest {
 	/// Optional new email
+	/// * Not applied until verified through `/api/account/verifyEmail`
 	pub email: Option<String>,
 	/// Optional new first name
 	pub first_name: Option<String>,
@@ -57,13 +95,177 @@ pub struct UpdateRequest {
 	/// Optional new disabilites
 	/// * String is a comma-separated list of preferences
 	pub disabilities: Option<String>,
+	/// Optional new interests, replacing the current ones
+	/// * Kept as strings so unknown values can be rejected with a 400, see [UpdateRequest::parse_interests]
+	#[schema(value_type = Option<Vec<Interest>>)]
+	#[serde(default)]
+	pub interests: Option<Vec<String>>,
+	/// Optional notification preferences, only the given fields are changed
+	#[serde(default)]
+	pub notification_preferences: Option<NotificationPreferencesUpdate>,
 	/// Optional new profile pic
 	pub profile_picture: Option<String>,
+	/// Optional new language for the LLM's replies, e.g. "en" or "pt-BR"
+	/// * Checked by [UpdateRequest::validate_language]
+	#[serde(default)]
+	pub preferred_language: Option<String>,
+}
+
+/// Request payload for PATCH `/api/account/preferences`.
+/// - Only `Some` fields are updated, no password is needed.
+#[derive(Debug, Default, Deserialize, ToSchema)]
+pub struct PreferencesRequest {
+	/// Optional new budget enum
+	pub budget_preference: Option<BudgetBucket>,
+	/// Optional new risk enum
+	pub risk_preference: Option<RiskTolerence>,
+	/// Optional new food and allergies preferences
+	/// * String is a comma-separated list of preferences
+	pub food_allergies: Option<String>,
+	/// Optional new disabilites
+	/// * String is a comma-separated list of preferences
+	pub disabilities: Option<String>,
+}
+
+/// Partial [NotificationPreferences] for POST `/api/account/update`.
+/// - Only `Some` fields are updated, unknown fields are rejected.
+#[derive(Debug, Default, Serialize, Deserialize, ToSchema)]
+#[serde(deny_unknown_fields)]
+pub struct NotificationPreferencesUpdate {
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub trip_reminders: Option<bool>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub share_notifications: Option<bool>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub marketing: Option<bool>,
+}
+
+impl UpdateRequest {
+	/// Parse `interests` into [Interest]s, naming the first unknown value on failure
+	pub fn parse_interests(&self) -> Result<Option<Vec<Interest>>, String> {
+		self.interests
+			.as_ref()
+			.map(|interests| {
+				interests
+					.iter()
+					.map(|interest| {
+						serde_json::from_value(serde_json::Value::String(interest.clone()))
+							.map_err(|_| format!("Unknown interest: {}", interest))
+					})
+					.collect()
+			})
+			.transpose()
+	}
+
+	/// Validate `preferred_language` if one is given
+	/// - 1 to `MAX_LANGUAGE_TAG_LENGTH` characters
+	/// - Only ASCII letters, digits and hyphens
+	pub fn validate_language(&self) -> Result<(), String> {
+		match &self.preferred_language {
+			Some(language) if !is_language_tag(language) => {
+				Err(format!("Invalid language: {}", language))
+			}
+			_ => Ok(()),
+		}
+	}
+}
+
+/// Whether `tag` looks like a language tag we can store, e.g. "en" or "pt-BR"
+pub fn is_language_tag(tag: &str) -> bool {
+	!tag.is_empty()
+		&& tag.len() <= MAX_LANGUAGE_TAG_LENGTH
+		&& tag.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+}
+
+/// Request payload for POST `/api/account/apiKey`.
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct ApiKeyRequest {
+	/// Label to tell keys apart, e.g. "CI"
+	pub name: String,
+}
+
+/// API route response for POST `/api/account/apiKey`.
+#[derive(Debug, Serialize, ToSchema, ToResponse)]
+pub struct NewApiKeyResponse {
+	pub id: i32,
+	pub name: String,
+	/// Plaintext key to send in the `X-API-Key` header
+	/// * Only ever returned here, it can't be recovered later
+	pub key: String,
+}
+
+/// An API key with its secret masked out.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ApiKeyInfo {
+	pub id: i32,
+	pub name: String,
+	/// Key with the secret part hidden, e.g. `12.********`
+	pub masked_key: String,
+	pub created_at: DateTime<Utc>,
+	pub last_used_at: Option<DateTime<Utc>>,
+}
+
+/// API route response for GET `/api/account/apiKeys`.
+#[derive(Debug, Serialize, ToSchema, ToResponse)]
+pub struct ApiKeysResponse {
+	/// Active keys belonging to the user, newest first
+	pub api_keys: Vec<ApiKeyInfo>,
+}
+
+/// A signed in session, i.e. one `auth-token` cookie.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct SessionInfo {
+	pub id: i32,
+	/// User agent of the client that signed in
+	pub user_agent: Option<String>,
+	pub created_at: DateTime<Utc>,
+	/// Last time the session made a request, accurate to a few minutes
+	pub last_seen: DateTime<Utc>,
+	/// Whether this is the session making the request
+	pub current: bool,
+}
+
+/// API route response for GET `/api/account/sessions`.
+#[derive(Debug, Serialize, ToSchema, ToResponse)]
+pub struct SessionsResponse {
+	/// Unexpired, unrevoked sessions, most recently seen first
+	pub sessions: Vec<SessionInfo>,
+}
+
+/// One entry in the user's auth audit log.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct AuthEvent {
+	pub event_type: AuthEventType,
+	/// IP address the request came from, if known
+	pub ip: Option<String>,
+	pub user_agent: Option<String>,
+	pub created_at: DateTime<Utc>,
+}
+
+/// API route response for POST `/api/account/profilePicture`.
+#[derive(Debug, Serialize, ToSchema, ToResponse)]
+pub struct ProfilePictureResponse {
+	/// URL the uploaded picture is served from
+	pub url: String,
+}
+
+/// API route response for GET `/api/account/activity`.
+#[derive(Debug, Serialize, ToSchema, ToResponse)]
+pub struct ActivityResponse {
+	/// Most recent events first
+	pub events: Vec<AuthEvent>,
+}
+
+/// API route response for GET `/api/account/favorites`.
+#[derive(Debug, Serialize, ToSchema, ToResponse)]
+pub struct FavoritesResponse {
+	/// Most recently favorited first
+	pub events: Vec<Event>,
 }
 
 /// API route response for POST `/api/account/update`.
 /// - Contains full updated account profile for convenience.
-#[derive(Serialize, ToSchema, ToResponse)]
+#[derive(Debug, Serialize, ToSchema, ToResponse)]
 pub struct UpdateResponse {
 	/// Current email
 	pub email: String,
@@ -81,8 +283,30 @@ pub struct UpdateResponse {
 	/// Optional disabilites
 	/// * String is a comma-separated list of preferences
 	pub disabilities: String,
+	/// Interests used to rank events
+	pub interests: Vec<Interest>,
+	/// Which notifications the user has opted into
+	#[schema(value_type = NotificationPreferences)]
+	pub notification_preferences: sqlx::types::Json<NotificationPreferences>,
 	/// Optional new profile pic
 	pub profile_picture: Option<String>,
+	/// Language the LLM replies in
+	pub preferred_language: String,
+}
+
+/// API route response for PATCH `/api/account/preferences`.
+#[derive(Debug, Serialize, ToSchema, ToResponse)]
+pub struct PreferencesResponse {
+	/// Optional budget enum
+	pub budget_preference: Option<BudgetBucket>,
+	/// Optional risk enum
+	pub risk_preference: Option<RiskTolerence>,
+	/// Food and allergies preferences
+	/// * String is a comma-separated list of preferences
+	pub food_allergies: String,
+	/// Disabilites
+	/// * String is a comma-separated list of preferences
+	pub disabilities: String,
 }
 
 /// API route response for GET `/api/account/current`.
@@ -105,8 +329,15 @@ pub struct CurrentResponse {
 	/// Optional food and allergies preferences
 	/// should this have option encoded...
 	pub disabilities: String,
+	/// Interests used to rank events
+	pub interests: Vec<Interest>,
+	/// Which notifications the user has opted into
+	#[schema(value_type = NotificationPreferences)]
+	pub notification_preferences: sqlx::types::Json<NotificationPreferences>,
 	/// Optional new profile pic
 	pub profile_picture: Option<String>,
+	/// Language the LLM replies in
+	pub preferred_language: String,
 }
 
 impl SignupRequest {
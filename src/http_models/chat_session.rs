@@ -10,13 +10,60 @@ pub struct ChatsResponse {
 	pub chat_sessions: Vec<ChatSessionRow>,
 }
 
-/// Response model from the `/api/chat/newChat` endpoint
+/// Orderings for the `/api/chat/chats` endpoint
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ChatSort {
+	/// Most recently active chats first, falling back to when the chat was created
+	LastMessageAt,
+}
+
+/// Query parameters for the `/api/chat/chats` endpoint, pinned chats always come first
+#[derive(Default, Deserialize, ToSchema)]
+pub struct ChatsQuery {
+	/// Chats with the latest messages come first if omitted, then empty chats in creation order
+	pub sort: Option<ChatSort>,
+	/// Also return archived chats
+	#[serde(default)]
+	pub include_archived: bool,
+}
+
+/// File formats for the `/api/chat/{id}/export` endpoint
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ExportFormat {
+	/// An array of [crate::http_models::message::ExportedMessage]
+	#[default]
+	Json,
+	/// User and bot turns with timestamps and itinerary summaries, also accepted as `md`
+	#[serde(alias = "md")]
+	Markdown,
+	/// One `[User]` or `[Bot]` line per message with its timestamp
+	Txt,
+}
+
+/// Query parameters for the `/api/chat/{id}/export` endpoint
+#[derive(Default, Deserialize, ToSchema)]
+pub struct ExportQuery {
+	/// JSON if omitted
+	#[serde(default)]
+	pub format: ExportFormat,
+}
+
+/// Response model from the `/api/chat/newChat` and `/api/chat/branch` endpoints
 #[derive(Serialize, ToSchema, ToResponse)]
 pub struct NewChatResponse {
-	/// this chat session is guaranteed to not have any messages in it
+	/// from `/newChat` this chat session is guaranteed to not have any messages in it
 	pub chat_session_id: i32,
 }
 
+/// Request model for the `/api/chat/branch` endpoint
+#[derive(Deserialize, ToSchema)]
+pub struct BranchRequest {
+	/// The last message copied into the new chat, along with every message before it
+	pub from_message_id: i32,
+}
+
 /// Request model for the `/api/chat/rename` endpoint
 #[derive(Deserialize, ToSchema)]
 pub struct RenameRequest {
@@ -24,15 +71,71 @@ pub struct RenameRequest {
 	pub id: i32,
 }
 
+/// Request model for the `/api/chat/pin` endpoint
+#[derive(Deserialize, ToSchema)]
+pub struct PinRequest {
+	pub id: i32,
+	/// Whether the chat should be pinned, at most `MAX_PINNED_CHATS` can be
+	pub pinned: bool,
+}
+
+/// Request model for the `/api/chat/archive` and `/api/chat/unarchive` endpoints
+#[derive(Deserialize, ToSchema)]
+pub struct ArchiveRequest {
+	pub chat_session_id: i32,
+}
+
 /// Request model for the `/api/chat/progress` endpoint
 #[derive(Deserialize, ToSchema)]
 pub struct ProgressRequest {
 	pub chat_session_id: i32,
 }
 
+/// Response model from the `/api/chat/{id}/summary` endpoint
+#[derive(Debug, Serialize, Deserialize, ToSchema, ToResponse)]
+pub struct ChatSummaryResponse {
+	/// What the chat is about in 2-3 sentences
+	pub summary: String,
+}
+
+/// Request model for the `/api/chat/regenerate` endpoint
+#[derive(Deserialize, ToSchema)]
+pub struct RegenerateRequest {
+	pub chat_session_id: i32,
+}
+
+/// Request model for the `/api/chat/cancel` endpoint
+#[derive(Deserialize, ToSchema)]
+pub struct CancelRequest {
+	pub chat_session_id: i32,
+}
+
 /// Response model from the `/api/chat/progress` endpoint
 #[derive(Debug, Serialize, ToSchema, ToResponse)]
 pub struct ProgressResponse {
 	pub progress: LlmProgress,
 	pub title: String,
+	/// Set while the chat is waiting for the user to answer a clarification question
+	pub clarification: Option<Clarification>,
+}
+
+/// What a clarification question asked the user for
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct Clarification {
+	/// Details the question asked for, e.g. `budget`
+	pub missing_info: Vec<String>,
+	/// Trip details already known when the question was asked
+	pub known: KnownTripDetails,
+}
+
+/// Trip details known when a clarification question was asked, to prefill a form with
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct KnownTripDetails {
+	pub destination: Option<String>,
+	/// YYYY-MM-DD
+	pub start_date: Option<String>,
+	/// YYYY-MM-DD
+	pub end_date: Option<String>,
+	/// In USD
+	pub budget: Option<f64>,
 }
@@ -1,4 +1,4 @@
-use chrono::{NaiveDate, NaiveDateTime};
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
 use google_maps::places_new::Place;
 use num_traits::ToPrimitive;
 use once_cell::sync::Lazy;
@@ -332,6 +332,9 @@ pub struct UserEventRequest {
 	pub postal_code: Option<i32>,
 	pub city: Option<String>,
 	pub country: Option<String>,
+	/// Looked up from the address when neither coordinate is given and geocoding is enabled
+	pub lat: Option<f64>,
+	pub lng: Option<f64>,
 	pub event_type: Option<String>,
 	pub event_description: Option<String>,
 	pub event_name: String,
@@ -384,9 +387,81 @@ pub struct SearchEventRequest {
 	pub hard_end_after: Option<NaiveDateTime>,
 	/// Search where timezone like ...
 	pub timezone: Option<String>,
+	/// Latitude of the point to search around, needs `lng` and `radius_km`
+	pub lat: Option<f64>,
+	/// Longitude of the point to search around, needs `lat` and `radius_km`
+	pub lng: Option<f64>,
+	/// Search where the event is at most this many km from `lat`, `lng`, nearest first.
+	/// Events without coordinates are left out.
+	pub radius_km: Option<f64>,
+	/// Free text matched against the name, description, city and types of the event, most
+	/// relevant first. Events matching any of the words are returned.
+	pub q: Option<String>,
+}
+
+/// Query parameters for the `/api/itinerary/searchEvent` endpoint
+#[derive(Debug, Default, Deserialize, ToSchema)]
+pub struct SearchEventQuery {
+	/// Also return the user's archived events
+	#[serde(default)]
+	pub include_archived: bool,
 }
 
 #[derive(Debug, Serialize, ToSchema, ToResponse)]
 pub struct SearchEventResponse {
-	pub events: Vec<Event>,
+	pub events: Vec<SearchEventResult>,
+}
+
+/// An event matching a `/api/itinerary/searchEvent` query
+#[derive(Debug, Serialize, ToSchema)]
+pub struct SearchEventResult {
+	#[serde(flatten)]
+	pub event: Event,
+	/// Distance in km from the searched point, only with a radius filter
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub distance_km: Option<f64>,
+	/// `ts_rank` relevance to `q`, higher is better, only with a `q`
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub relevance: Option<f32>,
+}
+
+/// Query parameters for GET `/api/events/popular`
+#[derive(Debug, Default, Deserialize, ToSchema)]
+pub struct PopularQuery {
+	/// Only events in this city, ignoring case
+	pub city: String,
+	/// Events to return, defaults to `POPULAR_EVENTS_LEN` and is capped at `POPULAR_EVENTS_MAX`
+	pub limit: Option<u32>,
+}
+
+/// Request model from POST /api/events/{id}/review
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct ReviewRequest {
+	/// 1 to 5 stars
+	pub rating: i16,
+	/// At most `REVIEW_COMMENT_MAX_LEN` characters
+	pub comment: Option<String>,
+}
+
+/// A user's review of an event
+#[derive(Debug, Serialize, Deserialize, ToSchema, ToResponse)]
+pub struct Review {
+	/// Primary key
+	pub id: i32,
+	/// First name of the account that wrote it
+	pub reviewer: String,
+	/// 1 to 5 stars
+	pub rating: i16,
+	pub comment: Option<String>,
+	/// When the review was last posted
+	pub created_at: DateTime<Utc>,
+}
+
+/// Response model from GET /api/events/{id}/reviews
+#[derive(Debug, Serialize, Deserialize, ToSchema, ToResponse)]
+pub struct ReviewsResponse {
+	/// Mean rating, or null when the event has no reviews
+	pub average_rating: Option<f64>,
+	/// Newest first
+	pub reviews: Vec<Review>,
 }
@@ -8,11 +8,15 @@
  *   used by itinerary routes.
  */
 
-use chrono::NaiveDate;
+use std::collections::{BTreeMap, HashSet};
+
+use chrono::{DateTime, NaiveDate, Utc};
 use serde::{Deserialize, Serialize};
 use utoipa::{ToResponse, ToSchema};
 
 use crate::http_models::event::Event;
+use crate::sql_models::TimeOfDay;
+use crate::util::geo::haversine_km;
 
 /// A complete itinerary with event details
 #[derive(Debug, Serialize, Deserialize, ToSchema, ToResponse)]
@@ -32,6 +36,10 @@ pub struct Itinerary {
 	pub title: String,
 	/// Events that are not assigned to any specific time slot
 	pub unassigned_events: Vec<Event>,
+	/// Whether the user starred it for quick access
+	/// * Ignored when saving, see `/api/itinerary/{id}/star`
+	#[serde(default)]
+	pub starred: bool,
 }
 
 /// A single day of events in an itinerary
@@ -62,9 +70,198 @@ pub struct SaveResponse {
 	pub id: i32,
 }
 
+/// A previous version of an itinerary, without its events
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct SnapshotInfo {
+	/// id to pass to `/api/itinerary/{id}/restore/{snapshot_id}`
+	pub id: i32,
+	/// When the save that replaced this version happened
+	pub saved_at: DateTime<Utc>,
+	/// Events in this version, scheduled or unassigned
+	pub event_count: usize,
+}
+
+/// API route response for GET `/api/itinerary/{id}/history`
+#[derive(Debug, Serialize, Deserialize, ToSchema, ToResponse)]
+pub struct HistoryResponse {
+	/// At most `ITINERARY_SNAPSHOT_LIMIT` versions, newest first
+	pub snapshots: Vec<SnapshotInfo>,
+}
+
+/// Response model from GET `/api/itinerary/{id}/stats`
+/// * Only counts scheduled events, not unassigned ones
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize, ToSchema, ToResponse)]
+pub struct ItineraryStats {
+	/// Number of scheduled events
+	pub total_events: i64,
+	/// Number of distinct cities the events are in, ignoring case
+	pub unique_cities: i64,
+	/// Straight-line distance between consecutive events with coordinates
+	pub estimated_distance_km: f64,
+	/// Sum of the low end of each event's price range
+	pub estimated_cost_low_usd: i64,
+	/// Sum of the high end of each event's price range
+	pub estimated_cost_high_usd: i64,
+	/// Number of events of each event type, e.g. `{ "Museums": 4 }`
+	pub event_type_counts: BTreeMap<String, i64>,
+}
+
+impl ItineraryStats {
+	/// Computes the stats of days of events, which must be in chronological order
+	/// with each time block sorted by `block_index`, as returned by the database.
+	pub fn from_event_days(event_days: &[EventDay]) -> Self {
+		let events = event_days.iter().flat_map(|day| {
+			day.morning_events
+				.iter()
+				.chain(&day.afternoon_events)
+				.chain(&day.evening_events)
+		});
+
+		let mut stats = Self::default();
+		let mut cities = HashSet::new();
+		let mut previous: Option<(f64, f64)> = None;
+		for event in events {
+			stats.total_events += 1;
+			if let Some(city) = event.city.as_deref().map(str::trim)
+				&& !city.is_empty()
+			{
+				cities.insert(city.to_lowercase());
+			}
+			if let (Some(lat), Some(lng)) = (event.lat, event.lng) {
+				if let Some(previous) = previous {
+					stats.estimated_distance_km += haversine_km(previous, (lat, lng));
+				}
+				previous = Some((lat, lng));
+			}
+			if let Some((low, high)) = event.price_level.and_then(price_range_usd) {
+				stats.estimated_cost_low_usd += low;
+				stats.estimated_cost_high_usd += high;
+			}
+			if let Some(event_type) = &event.event_type {
+				*stats
+					.event_type_counts
+					.entry(event_type.clone())
+					.or_default() += 1;
+			}
+		}
+		stats.unique_cities = cities.len() as i64;
+		stats
+	}
+}
+
+/// Rough cost in USD of one event at a Google Places price level
+/// * Free (0) and unknown levels cost nothing
+fn price_range_usd(price_level: i32) -> Option<(i64, i64)> {
+	match price_level {
+		1 => Some((0, 15)),
+		2 => Some((15, 50)),
+		3 => Some((50, 150)),
+		4 => Some((150, 500)),
+		_ => None,
+	}
+}
+
+/// Request model from PATCH /api/itinerary/{id}/title
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct TitleRequest {
+	/// The new title, at most `ITINERARY_TITLE_MAX_LEN` characters
+	pub title: String,
+}
+
+/// Response model from POST `/api/itinerary/{id}/share`
+#[derive(Debug, Serialize, Deserialize, ToSchema, ToResponse)]
+pub struct ShareResponse {
+	/// Link anyone can use to view the itinerary without logging in
+	pub share_url: String,
+}
+
+/// Query parameters for GET `/api/itinerary/saved`
+#[derive(Debug, Default, Deserialize, ToSchema)]
+pub struct SavedQuery {
+	/// Only starred itineraries when `true`, only the others when `false`
+	pub starred: Option<bool>,
+}
+
+/// Query parameters for GET `/api/itinerary/browse`
+#[derive(Debug, Default, Deserialize, ToSchema)]
+pub struct BrowseQuery {
+	/// Only itineraries whose title or event cities contain this, ignoring case
+	pub destination: Option<String>,
+	/// Page to return, starting at 0
+	#[serde(default)]
+	pub page: u32,
+	/// Itineraries per page, defaults to `ITINERARY_BROWSE_PAGE_LEN` and is capped at `ITINERARY_BROWSE_PAGE_MAX`
+	pub per_page: Option<u32>,
+}
+
+/// A public itinerary without its events or anything private about its author
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ItinerarySummary {
+	/// id to fetch or clone the itinerary with
+	pub id: i32,
+	pub title: String,
+	pub start_date: NaiveDate,
+	pub end_date: NaiveDate,
+	/// Events in the itinerary, scheduled or unassigned
+	pub event_count: i64,
+	pub author_first_name: String,
+}
+
+/// Response model from GET `/api/itinerary/browse`
+#[derive(Debug, Serialize, Deserialize, ToSchema, ToResponse)]
+pub struct BrowseResponse {
+	/// Newest first
+	pub itineraries: Vec<ItinerarySummary>,
+	/// Public itineraries matching the query across all pages
+	pub total_count: i64,
+}
+
 /// Request model from /api/itinerary/unsave
 #[derive(Debug, Deserialize, ToSchema)]
 pub struct UnsaveRequest {
 	/// itinerary id to unsave
 	pub id: i32,
 }
+
+/// Request model from PATCH /api/itinerary/{id}/reorder
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct ReorderRequest {
+	/// The day containing the time block (%Y-%m-%d)
+	pub date: NaiveDate,
+	/// The time block being reordered
+	pub time_of_day: TimeOfDay,
+	/// Every event id in the time block, in their new order
+	pub event_ids: Vec<i32>,
+}
+
+/// Request model from PATCH /api/itinerary/{id}/moveEvent
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct MoveEventRequest {
+	/// The event to move
+	pub event_id: i32,
+	/// The day the event is currently on (%Y-%m-%d)
+	pub from_date: NaiveDate,
+	/// The time block the event is currently in
+	pub from_time_of_day: TimeOfDay,
+	/// The day to move the event to (%Y-%m-%d)
+	pub to_date: NaiveDate,
+	/// The time block to move the event to
+	pub to_time_of_day: TimeOfDay,
+}
+
+/// Request model from POST /api/itinerary/merge
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct MergeRequest {
+	/// The itinerary whose days are added to the target, it's left as is
+	pub source_id: i32,
+	/// The itinerary the source's days are added to
+	pub target_id: i32,
+}
+
+/// Request model from POST /api/itinerary/{id}/regenerate
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct RegenerateItineraryRequest {
+	/// Events of the itinerary to keep exactly as they are, the rest is regenerated
+	#[serde(default)]
+	pub keep_event_ids: Vec<i32>,
+}
@@ -1,9 +1,12 @@
-use chrono::NaiveDateTime;
+use chrono::{DateTime, NaiveDateTime, Utc};
 use serde::{Deserialize, Serialize};
 use utoipa::{ToResponse, ToSchema};
+use uuid::Uuid;
+
+use crate::sql_models::{FeedbackRating, LlmProgress};
 
 /// A message in a chat session
-#[derive(Debug, Serialize, ToSchema, ToResponse)]
+#[derive(Debug, Serialize, Deserialize, ToSchema, ToResponse)]
 pub struct Message {
 	/// Primary key
 	pub id: i32,
@@ -37,6 +40,47 @@ pub struct MessagePageResponse {
 	pub prev_message_id: Option<i32>,
 }
 
+/// Request model for `/api/chat/searchMessages` endpoint
+#[derive(Deserialize, ToSchema)]
+pub struct SearchMessagesRequest {
+	/// Words to look for, in web search syntax e.g. `lisbon "day trip" -sintra`
+	pub query: String,
+	/// Only search this chat session, otherwise all of the user's chat sessions are searched
+	pub chat_session_id: Option<i32>,
+	/// Page of results to return, starting at 0
+	#[serde(default)]
+	pub page: u32,
+}
+
+/// A message matching a `/api/chat/searchMessages` query
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct MessageSearchResult {
+	pub chat_session_id: i32,
+	pub message_id: i32,
+	/// The part of the message around the matches, HTML escaped, with each match wrapped in `<b>` and `</b>`
+	pub snippet: String,
+	/// UTC timestamp the message was sent (%Y-%m-%d %H:%M:%S)
+	pub timestamp: NaiveDateTime,
+}
+
+/// Response model for `/api/chat/searchMessages` endpoint
+#[derive(Debug, Serialize, Deserialize, ToSchema, ToResponse)]
+pub struct SearchMessagesResponse {
+	/// At most `MESSAGE_PAGE_LEN` messages, best matches first
+	pub results: Vec<MessageSearchResult>,
+	/// Matching messages across all pages
+	pub total_count: i64,
+}
+
+/// A message in a `/api/chat/{id}/export` transcript
+#[derive(Serialize, ToSchema)]
+pub struct ExportedMessage {
+	#[serde(flatten)]
+	pub message: Message,
+	/// Title of the itinerary associated with this message, if it has one
+	pub itinerary_title: Option<String>,
+}
+
 /// Request model for `/api/chat/updateMessage` endpoint
 #[derive(Deserialize, ToSchema)]
 pub struct UpdateMessageRequest {
@@ -46,6 +90,17 @@ pub struct UpdateMessageRequest {
 	pub new_text: String,
 	/// A possible itinerary to give context to the LLM
 	pub itinerary_id: Option<i32>,
+	/// Unique id the client picks for this request, so a retry with it gets the original
+	/// response instead of the message being updated again
+	#[schema(value_type = Option<String>)]
+	pub client_request_id: Option<Uuid>,
+}
+
+/// Response model for the `/api/chat/message/{id}` and `/api/chat/message/{id}/restore` endpoints
+#[derive(Serialize, ToSchema, ToResponse)]
+pub struct MessageIdsResponse {
+	/// The user's message and the bot replies to it that were deleted or restored, in chronological order
+	pub message_ids: Vec<i32>,
 }
 
 /// Request model for `/api/chat/sendMessage` endpoint
@@ -57,13 +112,56 @@ pub struct SendMessageRequest {
 	pub text: String,
 	/// A possible itinerary to give context to the LLM
 	pub itinerary_id: Option<i32>,
+	/// Unique id the client picks for this request, so a retry with it gets the original
+	/// response instead of the message being sent again
+	#[schema(value_type = Option<String>)]
+	pub client_request_id: Option<Uuid>,
 }
 
 /// Response model for `/api/chat/sendMessage` endpoint
-#[derive(Debug, Serialize, ToSchema, ToResponse)]
+#[derive(Debug, Serialize, Deserialize, ToSchema, ToResponse)]
 pub struct SendMessageResponse {
 	/// The newly-created id of the message you just sent
 	pub user_message_id: i32,
 	/// The response message from the LLM
 	pub bot_message: Message,
+	/// Whether the LLM didn't reply in time, `bot_message` is then an apology and the message can be sent again
+	pub timed_out: bool,
+}
+
+/// Request model for `/api/chat/feedback` endpoint
+#[derive(Deserialize, ToSchema)]
+pub struct FeedbackRequest {
+	/// ID of the bot message to rate. It must be in a chat session which belongs to the user who made the request
+	pub message_id: i32,
+	pub rating: FeedbackRating,
+	/// Optional explanation, at most `FEEDBACK_COMMENT_MAX_LEN` characters
+	pub comment: Option<String>,
+}
+
+/// The user's feedback on a bot message
+#[derive(Debug, Serialize, Deserialize, ToSchema, ToResponse)]
+pub struct Feedback {
+	pub message_id: i32,
+	/// The itinerary the message referred to when it was rated
+	pub itinerary_id: Option<i32>,
+	pub rating: FeedbackRating,
+	pub comment: Option<String>,
+	/// When the feedback was last submitted
+	pub updated_at: DateTime<Utc>,
+}
+
+/// Feedback counts for bot messages that came out of one pipeline stage
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct FeedbackStageStats {
+	/// Stage the pipeline was at when the message was inserted, unknown for older messages
+	pub llm_progress: Option<LlmProgress>,
+	pub up: i64,
+	pub down: i64,
+}
+
+/// Response model for `/api/chat/feedbackStats` endpoint
+#[derive(Debug, Serialize, Deserialize, ToSchema, ToResponse)]
+pub struct FeedbackStatsResponse {
+	pub stages: Vec<FeedbackStageStats>,
 }
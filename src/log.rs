@@ -1,15 +1,19 @@
 use {
 	crate::global::*,
 	std::{
-		fs::{self, File},
-		io::{BufWriter, Write},
-		path::Path,
+		fs::{self, File, OpenOptions},
+		io::{self, BufWriter, Write},
+		path::{Path, PathBuf},
 		sync::{Once, OnceLock},
 	},
-	tracing::error,
-	tracing_appender::{non_blocking::NonBlocking, rolling},
+	tracing::{Subscriber, error},
+	tracing_appender::non_blocking::NonBlocking,
 	tracing_subscriber::{
-		EnvFilter, Layer, fmt::time::SystemTime, layer::SubscriberExt, util::SubscriberInitExt,
+		EnvFilter, Layer,
+		fmt::{MakeWriter, time::SystemTime},
+		layer::SubscriberExt,
+		registry::LookupSpan,
+		util::SubscriberInitExt,
 	},
 };
 
@@ -17,7 +21,109 @@ static INIT_LOG: Once = Once::new();
 static mut LOG_WRITER: OnceLock<NonBlocking> = OnceLock::new();
 static mut TOOLS_LOG_WRITER: OnceLock<NonBlocking> = OnceLock::new();
 
+/// A log file that is rolled over by [rotate_file] once writing to it would make it
+/// bigger than `max_bytes`, so the current log is always at the same path.
+pub struct RotatingFile {
+	dir: PathBuf,
+	file_name: String,
+	max_bytes: u64,
+	max_files: usize,
+	file: File,
+	len: u64,
+}
+
+impl RotatingFile {
+	/// Opens `dir/file_name` for appending, creating both if needed
+	pub fn new(
+		dir: impl AsRef<Path>,
+		file_name: &str,
+		max_bytes: u64,
+		max_files: usize,
+	) -> io::Result<Self> {
+		let dir = dir.as_ref().to_path_buf();
+		fs::create_dir_all(&dir)?;
+		let file = OpenOptions::new()
+			.create(true)
+			.append(true)
+			.open(dir.join(file_name))?;
+		let len = file.metadata()?.len();
+		Ok(Self {
+			dir,
+			file_name: file_name.to_string(),
+			max_bytes,
+			max_files,
+			file,
+			len,
+		})
+	}
+}
+
+impl Write for RotatingFile {
+	fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+		// A single write bigger than the limit still goes to a fresh file
+		if self.len > 0 && self.len + buf.len() as u64 > self.max_bytes {
+			self.file.flush()?;
+			rotate_file(&self.dir, &self.file_name, self.max_files)?;
+			self.file = File::create(self.dir.join(&self.file_name))?;
+			self.len = 0;
+		}
+		let written = self.file.write(buf)?;
+		self.len += written as u64;
+		Ok(written)
+	}
+
+	fn flush(&mut self) -> io::Result<()> {
+		self.file.flush()
+	}
+}
+
+/// Renames `dir/file_name` to `<name>.<timestamp>.<extension>`, then deletes the oldest of
+/// those rolled over files until at most `max_files` are left.
+/// * Does nothing if the file doesn't exist
+pub fn rotate_file(dir: &Path, file_name: &str, max_files: usize) -> io::Result<()> {
+	let path = dir.join(file_name);
+	if !path.exists() {
+		return Ok(());
+	}
+	let (name, extension) = file_name.rsplit_once('.').unwrap_or((file_name, "log"));
+	let timestamp = chrono::Local::now().format("%Y-%m-%dT%H-%M-%S%.6f");
+	let mut rotated = dir.join(format!("{name}.{timestamp}.{extension}"));
+	let mut attempt = 1;
+	while rotated.exists() {
+		rotated = dir.join(format!("{name}.{timestamp}-{attempt}.{extension}"));
+		attempt += 1;
+	}
+	fs::rename(&path, rotated)?;
+
+	// Timestamps sort in the order the files were rolled over
+	let (prefix, suffix) = (format!("{name}."), format!(".{extension}"));
+	let mut rotated_files: Vec<PathBuf> = fs::read_dir(dir)?
+		.filter_map(|entry| entry.ok())
+		.filter(|entry| {
+			let entry_name = entry.file_name().to_string_lossy().into_owned();
+			entry_name != file_name
+				&& entry_name.starts_with(&prefix)
+				&& entry_name.ends_with(&suffix)
+		})
+		.map(|entry| entry.path())
+		.collect();
+	rotated_files.sort();
+	let excess = rotated_files.len().saturating_sub(max_files);
+	for old in &rotated_files[..excess] {
+		fs::remove_file(old)?;
+	}
+	Ok(())
+}
+
+/// Whether the `LOG_FORMAT` env var is `json`, logs are human readable otherwise
+pub fn json_log_format() -> bool {
+	std::env::var(LOG_FORMAT).is_ok_and(|v| v.eq_ignore_ascii_case("json"))
+}
+
 /// When the program panics, the backtrace is outputted to `logs/crash.log`.
+/// The previous crash log is rolled over first, keeping `LOG_MAX_ROTATED_FILES` of them.
+///
+/// With [json_log_format] the crash log and the line printed to stdout are JSON objects.
 pub fn init_panic_handler() {
 	unsafe {
 		// Safety
@@ -30,24 +136,86 @@ pub fn init_panic_handler() {
 	}
 	std::panic::set_hook(Box::new(move |panic_info| {
 		const WRITE_ERR: &str = "Could not write to crash log";
+		let json = json_log_format();
+		let time = chrono::Local::now();
 		error!("{}", panic_info);
-		println!("{}", panic_info);
+		if json {
+			let line = serde_json::json!({
+				"timestamp": time.to_rfc3339(),
+				"level": "ERROR",
+				"message": panic_info.to_string(),
+			});
+			println!("{line}");
+		} else {
+			println!("{}", panic_info);
+		}
 
 		fs::create_dir_all(LOG_DIR).expect("Could create crash log");
+		_ = rotate_file(Path::new(LOG_DIR), CRASH_LOG, LOG_MAX_ROTATED_FILES);
 		let file = File::create(Path::new(LOG_DIR).join(CRASH_LOG))
 			.expect("Could not create crash log file");
 		let backtrace = std::backtrace::Backtrace::capture();
 		let mut writer = BufWriter::new(file);
 
-		writeln!(writer, "Time: {}", chrono::Local::now()).expect(WRITE_ERR);
-		writeln!(writer, "{panic_info}").expect(WRITE_ERR);
-		writeln!(writer, "stack backtrace:\n{backtrace}").expect(WRITE_ERR);
-		writeln!(writer, "Process finished with exit code 101").expect(WRITE_ERR);
+		if json {
+			let report = serde_json::json!({
+				"timestamp": time.to_rfc3339(),
+				"level": "ERROR",
+				"message": panic_info.to_string(),
+				"backtrace": backtrace.to_string(),
+				"exit_code": 101,
+			});
+			writeln!(writer, "{report}").expect(WRITE_ERR);
+		} else {
+			writeln!(writer, "Time: {time}").expect(WRITE_ERR);
+			writeln!(writer, "{panic_info}").expect(WRITE_ERR);
+			writeln!(writer, "stack backtrace:\n{backtrace}").expect(WRITE_ERR);
+			writeln!(writer, "Process finished with exit code 101").expect(WRITE_ERR);
+		}
 		writer.flush().expect(WRITE_ERR);
 	}));
 }
 
-/// Creates a tracing registry and adds a layer to it. Layer outputs to `logs/latest.log`.
+/// Layer writing the events let through by `RUST_LOG` to `writer`, as one JSON object per line
+/// when `json` is set and in the pretty format otherwise.
+/// * JSON lines have the event's fields, e.g. `chat_session_id` or `elapsed_ms`, at the top level
+/// * The fields of the spans the event is in, e.g. `request_id`, are under `span` and `spans`
+pub fn latest_log_layer<S, W>(writer: W, json: bool) -> Box<dyn Layer<S> + Send + Sync>
+where
+	S: Subscriber + for<'a> LookupSpan<'a> + 'static,
+	W: for<'w> MakeWriter<'w> + Send + Sync + 'static,
+{
+	let layer = tracing_subscriber::fmt::layer()
+		.with_timer(SystemTime)
+		.with_ansi(false)
+		.log_internal_errors(true)
+		.with_target(true)
+		.with_file(true)
+		.with_line_number(true)
+		.with_level(true)
+		.with_thread_names(true)
+		.with_thread_ids(true)
+		.with_writer(writer);
+	if json {
+		layer
+			.json()
+			.flatten_event(true)
+			.with_current_span(true)
+			.with_span_list(true)
+			.with_filter(EnvFilter::from_default_env())
+			.boxed()
+	} else {
+		layer
+			.pretty()
+			.with_filter(EnvFilter::from_default_env())
+			.boxed()
+	}
+}
+
+/// Creates a tracing registry and adds a layer to it. Layer outputs to `logs/latest.log`,
+/// see [latest_log_layer] for the format chosen by [json_log_format].
+///
+/// `logs/latest.log` and `logs/tools.log` are [RotatingFile]s limited to `LOG_MAX_BYTES`.
 ///
 /// See `.env` variable `RUST_LOG` for layer filter. These variables should be loaded into the environment for the filter to work.
 /// See [dotenvy].
@@ -58,25 +226,16 @@ pub fn init_logger() {
 		_ = fs::remove_file(Path::new(LOG_DIR).join(TOOLS_LOG));
 
 		// Setup main log writer
-		let (log_writer, log_guard) =
-			tracing_appender::non_blocking(rolling::never(LOG_DIR, LATEST_LOG));
-		let latest_log_layer = tracing_subscriber::fmt::layer()
-			.with_timer(SystemTime)
-			.with_ansi(false)
-			.log_internal_errors(true)
-			.with_target(true)
-			.with_file(true)
-			.with_line_number(true)
-			.with_level(true)
-			.with_thread_names(true)
-			.with_thread_ids(true)
-			.pretty()
-			.with_writer(log_writer.clone())
-			.with_filter(EnvFilter::from_default_env());
+		let latest_log =
+			RotatingFile::new(LOG_DIR, LATEST_LOG, LOG_MAX_BYTES, LOG_MAX_ROTATED_FILES)
+				.expect("Could not open latest log");
+		let (log_writer, log_guard) = tracing_appender::non_blocking(latest_log);
+		let latest_log_layer = latest_log_layer(log_writer.clone(), json_log_format());
 
 		// Setup tools log writer (only captures tool_trace target)
-		let (tools_log_writer, tools_guard) =
-			tracing_appender::non_blocking(rolling::never(LOG_DIR, TOOLS_LOG));
+		let tools_log = RotatingFile::new(LOG_DIR, TOOLS_LOG, LOG_MAX_BYTES, LOG_MAX_ROTATED_FILES)
+			.expect("Could not open tools log");
+		let (tools_log_writer, tools_guard) = tracing_appender::non_blocking(tools_log);
 		let tools_log_layer = tracing_subscriber::fmt::layer()
 			.with_timer(SystemTime)
 			.with_ansi(false)
@@ -119,25 +278,49 @@ pub fn log_writer() -> &'static mut NonBlocking {
 
 /// Macro for logging tool calls to tools.log with a simple stack trace format
 /// Usage: tool_trace!(agent: "orchestrator", tool: "route_task", status: "start", details: "task_type=research")
+///
+/// Lines logged while a request is handled end with its `request_id=...`, see
+/// [crate::middleware::current_request_id].
 #[macro_export]
 macro_rules! tool_trace {
 	(agent: $agent:expr, tool: $tool:expr, status: $status:expr) => {
-		tracing::info!(
-			target: "tool_trace",
-			"[{}] {} | {}",
-			$agent,
-			$tool,
-			$status
-		);
+		match $crate::middleware::current_request_id() {
+			Some(request_id) => tracing::info!(
+				target: "tool_trace",
+				"[{}] {} | {} | request_id={}",
+				$agent,
+				$tool,
+				$status,
+				request_id
+			),
+			None => tracing::info!(
+				target: "tool_trace",
+				"[{}] {} | {}",
+				$agent,
+				$tool,
+				$status
+			),
+		}
 	};
 	(agent: $agent:expr, tool: $tool:expr, status: $status:expr, details: $details:expr) => {
-		tracing::info!(
-			target: "tool_trace",
-			"[{}] {} | {} | {}",
-			$agent,
-			$tool,
-			$status,
-			$details
-		);
+		match $crate::middleware::current_request_id() {
+			Some(request_id) => tracing::info!(
+				target: "tool_trace",
+				"[{}] {} | {} | {} | request_id={}",
+				$agent,
+				$tool,
+				$status,
+				$details,
+				request_id
+			),
+			None => tracing::info!(
+				target: "tool_trace",
+				"[{}] {} | {} | {}",
+				$agent,
+				$tool,
+				$status,
+				$details
+			),
+		}
 	};
 }
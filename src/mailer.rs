@@ -0,0 +1,110 @@
+/*
+ * src/mailer.rs
+ *
+ * File for outgoing email delivery
+ *
+ * Purpose:
+ *   Abstract email delivery behind a trait so controllers can send mail
+ *   without depending on an SMTP server (and so tests can capture it).
+ */
+
+use crate::error::AppError;
+use crate::global::{SMTP_FROM, SMTP_HOST, SMTP_PASSWORD, SMTP_PORT, SMTP_USERNAME};
+use lettre::{
+	AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor, message::Mailbox,
+	transport::smtp::authentication::Credentials,
+};
+use std::sync::Arc;
+use tracing::{Instrument, error, info};
+
+/// Something that can deliver an email to a user.
+pub trait Mailer: Send + Sync {
+	fn send(&self, to: &str, subject: &str, body: &str) -> Result<(), AppError>;
+}
+
+/// Shared mailer handed to controllers through an [axum::Extension].
+pub type SharedMailer = Arc<dyn Mailer>;
+
+/// Writes outgoing emails to the `mailer` log target instead of sending them.
+///
+/// Stand-in until real email delivery is configured.
+pub struct LogMailer;
+
+impl Mailer for LogMailer {
+	fn send(&self, to: &str, subject: &str, body: &str) -> Result<(), AppError> {
+		info!(
+			target: "mailer",
+			to = to,
+			subject = subject,
+			body = body,
+			"Outgoing email"
+		);
+		Ok(())
+	}
+}
+
+/// Sends emails through an SMTP relay configured with the `SMTP_*` env vars.
+pub struct SmtpMailer {
+	transport: AsyncSmtpTransport<Tokio1Executor>,
+	from: Mailbox,
+}
+
+impl SmtpMailer {
+	/// Build the mailer from the environment, `None` when `SMTP_HOST` isn't set.
+	///
+	/// Panics if the SMTP env vars are set but invalid.
+	pub fn from_env() -> Option<Self> {
+		let host = std::env::var(SMTP_HOST).ok()?;
+		let from = std::env::var(SMTP_FROM)
+			.unwrap_or_else(|_| panic!("{SMTP_FROM} must be set when {SMTP_HOST} is"))
+			.parse::<Mailbox>()
+			.unwrap_or_else(|e| panic!("Invalid {SMTP_FROM}: {e}"));
+
+		let mut builder = AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&host)
+			.unwrap_or_else(|e| panic!("Invalid {SMTP_HOST}: {e}"));
+		if let Ok(port) = std::env::var(SMTP_PORT) {
+			builder = builder.port(
+				port.parse()
+					.unwrap_or_else(|e| panic!("Invalid {SMTP_PORT}: {e}")),
+			);
+		}
+		if let (Ok(username), Ok(password)) =
+			(std::env::var(SMTP_USERNAME), std::env::var(SMTP_PASSWORD))
+		{
+			builder = builder.credentials(Credentials::new(username, password));
+		}
+
+		Some(Self {
+			transport: builder.build(),
+			from,
+		})
+	}
+}
+
+impl Mailer for SmtpMailer {
+	/// Builds the email and delivers it in the background so requests don't wait on the relay.
+	fn send(&self, to: &str, subject: &str, body: &str) -> Result<(), AppError> {
+		let to_mailbox = to
+			.parse::<Mailbox>()
+			.map_err(|e| AppError::Internal(format!("invalid recipient {to}: {e}")))?;
+		let message = Message::builder()
+			.from(self.from.clone())
+			.to(to_mailbox)
+			.subject(subject)
+			.body(body.to_string())
+			.map_err(|e| AppError::Internal(format!("email build error: {e}")))?;
+
+		let transport = self.transport.clone();
+		let to = to.to_string();
+		tokio::spawn(
+			async move {
+				if let Err(e) = transport.send(message).await {
+					error!(target: "mailer", to = to, "Failed to send email: {e}");
+				}
+			}
+			// keeps the id of the request that sent it
+			.in_current_span(),
+		);
+		Ok(())
+	}
+}
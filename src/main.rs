@@ -2,10 +2,16 @@
 
 mod controllers;
 mod db;
+mod geocoding;
 mod http_models;
 mod log;
+mod mailer;
 mod middleware;
+mod notifications;
+mod oauth;
+mod rate_limit;
 mod sql_models;
+mod util;
 
 #[cfg(not(tarpaulin_include))]
 mod agent;
@@ -22,16 +28,17 @@ mod tests;
 use crate::controllers::AxumRouter;
 use crate::global::*;
 use crate::http_models::event::{REGEX_COUNTRY, REGEX_LOCALITY, REGEX_POST_CODE, REGEX_ST_ADDR};
-use axum::{Extension, routing::get_service};
+use axum::{Extension, extract::DefaultBodyLimit, routing::get_service};
 use http::{Method, header::HeaderValue};
 use std::env;
 use std::net::SocketAddr;
 use std::path::Path;
 use std::str::FromStr;
+use tower::ServiceBuilder;
 use tower_cookies::CookieManagerLayer;
-use tower_cookies::cookie::Key;
 use tower_http::{
 	cors::CorsLayer,
+	limit::RequestBodyLimitLayer,
 	services::{ServeDir, ServeFile},
 };
 
@@ -57,12 +64,30 @@ async fn main() -> std::result::Result<(), Box<dyn std::error::Error>> {
 		once_cell::sync::Lazy::force(&REGEX_LOCALITY);
 		once_cell::sync::Lazy::force(&REGEX_POST_CODE);
 		once_cell::sync::Lazy::force(&REGEX_COUNTRY);
+		// Fail at startup rather than on the first scrape if the allow-list is invalid
+		once_cell::sync::Lazy::force(&middleware::metrics::ALLOWED_CIDRS);
+		middleware::metrics::register_pipeline_metrics();
+
+		// Warn about agents falling back to LLM_MODEL
+		agent::validate_env();
 
 		// Initialize the AI agent
-		// The agent will use MockLLM when DEPLOY_LLM != "1", so creation should always succeed
-		let (agent, chat_session_id, user_id, context_store) =
-			agent::configs::orchestrator::create_orchestrator_agent(pool.clone())
-				.expect("Failed to create orchestrator agent");
+		// The agent will use MockLLM when DEPLOY_LLM != "1", and is None if it couldn't be created
+		// Events the optimizer fetched are cached, edits to user events evict them
+		let event_cache = agent::models::event::new_event_cache();
+		let (agent, context_store) = agent::configs::orchestrator::create_server_orchestrator_agent(
+			pool.clone(),
+			event_cache.clone(),
+		);
+		// Prompted directly by controllers, e.g. for chat summaries
+		let llm = agent::configs::orchestrator::create_server_llm();
+		agent::models::context::spawn_context_evictor(
+			context_store.clone(),
+			pool.clone(),
+			agent.clone(),
+		);
+		// Itineraries deleted with their chat are only purged after they can't be restored
+		controllers::itinerary::spawn_itinerary_purger(pool.clone());
 
 		/*
 		/ Configure CORS
@@ -79,22 +104,42 @@ async fn main() -> std::result::Result<(), Box<dyn std::error::Error>> {
 					.expect("Invalid frontend_url format"),
 			)
 			.allow_credentials(true)
-			.allow_methods([Method::GET, Method::POST, Method::DELETE])
+			.allow_methods([Method::GET, Method::POST, Method::PATCH, Method::DELETE])
 			.allow_headers([
 				http::header::CONTENT_TYPE,
 				http::header::ACCEPT,
 				http::header::AUTHORIZATION,
 				http::header::HeaderName::from_static("x-requested-with"),
+				http::header::HeaderName::from_static(middleware::API_KEY_HEADER),
+			])
+			.expose_headers([
+				http::header::RETRY_AFTER,
+				http::header::HeaderName::from_static(middleware::REQUEST_ID_HEADER),
 			]);
 
-		// Use an encryption/signing key for private cookies
-		let cookie_key = Key::generate();
+		// Use an encryption/signing key for private cookies, shared across restarts
+		let cookie_key = middleware::load_cookie_key(env::var(COOKIE_KEY).ok());
+
+		// Shared failed login/signup counters
+		let rate_limiter: rate_limit::SharedRateLimiter =
+			std::sync::Arc::new(rate_limit::RateLimiter::default());
+		rate_limit::spawn_pruner(rate_limiter.clone());
+
+		// Send emails over SMTP when it's configured, otherwise only log them
+		let mailer: mailer::SharedMailer = match mailer::SmtpMailer::from_env() {
+			Some(smtp) => std::sync::Arc::new(smtp),
+			None => {
+				tracing::warn!("{SMTP_HOST} is not set, outgoing emails will only be logged");
+				std::sync::Arc::new(mailer::LogMailer)
+			}
+		};
 
 		// API routes with CORS middleware
 		let api_routes = AxumRouter::new()
 			.nest("/account", controllers::account::account_routes())
 			.nest("/itinerary", controllers::itinerary::itinerary_routes())
-			.nest("/chat", controllers::chat::chat_routes());
+			.nest("/chat", controllers::chat::chat_routes())
+			.nest("/events", controllers::events::events_routes());
 		// TODO: nest other routes...
 
 		let api_routes = AxumRouter::new().nest("/api", api_routes);
@@ -105,6 +150,18 @@ async fn main() -> std::result::Result<(), Box<dyn std::error::Error>> {
 		// Build the main router
 		let app = axum::Router::new()
 			.merge(api_routes)
+			.route(
+				"/api/metrics",
+				axum::routing::get(middleware::metrics::api_metrics),
+			)
+			// Where Prometheus scrapes by default
+			.route(
+				"/metrics",
+				axum::routing::get(middleware::metrics::api_metrics),
+			)
+			.route_layer(axum::middleware::from_fn(
+				middleware::metrics::middleware_metrics,
+			))
 			// Static files served from /dist.
 			// Fallback must be index.html since react handles routing on front end
 			.fallback_service(get_service(
@@ -113,14 +170,28 @@ async fn main() -> std::result::Result<(), Box<dyn std::error::Error>> {
 			))
 			.layer(Extension(pool.clone()))
 			.layer(Extension(cookie_key.clone()))
-			.layer(Extension(std::sync::Arc::new(tokio::sync::Mutex::new(
-				agent,
-			))))
-			.layer(Extension(chat_session_id))
-			.layer(Extension(user_id))
+			.layer(Extension(agent))
+			.layer(Extension(llm))
 			.layer(Extension(context_store))
+			.layer(Extension(event_cache))
+			.layer(Extension(mailer))
+			.layer(Extension::<notifications::SharedNotifier>(
+				std::sync::Arc::new(notifications::LogNotifier),
+			))
+			.layer(Extension::<oauth::SharedGoogleOAuth>(std::sync::Arc::new(
+				oauth::GoogleOAuthClient::default(),
+			)))
+			.layer(Extension(rate_limiter))
 			.layer(CookieManagerLayer::new())
-			.layer(cors);
+			// One body limit for every route instead of axum's per-extractor default
+			.layer(DefaultBodyLimit::disable())
+			.layer(RequestBodyLimitLayer::new(MAX_REQUEST_BODY_BYTES))
+			// Outermost, so everything logged for a request is tagged with its id
+			.layer(
+				ServiceBuilder::new()
+					.layer(middleware::RequestIdMiddleware)
+					.layer(cors),
+			);
 
 		/*
 		/ Bind the router to a specific port
@@ -136,7 +207,12 @@ async fn main() -> std::result::Result<(), Box<dyn std::error::Error>> {
 		/ We will start the server with the configured router and address
 		*/
 		let listener = tokio::net::TcpListener::bind(addr).await.unwrap();
-		axum::serve(listener, app.into_make_service()).await?;
+		// Connect info gives the rate limiter the client's IP
+		axum::serve(
+			listener,
+			app.into_make_service_with_connect_info::<SocketAddr>(),
+		)
+		.await?;
 
 		Ok(())
 	}
@@ -1,7 +1,32 @@
+pub mod metrics;
+
 use crate::error::AppError;
-use axum::{extract::Request, middleware::Next, response::IntoResponse};
+use crate::global::{
+	AUTH_RATE_LIMIT_MAX_PER_EMAIL, AUTH_RATE_LIMIT_MAX_PER_IP, COOKIE_KEY, COOKIE_KEY_LEN,
+	SESSION_LAST_SEEN_INTERVAL_SECONDS,
+};
+use crate::http_models::account::is_language_tag;
+use crate::rate_limit::SharedRateLimiter;
+use argon2::{Argon2, PasswordHash, PasswordVerifier};
+use axum::{
+	body::{Body, to_bytes},
+	extract::{ConnectInfo, FromRequestParts, Request},
+	http::{HeaderValue, header, request::Parts},
+	middleware::Next,
+	response::{IntoResponse, Response},
+};
+use base64::{Engine, engine::general_purpose::STANDARD as BASE64};
 use chrono::Utc;
+use futures::future::BoxFuture;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
 use sqlx::PgPool;
+use std::{
+	convert::Infallible,
+	net::SocketAddr,
+	task::{Context, Poll},
+};
+use tower::{Layer, Service};
 use tower_cookies::{
 	Cookies,
 	cookie::{
@@ -9,6 +34,95 @@ use tower_cookies::{
 		time::{Duration, OffsetDateTime},
 	},
 };
+use tracing::Instrument;
+use uuid::Uuid;
+
+/// Request and response header with the id [RequestIdMiddleware] gave the request
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// Inserted into request extensions by [RequestIdMiddleware]
+#[derive(Clone, Copy, Debug)]
+pub struct RequestId(pub Uuid);
+
+tokio::task_local! {
+	/// Id of the request the current task is handling, set by [RequestIdMiddleware]
+	static CURRENT_REQUEST_ID: Uuid;
+}
+
+/// Id of the request being handled by the current task, `None` outside of a request
+/// or in tasks spawned from one.
+pub fn current_request_id() -> Option<Uuid> {
+	CURRENT_REQUEST_ID.try_with(|id| *id).ok()
+}
+
+/// Gives every request an id, so all of its log lines can be found together.
+/// * An incoming `X-Request-ID` is kept if it's a UUID, e.g. one set by a proxy or the
+///   frontend, otherwise a random one is generated
+/// * The request is handled in a `request` span with a `request_id` field, so log lines from
+///   middleware, controllers, and agent tools running for it are tagged with the id
+/// * The id is also available through [current_request_id] while the request is handled
+/// * The id is sent back in the `X-Request-ID` header
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RequestIdMiddleware;
+
+impl<S> Layer<S> for RequestIdMiddleware {
+	type Service = RequestIdService<S>;
+
+	fn layer(&self, inner: S) -> Self::Service {
+		RequestIdService { inner }
+	}
+}
+
+/// The service [RequestIdMiddleware] wraps routes in
+#[derive(Clone, Debug)]
+pub struct RequestIdService<S> {
+	inner: S,
+}
+
+impl<S> Service<Request> for RequestIdService<S>
+where
+	S: Service<Request, Response = Response> + Send + 'static,
+	S::Future: Send + 'static,
+{
+	type Response = Response;
+	type Error = S::Error;
+	type Future = BoxFuture<'static, Result<Response, S::Error>>;
+
+	fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+		self.inner.poll_ready(cx)
+	}
+
+	fn call(&mut self, mut req: Request) -> Self::Future {
+		let request_id = req
+			.headers()
+			.get(REQUEST_ID_HEADER)
+			.and_then(|value| value.to_str().ok())
+			.and_then(|value| Uuid::parse_str(value.trim()).ok())
+			.unwrap_or_else(Uuid::new_v4);
+		// Only the path, query strings can hold tokens
+		let span = tracing::info_span!(
+			"request",
+			request_id = %request_id,
+			method = %req.method(),
+			path = req.uri().path(),
+		);
+		req.extensions_mut().insert(RequestId(request_id));
+		let response =
+			CURRENT_REQUEST_ID.sync_scope(request_id, || span.in_scope(|| self.inner.call(req)));
+
+		Box::pin(
+			CURRENT_REQUEST_ID
+				.scope(request_id, async move {
+					let mut response = response.await?;
+					if let Ok(value) = HeaderValue::from_str(&request_id.to_string()) {
+						response.headers_mut().insert(REQUEST_ID_HEADER, value);
+					}
+					Ok(response)
+				})
+				.instrument(span),
+		)
+	}
+}
 
 /// Inserted into request extensions on authenticated requests
 #[derive(Clone, Copy, Debug)]
@@ -16,10 +130,179 @@ pub struct AuthUser {
 	pub id: i32,
 }
 
+/// Inserted into request extensions when the request was authenticated by a cookie
+#[derive(Clone, Copy, Debug)]
+pub struct AuthSession {
+	pub id: i32,
+}
+
+/// Where a request came from, recorded with sessions and auth events
+#[derive(Clone, Debug, Default)]
+pub struct ClientInfo {
+	/// Peer IP, only known when the server is run with connect info
+	pub ip: Option<String>,
+	pub user_agent: Option<String>,
+}
+
+impl<S: Send + Sync> FromRequestParts<S> for ClientInfo {
+	type Rejection = Infallible;
+
+	async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+		Ok(ClientInfo {
+			ip: parts
+				.extensions
+				.get::<ConnectInfo<SocketAddr>>()
+				.map(|ConnectInfo(addr)| addr.ip().to_string()),
+			user_agent: parts
+				.headers
+				.get(header::USER_AGENT)
+				.and_then(|v| v.to_str().ok())
+				.map(String::from),
+		})
+	}
+}
+
+/// Language asked for by the request's `Accept-Language` header, see [parse_accept_language]
+#[derive(Clone, Debug, Default)]
+pub struct AcceptLanguage(pub Option<String>);
+
+impl<S: Send + Sync> FromRequestParts<S> for AcceptLanguage {
+	type Rejection = Infallible;
+
+	async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+		Ok(AcceptLanguage(
+			parts
+				.headers
+				.get(header::ACCEPT_LANGUAGE)
+				.and_then(|v| v.to_str().ok())
+				.and_then(parse_accept_language),
+		))
+	}
+}
+
+/// The language with the highest quality in an `Accept-Language` value, e.g. `es` for
+/// `en;q=0.5, es`.
+/// * Earlier languages win ties
+/// * `*`, `q=0` and tags that couldn't be stored as a `preferred_language` are skipped
+pub fn parse_accept_language(value: &str) -> Option<String> {
+	let mut best: Option<(&str, f32)> = None;
+	for entry in value.split(',') {
+		let mut parts = entry.split(';');
+		let tag = parts.next().unwrap_or_default().trim();
+		let quality = parts
+			.find_map(|param| param.trim().strip_prefix("q="))
+			.map_or(Some(1.0), |q| q.trim().parse::<f32>().ok());
+		let Some(quality) = quality else {
+			continue;
+		};
+		if tag == "*" || !is_language_tag(tag) || quality <= 0.0 {
+			continue;
+		}
+		if best.is_none_or(|(_, best_quality)| quality > best_quality) {
+			best = Some((tag, quality));
+		}
+	}
+	best.map(|(tag, _)| tag.to_string())
+}
+
+/// Builds the private cookie [Key] from the base64 `COOKIE_KEY` env value.
+/// - Every instance sharing the key keeps cookies valid across restarts and instances
+/// - Without one, development generates a throwaway key and production panics
+///
+/// Panics if the value isn't base64 for exactly `COOKIE_KEY_LEN` bytes.
+pub fn load_cookie_key(encoded: Option<String>) -> Key {
+	let Some(encoded) = encoded else {
+		let app_env = option_env!("APP_ENV").unwrap_or("development");
+		if app_env == "production" {
+			panic!("{} must be set in production", COOKIE_KEY);
+		}
+		tracing::warn!(
+			"{} is not set, generating a key. Auth cookies won't survive a restart.",
+			COOKIE_KEY
+		);
+		return Key::generate();
+	};
+
+	let bytes = BASE64
+		.decode(encoded.trim())
+		.unwrap_or_else(|e| panic!("{} must be base64 encoded: {}", COOKIE_KEY, e));
+	if bytes.len() != COOKIE_KEY_LEN {
+		panic!(
+			"{} must decode to {} bytes, got {}",
+			COOKIE_KEY,
+			COOKIE_KEY_LEN,
+			bytes.len()
+		);
+	}
+	Key::from(&bytes)
+}
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Claims carried by a verified `auth-token`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TokenClaims {
+	pub user_id: i32,
+	/// Epoch seconds (UTC) the token stops being valid
+	pub exp: i64,
+	pub session_id: i32,
+}
+
+/// HMAC over `payload`, keyed by a key derived from the cookie [Key]'s signing half
+fn token_mac(key: &Key, payload: &str) -> HmacSha256 {
+	let mut derive =
+		HmacSha256::new_from_slice(key.signing()).expect("HMAC accepts keys of any length");
+	derive.update(b"auth-token");
+	let token_key = derive.finalize().into_bytes();
+
+	let mut mac = HmacSha256::new_from_slice(&token_key).expect("HMAC accepts keys of any length");
+	mac.update(payload.as_bytes());
+	mac
+}
+
+/// Builds an `auth-token` value: `user-<id>.<exp>.<session>.<signature>`
+/// - `<signature>` is the hex HMAC-SHA256 of everything before it
+pub fn sign_token(key: &Key, claims: TokenClaims) -> String {
+	let payload = format!(
+		"user-{}.{}.{}",
+		claims.user_id, claims.exp, claims.session_id
+	);
+	let signature = hex::encode(token_mac(key, &payload).finalize().into_bytes());
+	format!("{}.{}", payload, signature)
+}
+
+/// Checks an `auth-token` value's signature and that it hasn't expired as of `now`
+/// - Returns `None` for tampered, expired, or malformed tokens
+pub fn verify_token(key: &Key, token: &str, now: i64) -> Option<TokenClaims> {
+	let (payload, signature) = token.rsplit_once('.')?;
+	let signature = hex::decode(signature).ok()?;
+	// Constant time comparison
+	token_mac(key, payload).verify_slice(&signature).ok()?;
+
+	let parts: Vec<&str> = payload.split('.').collect();
+	if parts.len() != 3 {
+		return None;
+	}
+	let claims = TokenClaims {
+		user_id: parts[0].strip_prefix("user-")?.parse().ok()?,
+		exp: parts[1].parse().ok()?,
+		session_id: parts[2].parse().ok()?,
+	};
+
+	if now > claims.exp {
+		return None;
+	}
+	Some(claims)
+}
+
+/// Header programmatic clients send their API key in
+pub const API_KEY_HEADER: &str = "x-api-key";
+
 /// Auth middleware for account routes
-/// - Decrypts `auth-token` private cookie using `Key` from extensions
-/// - Validates embedded expiration and that the user exists in DB
-/// - Inserts `AuthUser` into request extensions on success; otherwise 401
+/// - Authenticates with the `X-API-Key` header if present
+/// - Otherwise decrypts `auth-token` private cookie using `Key` from extensions
+/// - Validates the token's signature, embedded expiration, and that the session is still active in DB
+/// - Inserts `AuthUser` and `AuthSession` into request extensions on success; otherwise 401
 pub async fn middleware_auth(cookies: Cookies, mut req: Request, next: Next) -> impl IntoResponse {
 	let key = match req.extensions().get::<Key>() {
 		Some(k) => k.clone(),
@@ -30,40 +313,87 @@ pub async fn middleware_auth(cookies: Cookies, mut req: Request, next: Next) ->
 		None => return AppError::Unauthorized.into_response(),
 	};
 
-	// Decrypt private cookie and extract token
-	let decrypted = match cookies.private(&key).get("auth-token") {
-		Some(c) => c,
+	// API key auth takes the place of the cookie entirely
+	if let Some(api_key) = req.headers().get(API_KEY_HEADER) {
+		let user_id = match api_key.to_str() {
+			Ok(api_key) => match verify_api_key(api_key, &pool).await {
+				Some(id) => id,
+				None => return AppError::Unauthorized.into_response(),
+			},
+			Err(_) => return AppError::Unauthorized.into_response(),
+		};
+		req.extensions_mut().insert(AuthUser { id: user_id });
+		return next.run(req).await;
+	}
+
+	let (user, session) = match authenticate_cookie(&cookies, &key, &pool).await {
+		Some(auth) => auth,
 		None => return AppError::Unauthorized.into_response(),
 	};
-	let token = decrypted.value().to_string();
 
-	// Expect format: user-<id>.<exp>.sign
-	let parts: Vec<&str> = token.split('.').collect();
+	// Attach user to request
+	req.extensions_mut().insert(user);
+	req.extensions_mut().insert(session);
 
-	if parts.len() != 3 || parts[2] != "sign" || !parts[0].starts_with("user-") {
-		return AppError::Unauthorized.into_response();
-	}
+	next.run(req).await
+}
 
-	let user_id: i32 = match parts[0][5..].parse() {
-		Ok(v) => v,
-		Err(_) => return AppError::Unauthorized.into_response(),
+/// Validates the `auth-token` private cookie, refreshing it if it's about to expire.
+///
+/// Shared by `middleware_auth` and handlers that can't sit behind it, like websocket upgrades.
+pub async fn authenticate_cookie(
+	cookies: &Cookies,
+	key: &Key,
+	pool: &PgPool,
+) -> Option<(AuthUser, AuthSession)> {
+	// Decrypt private cookie and extract token
+	let decrypted = match cookies.private(key).get("auth-token") {
+		Some(c) => c,
+		None => return None,
 	};
+	let now = Utc::now().timestamp();
+	let TokenClaims {
+		user_id,
+		exp,
+		session_id,
+	} = verify_token(key, decrypted.value(), now)?;
 
-	let exp: i64 = match parts[1].parse() {
-		Ok(v) => v,
-		Err(_) => return AppError::Unauthorized.into_response(),
+	// Ensure the session hasn't been revoked, which also ensures the user exists
+	let stale = match sqlx::query_as::<_, (bool,)>(
+		"SELECT last_seen < NOW() - make_interval(secs => $3)
+		FROM sessions
+		WHERE id = $1 AND account_id = $2 AND revoked_at IS NULL AND expires_at > NOW()",
+	)
+	.bind(session_id)
+	.bind(user_id)
+	.bind(SESSION_LAST_SEEN_INTERVAL_SECONDS as f64)
+	.fetch_optional(pool)
+	.await
+	{
+		Ok(Some((stale,))) => stale,
+		_ => return None,
 	};
 
-	let now = Utc::now().timestamp();
-	if now > exp {
-		return AppError::Unauthorized.into_response();
+	// Only touch last_seen every few minutes to avoid a write per request
+	if stale {
+		_ = sqlx::query("UPDATE sessions SET last_seen = NOW() WHERE id = $1")
+			.bind(session_id)
+			.execute(pool)
+			.await;
 	}
 
 	// If the cookie will expire in less than an hour, set it's expiration to one hour from now
 	let one_hour = 3600;
 	if exp - now < one_hour {
 		let new_exp = now + one_hour;
-		let new_token = format!("user-{}.{}.sign", user_id, new_exp);
+		let new_token = sign_token(
+			key,
+			TokenClaims {
+				user_id,
+				exp: new_exp,
+				session_id,
+			},
+		);
 
 		let domain = option_env!("DOMAIN").unwrap_or("localhost");
 		let app_env = option_env!("APP_ENV").unwrap_or("development");
@@ -83,23 +413,113 @@ pub async fn middleware_auth(cookies: Cookies, mut req: Request, next: Next) ->
 			.max_age(Duration::hours(1))
 			.build();
 
-		cookies.private(&key).add(new_cookie);
+		cookies.private(key).add(new_cookie);
+
+		// Keep the session alive as long as the cookie
+		_ = sqlx::query(
+			"UPDATE sessions SET expires_at = GREATEST(expires_at, to_timestamp($2)) WHERE id = $1",
+		)
+		.bind(session_id)
+		.bind(new_exp as f64)
+		.execute(pool)
+		.await;
 	}
 
-	// Ensure user exists
-	let exists_row =
-		sqlx::query_as::<_, (bool,)>("SELECT EXISTS(SELECT 1 FROM accounts WHERE id = $1)")
-			.bind(user_id)
-			.fetch_one(&pool)
-			.await
-			.unwrap_or((false,));
+	Some((AuthUser { id: user_id }, AuthSession { id: session_id }))
+}
+
+/// Verifies an API key of the form `<id>.<secret>` against `api_keys`.
+///
+/// Returns the owning account id and records the key as used.
+async fn verify_api_key(api_key: &str, pool: &PgPool) -> Option<i32> {
+	let (id, secret) = api_key.split_once('.')?;
+	let id: i32 = id.parse().ok()?;
+
+	let (account_id, key_hash) = sqlx::query_as::<_, (i32, String)>(
+		"SELECT account_id, key_hash FROM api_keys WHERE id = $1",
+	)
+	.bind(id)
+	.fetch_optional(pool)
+	.await
+	.ok()??;
 
-	if !exists_row.0 {
-		return AppError::Unauthorized.into_response();
+	let parsed_hash = PasswordHash::new(&key_hash).ok()?;
+	Argon2::default()
+		.verify_password(secret.as_bytes(), &parsed_hash)
+		.ok()?;
+
+	_ = sqlx::query("UPDATE api_keys SET last_used_at = NOW() WHERE id = $1")
+		.bind(id)
+		.execute(pool)
+		.await;
+
+	Some(account_id)
+}
+
+/// Largest body the rate limiter will buffer to find the email
+const RATE_LIMIT_BODY_LIMIT: usize = 64 * 1024;
+
+/// Rate limiting middleware for login/signup
+/// - Counts 4xx responses as failures per client IP and per `email` in the JSON body
+/// - Once either has too many failures in the window, responds 429 with `Retry-After`
+/// - A successful attempt clears the failures for that email
+pub async fn middleware_rate_limit(req: Request, next: Next) -> Response {
+	let limiter = match req.extensions().get::<SharedRateLimiter>() {
+		Some(l) => l.clone(),
+		None => {
+			return AppError::Internal("rate limiter not configured".to_string()).into_response();
+		}
+	};
+	let ip = req
+		.extensions()
+		.get::<ConnectInfo<SocketAddr>>()
+		.map(|ConnectInfo(addr)| format!("ip:{}", addr.ip()));
+
+	// Buffer the body so the email can be read before the handler gets it
+	let (parts, body) = req.into_parts();
+	let bytes = match to_bytes(body, RATE_LIMIT_BODY_LIMIT).await {
+		Ok(b) => b,
+		Err(_) => {
+			return AppError::BadRequest("request body too large".to_string()).into_response();
+		}
+	};
+	let email = serde_json::from_slice::<serde_json::Value>(&bytes)
+		.ok()
+		.and_then(|v| {
+			Some(format!(
+				"email:{}",
+				v.get("email")?.as_str()?.trim().to_lowercase()
+			))
+		});
+	let req = Request::from_parts(parts, Body::from(bytes));
+
+	let keys: Vec<(&String, u32)> = ip
+		.iter()
+		.map(|k| (k, AUTH_RATE_LIMIT_MAX_PER_IP))
+		.chain(email.iter().map(|k| (k, AUTH_RATE_LIMIT_MAX_PER_EMAIL)))
+		.collect();
+
+	if let Some(retry_after) = keys
+		.iter()
+		.filter_map(|(key, max)| limiter.retry_after(key, *max))
+		.max()
+	{
+		// round up so clients never retry a moment too early
+		let secs = retry_after.as_secs() + u64::from(retry_after.subsec_nanos() > 0);
+		return AppError::TooManyRequests(secs).into_response();
 	}
 
-	// Attach user to request
-	req.extensions_mut().insert(AuthUser { id: user_id });
+	let res = next.run(req).await;
 
-	next.run(req).await
+	if res.status().is_client_error() {
+		for (key, _) in &keys {
+			limiter.record_failure(key);
+		}
+	} else if res.status().is_success()
+		&& let Some(email) = &email
+	{
+		limiter.reset(email);
+	}
+
+	res
 }
@@ -0,0 +1,307 @@
+use crate::db;
+use crate::error::{ApiResult, AppError};
+use crate::global::{DEFAULT_METRICS_ALLOWED_CIDRS, METRICS_ALLOWED_CIDRS, METRICS_BEARER_TOKEN};
+use axum::{
+	extract::{ConnectInfo, MatchedPath, Request},
+	http::header,
+	middleware::Next,
+	response::{IntoResponse, Response},
+};
+use ipnet::IpNet;
+use once_cell::sync::Lazy;
+use prometheus::{
+	Encoder, HistogramOpts, HistogramTimer, HistogramVec, IntCounter, IntCounterVec, IntGauge,
+	IntGaugeVec, Opts, Registry, TextEncoder,
+};
+use sqlx::PgPool;
+use std::{
+	net::{IpAddr, SocketAddr},
+	time::Instant,
+};
+
+/// Registry every metric below is registered in, exported by `/api/metrics`
+pub static REGISTRY: Lazy<Registry> = Lazy::new(Registry::new);
+
+pub static HTTP_REQUESTS_TOTAL: Lazy<IntCounterVec> = Lazy::new(|| {
+	let counter = IntCounterVec::new(
+		Opts::new("http_requests_total", "Number of HTTP requests handled"),
+		&["method", "path", "status"],
+	)
+	.unwrap();
+	REGISTRY.register(Box::new(counter.clone())).unwrap();
+	counter
+});
+
+/// Responses by status class, e.g. `5xx`, for error rates across every route
+pub static HTTP_RESPONSES_TOTAL: Lazy<IntCounterVec> = Lazy::new(|| {
+	let counter = IntCounterVec::new(
+		Opts::new(
+			"http_responses_total",
+			"Number of HTTP responses by status class",
+		),
+		&["class"],
+	)
+	.unwrap();
+	REGISTRY.register(Box::new(counter.clone())).unwrap();
+	counter
+});
+
+pub static HTTP_REQUEST_DURATION_SECONDS: Lazy<HistogramVec> = Lazy::new(|| {
+	let histogram = HistogramVec::new(
+		HistogramOpts::new(
+			"http_request_duration_seconds",
+			"Time taken to handle HTTP requests",
+		),
+		&["method", "path"],
+	)
+	.unwrap();
+	REGISTRY.register(Box::new(histogram.clone())).unwrap();
+	histogram
+});
+
+pub static LLM_PIPELINE_DURATION_SECONDS: Lazy<HistogramVec> = Lazy::new(|| {
+	let histogram = HistogramVec::new(
+		// LLM calls take seconds to minutes, much longer than the default buckets
+		HistogramOpts::new(
+			"llm_pipeline_duration_seconds",
+			"Time taken by a sub-agent to handle a routed task",
+		)
+		.buckets(vec![
+			0.5, 1.0, 2.5, 5.0, 10.0, 20.0, 30.0, 60.0, 120.0, 300.0,
+		]),
+		&["agent"],
+	)
+	.unwrap();
+	REGISTRY.register(Box::new(histogram.clone())).unwrap();
+	histogram
+});
+
+pub static LLM_PIPELINE_RUNS_TOTAL: Lazy<IntCounter> = Lazy::new(|| {
+	let counter = IntCounter::new(
+		"llm_pipeline_runs_total",
+		"Number of times the orchestrator agent was invoked for a message",
+	)
+	.unwrap();
+	REGISTRY.register(Box::new(counter.clone())).unwrap();
+	counter
+});
+
+/// Agent runs that ended without a reply of their own, by `reason`:
+/// `error`, `panic`, `timeout` or `too_many_tool_calls`
+pub static LLM_PIPELINE_FAILURES_TOTAL: Lazy<IntCounterVec> = Lazy::new(|| {
+	let counter = IntCounterVec::new(
+		Opts::new(
+			"llm_pipeline_failures_total",
+			"Number of orchestrator agent runs that failed",
+		),
+		&["reason"],
+	)
+	.unwrap();
+	REGISTRY.register(Box::new(counter.clone())).unwrap();
+	counter
+});
+
+pub static CLARIFICATIONS_ASKED_TOTAL: Lazy<IntCounter> = Lazy::new(|| {
+	let counter = IntCounter::new(
+		"clarifications_asked_total",
+		"Number of messages asking the user for missing trip details",
+	)
+	.unwrap();
+	REGISTRY.register(Box::new(counter.clone())).unwrap();
+	counter
+});
+
+pub static ITINERARIES_GENERATED_TOTAL: Lazy<IntCounter> = Lazy::new(|| {
+	let counter = IntCounter::new(
+		"itineraries_generated_total",
+		"Number of itineraries sent to users by the agent",
+	)
+	.unwrap();
+	REGISTRY.register(Box::new(counter.clone())).unwrap();
+	counter
+});
+
+pub static CONTEXT_STORE_ENTRIES: Lazy<IntGauge> = Lazy::new(|| {
+	let gauge = IntGauge::new(
+		"context_store_entries",
+		"Number of chat contexts held in memory, as of the last eviction",
+	)
+	.unwrap();
+	REGISTRY.register(Box::new(gauge.clone())).unwrap();
+	gauge
+});
+
+/// Database pool connections by `state`, `idle` or `in_use`, as of the last scrape
+pub static DB_POOL_CONNECTIONS: Lazy<IntGaugeVec> = Lazy::new(|| {
+	let gauge = IntGaugeVec::new(
+		Opts::new(
+			"db_pool_connections",
+			"Number of open database connections by state",
+		),
+		&["state"],
+	)
+	.unwrap();
+	REGISTRY.register(Box::new(gauge.clone())).unwrap();
+	gauge
+});
+
+/// Networks allowed to scrape `/api/metrics`, parsed from `METRICS_ALLOWED_CIDRS`
+pub static ALLOWED_CIDRS: Lazy<Vec<IpNet>> = Lazy::new(|| {
+	let cidrs = std::env::var(METRICS_ALLOWED_CIDRS)
+		.unwrap_or_else(|_| DEFAULT_METRICS_ALLOWED_CIDRS.to_string());
+	parse_cidrs(&cidrs).unwrap_or_else(|e| panic!("Invalid {METRICS_ALLOWED_CIDRS}: {e}"))
+});
+
+/// Registers the metrics that are only updated by the agent pipeline, so they're scraped as
+/// zero before the first message instead of missing
+pub fn register_pipeline_metrics() {
+	Lazy::force(&LLM_PIPELINE_RUNS_TOTAL);
+	Lazy::force(&LLM_PIPELINE_FAILURES_TOTAL);
+	Lazy::force(&CLARIFICATIONS_ASKED_TOTAL);
+	Lazy::force(&ITINERARIES_GENERATED_TOTAL);
+}
+
+/// Parses a comma separated list of CIDRs, plain IPs are treated as a single address
+pub fn parse_cidrs(cidrs: &str) -> Result<Vec<IpNet>, String> {
+	cidrs
+		.split(',')
+		.map(str::trim)
+		.filter(|s| !s.is_empty())
+		.map(|s| {
+			s.parse::<IpNet>()
+				.or_else(|_| s.parse::<IpAddr>().map(IpNet::from))
+				.map_err(|_| format!("'{s}' is not a CIDR or IP address"))
+		})
+		.collect()
+}
+
+/// Whether `ip` is inside any of `cidrs`
+/// * IPv4-mapped IPv6 addresses are matched as IPv4
+pub fn ip_allowed(cidrs: &[IpNet], ip: IpAddr) -> bool {
+	let ip = ip.to_canonical();
+	cidrs.iter().any(|net| net.contains(&ip))
+}
+
+/// Whether `given` equals `expected`, taking the same time wherever they differ
+fn token_matches(given: &str, expected: &str) -> bool {
+	given.len() == expected.len()
+		&& given
+			.bytes()
+			.zip(expected.bytes())
+			.fold(0, |diff, (a, b)| diff | (a ^ b))
+			== 0
+}
+
+/// Whether a client may scrape metrics.
+/// * Clients in `cidrs` always can
+/// * Others can when `token` is set and `authorization` is `Bearer <token>`
+pub fn scrape_allowed(
+	cidrs: &[IpNet],
+	token: Option<&str>,
+	ip: Option<IpAddr>,
+	authorization: Option<&str>,
+) -> bool {
+	if ip.is_some_and(|ip| ip_allowed(cidrs, ip)) {
+		return true;
+	}
+	match (token.filter(|t| !t.is_empty()), authorization) {
+		(Some(token), Some(authorization)) => authorization
+			.strip_prefix("Bearer ")
+			.is_some_and(|given| token_matches(given.trim(), token)),
+		_ => false,
+	}
+}
+
+/// [scrape_allowed] for a request, using `ALLOWED_CIDRS` and the `METRICS_BEARER_TOKEN` env var
+/// * Without connect info the client's IP can't be checked, so only the token is
+pub fn request_scrape_allowed(req: &Request) -> bool {
+	let token = std::env::var(METRICS_BEARER_TOKEN).ok();
+	let ip = req
+		.extensions()
+		.get::<ConnectInfo<SocketAddr>>()
+		.map(|ConnectInfo(addr)| addr.ip());
+	let authorization = req
+		.headers()
+		.get(header::AUTHORIZATION)
+		.and_then(|value| value.to_str().ok());
+	scrape_allowed(&ALLOWED_CIDRS, token.as_deref(), ip, authorization)
+}
+
+/// Starts timing an LLM sub-agent, the duration is recorded when the timer is dropped
+pub fn start_llm_pipeline_timer(agent: &str) -> HistogramTimer {
+	LLM_PIPELINE_DURATION_SECONDS
+		.with_label_values(&[agent])
+		.start_timer()
+}
+
+/// Metrics middleware for all matched routes
+/// - Labels requests with the route template, not the raw path, to keep label cardinality bounded
+/// - Records `http_requests_total`, `http_responses_total` and `http_request_duration_seconds`
+pub async fn middleware_metrics(req: Request, next: Next) -> Response {
+	let start = Instant::now();
+	let method = req.method().to_string();
+	let path = req
+		.extensions()
+		.get::<MatchedPath>()
+		.map(|p| p.as_str().to_string())
+		.unwrap_or_else(|| "unmatched".to_string());
+
+	let res = next.run(req).await;
+
+	HTTP_REQUESTS_TOTAL
+		.with_label_values(&[method.as_str(), path.as_str(), res.status().as_str()])
+		.inc();
+	HTTP_RESPONSES_TOTAL
+		.with_label_values(&[format!("{}xx", res.status().as_u16() / 100).as_str()])
+		.inc();
+	HTTP_REQUEST_DURATION_SECONDS
+		.with_label_values(&[method.as_str(), path.as_str()])
+		.observe(start.elapsed().as_secs_f64());
+	res
+}
+
+/// Export metrics for Prometheus.
+///
+/// Reachable from the networks in `METRICS_ALLOWED_CIDRS` (loopback by default), or from
+/// anywhere with the `METRICS_BEARER_TOKEN` as a bearer token when it's set.
+/// The database pool's connection counts are read on each scrape.
+///
+/// # Method
+/// `GET /api/metrics` or `GET /metrics`
+///
+/// # Responses
+/// - `200 OK` - Metrics in the Prometheus text format
+/// - `403 FORBIDDEN` - The client IP isn't in the allow-list and no valid token was given
+/// - `500 INTERNAL_SERVER_ERROR` - Metrics could not be encoded
+///
+/// # Examples
+/// ```bash
+/// curl http://localhost:3001/api/metrics
+/// curl -H "Authorization: Bearer $METRICS_BEARER_TOKEN" https://example.com/metrics
+/// ```
+pub async fn api_metrics(req: Request) -> ApiResult<Response> {
+	if !request_scrape_allowed(&req) {
+		return Err(AppError::Forbidden);
+	}
+
+	if let Some(pool) = req.extensions().get::<PgPool>() {
+		let stats = db::pool_stats(pool);
+		DB_POOL_CONNECTIONS
+			.with_label_values(&["idle"])
+			.set(stats.idle.into());
+		DB_POOL_CONNECTIONS
+			.with_label_values(&["in_use"])
+			.set(stats.in_use.into());
+	}
+
+	let encoder = TextEncoder::new();
+	let mut buf = Vec::new();
+	encoder
+		.encode(&REGISTRY.gather(), &mut buf)
+		.map_err(|e| AppError::Internal(format!("metrics encode error: {e:?}")))?;
+	Ok((
+		[(header::CONTENT_TYPE, encoder.format_type().to_string())],
+		buf,
+	)
+		.into_response())
+}
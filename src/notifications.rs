@@ -0,0 +1,100 @@
+/*
+ * src/notifications.rs
+ *
+ * File for user notifications
+ *
+ * Purpose:
+ *   Abstract notification delivery behind a trait so features like trip
+ *   reminders and itinerary sharing have one place to notify users from,
+ *   respecting the account's notification preferences.
+ */
+
+use crate::error::{ApiResult, AppError};
+use crate::sql_models::account::NotificationPreferences;
+use sqlx::{PgPool, types::Json};
+use std::sync::Arc;
+use tracing::info;
+
+/// Kinds of notifications, each toggled by a field of [NotificationPreferences].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NotificationKind {
+	#[allow(dead_code)] // nothing sends trip reminders yet
+	TripReminder,
+	/// Sent by `api_share` when the user shares an itinerary
+	Share,
+	#[allow(dead_code)] // nothing sends marketing yet
+	Marketing,
+}
+
+impl NotificationKind {
+	/// Whether the user has opted into this kind of notification
+	pub fn enabled(self, preferences: &NotificationPreferences) -> bool {
+		match self {
+			NotificationKind::TripReminder => preferences.trip_reminders,
+			NotificationKind::Share => preferences.share_notifications,
+			NotificationKind::Marketing => preferences.marketing,
+		}
+	}
+}
+
+/// Something that can deliver a notification to a user.
+pub trait Notifier: Send + Sync {
+	fn notify(
+		&self,
+		account_id: i32,
+		kind: NotificationKind,
+		message: &str,
+	) -> Result<(), AppError>;
+}
+
+/// Shared notifier handed to controllers through an [axum::Extension].
+pub type SharedNotifier = Arc<dyn Notifier>;
+
+/// Writes notifications to the `notifications` log target instead of sending them.
+///
+/// Stand-in until real notification delivery is configured.
+pub struct LogNotifier;
+
+impl Notifier for LogNotifier {
+	fn notify(
+		&self,
+		account_id: i32,
+		kind: NotificationKind,
+		message: &str,
+	) -> Result<(), AppError> {
+		info!(
+			target: "notifications",
+			account_id = account_id,
+			kind = ?kind,
+			message = message,
+			"Outgoing notification"
+		);
+		Ok(())
+	}
+}
+
+/// Notify the account if it has opted into this kind of notification.
+///
+/// Returns whether the notification was sent.
+pub async fn notify_account(
+	pool: &PgPool,
+	notifier: &SharedNotifier,
+	account_id: i32,
+	kind: NotificationKind,
+	message: &str,
+) -> ApiResult<bool> {
+	let preferences = sqlx::query_scalar!(
+		r#"SELECT notification_preferences as "preferences: Json<NotificationPreferences>" FROM accounts WHERE id = $1"#,
+		account_id
+	)
+	.fetch_optional(pool)
+	.await
+	.map_err(AppError::from)?
+	.ok_or(AppError::NotFound)?;
+
+	if !kind.enabled(&preferences) {
+		return Ok(false);
+	}
+	notifier.notify(account_id, kind, message)?;
+	Ok(true)
+}
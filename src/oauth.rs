@@ -0,0 +1,128 @@
+/*
+ * src/oauth.rs
+ *
+ * File for third-party sign-in providers
+ *
+ * Purpose:
+ *   Exchange OAuth2 authorization codes for verified identities. The provider
+ *   is hidden behind a trait so controllers can be tested without Google.
+ */
+
+use crate::error::AppError;
+use crate::global::{GOOGLE_CLIENT_ID, GOOGLE_CLIENT_SECRET};
+use async_trait::async_trait;
+use serde::Deserialize;
+use std::sync::Arc;
+
+const GOOGLE_TOKEN_URL: &str = "https://oauth2.googleapis.com/token";
+const GOOGLE_TOKEN_INFO_URL: &str = "https://oauth2.googleapis.com/tokeninfo";
+const GOOGLE_ISSUERS: [&str; 2] = ["accounts.google.com", "https://accounts.google.com"];
+
+/// Verified identity of a Google account.
+#[derive(Debug, Clone)]
+pub struct GoogleIdentity {
+	pub email: String,
+	pub given_name: Option<String>,
+	pub family_name: Option<String>,
+}
+
+/// Something that can turn a Google authorization code into a verified identity.
+#[async_trait]
+pub trait GoogleOAuth: Send + Sync {
+	async fn verify_code(&self, code: &str, redirect_uri: &str)
+	-> Result<GoogleIdentity, AppError>;
+}
+
+/// Shared Google OAuth provider handed to controllers through an [axum::Extension].
+pub type SharedGoogleOAuth = Arc<dyn GoogleOAuth>;
+
+/// Talks to Google's OAuth2 endpoints using `GOOGLE_CLIENT_ID` and `GOOGLE_CLIENT_SECRET`.
+#[derive(Default)]
+pub struct GoogleOAuthClient {
+	client: reqwest::Client,
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+	id_token: String,
+}
+
+/// Claims returned by Google's tokeninfo endpoint, which also checks the token's signature.
+#[derive(Deserialize)]
+struct TokenInfo {
+	aud: String,
+	iss: String,
+	email: String,
+	email_verified: String,
+	given_name: Option<String>,
+	family_name: Option<String>,
+}
+
+#[async_trait]
+impl GoogleOAuth for GoogleOAuthClient {
+	async fn verify_code(
+		&self,
+		code: &str,
+		redirect_uri: &str,
+	) -> Result<GoogleIdentity, AppError> {
+		let client_id = std::env::var(GOOGLE_CLIENT_ID)?;
+		let client_secret = std::env::var(GOOGLE_CLIENT_SECRET)?;
+
+		// Exchange the authorization code for an ID token
+		let response = self
+			.client
+			.post(GOOGLE_TOKEN_URL)
+			.form(&[
+				("code", code),
+				("client_id", &client_id),
+				("client_secret", &client_secret),
+				("redirect_uri", redirect_uri),
+				("grant_type", "authorization_code"),
+			])
+			.send()
+			.await
+			.map_err(|e| AppError::Internal(format!("google token request failed: {e:?}")))?;
+		if !response.status().is_success() {
+			return Err(AppError::BadRequest(
+				"invalid google authorization code".to_string(),
+			));
+		}
+		let token = response
+			.json::<TokenResponse>()
+			.await
+			.map_err(|e| AppError::Internal(format!("google token response: {e:?}")))?;
+
+		// Verify the ID token and read its claims
+		let response = self
+			.client
+			.get(GOOGLE_TOKEN_INFO_URL)
+			.query(&[("id_token", &token.id_token)])
+			.send()
+			.await
+			.map_err(|e| AppError::Internal(format!("google tokeninfo request failed: {e:?}")))?;
+		if !response.status().is_success() {
+			return Err(AppError::BadRequest("invalid google id token".to_string()));
+		}
+		let info = response
+			.json::<TokenInfo>()
+			.await
+			.map_err(|e| AppError::Internal(format!("google tokeninfo response: {e:?}")))?;
+
+		if info.aud != client_id || !GOOGLE_ISSUERS.contains(&info.iss.as_str()) {
+			return Err(AppError::BadRequest(
+				"google id token was not issued for this app".to_string(),
+			));
+		}
+		if info.email_verified != "true" {
+			return Err(AppError::BadRequest(
+				"google account email is not verified".to_string(),
+			));
+		}
+
+		Ok(GoogleIdentity {
+			email: info.email,
+			given_name: info.given_name,
+			family_name: info.family_name,
+		})
+	}
+}
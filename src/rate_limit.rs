@@ -0,0 +1,99 @@
+/*
+ * src/rate_limit.rs
+ *
+ * File for in-memory rate limiting
+ *
+ * Purpose:
+ *   Count failed authentication attempts per key (IP or email) so repeated
+ *   failures can be rejected without an external store.
+ */
+
+use std::{
+	collections::HashMap,
+	sync::{Arc, Mutex},
+	time::{Duration, Instant},
+};
+
+use crate::global::AUTH_RATE_LIMIT_WINDOW_SECONDS;
+
+/// Failures recorded for one key in the current window.
+struct Attempts {
+	count: u32,
+	window_start: Instant,
+}
+
+/// Fixed window failure counter keyed by arbitrary strings.
+pub struct RateLimiter {
+	window: Duration,
+	attempts: Mutex<HashMap<String, Attempts>>,
+}
+
+/// Shared rate limiter handed to middleware through an [axum::Extension].
+pub type SharedRateLimiter = Arc<RateLimiter>;
+
+impl Default for RateLimiter {
+	fn default() -> Self {
+		Self::new(Duration::from_secs(AUTH_RATE_LIMIT_WINDOW_SECONDS))
+	}
+}
+
+impl RateLimiter {
+	pub fn new(window: Duration) -> Self {
+		Self {
+			window,
+			attempts: Mutex::new(HashMap::new()),
+		}
+	}
+
+	/// Returns how long until `key` may try again if it already has `max` failures this window.
+	pub fn retry_after(&self, key: &str, max: u32) -> Option<Duration> {
+		let attempts = self.attempts.lock().unwrap();
+		let entry = attempts.get(key)?;
+		let elapsed = entry.window_start.elapsed();
+		if entry.count >= max && elapsed < self.window {
+			Some(self.window - elapsed)
+		} else {
+			None
+		}
+	}
+
+	/// Counts a failure for `key`, starting a new window if the last one is over.
+	pub fn record_failure(&self, key: &str) {
+		let mut attempts = self.attempts.lock().unwrap();
+		let now = Instant::now();
+		let entry = attempts.entry(key.to_string()).or_insert(Attempts {
+			count: 0,
+			window_start: now,
+		});
+		if now.duration_since(entry.window_start) >= self.window {
+			entry.count = 0;
+			entry.window_start = now;
+		}
+		entry.count += 1;
+	}
+
+	/// Forgets all failures for `key`.
+	pub fn reset(&self, key: &str) {
+		self.attempts.lock().unwrap().remove(key);
+	}
+
+	/// Drops keys whose window has ended so the map doesn't grow forever.
+	pub fn prune(&self) {
+		let window = self.window;
+		self.attempts
+			.lock()
+			.unwrap()
+			.retain(|_, entry| entry.window_start.elapsed() < window);
+	}
+}
+
+/// Prunes `limiter` once per window for the lifetime of the server.
+pub fn spawn_pruner(limiter: SharedRateLimiter) {
+	tokio::spawn(async move {
+		let mut interval = tokio::time::interval(limiter.window);
+		loop {
+			interval.tick().await;
+			limiter.prune();
+		}
+	});
+}
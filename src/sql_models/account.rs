@@ -1,3 +1,6 @@
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
 /// Row model for the `accounts` table.
 /// - Represents a persisted user.
 pub struct AccountRow {
@@ -8,4 +11,29 @@ pub struct AccountRow {
 	pub email: String,
 	/// Argon2 hashed password
 	pub password: String,
+	/// Seconds until a lockout from too many consecutive failed logins ends, `None` if not locked
+	pub retry_after: Option<i64>,
+}
+
+/// JSON stored in `accounts.notification_preferences`.
+/// - Which notifications the user has opted into.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, ToSchema)]
+pub struct NotificationPreferences {
+	/// Reminders about upcoming trips
+	pub trip_reminders: bool,
+	/// Updates to itineraries shared with the user
+	pub share_notifications: bool,
+	/// Product news and offers
+	pub marketing: bool,
+}
+
+impl Default for NotificationPreferences {
+	/// Matches the column default in the migration
+	fn default() -> Self {
+		Self {
+			trip_reminders: true,
+			share_notifications: true,
+			marketing: false,
+		}
+	}
 }
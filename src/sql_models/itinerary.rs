@@ -18,4 +18,6 @@ pub struct ItineraryRow {
 	pub title: String,
 	/// Array of event IDs that are unassigned to any specific time slot
 	pub unassigned_event_ids: Option<Vec<i32>>,
+	/// Whether the user starred it
+	pub starred: bool,
 }
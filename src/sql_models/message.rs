@@ -1,4 +1,4 @@
-use chrono::NaiveDateTime;
+use chrono::{DateTime, NaiveDateTime, Utc};
 use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
 use utoipa::ToSchema;
@@ -10,6 +10,18 @@ pub struct ChatSessionRow {
 	pub id: i32,
 	/// Name of chat for user context
 	pub title: String,
+	/// When the latest message was sent, or `None` if the chat is empty
+	pub last_message_at: Option<DateTime<Utc>>,
+	/// Number of messages in the chat
+	pub message_count: i64,
+	/// Number of LLM messages sent since the user last read the chat
+	pub unread_count: i64,
+	/// Whether the user archived the chat, see `/api/chat/archive`
+	pub archived: bool,
+	/// Whether the user pinned the chat, see `/api/chat/pin`
+	pub pinned: bool,
+	/// When the latest message was sent, or when the chat was created if it's empty
+	pub last_activity: DateTime<Utc>,
 }
 
 /// Row model for `message` table
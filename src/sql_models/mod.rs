@@ -35,8 +35,27 @@ pub enum RiskTolerence {
 	RiskTaker,
 }
 
+/// Interest enum mapped to Postgres `interest`.
+/// Accounts store a list of these, used to rank events for the user.
+/// - Fields:
+///   - Enum variants representing kinds of activities
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Type, PartialEq, ToSchema)]
+#[sqlx(type_name = "interest")]
+pub enum Interest {
+	Museums,
+	Nightlife,
+	Outdoors,
+	Food,
+	Sports,
+	Shopping,
+	History,
+	Art,
+	Music,
+	Relaxation,
+}
+
 /// The time of day the event will take place in the itinerary
-#[derive(Debug, Serialize, Deserialize, Clone, Type, PartialEq)]
+#[derive(Debug, Serialize, Deserialize, Clone, Type, PartialEq, ToSchema)]
 #[sqlx(type_name = "time_of_day")]
 pub enum TimeOfDay {
 	Morning,
@@ -44,6 +63,28 @@ pub enum TimeOfDay {
 	Evening,
 }
 
+/// Kind of entry in the `auth_events` audit log
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Type, PartialEq, ToSchema)]
+#[sqlx(type_name = "auth_event_type")]
+pub enum AuthEventType {
+	LoginSuccess,
+	LoginFailure,
+	/// Too many consecutive login failures, a password reset is required
+	AccountLocked,
+	Logout,
+	PasswordChange,
+	EmailChange,
+}
+
+/// Whether the user liked a bot message, mapped to Postgres `feedback_rating`
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Type, PartialEq, ToSchema)]
+#[sqlx(type_name = "feedback_rating")]
+#[serde(rename_all = "lowercase")]
+pub enum FeedbackRating {
+	Up,
+	Down,
+}
+
 /// The status of the LLM pipeline
 #[derive(Debug, Serialize, Deserialize, Clone, Type, PartialEq, ToSchema)]
 #[sqlx(type_name = "llm_progress")]
@@ -69,6 +110,10 @@ pub enum LlmProgress {
 	RankingEvents,
 	// Final Response
 	FinalizingItinerary,
+	/// A clarification question was sent and the pipeline waits for the answer
+	AwaitingUser,
+	/// The pipeline errored, the next successful message resets it to `Ready`
+	Failed,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, Type, PartialEq, ToSchema)]
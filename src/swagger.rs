@@ -9,7 +9,11 @@ use utoipa::{
 use utoipa_axum::router::OpenApiRouter;
 use utoipa_swagger_ui::SwaggerUi;
 
-use crate::controllers::{account::AccountApiDoc, chat::ChatApiDoc, itinerary::ItineraryApiDoc};
+#[cfg(debug_assertions)]
+use crate::controllers::account::SwaggerAuthApiDoc;
+use crate::controllers::{
+	account::AccountApiDoc, chat::ChatApiDoc, events::EventsApiDoc, itinerary::ItineraryApiDoc,
+};
 
 #[derive(OpenApi)]
 #[openapi(
@@ -25,7 +29,8 @@ use crate::controllers::{account::AccountApiDoc, chat::ChatApiDoc, itinerary::It
     nest(
     	(path="/api/account", api=AccountApiDoc),
     	(path="/api/chat", api=ChatApiDoc),
-    	(path="/api/itinerary", api=ItineraryApiDoc)
+    	(path="/api/itinerary", api=ItineraryApiDoc),
+    	(path="/api/events", api=EventsApiDoc)
     ),
     servers(
     	(url="http://localhost:3001", description="Local host server for development"),
@@ -61,6 +66,9 @@ pub fn merge_swagger(router: OpenApiRouter) -> Router {
 	let mut file = File::create(docs_path.join("openapi.json")).unwrap();
 	file.write_all(doc.to_pretty_json().unwrap().as_bytes())
 		.unwrap();
+	// Served by the Swagger UI but left out of the written docs, since they're debug only
+	#[cfg(debug_assertions)]
+	let doc = doc.nest("/api/account", SwaggerAuthApiDoc::openapi());
 	let (router, api) = OpenApiRouter::with_openapi(doc)
 		.merge(router)
 		.split_for_parts();
@@ -0,0 +1,21 @@
+/*
+ * src/util/geo.rs
+ *
+ * File for geographic helpers
+ *
+ * Purpose:
+ *   Distances between coordinates, shared by itinerary stats, meal placement
+ *   and the event search radius filter.
+ */
+
+/// Mean radius of the Earth used for great-circle distances
+pub const EARTH_RADIUS_KM: f64 = 6371.0;
+
+/// Great-circle distance in km between two `(lat, lng)` points in degrees
+pub fn haversine_km((lat1, lng1): (f64, f64), (lat2, lng2): (f64, f64)) -> f64 {
+	let d_lat = (lat2 - lat1).to_radians();
+	let d_lng = (lng2 - lng1).to_radians();
+	let a = (d_lat / 2.0).sin().powi(2)
+		+ lat1.to_radians().cos() * lat2.to_radians().cos() * (d_lng / 2.0).sin().powi(2);
+	2.0 * EARTH_RADIUS_KM * a.sqrt().asin()
+}